@@ -0,0 +1,183 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use anyhow::anyhow;
+use serde_json::json;
+
+use loom_engine::http_client;
+
+mod soak;
+
+const USAGE: &str = "usage: loom-bench [--url URL] [--symbol SYMBOL] [--orders N] [--concurrency N] [--start-id N] [--cancel-ratio PERCENT] \
+[--soak SECS] [--order-url URL] [--admin-url URL] [--report-interval SECS] [--max-rss-growth-mb MB]";
+
+/// 合成订单的最低价格
+const PRICE_MIN: u64 = 95;
+/// 合成订单的价格档位数量
+const PRICE_LEVELS: u64 = 11;
+
+/// 压测参数
+#[derive(Debug, Clone)]
+struct BenchArgs {
+    /// 撮合接口地址
+    url: String,
+    symbol: String,
+    /// 发送订单总数
+    orders: u64,
+    /// 并发连接数
+    concurrency: u64,
+    /// 起始订单ID，避免与已有订单冲突
+    start_id: u64,
+    /// 撤单请求占比，百分比
+    cancel_ratio: u64,
+    /// 长时间压测的运行时间，设置后按时间而不是订单数量压测
+    soak: Option<Duration>,
+    /// 长时间压测使用的v2下单接口，同步返回成交用于检查不变式
+    order_url: String,
+    /// 长时间压测采样内存和订单簿的管理接口地址
+    admin_url: String,
+    /// 长时间压测的采样间隔
+    report_interval: Duration,
+    /// 长时间压测允许的内存增长，MB，超过时失败
+    max_rss_growth_mb: Option<u64>,
+}
+
+impl BenchArgs {
+    fn parse(mut args: impl Iterator<Item=String>) -> anyhow::Result<BenchArgs> {
+        let mut parsed = BenchArgs {
+            url: String::from("http://127.0.0.1:7001/api/v1/match"),
+            symbol: "LOOM-USDT-SPOT".into(),
+            orders: 10_000,
+            concurrency: 16,
+            start_id: 1,
+            cancel_ratio: 10,
+            soak: None,
+            order_url: String::from("http://127.0.0.1:7001/api/v2/order"),
+            admin_url: String::from("http://127.0.0.1:7001"),
+            report_interval: Duration::from_secs(10),
+            max_rss_growth_mb: None,
+        };
+        while let Some(flag) = args.next() {
+            let value = args.next().ok_or_else(|| anyhow!("missing value for {}\n{}", flag, USAGE))?;
+            match flag.as_str() {
+                "--url" => parsed.url = value,
+                "--symbol" => parsed.symbol = value,
+                "--orders" => parsed.orders = value.parse()?,
+                "--concurrency" => parsed.concurrency = value.parse::<u64>()?.max(1),
+                "--start-id" => parsed.start_id = value.parse()?,
+                "--cancel-ratio" => parsed.cancel_ratio = value.parse::<u64>()?.min(100),
+                "--soak" => parsed.soak = Some(Duration::from_secs(value.parse()?)),
+                "--order-url" => parsed.order_url = value,
+                "--admin-url" => parsed.admin_url = value,
+                "--report-interval" => parsed.report_interval = Duration::from_secs(value.parse::<u64>()?.max(1)),
+                "--max-rss-growth-mb" => parsed.max_rss_growth_mb = Some(value.parse()?),
+                _ => return Err(anyhow!("unknown flag {}\n{}", flag, USAGE)),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+/// 简单的伪随机数生成器
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+/// 生成一笔合成订单，价格围绕100上下波动以产生成交
+fn synthetic_order(args: &BenchArgs, id: u64, rng: &mut XorShift) -> serde_json::Value {
+    let r = rng.next();
+    let side = if r.is_multiple_of(2) { "BUY" } else { "SELL" };
+    if id > args.start_id && (r >> 8) % 100 < args.cancel_ratio {
+        // 撤销之前发送的订单
+        let target = args.start_id + (r >> 16) % (id - args.start_id);
+        return json!({
+            "id": target,
+            "symbol": args.symbol,
+            "side": side,
+            "qty": 1,
+            "ord_type": "LIMIT",
+            "action": "CANCEL",
+        });
+    }
+    let price = PRICE_MIN + (r >> 16) % PRICE_LEVELS;
+    json!({
+        "id": id,
+        "symbol": args.symbol,
+        "side": side,
+        "qty": 1 + (r >> 24) % 10,
+        "price": price.to_string(),
+        "ord_type": "LIMIT",
+        "action": "PLACE",
+    })
+}
+
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let idx = ((sorted.len() - 1) as f64 * p).round() as usize;
+    sorted[idx]
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args = Arc::new(BenchArgs::parse(std::env::args().skip(1))?);
+    if let Some(duration) = args.soak {
+        return soak::run(args, duration).await;
+    }
+    println!("target={} symbol={} orders={} concurrency={}", args.url, args.symbol, args.orders, args.concurrency);
+    let next_id = Arc::new(AtomicU64::new(args.start_id));
+    let errors = Arc::new(AtomicU64::new(0));
+    let end_id = args.start_id + args.orders;
+    let start = Instant::now();
+    let mut workers = Vec::new();
+    for worker in 0..args.concurrency {
+        let args = Arc::clone(&args);
+        let next_id = Arc::clone(&next_id);
+        let errors = Arc::clone(&errors);
+        workers.push(tokio::spawn(async move {
+            let mut rng = XorShift(0x9E3779B97F4A7C15 ^ (worker + 1));
+            let mut latencies = Vec::new();
+            loop {
+                let id = next_id.fetch_add(1, Ordering::Relaxed);
+                if id >= end_id {
+                    break;
+                }
+                let body = synthetic_order(&args, id, &mut rng).to_string();
+                let begin = Instant::now();
+                let result = http_client::post(&args.url, &[("Content-Type", "application/json")], body.as_bytes()).await;
+                latencies.push(begin.elapsed());
+                match result {
+                    Ok(resp) if resp.is_success() => {}
+                    _ => { errors.fetch_add(1, Ordering::Relaxed); }
+                }
+            }
+            latencies
+        }));
+    }
+    let mut latencies = Vec::with_capacity(args.orders as usize);
+    for worker in workers {
+        latencies.extend(worker.await?);
+    }
+    let elapsed = start.elapsed();
+    latencies.sort_unstable();
+    println!("requests={} errors={} elapsed={:?}", latencies.len(), errors.load(Ordering::Relaxed), elapsed);
+    println!("throughput={:.1} req/s", latencies.len() as f64 / elapsed.as_secs_f64());
+    println!(
+        "latency p50={:?} p90={:?} p99={:?} p999={:?} max={:?}",
+        percentile(&latencies, 0.50),
+        percentile(&latencies, 0.90),
+        percentile(&latencies, 0.99),
+        percentile(&latencies, 0.999),
+        latencies.last().copied().unwrap_or_default(),
+    );
+    Ok(())
+}
@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail};
+use bigdecimal::BigDecimal;
+use serde_json::{json, Value};
+
+use loom_core::market::MatchTrade;
+use loom_core::order::{OrderState, TradeSide};
+use loom_engine::http_client;
+
+use crate::{synthetic_order, BenchArgs, XorShift, PRICE_LEVELS, PRICE_MIN};
+
+/// 最多保留的违规明细
+const MAX_VIOLATIONS: usize = 20;
+
+/// 已发送且未结束的订单
+#[derive(Debug, Clone)]
+struct TrackedOrder {
+    side: TradeSide,
+    qty: u64,
+    px: BigDecimal,
+    /// 已观察到的累计成交
+    filled: u64,
+}
+
+/// 在线检查撮合结果的守恒不变式：累计成交不超过委托数量、剩余数量不超过未成交数量、
+/// 成交价在压测价格带内且不劣于taker限价。结束的订单不再跟踪，长时间运行时内存不增长
+#[derive(Debug)]
+pub struct InvariantChecker {
+    orders: HashMap<u64, TrackedOrder>,
+    min_px: BigDecimal,
+    max_px: BigDecimal,
+    trades: u64,
+    violations: u64,
+    details: Vec<String>,
+}
+
+impl InvariantChecker {
+    pub fn new(min_px: BigDecimal, max_px: BigDecimal) -> InvariantChecker {
+        InvariantChecker {
+            orders: HashMap::new(),
+            min_px,
+            max_px,
+            trades: 0,
+            violations: 0,
+            details: Vec::new(),
+        }
+    }
+
+    /// 发送下单请求前登记订单
+    pub fn place(&mut self, id: u64, side: TradeSide, qty: u64, px: BigDecimal) {
+        self.orders.insert(id, TrackedOrder { side, qty, px, filled: 0 });
+    }
+
+    fn violate(&mut self, detail: String) {
+        self.violations += 1;
+        if self.details.len() < MAX_VIOLATIONS {
+            self.details.push(detail);
+        }
+    }
+
+    /// 检查一次撮合的结果，并发请求的响应可能乱序，已结束或未登记的订单跳过
+    pub fn check(&mut self, trades: &[MatchTrade]) {
+        for trade in trades {
+            if trade.qty > 0 {
+                self.trades += 1;
+                if trade.px < self.min_px || trade.px > self.max_px {
+                    self.violate(format!("trade px out of band, trade={}, px={}", trade.id, &trade.px));
+                }
+                let worse = match (trade.taker_side, &trade.taker_px) {
+                    (Some(TradeSide::BUY), Some(limit)) => &trade.px > limit,
+                    (Some(TradeSide::SELL), Some(limit)) => &trade.px < limit,
+                    _ => false,
+                };
+                if worse {
+                    self.violate(format!("trade px worse than taker limit, trade={}, px={}", trade.id, &trade.px));
+                }
+                self.fill(trade.taker_oid, trade.qty, trade.taker_remain, trade.taker_state);
+                self.fill(trade.maker_oid, trade.qty, trade.maker_remain, trade.maker_state);
+            } else if is_final(trade.taker_state) {
+                self.orders.remove(&trade.taker_oid);
+            }
+        }
+    }
+
+    fn fill(&mut self, oid: u64, qty: u64, remain: u64, state: OrderState) {
+        let order = match self.orders.get_mut(&oid) {
+            Some(order) => order,
+            None => return,
+        };
+        order.filled += qty;
+        let (filled, total, side, px) = (order.filled, order.qty, order.side, order.px.clone());
+        if filled > total {
+            self.violate(format!("overfilled, oid={}, side={}, px={}, qty={}, filled={}", oid, side, px, total, filled));
+        } else if filled + remain > total {
+            self.violate(format!("remain exceeds unfilled qty, oid={}, qty={}, filled={}, remain={}", oid, total, filled, remain));
+        } else if state == OrderState::FULL_FILLED && filled != total {
+            self.violate(format!("filled state with unfilled qty, oid={}, qty={}, filled={}", oid, total, filled));
+        }
+        if is_final(state) {
+            self.orders.remove(&oid);
+        }
+    }
+
+    /// 跟踪中的订单数量
+    pub fn tracked(&self) -> usize {
+        self.orders.len()
+    }
+
+    pub fn violations(&self) -> u64 {
+        self.violations
+    }
+}
+
+fn is_final(state: OrderState) -> bool {
+    matches!(state, OrderState::FULL_FILLED | OrderState::CANCELED | OrderState::PARTIAL_CANCELLED)
+}
+
+/// 一次采样的服务端状态
+#[derive(Debug, Clone, Default)]
+struct Sample {
+    /// 进程常驻内存，字节
+    rss: Option<u64>,
+    /// 挂单数量
+    orders: Option<usize>,
+    /// 服务端检查的订单簿不变式违规
+    violation: Option<String>,
+}
+
+async fn get_json(url: &str) -> anyhow::Result<Value> {
+    let resp = http_client::request("GET", url, &[], &[]).await?;
+    if !resp.is_success() {
+        bail!("request failed, url={}, status={}", url, resp.status);
+    }
+    Ok(serde_json::from_slice(&resp.body)?)
+}
+
+async fn sample(args: &BenchArgs) -> Sample {
+    let admin = args.admin_url.trim_end_matches('/');
+    let rss = match http_client::request("GET", &format!("{}/metrics", admin), &[], &[]).await {
+        Ok(resp) => resp.body_text().lines()
+            .find_map(|line| line.strip_prefix("process_resident_memory_bytes "))
+            .and_then(|v| v.trim().parse::<f64>().ok())
+            .map(|v| v as u64),
+        Err(_) => None,
+    };
+    let orders = get_json(&format!("{}/admin/statehash?symbol={}", admin, args.symbol)).await.ok()
+        .and_then(|v| v["orders"].as_u64())
+        .map(|v| v as usize);
+    let violation = get_json(&format!("{}/admin/verify?symbol={}", admin, args.symbol)).await.ok()
+        .and_then(|v| v["violation"].as_str().map(String::from));
+    Sample { rss, orders, violation }
+}
+
+fn mb(bytes: u64) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
+
+/// 发送一笔订单并检查同步返回的撮合结果
+async fn send(args: &BenchArgs, id: u64, rng: &mut XorShift, checker: &Mutex<InvariantChecker>) -> anyhow::Result<bool> {
+    let mut order = synthetic_order(args, id, rng);
+    if order["action"] == "PLACE" {
+        let side = if order["side"] == "BUY" { TradeSide::BUY } else { TradeSide::SELL };
+        let qty = order["qty"].as_u64().unwrap_or(0);
+        let px: BigDecimal = order["price"].as_str().unwrap_or("0").parse()?;
+        checker.lock().unwrap().place(id, side, qty, px);
+    }
+    order["sync"] = json!(true);
+    let resp = http_client::post(&args.order_url, &[("Content-Type", "application/json")], order.to_string().as_bytes()).await?;
+    match resp.status {
+        200 => {
+            let body: Value = serde_json::from_slice(&resp.body)?;
+            let trades: Vec<MatchTrade> = match body.get("trades") {
+                Some(trades) if !trades.is_null() => serde_json::from_value(trades.clone())?,
+                _ => Vec::new(),
+            };
+            checker.lock().unwrap().check(&trades);
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// 长时间压测，按固定间隔采样服务端内存和挂单数量，结束时检查违规和内存增长
+pub async fn run(args: Arc<BenchArgs>, duration: Duration) -> anyhow::Result<()> {
+    println!("soak target={} symbol={} duration={:?} concurrency={}", args.order_url, args.symbol, duration, args.concurrency);
+    let min_px = BigDecimal::from(PRICE_MIN);
+    let max_px = BigDecimal::from(PRICE_MIN + PRICE_LEVELS - 1);
+    let checker = Arc::new(Mutex::new(InvariantChecker::new(min_px, max_px)));
+    let next_id = Arc::new(AtomicU64::new(args.start_id));
+    let requests = Arc::new(AtomicU64::new(0));
+    let errors = Arc::new(AtomicU64::new(0));
+    let deadline = Instant::now() + duration;
+    let mut workers = Vec::new();
+    for worker in 0..args.concurrency {
+        let (args, checker, next_id) = (Arc::clone(&args), Arc::clone(&checker), Arc::clone(&next_id));
+        let (requests, errors) = (Arc::clone(&requests), Arc::clone(&errors));
+        workers.push(tokio::spawn(async move {
+            let mut rng = XorShift(0x9E3779B97F4A7C15 ^ (worker + 1));
+            while Instant::now() < deadline {
+                let id = next_id.fetch_add(1, Ordering::Relaxed);
+                let ok = send(&args, id, &mut rng, &checker).await.unwrap_or(false);
+                requests.fetch_add(1, Ordering::Relaxed);
+                if !ok {
+                    errors.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }));
+    }
+
+    // 第一次采样作为内存基线，预热期间的增长不计入
+    let start = Instant::now();
+    let baseline = sample(&args).await;
+    let mut last;
+    let mut book_violation = None;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        tokio::time::sleep(args.report_interval.min(remaining)).await;
+        last = sample(&args).await;
+        if book_violation.is_none() {
+            book_violation = last.violation.clone();
+        }
+        let (trades, violations, tracked) = {
+            let checker = checker.lock().unwrap();
+            (checker.trades, checker.violations(), checker.tracked())
+        };
+        println!(
+            "elapsed={:?} requests={} errors={} trades={} violations={} tracked={} book_orders={} rss={}",
+            start.elapsed(), requests.load(Ordering::Relaxed), errors.load(Ordering::Relaxed), trades, violations, tracked,
+            last.orders.map(|v| v.to_string()).unwrap_or_else(|| String::from("-")),
+            last.rss.map(|v| format!("{:.1}MB", mb(v))).unwrap_or_else(|| String::from("-")),
+        );
+        if remaining.is_zero() {
+            break;
+        }
+    }
+    for worker in workers {
+        worker.await?;
+    }
+
+    let checker = checker.lock().unwrap();
+    let mut failures = Vec::new();
+    if checker.violations() > 0 {
+        checker.details.iter().for_each(|d| println!("violation: {}", d));
+        failures.push(format!("{} invariant violations", checker.violations()));
+    }
+    if let Some(violation) = book_violation {
+        failures.push(format!("book invariant violated: {}", violation));
+    }
+    if let (Some(before), Some(after)) = (baseline.rss, last.rss) {
+        let growth = after.saturating_sub(before);
+        let hours = start.elapsed().as_secs_f64() / 3600.0;
+        println!("rss baseline={:.1}MB final={:.1}MB growth={:.1}MB ({:.1}MB/h)", mb(before), mb(after), mb(growth), mb(growth) / hours.max(1e-9));
+        if args.max_rss_growth_mb.is_some_and(|max| growth > max * 1024 * 1024) {
+            failures.push(format!("rss grew {:.1}MB", mb(growth)));
+        }
+    }
+    if failures.is_empty() {
+        println!("soak passed");
+        Ok(())
+    } else {
+        Err(anyhow!("soak failed: {}", failures.join(", ")))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bigdecimal::BigDecimal;
+
+    use loom_core::market::MarketBook;
+    use loom_core::order::{OrderState, TradeSide};
+    use loom_core::testing::new_order;
+
+    use crate::soak::InvariantChecker;
+
+    #[test]
+    fn checker_test() {
+        let mut checker = InvariantChecker::new(BigDecimal::from(95), BigDecimal::from(105));
+        let mut book = MarketBook::new("LOOM-USDT-SPOT");
+        for order in [new_order(1, TradeSide::SELL, 3, "100"), new_order(2, TradeSide::BUY, 5, "101")] {
+            checker.place(order.id, order.side, order.qty, order.price.clone());
+            checker.check(&book.try_match(order));
+        }
+        assert_eq!(checker.violations(), 0);
+        // 卖单全部成交后不再跟踪
+        assert_eq!(checker.tracked(), 1);
+
+        // 重复推送的成交会导致超额成交
+        let mut trades = book.try_match(new_order(3, TradeSide::SELL, 2, "99")).to_vec();
+        checker.place(3, TradeSide::SELL, 2, BigDecimal::from(99));
+        trades[0].maker_state = OrderState::PARTIAL_FILLED;
+        trades[0].taker_state = OrderState::PARTIAL_FILLED;
+        checker.check(&trades);
+        checker.check(&trades);
+        assert!(checker.violations() > 0);
+
+        let mut checker = InvariantChecker::new(BigDecimal::from(95), BigDecimal::from(105));
+        trades[0].px = BigDecimal::from(90);
+        checker.check(&trades);
+        assert_eq!(checker.violations(), 2);
+    }
+}
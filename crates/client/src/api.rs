@@ -0,0 +1,369 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+use bigdecimal::BigDecimal;
+use log::warn;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use url::form_urlencoded;
+
+use loom_core::market::{CancelResult, FillSummary, MatchTrade};
+use loom_core::order::{OrderAction, OrderState, OrderTimeInForce, OrderType, TradeSide};
+use loom_core::snapshot::BookSnapshot;
+use loom_engine::http_client;
+use loom_engine::trader::MarketStatus;
+
+/// 默认API密钥请求头，与服务端默认配置一致
+pub const DEFAULT_API_KEY_HEADER: &str = "x-api-key";
+
+/// 幂等键请求头
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// 下单、撤单和减量请求，字段与/api/v2/order的参数一致
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct OrderRequest {
+    /// 客户端订单ID
+    pub id: u64,
+    pub symbol: String,
+    pub side: TradeSide,
+    /// 委托数量，减量时为减少的数量
+    pub qty: u64,
+    pub price: Option<BigDecimal>,
+    pub ord_type: OrderType,
+    pub tif: Option<OrderTimeInForce>,
+    pub action: OrderAction,
+    pub ts: Option<u128>,
+    /// 下单账户
+    pub account: Option<String>,
+    /// GTD订单的到期时间，毫秒
+    pub expire_ts: Option<u128>,
+    /// 最晚出队时间，毫秒，超过后以TOO_LATE拒绝
+    #[serde(default)]
+    pub valid_until_ts: Option<u128>,
+    /// 最长排队时间，毫秒
+    #[serde(default)]
+    pub max_age: Option<u64>,
+    /// 订单标签，原样带入成交结果
+    #[serde(default)]
+    pub tag: Option<String>,
+    /// 同步模式，等待撮合完成后返回成交
+    pub sync: Option<bool>,
+}
+
+impl OrderRequest {
+    fn new(id: u64, symbol: &str, side: TradeSide, qty: u64, ord_type: OrderType, action: OrderAction) -> OrderRequest {
+        OrderRequest {
+            id,
+            symbol: symbol.into(),
+            side,
+            qty,
+            price: None,
+            ord_type,
+            tif: None,
+            action,
+            ts: None,
+            account: None,
+            expire_ts: None,
+            valid_until_ts: None,
+            max_age: None,
+            tag: None,
+            sync: None,
+        }
+    }
+
+    /// 限价单，默认GTC
+    pub fn limit(id: u64, symbol: &str, side: TradeSide, qty: u64, price: BigDecimal) -> OrderRequest {
+        OrderRequest { price: Some(price), ..Self::new(id, symbol, side, qty, OrderType::LIMIT, OrderAction::PLACE) }
+    }
+
+    /// 市价单，默认IOC
+    pub fn market(id: u64, symbol: &str, side: TradeSide, qty: u64) -> OrderRequest {
+        Self::new(id, symbol, side, qty, OrderType::MARKET, OrderAction::PLACE)
+    }
+
+    /// 撤销订单
+    pub fn cancel(id: u64, symbol: &str, side: TradeSide) -> OrderRequest {
+        Self::new(id, symbol, side, 1, OrderType::LIMIT, OrderAction::CANCEL)
+    }
+
+    /// 减少挂单的委托数量并保留排队位置，减少的数量不小于剩余数量时撤销订单
+    pub fn reduce(id: u64, symbol: &str, side: TradeSide, qty: u64) -> OrderRequest {
+        Self::new(id, symbol, side, qty, OrderType::LIMIT, OrderAction::REDUCE)
+    }
+
+    pub fn with_tif(mut self, tif: OrderTimeInForce) -> OrderRequest {
+        self.tif = Some(tif);
+        self
+    }
+
+    /// GTD订单，到期后自动撤销
+    pub fn with_expire_ts(mut self, expire_ts: u128) -> OrderRequest {
+        self.tif = Some(OrderTimeInForce::GTD);
+        self.expire_ts = Some(expire_ts);
+        self
+    }
+
+    /// 排队超过max_age毫秒后出队的订单以TOO_LATE拒绝
+    pub fn with_max_age(mut self, max_age: u64) -> OrderRequest {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// 超过valid_until_ts后出队的订单以TOO_LATE拒绝
+    pub fn with_valid_until_ts(mut self, valid_until_ts: u128) -> OrderRequest {
+        self.valid_until_ts = Some(valid_until_ts);
+        self
+    }
+
+    pub fn with_account(mut self, account: &str) -> OrderRequest {
+        self.account = Some(account.to_string());
+        self
+    }
+
+    /// 附加订单标签，例如策略ID或交易台，最长64字节
+    pub fn with_tag(mut self, tag: &str) -> OrderRequest {
+        self.tag = Some(tag.to_string());
+        self
+    }
+
+    pub fn with_sync(mut self, sync: bool) -> OrderRequest {
+        self.sync = Some(sync);
+        self
+    }
+}
+
+/// 下单结果
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct OrderResponse {
+    /// 客户端订单ID
+    pub id: u64,
+    /// 引擎分配的序列号
+    pub seq: u64,
+    pub symbol: String,
+    pub action: OrderAction,
+    /// 请求受理时间
+    pub accepted_ts: u128,
+    /// 撮合后的订单状态，仅同步模式且撮合完成时返回
+    pub state: Option<OrderState>,
+    /// 撮合产生的成交，仅同步模式且撮合完成时返回
+    pub trades: Option<Vec<MatchTrade>>,
+    /// 只挂单改价后的委托价格，仅同步模式且发生改价时返回
+    #[serde(default)]
+    pub adjusted_px: Option<BigDecimal>,
+    /// 撤单结果，仅同步撤单且撮合完成时返回
+    #[serde(default)]
+    pub cancel: Option<CancelResult>,
+    /// 成交汇总，仅同步下单且撮合完成时返回
+    #[serde(default)]
+    pub fill: Option<FillSummary>,
+}
+
+/// 最新成交价和市场状态
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct PriceResult {
+    pub symbol: String,
+    /// 最新成交价，没有成交时为0
+    pub px: BigDecimal,
+    /// 最后处理请求的时间
+    pub ts: u128,
+    /// 快照版本号
+    pub version: u64,
+    pub status: MarketStatus,
+}
+
+/// 账户成交的一页
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct MyTradesPage {
+    pub trades: Vec<MatchTrade>,
+    /// 下一页的位置，没有更多成交时为空
+    pub next_cursor: Option<String>,
+}
+
+/// 服务端返回的错误，status为HTTP状态码，429和503可稍后重试
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ApiError {
+    pub status: u16,
+    pub message: String,
+}
+
+impl ApiError {
+    /// 队列已满、交易对暂停或恢复中等可重试的错误
+    pub fn is_retryable(&self) -> bool {
+        matches!(self.status, 429 | 503)
+    }
+}
+
+impl Display for ApiError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "loom api error, status={}, message={}", self.status, self.message)
+    }
+}
+
+impl Error for ApiError {}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    error: String,
+}
+
+/// 撮合服务HTTP API客户端，每次请求使用独立连接
+#[derive(Debug, Clone)]
+pub struct LoomClient {
+    base_url: String,
+    /// API密钥请求头和密钥，多租户部署时服务端按密钥确定租户
+    api_key: Option<(String, String)>,
+}
+
+impl LoomClient {
+    /// base_url为服务地址，例如http://127.0.0.1:8080
+    pub fn new(base_url: &str) -> LoomClient {
+        LoomClient { base_url: base_url.trim_end_matches('/').to_string(), api_key: None }
+    }
+
+    pub fn with_api_key(self, api_key: &str) -> LoomClient {
+        self.with_api_key_header(DEFAULT_API_KEY_HEADER, api_key)
+    }
+
+    pub fn with_api_key_header(mut self, header: &str, api_key: &str) -> LoomClient {
+        self.api_key = Some((header.to_string(), api_key.to_string()));
+        self
+    }
+
+    /// 提交下单、撤单或减量请求，idempotency_key为空时服务端按客户端订单ID去重
+    pub async fn submit(&self, request: &OrderRequest, idempotency_key: Option<&str>) -> anyhow::Result<OrderResponse> {
+        let body = serde_json::to_vec(request)?;
+        let mut headers = vec![("Content-Type", "application/json")];
+        if let Some(key) = idempotency_key {
+            headers.push((IDEMPOTENCY_KEY_HEADER, key));
+        }
+        self.send("POST", "/api/v2/order", &headers, &body).await
+    }
+
+    /// 下单，同步模式时返回撮合后的订单状态和成交
+    pub async fn place(&self, request: &OrderRequest) -> anyhow::Result<OrderResponse> {
+        self.submit(request, None).await
+    }
+
+    /// 同步撤单，撤单结果说明订单是否已撤销、已成交或不存在
+    pub async fn cancel(&self, symbol: &str, id: u64, side: TradeSide) -> anyhow::Result<OrderResponse> {
+        self.submit(&OrderRequest::cancel(id, symbol, side).with_sync(true), None).await
+    }
+
+    /// 同步减少挂单数量，引擎不支持增加数量或改价，需要撤单后重新下单
+    pub async fn amend(&self, symbol: &str, id: u64, side: TradeSide, reduce_qty: u64) -> anyhow::Result<OrderResponse> {
+        self.submit(&OrderRequest::reduce(id, symbol, side, reduce_qty).with_sync(true), None).await
+    }
+
+    /// 查询深度，limit为返回的档位数量
+    pub async fn depth(&self, symbol: &str, limit: Option<usize>) -> anyhow::Result<BookSnapshot> {
+        let mut path = format!("/api/v1/depth?symbol={}", encode(symbol));
+        if let Some(limit) = limit {
+            path.push_str(&format!("&limit={}", limit));
+        }
+        self.send("GET", &path, &[], &[]).await
+    }
+
+    /// 查询最新成交价和市场状态
+    pub async fn price(&self, symbol: &str) -> anyhow::Result<PriceResult> {
+        self.send("GET", &format!("/api/v1/price?symbol={}", encode(symbol)), &[], &[]).await
+    }
+
+    /// 分页查询账户在[from, to)内的成交，cursor为上一页返回的next_cursor，服务端需配置成交归档
+    pub async fn my_trades(&self, symbol: &str, account: &str, from: Option<u128>, to: Option<u128>, cursor: Option<&str>, limit: Option<usize>) -> anyhow::Result<MyTradesPage> {
+        let mut path = format!("/api/v1/myTrades?symbol={}&account={}", encode(symbol), encode(account));
+        if let Some(from) = from {
+            path.push_str(&format!("&from={}", from));
+        }
+        if let Some(to) = to {
+            path.push_str(&format!("&to={}", to));
+        }
+        if let Some(cursor) = cursor {
+            path.push_str(&format!("&cursor={}", encode(cursor)));
+        }
+        if let Some(limit) = limit {
+            path.push_str(&format!("&limit={}", limit));
+        }
+        self.send("GET", &path, &[], &[]).await
+    }
+
+    /// 按interval轮询深度，快照版本变化时发送，接收端关闭后停止轮询；请求失败时记录日志并继续轮询
+    pub fn watch_depth(&self, symbol: &str, limit: Option<usize>, interval: Duration) -> mpsc::Receiver<BookSnapshot> {
+        let (tx, rx) = mpsc::channel(16);
+        let (client, symbol) = (self.clone(), symbol.to_string());
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            let mut version = None;
+            while !tx.is_closed() {
+                ticker.tick().await;
+                match client.depth(&symbol, limit).await {
+                    Ok(snapshot) if Some(snapshot.version) != version => {
+                        version = Some(snapshot.version);
+                        if tx.send(snapshot).await.is_err() {
+                            break;
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => warn!("POLL DEPTH FAILED: symbol={}, err={}", &symbol, e),
+                }
+            }
+        });
+        rx
+    }
+
+    async fn send<T: DeserializeOwned>(&self, method: &str, path: &str, headers: &[(&str, &str)], body: &[u8]) -> anyhow::Result<T> {
+        let mut headers = headers.to_vec();
+        if let Some((header, key)) = &self.api_key {
+            headers.push((header.as_str(), key.as_str()));
+        }
+        let resp = http_client::request(method, &format!("{}{}", &self.base_url, path), &headers, body).await?;
+        if !resp.is_success() {
+            return Err(api_error(resp.status, &resp.body).into());
+        }
+        Ok(serde_json::from_slice(&resp.body)?)
+    }
+}
+
+fn encode(value: &str) -> String {
+    form_urlencoded::byte_serialize(value.as_bytes()).collect()
+}
+
+/// v2接口的错误响应为{"error": ...}，其他接口为纯文本
+fn api_error(status: u16, body: &[u8]) -> ApiError {
+    let message = serde_json::from_slice::<ErrorBody>(body)
+        .map(|b| b.error)
+        .unwrap_or_else(|_| String::from_utf8_lossy(body).to_string());
+    ApiError { status, message }
+}
+
+#[cfg(test)]
+mod test {
+    use bigdecimal::BigDecimal;
+    use serde_json::json;
+
+    use loom_core::order::{OrderAction, OrderTimeInForce, TradeSide};
+
+    use crate::api::{api_error, encode, OrderRequest};
+
+    #[test]
+    fn request_test() {
+        let order = OrderRequest::limit(1, "LOOM-USDT-SPOT", TradeSide::BUY, 2, BigDecimal::from(100))
+            .with_expire_ts(1000)
+            .with_account("alice");
+        let value = serde_json::to_value(&order).unwrap();
+        assert_eq!(value["tif"], json!("GTD"));
+        assert_eq!(value["ord_type"], json!("LIMIT"));
+        assert_eq!(serde_json::from_value::<OrderRequest>(value).unwrap(), order);
+        let reduce = OrderRequest::reduce(1, "LOOM-USDT-SPOT", TradeSide::BUY, 1);
+        assert_eq!((reduce.action, reduce.tif), (OrderAction::REDUCE, None));
+        assert_eq!(OrderRequest::cancel(1, "LOOM-USDT-SPOT", TradeSide::SELL).with_tif(OrderTimeInForce::IOC).tif, Some(OrderTimeInForce::IOC));
+        let quote = OrderRequest::limit(2, "LOOM-USDT-SPOT", TradeSide::SELL, 1, BigDecimal::from(101)).with_max_age(50);
+        assert_eq!(serde_json::to_value(&quote).unwrap()["max_age"], json!(50));
+
+        let err = api_error(404, br#"{"error":"order not found"}"#);
+        assert_eq!((err.status, err.message.as_str(), err.is_retryable()), (404, "order not found", false));
+        assert!(api_error(503, b"symbol paused").is_retryable());
+        assert_eq!(encode("acme LOOM&"), "acme+LOOM%26");
+    }
+}
@@ -0,0 +1,345 @@
+use std::future::Future;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use log::{info, warn};
+use redis::aio::MultiplexedConnection;
+use redis::Value;
+use tokio::sync::broadcast;
+
+use loom_core::market::MatchTrade;
+use loom_engine::cache::CACHE_PREFIX;
+use loom_engine::tenant;
+
+/// 默认每次读取的消息数量
+pub const DEFAULT_COUNT: usize = 100;
+
+/// 默认等待新消息的时长
+pub const DEFAULT_BLOCK: Duration = Duration::from_secs(5);
+
+/// 交易对成交流的键，与引擎写入的键一致，租户交易对写作tenant.symbol
+pub fn stream_key(symbol: &str) -> String {
+    match tenant::split(symbol) {
+        (Some(tenant), name) => format!("{}:{}:TRADES:{}", CACHE_PREFIX, tenant, name),
+        (None, name) => format!("{}:TRADES:{}", CACHE_PREFIX, name),
+    }
+}
+
+/// 成交流中的一条消息，对应引擎一次推送的成交
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TradeEntry {
+    /// 消息ID，确认时使用
+    pub id: String,
+    pub trades: Vec<MatchTrade>,
+}
+
+/// 一次读取的结果
+#[derive(Debug, Default)]
+struct Batch {
+    entries: Vec<TradeEntry>,
+    /// 已被裁剪的消息ID
+    deleted: Vec<String>,
+    /// 读到的最后一条消息ID，包括已被裁剪的消息
+    last: Option<String>,
+}
+
+/// 以消费组方式读取交易对成交流：先重新投递本消费者未确认的消息，再认领其他消费者超时未确认的消息，最后读取新消息
+pub struct TradeStream {
+    conn: MultiplexedConnection,
+    key: String,
+    group: String,
+    consumer: String,
+    /// 每次读取的消息数量
+    count: usize,
+    /// 等待新消息的时长
+    block: Duration,
+    /// 其他消费者的消息超过该时长未确认时由本消费者认领，未配置时不认领
+    claim_idle: Option<Duration>,
+    /// 创建消费组时的起始消息ID
+    start_id: String,
+    /// 读取本消费者未确认消息的游标，读完后为None
+    pending_cursor: Option<String>,
+    /// 认领超时消息的游标
+    claim_cursor: String,
+}
+
+impl TradeStream {
+    pub fn new(conn: MultiplexedConnection, symbol: &str, group: &str, consumer: &str) -> TradeStream {
+        TradeStream {
+            conn,
+            key: stream_key(symbol),
+            group: group.to_string(),
+            consumer: consumer.to_string(),
+            count: DEFAULT_COUNT,
+            block: DEFAULT_BLOCK,
+            claim_idle: None,
+            start_id: String::from("0"),
+            pending_cursor: Some(String::from("0")),
+            claim_cursor: String::from("0-0"),
+        }
+    }
+
+    /// 连接Redis并创建消费组，消费组已存在时沿用已有的进度
+    pub async fn connect(redis_uri: &str, symbol: &str, group: &str, consumer: &str) -> anyhow::Result<TradeStream> {
+        let conn = redis::Client::open(redis_uri)?.get_multiplexed_async_connection().await?;
+        let mut stream = Self::new(conn, symbol, group, consumer);
+        stream.ensure_group().await?;
+        Ok(stream)
+    }
+
+    pub fn with_count(mut self, count: usize) -> TradeStream {
+        self.count = count.max(1);
+        self
+    }
+
+    pub fn with_block(mut self, block: Duration) -> TradeStream {
+        self.block = block;
+        self
+    }
+
+    pub fn with_claim_idle(mut self, claim_idle: Duration) -> TradeStream {
+        self.claim_idle = Some(claim_idle);
+        self
+    }
+
+    /// 新建消费组时的起始消息ID，默认0即从保留的第一条开始，$为只读取之后的新消息
+    pub fn with_start_id(mut self, start_id: &str) -> TradeStream {
+        self.start_id = start_id.to_string();
+        self
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// 创建消费组，返回是否新建
+    pub async fn ensure_group(&mut self) -> anyhow::Result<bool> {
+        let created = redis::cmd("XGROUP").arg("CREATE").arg(&self.key).arg(&self.group).arg(&self.start_id).arg("MKSTREAM")
+            .query_async::<_, ()>(&mut self.conn)
+            .await;
+        match created {
+            Ok(_) => {
+                info!("CONSUMER GROUP CREATED: key={}, group={}, start={}", &self.key, &self.group, &self.start_id);
+                Ok(true)
+            }
+            Err(e) if e.code() == Some("BUSYGROUP") => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// 读取下一批消息，没有消息时等待block时长后返回空
+    pub async fn read(&mut self) -> anyhow::Result<Vec<TradeEntry>> {
+        if let Some(cursor) = self.pending_cursor.clone() {
+            let batch = self.read_group(&cursor, None).await?;
+            // 已被裁剪的消息无法重新投递，直接确认
+            self.ack_ids(&batch.deleted).await?;
+            self.pending_cursor = batch.last;
+            if self.pending_cursor.is_some() {
+                return Ok(batch.entries);
+            }
+        }
+        if let Some(idle) = self.claim_idle {
+            let entries = self.claim(idle).await?;
+            if !entries.is_empty() {
+                return Ok(entries);
+            }
+        }
+        Ok(self.read_group(">", Some(self.block)).await?.entries)
+    }
+
+    /// 确认已处理的消息
+    pub async fn ack(&mut self, entries: &[TradeEntry]) -> anyhow::Result<u64> {
+        let ids: Vec<String> = entries.iter().map(|e| e.id.clone()).collect();
+        self.ack_ids(&ids).await
+    }
+
+    /// 消费组中所有消费者未确认的消息数量
+    pub async fn pending(&mut self) -> anyhow::Result<u64> {
+        let summary = redis::cmd("XPENDING").arg(&self.key).arg(&self.group)
+            .query_async::<_, Value>(&mut self.conn)
+            .await?;
+        match summary {
+            Value::Bulk(items) => Ok(items.first().map(redis::from_redis_value).transpose()?.unwrap_or(0)),
+            _ => Ok(0),
+        }
+    }
+
+    /// 持续读取并处理消息，处理成功后确认，失败时不确认并返回错误，重启后重新投递；收到退出信号后返回
+    pub async fn run<F, Fut>(&mut self, mut ctx: broadcast::Receiver<bool>, mut handler: F) -> anyhow::Result<()>
+        where F: FnMut(TradeEntry) -> Fut,
+              Fut: Future<Output=anyhow::Result<()>> {
+        loop {
+            if let Ok(true) = ctx.try_recv() {
+                return Ok(());
+            }
+            for entry in self.read().await? {
+                let id = entry.id.clone();
+                handler(entry).await
+                    .map_err(|e| anyhow!("handle trades failed, key={}, id={}, err={}", &self.key, &id, e))?;
+                self.ack_ids(&[id]).await?;
+            }
+        }
+    }
+
+    async fn ack_ids(&mut self, ids: &[String]) -> anyhow::Result<u64> {
+        if ids.is_empty() {
+            return Ok(0);
+        }
+        let acked = redis::cmd("XACK").arg(&self.key).arg(&self.group).arg(ids)
+            .query_async::<_, u64>(&mut self.conn)
+            .await?;
+        Ok(acked)
+    }
+
+    async fn read_group(&mut self, id: &str, block: Option<Duration>) -> anyhow::Result<Batch> {
+        let mut cmd = redis::cmd("XREADGROUP");
+        cmd.arg("GROUP").arg(&self.group).arg(&self.consumer).arg("COUNT").arg(self.count);
+        if let Some(block) = block {
+            cmd.arg("BLOCK").arg(block.as_millis() as u64);
+        }
+        let streams = cmd.arg("STREAMS").arg(&self.key).arg(id)
+            .query_async::<_, Value>(&mut self.conn)
+            .await?;
+        // 返回结构为[[stream, entries]]，超时返回nil
+        match streams {
+            Value::Bulk(streams) => match streams.first() {
+                Some(Value::Bulk(stream)) if stream.len() == 2 => parse_entries(&stream[1]),
+                _ => Ok(Batch::default()),
+            },
+            _ => Ok(Batch::default()),
+        }
+    }
+
+    /// 认领其他消费者超过idle未确认的消息，一轮扫描结束后游标回到开头
+    async fn claim(&mut self, idle: Duration) -> anyhow::Result<Vec<TradeEntry>> {
+        let reply = redis::cmd("XAUTOCLAIM").arg(&self.key).arg(&self.group).arg(&self.consumer)
+            .arg(idle.as_millis() as u64).arg(&self.claim_cursor).arg("COUNT").arg(self.count)
+            .query_async::<_, Value>(&mut self.conn)
+            .await?;
+        // 返回结构为[cursor, entries, deleted]，Redis 7之前没有deleted
+        let Value::Bulk(reply) = reply else {
+            return Ok(Vec::new());
+        };
+        let cursor: String = reply.first().map(redis::from_redis_value).transpose()?.unwrap_or_else(|| String::from("0-0"));
+        self.claim_cursor = cursor;
+        let entries = match reply.get(1) {
+            Some(entries) => parse_entries(entries)?.entries,
+            None => Vec::new(),
+        };
+        if !entries.is_empty() {
+            warn!("PENDING TRADES CLAIMED: key={}, group={}, consumer={}, entries={}", &self.key, &self.group, &self.consumer, entries.len());
+        }
+        Ok(entries)
+    }
+}
+
+/// 解析消息，每条为[id, [field, value, ...]]，已被裁剪的消息为[id, nil]，单独返回其ID
+fn parse_entries(entries: &Value) -> anyhow::Result<Batch> {
+    let Value::Bulk(entries) = entries else {
+        return Ok(Batch::default());
+    };
+    let mut batch = Batch::default();
+    for entry in entries {
+        let Value::Bulk(parts) = entry else {
+            return Err(anyhow!("invalid stream entry, entry={:?}", entry));
+        };
+        let id: String = parts.first().map(redis::from_redis_value).transpose()?
+            .ok_or_else(|| anyhow!("stream entry without id"))?;
+        batch.last = Some(id.clone());
+        match parts.get(1) {
+            None | Some(Value::Nil) => batch.deleted.push(id),
+            Some(fields) => {
+                let fields: Vec<(String, String)> = redis::from_redis_value(fields)?;
+                let trades = fields.iter().find(|(f, _)| f == "trades")
+                    .ok_or_else(|| anyhow!("stream entry without trades, id={}", id))?;
+                batch.entries.push(TradeEntry { trades: serde_json::from_str(&trades.1)?, id });
+            }
+        }
+    }
+    Ok(batch)
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use bigdecimal::BigDecimal;
+    use redis::Value;
+
+    use loom_core::market::MatchTrade;
+    use loom_core::order::OrderState;
+
+    use crate::stream::{parse_entries, stream_key, TradeStream};
+
+    fn new_trade(id: u64) -> MatchTrade {
+        MatchTrade {
+            id,
+            symbol: "LOOM-USDT-SPOT".into(),
+            qty: 1,
+            px: BigDecimal::from(100),
+            taker_oid: 2,
+            maker_oid: 1,
+            taker_state: OrderState::FULL_FILLED,
+            maker_state: OrderState::FULL_FILLED,
+            ts: 0,
+            reduce_qty: 0,
+            reject: None,
+            taker_side: None,
+            taker_ord_type: None,
+            taker_px: None,
+            taker_remain: 0,
+            maker_remain: 0,
+            taker_account: None,
+            maker_account: None,
+            taker_tag: None,
+            maker_tag: None,
+        }
+    }
+
+    fn data(s: &str) -> Value {
+        Value::Data(s.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn parse_test() {
+        assert_eq!(stream_key("LOOM-USDT-SPOT"), "Loom:TRADES:LOOM-USDT-SPOT");
+        assert_eq!(stream_key("acme.LOOM-USDT-SPOT"), "Loom:acme:TRADES:LOOM-USDT-SPOT");
+        let trades = serde_json::to_string(&vec![new_trade(1), new_trade(2)]).unwrap();
+        let entries = Value::Bulk(vec![
+            Value::Bulk(vec![data("1-0"), Value::Bulk(vec![data("trades"), data(&trades)])]),
+            Value::Bulk(vec![data("2-0"), Value::Nil]),
+        ]);
+        let batch = parse_entries(&entries).unwrap();
+        assert_eq!(batch.entries.len(), 1);
+        assert_eq!((batch.entries[0].id.as_str(), batch.entries[0].trades[1].id), ("1-0", 2));
+        assert_eq!(batch.deleted, vec![String::from("2-0")]);
+        assert_eq!(batch.last.as_deref(), Some("2-0"));
+        let invalid = Value::Bulk(vec![Value::Bulk(vec![data("3-0"), Value::Bulk(vec![data("order"), data("{}")])])]);
+        assert!(parse_entries(&invalid).is_err());
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn stream_test() {
+        let client = redis::Client::open("redis://localhost:6379").unwrap();
+        let mut conn = client.get_multiplexed_async_connection().await.unwrap();
+        let key = stream_key("CLIENT-TEST");
+        redis::cmd("DEL").arg(&key).query_async::<_, ()>(&mut conn).await.unwrap();
+        let trades = serde_json::to_string(&vec![new_trade(1)]).unwrap();
+        redis::cmd("XADD").arg(&key).arg("*").arg("trades").arg(&trades).query_async::<_, String>(&mut conn).await.unwrap();
+
+        let mut first = TradeStream::connect("redis://localhost:6379", "CLIENT-TEST", "test", "a").await.unwrap()
+            .with_block(Duration::from_millis(10));
+        let entries = first.read().await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(first.pending().await.unwrap(), 1);
+        // 未确认的消息由其他消费者认领
+        let mut second = TradeStream::connect("redis://localhost:6379", "CLIENT-TEST", "test", "b").await.unwrap()
+            .with_block(Duration::from_millis(10))
+            .with_claim_idle(Duration::ZERO);
+        let claimed = second.read().await.unwrap();
+        assert_eq!(claimed, entries);
+        assert_eq!(second.ack(&claimed).await.unwrap(), 1);
+        assert_eq!(second.pending().await.unwrap(), 0);
+    }
+}
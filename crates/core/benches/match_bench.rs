@@ -0,0 +1,191 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use bigdecimal::BigDecimal;
+
+use loom_core::market::MarketBook;
+use loom_core::order::{Order, OrderTimeInForce, TradeSide};
+
+const SYMBOL: &str = "LOOM-USDT-SPOT";
+
+/// 统计分配次数的全局分配器，用于对比优化前后的分配压力，只在统计轮的测量区间内计数
+struct CountingAlloc;
+
+static COUNTING: AtomicBool = AtomicBool::new(false);
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if COUNTING.load(Ordering::Relaxed) {
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        }
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        if COUNTING.load(Ordering::Relaxed) {
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        }
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// 测量区间的耗时和分配次数，计时轮只计时，统计轮只计数
+struct Meter {
+    counting: bool,
+    start: Instant,
+    base: u64,
+    elapsed: Duration,
+    allocs: u64,
+}
+
+impl Meter {
+    fn new(counting: bool) -> Meter {
+        Meter { counting, start: Instant::now(), base: 0, elapsed: Duration::ZERO, allocs: 0 }
+    }
+
+    fn start(&mut self) {
+        if self.counting {
+            self.base = ALLOCATIONS.load(Ordering::Relaxed);
+            COUNTING.store(true, Ordering::Relaxed);
+        } else {
+            self.start = Instant::now();
+        }
+    }
+
+    fn stop(&mut self) {
+        if self.counting {
+            COUNTING.store(false, Ordering::Relaxed);
+            self.allocs += ALLOCATIONS.load(Ordering::Relaxed) - self.base;
+        } else {
+            self.elapsed += self.start.elapsed();
+        }
+    }
+}
+
+/// 先运行一轮计时，再单独运行一轮统计分配次数，计数不影响计时
+fn measure(name: &str, iters: u64, run: impl Fn(&mut Meter)) {
+    let mut timed = Meter::new(false);
+    run(&mut timed);
+    let mut counted = Meter::new(true);
+    run(&mut counted);
+    report(name, iters, timed.elapsed, counted.allocs);
+}
+
+/// 简单的伪随机数生成器，保证每次运行的订单流一致
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+fn new_order(id: u64, side: TradeSide, qty: u64, price: u64, tif: OrderTimeInForce) -> Order {
+    Order {
+        id,
+        symbol: SYMBOL.into(),
+        side,
+        qty,
+        price: BigDecimal::from(price),
+        tif,
+        ..Default::default()
+    }
+}
+
+/// 构造深度为depth的卖方订单簿，价格从10000开始每档一个订单
+fn seed_book(depth: u64) -> MarketBook {
+    let mut book = MarketBook::new(SYMBOL);
+    for i in 0..depth {
+        book.try_match(new_order(i + 1, TradeSide::SELL, 10, 10_000 + i, OrderTimeInForce::GTC));
+    }
+    book
+}
+
+fn report(name: &str, iters: u64, elapsed: Duration, allocs: u64) {
+    let per_op = elapsed.as_nanos() as f64 / iters as f64;
+    println!(
+        "{:<48} {:>10} iters {:>12.1} ns/op {:>12.0} ops/s {:>8.2} allocs/op",
+        name, iters, per_op, 1e9 / per_op, allocs as f64 / iters as f64,
+    );
+}
+
+/// 不可成交的被动挂单
+fn bench_passive(depth: u64, iters: u64) {
+    measure(&format!("passive_place/depth={}", depth), iters, |meter| {
+        let mut book = seed_book(depth);
+        let mut rng = XorShift(0x9E3779B97F4A7C15);
+        meter.start();
+        for i in 0..iters {
+            let price = 9_000 + rng.next() % 1_000;
+            black_box(book.try_match(new_order(depth + i + 1, TradeSide::BUY, 1, price, OrderTimeInForce::GTC)));
+        }
+        meter.stop();
+    });
+}
+
+/// 与卖一成交的主动单，每次成交后补充一笔卖单保持深度
+fn bench_aggressive(depth: u64, iters: u64) {
+    measure(&format!("aggressive_fill+replenish/depth={}", depth), iters, |meter| {
+        let mut book = seed_book(depth);
+        let mut next_id = depth + 1;
+        meter.start();
+        for _ in 0..iters {
+            black_box(book.try_match(new_order(next_id, TradeSide::BUY, 10, 10_000 + depth, OrderTimeInForce::IOC)));
+            next_id += 1;
+            black_box(book.try_match(new_order(next_id, TradeSide::SELL, 10, 10_000, OrderTimeInForce::GTC)));
+            next_id += 1;
+        }
+        meter.stop();
+    });
+}
+
+/// 一次扫过多个价格档位
+fn bench_sweep(depth: u64, levels: u64, iters: u64) {
+    measure(&format!("sweep/depth={},levels={}", depth, levels), iters, |meter| {
+        for i in 0..iters {
+            let mut book = seed_book(depth);
+            meter.start();
+            black_box(book.try_match(new_order(depth + i + 1, TradeSide::BUY, levels * 10, 10_000 + depth, OrderTimeInForce::IOC)));
+            meter.stop();
+        }
+    });
+}
+
+/// 随机的挂单、主动单混合订单流
+fn bench_mixed(depth: u64, iters: u64) {
+    measure(&format!("mixed_flow/depth={}", depth), iters, |meter| {
+        let mut book = seed_book(depth);
+        let mut rng = XorShift(0xD1B54A32D192ED03);
+        meter.start();
+        for i in 0..iters {
+            let r = rng.next();
+            let side = if r.is_multiple_of(2) { TradeSide::BUY } else { TradeSide::SELL };
+            let price = 9_950 + (r >> 8) % 100;
+            let tif = if (r >> 16).is_multiple_of(4) { OrderTimeInForce::IOC } else { OrderTimeInForce::GTC };
+            black_box(book.try_match(new_order(depth + i + 1, side, 1 + (r >> 24) % 20, price, tif)));
+        }
+        meter.stop();
+    });
+}
+
+fn main() {
+    for depth in [10, 1_000, 100_000] {
+        bench_passive(depth, 100_000);
+        bench_aggressive(depth, 100_000);
+        bench_mixed(depth, 100_000);
+    }
+    bench_sweep(1_000, 100, 1_000);
+}
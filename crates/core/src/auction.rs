@@ -0,0 +1,68 @@
+use bigdecimal::BigDecimal;
+use serde::{Deserialize, Serialize};
+
+use crate::book::OrderBook;
+use crate::order::TradeSide;
+use crate::price::Price;
+
+/// 集合竞价的参考撮合结果
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct AuctionResult {
+    /// 集合竞价价格
+    pub price: BigDecimal,
+    /// 按该价格可成交的数量
+    pub volume: u64,
+    /// 成交后有剩余的一方，买卖数量相等时为空
+    pub imbalance_side: Option<TradeSide>,
+    /// 剩余数量
+    pub imbalance_qty: u64,
+}
+
+/// 集合竞价价格的候选结果
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) struct Equilibrium {
+    pub price: Price,
+    pub volume: u64,
+    /// 买方数量减去卖方数量
+    pub imbalance: i128,
+}
+
+impl Equilibrium {
+    pub fn result(&self, decimals: u32) -> AuctionResult {
+        let imbalance_side = match self.imbalance {
+            i if i > 0 => Some(TradeSide::BUY),
+            i if i < 0 => Some(TradeSide::SELL),
+            _ => None,
+        };
+        AuctionResult {
+            price: self.price.to_decimal(decimals),
+            volume: self.volume,
+            imbalance_side,
+            imbalance_qty: self.imbalance.unsigned_abs() as u64,
+        }
+    }
+}
+
+/// 计算集合竞价价格：取成交量最大的价格，成交量相同时取剩余数量最少的价格，
+/// 仍相同时取最接近参考价的价格，最后取较低的价格，订单簿没有交叉时返回None
+pub(crate) fn equilibrium(buy: &OrderBook, sell: &OrderBook, reference: Price) -> Option<Equilibrium> {
+    let (bid, ask) = (buy.best_price()?, sell.best_price()?);
+    if bid < ask {
+        return None;
+    }
+    // 只有买一和卖一之间的档位价格可能成交
+    let mut candidates: Vec<Price> = buy.iter_levels().map(|l| l.price()).take_while(|p| *p >= ask)
+        .chain(sell.iter_levels().map(|l| l.price()).take_while(|p| *p <= bid))
+        .collect();
+    candidates.sort_unstable();
+    candidates.dedup();
+    let distance = |p: Price| if reference.raw() > 0 { (p.raw() - reference.raw()).unsigned_abs() } else { 0 };
+    candidates.into_iter()
+        .map(|price| {
+            let demand: u64 = buy.iter_levels().take_while(|l| l.price() >= price).map(|l| l.total_qty()).sum();
+            let supply: u64 = sell.iter_levels().take_while(|l| l.price() <= price).map(|l| l.total_qty()).sum();
+            Equilibrium { price, volume: demand.min(supply), imbalance: demand as i128 - supply as i128 }
+        })
+        .filter(|e| e.volume > 0)
+        .min_by_key(|e| (std::cmp::Reverse(e.volume), e.imbalance.unsigned_abs(), distance(e.price), e.price))
+}
@@ -1,8 +1,82 @@
-use std::{cell::RefCell, collections::BTreeMap, rc::Rc};
+use std::collections::{BTreeMap, HashMap};
 
-use crate::order::{Order, OrderKey, TradeSide};
+use bigdecimal::num_bigint::BigInt;
+use bigdecimal::BigDecimal;
+use slab::Slab;
 
-pub type OrderRef = Rc<RefCell<Order>>;
+use crate::order::{Order, OrderKey, OrderState, TradeSide};
+use crate::price::Price;
+use crate::snapshot::{SideStats, StateHasher};
+
+/// 挂单句柄，指向订单簿内存池中的订单
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug)]
+pub struct OrderHandle(usize);
+
+/// 内存池中的挂单，同档位的挂单通过前后句柄串成队列，删除时直接摘除
+#[derive(Debug)]
+struct RestingOrder {
+    order: Order,
+    price: Price,
+    /// 档位队列中的前一个挂单
+    prev: Option<OrderHandle>,
+    /// 档位队列中的后一个挂单
+    next: Option<OrderHandle>,
+}
+
+/// 价格档位，同价格的订单按到达顺序排队
+#[derive(Debug)]
+pub struct Level {
+    /// 档位价格
+    price: Price,
+    /// 队首挂单
+    head: Option<OrderHandle>,
+    /// 队尾挂单
+    tail: Option<OrderHandle>,
+    /// 档位内的挂单数量
+    len: usize,
+    /// 档位内订单剩余数量之和
+    total_qty: u64,
+}
+
+impl Level {
+    fn new(price: Price) -> Level {
+        Level {
+            price,
+            head: None,
+            tail: None,
+            len: 0,
+            total_qty: 0,
+        }
+    }
+
+    pub fn price(&self) -> Price {
+        self.price
+    }
+
+    pub fn total_qty(&self) -> u64 {
+        self.total_qty
+    }
+
+    pub fn size(&self) -> usize {
+        self.len
+    }
+}
+
+/// 按到达顺序遍历档位内的挂单句柄
+struct LevelIter<'a> {
+    orders: &'a Slab<RestingOrder>,
+    next: Option<OrderHandle>,
+}
+
+impl Iterator for LevelIter<'_> {
+    type Item = OrderHandle;
+
+    fn next(&mut self) -> Option<OrderHandle> {
+        let handle = self.next?;
+        self.next = self.orders.get(handle.0).and_then(|r| r.next);
+        Some(handle)
+    }
+}
 
 /// 订单薄结构体，结构体中保存了同交易对同方向的的所有订单
 #[derive(Debug)]
@@ -11,21 +85,36 @@ pub struct OrderBook {
     symbol: String,
     /// 交易方向
     side: TradeSide,
-    /// 订单列表
-    orders: BTreeMap<OrderKey, OrderRef>,
+    /// 价格精度
+    price_decimals: u32,
+    /// 价格档位
+    levels: BTreeMap<Price, Level>,
+    /// 挂单内存池
+    orders: Slab<RestingOrder>,
+    /// 订单ID索引
+    index: HashMap<u64, OrderHandle>,
+    /// 所有挂单剩余数量之和
+    total_qty: u64,
+    /// 所有挂单定点价格乘以剩余数量之和
+    notional: i128,
 }
 
 impl OrderBook {
     /// 构造一个订单簿，订单名称和订单方向
-    pub fn new(symbol: &str, side: TradeSide) -> OrderBook {
+    pub fn new(symbol: &str, side: TradeSide, price_decimals: u32) -> OrderBook {
         OrderBook {
             symbol: String::from(symbol),
             side,
-            orders: BTreeMap::default(),
+            price_decimals,
+            levels: BTreeMap::default(),
+            orders: Slab::new(),
+            index: HashMap::new(),
+            total_qty: 0,
+            notional: 0,
         }
     }
 
-    /// 添加订单
+    /// 添加订单，方向不符或订单ID已存在时返回错误
     pub fn add(&mut self, order: Order) -> anyhow::Result<&Self> {
         // 判断订单方向
         if order.side != self.side {
@@ -33,33 +122,443 @@ impl OrderBook {
             return Err(anyhow::Error::msg("order trade side mismatch"));
         }
         // 排序键
-        let order_key = OrderKey::new(&order);
-        if !self.exist_by_key(&order_key) {
-            // 插入订单
-            self.orders.insert(order_key, Rc::new(RefCell::new(order)));
+        let order_key = self.key(&order)?;
+        // 同一订单ID只能存在一个挂单
+        if self.exist_by_id(order_key.sequence_id) {
+            return Err(anyhow::anyhow!("duplicate order id, side={}, oid={}", self.side, order_key.sequence_id));
+        }
+        // 插入订单并接到档位队尾
+        let id = order.id;
+        let remain = order.remain();
+        let level = self.levels
+            .entry(order_key.price)
+            .or_insert_with(|| Level::new(order_key.price));
+        let handle = OrderHandle(self.orders.insert(RestingOrder { order, price: order_key.price, prev: level.tail, next: None }));
+        match level.tail {
+            Some(tail) => self.orders[tail.0].next = Some(handle),
+            None => level.head = Some(handle),
         }
+        level.tail = Some(handle);
+        level.len += 1;
+        level.total_qty += remain;
+        self.index.insert(id, handle);
+        self.total_qty += remain;
+        self.notional += order_key.price.raw() as i128 * remain as i128;
         Ok(self)
     }
 
     /// 删除订单
-    pub fn del(&mut self, order: &Order) -> Option<OrderRef> {
-        self.orders.remove(&OrderKey::new(&order))
+    pub fn del(&mut self, order: &Order) -> Option<Order> {
+        let order_key = self.key(order).ok()?;
+        self.del_by_key(&order_key)
+    }
+
+    pub fn del_by_key(&mut self, order_key: &OrderKey) -> Option<Order> {
+        if !self.exist_by_key(order_key) {
+            return None;
+        }
+        self.del_by_id(order_key.sequence_id)
+    }
+
+    /// 按订单ID删除订单
+    pub fn del_by_id(&mut self, id: u64) -> Option<Order> {
+        let handle = self.index.remove(&id)?;
+        let resting = self.orders.remove(handle.0);
+        if let Some(level) = self.levels.get_mut(&resting.price) {
+            // 前后挂单直接相连，不需要在档位中查找
+            match resting.prev {
+                Some(prev) => self.orders[prev.0].next = resting.next,
+                None => level.head = resting.next,
+            }
+            match resting.next {
+                Some(next) => self.orders[next.0].prev = resting.prev,
+                None => level.tail = resting.prev,
+            }
+            level.len -= 1;
+            level.total_qty = level.total_qty.saturating_sub(resting.order.remain());
+            if level.len == 0 {
+                self.levels.remove(&resting.price);
+            }
+        }
+        self.unrest(resting.price, resting.order.remain());
+        Some(resting.order)
+    }
+
+    /// 取订单簿头，返回排序键和订单句柄
+    pub fn head(&self) -> Option<(OrderKey, OrderHandle)> {
+        let level = self.best_level()?;
+        let handle = level.head?;
+        let key = OrderKey {
+            sequence_id: self.orders[handle.0].order.id,
+            price: level.price,
+            side: self.side,
+        };
+        Some((key, handle))
+    }
+
+    pub fn get(&self, handle: OrderHandle) -> Option<&Order> {
+        self.orders.get(handle.0).map(|r| &r.order)
+    }
+
+    pub fn get_by_id(&self, id: u64) -> Option<&Order> {
+        self.index.get(&id).and_then(|h| self.get(*h))
+    }
+
+    /// 按订单ID查找挂单的排序键
+    pub fn key_by_id(&self, id: u64) -> Option<OrderKey> {
+        let resting = self.orders.get(self.index.get(&id)?.0)?;
+        Some(OrderKey {
+            sequence_id: id,
+            price: resting.price,
+            side: self.side,
+        })
+    }
+
+    /// 填充挂单的撮合结果并更新档位数量，订单剩余为0时从订单簿中删除，返回剩余数量
+    pub fn fill(&mut self, handle: OrderHandle, filled_qty: u64, state: OrderState) -> Option<u64> {
+        let resting = self.orders.get_mut(handle.0)?;
+        resting.order.fill(filled_qty, state);
+        let (id, price, remain) = (resting.order.id, resting.price, resting.order.remain());
+        if let Some(level) = self.levels.get_mut(&price) {
+            level.total_qty = level.total_qty.saturating_sub(filled_qty);
+        }
+        self.unrest(price, filled_qty);
+        if remain == 0 {
+            self.del_by_id(id);
+        }
+        Some(remain)
+    }
+
+    /// 减少挂单的委托数量并保留排队位置，减少的数量必须小于剩余数量，返回剩余数量
+    pub fn reduce(&mut self, id: u64, qty: u64) -> Option<u64> {
+        let handle = *self.index.get(&id)?;
+        let resting = self.orders.get_mut(handle.0)?;
+        if qty >= resting.order.remain() {
+            return None;
+        }
+        resting.order.qty -= qty;
+        let (price, remain) = (resting.price, resting.order.remain());
+        if let Some(level) = self.levels.get_mut(&price) {
+            level.total_qty = level.total_qty.saturating_sub(qty);
+        }
+        self.unrest(price, qty);
+        Some(remain)
+    }
+
+    /// 价格为price的挂单减少qty剩余数量
+    fn unrest(&mut self, price: Price, qty: u64) {
+        self.total_qty = self.total_qty.saturating_sub(qty);
+        self.notional -= price.raw() as i128 * qty as i128;
+    }
+
+    /// 最优价格档位，买方为最高价，卖方为最低价
+    pub fn best_level(&self) -> Option<&Level> {
+        match self.side {
+            TradeSide::BUY => self.levels.values().next_back(),
+            TradeSide::SELL => self.levels.values().next(),
+        }
+    }
+
+    /// 按撮合优先级遍历价格档位，买方从高到低，卖方从低到高
+    pub(crate) fn iter_levels(&self) -> Box<dyn Iterator<Item=&Level> + '_> {
+        match self.side {
+            TradeSide::BUY => Box::new(self.levels.values().rev()),
+            TradeSide::SELL => Box::new(self.levels.values()),
+        }
     }
 
-    pub fn del_by_key(&mut self, order_key: &OrderKey) -> Option<OrderRef> {
-        self.orders.remove(order_key)
+    /// 按撮合优先级取前n个价格档位
+    pub fn levels(&self, n: usize) -> impl Iterator<Item=&Level> + '_ {
+        self.iter_levels().take(n)
     }
 
-    /// 取订单簿头
-    pub fn head(&mut self) -> Option<OrderRef> {
-        return self.orders.first_key_value().map(|(_, v)| Rc::clone(v));
+    /// 按到达顺序遍历档位内的挂单句柄
+    fn level_orders(&self, level: &Level) -> LevelIter<'_> {
+        LevelIter { orders: &self.orders, next: level.head }
+    }
+
+    /// 按撮合优先级遍历挂单
+    pub fn iter(&self) -> impl Iterator<Item=&Order> + '_ {
+        self.iter_levels()
+            .flat_map(|level| self.level_orders(level))
+            .map(|handle| &self.orders[handle.0].order)
+    }
+
+    /// 按撮合优先级写入挂单的订单ID、价格、数量和成交数量
+    pub(crate) fn hash_into(&self, hasher: &mut StateHasher) {
+        for level in self.iter_levels() {
+            for handle in self.level_orders(level) {
+                let resting = &self.orders[handle.0];
+                hasher.write_u64(resting.order.id);
+                hasher.write_u64(resting.price.raw() as u64);
+                hasher.write_u64(resting.order.qty);
+                hasher.write_u64(resting.order.acc_fill_qty);
+            }
+        }
+    }
+
+    /// 检查订单簿不变式：档位非空且数量与挂单一致，挂单剩余数量为正，订单ID索引与内存池一致
+    pub fn verify(&self) -> anyhow::Result<()> {
+        let mut indexed = 0;
+        for (price, level) in self.levels.iter() {
+            if level.price != *price || level.len == 0 {
+                return Err(anyhow::anyhow!("invalid level, side={}, price={:?}, orders={}", self.side, price, level.len));
+            }
+            let (mut total_qty, mut count, mut prev) = (0, 0, None);
+            for handle in self.level_orders(level) {
+                let resting = self.orders.get(handle.0)
+                    .ok_or_else(|| anyhow::anyhow!("dangling handle in level, side={}, price={:?}", self.side, price))?;
+                let order = &resting.order;
+                if resting.price != *price || order.side != self.side {
+                    return Err(anyhow::anyhow!("order in wrong level, oid={}, side={}, price={:?}", order.id, order.side, price));
+                }
+                if order.acc_fill_qty >= order.qty {
+                    return Err(anyhow::anyhow!("resting order has no remain, oid={}, qty={}, acc_fill_qty={}", order.id, order.qty, order.acc_fill_qty));
+                }
+                if self.index.get(&order.id) != Some(&handle) {
+                    return Err(anyhow::anyhow!("order id index mismatch, oid={}", order.id));
+                }
+                if resting.prev != prev {
+                    return Err(anyhow::anyhow!("broken level link, oid={}, side={}, price={:?}", order.id, self.side, price));
+                }
+                prev = Some(handle);
+                total_qty += order.remain();
+                count += 1;
+                indexed += 1;
+            }
+            if count != level.len || prev != level.tail {
+                return Err(anyhow::anyhow!("level size mismatch, side={}, price={:?}, len={}, orders={}", self.side, price, level.len, count));
+            }
+            if total_qty != level.total_qty {
+                return Err(anyhow::anyhow!("level qty mismatch, side={}, price={:?}, total_qty={}, remain={}", self.side, price, level.total_qty, total_qty));
+            }
+        }
+        if indexed != self.orders.len() || indexed != self.index.len() {
+            return Err(anyhow::anyhow!("order count mismatch, levels={}, pool={}, index={}", indexed, self.orders.len(), self.index.len()));
+        }
+        let notional: i128 = self.orders.iter().map(|(_, r)| r.price.raw() as i128 * r.order.remain() as i128).sum();
+        let total_qty: u64 = self.levels.values().map(|level| level.total_qty).sum();
+        if total_qty != self.total_qty || notional != self.notional {
+            return Err(anyhow::anyhow!("book aggregate mismatch, side={}, total_qty={}, remain={}, notional={}, expected={}", self.side, self.total_qty, total_qty, self.notional, notional));
+        }
+        Ok(())
+    }
+
+    /// 订单的排序键
+    pub fn key(&self, order: &Order) -> anyhow::Result<OrderKey> {
+        Ok(OrderKey::new(order, self.price(order)?))
+    }
+
+    /// 将订单价格转换为定点价格
+    pub fn price(&self, order: &Order) -> anyhow::Result<Price> {
+        Price::from_decimal(&order.price, self.price_decimals)
+    }
+
+    pub fn symbol(&self) -> &str {
+        &self.symbol
+    }
+
+    pub fn price_decimals(&self) -> u32 {
+        self.price_decimals
     }
 
     pub fn size(&self) -> usize {
         self.orders.len()
     }
 
+    /// 价格档位数量
+    pub fn level_count(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// 所有挂单剩余数量之和
+    pub fn total_qty(&self) -> u64 {
+        self.total_qty
+    }
+
+    /// 所有挂单价格乘以剩余数量之和
+    pub fn notional(&self) -> BigDecimal {
+        BigDecimal::new(BigInt::from(self.notional), self.price_decimals as i64)
+    }
+
+    /// 挂单汇总，随挂单增删和成交增量维护
+    pub fn stats(&self) -> SideStats {
+        SideStats {
+            orders: self.size(),
+            qty: self.total_qty,
+            notional: self.notional(),
+        }
+    }
+
+    /// 最优价格
+    pub fn best_price(&self) -> Option<Price> {
+        self.best_level().map(|level| level.price)
+    }
+
+    /// 指定价格档位的挂单剩余数量，档位不存在时为0
+    pub fn volume_at(&self, price: Price) -> u64 {
+        self.levels.get(&price).map(|level| level.total_qty).unwrap_or(0)
+    }
+
     pub fn exist_by_key(&self, key: &OrderKey) -> bool {
-        self.orders.contains_key(key)
+        self.index
+            .get(&key.sequence_id)
+            .map(|h| self.orders[h.0].price == key.price)
+            .unwrap_or(false)
+    }
+
+    pub fn exist_by_id(&self, id: u64) -> bool {
+        self.index.contains_key(&id)
+    }
+
+    /// 挂单在其价格档位中的排队位置，返回排在前面的订单数量和剩余数量之和
+    pub fn queue_ahead(&self, id: u64) -> Option<(usize, u64)> {
+        let handle = *self.index.get(&id)?;
+        let level = self.levels.get(&self.orders.get(handle.0)?.price)?;
+        let ahead: Vec<OrderHandle> = self.level_orders(level).take_while(|h| *h != handle).collect();
+        let qty = ahead.iter().map(|h| self.orders[h.0].order.remain()).sum();
+        Some((ahead.len(), qty))
+    }
+}
+
+#[cfg(test)]
+mod book_test {
+    use bigdecimal::BigDecimal;
+
+    use crate::book::OrderBook;
+    use crate::order::{OrderState, TradeSide};
+    use crate::price::Price;
+    use crate::snapshot::SideStats;
+    use crate::testing::new_order;
+
+    #[test]
+    fn level_aggregate_test() {
+        let mut book = OrderBook::new("LOOM-USDT-SPOT", TradeSide::BUY, 0);
+        book.add(new_order(1, TradeSide::BUY, 2, "100")).unwrap();
+        book.add(new_order(2, TradeSide::BUY, 3, "100")).unwrap();
+        book.add(new_order(3, TradeSide::BUY, 5, "99")).unwrap();
+        assert_eq!(book.size(), 3);
+        assert_eq!(book.level_count(), 2);
+        let best = book.best_level().unwrap();
+        assert_eq!(best.price(), Price(100));
+        assert_eq!(best.total_qty(), 5);
+        // 同价格先到先得
+        let (key, handle) = book.head().unwrap();
+        assert_eq!(book.get(handle).unwrap().id, 1);
+        book.del_by_key(&key);
+        assert_eq!(book.best_level().unwrap().total_qty(), 3);
+    }
+
+    #[test]
+    fn fill_test() {
+        let mut book = OrderBook::new("LOOM-USDT-SPOT", TradeSide::BUY, 0);
+        book.add(new_order(1, TradeSide::BUY, 2, "100")).unwrap();
+        let (_, handle) = book.head().unwrap();
+        book.fill(handle, 1, OrderState::PARTIAL_FILLED);
+        assert_eq!(book.best_level().unwrap().total_qty(), 1);
+        book.fill(handle, 1, OrderState::FULL_FILLED);
+        assert_eq!(book.size(), 0);
+        assert_eq!(book.level_count(), 0);
+    }
+
+    #[test]
+    fn del_by_id_test() {
+        let mut book = OrderBook::new("LOOM-USDT-SPOT", TradeSide::BUY, 0);
+        book.add(new_order(1, TradeSide::BUY, 2, "100")).unwrap();
+        book.add(new_order(2, TradeSide::BUY, 3, "100")).unwrap();
+        assert_eq!(book.del_by_id(2).unwrap().id, 2);
+        assert!(book.del_by_id(2).is_none());
+        assert!(book.exist_by_id(1));
+        assert_eq!(book.best_level().unwrap().total_qty(), 2);
+        let key = book.key_by_id(1).unwrap();
+        assert_eq!(key.price, Price(100));
+        assert!(book.exist_by_key(&key));
+        assert!(book.key_by_id(2).is_none());
+        // 从档位中间、队首和队尾删除后队列顺序不变
+        for id in 3..=6 {
+            book.add(new_order(id, TradeSide::BUY, 1, "100")).unwrap();
+        }
+        book.del_by_id(4).unwrap();
+        book.del_by_id(1).unwrap();
+        book.del_by_id(6).unwrap();
+        book.verify().unwrap();
+        assert_eq!(book.iter().map(|o| o.id).collect::<Vec<_>>(), vec![3, 5]);
+        assert_eq!(book.queue_ahead(5), Some((1, 1)));
+        assert_eq!(book.best_level().unwrap().size(), 2);
+    }
+
+    #[test]
+    fn duplicate_add_test() {
+        let mut book = OrderBook::new("LOOM-USDT-SPOT", TradeSide::BUY, 0);
+        book.add(new_order(1, TradeSide::BUY, 2, "100")).unwrap();
+        // 重复的订单ID返回错误，不覆盖已有挂单
+        assert!(book.add(new_order(1, TradeSide::BUY, 3, "99")).is_err());
+        assert_eq!(book.get_by_id(1).unwrap().qty, 2);
+        assert_eq!(book.size(), 1);
+        book.verify().unwrap();
+    }
+
+    #[test]
+    fn reduce_test() {
+        let mut book = OrderBook::new("LOOM-USDT-SPOT", TradeSide::BUY, 0);
+        book.add(new_order(1, TradeSide::BUY, 5, "100")).unwrap();
+        book.add(new_order(2, TradeSide::BUY, 3, "100")).unwrap();
+        assert_eq!(book.reduce(1, 2), Some(3));
+        assert_eq!(book.get_by_id(1).unwrap().qty, 3);
+        assert_eq!(book.volume_at(Price(100)), 6);
+        // 减少后仍在档位头部
+        assert_eq!(book.get(book.head().unwrap().1).unwrap().id, 1);
+        assert_eq!(book.reduce(1, 3), None);
+        assert_eq!(book.reduce(3, 1), None);
+    }
+
+    #[test]
+    fn verify_test() {
+        let mut book = OrderBook::new("LOOM-USDT-SPOT", TradeSide::BUY, 0);
+        book.add(new_order(1, TradeSide::BUY, 2, "100")).unwrap();
+        book.add(new_order(2, TradeSide::BUY, 3, "99")).unwrap();
+        let (_, handle) = book.head().unwrap();
+        book.fill(handle, 1, OrderState::PARTIAL_FILLED);
+        book.verify().unwrap();
+        // 绕过档位修改挂单数量
+        book.orders[handle.0].order.qty += 1;
+        assert!(book.verify().is_err());
+    }
+
+    #[test]
+    fn depth_test() {
+        let mut book = OrderBook::new("LOOM-USDT-SPOT", TradeSide::BUY, 0);
+        assert_eq!(book.best_price(), None);
+        book.add(new_order(1, TradeSide::BUY, 2, "99")).unwrap();
+        book.add(new_order(2, TradeSide::BUY, 3, "101")).unwrap();
+        book.add(new_order(3, TradeSide::BUY, 4, "100")).unwrap();
+        book.add(new_order(4, TradeSide::BUY, 1, "101")).unwrap();
+        assert_eq!(book.best_price(), Some(Price(101)));
+        assert_eq!(book.total_qty(), 10);
+        assert_eq!(book.volume_at(Price(101)), 4);
+        assert_eq!(book.volume_at(Price(98)), 0);
+        let levels: Vec<(Price, u64)> = book.levels(2).map(|l| (l.price(), l.total_qty())).collect();
+        assert_eq!(levels, vec![(Price(101), 4), (Price(100), 4)]);
+        let ids: Vec<u64> = book.iter().map(|o| o.id).collect();
+        assert_eq!(ids, vec![2, 4, 3, 1]);
+    }
+
+    #[test]
+    fn stats_test() {
+        let mut book = OrderBook::new("LOOM-USDT-SPOT", TradeSide::BUY, 2);
+        book.add(new_order(1, TradeSide::BUY, 5, "100")).unwrap();
+        book.add(new_order(2, TradeSide::BUY, 3, "99")).unwrap();
+        book.add(new_order(3, TradeSide::BUY, 2, "100")).unwrap();
+        let (_, handle) = book.head().unwrap();
+        book.fill(handle, 4, OrderState::PARTIAL_FILLED);
+        book.reduce(3, 1);
+        book.del_by_id(2);
+        let stats = book.stats();
+        assert_eq!((stats.orders, stats.qty), (2, 2));
+        assert_eq!(stats.notional, BigDecimal::from(200));
+        book.verify().unwrap();
+        book.fill(handle, 1, OrderState::FULL_FILLED);
+        book.del_by_id(3);
+        assert_eq!(book.stats(), SideStats::default());
     }
 }
@@ -0,0 +1,71 @@
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::utils;
+
+/// 时钟，撮合中的时间戳都从时钟获取，测试和回放时替换为可控时钟保证结果确定
+pub trait Clock: Debug + Send + Sync {
+    /// 当前时间戳，mills
+    fn now_ts(&self) -> u128;
+
+    /// 单调递增的纳秒时间戳，用于同一毫秒内的先后排序
+    fn now_ns(&self) -> u64 {
+        (self.now_ts() * 1_000_000) as u64
+    }
+}
+
+/// 系统时钟
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_ts(&self) -> u128 {
+        utils::now_ts()
+    }
+
+    fn now_ns(&self) -> u64 {
+        utils::monotonic_ns()
+    }
+}
+
+/// 手动时钟，时间只在调用`set`或`advance`时变化
+#[derive(Debug, Default)]
+pub struct ManualClock {
+    ts: AtomicU64,
+}
+
+impl ManualClock {
+    pub fn new(ts: u64) -> ManualClock {
+        ManualClock { ts: AtomicU64::new(ts) }
+    }
+
+    pub fn set(&self, ts: u64) {
+        self.ts.store(ts, Ordering::Release);
+    }
+
+    /// 时间前进mills毫秒
+    pub fn advance(&self, mills: u64) {
+        self.ts.fetch_add(mills, Ordering::AcqRel);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now_ts(&self) -> u128 {
+        self.ts.load(Ordering::Acquire) as u128
+    }
+}
+
+#[cfg(test)]
+mod clock_test {
+    use crate::clock::{Clock, ManualClock};
+
+    #[test]
+    fn manual_clock_test() {
+        let clock = ManualClock::new(1000);
+        assert_eq!(clock.now_ts(), 1000);
+        clock.advance(5);
+        assert_eq!(clock.now_ts(), 1005);
+        clock.set(0);
+        assert_eq!(clock.now_ts(), 0);
+    }
+}
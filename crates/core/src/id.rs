@@ -0,0 +1,154 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+/// 分布式ID的时间起点，2024-01-01T00:00:00Z，毫秒
+pub const SNOWFLAKE_EPOCH_MS: u128 = 1_704_067_200_000;
+
+/// 节点号位数
+const NODE_BITS: u32 = 10;
+
+/// 毫秒内序号位数
+const SEQ_BITS: u32 = 12;
+
+/// 最大节点号
+pub const MAX_NODE: u16 = (1 << NODE_BITS) - 1;
+
+/// 成交ID的分配方式
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub enum IdStrategy {
+    /// 每个交易对从高水位开始连续递增
+    #[default]
+    Sequential,
+    /// 时间+节点+序号，节点号不同的引擎分配的ID不会重复，同一引擎内所有交易对共享
+    Snowflake { node: u16 },
+}
+
+/// Snowflake风格的ID生成器，高41位为起点之后的毫秒数，中间10位为节点号，低12位为毫秒内序号
+///
+/// 同一毫秒的序号用完或时钟回拨时借用下一毫秒，ID始终单调递增
+#[derive(Debug)]
+pub struct SnowflakeIds {
+    node: u64,
+    /// 最后分配的ID
+    last: AtomicU64,
+}
+
+impl SnowflakeIds {
+    pub fn new(node: u16) -> anyhow::Result<SnowflakeIds> {
+        if node > MAX_NODE {
+            return Err(anyhow!("invalid snowflake node, node={}, max={}", node, MAX_NODE));
+        }
+        Ok(SnowflakeIds { node: node as u64, last: AtomicU64::new(0) })
+    }
+
+    pub fn node(&self) -> u16 {
+        self.node as u16
+    }
+
+    /// 分配now时刻的ID，不小于floor，floor为交易对已分配的成交ID高水位
+    pub fn next(&self, now: u128, floor: u64) -> u64 {
+        let ms = (now.saturating_sub(SNOWFLAKE_EPOCH_MS) as u64) << (NODE_BITS + SEQ_BITS);
+        let base = ms | (self.node << SEQ_BITS);
+        let mut last = self.last.load(Ordering::Relaxed);
+        loop {
+            let prev = last.max(floor);
+            let id = if base > prev {
+                base
+            } else {
+                Self::successor(prev, self.node)
+            };
+            match self.last.compare_exchange_weak(last, id, Ordering::Relaxed, Ordering::Relaxed) {
+                Ok(_) => return id,
+                Err(actual) => last = actual,
+            }
+        }
+    }
+
+    /// 保持节点号不变的下一个ID，序号用完时进位到毫秒
+    fn successor(prev: u64, node: u64) -> u64 {
+        let seq_mask = (1 << SEQ_BITS) - 1;
+        let ms = prev >> (NODE_BITS + SEQ_BITS);
+        let seq = prev & seq_mask;
+        // 高水位来自其他节点或连续ID时，序号从下一毫秒重新开始
+        if (prev >> SEQ_BITS) & MAX_NODE as u64 != node || seq == seq_mask {
+            return ((ms + 1) << (NODE_BITS + SEQ_BITS)) | (node << SEQ_BITS);
+        }
+        prev + 1
+    }
+}
+
+/// 交易对的成交ID分配器，未配置分布式ID时连续递增
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TradeIds {
+    /// 最后分配的成交ID
+    pub(crate) last: u64,
+    pub(crate) snowflake: Option<Arc<SnowflakeIds>>,
+}
+
+impl TradeIds {
+    pub(crate) fn next(&mut self, now: u128) -> u64 {
+        self.last = match &self.snowflake {
+            Some(snowflake) => snowflake.next(now, self.last),
+            None => self.last + 1,
+        };
+        self.last
+    }
+}
+
+/// 拆分ID为毫秒时间、节点号和序号
+pub fn snowflake_parts(id: u64) -> (u128, u16, u16) {
+    let ms = (id >> (NODE_BITS + SEQ_BITS)) as u128 + SNOWFLAKE_EPOCH_MS;
+    let node = ((id >> SEQ_BITS) & MAX_NODE as u64) as u16;
+    let seq = (id & ((1 << SEQ_BITS) - 1)) as u16;
+    (ms, node, seq)
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+    use std::sync::Arc;
+
+    use crate::id::{snowflake_parts, SnowflakeIds, SNOWFLAKE_EPOCH_MS};
+
+    #[test]
+    fn snowflake_test() {
+        let now = SNOWFLAKE_EPOCH_MS + 1000;
+        let ids = SnowflakeIds::new(3).unwrap();
+        let first = ids.next(now, 0);
+        assert_eq!(snowflake_parts(first), (now, 3, 0));
+        assert_eq!(snowflake_parts(ids.next(now, 0)), (now, 3, 1));
+        // 时钟回拨时继续递增
+        assert_eq!(snowflake_parts(ids.next(now - 10, 0)), (now, 3, 2));
+        // 序号用完借用下一毫秒
+        let mut last = 0;
+        for _ in 0..5000 {
+            last = ids.next(now, 0);
+        }
+        assert_eq!(snowflake_parts(last).0, now + 1);
+        // 高水位高于当前时间时从高水位之后分配
+        let other = SnowflakeIds::new(4).unwrap();
+        let id = other.next(now, last);
+        assert!(id > last);
+        assert_eq!(snowflake_parts(id), (now + 2, 4, 0));
+        assert!(SnowflakeIds::new(1024).is_err());
+    }
+
+    #[test]
+    fn collision_test() {
+        let now = SNOWFLAKE_EPOCH_MS + 1000;
+        let nodes: Vec<Arc<SnowflakeIds>> = (0..2).map(|n| Arc::new(SnowflakeIds::new(n).unwrap())).collect();
+        let handles: Vec<_> = (0..4).map(|i| {
+            let ids = Arc::clone(&nodes[i % 2]);
+            std::thread::spawn(move || (0..10_000).map(|_| ids.next(now, 0)).collect::<Vec<u64>>())
+        }).collect();
+        let mut seen = HashSet::new();
+        for handle in handles {
+            for id in handle.join().unwrap() {
+                assert!(seen.insert(id));
+            }
+        }
+    }
+}
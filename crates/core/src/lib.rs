@@ -1,4 +1,13 @@
+pub mod auction;
 pub mod book;
+pub mod clock;
+pub mod id;
 pub mod market;
 pub mod order;
+pub mod price;
+pub mod snapshot;
+pub mod symbol;
+#[cfg(any(test, feature = "test-util"))]
+pub mod testing;
+pub mod timer;
 pub mod utils;
@@ -1,143 +1,949 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
 use bigdecimal::BigDecimal;
-use log::debug;
+use log::{debug, warn};
 use serde::{Deserialize, Serialize};
+use smallvec::SmallVec;
 
+use crate::auction::{self, AuctionResult};
 use crate::book::OrderBook;
-use crate::order::{Order, OrderKey, OrderState};
-use crate::order::OrderState::{CANCELED, FULL_FILLED, INIT, PARTIAL_CANCELLED, PARTIAL_FILLED};
-use crate::order::OrderTimeInForce::{FOK, GTC, IOC};
+use crate::clock::{Clock, SystemClock};
+use crate::id::{SnowflakeIds, TradeIds};
+use crate::order::{Order, OrderKey, OrderState, OrderType, TradeSide};
+use crate::order::OrderState::{CANCELED, FULL_FILLED, INIT, LIVE, PARTIAL_CANCELLED, PARTIAL_FILLED};
+use crate::order::OrderTimeInForce::{FOK, GTC, GTD, GTX, IOC};
 use crate::order::OrderType::LIMIT;
 use crate::order::TradeSide::{BUY, SELL};
-use crate::utils;
+use crate::price::Price;
+use crate::snapshot::{BookDump, BookImage, BookSnapshot, BookStats, QueuePosition, StateHash, StateHasher};
+use crate::symbol::{CrossPolicy, Symbol, SymbolSpec};
+use crate::timer::TimerWheel;
+
+/// 单次撮合产生的成交，多数请求只产生少量成交，无需堆分配
+pub type MatchTrades = SmallVec<[MatchTrade; 4]>;
+
+/// 恢复后订单簿交叉时的处理策略
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub enum UncrossPolicy {
+    /// 较新的订单作为taker与对手方撮合
+    #[default]
+    Match,
+    /// 撤销较新的订单
+    CancelNewer,
+}
+
+/// 默认记录的最近订单ID数量
+pub const DEFAULT_RECENT_IDS: usize = 65536;
+
+/// 最近下单的订单ID，按到达顺序淘汰最早的ID，同时记录订单是否已完全成交
+#[derive(Debug)]
+struct RecentIds {
+    order: VecDeque<u64>,
+    ids: HashMap<u64, bool>,
+    capacity: usize,
+}
+
+impl RecentIds {
+    fn new(capacity: usize) -> RecentIds {
+        RecentIds {
+            order: VecDeque::new(),
+            ids: HashMap::new(),
+            capacity,
+        }
+    }
+
+    fn contains(&self, id: u64) -> bool {
+        self.ids.contains_key(&id)
+    }
+
+    fn is_filled(&self, id: u64) -> bool {
+        self.ids.get(&id).copied().unwrap_or(false)
+    }
+
+    fn insert(&mut self, id: u64) {
+        if self.capacity == 0 || self.ids.contains_key(&id) {
+            return;
+        }
+        self.ids.insert(id, false);
+        self.order.push_back(id);
+        if self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.ids.remove(&oldest);
+            }
+        }
+    }
+
+    /// 记录成交结果中完全成交的订单，恢复的挂单不在窗口中时一并加入
+    fn record(&mut self, trades: &[MatchTrade]) {
+        for trade in trades.iter().filter(|t| t.qty > 0) {
+            for (id, state) in [(trade.taker_oid, trade.taker_state), (trade.maker_oid, trade.maker_state)] {
+                if state == FULL_FILLED {
+                    self.insert(id);
+                    if let Some(filled) = self.ids.get_mut(&id) {
+                        *filled = true;
+                    }
+                }
+            }
+        }
+    }
+}
 
 /// 市场结构体，其中记录了最新成交价格和买卖双方的订单簿
 #[derive(Debug)]
 pub struct MarketBook {
     /// 交易对
-    pub symbol: String,
+    pub symbol: Symbol,
+    /// 交易对规格
+    spec: SymbolSpec,
+    /// 定点表示的最小价格变动
+    tick: Option<Price>,
     /// 买方订单簿
     buy: OrderBook,
     /// 卖方订单簿
     sell: OrderBook,
     /// 最新成交价
-    px: BigDecimal,
-    /// 最新成交时间
+    px: Price,
+    /// 最新成交价的成交时间，没有成交时为0
+    px_ts: u128,
+    /// 最后处理请求的时间
     ts: u128,
+    /// 市场版本号，每次处理请求后递增
+    version: u64,
+    /// 成交ID分配器
+    trade_id: TradeIds,
+    /// 已处理的最大请求序列号
+    seq: u64,
+    /// 时钟
+    clock: Arc<dyn Clock>,
+    /// GTD挂单的到期定时器
+    timers: TimerWheel,
+    /// 最近下单的订单ID，用于拒绝已离开订单簿的重复订单ID
+    recent: RecentIds,
+    /// 是否处于集合竞价阶段，期间限价单只进入订单簿不撮合
+    auction: bool,
 }
 
-unsafe impl Send for MarketBook {}
-
 impl MarketBook {
     pub fn new(symbol: &str) -> MarketBook {
+        Self::with_spec(SymbolSpec::new(symbol))
+    }
+
+    /// 按交易对规格构造市场
+    pub fn with_spec(spec: SymbolSpec) -> MarketBook {
         MarketBook {
-            symbol: String::from(symbol),
-            buy: OrderBook::new(symbol, BUY),
-            sell: OrderBook::new(symbol, SELL),
-            px: BigDecimal::from(0),
-            ts: Self::now_ts(),
+            symbol: Symbol::intern(&spec.symbol),
+            buy: OrderBook::new(&spec.symbol, BUY, spec.price_decimals),
+            sell: OrderBook::new(&spec.symbol, SELL, spec.price_decimals),
+            // 无效的步长不检查，规格应在构造前校验
+            tick: spec.tick().ok().flatten(),
+            spec,
+            px: Price::ZERO,
+            px_ts: 0,
+            ts: SystemClock.now_ts(),
+            version: 0,
+            trade_id: TradeIds::default(),
+            seq: 0,
+            clock: Arc::new(SystemClock),
+            timers: TimerWheel::new(SystemClock.now_ts()),
+            recent: RecentIds::new(DEFAULT_RECENT_IDS),
+            auction: false,
         }
     }
 
-    fn now_ts() -> u128 {
-        utils::now_ts()
+    /// 替换市场时钟
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> MarketBook {
+        self.ts = clock.now_ts();
+        self.timers = TimerWheel::new(self.ts);
+        self.clock = clock;
+        self
+    }
+
+    /// 设置记录的最近订单ID数量，更早的订单ID离开订单簿后不再检查重复，0表示只检查挂单
+    pub fn with_recent_ids(mut self, capacity: usize) -> MarketBook {
+        self.recent = RecentIds::new(capacity);
+        self
+    }
+
+    /// 使用分布式成交ID，多个交易对可共享同一生成器
+    pub fn with_snowflake(mut self, snowflake: Arc<SnowflakeIds>) -> MarketBook {
+        self.trade_id.snowflake = Some(snowflake);
+        self
+    }
+
+    /// 从持久化的成交ID高水位继续分配成交ID
+    pub fn with_trade_id(mut self, trade_id: u64) -> MarketBook {
+        self.trade_id.last = trade_id;
+        self
+    }
+
+    /// 最后分配的成交ID
+    pub fn trade_id(&self) -> u64 {
+        self.trade_id.last
+    }
+
+    /// 已处理的最大请求序列号
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    /// 最新成交价，没有成交时为0
+    pub fn last_px(&self) -> BigDecimal {
+        self.px.to_decimal(self.spec.price_decimals)
+    }
+
+    /// 最新成交价的成交时间，没有成交时为0
+    pub fn last_px_ts(&self) -> u128 {
+        self.px_ts
+    }
+
+    /// 最后处理请求的时间
+    pub fn last_ts(&self) -> u128 {
+        self.ts
+    }
+
+    /// 计算市场状态哈希，包含序列号、最新成交价和全部挂单，不包含时间和成交ID，耗时与挂单数量成正比
+    pub fn state_hash(&self) -> StateHash {
+        let mut hasher = StateHasher::new();
+        hasher.write(self.symbol.as_bytes());
+        hasher.write_u64(self.seq);
+        hasher.write_u64(self.px.raw() as u64);
+        self.buy.hash_into(&mut hasher);
+        // 买卖双方之间写入分隔，避免挂单在两侧移动时哈希相同
+        hasher.write(b"|");
+        self.sell.hash_into(&mut hasher);
+        StateHash {
+            symbol: self.symbol.to_string(),
+            seq: self.seq,
+            orders: self.buy.size() + self.sell.size(),
+            hash: format!("{:016x}", hasher.finish()),
+        }
+    }
+
+    /// 市场版本号，版本号未变化时订单簿没有修改
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// 生成档位汇总快照，耗时与档位数量成正比，不复制订单
+    pub fn snapshot(&self) -> BookSnapshot {
+        let decimals = self.spec.price_decimals;
+        BookSnapshot {
+            symbol: self.symbol.to_string(),
+            version: self.version,
+            px: self.px.to_decimal(decimals),
+            px_ts: self.px_ts,
+            ts: self.ts,
+            bids: BookSnapshot::levels(&self.buy, decimals),
+            asks: BookSnapshot::levels(&self.sell, decimals),
+            indicative: if self.auction { self.indicative() } else { None },
+            stats: self.stats(),
+        }
+    }
+
+    /// 买卖两侧的挂单汇总
+    pub fn stats(&self) -> BookStats {
+        BookStats {
+            bids: self.buy.stats(),
+            asks: self.sell.stats(),
+        }
     }
 
     /// 取消订单
-    pub fn try_cancel(&mut self, cancel: Order) -> Vec<MatchTrade> {
-        match cancel.side {
-            BUY => Self::cancel_book(&mut self.buy, cancel),
-            SELL => Self::cancel_book(&mut self.sell, cancel)
+    pub fn try_cancel(&mut self, cancel: Order) -> MatchTrades {
+        let mut trades = MatchTrades::new();
+        self.try_cancel_into(cancel, &mut trades);
+        trades
+    }
+
+    /// 按订单ID取消订单，不要求价格与挂单一致，结果追加到调用方提供的缓冲区中
+    ///
+    /// 订单不在订单簿中或属于其他账户时不产生结果，最近订单窗口内完全成交的订单返回`ALREADY_FILLED`，其他返回`NOT_FOUND`
+    pub fn try_cancel_into(&mut self, cancel: Order, trades: &mut MatchTrades) -> CancelResult {
+        self.version += 1;
+        self.seq = self.seq.max(cancel.seq);
+        let now = self.clock.now_ts();
+        let side = match self.locate(&cancel) {
+            Some(side) => side,
+            None if self.recent.is_filled(cancel.id) => return CancelResult::ALREADY_FILLED,
+            None => return CancelResult::NOT_FOUND,
+        };
+        Self::cancel_book(self.side_book_mut(side), cancel.id, now, trades);
+        CancelResult::CANCELED
+    }
+
+    /// 按订单ID减少挂单的委托数量并保留排队位置，减少的数量不小于剩余数量时撤销订单，订单不存在时返回false
+    pub fn try_reduce_into(&mut self, reduce: Order, trades: &mut MatchTrades) -> bool {
+        self.version += 1;
+        self.seq = self.seq.max(reduce.seq);
+        let now = self.clock.now_ts();
+        let side = match self.locate(&reduce) {
+            Some(side) => side,
+            None => return false,
+        };
+        let book = self.side_book_mut(side);
+        match book.reduce(reduce.id, reduce.qty).and_then(|_| book.get_by_id(reduce.id)) {
+            Some(order) => trades.push(MatchTrade::new_taker_reduce(order, reduce.qty, now)),
+            None => Self::cancel_book(book, reduce.id, now, trades),
+        }
+        true
+    }
+
+    /// 检查市场不变式，撮合之外买一价必须低于卖一价，集合竞价期间允许交叉，耗时与挂单数量成正比
+    pub fn verify(&self) -> anyhow::Result<()> {
+        self.buy.verify()?;
+        self.sell.verify()?;
+        if self.auction {
+            return Ok(());
+        }
+        if let (Some(bid), Some(ask)) = (self.buy.best_price(), self.sell.best_price()) {
+            if bid >= ask {
+                return Err(anyhow::anyhow!("book crossed, symbol={}, bid={:?}, ask={:?}", self.symbol, bid, ask));
+            }
+        }
+        Ok(())
+    }
+
+    /// 按订单ID查找挂单所在的方向，优先在请求方向的订单簿中查找，请求带账户时必须与挂单的账户一致，否则视为不存在
+    fn locate(&self, order: &Order) -> Option<TradeSide> {
+        let side = match order.side {
+            BUY if self.buy.exist_by_id(order.id) => BUY,
+            SELL if self.sell.exist_by_id(order.id) => SELL,
+            _ if self.buy.exist_by_id(order.id) => BUY,
+            _ if self.sell.exist_by_id(order.id) => SELL,
+            _ => return None,
+        };
+        let owner = self.side_book(side).get_by_id(order.id).map(|o| &o.account);
+        (order.account.is_none() || owner == Some(&order.account)).then_some(side)
+    }
+
+    /// 转储全部挂单，耗时与挂单数量成正比
+    pub fn dump(&self) -> BookDump {
+        BookDump {
+            symbol: self.symbol.to_string(),
+            version: self.version,
+            px: self.px.to_decimal(self.spec.price_decimals),
+            ts: self.ts,
+            bids: self.buy.iter().cloned().collect(),
+            asks: self.sell.iter().cloned().collect(),
+        }
+    }
+
+    pub fn spec(&self) -> &SymbolSpec {
+        &self.spec
+    }
+
+    /// 替换交易对规格，之后的请求按新规格检查，价格精度不可修改
+    pub fn update_spec(&mut self, spec: SymbolSpec) -> anyhow::Result<()> {
+        if spec.symbol != self.symbol || spec.price_decimals != self.spec.price_decimals {
+            return Err(anyhow::anyhow!(
+                "spec mismatch, symbol={}, decimals={}, spec_symbol={}, spec_decimals={}",
+                self.symbol, self.spec.price_decimals, spec.symbol, spec.price_decimals
+            ));
+        }
+        spec.validate()?;
+        self.tick = spec.tick()?;
+        self.spec = spec;
+        Ok(())
+    }
+
+    /// 按交易对规格检查新订单的价格步长、数量步长和限价带
+    fn check_spec(&self, order: &Order, px: Price, now: u128) -> anyhow::Result<()> {
+        if self.auction && !(order.ord_type == LIMIT && matches!(order.tif, GTC | GTD | GTX)) {
+            return Err(anyhow::anyhow!("only GTC, GTD and GTX limit orders are accepted during auction"));
+        }
+        if order.tif == GTX && order.ord_type != LIMIT {
+            return Err(anyhow::anyhow!("post-only order must be a limit order"));
+        }
+        if order.tif == GTD && order.expire_ts <= now {
+            return Err(anyhow::anyhow!("order expired or missing expire_ts, expire_ts={}", order.expire_ts));
+        }
+        if let Some(lot) = self.spec.lot_size {
+            if !order.qty.is_multiple_of(lot) {
+                return Err(anyhow::anyhow!("qty {} is not a multiple of lot size {}", order.qty, lot));
+            }
+        }
+        if order.ord_type != LIMIT {
+            return Ok(());
+        }
+        if let Some(tick) = self.tick {
+            if px.raw() % tick.raw() != 0 {
+                return Err(anyhow::anyhow!("price {} is not a multiple of tick size {}", order.price, tick.to_decimal(self.spec.price_decimals)));
+            }
+        }
+        if let (Some(pct), true) = (&self.spec.price_band_pct, self.px.raw() > 0) {
+            let deviation = BigDecimal::from((px.raw() - self.px.raw()).abs()) * BigDecimal::from(100) / BigDecimal::from(self.px.raw());
+            if &deviation > pct {
+                return Err(anyhow::anyhow!("price {} deviates {}% from last price", order.price, deviation.round(2)));
+            }
+        }
+        Ok(())
+    }
+
+    /// 生成市场镜像，耗时与挂单数量成正比
+    pub fn image(&self) -> BookImage {
+        BookImage {
+            symbol: self.symbol.to_string(),
+            price_decimals: self.spec.price_decimals,
+            version: self.version,
+            seq: self.seq,
+            trade_id: self.trade_id.last,
+            px: self.px.to_decimal(self.spec.price_decimals),
+            ts: self.ts,
+            bids: self.buy.iter().cloned().collect(),
+            asks: self.sell.iter().cloned().collect(),
+        }
+    }
+
+    /// 用镜像替换市场状态，保留交易对规格和时钟，镜像与规格不一致时不修改市场
+    pub fn restore(&mut self, image: BookImage) -> anyhow::Result<()> {
+        if image.symbol != self.symbol || image.price_decimals != self.spec.price_decimals {
+            return Err(anyhow::anyhow!(
+                "image mismatch, symbol={}, decimals={}, image_symbol={}, image_decimals={}",
+                self.symbol, self.spec.price_decimals, image.symbol, image.price_decimals
+            ));
+        }
+        let px = Price::from_decimal(&image.px, self.spec.price_decimals)?;
+        let mut buy = OrderBook::new(&self.symbol, BUY, self.spec.price_decimals);
+        let mut sell = OrderBook::new(&self.symbol, SELL, self.spec.price_decimals);
+        // 按撮合优先级依次加入，同价格档位内保持原有顺序
+        for order in image.bids {
+            buy.add(order)?;
+        }
+        for order in image.asks {
+            sell.add(order)?;
+        }
+        let mut timers = TimerWheel::new(self.clock.now_ts());
+        for order in buy.iter().chain(sell.iter()).filter(|o| o.tif == GTD) {
+            timers.insert(order.id, order.expire_ts);
+        }
+        self.buy = buy;
+        self.sell = sell;
+        self.timers = timers;
+        self.px = px;
+        // 镜像不记录成交时间，以镜像时间代替
+        self.px_ts = if px.raw() > 0 { image.ts } else { 0 };
+        self.ts = image.ts;
+        self.version = image.version;
+        self.seq = image.seq;
+        self.trade_id.last = image.trade_id;
+        Ok(())
+    }
+
+    /// 买一价是否不低于卖一价，正常撮合后不会出现，只可能由部分持久化的恢复数据造成
+    pub fn is_crossed(&self) -> bool {
+        matches!((self.buy.best_price(), self.sell.best_price()), (Some(bid), Some(ask)) if bid >= ask)
+    }
+
+    /// 按策略消除订单簿交叉，每次取买一和卖一档头中到达较晚的订单处理，结果追加到trades中，返回处理的订单数量
+    pub fn uncross_into(&mut self, policy: UncrossPolicy, trades: &mut MatchTrades) -> usize {
+        let now = self.clock.now_ts();
+        let start = trades.len();
+        let mut resolved = 0;
+        while self.is_crossed() {
+            let (bid, ask) = match (self.buy.head(), self.sell.head()) {
+                (Some((bid, _)), Some((ask, _))) => (bid, ask),
+                _ => break,
+            };
+            let newer = |key: &OrderKey, book: &OrderBook| book.get_by_id(key.sequence_id).map(|o| (o.arrival_ns(), o.id));
+            let side = if newer(&bid, &self.buy) >= newer(&ask, &self.sell) { BUY } else { SELL };
+            let (key, taker_book, maker_book) = match side {
+                BUY => (bid, &mut self.buy, &mut self.sell),
+                SELL => (ask, &mut self.sell, &mut self.buy),
+            };
+            warn!("UNCROSS ORDER: symbol={}, oid={}, side={}, policy={:?}", &self.symbol, key.sequence_id, side, policy);
+            match policy {
+                UncrossPolicy::Match => {
+                    let order = match taker_book.del_by_id(key.sequence_id) {
+                        Some(order) => order,
+                        None => break,
+                    };
+                    if let Some(px) = Self::match_book(order, key.price, now, &mut self.trade_id, maker_book, taker_book, trades) {
+                        self.px = px;
+                        self.px_ts = now;
+                    }
+                }
+                UncrossPolicy::CancelNewer => Self::cancel_book(taker_book, key.sequence_id, now, trades),
+            }
+            resolved += 1;
+        }
+        if resolved > 0 {
+            self.version += 1;
+            self.ts = now;
+        }
+        self.recent.record(&trades[start..]);
+        resolved
+    }
+
+    /// 撤销到期的GTD挂单，撤单结果追加到trades中，返回撤销的数量
+    pub fn expire_into(&mut self, trades: &mut MatchTrades) -> usize {
+        if self.timers.is_empty() {
+            return 0;
+        }
+        let now = self.clock.now_ts();
+        let mut expired = Vec::new();
+        self.timers.advance(now, &mut expired);
+        let mut count = 0;
+        for oid in expired {
+            // 已成交或撤销的订单不在订单簿中
+            let side = match (self.buy.get_by_id(oid), self.sell.get_by_id(oid)) {
+                (Some(order), _) if order.expire_ts <= now => BUY,
+                (_, Some(order)) if order.expire_ts <= now => SELL,
+                _ => continue,
+            };
+            if let Some(order) = self.side_book_mut(side).del_by_id(oid) {
+                let trade = if order.remain() != order.qty {
+                    MatchTrade::new_taker_partial_cancel(&order, now)
+                } else {
+                    MatchTrade::new_taker_cancel(&order, now)
+                };
+                trades.push(trade);
+                count += 1;
+            }
+        }
+        if count > 0 {
+            self.version += 1;
+            self.ts = now;
+        }
+        count
+    }
+
+    /// 撤销所有挂单，按买卖方向和价格时间优先顺序输出撤单结果，返回撤销的数量
+    pub fn cancel_all_into(&mut self, trades: &mut MatchTrades) -> usize {
+        let now = self.clock.now_ts();
+        let mut count = 0;
+        for book in [&mut self.buy, &mut self.sell] {
+            while let Some((key, _)) = book.head() {
+                Self::cancel_book(book, key.sequence_id, now, trades);
+                count += 1;
+            }
+        }
+        if count > 0 {
+            self.version += 1;
+            self.ts = now;
+        }
+        count
+    }
+
+    /// 撤销账户的所有挂单，返回撤销的数量
+    pub fn cancel_account_into(&mut self, account: &str, trades: &mut MatchTrades) -> usize {
+        let now = self.clock.now_ts();
+        let mut count = 0;
+        for book in [&mut self.buy, &mut self.sell] {
+            let ids: Vec<u64> = book.iter().filter(|o| o.account.as_deref() == Some(account)).map(|o| o.id).collect();
+            for id in ids {
+                Self::cancel_book(book, id, now, trades);
+                count += 1;
+            }
+        }
+        if count > 0 {
+            self.version += 1;
+            self.ts = now;
+        }
+        count
+    }
+
+    /// 订单立即撮合时最后一笔成交的价格，即扫过的最深档位价格，订单不能成交或处于集合竞价时返回None
+    pub fn sweep_px(&self, order: &Order) -> Option<BigDecimal> {
+        if self.auction {
+            return None;
+        }
+        let taker_px = Price::from_decimal(&order.price, self.spec.price_decimals).ok()?;
+        let maker_book = match order.side {
+            BUY => &self.sell,
+            SELL => &self.buy,
+        };
+        let (mut remain, mut last) = (order.remain(), None);
+        for level in maker_book.iter_levels() {
+            if remain == 0 || !Self::can_trade(order, taker_px, level.price()) {
+                break;
+            }
+            remain = remain.saturating_sub(level.total_qty());
+            last = Some(level.price());
+        }
+        last.map(|px| px.to_decimal(self.spec.price_decimals))
+    }
+
+    /// 撮合前拒绝订单，订单不进入订单簿
+    pub fn reject_into(&mut self, order: &Order, code: RejectCode, trades: &mut MatchTrades) {
+        self.version += 1;
+        self.seq = self.seq.max(order.seq);
+        let now = self.clock.now_ts();
+        warn!("REJECT ORDER: symbol={}, oid={}, code={:?}", &order.symbol, order.id, code);
+        trades.push(MatchTrade::new_taker_reject(order, code, now));
+        self.ts = now;
+    }
+
+    /// 挂单在其价格档位中的排队位置，订单不在订单簿中时返回None
+    pub fn queue_position(&self, oid: u64) -> Option<QueuePosition> {
+        let book = [&self.buy, &self.sell].into_iter().find(|book| book.exist_by_id(oid))?;
+        let order = book.get_by_id(oid)?;
+        let key = book.key_by_id(oid)?;
+        let (orders_ahead, qty_ahead) = book.queue_ahead(oid)?;
+        let level = book.iter_levels().find(|level| level.price() == key.price)?;
+        Some(QueuePosition {
+            symbol: self.symbol.to_string(),
+            oid,
+            side: order.side,
+            px: key.price.to_decimal(self.spec.price_decimals),
+            remain: order.remain(),
+            orders_ahead,
+            qty_ahead,
+            level_orders: level.size(),
+            level_qty: level.total_qty(),
+        })
+    }
+
+    /// 当前时钟时间
+    pub fn now_ts(&self) -> u128 {
+        self.clock.now_ts()
+    }
+
+    /// 进入集合竞价阶段
+    pub fn begin_auction(&mut self) {
+        self.auction = true;
+        self.version += 1;
+    }
+
+    pub fn in_auction(&self) -> bool {
+        self.auction
+    }
+
+    /// 按当前挂单计算的集合竞价参考结果，订单簿没有交叉时为空
+    pub fn indicative(&self) -> Option<AuctionResult> {
+        auction::equilibrium(&self.buy, &self.sell, self.px).map(|e| e.result(self.spec.price_decimals))
+    }
+
+    /// 结束集合竞价，所有可成交的订单按集合竞价价格撮合，之后恢复连续撮合，没有成交时返回None
+    pub fn end_auction_into(&mut self, trades: &mut MatchTrades) -> Option<AuctionResult> {
+        if !self.auction {
+            return None;
+        }
+        self.auction = false;
+        self.version += 1;
+        let now = self.clock.now_ts();
+        self.ts = now;
+        let equilibrium = auction::equilibrium(&self.buy, &self.sell, self.px)?;
+        let decimals = self.spec.price_decimals;
+        let px = equilibrium.price.to_decimal(decimals);
+        let start = trades.len();
+        while let (Some((bid_key, bid)), Some((ask_key, ask))) = (self.buy.head(), self.sell.head()) {
+            if bid_key.price < equilibrium.price || ask_key.price > equilibrium.price {
+                break;
+            }
+            let (bid_order, ask_order) = (self.buy.get(bid).unwrap().clone(), self.sell.get(ask).unwrap().clone());
+            let qty = bid_order.remain().min(ask_order.remain());
+            let state = |order: &Order| if order.remain() > qty { PARTIAL_FILLED } else { FULL_FILLED };
+            let (bid_state, ask_state) = (state(&bid_order), state(&ask_order));
+            let bid_remain = self.buy.fill(bid, qty, bid_state).unwrap_or(0);
+            let ask_remain = self.sell.fill(ask, qty, ask_state).unwrap_or(0);
+            // 到达较晚的订单作为taker
+            let buy_taker = (bid_order.arrival_ns(), bid_order.id) >= (ask_order.arrival_ns(), ask_order.id);
+            let ((taker, taker_state, taker_remain), (maker, maker_state, maker_remain)) = if buy_taker {
+                ((&bid_order, bid_state, bid_remain), (&ask_order, ask_state, ask_remain))
+            } else {
+                ((&ask_order, ask_state, ask_remain), (&bid_order, bid_state, bid_remain))
+            };
+            trades.push(MatchTrade {
+                id: self.trade_id.next(now),
+                symbol: self.symbol.clone(),
+                qty,
+                px: px.clone(),
+                taker_oid: taker.id,
+                maker_oid: maker.id,
+                taker_state,
+                maker_state,
+                ts: now,
+                reduce_qty: 0,
+                reject: None,
+                taker_side: Some(taker.side),
+                taker_ord_type: Some(taker.ord_type),
+                taker_px: Some(taker.price.clone()),
+                taker_remain,
+                maker_remain,
+                taker_account: taker.account.clone(),
+                maker_account: maker.account.clone(),
+                taker_tag: taker.tag.clone(),
+                maker_tag: maker.tag.clone(),
+            });
+        }
+        self.recent.record(&trades[start..]);
+        self.px = equilibrium.price;
+        self.px_ts = now;
+        Some(equilibrium.result(decimals))
+    }
+
+    /// 等待到期的GTD挂单数量，包括已离开订单簿但定时器尚未到期的订单
+    pub fn timer_count(&self) -> usize {
+        self.timers.len()
+    }
+
+    /// 买方订单簿
+    pub fn bids(&self) -> &OrderBook {
+        &self.buy
+    }
+
+    /// 卖方订单簿
+    pub fn asks(&self) -> &OrderBook {
+        &self.sell
+    }
+
+    fn side_book(&self, side: TradeSide) -> &OrderBook {
+        match side {
+            BUY => &self.buy,
+            SELL => &self.sell,
+        }
+    }
+
+    fn side_book_mut(&mut self, side: TradeSide) -> &mut OrderBook {
+        match side {
+            BUY => &mut self.buy,
+            SELL => &mut self.sell,
+        }
+    }
+
+    /// 恢复时按到达顺序批量挂单，不会与对手方成交的订单不经过撮合直接加入订单簿，结果与逐个撮合相同
+    ///
+    /// 遇到需要撮合或会被拒绝的订单时停止，返回从该订单开始的剩余订单，需逐个撮合
+    pub fn load(&mut self, orders: Vec<Order>) -> Vec<Order> {
+        let now = self.clock.now_ts();
+        let mut orders = orders.into_iter();
+        while let Some(order) = orders.next() {
+            if !self.can_rest(&order, now) {
+                return std::iter::once(order).chain(orders).collect();
+            }
+            self.version += 1;
+            self.seq = self.seq.max(order.seq);
+            self.recent.insert(order.id);
+            if order.tif == GTD {
+                self.timers.insert(order.id, order.expire_ts);
+            }
+            self.side_book_mut(order.side).add(order).unwrap();
+            self.ts = now;
         }
+        Vec::new()
+    }
+
+    /// 订单是否通过检查且不会与对手方成交，可直接加入订单簿
+    fn can_rest(&self, order: &Order, now: u128) -> bool {
+        if order.ord_type != LIMIT || !matches!(order.tif, GTC | GTD | GTX) || order.acc_fill_qty >= order.qty || order.is_stale(now) {
+            return false;
+        }
+        if self.buy.exist_by_id(order.id) || self.sell.exist_by_id(order.id) || self.recent.contains(order.id) {
+            return false;
+        }
+        let px = match Price::from_decimal(&order.price, self.spec.price_decimals) {
+            Ok(px) if self.check_spec(order, px, now).is_ok() => px,
+            _ => return false,
+        };
+        let opposite = match order.side {
+            BUY => self.sell.best_price(),
+            SELL => self.buy.best_price(),
+        };
+        self.auction || !opposite.is_some_and(|opposite| Self::can_trade(order, px, opposite))
     }
 
     /// 传入taker_order尝试撮合订单
-    pub fn try_match(&mut self, taker_order: Order) -> Vec<MatchTrade> {
-        let trades = match taker_order.side {
-            BUY => Self::match_book(taker_order, &mut self.sell, &mut self.buy),
-            SELL => Self::match_book(taker_order, &mut self.buy, &mut self.sell),
+    pub fn try_match(&mut self, taker_order: Order) -> MatchTrades {
+        let mut trades = MatchTrades::new();
+        self.try_match_into(taker_order, &mut trades);
+        trades
+    }
+
+    /// 撮合订单，成交追加到调用方提供的缓冲区中，便于在多次撮合间复用
+    pub fn try_match_into(&mut self, taker_order: Order, trades: &mut MatchTrades) {
+        self.version += 1;
+        self.seq = self.seq.max(taker_order.seq);
+        let now = self.clock.now_ts();
+        // 订单ID与挂单或最近的订单重复时拒绝，不会影响原订单
+        if self.buy.exist_by_id(taker_order.id) || self.sell.exist_by_id(taker_order.id) || self.recent.contains(taker_order.id) {
+            warn!("REJECT ORDER: symbol={}, oid={}, err=duplicate order id", &taker_order.symbol, taker_order.id);
+            trades.push(MatchTrade::new_taker_reject(&taker_order, RejectCode::DUPLICATE_ID, now));
+            return;
+        }
+        // 排队超过有效期的订单拒绝，避免网络阻塞后成交过时的报价
+        if taker_order.is_stale(now) {
+            warn!("REJECT ORDER: symbol={}, oid={}, err=too late, valid_until_ts={}, now={}", &taker_order.symbol, taker_order.id, taker_order.valid_until_ts, now);
+            trades.push(MatchTrade::new_taker_reject(&taker_order, RejectCode::TOO_LATE, now));
+            return;
+        }
+        // 价格精度或步长不符合交易对规格的订单直接取消
+        let taker_px = match Price::from_decimal(&taker_order.price, self.spec.price_decimals)
+            .and_then(|px| self.check_spec(&taker_order, px, now).map(|_| px)) {
+            Ok(px) => px,
+            Err(e) => {
+                warn!("REJECT ORDER: symbol={}, oid={}, err={}", &taker_order.symbol, taker_order.id, e);
+                trades.push(MatchTrade::new_taker_reject(&taker_order, RejectCode::INVALID_ORDER, now));
+                return;
+            }
+        };
+        // 只挂单订单会与对手方成交时按策略改价或拒绝
+        let (taker_order, taker_px) = match self.post_only(taker_order, taker_px, now, trades) {
+            Some(adjusted) => adjusted,
+            None => return,
+        };
+        self.recent.insert(taker_order.id);
+        let (oid, side) = (taker_order.id, taker_order.side);
+        let expire_ts = if taker_order.tif == GTD { taker_order.expire_ts } else { 0 };
+        if self.auction {
+            // 集合竞价期间只挂单，结束时统一撮合
+            if expire_ts != 0 {
+                self.timers.insert(oid, expire_ts);
+            }
+            self.side_book_mut(side).add(taker_order).unwrap();
+            self.ts = now;
+            return;
+        }
+        let start = trades.len();
+        let last_px = match taker_order.side {
+            BUY => Self::match_book(taker_order, taker_px, now, &mut self.trade_id, &mut self.sell, &mut self.buy, trades),
+            SELL => Self::match_book(taker_order, taker_px, now, &mut self.trade_id, &mut self.buy, &mut self.sell, trades),
         };
+        self.recent.record(&trades[start..]);
+        // 进入订单簿的GTD订单加入到期定时器
+        if expire_ts != 0 && self.side_book(side).exist_by_id(oid) {
+            self.timers.insert(oid, expire_ts);
+        }
         // 更新时间
-        self.ts = Self::now_ts();
+        self.ts = now;
         // 更新最新成交价格
-        if let Some(px) = trades.last().map(|trade| trade.px.clone()) {
+        if let Some(px) = last_px {
             self.px = px;
+            self.px_ts = now;
+        }
+    }
+
+    /// 按交易对的交叉处理策略调整会与对手方成交的只挂单订单，改价时追加改价结果，拒绝时追加拒绝结果并返回None
+    fn post_only(&self, mut order: Order, px: Price, now: u128, trades: &mut MatchTrades) -> Option<(Order, Price)> {
+        if order.tif != GTX || self.auction {
+            return Some((order, px));
+        }
+        let (opposite, own) = match order.side {
+            BUY => (self.sell.best_price(), self.buy.best_price()),
+            SELL => (self.buy.best_price(), self.sell.best_price()),
+        };
+        let Some(opposite) = opposite.filter(|opposite| Self::can_trade(&order, px, *opposite)) else {
+            return Some((order, px));
+        };
+        let tick = self.tick.map(|tick| tick.raw()).unwrap_or(1);
+        let passive = Price(match order.side {
+            BUY => opposite.raw() - tick,
+            SELL => opposite.raw() + tick,
+        });
+        let adjusted = match self.spec.cross_policy {
+            CrossPolicy::Reject => None,
+            CrossPolicy::Reprice => Some(passive),
+            CrossPolicy::Slide => Some(own.unwrap_or(passive)),
+        };
+        match adjusted.filter(|px| px.raw() > 0) {
+            Some(adjusted) => {
+                debug!("POST ONLY REPRICED: symbol={}, oid={}, price={}, adjusted={}", &order.symbol, order.id, &order.price, adjusted);
+                order.price = adjusted.to_decimal(self.spec.price_decimals);
+                trades.push(MatchTrade::new_taker_reprice(&order, now));
+                Some((order, adjusted))
+            }
+            None => {
+                warn!("REJECT ORDER: symbol={}, oid={}, err=post-only order would cross", &order.symbol, order.id);
+                trades.push(MatchTrade::new_taker_reject(&order, RejectCode::WOULD_CROSS, now));
+                None
+            }
         }
-        trades
     }
 
-    fn cancel_book(book: &mut OrderBook, cancel: Order) -> Vec<MatchTrade> {
-        let mut trades = Vec::new();
-        let order_key = OrderKey::new(&cancel);
-        if let Some(order) = book.del_by_key(&order_key) {
-            let order = order.borrow_mut();
-            let trade;
-            if order.remain() != order.qty {
+    fn cancel_book(book: &mut OrderBook, oid: u64, now: u128, trades: &mut MatchTrades) {
+        if let Some(order) = book.del_by_id(oid) {
+            let trade = if order.remain() != order.qty {
                 // 有部分成交
-                trade = MatchTrade::new_taker_partial_cancel(&order.symbol, order.id);
+                MatchTrade::new_taker_partial_cancel(&order, now)
             } else {
                 // 没有成交数量
-                trade = MatchTrade::new_taker_cancel(&order.symbol, order.id);
-            }
+                MatchTrade::new_taker_cancel(&order, now)
+            };
             trades.push(trade);
         }
-        trades
+    }
+
+    /// taker订单与maker订单簿头的价格是否可成交
+    fn can_trade(taker_order: &Order, taker_px: Price, maker_px: Price) -> bool {
+        if taker_order.ord_type == OrderType::MARKET {
+            // 如果为市价单，跳过比价可以直接成交
+            return true;
+        }
+        match taker_order.side {
+            // buy price >= sell price can trade
+            TradeSide::BUY => taker_px >= maker_px,
+            // sell price <= buy price can trade
+            TradeSide::SELL => taker_px <= maker_px,
+        }
+    }
+
+    /// 订单簿中可与taker订单成交的数量是否不少于qty
+    fn fillable(taker_order: &Order, taker_px: Price, qty: u64, maker_book: &OrderBook) -> bool {
+        let mut available = 0;
+        for level in maker_book.iter_levels() {
+            if !Self::can_trade(taker_order, taker_px, level.price()) {
+                break;
+            }
+            available += level.total_qty();
+            if available >= qty {
+                return true;
+            }
+        }
+        false
     }
 
     fn match_book(
         mut taker_order: Order,
+        taker_px: Price,
+        now: u128,
+        trade_id: &mut TradeIds,
         maker_book: &mut OrderBook,
         taker_book: &mut OrderBook,
-    ) -> Vec<MatchTrade> {
+        trades: &mut MatchTrades,
+    ) -> Option<Price> {
         // 检查taker_order是否存在，防止重复请求
-        if taker_book.exist_by_key(&OrderKey::new(&taker_order)) {
-            return Vec::with_capacity(0);
+        if taker_book.exist_by_key(&OrderKey::new(&taker_order, taker_px)) {
+            return None;
         }
-        let mut trades = Vec::with_capacity(10);
+        let decimals = maker_book.price_decimals();
+        let mut last_px = None;
         let mut taker_remain = taker_order.remain();
-        loop {
-            if taker_remain <= 0 {
-                // 已撮合完成，直接退出
-                break;
-            }
-
-            let maker_order = match maker_book.head() {
-                Some(order) => order,
+        // FOK订单按所有可成交档位的数量之和判断能否全部成交，不能时不撮合
+        let fillable = taker_order.tif != FOK || Self::fillable(&taker_order, taker_px, taker_remain, maker_book);
+        // 撮合完成后退出
+        while fillable && taker_remain > 0 {
+            let (maker_key, maker_handle) = match maker_book.head() {
+                Some(head) => head,
                 None => {
                     break;
                 }
             };
 
-            let mut maker_order = maker_order.borrow_mut();
+            let maker_order = maker_book.get(maker_handle).unwrap();
             // 检查是否可成交
-            if !taker_order.can_trade(&maker_order) {
+            if !Self::can_trade(&taker_order, taker_px, maker_key.price) {
                 // 与买/卖一不能成交，跳出循环
                 break;
             }
 
             // 确定撮合数量
-            let mut maker_remain = maker_order.remain();
-            let matched_qty;
-            if taker_order.tif == FOK && maker_remain < taker_remain {
-                // 不能完全成交,直接跳出
-                break;
-            } else {
-                matched_qty = taker_remain.min(maker_remain);
-            }
-            if matched_qty <= 0 {
+            let maker_remain = maker_order.remain();
+            let matched_qty = taker_remain.min(maker_remain);
+            if matched_qty == 0 {
                 break;
             }
 
-            // 修改maker订单
-            maker_remain -= matched_qty;
-            if maker_remain > 0 {
+            let maker_account = maker_order.account.clone();
+            let maker_tag = maker_order.tag.clone();
+            // 修改maker订单，完全成交的maker从订单簿中删除
+            let maker_state = if maker_remain > matched_qty {
                 // 有剩余部分成交
-                maker_order.fill(matched_qty, PARTIAL_FILLED);
+                PARTIAL_FILLED
             } else {
                 // 无剩余，完全成交
-                maker_order.fill(matched_qty, FULL_FILLED);
-                // 从订单簿中删除
-                maker_book.del(&maker_order);
-            }
+                FULL_FILLED
+            };
+            let maker_remain = maker_book.fill(maker_handle, matched_qty, maker_state).unwrap_or(0);
 
             // 修改taker订单
             taker_remain -= matched_qty;
@@ -151,16 +957,29 @@ impl MarketBook {
 
             // 构造撮合结果
             let trade = MatchTrade {
+                id: trade_id.next(now),
                 symbol: taker_order.symbol.clone(),
                 qty: matched_qty,
-                px: maker_order.price.clone(),
+                px: maker_key.price.to_decimal(decimals),
                 taker_oid: taker_order.id,
-                maker_oid: maker_order.id,
+                maker_oid: maker_key.sequence_id,
                 taker_state: taker_order.state,
-                maker_state: maker_order.state,
-                ts: Self::now_ts(),
+                maker_state,
+                ts: now,
+                reduce_qty: 0,
+                reject: None,
+                taker_side: Some(taker_order.side),
+                taker_ord_type: Some(taker_order.ord_type),
+                taker_px: Some(taker_order.price.clone()),
+                taker_remain,
+                maker_remain,
+                taker_account: taker_order.account.clone(),
+                maker_account,
+                taker_tag: taker_order.tag.clone(),
+                maker_tag,
             };
             trades.push(trade);
+            last_px = Some(maker_key.price);
 
             // 检查IOC订单是否要继续匹配
             if taker_order.tif == IOC && taker_order.state == PARTIAL_CANCELLED {
@@ -171,43 +990,71 @@ impl MarketBook {
 
         if taker_remain > 0 {
             match taker_order.tif {
-                GTC => {
-                    match taker_order.ord_type {
-                        LIMIT => {
-                            // 不能立即成交的限价单放入订单簿等待以后成交
-                            taker_book.add(taker_order).unwrap();
-                        }
-                        _ => {}
+                GTC | GTD | GTX => {
+                    if taker_order.ord_type == LIMIT {
+                        // 不能立即成交的限价单放入订单簿等待以后成交
+                        taker_book.add(taker_order).unwrap();
                     }
                 }
                 IOC => {
                     if taker_remain == taker_order.qty {
                         // 完全没有成交
                         taker_order.fill(0, CANCELED);
-                        trades.push(MatchTrade::new_taker_cancel(&taker_order.symbol, taker_order.id));
+                        trades.push(MatchTrade::new_taker_cancel(&taker_order, now));
                     } else {
                         // 有部分成交
                         taker_order.fill(0, PARTIAL_CANCELLED);
-                        trades.push(MatchTrade::new_taker_partial_cancel(&taker_order.symbol, taker_order.id));
+                        trades.push(MatchTrade::new_taker_partial_cancel(&taker_order, now));
                     }
                 }
                 FOK => {
                     taker_order.fill(0, CANCELED);
-                    trades.push(MatchTrade::new_taker_cancel(&taker_order.symbol, taker_order.id));
+                    trades.push(MatchTrade::new_taker_cancel(&taker_order, now));
                 }
             }
         }
-        trades
+        last_px
     }
 }
 
+/// 撮合器拒绝订单的原因
+#[allow(non_camel_case_types)]
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum RejectCode {
+    /// 不符合交易对规格
+    INVALID_ORDER,
+    /// 订单ID与挂单或最近的订单重复
+    DUPLICATE_ID,
+    /// 成交价偏离近期成交均价过多
+    PRICE_COLLAR,
+    /// 只挂单订单会与对手方成交
+    WOULD_CROSS,
+    /// 出队时已超过订单有效期
+    TOO_LATE,
+    /// 按撮合时的订单簿价格，市价买单所需资金超过预留和可用余额
+    INSUFFICIENT_FUNDS,
+}
+
+/// 撤单结果
+#[allow(non_camel_case_types)]
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum CancelResult {
+    /// 挂单已撤销
+    CANCELED,
+    /// 订单不在订单簿中，可能已撤销、已过期或从未挂单
+    NOT_FOUND,
+    /// 订单已完全成交
+    ALREADY_FILLED,
+}
+
 /// 成交结构体，记录了撮合的成交
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub struct MatchTrade {
-    /// 成交ID
-    // id: u64,
+    /// 成交ID，同一交易对内单调递增，撤单结果为0
+    #[serde(default)]
+    pub id: u64,
     /// symbol
-    pub symbol: String,
+    pub symbol: Symbol,
     /// 撮合数量
     pub qty: u64,
     /// 撮合价格
@@ -222,32 +1069,752 @@ pub struct MatchTrade {
     pub maker_state: OrderState,
     /// 成交时间
     pub ts: u128,
+    /// 减量结果减少的委托数量，其他结果为0
+    #[serde(default)]
+    pub reduce_qty: u64,
+    /// 订单被拒绝的原因
+    #[serde(default)]
+    pub reject: Option<RejectCode>,
+    /// taker订单方向，即主动成交方，maker为相反方向
+    #[serde(default)]
+    pub taker_side: Option<TradeSide>,
+    /// taker订单类型
+    #[serde(default)]
+    pub taker_ord_type: Option<OrderType>,
+    /// taker订单委托价格
+    #[serde(default)]
+    pub taker_px: Option<BigDecimal>,
+    /// 撮合后taker订单剩余数量，撤单和拒绝结果为0
+    #[serde(default)]
+    pub taker_remain: u64,
+    /// 撮合后maker订单剩余数量
+    #[serde(default)]
+    pub maker_remain: u64,
+    /// taker订单所属账户
+    #[serde(default)]
+    pub taker_account: Option<String>,
+    /// maker订单所属账户
+    #[serde(default)]
+    pub maker_account: Option<String>,
+    /// taker订单的标签
+    #[serde(default)]
+    pub taker_tag: Option<String>,
+    /// maker订单的标签
+    #[serde(default)]
+    pub maker_tag: Option<String>,
 }
 
 impl MatchTrade {
-    fn new_taker_cancel(s: &str, oid: u64) -> MatchTrade {
+    /// 只涉及taker订单的结果，如撤单、减量和拒绝
+    fn new_taker_result(order: &Order, state: OrderState, ts: u128) -> MatchTrade {
         MatchTrade {
-            symbol: s.to_owned(),
+            id: 0,
+            symbol: order.symbol.clone(),
             qty: 0,
             px: BigDecimal::from(0),
-            taker_oid: oid,
+            taker_oid: order.id,
             maker_oid: 0,
-            taker_state: CANCELED,
+            taker_state: state,
             maker_state: INIT,
-            ts: MarketBook::now_ts(),
+            ts,
+            reduce_qty: 0,
+            reject: None,
+            taker_side: Some(order.side),
+            taker_ord_type: Some(order.ord_type),
+            taker_px: Some(order.price.clone()),
+            taker_remain: 0,
+            maker_remain: 0,
+            taker_account: order.account.clone(),
+            maker_account: None,
+            taker_tag: order.tag.clone(),
+            maker_tag: None,
+        }
+    }
+
+    fn new_taker_cancel(order: &Order, ts: u128) -> MatchTrade {
+        MatchTrade::new_taker_result(order, CANCELED, ts)
+    }
+
+    fn new_taker_partial_cancel(order: &Order, ts: u128) -> MatchTrade {
+        MatchTrade::new_taker_result(order, PARTIAL_CANCELLED, ts)
+    }
+
+    /// order为减量后的订单
+    fn new_taker_reduce(order: &Order, qty: u64, ts: u128) -> MatchTrade {
+        MatchTrade {
+            reduce_qty: qty,
+            taker_remain: order.remain(),
+            ..MatchTrade::new_taker_result(order, order.state, ts)
         }
     }
 
-    fn new_taker_partial_cancel(s: &str, oid: u64) -> MatchTrade {
+    /// 只挂单订单改价后的结果，taker_px为改价后的价格
+    fn new_taker_reprice(order: &Order, ts: u128) -> MatchTrade {
         MatchTrade {
-            symbol: s.to_owned(),
-            qty: 0,
-            px: BigDecimal::from(0),
-            taker_oid: oid,
-            maker_oid: 0,
-            taker_state: PARTIAL_CANCELLED,
-            maker_state: INIT,
-            ts: MarketBook::now_ts(),
+            taker_remain: order.remain(),
+            ..MatchTrade::new_taker_result(order, LIVE, ts)
+        }
+    }
+
+    fn new_taker_reject(order: &Order, code: RejectCode, ts: u128) -> MatchTrade {
+        MatchTrade {
+            reject: Some(code),
+            ..MatchTrade::new_taker_cancel(order, ts)
+        }
+    }
+
+    /// 是否为减量结果
+    pub fn is_reduce(&self) -> bool {
+        self.reduce_qty != 0
+    }
+
+    /// 是否为只挂单订单的改价结果
+    pub fn is_reprice(&self) -> bool {
+        self.qty == 0 && self.maker_oid == 0 && !self.is_reduce() && self.reject.is_none() && self.taker_state == LIVE
+    }
+
+    /// maker订单方向，与taker方向相反
+    pub fn maker_side(&self) -> Option<TradeSide> {
+        self.taker_side.map(|side| match side {
+            BUY => SELL,
+            SELL => BUY,
+        })
+    }
+}
+
+/// taker订单的一笔成交
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Fill {
+    /// 成交ID
+    pub trade_id: u64,
+    /// maker的订单ID
+    pub maker_oid: u64,
+    pub px: BigDecimal,
+    pub qty: u64,
+}
+
+/// taker订单在一次撮合中的成交汇总
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct FillSummary {
+    /// 累计成交数量
+    pub filled_qty: u64,
+    /// 成交金额
+    pub notional: BigDecimal,
+    /// 成交均价，保留8位小数，没有成交时为空
+    pub avg_px: Option<BigDecimal>,
+    /// 撮合后的订单状态
+    pub state: Option<OrderState>,
+    /// 逐笔成交，按撮合顺序排列
+    pub fills: Vec<Fill>,
+}
+
+impl FillSummary {
+    /// 汇总taker订单的成交，忽略撤单、减量、改价和拒绝结果，state为撮合后的订单状态
+    pub fn of(oid: u64, trades: &[MatchTrade], state: Option<OrderState>) -> FillSummary {
+        let fills: Vec<Fill> = trades.iter()
+            .filter(|t| t.taker_oid == oid && t.qty > 0)
+            .map(|t| Fill { trade_id: t.id, maker_oid: t.maker_oid, px: t.px.clone(), qty: t.qty })
+            .collect();
+        let filled_qty: u64 = fills.iter().map(|f| f.qty).sum();
+        let notional: BigDecimal = fills.iter().map(|f| &f.px * BigDecimal::from(f.qty)).sum();
+        let avg_px = (filled_qty > 0).then(|| (&notional / BigDecimal::from(filled_qty)).round(8));
+        FillSummary { filled_qty, notional, avg_px, state, fills }
+    }
+}
+
+#[cfg(test)]
+mod market_test {
+    use std::str::FromStr;
+    use std::sync::Arc;
+
+    use bigdecimal::BigDecimal;
+
+    use crate::clock::ManualClock;
+    use crate::market::{CancelResult, MarketBook, MatchTrades, RejectCode, UncrossPolicy};
+    use crate::order::{Order, OrderAction, OrderState, OrderTimeInForce, OrderType, TradeSide};
+    use crate::symbol::{CrossPolicy, SymbolSpec};
+    use crate::snapshot::BookSnapshot;
+    use crate::testing::new_order;
+
+    #[test]
+    fn match_at_maker_price_test() {
+        let mut book = MarketBook::with_spec(SymbolSpec::new("LOOM-USDT-SPOT").with_price_decimals(2));
+        assert!(book.try_match(new_order(1, TradeSide::SELL, 3, "100.5")).is_empty());
+        let trades = book.try_match(new_order(2, TradeSide::BUY, 2, "101"));
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].qty, 2);
+        assert_eq!(trades[0].px, BigDecimal::from_str("100.5").unwrap());
+        assert_eq!(trades[0].maker_state, OrderState::PARTIAL_FILLED);
+        assert_eq!(trades[0].taker_state, OrderState::FULL_FILLED);
+        // 成交中包含双方剩余数量和taker委托信息
+        assert_eq!((trades[0].taker_remain, trades[0].maker_remain), (0, 1));
+        assert_eq!((trades[0].taker_side, trades[0].maker_side()), (Some(TradeSide::BUY), Some(TradeSide::SELL)));
+        assert_eq!(trades[0].taker_ord_type, Some(OrderType::LIMIT));
+        assert_eq!(trades[0].taker_px, Some(BigDecimal::from(101)));
+        let trades = book.try_cancel(new_order(1, TradeSide::SELL, 3, "100.5"));
+        assert_eq!((trades[0].taker_side, trades[0].taker_remain), (Some(TradeSide::SELL), 0));
+    }
+
+    #[test]
+    fn sweep_levels_test() {
+        let mut book = MarketBook::new("LOOM-USDT-SPOT");
+        book.try_match(new_order(1, TradeSide::SELL, 1, "100"));
+        book.try_match(new_order(2, TradeSide::SELL, 1, "101"));
+        book.try_match(new_order(3, TradeSide::SELL, 1, "100"));
+        let trades = book.try_match(new_order(4, TradeSide::BUY, 3, "101"));
+        let makers: Vec<u64> = trades.iter().map(|t| t.maker_oid).collect();
+        assert_eq!(makers, vec![1, 3, 2]);
+        let ids: Vec<u64> = trades.iter().map(|t| t.id).collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+        assert_eq!(trades[2].taker_state, OrderState::FULL_FILLED);
+    }
+
+    #[test]
+    fn reuse_trade_buffer_test() {
+        let mut book = MarketBook::new("LOOM-USDT-SPOT");
+        let mut trades = MatchTrades::new();
+        for id in 1..=8 {
+            book.try_match_into(new_order(id, TradeSide::SELL, 1, "100"), &mut trades);
         }
+        assert!(trades.is_empty());
+        book.try_match_into(new_order(9, TradeSide::BUY, 8, "100"), &mut trades);
+        assert_eq!(trades.len(), 8);
+        assert!(trades.spilled());
+        let capacity = trades.capacity();
+        trades.clear();
+        book.try_match_into(new_order(10, TradeSide::SELL, 1, "100"), &mut trades);
+        book.try_cancel_into(new_order(10, TradeSide::SELL, 1, "100"), &mut trades);
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades.capacity(), capacity);
+    }
+
+    #[test]
+    fn snapshot_test() {
+        let mut book = MarketBook::with_spec(SymbolSpec::new("LOOM-USDT-SPOT").with_price_decimals(2));
+        book.try_match(new_order(1, TradeSide::BUY, 1, "99"));
+        book.try_match(new_order(2, TradeSide::BUY, 2, "99.5"));
+        book.try_match(new_order(3, TradeSide::SELL, 3, "101"));
+        book.try_match(new_order(4, TradeSide::SELL, 4, "101"));
+        let snapshot = book.snapshot();
+        assert_eq!(snapshot.version, book.version());
+        let bids: Vec<String> = snapshot.bids.iter().map(|l| l.px.to_string()).collect();
+        assert_eq!(bids, vec!["99.50", "99.00"]);
+        assert_eq!(snapshot.asks.len(), 1);
+        assert_eq!(snapshot.asks[0].qty, 7);
+        assert_eq!(snapshot.asks[0].orders, 2);
+        // 快照不受后续撮合影响
+        book.try_match(new_order(5, TradeSide::BUY, 7, "101"));
+        assert_eq!(snapshot.asks[0].qty, 7);
+        assert!(book.snapshot().asks.is_empty());
+        let dump = book.dump();
+        let bids: Vec<u64> = dump.bids.iter().map(|o| o.id).collect();
+        assert_eq!(bids, vec![2, 1]);
+        assert!(dump.asks.is_empty());
+    }
+
+    #[test]
+    fn trade_id_test() {
+        let mut book = MarketBook::new("LOOM-USDT-SPOT").with_trade_id(41);
+        book.try_match(new_order(1, TradeSide::SELL, 2, "100"));
+        assert_eq!(book.try_match(new_order(2, TradeSide::BUY, 1, "100"))[0].id, 42);
+        let trades = book.try_cancel(new_order(1, TradeSide::SELL, 2, "100"));
+        assert_eq!(trades[0].id, 0);
+        assert_eq!(book.trade_id(), 42);
+    }
+
+    #[test]
+    fn clock_test() {
+        let clock = Arc::new(ManualClock::new(1000));
+        let mut book = MarketBook::new("LOOM-USDT-SPOT").with_clock(clock.clone());
+        book.try_match(new_order(1, TradeSide::SELL, 2, "100"));
+        clock.advance(10);
+        let trades = book.try_match(new_order(2, TradeSide::BUY, 1, "100"));
+        assert_eq!(trades[0].ts, 1010);
+        assert_eq!(book.snapshot().ts, 1010);
+        assert_eq!((book.last_px(), book.last_ts()), (BigDecimal::from(100), 1010));
+    }
+
+    #[test]
+    fn too_late_test() {
+        let clock = Arc::new(ManualClock::new(1000));
+        let mut book = MarketBook::new("LOOM-USDT-SPOT").with_clock(clock.clone());
+        book.try_match(new_order(1, TradeSide::SELL, 2, "100"));
+        let quote = |id: u64| Order { valid_until_ts: 1005, ..new_order(id, TradeSide::BUY, 1, "100") };
+        assert_eq!(book.try_match(quote(2))[0].maker_oid, 1);
+        clock.advance(10);
+        let trades = book.try_match(quote(3));
+        assert_eq!((trades[0].reject, trades[0].taker_state, trades[0].qty), (Some(RejectCode::TOO_LATE), OrderState::CANCELED, 0));
+        assert_eq!(book.snapshot().asks[0].qty, 1);
+        // 被拒绝的订单ID可以用新的有效期重新提交
+        assert_eq!(book.try_match(Order { valid_until_ts: 0, ..quote(3) })[0].maker_oid, 1);
+    }
+
+    #[test]
+    fn state_hash_test() {
+        let requests = |book: &mut MarketBook| {
+            for (id, side, qty, px) in [(1, TradeSide::SELL, 3, "100"), (2, TradeSide::BUY, 1, "100"), (3, TradeSide::BUY, 2, "99")] {
+                let mut order = new_order(id, side, qty, px);
+                order.seq = id;
+                book.try_match(order);
+            }
+        };
+        let mut primary = MarketBook::new("LOOM-USDT-SPOT");
+        let mut replica = MarketBook::new("LOOM-USDT-SPOT").with_trade_id(100);
+        requests(&mut primary);
+        requests(&mut replica);
+        let hash = primary.state_hash();
+        assert_eq!(hash, replica.state_hash());
+        assert_eq!((hash.seq, hash.orders), (3, 2));
+        // 副本多处理一个请求后哈希不同
+        let mut cancel = new_order(3, TradeSide::BUY, 2, "99");
+        cancel.seq = 4;
+        replica.try_cancel(cancel);
+        assert_eq!(replica.state_hash().orders, 1);
+        assert_ne!(replica.state_hash().hash, hash.hash);
+    }
+
+    #[test]
+    fn image_restore_test() {
+        let mut book = MarketBook::with_spec(SymbolSpec::new("LOOM-USDT-SPOT").with_price_decimals(2));
+        for (id, side, qty, px) in [(1, TradeSide::SELL, 3, "100"), (2, TradeSide::SELL, 1, "100"), (3, TradeSide::BUY, 1, "100"), (4, TradeSide::BUY, 2, "99.5")] {
+            let mut order = new_order(id, side, qty, px);
+            order.seq = id;
+            book.try_match(order);
+        }
+        let image = book.image();
+        let mut restored = MarketBook::with_spec(SymbolSpec::new("LOOM-USDT-SPOT").with_price_decimals(2));
+        restored.restore(image.clone()).unwrap();
+        assert_eq!(restored.state_hash(), book.state_hash());
+        assert_eq!(restored.image(), image);
+        // 恢复后继续撮合，同档位先到先得
+        let trades = restored.try_match(new_order(5, TradeSide::BUY, 3, "100"));
+        let makers: Vec<u64> = trades.iter().map(|t| t.maker_oid).collect();
+        assert_eq!(makers, vec![1, 2]);
+        assert_eq!(trades[0].id, book.trade_id() + 1);
+        assert!(MarketBook::new("LOOM-USDT-SPOT").restore(image).is_err());
+    }
+
+    #[test]
+    fn spec_check_test() {
+        let spec = SymbolSpec::new("LOOM-USDT-SPOT")
+            .with_price_decimals(2)
+            .with_tick_size(BigDecimal::from_str("0.5").unwrap())
+            .with_lot_size(2);
+        let mut book = MarketBook::with_spec(spec);
+        let rejected = |trades: MatchTrades| trades.len() == 1 && trades[0].taker_state == OrderState::CANCELED;
+        assert!(rejected(book.try_match(new_order(1, TradeSide::SELL, 2, "100.25"))));
+        assert!(rejected(book.try_match(new_order(2, TradeSide::SELL, 3, "100.5"))));
+        assert!(book.try_match(new_order(3, TradeSide::SELL, 2, "100.5")).is_empty());
+        book.try_match(new_order(4, TradeSide::BUY, 2, "100.5"));
+        // 运行时加入限价带
+        let spec = book.spec().clone().with_price_band_pct(BigDecimal::from(10));
+        book.update_spec(spec).unwrap();
+        assert!(rejected(book.try_match(new_order(5, TradeSide::SELL, 2, "111"))));
+        assert!(book.try_match(new_order(6, TradeSide::SELL, 2, "110")).is_empty());
+        assert!(book.update_spec(SymbolSpec::new("LOOM-USDT-SPOT")).is_err());
+    }
+
+    #[test]
+    fn cancel_by_id_test() {
+        let mut book = MarketBook::new("LOOM-USDT-SPOT");
+        book.try_match(new_order(1, TradeSide::SELL, 2, "100"));
+        book.try_match(new_order(2, TradeSide::BUY, 1, "100"));
+        // 价格和方向与挂单不一致时也能撤单
+        let mut trades = MatchTrades::new();
+        assert_eq!(book.try_cancel_into(new_order(1, TradeSide::BUY, 2, "99"), &mut trades), CancelResult::CANCELED);
+        assert_eq!((trades[0].taker_oid, trades[0].taker_state), (1, OrderState::PARTIAL_CANCELLED));
+        assert_eq!(book.try_cancel_into(new_order(1, TradeSide::SELL, 2, "100"), &mut trades), CancelResult::NOT_FOUND);
+        assert_eq!(trades.len(), 1);
+        assert!(book.dump().asks.is_empty());
+        // 完全成交的maker和taker都能识别
+        book.try_match(new_order(3, TradeSide::SELL, 1, "100"));
+        book.try_match(new_order(4, TradeSide::BUY, 1, "100"));
+        assert_eq!(book.try_cancel_into(new_order(3, TradeSide::SELL, 1, "100"), &mut trades), CancelResult::ALREADY_FILLED);
+        assert_eq!(book.try_cancel_into(new_order(4, TradeSide::BUY, 1, "100"), &mut trades), CancelResult::ALREADY_FILLED);
+        assert_eq!(book.try_cancel_into(new_order(9, TradeSide::BUY, 1, "100"), &mut trades), CancelResult::NOT_FOUND);
+        assert_eq!(trades.len(), 1);
+    }
+
+    #[test]
+    fn duplicate_id_test() {
+        let mut book = MarketBook::new("LOOM-USDT-SPOT").with_recent_ids(2);
+        book.try_match(new_order(1, TradeSide::SELL, 1, "100"));
+        book.try_match(new_order(2, TradeSide::BUY, 1, "99"));
+        // 挂单的订单ID在另一方向和不同价格上重复
+        let trades = book.try_match(new_order(1, TradeSide::BUY, 1, "101"));
+        assert_eq!((trades[0].reject, trades[0].taker_state), (Some(RejectCode::DUPLICATE_ID), OrderState::CANCELED));
+        assert_eq!(book.dump().asks.len(), 1);
+        // 已成交的订单ID在窗口内仍然拒绝
+        book.try_match(new_order(3, TradeSide::BUY, 1, "100"));
+        let trades = book.try_match(new_order(3, TradeSide::BUY, 1, "98"));
+        assert_eq!(trades[0].reject, Some(RejectCode::DUPLICATE_ID));
+        // 超出窗口后只检查挂单
+        book.try_match(new_order(4, TradeSide::BUY, 1, "98"));
+        book.try_match(new_order(5, TradeSide::BUY, 1, "98"));
+        assert!(book.try_match(new_order(3, TradeSide::BUY, 1, "97")).is_empty());
+        assert_eq!(book.try_match(new_order(2, TradeSide::BUY, 1, "97"))[0].reject, Some(RejectCode::DUPLICATE_ID));
+    }
+
+    #[test]
+    fn send_sync_test() {
+        // 订单簿使用内存池和索引保存挂单，可以安全地在线程间转移和共享
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<MarketBook>();
+        assert_send_sync::<MatchTrades>();
+    }
+
+    #[test]
+    fn verify_test() {
+        let mut book = MarketBook::new("LOOM-USDT-SPOT");
+        book.try_match(new_order(1, TradeSide::SELL, 3, "101"));
+        book.try_match(new_order(2, TradeSide::BUY, 2, "100"));
+        book.try_match(new_order(3, TradeSide::BUY, 1, "101"));
+        book.verify().unwrap();
+        // 直接加入订单簿的挂单与卖一交叉
+        book.buy.add(new_order(4, TradeSide::BUY, 1, "102")).unwrap();
+        assert!(book.verify().is_err());
+    }
+
+    #[test]
+    fn uncross_test() {
+        let crossed = || {
+            let mut book = MarketBook::new("LOOM-USDT-SPOT");
+            let mut image = book.image();
+            for (id, side, qty, px) in [(1, TradeSide::BUY, 2, "101"), (2, TradeSide::BUY, 1, "99"), (3, TradeSide::SELL, 3, "100"), (4, TradeSide::SELL, 1, "102")] {
+                let mut order = new_order(id, side, qty, px);
+                order.ts = id as u128;
+                match side {
+                    TradeSide::BUY => image.bids.push(order),
+                    TradeSide::SELL => image.asks.push(order),
+                }
+            }
+            book.restore(image).unwrap();
+            assert!(book.is_crossed());
+            book
+        };
+        // 较新的卖单3作为taker在买单1的价格成交
+        let mut book = crossed();
+        let mut trades = MatchTrades::new();
+        assert_eq!(book.uncross_into(UncrossPolicy::Match, &mut trades), 1);
+        assert_eq!((trades[0].taker_oid, trades[0].maker_oid, trades[0].qty), (3, 1, 2));
+        assert_eq!(trades[0].px, BigDecimal::from(101));
+        assert!(!book.is_crossed());
+        book.verify().unwrap();
+        assert_eq!(book.snapshot().asks[0].qty, 1);
+        let mut book = crossed();
+        let mut trades = MatchTrades::new();
+        assert_eq!(book.uncross_into(UncrossPolicy::CancelNewer, &mut trades), 1);
+        assert_eq!((trades[0].taker_oid, trades[0].taker_state), (3, OrderState::CANCELED));
+        assert_eq!(book.uncross_into(UncrossPolicy::CancelNewer, &mut trades), 0);
+        assert_eq!(book.dump().bids.len(), 2);
+    }
+
+    #[test]
+    fn arrival_order_test() {
+        // 同一毫秒内买单9先到达，卖单3后到达，按到达时间而非订单ID决定taker
+        let mut book = MarketBook::new("LOOM-USDT-SPOT");
+        let mut image = book.image();
+        let mut bid = new_order(9, TradeSide::BUY, 1, "101");
+        (bid.ts, bid.ts_ns) = (1, 1_000_100);
+        let mut ask = new_order(3, TradeSide::SELL, 1, "100");
+        (ask.ts, ask.ts_ns) = (1, 1_000_200);
+        image.bids.push(bid);
+        image.asks.push(ask);
+        book.restore(image).unwrap();
+        let mut trades = MatchTrades::new();
+        assert_eq!(book.uncross_into(UncrossPolicy::Match, &mut trades), 1);
+        assert_eq!((trades[0].taker_oid, trades[0].maker_oid), (3, 9));
+        // 未分配单调时间时按毫秒时间换算
+        let order = Order { ts: 2, ..new_order(1, TradeSide::BUY, 1, "100") };
+        assert_eq!(order.arrival_ns(), 2_000_000);
+    }
+
+    #[test]
+    fn load_test() {
+        let orders: Vec<Order> = [(1, TradeSide::SELL, "101"), (2, TradeSide::BUY, "100"), (3, TradeSide::SELL, "101"), (4, TradeSide::BUY, "101"), (5, TradeSide::SELL, "102")]
+            .into_iter()
+            .map(|(id, side, px)| {
+                let mut order = new_order(id, side, 1, px);
+                order.seq = id;
+                order
+            })
+            .collect();
+        let mut loaded = MarketBook::new("LOOM-USDT-SPOT");
+        // 4会与1成交，从4开始需要逐个撮合
+        let rest = loaded.load(orders.clone());
+        assert_eq!(rest.iter().map(|o| o.id).collect::<Vec<u64>>(), vec![4, 5]);
+        assert_eq!(loaded.state_hash().seq, 3);
+        let trades: Vec<MatchTrades> = rest.into_iter().map(|o| loaded.try_match(o)).collect();
+        assert_eq!((trades[0][0].maker_oid, trades[0][0].qty), (1, 1));
+        let mut matched = MarketBook::new("LOOM-USDT-SPOT");
+        for order in orders {
+            matched.try_match(order);
+        }
+        assert_eq!(loaded.state_hash(), matched.state_hash());
+        // 重复的订单ID不能直接挂单
+        assert_eq!(loaded.load(vec![new_order(3, TradeSide::SELL, 1, "101")]).len(), 1);
+    }
+
+    #[test]
+    fn reduce_test() {
+        let mut book = MarketBook::new("LOOM-USDT-SPOT");
+        book.try_match(new_order(1, TradeSide::SELL, 5, "100"));
+        book.try_match(new_order(2, TradeSide::SELL, 5, "100"));
+        book.try_match(new_order(3, TradeSide::BUY, 1, "100"));
+        let mut trades = MatchTrades::new();
+        let mut reduce = new_order(1, TradeSide::SELL, 2, "100");
+        reduce.action = OrderAction::REDUCE;
+        assert!(book.try_reduce_into(reduce.clone(), &mut trades));
+        assert!(trades[0].is_reduce());
+        assert_eq!((trades[0].reduce_qty, trades[0].taker_state), (2, OrderState::PARTIAL_FILLED));
+        // 减量后保留排队位置
+        let trades = book.try_match(new_order(4, TradeSide::BUY, 3, "100"));
+        let fills: Vec<(u64, u64)> = trades.iter().map(|t| (t.maker_oid, t.qty)).collect();
+        assert_eq!(fills, vec![(1, 2), (2, 1)]);
+        // 减少的数量不小于剩余数量时撤单
+        let mut trades = MatchTrades::new();
+        reduce.id = 2;
+        reduce.qty = 4;
+        assert!(book.try_reduce_into(reduce.clone(), &mut trades));
+        assert_eq!(trades[0].taker_state, OrderState::PARTIAL_CANCELLED);
+        assert!(!book.try_reduce_into(reduce, &mut trades));
+        assert!(book.dump().asks.is_empty());
+    }
+
+    #[test]
+    fn expire_test() {
+        let clock = Arc::new(ManualClock::new(1000));
+        let mut book = MarketBook::new("LOOM-USDT-SPOT").with_clock(clock.clone());
+        let gtd = |id, side, qty, expire_ts| {
+            let mut order = new_order(id, side, qty, "100");
+            order.tif = OrderTimeInForce::GTD;
+            order.expire_ts = expire_ts;
+            order
+        };
+        let rejected = |trades: MatchTrades| trades.len() == 1 && trades[0].taker_state == OrderState::CANCELED;
+        assert!(rejected(book.try_match(gtd(1, TradeSide::SELL, 1, 1000))));
+        assert!(book.try_match(gtd(2, TradeSide::SELL, 2, 1010)).is_empty());
+        assert!(book.try_match(gtd(3, TradeSide::SELL, 1, 1020)).is_empty());
+        book.try_match(new_order(4, TradeSide::BUY, 1, "100"));
+        let mut trades = MatchTrades::new();
+        assert_eq!(book.expire_into(&mut trades), 0);
+        clock.advance(10);
+        // 部分成交的订单到期
+        assert_eq!(book.expire_into(&mut trades), 1);
+        assert_eq!((trades[0].taker_oid, trades[0].taker_state), (2, OrderState::PARTIAL_CANCELLED));
+        // 已成交的订单不会再到期
+        book.try_match(new_order(5, TradeSide::BUY, 1, "100"));
+        clock.advance(10);
+        assert_eq!(book.expire_into(&mut trades), 0);
+        assert_eq!(book.timer_count(), 0);
+        assert!(book.dump().asks.is_empty());
+        // 恢复镜像后重新加入定时器
+        book.try_match(gtd(6, TradeSide::BUY, 1, 1030));
+        let mut restored = MarketBook::new("LOOM-USDT-SPOT").with_clock(clock.clone());
+        restored.restore(book.image()).unwrap();
+        clock.advance(10);
+        assert_eq!(restored.expire_into(&mut trades), 1);
+    }
+
+    #[test]
+    fn cancel_all_test() {
+        let mut book = MarketBook::new("LOOM-USDT-SPOT");
+        book.try_match(new_order(1, TradeSide::SELL, 2, "101"));
+        book.try_match(new_order(2, TradeSide::SELL, 1, "100"));
+        book.try_match(new_order(3, TradeSide::BUY, 1, "99"));
+        book.try_match(new_order(4, TradeSide::BUY, 2, "101"));
+        let mut trades = MatchTrades::new();
+        assert_eq!(book.cancel_all_into(&mut trades), 2);
+        let cancelled: Vec<_> = trades.iter().map(|t| (t.taker_oid, t.taker_state)).collect();
+        assert_eq!(cancelled, vec![(3, OrderState::CANCELED), (1, OrderState::PARTIAL_CANCELLED)]);
+        assert_eq!(book.state_hash().orders, 0);
+        assert_eq!(book.cancel_all_into(&mut trades), 0);
+    }
+
+    #[test]
+    fn cancel_account_test() {
+        let mut book = MarketBook::new("LOOM-USDT-SPOT");
+        for (id, side, price) in [(1, TradeSide::SELL, "101"), (2, TradeSide::BUY, "99"), (3, TradeSide::BUY, "98")] {
+            let mut order = new_order(id, side, 1, price);
+            order.account = Some(String::from(if id == 3 { "bob" } else { "alice" }));
+            book.try_match(order);
+        }
+        let mut trades = MatchTrades::new();
+        assert_eq!(book.cancel_account_into("alice", &mut trades), 2);
+        assert!(trades.iter().all(|t| t.taker_state == OrderState::CANCELED && t.taker_account.as_deref() == Some("alice")));
+        assert_eq!(book.state_hash().orders, 1);
+        assert_eq!(book.cancel_account_into("alice", &mut trades), 0);
+    }
+
+    #[test]
+    fn cancel_other_account_test() {
+        let mut book = MarketBook::new("LOOM-USDT-SPOT");
+        book.try_match(Order { account: Some(String::from("alice")), ..new_order(1, TradeSide::SELL, 2, "100") });
+        // 其他账户不能撤销或减少挂单，也无法得知订单存在
+        let mut trades = MatchTrades::new();
+        let cancel = Order { account: Some(String::from("bob")), ..new_order(1, TradeSide::SELL, 2, "100") };
+        assert_eq!(book.try_cancel_into(cancel.clone(), &mut trades), CancelResult::NOT_FOUND);
+        assert!(!book.try_reduce_into(Order { qty: 1, ..cancel }, &mut trades));
+        assert!(trades.is_empty());
+        assert_eq!(book.state_hash().orders, 1);
+        // 本账户和不带账户的内部撤单不受影响
+        let reduce = Order { account: Some(String::from("alice")), ..new_order(1, TradeSide::SELL, 1, "100") };
+        assert!(book.try_reduce_into(reduce, &mut trades));
+        assert_eq!(book.try_cancel_into(new_order(1, TradeSide::SELL, 1, "100"), &mut trades), CancelResult::CANCELED);
+        assert_eq!(trades.len(), 2);
+    }
+
+    #[test]
+    fn order_tag_test() {
+        let mut book = MarketBook::new("LOOM-USDT-SPOT");
+        let maker = Order { tag: Some(String::from("desk-a")), ..new_order(1, TradeSide::SELL, 1, "100") };
+        book.try_match(maker);
+        let taker = Order { tag: Some(String::from("strategy-7")), ..new_order(2, TradeSide::BUY, 2, "100") };
+        let trades = book.try_match(taker);
+        assert_eq!((trades[0].taker_tag.as_deref(), trades[0].maker_tag.as_deref()), (Some("strategy-7"), Some("desk-a")));
+        // 撤单结果带上挂单的标签
+        let cancel = Order { action: OrderAction::CANCEL, ..new_order(2, TradeSide::BUY, 1, "100") };
+        let mut trades = MatchTrades::new();
+        assert_eq!(book.try_cancel_into(cancel, &mut trades), CancelResult::CANCELED);
+        assert_eq!(trades[0].taker_tag.as_deref(), Some("strategy-7"));
+    }
+
+    #[test]
+    fn queue_position_test() {
+        let mut book = MarketBook::new("LOOM-USDT-SPOT");
+        book.try_match(new_order(1, TradeSide::BUY, 2, "100"));
+        book.try_match(new_order(2, TradeSide::BUY, 3, "101"));
+        book.try_match(new_order(3, TradeSide::BUY, 4, "100"));
+        book.try_match(new_order(4, TradeSide::BUY, 5, "100"));
+        let position = book.queue_position(4).unwrap();
+        assert_eq!((position.orders_ahead, position.qty_ahead, position.remain), (2, 6, 5));
+        assert_eq!((position.level_orders, position.level_qty, position.px), (3, 11, BigDecimal::from(100)));
+        assert_eq!(book.queue_position(2).unwrap().orders_ahead, 0);
+        // 卖单先吃掉101档，再成交100档的第一笔订单
+        book.try_match(new_order(5, TradeSide::SELL, 4, "100"));
+        let position = book.queue_position(4).unwrap();
+        assert_eq!((position.orders_ahead, position.qty_ahead), (2, 5));
+        assert_eq!(book.queue_position(5), None);
+
+        let mut snapshot = book.snapshot();
+        assert_eq!(snapshot.imbalance(5), Some(BigDecimal::from(1)));
+        snapshot.asks = snapshot.bids.clone();
+        assert_eq!(snapshot.imbalance(5), Some(BigDecimal::from(0)));
+        assert_eq!(BookSnapshot::empty("LOOM-USDT-SPOT").imbalance(5), None);
+    }
+
+    #[test]
+    fn preview_test() {
+        let mut book = MarketBook::new("LOOM-USDT-SPOT");
+        book.try_match(new_order(1, TradeSide::SELL, 2, "100"));
+        book.try_match(new_order(2, TradeSide::SELL, 3, "102"));
+        book.try_match(new_order(3, TradeSide::SELL, 5, "104"));
+        let snapshot = book.snapshot();
+        let preview = snapshot.preview(TradeSide::BUY, 4, None, usize::MAX);
+        assert_eq!((preview.filled, preview.remain), (4, 0));
+        assert_eq!(preview.fills.iter().map(|f| f.qty).collect::<Vec<_>>(), vec![2, 2]);
+        assert_eq!(preview.notional, BigDecimal::from(404));
+        assert_eq!(preview.avg_px, Some(BigDecimal::from(101)));
+        assert_eq!(preview.slippage_bps, Some(BigDecimal::from(100)));
+        // 限价和档位数量都会截断模拟
+        let preview = snapshot.preview(TradeSide::BUY, 20, Some(&BigDecimal::from(103)), usize::MAX);
+        assert_eq!((preview.filled, preview.remain), (5, 15));
+        assert_eq!(snapshot.preview(TradeSide::BUY, 20, None, 1).filled, 2);
+        let preview = snapshot.preview(TradeSide::SELL, 1, None, usize::MAX);
+        assert_eq!((preview.filled, preview.avg_px, preview.best_px), (0, None, None));
+        // 模拟不修改订单簿
+        assert_eq!(book.snapshot(), snapshot);
+    }
+
+    #[test]
+    fn post_only_test() {
+        let post_only = |id: u64, side: TradeSide, price: &str| Order { tif: OrderTimeInForce::GTX, ..new_order(id, side, 1, price) };
+        let spec = SymbolSpec::new("LOOM-USDT-SPOT").with_price_decimals(2).with_tick_size(BigDecimal::from_str("0.5").unwrap());
+        let mut book = MarketBook::with_spec(spec.clone());
+        book.try_match(new_order(1, TradeSide::SELL, 1, "101"));
+        book.try_match(new_order(2, TradeSide::BUY, 1, "99"));
+        // 不交叉时直接挂单
+        assert!(book.try_match(post_only(3, TradeSide::BUY, "100")).is_empty());
+        // 默认拒绝
+        let trades = book.try_match(post_only(4, TradeSide::BUY, "101"));
+        assert_eq!((trades[0].reject, trades[0].taker_state), (Some(RejectCode::WOULD_CROSS), OrderState::CANCELED));
+        assert_eq!(book.state_hash().orders, 3);
+
+        let mut book = MarketBook::with_spec(spec.clone().with_cross_policy(CrossPolicy::Reprice));
+        book.try_match(new_order(1, TradeSide::SELL, 1, "101"));
+        book.try_match(new_order(2, TradeSide::BUY, 1, "99"));
+        let trades = book.try_match(post_only(3, TradeSide::BUY, "102"));
+        assert!(trades[0].is_reprice());
+        assert_eq!((trades[0].taker_px.clone(), trades[0].taker_state), (Some(BigDecimal::from_str("100.5").unwrap()), OrderState::LIVE));
+        assert_eq!(book.queue_position(3).unwrap().px, BigDecimal::from_str("100.5").unwrap());
+        assert!(!book.is_crossed());
+
+        let mut book = MarketBook::with_spec(spec.with_cross_policy(CrossPolicy::Slide));
+        book.try_match(new_order(1, TradeSide::SELL, 1, "101"));
+        // 本方没有挂单时改价到对手方之外一个步长
+        let trades = book.try_match(post_only(2, TradeSide::BUY, "102"));
+        assert_eq!(trades[0].taker_px, Some(BigDecimal::from_str("100.5").unwrap()));
+        // 之后加入本方最优价排队
+        let trades = book.try_match(post_only(3, TradeSide::BUY, "102"));
+        assert_eq!(trades[0].taker_px, Some(BigDecimal::from_str("100.5").unwrap()));
+        assert_eq!(book.queue_position(3).unwrap().orders_ahead, 1);
+        assert!(book.try_match(Order { ord_type: OrderType::MARKET, ..post_only(5, TradeSide::SELL, "0") })[0].reject.is_some());
+    }
+
+    #[test]
+    fn sweep_test() {
+        let mut book = MarketBook::new("LOOM-USDT-SPOT");
+        book.try_match(new_order(1, TradeSide::SELL, 2, "101"));
+        book.try_match(new_order(2, TradeSide::SELL, 2, "103"));
+        assert_eq!(book.sweep_px(&new_order(3, TradeSide::BUY, 2, "105")), Some(BigDecimal::from(101)));
+        assert_eq!(book.sweep_px(&new_order(3, TradeSide::BUY, 3, "105")), Some(BigDecimal::from(103)));
+        // 限价限制扫过的档位
+        assert_eq!(book.sweep_px(&new_order(3, TradeSide::BUY, 3, "102")), Some(BigDecimal::from(101)));
+        assert_eq!(book.sweep_px(&new_order(3, TradeSide::BUY, 3, "100")), None);
+        assert_eq!(book.sweep_px(&new_order(3, TradeSide::SELL, 3, "100")), None);
+
+        let mut trades = MatchTrades::new();
+        book.reject_into(&new_order(3, TradeSide::BUY, 3, "105"), RejectCode::PRICE_COLLAR, &mut trades);
+        assert_eq!((trades[0].reject, trades[0].taker_state), (Some(RejectCode::PRICE_COLLAR), OrderState::CANCELED));
+        assert_eq!(book.state_hash().orders, 2);
+    }
+
+    #[test]
+    fn auction_test() {
+        let mut book = MarketBook::new("LOOM-USDT-SPOT");
+        book.begin_auction();
+        book.try_match(new_order(1, TradeSide::BUY, 5, "102"));
+        book.try_match(new_order(2, TradeSide::BUY, 3, "100"));
+        book.try_match(new_order(3, TradeSide::SELL, 4, "99"));
+        book.try_match(new_order(4, TradeSide::SELL, 3, "101"));
+        // 集合竞价期间不撮合，只接受限价挂单
+        assert_eq!(book.state_hash().orders, 4);
+        assert!(book.verify().is_ok());
+        let mut ioc = new_order(5, TradeSide::BUY, 1, "105");
+        ioc.tif = OrderTimeInForce::IOC;
+        assert_eq!(book.try_match(ioc)[0].reject, Some(RejectCode::INVALID_ORDER));
+        // 101成交量为5且剩余2，最大
+        let indicative = book.indicative().unwrap();
+        assert_eq!(book.snapshot().indicative, Some(indicative.clone()));
+        assert_eq!((indicative.price.clone(), indicative.volume), (BigDecimal::from(101), 5));
+        assert_eq!((indicative.imbalance_side, indicative.imbalance_qty), (Some(TradeSide::SELL), 2));
+        let mut trades = MatchTrades::new();
+        assert_eq!(book.end_auction_into(&mut trades), Some(indicative));
+        assert!(trades.iter().all(|t| t.px == BigDecimal::from(101)));
+        assert_eq!(trades.iter().map(|t| t.qty).sum::<u64>(), 5);
+        assert_eq!(book.last_px(), BigDecimal::from(101));
+        assert!(!book.in_auction() && !book.is_crossed());
+        assert_eq!(book.snapshot().indicative, None);
+        assert!(book.verify().is_ok());
+        // 之后恢复连续撮合
+        assert_eq!(book.try_match(new_order(6, TradeSide::BUY, 1, "101")).len(), 1);
+    }
+
+    #[test]
+    fn reject_price_precision_test() {
+        let mut book = MarketBook::with_spec(SymbolSpec::new("LOOM-USDT-SPOT").with_price_decimals(2));
+        let trades = book.try_match(new_order(1, TradeSide::SELL, 3, "100.555"));
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].taker_state, OrderState::CANCELED);
     }
 }
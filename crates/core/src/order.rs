@@ -7,11 +7,13 @@ use anyhow::anyhow;
 use bigdecimal::BigDecimal;
 use serde::{Deserialize, Serialize};
 
-use crate::order::OrderAction::{CANCEL, PLACE};
+use crate::order::OrderAction::{CANCEL, PLACE, REDUCE};
 use crate::order::OrderState::{CANCELED, FULL_FILLED, INIT, LIVE, PARTIAL_CANCELLED, PARTIAL_FILLED};
-use crate::order::OrderTimeInForce::{FOK, GTC, IOC};
+use crate::order::OrderTimeInForce::{FOK, GTC, GTD, GTX, IOC};
 use crate::order::OrderType::{LIMIT, MARKET};
 use crate::order::TradeSide::{BUY, SELL};
+use crate::price::Price;
+use crate::symbol::Symbol;
 
 #[derive(Eq, PartialEq, Hash, Copy, Clone, Debug, Serialize, Deserialize)]
 pub enum TradeSide {
@@ -67,6 +69,7 @@ impl FromStr for OrderType {
     }
 }
 
+#[allow(non_camel_case_types)]
 #[derive(Eq, PartialEq, Hash, Copy, Clone, Debug, Serialize, Deserialize)]
 pub enum OrderState {
     INIT,
@@ -123,6 +126,10 @@ pub enum OrderTimeInForce {
     GTC,
     IOC,
     FOK,
+    /// 挂单到expire_ts后自动撤销
+    GTD,
+    /// 只挂单，会与对手方成交时按交易对的交叉处理策略调整价格或拒绝
+    GTX,
 }
 impl Display for OrderTimeInForce {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
@@ -130,6 +137,8 @@ impl Display for OrderTimeInForce {
             GTC => write!(f, "GTC"),
             IOC => write!(f, "IOC"),
             FOK => write!(f, "FOK"),
+            GTD => write!(f, "GTD"),
+            GTX => write!(f, "GTX"),
         }
     }
 }
@@ -141,6 +150,8 @@ impl FromStr for OrderTimeInForce {
             "GTC" => Ok(GTC),
             "IOC" => Ok(IOC),
             "FOK" => Ok(FOK),
+            "GTD" => Ok(GTD),
+            "GTX" => Ok(GTX),
             _ => Err(anyhow!("no match OrderTimeInForce value={}", s))
         }
     }
@@ -150,12 +161,15 @@ impl FromStr for OrderTimeInForce {
 pub enum OrderAction {
     PLACE,
     CANCEL,
+    /// 减少挂单的委托数量并保留排队位置，qty为减少的数量
+    REDUCE,
 }
 impl Display for OrderAction {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             PLACE => write!(f, "PLACE"),
-            CANCEL => write!(f, "CANCEL")
+            CANCEL => write!(f, "CANCEL"),
+            REDUCE => write!(f, "REDUCE"),
         }
     }
 }
@@ -166,6 +180,7 @@ impl FromStr for OrderAction {
         match s {
             "PLACE" => Ok(PLACE),
             "CANCEL" => Ok(CANCEL),
+            "REDUCE" => Ok(REDUCE),
             _ => Err(anyhow!("no match OrderTimeInForce value={}", s))
         }
     }
@@ -177,7 +192,7 @@ pub struct Order {
     /// 订单序列号
     pub id: u64,
     /// 交易对
-    pub symbol: String,
+    pub symbol: Symbol,
     /// 交易方向
     pub side: TradeSide,
     /// 委托数量
@@ -198,18 +213,60 @@ pub struct Order {
     pub tif: OrderTimeInForce,
     /// 订单动作
     pub action: OrderAction,
+    /// 下单账户，未设置时不受账户限制
+    #[serde(default)]
+    pub account: Option<String>,
+    /// 引擎分配的请求序列号，同一交易对内单调递增，0表示未分配
+    #[serde(default)]
+    pub seq: u64,
+    /// GTD订单的到期时间，毫秒，0表示不过期
+    #[serde(default)]
+    pub expire_ts: u128,
+    /// 进入引擎的单调时间，纳秒，同一毫秒内的订单按此排序，显示仍使用ts，0表示未分配
+    #[serde(default)]
+    pub ts_ns: u64,
+    /// 最晚出队时间，毫秒，撮合时已超过则以TOO_LATE拒绝，0表示不限制
+    #[serde(default)]
+    pub valid_until_ts: u128,
+    /// 接入方附加的标签，例如策略ID或交易台，引擎不解析，原样带入成交结果
+    #[serde(default)]
+    pub tag: Option<String>,
 }
 
-// unsafe impl Send for Order {}
+/// 未成交的GTC限价买单，其余字段为0或空，构造订单时用结构体更新语法只填写需要的字段
+impl Default for Order {
+    fn default() -> Self {
+        Order {
+            id: 0,
+            symbol: Symbol::default(),
+            side: TradeSide::BUY,
+            qty: 0,
+            price: BigDecimal::from(0),
+            acc_fill_qty: 0,
+            ord_type: OrderType::LIMIT,
+            ts: 0,
+            update_ts: 0,
+            state: OrderState::LIVE,
+            tif: OrderTimeInForce::GTC,
+            action: OrderAction::PLACE,
+            account: None,
+            seq: 0,
+            expire_ts: 0,
+            ts_ns: 0,
+            valid_until_ts: 0,
+            tag: None,
+        }
+    }
+}
 
 impl Order {
     pub fn from_map(map: &HashMap<String, String>) -> anyhow::Result<Order> {
         Ok(Order {
             id: map.get("id").unwrap().parse()?,
-            symbol: map.get("symbol").unwrap().to_string(),
+            symbol: Symbol::from(map.get("symbol").unwrap()),
             side: map.get("side").unwrap().parse()?,
             qty: map.get("qty").unwrap().parse()?,
-            price: BigDecimal::from_str(&map.get("price").unwrap())?,
+            price: BigDecimal::from_str(map.get("price").unwrap())?,
             acc_fill_qty: map.get("acc_fill_qty").unwrap().parse()?,
             ord_type: map.get("ord_type").unwrap().parse()?,
             ts: map.get("ts").unwrap().parse()?,
@@ -217,12 +274,32 @@ impl Order {
             state: map.get("state").unwrap().parse()?,
             tif: map.get("tif").unwrap().parse()?,
             action: map.get("action").unwrap().parse()?,
+            account: map.get("account").filter(|a| !a.is_empty()).cloned(),
+            seq: map.get("seq").map(|s| s.parse()).transpose()?.unwrap_or(0),
+            expire_ts: map.get("expire_ts").map(|s| s.parse()).transpose()?.unwrap_or(0),
+            ts_ns: map.get("ts_ns").map(|s| s.parse()).transpose()?.unwrap_or(0),
+            // 有效期只约束首次出队，不写入缓存，恢复的挂单不会因此被拒绝
+            valid_until_ts: 0,
+            tag: map.get("tag").filter(|t| !t.is_empty()).cloned(),
         })
     }
 
+    /// 排序使用的到达时间，纳秒，未分配单调时间的订单使用ts换算
+    pub fn arrival_ns(&self) -> u128 {
+        match self.ts_ns {
+            0 => self.ts * 1_000_000,
+            ts_ns => ts_ns as u128,
+        }
+    }
+
+    /// 出队时是否已超过有效期
+    pub fn is_stale(&self, now: u128) -> bool {
+        self.valid_until_ts != 0 && now > self.valid_until_ts
+    }
+
     /// 订单剩余未撮合的数量
     pub fn remain(&self) -> u64 {
-        return self.qty - self.acc_fill_qty;
+        self.qty - self.acc_fill_qty
     }
 
     /// 当前订单是否可与传入的订单撮合
@@ -258,8 +335,8 @@ impl Order {
 pub struct OrderKey {
     /// 订单序列
     pub sequence_id: u64,
-    /// 订单价格
-    pub price: BigDecimal,
+    /// 订单定点价格
+    pub price: Price,
     /// 交易方向
     pub side: TradeSide,
 }
@@ -277,15 +354,10 @@ impl Ord for OrderKey {
         if self.side != other.side {
             panic!("order side mismatch!")
         }
-        let ordering: Ordering;
-        match other.side {
-            TradeSide::BUY => {
-                ordering = other.price.cmp(&self.price);
-            }
-            TradeSide::SELL => {
-                ordering = self.price.cmp(&other.price);
-            }
-        }
+        let ordering = match other.side {
+            TradeSide::BUY => other.price.cmp(&self.price),
+            TradeSide::SELL => self.price.cmp(&other.price),
+        };
         match ordering {
             Ordering::Equal => {
                 // if price eq else cmp sequence
@@ -297,11 +369,12 @@ impl Ord for OrderKey {
 }
 
 impl OrderKey {
-    pub fn new(order: &Order) -> OrderKey {
+    /// 构造排序键，price为按交易对精度转换后的定点价格
+    pub fn new(order: &Order, price: Price) -> OrderKey {
         OrderKey {
             sequence_id: order.id,
-            price: order.price.clone(),
-            side: order.side.clone(),
+            price,
+            side: order.side,
         }
     }
 }
@@ -310,26 +383,25 @@ impl OrderKey {
 mod order_key_test {
     use std::cmp::Ordering;
 
-    use bigdecimal::BigDecimal;
-
     use crate::order::OrderKey;
     use crate::order::TradeSide;
+    use crate::price::Price;
 
     #[test]
     fn order_key_buy_test() {
         let o1 = OrderKey {
             sequence_id: 0,
-            price: BigDecimal::from(0),
+            price: Price(0),
             side: TradeSide::BUY,
         };
         let o2 = OrderKey {
             sequence_id: 1,
-            price: BigDecimal::from(1),
+            price: Price(1),
             side: TradeSide::BUY,
         };
         let o3 = OrderKey {
             sequence_id: 2,
-            price: BigDecimal::from(2),
+            price: Price(2),
             side: TradeSide::BUY,
         };
         // 期望排序：o3,o2,o1
@@ -342,17 +414,17 @@ mod order_key_test {
     fn order_key_sell_test() {
         let o1 = OrderKey {
             sequence_id: 0,
-            price: BigDecimal::from(0),
+            price: Price(0),
             side: TradeSide::SELL,
         };
         let o2 = OrderKey {
             sequence_id: 1,
-            price: BigDecimal::from(1),
+            price: Price(1),
             side: TradeSide::SELL,
         };
         let o3 = OrderKey {
             sequence_id: 2,
-            price: BigDecimal::from(2),
+            price: Price(2),
             side: TradeSide::SELL,
         };
         // 期望排序：o1,o2,o3
@@ -365,17 +437,17 @@ mod order_key_test {
     fn order_key_eq_price_test() {
         let o1 = OrderKey {
             sequence_id: 0,
-            price: BigDecimal::from(1),
+            price: Price(1),
             side: TradeSide::BUY,
         };
         let o2 = OrderKey {
             sequence_id: 1,
-            price: BigDecimal::from(1),
+            price: Price(1),
             side: TradeSide::BUY,
         };
         let o3 = OrderKey {
             sequence_id: 2,
-            price: BigDecimal::from(1),
+            price: Price(1),
             side: TradeSide::BUY,
         };
         // 期望排序：o1,o2,o3
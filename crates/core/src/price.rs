@@ -0,0 +1,72 @@
+use std::fmt::{Display, Formatter};
+
+use anyhow::anyhow;
+use bigdecimal::num_bigint::BigInt;
+use bigdecimal::{BigDecimal, ToPrimitive};
+use serde::{Deserialize, Serialize};
+
+/// 定点价格，以交易对价格精度缩放后的整数表示，撮合内部只使用该类型比较
+#[derive(Eq, PartialEq, Ord, PartialOrd, Hash, Copy, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Price(pub i64);
+
+impl Price {
+    pub const ZERO: Price = Price(0);
+
+    /// 将BigDecimal价格按精度转换为定点价格，精度超出或溢出时返回错误
+    pub fn from_decimal(px: &BigDecimal, decimals: u32) -> anyhow::Result<Price> {
+        let scaled = px.with_scale(decimals as i64);
+        if &scaled != px {
+            return Err(anyhow!("price precision exceeds {} decimals, price={}", decimals, px));
+        }
+        let (value, _) = scaled.as_bigint_and_exponent();
+        value.to_i64()
+            .map(Price)
+            .ok_or_else(|| anyhow!("price overflow, price={}", px))
+    }
+
+    /// 按精度还原为BigDecimal价格
+    pub fn to_decimal(&self, decimals: u32) -> BigDecimal {
+        BigDecimal::new(BigInt::from(self.0), decimals as i64)
+    }
+
+    pub fn raw(&self) -> i64 {
+        self.0
+    }
+}
+
+impl Display for Price {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod price_test {
+    use std::str::FromStr;
+
+    use bigdecimal::BigDecimal;
+
+    use crate::price::Price;
+
+    #[test]
+    fn price_round_trip_test() {
+        let px = BigDecimal::from_str("100.25").unwrap();
+        let price = Price::from_decimal(&px, 4).unwrap();
+        assert_eq!(price, Price(1_002_500));
+        assert_eq!(price.to_decimal(4), px);
+    }
+
+    #[test]
+    fn price_precision_test() {
+        let px = BigDecimal::from_str("0.123").unwrap();
+        assert!(Price::from_decimal(&px, 2).is_err());
+        assert!(Price::from_decimal(&px, 3).is_ok());
+    }
+
+    #[test]
+    fn price_order_test() {
+        let low = Price::from_decimal(&BigDecimal::from_str("9.99").unwrap(), 2).unwrap();
+        let high = Price::from_decimal(&BigDecimal::from(10), 2).unwrap();
+        assert!(low < high);
+    }
+}
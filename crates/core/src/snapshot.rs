@@ -0,0 +1,285 @@
+use bigdecimal::BigDecimal;
+use serde::{Deserialize, Serialize};
+
+use crate::auction::AuctionResult;
+use crate::book::{Level, OrderBook};
+use crate::order::{Order, TradeSide};
+
+/// 价格档位快照
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct LevelSnapshot {
+    /// 档位价格
+    pub px: BigDecimal,
+    /// 档位剩余数量
+    pub qty: u64,
+    /// 档位订单数量
+    pub orders: usize,
+}
+
+/// 单边挂单汇总
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SideStats {
+    /// 挂单数量
+    pub orders: usize,
+    /// 挂单剩余数量之和
+    pub qty: u64,
+    /// 挂单价格乘以剩余数量之和
+    pub notional: BigDecimal,
+}
+
+/// 订单簿挂单汇总
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct BookStats {
+    pub bids: SideStats,
+    pub asks: SideStats,
+}
+
+/// 市场只读快照，只包含档位汇总，生成后与撮合状态无关，可在撮合锁之外序列化
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct BookSnapshot {
+    pub symbol: String,
+    /// 生成快照时的市场版本号，每次修改订单簿后递增
+    pub version: u64,
+    /// 最新成交价
+    pub px: BigDecimal,
+    /// 最新成交价的成交时间，没有成交时为0
+    #[serde(default)]
+    pub px_ts: u128,
+    /// 最后处理请求的时间
+    pub ts: u128,
+    /// 买方档位，价格从高到低
+    pub bids: Vec<LevelSnapshot>,
+    /// 卖方档位，价格从低到高
+    pub asks: Vec<LevelSnapshot>,
+    /// 集合竞价期间按当前挂单计算的参考撮合结果，订单簿没有交叉或不在集合竞价时为空
+    #[serde(default)]
+    pub indicative: Option<AuctionResult>,
+    /// 全部挂单的汇总，不受档位截断影响
+    #[serde(default)]
+    pub stats: BookStats,
+}
+
+/// 模拟成交的单个价格档位
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct PreviewFill {
+    /// 成交价格
+    pub px: BigDecimal,
+    /// 成交数量
+    pub qty: u64,
+}
+
+/// 按快照档位模拟撮合的结果，不考虑自成交保护等只在撮合线程中生效的规则
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct MatchPreview {
+    pub symbol: String,
+    /// 模拟所基于的快照版本号
+    pub version: u64,
+    pub side: TradeSide,
+    /// 委托数量
+    pub qty: u64,
+    /// 逐档成交，按撮合顺序排列
+    pub fills: Vec<PreviewFill>,
+    /// 成交数量之和
+    pub filled: u64,
+    /// 未成交数量
+    pub remain: u64,
+    /// 成交金额
+    pub notional: BigDecimal,
+    /// 成交均价，没有成交时为空
+    pub avg_px: Option<BigDecimal>,
+    /// 对手方最优价格，对手方没有挂单时为空
+    pub best_px: Option<BigDecimal>,
+    /// 成交均价相对最优价格的不利偏离，单位基点
+    pub slippage_bps: Option<BigDecimal>,
+}
+
+/// 挂单的排队位置
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct QueuePosition {
+    pub symbol: String,
+    pub oid: u64,
+    pub side: TradeSide,
+    /// 挂单价格
+    pub px: BigDecimal,
+    /// 挂单剩余数量
+    pub remain: u64,
+    /// 同档位排在前面的订单数量
+    pub orders_ahead: usize,
+    /// 同档位排在前面的订单剩余数量之和
+    pub qty_ahead: u64,
+    /// 档位订单数量
+    pub level_orders: usize,
+    /// 档位剩余数量
+    pub level_qty: u64,
+}
+
+/// 市场状态哈希，相同请求序列撮合后的市场哈希相同，用于比对副本状态
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct StateHash {
+    pub symbol: String,
+    /// 市场已处理的最大请求序列号
+    pub seq: u64,
+    /// 挂单数量
+    pub orders: usize,
+    /// 状态哈希，十六进制
+    pub hash: String,
+}
+
+/// 市场镜像，包含恢复市场所需的全部状态，用于快速重启和迁移
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct BookImage {
+    pub symbol: String,
+    /// 价格精度，恢复时需与交易对规格一致
+    pub price_decimals: u32,
+    pub version: u64,
+    /// 已处理的最大请求序列号
+    pub seq: u64,
+    /// 最后分配的成交ID
+    pub trade_id: u64,
+    /// 最新成交价
+    pub px: BigDecimal,
+    /// 最新成交时间
+    pub ts: u128,
+    /// 买方挂单，按撮合优先级排列
+    pub bids: Vec<Order>,
+    /// 卖方挂单，按撮合优先级排列
+    pub asks: Vec<Order>,
+}
+
+/// FNV-1a哈希，结果不依赖进程和平台
+pub(crate) struct StateHasher(u64);
+
+impl StateHasher {
+    pub(crate) fn new() -> StateHasher {
+        StateHasher(0xcbf2_9ce4_8422_2325)
+    }
+
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    pub(crate) fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    pub(crate) fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// 市场完整转储，包含全部挂单，用于故障排查
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct BookDump {
+    pub symbol: String,
+    pub version: u64,
+    /// 最新成交价
+    pub px: BigDecimal,
+    /// 最新成交时间
+    pub ts: u128,
+    /// 买方挂单，按撮合优先级排列
+    pub bids: Vec<Order>,
+    /// 卖方挂单，按撮合优先级排列
+    pub asks: Vec<Order>,
+}
+
+impl BookSnapshot {
+    /// 空快照，用于交易员尚未发布快照时
+    pub fn empty(symbol: &str) -> BookSnapshot {
+        BookSnapshot {
+            symbol: String::from(symbol),
+            version: 0,
+            px: BigDecimal::from(0),
+            px_ts: 0,
+            ts: 0,
+            bids: Vec::new(),
+            asks: Vec::new(),
+            indicative: None,
+            stats: BookStats::default(),
+        }
+    }
+
+    /// 只保留前limit档
+    pub fn truncate(&mut self, limit: usize) {
+        self.bids.truncate(limit);
+        self.asks.truncate(limit);
+    }
+
+    /// 前levels档的买卖数量失衡，(买量 - 卖量) / (买量 + 卖量)，取值[-1, 1]，两侧都没有挂单时为空
+    pub fn imbalance(&self, levels: usize) -> Option<BigDecimal> {
+        let bid: u64 = self.bids.iter().take(levels).map(|l| l.qty).sum();
+        let ask: u64 = self.asks.iter().take(levels).map(|l| l.qty).sum();
+        if bid + ask == 0 {
+            return None;
+        }
+        let imbalance = (BigDecimal::from(bid) - BigDecimal::from(ask)) / BigDecimal::from(bid + ask);
+        Some(imbalance.round(4))
+    }
+
+    /// 模拟side方向数量为qty的订单吃掉对手方挂单，px为限价，最多吃depth档，不修改快照
+    pub fn preview(&self, side: TradeSide, qty: u64, px: Option<&BigDecimal>, depth: usize) -> MatchPreview {
+        let opposite = match side {
+            TradeSide::BUY => &self.asks,
+            TradeSide::SELL => &self.bids,
+        };
+        let crosses = |level: &&LevelSnapshot| match (px, side) {
+            (None, _) => true,
+            (Some(px), TradeSide::BUY) => level.px <= *px,
+            (Some(px), TradeSide::SELL) => level.px >= *px,
+        };
+        let mut remain = qty;
+        let mut notional = BigDecimal::from(0);
+        let mut fills = Vec::new();
+        for level in opposite.iter().take(depth).take_while(crosses) {
+            if remain == 0 {
+                break;
+            }
+            let filled = remain.min(level.qty);
+            remain -= filled;
+            notional += &level.px * BigDecimal::from(filled);
+            fills.push(PreviewFill { px: level.px.clone(), qty: filled });
+        }
+        let filled = qty - remain;
+        let best_px = opposite.first().map(|level| level.px.clone());
+        let avg_px = (filled > 0).then(|| &notional / BigDecimal::from(filled));
+        let slippage_bps = match (&avg_px, &best_px) {
+            (Some(avg), Some(best)) if *best > BigDecimal::from(0) => {
+                let diff = match side {
+                    TradeSide::BUY => avg - best,
+                    TradeSide::SELL => best - avg,
+                };
+                Some((diff * BigDecimal::from(10_000) / best).round(2))
+            }
+            _ => None,
+        };
+        MatchPreview {
+            symbol: self.symbol.clone(),
+            version: self.version,
+            side,
+            qty,
+            fills,
+            filled,
+            remain,
+            notional,
+            avg_px: avg_px.map(|px| px.round(8)),
+            best_px,
+            slippage_bps,
+        }
+    }
+
+    pub(crate) fn levels(book: &OrderBook, decimals: u32) -> Vec<LevelSnapshot> {
+        book.iter_levels().map(|level| LevelSnapshot::new(level, decimals)).collect()
+    }
+}
+
+impl LevelSnapshot {
+    fn new(level: &Level, decimals: u32) -> LevelSnapshot {
+        LevelSnapshot {
+            px: level.price().to_decimal(decimals),
+            qty: level.total_qty(),
+            orders: level.size(),
+        }
+    }
+}
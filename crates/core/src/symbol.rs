@@ -0,0 +1,488 @@
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::sync::{Arc, LazyLock, RwLock};
+
+use anyhow::anyhow;
+use bigdecimal::{BigDecimal, Zero};
+use serde::{Deserialize, Serialize};
+
+use crate::price::Price;
+
+/// 默认价格精度
+pub const DEFAULT_PRICE_DECIMALS: u32 = 8;
+
+fn default_price_decimals() -> u32 {
+    DEFAULT_PRICE_DECIMALS
+}
+
+/// 撮合算法
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub enum MatchAlgorithm {
+    /// 价格优先，同价格先到先得
+    #[default]
+    PriceTime,
+}
+
+/// 只挂单订单会与对手方成交时的处理策略
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub enum CrossPolicy {
+    /// 拒绝订单
+    #[default]
+    Reject,
+    /// 改价到对手方最优价之外一个价格步长
+    Reprice,
+    /// 改价到本方最优价排队，本方没有挂单时按Reprice处理
+    Slide,
+}
+
+/// 手续费率，按成交金额计算，负值为返佣
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct FeeSchedule {
+    pub maker: BigDecimal,
+    pub taker: BigDecimal,
+}
+
+/// 交易对规格
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SymbolSpec {
+    /// 交易对
+    pub symbol: String,
+    /// 价格精度，小数位数
+    #[serde(default = "default_price_decimals")]
+    pub price_decimals: u32,
+    /// 最小价格变动，限价单价格须为其整数倍
+    #[serde(default)]
+    pub tick_size: Option<BigDecimal>,
+    /// 最小数量变动，订单数量须为其整数倍
+    #[serde(default)]
+    pub lot_size: Option<u64>,
+    /// 手续费率
+    #[serde(default)]
+    pub fees: FeeSchedule,
+    /// 限价偏离最新成交价的最大百分比
+    #[serde(default)]
+    pub price_band_pct: Option<BigDecimal>,
+    /// 撮合算法
+    #[serde(default)]
+    pub algorithm: MatchAlgorithm,
+    /// 只挂单订单会与对手方成交时的处理策略
+    #[serde(default)]
+    pub cross_policy: CrossPolicy,
+    /// 每秒最多接收的新订单数量，超过后引擎拒绝新订单，避免单个热门交易对挤占共享运行时
+    #[serde(default)]
+    pub max_orders_per_sec: Option<u32>,
+}
+
+/// 交易对规格的运行时修改，未设置的项保持不变，价格精度不可修改
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SymbolPatch {
+    pub tick_size: Option<BigDecimal>,
+    pub lot_size: Option<u64>,
+    pub fees: Option<FeeSchedule>,
+    pub price_band_pct: Option<BigDecimal>,
+    pub algorithm: Option<MatchAlgorithm>,
+    pub cross_policy: Option<CrossPolicy>,
+    pub max_orders_per_sec: Option<u32>,
+}
+
+impl SymbolSpec {
+    pub fn new(symbol: &str) -> SymbolSpec {
+        SymbolSpec {
+            symbol: String::from(symbol),
+            price_decimals: DEFAULT_PRICE_DECIMALS,
+            tick_size: None,
+            lot_size: None,
+            fees: FeeSchedule::default(),
+            price_band_pct: None,
+            algorithm: MatchAlgorithm::default(),
+            cross_policy: CrossPolicy::default(),
+            max_orders_per_sec: None,
+        }
+    }
+
+    pub fn with_price_decimals(mut self, price_decimals: u32) -> SymbolSpec {
+        self.price_decimals = price_decimals;
+        self
+    }
+
+    pub fn with_tick_size(mut self, tick_size: BigDecimal) -> SymbolSpec {
+        self.tick_size = Some(tick_size);
+        self
+    }
+
+    pub fn with_lot_size(mut self, lot_size: u64) -> SymbolSpec {
+        self.lot_size = Some(lot_size);
+        self
+    }
+
+    pub fn with_fees(mut self, fees: FeeSchedule) -> SymbolSpec {
+        self.fees = fees;
+        self
+    }
+
+    pub fn with_price_band_pct(mut self, price_band_pct: BigDecimal) -> SymbolSpec {
+        self.price_band_pct = Some(price_band_pct);
+        self
+    }
+
+    pub fn with_cross_policy(mut self, cross_policy: CrossPolicy) -> SymbolSpec {
+        self.cross_policy = cross_policy;
+        self
+    }
+
+    pub fn with_max_orders_per_sec(mut self, max_orders_per_sec: u32) -> SymbolSpec {
+        self.max_orders_per_sec = Some(max_orders_per_sec);
+        self
+    }
+
+    /// 定点表示的最小价格变动
+    pub fn tick(&self) -> anyhow::Result<Option<Price>> {
+        self.tick_size.as_ref()
+            .map(|tick| Price::from_decimal(tick, self.price_decimals))
+            .transpose()
+    }
+
+    /// 检查规格是否有效
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.tick()?.is_some_and(|tick| tick.raw() <= 0) {
+            return Err(anyhow!("tick size must be positive, symbol={}", self.symbol));
+        }
+        if self.lot_size == Some(0) {
+            return Err(anyhow!("lot size must be positive, symbol={}", self.symbol));
+        }
+        if self.price_band_pct.as_ref().is_some_and(|pct| pct <= &BigDecimal::zero()) {
+            return Err(anyhow!("price band must be positive, symbol={}", self.symbol));
+        }
+        if self.max_orders_per_sec == Some(0) {
+            return Err(anyhow!("max orders per sec must be positive, symbol={}", self.symbol));
+        }
+        Ok(())
+    }
+
+    /// 应用运行时修改，返回修改后的规格
+    pub fn apply(&self, patch: &SymbolPatch) -> anyhow::Result<SymbolSpec> {
+        let mut spec = self.clone();
+        if let Some(tick_size) = &patch.tick_size {
+            spec.tick_size = Some(tick_size.clone());
+        }
+        if let Some(lot_size) = patch.lot_size {
+            spec.lot_size = Some(lot_size);
+        }
+        if let Some(fees) = &patch.fees {
+            spec.fees = fees.clone();
+        }
+        if let Some(pct) = &patch.price_band_pct {
+            spec.price_band_pct = Some(pct.clone());
+        }
+        if let Some(algorithm) = patch.algorithm {
+            spec.algorithm = algorithm;
+        }
+        if let Some(cross_policy) = patch.cross_policy {
+            spec.cross_policy = cross_policy;
+        }
+        if let Some(max_orders_per_sec) = patch.max_orders_per_sec {
+            spec.max_orders_per_sec = Some(max_orders_per_sec);
+        }
+        spec.validate()?;
+        Ok(spec)
+    }
+}
+
+/// 交易对编号，引擎内部代替交易对字符串使用
+#[derive(Eq, PartialEq, Ord, PartialOrd, Hash, Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct SymbolId(pub u32);
+
+impl SymbolId {
+    /// 在进程内的驻留表中注册交易对，返回其编号，已注册时返回原编号
+    pub fn intern(symbol: &str) -> SymbolId {
+        if let Some(id) = SymbolId::lookup(symbol) {
+            return id;
+        }
+        SYMBOLS.write().unwrap().intern(symbol)
+    }
+
+    /// 查找已注册交易对的编号
+    pub fn lookup(symbol: &str) -> Option<SymbolId> {
+        SYMBOLS.read().unwrap().get(symbol)
+    }
+}
+
+impl Display for SymbolId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// 交易对字符串驻留表，为每个交易对分配连续的编号
+#[derive(Debug, Clone, Default)]
+pub struct SymbolInterner {
+    ids: HashMap<Arc<str>, SymbolId>,
+    names: Vec<Arc<str>>,
+}
+
+impl SymbolInterner {
+    pub fn new() -> SymbolInterner {
+        SymbolInterner::default()
+    }
+
+    /// 获取交易对编号，不存在时分配新编号
+    pub fn intern(&mut self, symbol: &str) -> SymbolId {
+        if let Some(id) = self.ids.get(symbol) {
+            return *id;
+        }
+        let id = SymbolId(self.names.len() as u32);
+        let name: Arc<str> = Arc::from(symbol);
+        self.names.push(Arc::clone(&name));
+        self.ids.insert(name, id);
+        id
+    }
+
+    pub fn get(&self, symbol: &str) -> Option<SymbolId> {
+        self.ids.get(symbol).copied()
+    }
+
+    /// 由编号还原交易对字符串
+    pub fn name(&self, id: SymbolId) -> Option<&str> {
+        self.names.get(id.0 as usize).map(|s| s.as_ref())
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=(SymbolId, &str)> {
+        self.names.iter().enumerate().map(|(i, s)| (SymbolId(i as u32), s.as_ref()))
+    }
+
+    fn symbol(&self, name: &str) -> Option<Symbol> {
+        let id = self.get(name)?;
+        Some(Symbol { id: Some(id), name: Arc::clone(&self.names[id.0 as usize]) })
+    }
+}
+
+/// 进程内共享的交易对驻留表，只有注册的交易对分配编号，编号不回收
+static SYMBOLS: LazyLock<RwLock<SymbolInterner>> = LazyLock::new(Default::default);
+
+/// 订单和成交携带的交易对，已注册的交易对带编号并共享名称，复制时不分配内存，序列化为交易对字符串
+///
+/// 由字符串构造时只查找驻留表，不会为未知交易对分配编号，引擎注册交易对时调用[`Symbol::intern`]
+#[derive(Clone)]
+pub struct Symbol {
+    id: Option<SymbolId>,
+    name: Arc<str>,
+}
+
+impl Symbol {
+    /// 注册交易对，分配进程内唯一的编号
+    pub fn intern(name: &str) -> Symbol {
+        let id = SymbolId::intern(name);
+        Symbol { id: Some(id), name: Arc::clone(&SYMBOLS.read().unwrap().names[id.0 as usize]) }
+    }
+
+    /// 交易对编号，构造时尚未注册的交易对在注册后按名称查找
+    pub fn id(&self) -> Option<SymbolId> {
+        self.id.or_else(|| SymbolId::lookup(&self.name))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.name
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Symbol {
+        SYMBOLS.read().unwrap().symbol(name)
+            .unwrap_or_else(|| Symbol { id: None, name: Arc::from(name) })
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(name: String) -> Symbol {
+        Symbol::from(name.as_str())
+    }
+}
+
+impl From<&String> for Symbol {
+    fn from(name: &String) -> Symbol {
+        Symbol::from(name.as_str())
+    }
+}
+
+impl From<Symbol> for String {
+    fn from(symbol: Symbol) -> String {
+        symbol.name.to_string()
+    }
+}
+
+impl Default for Symbol {
+    fn default() -> Symbol {
+        Symbol::from("")
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.name
+    }
+}
+
+impl AsRef<str> for Symbol {
+    fn as_ref(&self) -> &str {
+        &self.name
+    }
+}
+
+impl Borrow<str> for Symbol {
+    fn borrow(&self) -> &str {
+        &self.name
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Symbol) -> bool {
+        match (self.id, other.id) {
+            (Some(a), Some(b)) => a == b,
+            _ => self.name == other.name,
+        }
+    }
+}
+
+impl Eq for Symbol {}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        &*self.name == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        &*self.name == *other
+    }
+}
+
+impl PartialEq<String> for Symbol {
+    fn eq(&self, other: &String) -> bool {
+        *self.name == **other
+    }
+}
+
+impl PartialEq<Symbol> for String {
+    fn eq(&self, other: &Symbol) -> bool {
+        **self == *other.name
+    }
+}
+
+impl PartialEq<Symbol> for &str {
+    fn eq(&self, other: &Symbol) -> bool {
+        **self == *other.name
+    }
+}
+
+/// 与`str`的哈希一致，可以用交易对字符串查找以`Symbol`为键的表
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state)
+    }
+}
+
+impl PartialOrd for Symbol {
+    fn partial_cmp(&self, other: &Symbol) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Symbol {
+    fn cmp(&self, other: &Symbol) -> Ordering {
+        self.name.cmp(&other.name)
+    }
+}
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+impl Debug for Symbol {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&*self.name, f)
+    }
+}
+
+impl Serialize for Symbol {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.name)
+    }
+}
+
+impl<'de> Deserialize<'de> for Symbol {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Symbol, D::Error> {
+        let name = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        Ok(Symbol::from(name.as_ref()))
+    }
+}
+
+#[cfg(test)]
+mod symbol_test {
+    use std::str::FromStr;
+
+    use bigdecimal::BigDecimal;
+
+    use crate::price::Price;
+    use crate::symbol::{Symbol, SymbolId, SymbolInterner, SymbolPatch, SymbolSpec};
+
+    #[test]
+    fn intern_test() {
+        let mut interner = SymbolInterner::new();
+        let a = interner.intern("LOOM-USDT-SPOT");
+        let b = interner.intern("BTC-USDT-SPOT");
+        assert_eq!(a, SymbolId(0));
+        assert_eq!(b, SymbolId(1));
+        assert_eq!(interner.intern("LOOM-USDT-SPOT"), a);
+        assert_eq!(interner.name(b), Some("BTC-USDT-SPOT"));
+        assert_eq!(interner.get("ETH-USDT-SPOT"), None);
+    }
+
+    #[test]
+    fn symbol_test() {
+        // 未注册的交易对不分配编号，注册后按名称查到编号
+        let unknown = Symbol::from("INTERN-TEST-SPOT");
+        assert_eq!(unknown.id(), None);
+        let symbol = Symbol::intern("INTERN-TEST-SPOT");
+        assert!(symbol.id().is_some());
+        assert_eq!(unknown.id(), symbol.id());
+        assert_eq!(unknown, symbol);
+        assert_eq!(Symbol::from(String::from("INTERN-TEST-SPOT")).id(), symbol.id());
+        assert_eq!(symbol, "INTERN-TEST-SPOT");
+        assert_eq!(serde_json::to_string(&symbol).unwrap(), "\"INTERN-TEST-SPOT\"");
+        let parsed: Symbol = serde_json::from_str("\"INTERN-TEST-SPOT\"").unwrap();
+        assert_eq!(parsed.id(), symbol.id());
+        assert_eq!(serde_json::from_str::<Symbol>("\"OTHER-TEST-SPOT\"").unwrap().id(), None);
+    }
+
+    #[test]
+    fn spec_patch_test() {
+        let spec: SymbolSpec = serde_json::from_str(r#"{"symbol":"LOOM-USDT-SPOT","price_decimals":2,"tick_size":"0.05"}"#).unwrap();
+        assert_eq!(spec.tick().unwrap(), Some(Price(5)));
+        assert_eq!(spec.lot_size, None);
+        let patch = SymbolPatch { lot_size: Some(10), ..SymbolPatch::default() };
+        let patched = spec.apply(&patch).unwrap();
+        assert_eq!((patched.lot_size, patched.tick_size.clone()), (Some(10), spec.tick_size.clone()));
+        // 步长精度超出价格精度
+        let patch = SymbolPatch { tick_size: Some(BigDecimal::from_str("0.001").unwrap()), ..SymbolPatch::default() };
+        assert!(spec.apply(&patch).is_err());
+        assert!(spec.apply(&SymbolPatch { lot_size: Some(0), ..SymbolPatch::default() }).is_err());
+        assert!(spec.apply(&SymbolPatch { max_orders_per_sec: Some(0), ..SymbolPatch::default() }).is_err());
+    }
+}
@@ -0,0 +1,25 @@
+use std::str::FromStr;
+
+use bigdecimal::BigDecimal;
+
+use crate::order::{Order, TradeSide};
+
+/// 测试默认使用的交易对
+pub const SYMBOL: &str = "LOOM-USDT-SPOT";
+
+/// 构造交易对为SYMBOL的限价单，价格为十进制字符串，其余字段取默认值，需要时用结构体更新语法覆盖
+pub fn new_order(id: u64, side: TradeSide, qty: u64, price: &str) -> Order {
+    Order {
+        id,
+        symbol: SYMBOL.into(),
+        side,
+        qty,
+        price: BigDecimal::from_str(price).unwrap(),
+        ..Default::default()
+    }
+}
+
+/// 构造指定下单账户的限价单
+pub fn account_order(id: u64, account: &str, side: TradeSide, qty: u64, price: &str) -> Order {
+    Order { account: Some(account.to_string()), ..new_order(id, side, qty, price) }
+}
@@ -0,0 +1,187 @@
+/// 每层槽位数量的位数
+const LEVEL_BITS: u32 = 6;
+/// 每层槽位数量
+const SLOTS: usize = 1 << LEVEL_BITS;
+/// 层数，6层以毫秒为刻度可覆盖约2年，更远的定时器放入溢出列表
+const LEVELS: usize = 6;
+
+#[derive(Debug, Copy, Clone)]
+struct Timer {
+    id: u64,
+    /// 到期刻度
+    at: u64,
+}
+
+/// 分层时间轮，刻度为1毫秒，插入和到期均摊为O(1)，无需扫描订单簿
+///
+/// 定时器不支持删除，到期的ID可能已不在订单簿中，由调用方忽略
+#[derive(Debug)]
+pub struct TimerWheel {
+    /// 当前刻度
+    now: u64,
+    /// 各层槽位，第n层每个槽位覆盖64^n个刻度
+    levels: Vec<Vec<Vec<Timer>>>,
+    /// 各层定时器数量，用于跳过空层
+    counts: [usize; LEVELS],
+    /// 超出最高层范围的定时器
+    overflow: Vec<Timer>,
+    /// 插入时已到期的定时器
+    due: Vec<Timer>,
+    len: usize,
+}
+
+impl TimerWheel {
+    pub fn new(now_ts: u128) -> TimerWheel {
+        TimerWheel {
+            now: now_ts as u64,
+            levels: (0..LEVELS).map(|_| (0..SLOTS).map(|_| Vec::new()).collect()).collect(),
+            counts: [0; LEVELS],
+            overflow: Vec::new(),
+            due: Vec::new(),
+            len: 0,
+        }
+    }
+
+    /// 插入在expire_ts毫秒到期的定时器
+    pub fn insert(&mut self, id: u64, expire_ts: u128) {
+        self.len += 1;
+        self.place(Timer { id, at: expire_ts as u64 });
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// 推进到now_ts毫秒，到期的定时器ID追加到expired中
+    pub fn advance(&mut self, now_ts: u128, expired: &mut Vec<u64>) {
+        let target = now_ts as u64;
+        self.fire_due(expired);
+        while self.now < target {
+            if self.len == 0 {
+                self.now = target;
+                break;
+            }
+            // 跳到下一个有定时器的层的边界
+            let level = self.counts.iter().position(|c| *c > 0).unwrap_or(LEVELS);
+            let granularity = 1u64 << (LEVEL_BITS * level as u32);
+            let next = (self.now / granularity + 1).saturating_mul(granularity);
+            if next > target {
+                self.now = target;
+                break;
+            }
+            self.now = next;
+            self.cascade();
+            self.fire_due(expired);
+            let slot = &mut self.levels[0][(self.now as usize) & (SLOTS - 1)];
+            if !slot.is_empty() {
+                self.counts[0] -= slot.len();
+                self.len -= slot.len();
+                expired.extend(slot.drain(..).map(|t| t.id));
+            }
+        }
+    }
+
+    /// 放入定时器所在的层和槽位，已到期的放入到期列表
+    fn place(&mut self, timer: Timer) {
+        if timer.at <= self.now {
+            self.due.push(timer);
+            return;
+        }
+        let diff = timer.at ^ self.now;
+        let level = ((63 - diff.leading_zeros()) / LEVEL_BITS) as usize;
+        if level >= LEVELS {
+            self.overflow.push(timer);
+            return;
+        }
+        let slot = ((timer.at >> (LEVEL_BITS * level as u32)) as usize) & (SLOTS - 1);
+        self.levels[level][slot].push(timer);
+        self.counts[level] += 1;
+    }
+
+    /// 当前刻度跨越高层边界时，将对应槽位的定时器重新放入低层
+    fn cascade(&mut self) {
+        let top = 1u64 << (LEVEL_BITS * LEVELS as u32);
+        if self.now.is_multiple_of(top) && !self.overflow.is_empty() {
+            for timer in std::mem::take(&mut self.overflow) {
+                self.place(timer);
+            }
+        }
+        for level in (1..LEVELS).rev() {
+            let shift = LEVEL_BITS * level as u32;
+            if !self.now.is_multiple_of(1u64 << shift) {
+                continue;
+            }
+            let slot = ((self.now >> shift) as usize) & (SLOTS - 1);
+            let timers = std::mem::take(&mut self.levels[level][slot]);
+            self.counts[level] -= timers.len();
+            for timer in timers {
+                self.place(timer);
+            }
+        }
+    }
+
+    fn fire_due(&mut self, expired: &mut Vec<u64>) {
+        self.len -= self.due.len();
+        expired.extend(self.due.drain(..).map(|t| t.id));
+    }
+}
+
+#[cfg(test)]
+mod timer_test {
+    use crate::timer::TimerWheel;
+
+    fn advance(wheel: &mut TimerWheel, now: u128) -> Vec<u64> {
+        let mut expired = Vec::new();
+        wheel.advance(now, &mut expired);
+        expired.sort();
+        expired
+    }
+
+    #[test]
+    fn expire_test() {
+        let mut wheel = TimerWheel::new(1000);
+        wheel.insert(1, 1010);
+        wheel.insert(2, 1010);
+        wheel.insert(3, 1000 + 5000);
+        wheel.insert(4, 1000 + 90 * 86_400_000);
+        wheel.insert(5, 900);
+        assert_eq!(advance(&mut wheel, 1009), vec![5]);
+        assert_eq!(advance(&mut wheel, 1010), vec![1, 2]);
+        assert_eq!(advance(&mut wheel, 5999), Vec::<u64>::new());
+        assert_eq!(advance(&mut wheel, 6000), vec![3]);
+        assert_eq!(wheel.len(), 1);
+        assert_eq!(advance(&mut wheel, 1000 + 90 * 86_400_000 - 1), Vec::<u64>::new());
+        assert_eq!(advance(&mut wheel, 1000 + 90 * 86_400_000), vec![4]);
+        assert!(wheel.is_empty());
+    }
+
+    #[test]
+    fn random_expire_test() {
+        // 与逐个比较到期时间的结果一致
+        let mut wheel = TimerWheel::new(0);
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut timers = Vec::new();
+        for id in 0..2000u64 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let at = (state % 5_000_000) as u128;
+            wheel.insert(id, at);
+            timers.push((id, at));
+        }
+        let mut now = 0;
+        while !wheel.is_empty() {
+            now += 37_123;
+            let (due, pending): (Vec<_>, Vec<_>) = timers.into_iter().partition(|(_, at)| *at <= now);
+            timers = pending;
+            let mut expected: Vec<u64> = due.into_iter().map(|(id, _)| id).collect();
+            expected.sort();
+            assert_eq!(advance(&mut wheel, now), expected);
+        }
+        assert!(timers.is_empty());
+    }
+}
@@ -1,4 +1,6 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
 /// 返回当前系统时间戳，mills
 pub fn now_ts() -> u128 {
@@ -6,4 +8,67 @@ pub fn now_ts() -> u128 {
         .duration_since(UNIX_EPOCH)
         .unwrap()
         .as_millis()
-}
\ No newline at end of file
+}
+
+/// 返回单调递增的纳秒时间戳，以进程启动时的系统时间为起点，不受系统时钟回拨影响，每次调用都严格大于上一次
+pub fn monotonic_ns() -> u64 {
+    static ANCHOR: OnceLock<(Instant, u64)> = OnceLock::new();
+    static LAST: AtomicU64 = AtomicU64::new(0);
+    let (instant, wall) = ANCHOR.get_or_init(|| {
+        let wall = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos() as u64;
+        (Instant::now(), wall)
+    });
+    let now = wall + instant.elapsed().as_nanos() as u64;
+    let mut last = LAST.load(Ordering::Relaxed);
+    loop {
+        let next = now.max(last + 1);
+        match LAST.compare_exchange_weak(last, next, Ordering::Relaxed, Ordering::Relaxed) {
+            Ok(_) => return next,
+            Err(actual) => last = actual,
+        }
+    }
+}
+/// 毫秒时间戳所在的UTC日期，格式为YYYY-MM-DD
+pub fn format_date(ts: u128) -> String {
+    let days = (ts / 86_400_000) as i64;
+    // 按公历400年周期换算
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::utils::{format_date, monotonic_ns, now_ts};
+
+    #[test]
+    fn format_date_test() {
+        assert_eq!(format_date(0), "1970-01-01");
+        assert_eq!(format_date(951_782_400_000), "2000-02-29");
+        assert_eq!(format_date(1_791_935_999_999), "2026-10-13");
+    }
+
+    #[test]
+    fn monotonic_ns_test() {
+        let handles: Vec<_> = (0..4).map(|_| std::thread::spawn(|| {
+            let values: Vec<u64> = (0..10_000).map(|_| monotonic_ns()).collect();
+            assert!(values.windows(2).all(|w| w[0] < w[1]));
+            values
+        })).collect();
+        let mut all: Vec<u64> = handles.into_iter().flat_map(|h| h.join().unwrap()).collect();
+        let len = all.len();
+        all.sort_unstable();
+        all.dedup();
+        assert_eq!(all.len(), len);
+        // 与系统时间的毫秒数一致
+        let ms = (monotonic_ns() / 1_000_000) as u128;
+        assert!(ms.abs_diff(now_ts()) < 1000);
+    }
+}
@@ -0,0 +1,287 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::{Display, Formatter};
+use std::time::{Duration, Instant};
+
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+use loom_core::utils;
+
+#[cfg(feature = "redis")]
+use crate::cache::CacheManager;
+use crate::engine::EngineHandle;
+use crate::http_client;
+
+/// 默认检查间隔
+pub const DEFAULT_ALERT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// 消费失败速率统计窗口
+const FAILURE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Redis探测超时时间
+#[cfg(feature = "redis")]
+const REDIS_PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// 告警类型
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum AlertKind {
+    /// 撮合请求队列积压
+    QueueDepth,
+    /// 每分钟成交推送失败次数
+    ConsumerFailures,
+    /// Redis响应延迟，毫秒
+    RedisLatency,
+    /// 交易员已停止
+    TraderHalted,
+    /// 缓存与订单簿不一致的订单数量
+    CacheDrift,
+}
+
+/// 告警
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Alert {
+    pub kind: AlertKind,
+    /// 交易对，非交易对告警为空
+    pub symbol: Option<String>,
+    /// 当前值
+    pub value: u64,
+    /// 阈值
+    pub threshold: u64,
+    pub ts: u128,
+}
+
+impl Display for Alert {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?} breached, symbol={}, value={}, threshold={}",
+               self.kind, self.symbol.as_deref().unwrap_or("-"), self.value, self.threshold)
+    }
+}
+
+/// 告警阈值，未配置的项不检查
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AlertThresholds {
+    /// 撮合请求队列积压数量
+    pub queue_depth: Option<usize>,
+    /// 每分钟成交推送失败次数
+    pub consumer_failures_per_min: Option<u64>,
+    /// Redis响应延迟，毫秒
+    pub redis_latency_ms: Option<u64>,
+    /// 交易员停止时告警
+    pub trader_halted: bool,
+}
+
+/// 告警输出
+#[derive(Debug, Clone)]
+pub enum AlertSink {
+    /// 输出到错误日志
+    Log,
+    /// 以JSON POST到指定地址
+    Webhook(String),
+    /// 发布到Redis频道
+    #[cfg(feature = "redis")]
+    Redis { cache: CacheManager, channel: String },
+}
+
+impl AlertSink {
+    pub async fn send(&self, alert: &Alert) -> anyhow::Result<()> {
+        match self {
+            AlertSink::Log => {
+                error!("ALERT: {}", alert);
+            }
+            AlertSink::Webhook(url) => {
+                let body = serde_json::to_vec(alert)?;
+                let resp = http_client::post(url, &[("Content-Type", "application/json")], &body).await?;
+                if !resp.is_success() {
+                    return Err(anyhow::anyhow!("alert webhook failed, status={}, body={}", resp.status, resp.body_text()));
+                }
+            }
+            #[cfg(feature = "redis")]
+            AlertSink::Redis { cache, channel } => {
+                cache.publish(channel, &serde_json::to_string(alert)?).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 一次检查采集的指标
+#[derive(Debug, Clone, Default)]
+pub struct AlertSample {
+    /// 各交易对队列积压
+    pub pending: Vec<(String, usize)>,
+    /// 各交易对累计推送失败次数
+    pub failures: Vec<(String, u64)>,
+    /// 各交易对交易员是否存活
+    pub liveness: Vec<(String, bool)>,
+    /// Redis响应延迟，探测失败时为超时时间
+    pub redis_latency: Option<Duration>,
+}
+
+/// 告警监视器，定时采集引擎指标并与阈值比较，越过阈值时触发一次告警，恢复后才会再次触发
+pub struct AlertMonitor {
+    thresholds: AlertThresholds,
+    sinks: Vec<AlertSink>,
+    interval: Duration,
+    /// 处于告警状态的项
+    active: HashSet<(AlertKind, Option<String>)>,
+    /// 各交易对累计失败次数采样
+    failure_samples: HashMap<String, VecDeque<(Instant, u64)>>,
+}
+
+impl AlertMonitor {
+    pub fn new(thresholds: AlertThresholds, sinks: Vec<AlertSink>) -> AlertMonitor {
+        AlertMonitor {
+            thresholds,
+            sinks,
+            interval: DEFAULT_ALERT_INTERVAL,
+            active: HashSet::new(),
+            failure_samples: HashMap::new(),
+        }
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> AlertMonitor {
+        self.interval = interval;
+        self
+    }
+
+    /// 启动监视协程，收到退出信号后停止
+    pub fn launch(mut self, engine: EngineHandle, mut ctx: broadcast::Receiver<bool>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.interval);
+            loop {
+                tokio::select! {
+                    Ok(true) = ctx.recv() => break,
+                    _ = ticker.tick() => {
+                        let sample = self.collect(&engine).await;
+                        for alert in self.evaluate(sample, Instant::now()) {
+                            self.fire(&alert).await;
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    /// 未配置缓存时为空
+    #[cfg(feature = "redis")]
+    async fn redis_latency(engine: &EngineHandle) -> Option<Duration> {
+        let cache = engine.cache_manager()?;
+        let started = Instant::now();
+        match tokio::time::timeout(REDIS_PROBE_TIMEOUT, cache.ping()).await {
+            Ok(Ok(_)) => Some(started.elapsed()),
+            _ => Some(REDIS_PROBE_TIMEOUT),
+        }
+    }
+
+    #[cfg(not(feature = "redis"))]
+    async fn redis_latency(_engine: &EngineHandle) -> Option<Duration> {
+        None
+    }
+
+    async fn collect(&self, engine: &EngineHandle) -> AlertSample {
+        let pending = engine.symbols()
+            .into_iter()
+            .filter_map(|symbol| engine.sender(&symbol).map(|s| (symbol, s.pending())))
+            .collect();
+        let redis_latency = match self.thresholds.redis_latency_ms {
+            Some(_) => Self::redis_latency(engine).await,
+            None => None,
+        };
+        AlertSample {
+            pending,
+            failures: engine.consumer_failures(),
+            liveness: engine.trader_liveness(),
+            redis_latency,
+        }
+    }
+
+    /// 比较指标与阈值，返回新进入告警状态的项
+    pub fn evaluate(&mut self, sample: AlertSample, now: Instant) -> Vec<Alert> {
+        let mut breaches = Vec::new();
+        if let Some(threshold) = self.thresholds.queue_depth {
+            for (symbol, pending) in &sample.pending {
+                self.check(&mut breaches, AlertKind::QueueDepth, Some(symbol), *pending as u64, threshold as u64);
+            }
+        }
+        if let Some(threshold) = self.thresholds.consumer_failures_per_min {
+            for (symbol, failures) in &sample.failures {
+                let samples = self.failure_samples.entry(symbol.clone()).or_default();
+                samples.push_back((now, *failures));
+                while samples.front().is_some_and(|(ts, _)| now.duration_since(*ts) > FAILURE_WINDOW) {
+                    samples.pop_front();
+                }
+                let rate = failures - samples.front().map(|(_, c)| *c).unwrap_or(*failures);
+                self.check(&mut breaches, AlertKind::ConsumerFailures, Some(symbol), rate, threshold);
+            }
+        }
+        if let (Some(threshold), Some(latency)) = (self.thresholds.redis_latency_ms, sample.redis_latency) {
+            self.check(&mut breaches, AlertKind::RedisLatency, None, latency.as_millis() as u64, threshold);
+        }
+        if self.thresholds.trader_halted {
+            for (symbol, alive) in &sample.liveness {
+                self.check(&mut breaches, AlertKind::TraderHalted, Some(symbol), !alive as u64, 0);
+            }
+        }
+        breaches
+    }
+
+    fn check(&mut self, breaches: &mut Vec<Alert>, kind: AlertKind, symbol: Option<&String>, value: u64, threshold: u64) {
+        let key = (kind, symbol.cloned());
+        if value > threshold {
+            if self.active.insert(key) {
+                breaches.push(Alert { kind, symbol: symbol.cloned(), value, threshold, ts: utils::now_ts() });
+            }
+        } else if self.active.remove(&key) {
+            info!("ALERT RESOLVED: {:?}, symbol={}, value={}", kind, symbol.map(|s| s.as_str()).unwrap_or("-"), value);
+        }
+    }
+
+    async fn fire(&self, alert: &Alert) {
+        for sink in &self.sinks {
+            if let Err(e) = sink.send(alert).await {
+                warn!("SEND ALERT FAILED: alert={}, err={}", alert, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use crate::alert::{AlertKind, AlertMonitor, AlertSample, AlertThresholds};
+
+    #[test]
+    fn evaluate_test() {
+        let thresholds = AlertThresholds {
+            queue_depth: Some(10),
+            consumer_failures_per_min: Some(2),
+            redis_latency_ms: Some(100),
+            trader_halted: true,
+        };
+        let mut monitor = AlertMonitor::new(thresholds, Vec::new());
+        let symbol = String::from("LOOM-USDT-SPOT");
+        let sample = |pending: usize, failures: u64, alive: bool, latency: u64| AlertSample {
+            pending: vec![(symbol.clone(), pending)],
+            failures: vec![(symbol.clone(), failures)],
+            liveness: vec![(symbol.clone(), alive)],
+            redis_latency: Some(Duration::from_millis(latency)),
+        };
+        let start = Instant::now();
+        assert!(monitor.evaluate(sample(1, 0, true, 1), start).is_empty());
+        let kinds: Vec<AlertKind> = monitor.evaluate(sample(11, 3, false, 200), start + Duration::from_secs(5))
+            .iter()
+            .map(|a| a.kind)
+            .collect();
+        assert_eq!(kinds, vec![AlertKind::QueueDepth, AlertKind::ConsumerFailures, AlertKind::RedisLatency, AlertKind::TraderHalted]);
+        // 持续越过阈值不重复告警
+        assert!(monitor.evaluate(sample(12, 3, false, 200), start + Duration::from_secs(10)).is_empty());
+        // 恢复后再次越过阈值重新告警，失败次数超出统计窗口后不再计入
+        assert!(monitor.evaluate(sample(1, 3, true, 1), start + Duration::from_secs(80)).is_empty());
+        let alerts = monitor.evaluate(sample(11, 3, true, 1), start + Duration::from_secs(85));
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].kind, AlertKind::QueueDepth);
+    }
+}
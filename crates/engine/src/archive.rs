@@ -0,0 +1,268 @@
+use std::fmt::{Display, Formatter};
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::anyhow;
+
+use loom_core::market::MatchTrade;
+use loom_core::utils;
+
+/// 一天的毫秒数，归档文件按UTC日期切分
+const DAY_MS: u128 = 86_400_000;
+
+/// 归档文件名能表示的最晚时间，9999-12-31T23:59:59.999Z
+const MAX_ARCHIVE_TS: u128 = 253_402_300_799_999;
+
+/// 账户成交分页的最大条数
+pub const MAX_PAGE_LIMIT: usize = 1000;
+
+/// 账户成交分页位置，上一页最后一笔成交的时间和成交ID
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+pub struct TradeCursor {
+    pub ts: u128,
+    pub id: u64,
+}
+
+impl Display for TradeCursor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.ts, self.id)
+    }
+}
+
+impl FromStr for TradeCursor {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (ts, id) = s.split_once('-').ok_or_else(|| anyhow!("invalid cursor {}", s))?;
+        Ok(TradeCursor { ts: ts.parse()?, id: id.parse()? })
+    }
+}
+
+/// 账户成交的一页，还有更多成交时携带下一页的位置
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TradePage {
+    pub trades: Vec<MatchTrade>,
+    pub next: Option<TradeCursor>,
+}
+
+/// 成交归档，每个交易对每天一个JSON行文件，只保存成交，作为对账和报表的数据源
+///
+/// 同时按账户建立索引，每个交易对的每个账户每天一个文件，用于分页查询账户的成交记录
+#[derive(Debug, Clone)]
+pub struct TradeArchive {
+    dir: PathBuf,
+}
+
+impl TradeArchive {
+    pub fn new(dir: &Path) -> TradeArchive {
+        TradeArchive { dir: dir.to_path_buf() }
+    }
+
+    fn file(&self, symbol: &str, ts: u128) -> PathBuf {
+        self.dir.join(symbol).join(format!("{}.jsonl", utils::format_date(ts)))
+    }
+
+    fn account_dir(&self, symbol: &str, account: &str) -> PathBuf {
+        self.dir.join(symbol).join("accounts").join(encode_account(account))
+    }
+
+    fn account_file(&self, symbol: &str, account: &str, ts: u128) -> PathBuf {
+        self.account_dir(symbol, account).join(format!("{}.jsonl", utils::format_date(ts)))
+    }
+
+    /// 追加成交，撤单、减量和拒绝结果不归档
+    pub fn append(&self, trades: &[MatchTrade]) -> anyhow::Result<()> {
+        let mut files: Vec<(PathBuf, Vec<u8>)> = Vec::new();
+        for trade in trades.iter().filter(|t| t.qty != 0) {
+            let line = serde_json::to_vec(trade)?;
+            let mut paths = vec![self.file(&trade.symbol, trade.ts)];
+            // 自成交只写入一次账户索引
+            for account in [&trade.taker_account, &trade.maker_account].into_iter().flatten() {
+                let path = self.account_file(&trade.symbol, account, trade.ts);
+                if !paths.contains(&path) {
+                    paths.push(path);
+                }
+            }
+            for path in paths {
+                let buf = match files.iter_mut().find(|(p, _)| *p == path) {
+                    Some((_, buf)) => buf,
+                    None => {
+                        files.push((path, Vec::new()));
+                        &mut files.last_mut().unwrap().1
+                    }
+                };
+                buf.extend_from_slice(&line);
+                buf.push(b'\n');
+            }
+        }
+        for (path, buf) in files {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            OpenOptions::new().create(true).append(true).open(&path)?.write_all(&buf)?;
+        }
+        Ok(())
+    }
+
+    /// 读取交易对在[from, to)时间范围内的成交，按归档顺序返回
+    pub fn read(&self, symbol: &str, from: u128, to: u128) -> anyhow::Result<Vec<MatchTrade>> {
+        let mut trades = Vec::new();
+        let mut day = from - from % DAY_MS;
+        while day < to {
+            let path = self.file(symbol, day);
+            day += DAY_MS;
+            trades.extend(read_file(&path)?.into_iter().filter(|t| t.ts >= from && t.ts < to));
+        }
+        Ok(trades)
+    }
+
+    /// 按时间和成交ID升序分页读取账户在[from, to)时间范围内的成交，after为上一页返回的位置
+    pub fn account_trades(&self, symbol: &str, account: &str, from: u128, to: u128, after: Option<TradeCursor>, limit: usize) -> anyhow::Result<TradePage> {
+        let limit = limit.clamp(1, MAX_PAGE_LIMIT);
+        let start = after.map(|c| c.ts.max(from)).unwrap_or(from);
+        if start >= to {
+            return Ok(TradePage { trades: Vec::new(), next: None });
+        }
+        // 只读取账户索引中存在的日期文件，文件名按日期排序
+        let (first, last) = (format!("{}.jsonl", utils::format_date(start)), format!("{}.jsonl", utils::format_date((to - 1).min(MAX_ARCHIVE_TS))));
+        let dir = self.account_dir(symbol, account);
+        let mut days = match fs::read_dir(&dir) {
+            Ok(entries) => entries.map(|e| e.map(|e| e.file_name().to_string_lossy().to_string())).collect::<Result<Vec<_>, _>>()?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(anyhow!("open archive failed, path={}, err={}", dir.display(), e)),
+        };
+        days.retain(|d| d.ends_with(".jsonl") && *d >= first && *d <= last);
+        days.sort();
+        let mut trades = Vec::new();
+        for day in days {
+            if trades.len() > limit {
+                break;
+            }
+            let mut page: Vec<MatchTrade> = read_file(&dir.join(day))?.into_iter()
+                .filter(|t| t.ts >= from && t.ts < to)
+                .filter(|t| after.is_none_or(|c| (t.ts, t.id) > (c.ts, c.id)))
+                .collect();
+            page.sort_by_key(|t| (t.ts, t.id));
+            trades.extend(page);
+        }
+        let next = if trades.len() > limit {
+            trades.truncate(limit);
+            trades.last().map(|t| TradeCursor { ts: t.ts, id: t.id })
+        } else {
+            None
+        };
+        Ok(TradePage { trades, next })
+    }
+
+    /// 已归档的交易对，按名称排序
+    pub fn symbols(&self) -> anyhow::Result<Vec<String>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut symbols = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                symbols.push(entry.file_name().to_string_lossy().to_string());
+            }
+        }
+        symbols.sort();
+        Ok(symbols)
+    }
+}
+
+/// 读取归档文件，文件不存在时返回空
+fn read_file(path: &Path) -> anyhow::Result<Vec<MatchTrade>> {
+    let file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(anyhow!("open archive failed, path={}, err={}", path.display(), e)),
+    };
+    let mut trades = Vec::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let trade: MatchTrade = serde_json::from_str(&line)
+            .map_err(|e| anyhow!("invalid archive line, path={}, line={}, err={}", path.display(), i + 1, e))?;
+        trades.push(trade);
+    }
+    Ok(trades)
+}
+
+/// 账户名作为目录名，字母、数字、下划线和连字符以外的字节按%XX转义
+fn encode_account(account: &str) -> String {
+    let mut encoded = String::with_capacity(account.len());
+    for b in account.bytes() {
+        if b.is_ascii_alphanumeric() || b == b'_' || b == b'-' {
+            encoded.push(b as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", b));
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod test {
+    use loom_core::market::{MarketBook, MatchTrades};
+    use loom_core::order::{Order, TradeSide};
+    use loom_core::testing::new_order;
+
+    use crate::archive::{encode_account, TradeArchive, TradeCursor};
+
+    #[test]
+    fn archive_test() {
+        let dir = std::env::temp_dir().join(format!("loom-archive-test-{}", std::process::id()));
+        let archive = TradeArchive::new(&dir);
+        let mut book = MarketBook::new("LOOM-USDT-SPOT");
+        let mut trades = MatchTrades::new();
+        book.try_match_into(new_order(1, TradeSide::SELL, 1, "100"), &mut trades);
+        book.try_match_into(new_order(2, TradeSide::BUY, 1, "100"), &mut trades);
+        let mut trades: Vec<_> = trades.into_iter().collect();
+        let mut next_day = trades[0].clone();
+        next_day.ts += 86_400_000;
+        trades.push(next_day);
+        archive.append(&trades).unwrap();
+        assert_eq!(archive.symbols().unwrap(), vec!["LOOM-USDT-SPOT"]);
+        let ts = trades[0].ts;
+        assert_eq!(archive.read("LOOM-USDT-SPOT", ts, ts + 1).unwrap(), trades[..1].to_vec());
+        assert_eq!(archive.read("LOOM-USDT-SPOT", 0, ts + 2 * 86_400_000).unwrap().len(), 2);
+        assert!(archive.read("BTC-USDT-SPOT", 0, ts + 1).unwrap().is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn account_trades_test() {
+        let dir = std::env::temp_dir().join(format!("loom-account-trades-test-{}", std::process::id()));
+        let archive = TradeArchive::new(&dir);
+        let mut book = MarketBook::new("LOOM-USDT-SPOT");
+        let mut trades = MatchTrades::new();
+        book.try_match_into(Order { qty: 5, account: Some(String::from("maker")), ..new_order(1, TradeSide::SELL, 1, "100") }, &mut trades);
+        for id in 2..=6 {
+            let account = if id % 2 == 0 { "acme.alice" } else { "bob" };
+            book.try_match_into(Order { account: Some(account.to_string()), ..new_order(id, TradeSide::BUY, 1, "100") }, &mut trades);
+        }
+        archive.append(&trades).unwrap();
+        // 第一页两条，之后从游标继续
+        let page = archive.account_trades("LOOM-USDT-SPOT", "maker", 0, u128::MAX, None, 2).unwrap();
+        assert_eq!(page.trades.iter().map(|t| t.taker_oid).collect::<Vec<_>>(), vec![2, 3]);
+        let cursor: TradeCursor = page.next.unwrap().to_string().parse().unwrap();
+        let page = archive.account_trades("LOOM-USDT-SPOT", "maker", 0, u128::MAX, Some(cursor), 2).unwrap();
+        assert_eq!(page.trades.iter().map(|t| t.taker_oid).collect::<Vec<_>>(), vec![4, 5]);
+        let page = archive.account_trades("LOOM-USDT-SPOT", "maker", 0, u128::MAX, page.next, 2).unwrap();
+        assert_eq!((page.trades.len(), page.next), (1, None));
+        // 只返回该账户参与的成交
+        let page = archive.account_trades("LOOM-USDT-SPOT", "acme.alice", 0, u128::MAX, None, 10).unwrap();
+        assert_eq!(page.trades.iter().map(|t| t.taker_oid).collect::<Vec<_>>(), vec![2, 4, 6]);
+        assert!(archive.account_trades("LOOM-USDT-SPOT", "carol", 0, u128::MAX, None, 10).unwrap().trades.is_empty());
+        assert_eq!(encode_account("../acme.alice"), "%2E%2E%2Facme%2Ealice");
+        assert!("1-".parse::<TradeCursor>().is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
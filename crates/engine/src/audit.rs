@@ -0,0 +1,286 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail};
+use bigdecimal::BigDecimal;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use loom_core::market::MatchTrade;
+use loom_core::order::Order;
+use loom_core::utils;
+
+/// 账本审计日志的名称，与交易对的审计日志放在同一目录
+pub const LEDGER_AUDIT: &str = "LEDGER";
+
+/// 第一条记录的前一哈希
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// 审计事件
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub enum AuditEvent {
+    /// 交易员收到的撮合请求
+    Order(Order),
+    /// 撮合结果，包括撤单、减量和拒绝
+    Trade(MatchTrade),
+    /// 管理接口的余额调整，写入账本的审计日志
+    Adjust(BalanceAdjustment),
+}
+
+/// 余额调整，available为调整后的可用余额
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct BalanceAdjustment {
+    pub account: String,
+    pub asset: String,
+    pub amount: BigDecimal,
+    pub available: BigDecimal,
+}
+
+/// 审计记录，hash为去掉hash字段后的记录按键排序序列化的SHA-256，记录中包含前一条的哈希
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// 记录序号，从1开始连续递增
+    pub seq: u64,
+    /// 写入时间
+    pub ts: u128,
+    pub event: AuditEvent,
+    /// 前一条记录的哈希
+    pub prev: String,
+    pub hash: String,
+}
+
+/// 审计日志校验结果
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct AuditSummary {
+    /// 记录数量
+    pub records: u64,
+    /// 最后一条记录的哈希
+    pub hash: String,
+}
+
+/// 交易对的审计日志，只追加的JSON行文件，每条记录通过哈希链接前一条记录，修改、删除或插入记录都会被校验发现
+#[derive(Debug)]
+pub struct AuditLog {
+    file: File,
+    seq: u64,
+    prev: String,
+}
+
+impl AuditLog {
+    /// 审计日志文件
+    pub fn path(dir: &Path, symbol: &str) -> PathBuf {
+        dir.join(format!("{}.audit.jsonl", symbol))
+    }
+
+    /// 打开交易对的审计日志，从最后一条记录继续哈希链
+    pub fn open(dir: &Path, symbol: &str) -> anyhow::Result<AuditLog> {
+        fs::create_dir_all(dir)?;
+        let path = Self::path(dir, symbol);
+        let (mut seq, mut prev) = (0, String::from(GENESIS_HASH));
+        if path.exists() {
+            let last = BufReader::new(File::open(&path)?).lines()
+                .map_while(Result::ok)
+                .filter(|line| !line.trim().is_empty())
+                .last();
+            if let Some(line) = last {
+                let record: AuditRecord = serde_json::from_str(&line)
+                    .map_err(|e| anyhow!("invalid audit tail, path={}, err={}", path.display(), e))?;
+                (seq, prev) = (record.seq, record.hash);
+            }
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(AuditLog { file, seq, prev })
+    }
+
+    /// 最后一条记录的序号
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    pub fn append_order(&mut self, order: &Order) -> anyhow::Result<()> {
+        self.append(std::iter::once(AuditEvent::Order(order.clone())))
+    }
+
+    pub fn append_trades(&mut self, trades: &[MatchTrade]) -> anyhow::Result<()> {
+        self.append(trades.iter().cloned().map(AuditEvent::Trade))
+    }
+
+    pub fn append_adjustment(&mut self, adjustment: &BalanceAdjustment) -> anyhow::Result<()> {
+        self.append(std::iter::once(AuditEvent::Adjust(adjustment.clone())))
+    }
+
+    /// 一批事件一次写入，写入失败时哈希链不前进
+    fn append(&mut self, events: impl Iterator<Item=AuditEvent>) -> anyhow::Result<()> {
+        let (mut seq, mut prev) = (self.seq, self.prev.clone());
+        let mut buf = Vec::new();
+        let ts = utils::now_ts();
+        for event in events {
+            seq += 1;
+            let mut record = AuditRecord { seq, ts, event, prev, hash: String::new() };
+            record.hash = record_hash(serde_json::to_value(&record)?)?;
+            serde_json::to_writer(&mut buf, &record)?;
+            buf.push(b'\n');
+            prev = record.hash;
+        }
+        if buf.is_empty() {
+            return Ok(());
+        }
+        self.file.write_all(&buf)?;
+        (self.seq, self.prev) = (seq, prev);
+        Ok(())
+    }
+}
+
+/// 去掉hash字段后按键排序序列化计算哈希，与字段顺序和数值格式无关
+fn record_hash(mut record: Value) -> anyhow::Result<String> {
+    let object = record.as_object_mut().ok_or_else(|| anyhow!("audit record is not an object"))?;
+    object.remove("hash");
+    Ok(hex(&sha256(&serde_json::to_vec(&record)?)))
+}
+
+/// 校验审计日志的哈希链，返回第一处不一致的行号
+pub fn verify(path: &Path) -> anyhow::Result<AuditSummary> {
+    let file = File::open(path).map_err(|e| anyhow!("open audit log failed, path={}, err={}", path.display(), e))?;
+    let mut summary = AuditSummary { records: 0, hash: String::from(GENESIS_HASH) };
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let value: Value = serde_json::from_str(&line)
+            .map_err(|e| anyhow!("invalid audit record, line={}, err={}", i + 1, e))?;
+        let field = |name: &str| value.get(name).cloned().unwrap_or(Value::Null);
+        if field("seq").as_u64() != Some(summary.records + 1) {
+            bail!("audit sequence broken, line={}, expected={}, actual={}", i + 1, summary.records + 1, field("seq"));
+        }
+        if field("prev").as_str() != Some(&summary.hash) {
+            bail!("audit chain broken, line={}, prev={}, expected={}", i + 1, field("prev"), summary.hash);
+        }
+        let expected = record_hash(value.clone())?;
+        if field("hash").as_str() != Some(&expected) {
+            bail!("audit record tampered, line={}, hash={}, expected={}", i + 1, field("hash"), expected);
+        }
+        summary.records += 1;
+        summary.hash = expected;
+    }
+    Ok(summary)
+}
+
+/// 读取审计日志中的记录
+pub fn read(path: &Path) -> anyhow::Result<Vec<AuditRecord>> {
+    let mut records = Vec::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            records.push(serde_json::from_str(&line)?);
+        }
+    }
+    Ok(records)
+}
+
+/// 小写十六进制编码
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// SHA-256，审计日志只需要对整条记录计算摘要，不引入额外依赖
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut h: [u32; 8] = [0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+    for chunk in message.chunks(64) {
+        let mut w = [0u32; 64];
+        for (i, word) in chunk.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = h;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = hh.wrapping_add(s1).wrapping_add(ch).wrapping_add(K[i]).wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            (hh, g, f, e, d, c, b, a) = (g, f, e, d.wrapping_add(t1), c, b, a, t1.wrapping_add(t2));
+        }
+        for (x, y) in h.iter_mut().zip([a, b, c, d, e, f, g, hh]) {
+            *x = x.wrapping_add(y);
+        }
+    }
+    let mut digest = [0u8; 32];
+    for (i, x) in h.iter().enumerate() {
+        digest[i * 4..i * 4 + 4].copy_from_slice(&x.to_be_bytes());
+    }
+    digest
+}
+
+#[cfg(test)]
+mod test {
+    use loom_core::market::MarketBook;
+    use loom_core::order::TradeSide;
+    use loom_core::testing::account_order;
+
+    use crate::audit::{self, hex, sha256, AuditEvent, AuditLog, GENESIS_HASH};
+
+    #[test]
+    fn sha256_test() {
+        assert_eq!(hex(&sha256(b"")), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        assert_eq!(hex(&sha256(b"abc")), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        let long = b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq";
+        assert_eq!(hex(&sha256(long)), "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1");
+    }
+
+    #[test]
+    fn chain_test() {
+        let dir = std::env::temp_dir().join(format!("loom-audit-test-{}", std::process::id()));
+        let path = AuditLog::path(&dir, "LOOM-USDT-SPOT");
+        let mut book = MarketBook::new("LOOM-USDT-SPOT");
+        let mut audit = AuditLog::open(&dir, "LOOM-USDT-SPOT").unwrap();
+        for order in [account_order(1, "alice", TradeSide::SELL, 1, "100"), account_order(2, "alice", TradeSide::BUY, 1, "100")] {
+            audit.append_order(&order).unwrap();
+            audit.append_trades(&book.try_match(order)).unwrap();
+        }
+        // 重新打开后继续哈希链
+        let mut audit = AuditLog::open(&dir, "LOOM-USDT-SPOT").unwrap();
+        assert_eq!(audit.seq(), 3);
+        audit.append_order(&account_order(3, "alice", TradeSide::BUY, 1, "100")).unwrap();
+        let summary = audit::verify(&path).unwrap();
+        assert_eq!(summary.records, 4);
+        let records = audit::read(&path).unwrap();
+        assert_eq!(records[0].prev, GENESIS_HASH);
+        assert_eq!(records[3].hash, summary.hash);
+        assert!(matches!(&records[2].event, AuditEvent::Trade(t) if t.qty == 1));
+
+        // 修改成交数量
+        let content = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        let tampered = lines[2].replacen("\"qty\":1", "\"qty\":2", 1);
+        std::fs::write(&path, [lines[0], lines[1], &tampered, lines[3]].join("\n")).unwrap();
+        assert!(audit::verify(&path).unwrap_err().to_string().contains("tampered, line=3"));
+        // 删除记录
+        std::fs::write(&path, [lines[0], lines[2], lines[3]].join("\n")).unwrap();
+        assert!(audit::verify(&path).unwrap_err().to_string().contains("line=2"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
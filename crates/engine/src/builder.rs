@@ -0,0 +1,303 @@
+use tokio::sync::broadcast;
+
+use loom_core::symbol::SymbolSpec;
+
+#[cfg(feature = "redis")]
+use crate::cache::CacheManager;
+use crate::consumer::{BroadcastConsumer, TradeConsumer};
+use crate::engine::MatchEngine;
+use crate::trader::TraderOptions;
+
+/// 默认成交广播缓冲的成交数量
+pub const DEFAULT_TRADE_FEED_CAPACITY: usize = 4096;
+
+/// 订单持久化方式
+#[derive(Debug, Clone, Default)]
+pub enum Persistence {
+    /// 订单只保存在内存中，重启后无法恢复，对账和主备复制不可用
+    #[default]
+    Memory,
+    /// 订单写入Redis，创建交易员时从缓存恢复
+    #[cfg(feature = "redis")]
+    Redis(CacheManager),
+}
+
+/// 嵌入式引擎构造器，不依赖HTTP服务和配置文件
+///
+/// ```ignore
+/// let engine = MatchEngine::builder()
+///     .symbol("LOOM-USDT-SPOT")
+///     .build()
+///     .await?;
+/// let mut trades = engine.handle().subscribe_trades()?;
+/// engine.feed(order).await?;
+/// ```
+pub struct EngineBuilder {
+    symbols: Vec<(SymbolSpec, TraderOptions)>,
+    consumer: Option<TradeConsumer>,
+    persistence: Persistence,
+    shards: Option<usize>,
+    trade_feed_capacity: usize,
+    configure: Vec<Box<dyn FnOnce(MatchEngine) -> MatchEngine + Send>>,
+}
+
+impl Default for EngineBuilder {
+    fn default() -> Self {
+        EngineBuilder {
+            symbols: Vec::new(),
+            consumer: None,
+            persistence: Persistence::default(),
+            shards: None,
+            trade_feed_capacity: DEFAULT_TRADE_FEED_CAPACITY,
+            configure: Vec::new(),
+        }
+    }
+}
+
+impl EngineBuilder {
+    /// 添加使用默认规格和配置的交易对
+    pub fn symbol(self, symbol: &str) -> EngineBuilder {
+        self.spec(SymbolSpec::new(symbol), TraderOptions::default())
+    }
+
+    /// 添加交易对
+    pub fn spec(mut self, spec: SymbolSpec, options: TraderOptions) -> EngineBuilder {
+        self.symbols.push((spec, options));
+        self
+    }
+
+    /// 成交写入的下游消费器，未设置时成交只广播给订阅者
+    pub fn consumer(mut self, consumer: TradeConsumer) -> EngineBuilder {
+        self.consumer = Some(consumer);
+        self
+    }
+
+    pub fn persistence(mut self, persistence: Persistence) -> EngineBuilder {
+        self.persistence = persistence;
+        self
+    }
+
+    /// 将交易员分配到shards个工作分片中运行
+    pub fn shards(mut self, shards: usize) -> EngineBuilder {
+        self.shards = Some(shards);
+        self
+    }
+
+    /// 成交广播缓冲的成交数量
+    pub fn trade_feed_capacity(mut self, capacity: usize) -> EngineBuilder {
+        self.trade_feed_capacity = capacity.max(1);
+        self
+    }
+
+    /// 在创建交易员之前配置引擎，例如启用风控和账户限制
+    pub fn configure<F>(mut self, f: F) -> EngineBuilder
+        where F: FnOnce(MatchEngine) -> MatchEngine + Send + 'static
+    {
+        self.configure.push(Box::new(f));
+        self
+    }
+
+    /// 构造引擎并创建所有交易员，持久化到Redis时从缓存恢复后返回
+    pub async fn build(self) -> anyhow::Result<MatchEngine> {
+        let (sender, _) = broadcast::channel(self.trade_feed_capacity);
+        let mut engine = MatchEngine::in_memory().with_trade_feed(sender.clone());
+        match self.persistence {
+            Persistence::Memory => {}
+            #[cfg(feature = "redis")]
+            Persistence::Redis(cache_manager) => engine = engine.with_cache_manager(cache_manager),
+        }
+        if let Some(shards) = self.shards {
+            engine = engine.with_shard_pool(shards)?;
+        }
+        for f in self.configure {
+            engine = f(engine);
+        }
+        let consumer = TradeConsumer::Broadcast(BroadcastConsumer::new(sender, self.consumer));
+        for (spec, options) in self.symbols {
+            let symbol = spec.symbol.clone();
+            engine.new_trader_with_options(&symbol, consumer.clone(), options.with_spec(spec)).await?;
+        }
+        Ok(engine)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use bigdecimal::BigDecimal;
+
+    use loom_core::market::MarketBook;
+    use loom_core::order::{Order, OrderAction, TradeSide};
+    use loom_core::symbol::SymbolSpec;
+    use loom_core::testing::{account_order, new_order};
+
+    use crate::engine::{MatchEngine, UnknownSymbol};
+    use crate::trader::TraderStopped;
+    use crate::image::{self, EngineImage};
+    use crate::limits::{AccountLimitExceeded, AccountLimits};
+    #[cfg(feature = "redis")]
+    use crate::replication::ReplicationRole;
+    use crate::risk::{RiskChain, RiskConfig, RiskRejected};
+    use crate::trader::TraderOptions;
+    use crate::warmup::RecoveryPolicy;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn build_test() {
+        let mut engine = MatchEngine::builder()
+            .symbol("LOOM-USDT-SPOT")
+            .symbol("BTC-USDT-SPOT")
+            .build()
+            .await
+            .unwrap();
+        #[cfg(feature = "redis")]
+        assert!(engine.handle().cache_manager().is_none());
+        assert!(engine.handle().is_ready("BTC-USDT-SPOT"));
+        let mut trades = engine.handle().subscribe_trades().unwrap();
+        engine.feed(new_order(1, TradeSide::BUY, 10, "100")).await.unwrap();
+        engine.feed(new_order(2, TradeSide::SELL, 10, "100")).await.unwrap();
+        let trade = tokio::time::timeout(Duration::from_secs(5), trades.recv()).await.unwrap().unwrap();
+        assert_eq!((trade.symbol.as_str(), trade.qty), ("LOOM-USDT-SPOT", 10));
+        let err = engine.feed(Order { symbol: "ETH-USDT-SPOT".into(), ..new_order(3, TradeSide::BUY, 10, "100") }).await.unwrap_err();
+        assert!(err.downcast_ref::<UnknownSymbol>().is_some());
+        assert!(engine.shutdown().await);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn id_check_test() {
+        let mut engine = MatchEngine::builder()
+            .symbol("LOOM-USDT-SPOT")
+            .configure(|engine| engine.with_id_check())
+            .build()
+            .await
+            .unwrap();
+        for id in [1, 2, 5, 4] {
+            engine.feed(new_order(id, TradeSide::BUY, 10, "100")).await.unwrap();
+        }
+        // 撤单不参与检查
+        let mut cancel = new_order(1, TradeSide::BUY, 10, "100");
+        cancel.action = OrderAction::CANCEL;
+        engine.feed(cancel).await.unwrap();
+        let watermarks = engine.handle().id_watermarks();
+        let (symbol, ids) = &watermarks[0];
+        assert_eq!((symbol.as_str(), ids.high(), ids.gaps(), ids.regressions()), ("LOOM-USDT-SPOT", 5, 2, 1));
+        // 高水位由定时任务写入，不在下单路径上写入
+        assert_eq!(ids.unsaved(), Some(5));
+        assert_eq!(engine.handle().flush_client_ids().await.unwrap(), 1);
+        assert_eq!(ids.unsaved(), None);
+        assert!(engine.shutdown().await);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn image_recovery_test() {
+        let dir = std::env::temp_dir().join(format!("loom-recovery-test-{}", std::process::id()));
+        let mut book = MarketBook::new("LOOM-USDT-SPOT");
+        book.try_match(new_order(1, TradeSide::BUY, 10, "100"));
+        image::write_image(&dir, &EngineImage::new(vec![book.image()])).unwrap();
+        let recovery = RecoveryPolicy::Image { path: dir.display().to_string() };
+        let mut engine = MatchEngine::builder()
+            .spec(SymbolSpec::new("LOOM-USDT-SPOT"), TraderOptions::default().with_recovery(recovery))
+            .build()
+            .await
+            .unwrap();
+        assert!(engine.handle().is_ready("LOOM-USDT-SPOT"));
+        let snapshot = engine.handle().snapshot("LOOM-USDT-SPOT").unwrap();
+        assert_eq!(snapshot.stats.bids.orders, 1);
+        assert!(engine.shutdown().await);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn stopped_rollback_test() {
+        let mut engine = MatchEngine::builder()
+            .symbol("LOOM-USDT-SPOT")
+            .shards(1)
+            .configure(|engine| engine.with_ledger())
+            .build()
+            .await
+            .unwrap();
+        let ledger = engine.handle().ledger().unwrap().clone();
+        ledger.adjust("alice", "USDT", &BigDecimal::from(1000)).unwrap();
+        engine.shutdown_shard(0).await.unwrap();
+        // 交易员已停止，预留的资金退回可用余额
+        let order = account_order(1, "alice", TradeSide::BUY, 10, "100");
+        let err = engine.feed(order).await.unwrap_err();
+        assert_eq!(err.downcast_ref::<TraderStopped>().unwrap().order.id, 1);
+        let balance = &ledger.balances("alice")["USDT"];
+        assert_eq!((balance.available.clone(), balance.frozen.clone()), (BigDecimal::from(1000), BigDecimal::from(0)));
+        assert!(engine.shutdown().await);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn throttle_refund_test() {
+        let spec = SymbolSpec { max_orders_per_sec: Some(1), ..SymbolSpec::new("LOOM-USDT-SPOT") };
+        let risk = RiskConfig { max_notional: Some(BigDecimal::from(500)), ..Default::default() };
+        let mut engine = MatchEngine::builder()
+            .spec(spec, TraderOptions::default())
+            .configure(move |engine| engine.with_risk_checks(RiskChain::from_config(&risk)))
+            .build()
+            .await
+            .unwrap();
+        // 风控拒绝的订单不占用交易对的下单额度
+        let err = engine.feed(new_order(1, TradeSide::BUY, 10, "100")).await.unwrap_err();
+        assert!(err.downcast_ref::<RiskRejected>().is_some());
+        let order = Order { qty: 1, ..new_order(2, TradeSide::BUY, 10, "100") };
+        engine.feed(order).await.unwrap();
+        assert!(engine.shutdown().await);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn throttle_account_refund_test() {
+        let spec = SymbolSpec { max_orders_per_sec: Some(2), ..SymbolSpec::new("LOOM-USDT-SPOT") };
+        let limits = AccountLimits { max_open_orders: Some(1), ..Default::default() };
+        let mut engine = MatchEngine::builder()
+            .spec(spec, TraderOptions::default())
+            .configure(move |engine| engine.with_account_limits(limits))
+            .build()
+            .await
+            .unwrap();
+        let alice = |id| account_order(id, "alice", TradeSide::SELL, 10, "100");
+        engine.feed(alice(1)).await.unwrap();
+        // 账户限制拒绝的订单不占用交易对的下单额度
+        let err = engine.feed(alice(2)).await.unwrap_err();
+        assert!(err.downcast_ref::<AccountLimitExceeded>().is_some());
+        let order = account_order(3, "bob", TradeSide::SELL, 10, "100");
+        engine.feed(order).await.unwrap();
+        assert!(engine.shutdown().await);
+    }
+
+    #[cfg(all(feature = "redis", feature = "fault-injection"))]
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore]
+    async fn journal_fault_test() {
+        use std::sync::Arc;
+
+        use crate::builder::Persistence;
+        use crate::cache::CacheManager;
+        use crate::fault::{FaultConfig, FaultInjector, InjectedFault};
+
+        let faults = Arc::new(FaultInjector::new(FaultConfig::default()));
+        let cache = CacheManager::new("redis://localhost:6379").await.unwrap().with_faults(faults.clone());
+        let mut engine = MatchEngine::builder()
+            .symbol("JOURNAL-USDT-SPOT")
+            .persistence(Persistence::Redis(cache.clone()))
+            .configure(|engine| engine.with_replication(ReplicationRole::Primary, 1000))
+            .build()
+            .await
+            .unwrap();
+        let mut trades = engine.handle().subscribe_trades().unwrap();
+        let id = loom_core::utils::now_ts() as u64;
+        engine.feed(Order { symbol: "JOURNAL-USDT-SPOT".into(), ..new_order(id, TradeSide::SELL, 10, "100") }).await.unwrap();
+        // 日志写入失败的订单不进入撮合队列，也不留在缓存中
+        faults.set_failing("append_journal", true);
+        let err = engine.feed(Order { symbol: "JOURNAL-USDT-SPOT".into(), ..new_order(id + 1, TradeSide::BUY, 10, "100") }).await.unwrap_err();
+        assert!(err.is::<InjectedFault>());
+        assert!(tokio::time::timeout(Duration::from_millis(200), trades.recv()).await.is_err());
+        assert!(cache.get_orders_by_ids("JOURNAL-USDT-SPOT", &[id + 1]).await.unwrap().is_empty());
+        faults.set_failing("append_journal", false);
+        let mut cancel = Order { symbol: "JOURNAL-USDT-SPOT".into(), ..new_order(id, TradeSide::SELL, 10, "100") };
+        cancel.action = OrderAction::CANCEL;
+        engine.feed(cancel).await.unwrap();
+        assert!(engine.shutdown().await);
+    }
+}
@@ -1,23 +1,55 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+#[cfg(feature = "fault-injection")]
+use std::sync::Arc;
 use std::time::Duration;
 
 use bb8_redis::{bb8, RedisConnectionManager};
-use bb8_redis::bb8::Pool;
+use bb8_redis::bb8::{Pool, PooledConnection};
 use log::debug;
 use redis::aio::MultiplexedConnection;
-use redis::ConnectionLike;
 use serde::{Deserialize, Serialize};
 use validator::HasLen;
 
 use loom_core::market::MatchTrade;
 use loom_core::order::{Order, OrderState};
+use loom_core::snapshot::StateHash;
 use loom_core::utils;
 
+#[cfg(feature = "fault-injection")]
+use crate::fault::FaultInjector;
+use crate::ledger::Balance;
+use crate::tenant;
+
 pub const CACHE_PREFIX: &str = "Loom";
 
+/// 成交队列的分区方式
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub enum TradeStream {
+    /// 每个交易对一个成交队列
+    #[default]
+    PerSymbol,
+    /// 所有交易对写入同一个全局成交队列，队列顺序即跨交易对的提交顺序，租户各有一个全局队列
+    Global,
+    /// 同时写入交易对成交队列和全局成交队列
+    Both,
+}
+
+impl TradeStream {
+    fn per_symbol(&self) -> bool {
+        matches!(self, TradeStream::PerSymbol | TradeStream::Both)
+    }
+
+    fn global(&self) -> bool {
+        matches!(self, TradeStream::Global | TradeStream::Both)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct CacheManager {
     pool: Pool<RedisConnectionManager>,
+    /// 测试用的故障注入
+    #[cfg(feature = "fault-injection")]
+    faults: Option<Arc<FaultInjector>>,
 }
 
 
@@ -31,20 +63,325 @@ impl CacheManager {
             .await
             .unwrap();
         Ok(
-            CacheManager { pool }
+            CacheManager {
+                pool,
+                #[cfg(feature = "fault-injection")]
+                faults: None,
+            }
         )
     }
 
+    /// 所有Redis操作经过故障注入器，用于测试Redis中断和慢请求
+    #[cfg(feature = "fault-injection")]
+    pub fn with_faults(mut self, faults: Arc<FaultInjector>) -> CacheManager {
+        self.faults = Some(faults);
+        self
+    }
+
+    async fn conn(&self) -> anyhow::Result<PooledConnection<'_, RedisConnectionManager>> {
+        #[cfg(feature = "fault-injection")]
+        if let Some(faults) = &self.faults {
+            faults.before("redis").await?;
+        }
+        Ok(self.pool.get().await?)
+    }
+
+    /// 检查Redis连通性
+    pub async fn ping(&self) -> anyhow::Result<()> {
+        let conn = self.conn().await?;
+        redis::cmd("PING").query_async::<_, String>(&mut conn.to_owned()).await?;
+        Ok(())
+    }
+
+    /// 发布消息到Redis频道
+    pub async fn publish(&self, channel: &str, payload: &str) -> anyhow::Result<()> {
+        let conn = self.conn().await?;
+        redis::cmd("PUBLISH").arg(channel).arg(payload).query_async::<_, i64>(&mut conn.to_owned()).await?;
+        Ok(())
+    }
+
+    /// 租户的交易对和账户的缓存键以租户为前缀，如Loom:acme:ID:LOOM-USDT-SPOT
+    fn scoped_key(kind: &str, name: &str) -> String {
+        match tenant::split(name) {
+            (Some(tenant), name) => format!("{}:{}:{}:{}", CACHE_PREFIX, tenant, kind, name),
+            (None, name) => format!("{}:{}:{}", CACHE_PREFIX, kind, name),
+        }
+    }
+
+    fn cache_key_journal(symbol: &str) -> String {
+        Self::scoped_key("JOURNAL", symbol)
+    }
+
+    fn cache_key_checkpoint(symbol: &str) -> String {
+        Self::scoped_key("CHECKPOINT", symbol)
+    }
+
+    /// 将已受理的撮合请求追加到日志流，日志流保留约max_len条
+    pub async fn append_journal(&self, order: &Order, max_len: usize) -> anyhow::Result<()> {
+        #[cfg(feature = "fault-injection")]
+        if let Some(faults) = &self.faults {
+            faults.before("append_journal").await?;
+        }
+        let conn = self.conn().await?;
+        redis::cmd("XADD").arg(Self::cache_key_journal(&order.symbol))
+            .arg("MAXLEN").arg("~").arg(max_len)
+            .arg("*").arg("order").arg(serde_json::to_string(order)?)
+            .query_async::<_, String>(&mut conn.to_owned())
+            .await?;
+        Ok(())
+    }
+
+    /// 日志流最后一条的ID，日志流为空时返回0-0
+    pub async fn journal_tail(&self, symbol: &str) -> anyhow::Result<String> {
+        let conn = self.conn().await?;
+        let entries = redis::cmd("XREVRANGE").arg(Self::cache_key_journal(symbol))
+            .arg("+").arg("-").arg("COUNT").arg(1)
+            .query_async::<_, redis::Value>(&mut conn.to_owned())
+            .await?;
+        let tail = Self::journal_entries(&entries)?.pop().map(|(id, _)| id);
+        Ok(tail.unwrap_or_else(|| String::from("0-0")))
+    }
+
+    /// 读取after之后的日志，block为空时不等待新日志
+    pub async fn read_journal(&self, symbol: &str, after: &str, count: usize, block: Option<Duration>) -> anyhow::Result<Vec<(String, Order)>> {
+        let conn = self.conn().await?;
+        let mut cmd = redis::cmd("XREAD");
+        cmd.arg("COUNT").arg(count);
+        if let Some(block) = block {
+            cmd.arg("BLOCK").arg(block.as_millis() as u64);
+        }
+        let streams = cmd.arg("STREAMS").arg(Self::cache_key_journal(symbol)).arg(after)
+            .query_async::<_, redis::Value>(&mut conn.to_owned())
+            .await?;
+        // 返回结构为[[stream, entries]]，超时返回nil
+        match streams {
+            redis::Value::Bulk(streams) => match streams.first() {
+                Some(redis::Value::Bulk(stream)) if stream.len() == 2 => Self::journal_entries(&stream[1]),
+                _ => Ok(Vec::new()),
+            },
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    /// 解析日志条目，每条为[id, [field, value, ...]]
+    fn journal_entries(entries: &redis::Value) -> anyhow::Result<Vec<(String, Order)>> {
+        let entries = match entries {
+            redis::Value::Bulk(entries) => entries,
+            _ => return Ok(Vec::new()),
+        };
+        let mut orders = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let (id, fields): (String, HashMap<String, String>) = redis::from_redis_value(entry)?;
+            let order = fields.get("order").ok_or_else(|| anyhow::anyhow!("journal entry without order, id={}", id))?;
+            orders.push((id, serde_json::from_str(order)?));
+        }
+        Ok(orders)
+    }
+
+    /// 保存主机的状态哈希检查点
+    pub async fn set_checkpoint(&self, hash: &StateHash) -> anyhow::Result<()> {
+        let conn = self.conn().await?;
+        redis::cmd("SET").arg(Self::cache_key_checkpoint(&hash.symbol)).arg(serde_json::to_string(hash)?)
+            .query_async::<_, ()>(&mut conn.to_owned())
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_checkpoint(&self, symbol: &str) -> anyhow::Result<Option<StateHash>> {
+        let conn = self.conn().await?;
+        let hash = redis::cmd("GET").arg(Self::cache_key_checkpoint(symbol))
+            .query_async::<_, Option<String>>(&mut conn.to_owned())
+            .await?;
+        match hash {
+            Some(hash) => Ok(Some(serde_json::from_str(&hash)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn cache_key_sequence(symbol: &str) -> String {
+        Self::scoped_key("SEQ", symbol)
+    }
+
+    /// 预留count个序列号，返回预留后的高水位
+    pub async fn reserve_sequence(&self, symbol: &str, count: u64) -> anyhow::Result<u64> {
+        let conn = self.conn().await?;
+        let limit = redis::cmd("INCRBY").arg(Self::cache_key_sequence(symbol)).arg(count)
+            .query_async::<_, u64>(&mut conn.to_owned())
+            .await?;
+        Ok(limit)
+    }
+
+    fn cache_key_client_id(symbol: &str) -> String {
+        Self::scoped_key("CLIENT_ID", symbol)
+    }
+
+    /// 读取交易对已受理订单的客户端ID高水位
+    pub async fn get_client_id(&self, symbol: &str) -> anyhow::Result<u64> {
+        let conn = self.conn().await?;
+        let id = redis::cmd("GET").arg(Self::cache_key_client_id(symbol))
+            .query_async::<_, Option<u64>>(&mut conn.to_owned())
+            .await?;
+        Ok(id.unwrap_or(0))
+    }
+
+    /// 提高交易对的客户端ID高水位，不低于已记录的值
+    pub async fn raise_client_id(&self, symbol: &str, id: u64) -> anyhow::Result<()> {
+        let conn = self.conn().await?;
+        redis::Script::new(RAISE_SCRIPT)
+            .key(Self::cache_key_client_id(symbol))
+            .arg(id)
+            .invoke_async::<_, ()>(&mut conn.to_owned())
+            .await?;
+        Ok(())
+    }
+
+    fn cache_key_idempotency(key: &str) -> String {
+        format!("{}:IDEMPOTENCY:{}", CACHE_PREFIX, key)
+    }
+
+    /// 占用幂等键，成功时返回None，已被占用时返回已记录的结果
+    pub async fn claim_idempotency(&self, key: &str, pending: &str, ttl: Duration) -> anyhow::Result<Option<String>> {
+        let conn = self.conn().await?;
+        let cache_key = Self::cache_key_idempotency(key);
+        let (claimed, existing) = redis::pipe()
+            .atomic()
+            .cmd("SET").arg(&cache_key).arg(pending).arg("NX").arg("PX").arg(ttl.as_millis() as u64)
+            .cmd("GET").arg(&cache_key)
+            .query_async::<_, (Option<String>, Option<String>)>(&mut conn.to_owned())
+            .await?;
+        Ok(match claimed {
+            Some(_) => None,
+            None => existing,
+        })
+    }
+
+    /// 记录幂等键对应的处理结果
+    pub async fn store_idempotency(&self, key: &str, result: &str, ttl: Duration) -> anyhow::Result<()> {
+        let conn = self.conn().await?;
+        redis::cmd("SET").arg(Self::cache_key_idempotency(key)).arg(result).arg("PX").arg(ttl.as_millis() as u64)
+            .query_async::<_, ()>(&mut conn.to_owned())
+            .await?;
+        Ok(())
+    }
+
+    /// 释放幂等键，允许客户端重试
+    pub async fn release_idempotency(&self, key: &str) -> anyhow::Result<()> {
+        let conn = self.conn().await?;
+        redis::cmd("DEL").arg(Self::cache_key_idempotency(key))
+            .query_async::<_, ()>(&mut conn.to_owned())
+            .await?;
+        Ok(())
+    }
+
+    fn cache_key_accounts() -> String {
+        format!("{}:ACCOUNTS", CACHE_PREFIX)
+    }
+
+    fn cache_key_balance(account: &str) -> String {
+        Self::scoped_key("BALANCE", account)
+    }
+
+    /// 写入账户余额，每个账户一个哈希，字段为资产
+    pub async fn set_balances(&self, balances: &[(String, String, Balance)]) -> anyhow::Result<()> {
+        if balances.is_empty() {
+            return Ok(());
+        }
+        let conn = self.conn().await?;
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        for (account, asset, balance) in balances {
+            pipe.cmd("SADD").arg(Self::cache_key_accounts()).arg(account).ignore()
+                .cmd("HSET").arg(Self::cache_key_balance(account)).arg(asset).arg(serde_json::to_string(balance)?).ignore();
+        }
+        pipe.query_async::<_, ()>(&mut conn.to_owned()).await?;
+        Ok(())
+    }
+
+    /// 读取所有账户余额，返回账户、资产和余额
+    pub async fn get_balances(&self) -> anyhow::Result<Vec<(String, String, Balance)>> {
+        let mut conn = self.conn().await?.to_owned();
+        let accounts = redis::cmd("SMEMBERS").arg(Self::cache_key_accounts())
+            .query_async::<_, Vec<String>>(&mut conn)
+            .await?;
+        if accounts.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut pipe = redis::pipe();
+        for account in accounts.iter() {
+            pipe.cmd("HGETALL").arg(Self::cache_key_balance(account));
+        }
+        let assets = pipe.query_async::<_, Vec<HashMap<String, String>>>(&mut conn).await?;
+        let mut balances = Vec::new();
+        for (account, assets) in accounts.into_iter().zip(assets) {
+            for (asset, balance) in assets {
+                balances.push((account.clone(), asset, serde_json::from_str(&balance)?));
+            }
+        }
+        Ok(balances)
+    }
+
     fn cache_key_id(symbol: &str) -> String {
-        format!("{}:ID:{}", CACHE_PREFIX, symbol)
+        Self::scoped_key("ID", symbol)
     }
 
     fn cache_key_order(symbol: &str, id: u64) -> String {
-        format!("{}:ORDER:{}:{}", CACHE_PREFIX, symbol, id)
+        format!("{}:{}", Self::scoped_key("ORDER", symbol), id)
     }
 
     fn cache_key_trades(symbol: &str) -> String {
-        format!("{}:TRADES:{}", CACHE_PREFIX, symbol)
+        Self::scoped_key("TRADES", symbol)
+    }
+
+    /// 全局成交队列，按交易对所属的租户区分
+    fn cache_key_global_trades(symbol: &str) -> String {
+        match tenant::split(symbol) {
+            (Some(tenant), _) => format!("{}:{}:TRADES", CACHE_PREFIX, tenant),
+            (None, _) => format!("{}:TRADES", CACHE_PREFIX),
+        }
+    }
+
+    fn cache_key_trade_id(symbol: &str) -> String {
+        Self::scoped_key("TRADE_ID", symbol)
+    }
+
+    /// 读取交易对已分配的成交ID高水位
+    pub async fn get_trade_id(&self, symbol: &str) -> anyhow::Result<u64> {
+        let conn = self.conn().await?;
+        let trade_id = redis::cmd("GET").arg(Self::cache_key_trade_id(symbol))
+            .query_async::<_, Option<u64>>(&mut conn.to_owned())
+            .await?;
+        Ok(trade_id.unwrap_or(0))
+    }
+
+    /// 设置交易对的成交ID高水位
+    pub async fn set_trade_id(&self, symbol: &str, trade_id: u64) -> anyhow::Result<()> {
+        let conn = self.conn().await?;
+        redis::cmd("SET").arg(Self::cache_key_trade_id(symbol)).arg(trade_id)
+            .query_async::<_, ()>(&mut conn.to_owned())
+            .await?;
+        Ok(())
+    }
+
+    /// 删除交易对的订单ID集合和全部订单哈希，包括不在ID集合中的残留哈希，返回删除的订单哈希数量
+    pub async fn clear_orders(&self, symbol: &str) -> anyhow::Result<usize> {
+        let mut conn = self.conn().await?.to_owned();
+        let pattern = format!("{}:*", Self::scoped_key("ORDER", symbol));
+        let mut keys = Vec::new();
+        {
+            let mut cmd = redis::cmd("SCAN");
+            cmd.cursor_arg(0).arg("MATCH").arg(&pattern).arg("COUNT").arg(1000);
+            let mut iter = cmd.iter_async::<String>(&mut conn).await?;
+            while let Some(key) = iter.next_item().await {
+                keys.push(key);
+            }
+        }
+        let mut pipe = redis::pipe();
+        pipe.del(Self::cache_key_id(symbol)).ignore();
+        for chunk in keys.chunks(1000) {
+            pipe.del(chunk).ignore();
+        }
+        pipe.query_async::<_, ()>(&mut conn).await?;
+        Ok(keys.len())
     }
 
     pub fn cache_key(order_ref: &Order) -> (String, String) {
@@ -55,13 +392,13 @@ impl CacheManager {
     }
 
     pub async fn add_if_absent(&self, order: Order) -> anyhow::Result<bool> {
-        let conn = self.pool.get().await?.to_owned();
+        let conn = self.conn().await?.to_owned();
         let (id_key, order_key) = Self::cache_key(&order);
-        let resp = redis::pipe()
-            .atomic()
+        let mut pipe = redis::pipe();
+        pipe.atomic()
             .cmd("ZADD").arg(id_key).arg("NX").arg(order.ts.to_string()).arg(order.id.to_string())
             .cmd("HSETNX").arg(&order_key).arg("id").arg(order.id.to_string())
-            .cmd("HSETNX").arg(&order_key).arg("symbol").arg(order.symbol.clone())
+            .cmd("HSETNX").arg(&order_key).arg("symbol").arg(order.symbol.as_str())
             .cmd("HSETNX").arg(&order_key).arg("side").arg(order.side.to_string())
             .cmd("HSETNX").arg(&order_key).arg("qty").arg(order.qty.to_string())
             .cmd("HSETNX").arg(&order_key).arg("price").arg(order.price.to_string())
@@ -71,22 +408,84 @@ impl CacheManager {
             .cmd("HSETNX").arg(&order_key).arg("update_ts").arg(order.update_ts.to_string())
             .cmd("HSETNX").arg(&order_key).arg("state").arg(order.state.to_string())
             .cmd("HSETNX").arg(&order_key).arg("tif").arg(order.tif.to_string())
-            .cmd("HSETNX").arg(&order_key).arg("action").arg(order.action.to_string())
+            .cmd("HSETNX").arg(&order_key).arg("action").arg(order.action.to_string());
+        if let Some(account) = &order.account {
+            pipe.cmd("HSETNX").arg(&order_key).arg("account").arg(account);
+        }
+        if order.seq != 0 {
+            pipe.cmd("HSETNX").arg(&order_key).arg("seq").arg(order.seq.to_string());
+        }
+        if order.expire_ts != 0 {
+            pipe.cmd("HSETNX").arg(&order_key).arg("expire_ts").arg(order.expire_ts.to_string());
+        }
+        if order.ts_ns != 0 {
+            pipe.cmd("HSETNX").arg(&order_key).arg("ts_ns").arg(order.ts_ns.to_string());
+        }
+        if let Some(tag) = &order.tag {
+            pipe.cmd("HSETNX").arg(&order_key).arg("tag").arg(tag);
+        }
+        let resp = pipe
             .query_async::<MultiplexedConnection, Vec<i32>>(&mut conn.to_owned())
             .await?;
-        Ok(resp.get(0).map(|i| i.to_owned() == 1).unwrap_or(false) &&
-            resp.get(1).map(|i| i.to_owned() == 1).unwrap_or(false)
+        Ok(resp.first().map(|i| *i == 1).unwrap_or(false) &&
+            resp.get(1).map(|i| *i == 1).unwrap_or(false)
         )
     }
 
+    /// 用订单覆盖缓存中的订单哈希并加入订单ID集合，用于按订单簿修复缓存
+    pub async fn put_order(&self, order: &Order) -> anyhow::Result<()> {
+        let conn = self.conn().await?.to_owned();
+        let (id_key, order_key) = Self::cache_key(order);
+        let mut fields = vec![
+            ("id", order.id.to_string()),
+            ("symbol", order.symbol.to_string()),
+            ("side", order.side.to_string()),
+            ("qty", order.qty.to_string()),
+            ("price", order.price.to_string()),
+            ("acc_fill_qty", order.acc_fill_qty.to_string()),
+            ("ord_type", order.ord_type.to_string()),
+            ("ts", order.ts.to_string()),
+            ("update_ts", order.update_ts.to_string()),
+            ("state", order.state.to_string()),
+            ("tif", order.tif.to_string()),
+            ("action", order.action.to_string()),
+        ];
+        if let Some(account) = &order.account {
+            fields.push(("account", account.clone()));
+        }
+        if order.seq != 0 {
+            fields.push(("seq", order.seq.to_string()));
+        }
+        if order.expire_ts != 0 {
+            fields.push(("expire_ts", order.expire_ts.to_string()));
+        }
+        if order.ts_ns != 0 {
+            fields.push(("ts_ns", order.ts_ns.to_string()));
+        }
+        if let Some(tag) = &order.tag {
+            fields.push(("tag", tag.clone()));
+        }
+        redis::pipe()
+            .atomic()
+            .cmd("ZADD").arg(id_key).arg(order.ts.to_string()).arg(order.id.to_string()).ignore()
+            .hset_multiple(order_key, &fields).ignore()
+            .query_async::<_, ()>(&mut conn.to_owned())
+            .await?;
+        Ok(())
+    }
+
     pub async fn del(&self, order_ref: &Order) -> anyhow::Result<()> {
-        let conn = self.pool.get().await?;
-        let (id_key, order_key) = Self::cache_key(order_ref);
+        self.del_id(&order_ref.symbol, order_ref.id).await
+    }
+
+    /// 删除订单哈希和订单ID，订单哈希不存在时也会删除ID
+    pub async fn del_id(&self, symbol: &str, id: u64) -> anyhow::Result<()> {
+        let conn = self.conn().await?;
         redis::pipe()
             .atomic()
-            .zrem(id_key, order_ref.id.to_string())
-            .del(order_key)
-            .query_async(&mut conn.to_owned())
+            .zrem(Self::cache_key_id(symbol), id.to_string())
+            .del(Self::cache_key_order(symbol, id))
+            .query_async::<_, ()>(&mut conn.to_owned())
             .await?;
         Ok(())
     }
@@ -95,7 +494,7 @@ impl CacheManager {
         where
             F: FnMut(u64) -> anyhow::Result<()>
     {
-        let mut conn = self.pool.get().await?.to_owned();
+        let mut conn = self.conn().await?.to_owned();
         let id_key = Self::cache_key_id(symbol);
         let mut cmd = redis::cmd("ZRANGEBYSCORE");
         cmd.arg(id_key)
@@ -109,12 +508,12 @@ impl CacheManager {
         Ok(())
     }
 
-    pub async fn get_orders_by_ids(&self, symbol: &str, ids: &Vec<u64>) -> anyhow::Result<Vec<Order>> {
+    pub async fn get_orders_by_ids(&self, symbol: &str, ids: &[u64]) -> anyhow::Result<Vec<Order>> {
         if ids.is_empty() {
             return Ok(Vec::new());
         }
-        let mut conn = self.pool.get().await?.to_owned();
-        let order_keys: Vec<String> = ids.iter().map(|id| Self::cache_key_order(symbol, id.clone())).collect();
+        let mut conn = self.conn().await?.to_owned();
+        let order_keys: Vec<String> = ids.iter().map(|id| Self::cache_key_order(symbol, *id)).collect();
         let mut pipe = redis::pipe();
         for order_key in order_keys {
             pipe.cmd("HGETALL").arg(order_key);
@@ -124,17 +523,27 @@ impl CacheManager {
             .await?;
         let orders = orders.iter()
             .filter(|i| !i.is_empty())
-            .map(|i| Order::from_map(i))
-            .filter(|i| i.is_ok())
-            .map(|i| i.unwrap())
+            .flat_map(Order::from_map)
             .collect();
         Ok(orders)
     }
 
-    pub async fn get_order_batch<F>(&self, symbol: &str, batch: usize, consumer: F) -> anyhow::Result<()>
-        where F: Fn(Order) -> anyhow::Result<()>
+    /// 按批次读取交易对的全部订单，每批订单哈希用一次流水线读取
+    /// 交易对缓存中待恢复的订单数量
+    pub async fn count_ids(&self, symbol: &str) -> anyhow::Result<usize> {
+        let mut conn = self.conn().await?.to_owned();
+        let count: usize = redis::cmd("ZCOUNT")
+            .arg(Self::cache_key_id(symbol))
+            .arg("0")
+            .arg(utils::now_ts().to_string())
+            .query_async(&mut conn).await?;
+        Ok(count)
+    }
+
+    pub async fn get_order_batch<F>(&self, symbol: &str, batch: usize, mut consumer: F) -> anyhow::Result<()>
+        where F: FnMut(Order) -> anyhow::Result<()>
     {
-        let mut conn = self.pool.get().await?.to_owned();
+        let mut conn = self.conn().await?.to_owned();
         let id_key = Self::cache_key_id(symbol);
         let mut cmd = redis::cmd("ZRANGEBYSCORE");
         cmd.arg(id_key)
@@ -145,7 +554,7 @@ impl CacheManager {
         while let Some(id) = iter.next_item().await {
             let id = id.parse::<u64>()?;
             buffer.push(id);
-            if buffer.length() >= batch as u64 {
+            if buffer.length() >= batch.max(1) as u64 {
                 // 获取order
                 let mut orders = self.get_orders_by_ids(symbol, &buffer).await?;
                 for order in orders.drain(..) {
@@ -155,84 +564,204 @@ impl CacheManager {
                 buffer.clear();
             }
         }
+        for order in self.get_orders_by_ids(symbol, &buffer).await? {
+            consumer(order)?;
+        }
         Ok(())
     }
 
-    pub async fn offer_trades(&self, trades: Vec<MatchTrade>) -> anyhow::Result<()> {
+    /// 写入一批同一交易对的成交，stream决定成交写入哪些成交队列
+    pub async fn offer_trades(&self, trades: &[MatchTrade], stream: TradeStream) -> anyhow::Result<()> {
+        // 模拟只写入了前一部分成交后连接中断
+        #[cfg(feature = "fault-injection")]
+        if let Some(faults) = &self.faults {
+            if let Some(written) = faults.partial(trades.len()) {
+                self.write_trades(&trades[..written], stream).await?;
+                return Err(faults.partial_fault("offer_trades", written));
+            }
+        }
+        self.write_trades(trades, stream).await
+    }
+
+    /// 在事务中写入成交对订单的修改、成交队列和成交ID高水位，监视的键在提交前被修改时重新读取并提交
+    async fn write_trades(&self, trades: &[MatchTrade], stream: TradeStream) -> anyhow::Result<()> {
         if trades.is_empty() {
             return Ok(());
         }
-        let mut conn = self.pool.get().await?.to_owned();
-        /// 脚本参数描述
-        /// KEYS
-        /// 1. trades_key
-        /// ARGV:
-        /// 1.OrderUpdates: [{...}]
-        /// 2. trades
-        let script = redis::Script::new(r"
-            local function update_order(oid_key, order_key, oid, acc_fill_qty, state, ts, del_flag)
-                local exist = redis.call('EXISTS', order_key);
-                if exist == 1 then
-                    -- 判断是否需要删除order
-                    if del_flag or del_flag == 'true' then
-                        -- 删除订单
-                        redis.call('DEL', order_key);
-                        -- 删除ID
-                        redis.call('ZREM', oid_key, oid);
-                        return;
-                    end
-                    -- 判断是否需要更新累计成交量
-                    if acc_fill_qty > 0 then
-                        local pre_acc_fill_qty = tonumber(redis.call('HGET', order_key, 'acc_fill_qty'));
-                        redis.call('HSET', order_key, 'acc_fill_qty', pre_acc_fill_qty + acc_fill_qty);
-                    end
-                    -- 判断是否需要更新订单状态
-                    if not del_flag or del_flag == 'false' then
-                        redis.call('HSET', order_key, 'state', state);
-                        redis.call('HSET', order_key, 'update_ts', ts);
-                    end
-                end
-            end
-
-
-            local updates = cjson.decode(ARGV[1]);
-            for key,update in ipairs(updates) do
-                local oid_key = update['oid_key'];
-                local acc_fill_qty = update['qty'];
-                local ts = update['ts'];
-
-                local taker_order_key = update['taker_order_key'];
-                local taker_state = update['taker_state'];
-                local del_taker_flag = update['del_taker_flag'];
-                local taker_oid = update['taker_oid'];
-                update_order(oid_key, taker_order_key, taker_oid, acc_fill_qty, taker_state, ts, del_taker_flag);
-
-                local maker_order_key = update['maker_order_key'];
-                local maker_state = update['maker_state'];
-                local del_maker_flag = update['del_maker_flag'];
-                local maker_oid = update['maker_oid'];
-                update_order(oid_key, maker_order_key, maker_oid, acc_fill_qty, maker_state, ts, del_maker_flag);
-            end
-
-            -- add trade queue
-            local trades_key = KEYS[1];
-            redis.call('XADD', trades_key, 'MAXLEN', '~', '1000', '*', 'trades', ARGV[2]);
-        ");
-        let updates: Vec<OrderUpdate> = trades.iter().map(|i| OrderUpdate::new(i)).collect();
-        let symbol = &(trades.get(0).unwrap().symbol);
-        let trades_key = CacheManager::cache_key_trades(symbol);
-        let updates = serde_json::to_string(&updates)?;
-        let trades = serde_json::to_string(&trades)?;
-        debug!("NEW UPDATES: {}", &updates);
-        script.key(trades_key)
-            .arg(updates)
-            .arg(trades)
-            .invoke_async(&mut conn)
+        let updates: Vec<OrderUpdate> = trades.iter().map(OrderUpdate::new).collect();
+        let symbol = &(trades.first().unwrap().symbol);
+        let mut order_keys: Vec<&str> = updates.iter()
+            .flat_map(|u| [u.taker_order_key.as_str(), u.maker_order_key.as_str()])
+            .collect();
+        order_keys.sort_unstable();
+        order_keys.dedup();
+        let write = TradeWrite {
+            updates: &updates,
+            order_keys,
+            trades_key: stream.per_symbol().then(|| CacheManager::cache_key_trades(symbol)),
+            global_key: stream.global().then(|| CacheManager::cache_key_global_trades(symbol)),
+            symbol,
+            trade_id_key: CacheManager::cache_key_trade_id(symbol),
+            trade_id: trades.iter().map(|t| t.id).max().unwrap_or(0),
+            payload: serde_json::to_string(trades)?,
+        };
+        debug!("NEW UPDATES: symbol={}, updates={}", symbol, updates.len());
+        // 监视状态属于连接，整个事务期间持有同一个连接
+        let pooled = self.conn().await?;
+        let mut conn = pooled.to_owned();
+        for _ in 0..MAX_WATCH_RETRIES {
+            match write.attempt(&mut conn).await {
+                Ok(true) => return Ok(()),
+                Ok(false) => continue,
+                Err(e) => {
+                    // 连接归还连接池前取消监视，避免其他事务被放弃
+                    let _ = redis::cmd("UNWATCH").query_async::<_, ()>(&mut conn).await;
+                    return Err(e.into());
+                }
+            }
+        }
+        Err(anyhow::anyhow!("write trades aborted, watched keys kept changing, symbol={}", symbol))
+    }
+}
+
+/// 一批成交的缓存写入
+struct TradeWrite<'a> {
+    updates: &'a [OrderUpdate],
+    /// 涉及的订单键
+    order_keys: Vec<&'a str>,
+    /// 交易对成交队列，不写入时为空
+    trades_key: Option<String>,
+    /// 全局成交队列，不写入时为空
+    global_key: Option<String>,
+    symbol: &'a str,
+    trade_id_key: String,
+    /// 本批成交的最大成交ID
+    trade_id: u64,
+    /// 序列化的成交
+    payload: String,
+}
+
+impl TradeWrite<'_> {
+    /// 监视订单键和成交ID高水位，读取后在事务中提交，事务被放弃时返回false
+    async fn attempt(&self, conn: &mut MultiplexedConnection) -> redis::RedisResult<bool> {
+        redis::cmd("WATCH").arg(&self.order_keys).arg(&self.trade_id_key)
+            .query_async::<_, ()>(conn)
             .await?;
-        Ok(())
+        let mut read = redis::pipe();
+        for key in &self.order_keys {
+            read.exists(*key);
+        }
+        let exists = read.query_async::<_, Vec<bool>>(conn).await?;
+        let existing: HashSet<&str> = self.order_keys.iter().zip(exists).filter(|(_, e)| *e).map(|(k, _)| *k).collect();
+        let current = redis::cmd("GET").arg(&self.trade_id_key)
+            .query_async::<_, Option<u64>>(conn)
+            .await?
+            .unwrap_or(0);
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        for op in plan_updates(self.updates, &existing) {
+            op.apply(&mut pipe);
+        }
+        if let Some(trades_key) = &self.trades_key {
+            pipe.cmd("XADD").arg(trades_key).arg("MAXLEN").arg("~").arg(TRADES_MAX_LEN).arg("*").arg("trades").arg(&self.payload).ignore();
+        }
+        if let Some(global_key) = &self.global_key {
+            // 与订单修改在同一事务中追加，全局队列的顺序与各交易对的提交顺序一致
+            pipe.cmd("XADD").arg(global_key).arg("MAXLEN").arg("~").arg(GLOBAL_TRADES_MAX_LEN).arg("*")
+                .arg("symbol").arg(self.symbol).arg("trades").arg(&self.payload).ignore();
+        }
+        if self.trade_id > current {
+            pipe.set(&self.trade_id_key, self.trade_id).ignore();
+        }
+        Ok(pipe.query_async::<_, Option<redis::Value>>(conn).await?.is_some())
+    }
+}
+
+/// 交易对成交队列保留的大约条数
+const TRADES_MAX_LEN: usize = 1000;
+
+/// 全局成交队列保留的大约条数，所有交易对共用
+const GLOBAL_TRADES_MAX_LEN: usize = 100_000;
+
+/// 写入成交时监视的键被其他客户端修改的最大重试次数
+const MAX_WATCH_RETRIES: usize = 8;
+
+/// 键不存在或小于参数时写入参数
+const RAISE_SCRIPT: &str = r#"
+local current = tonumber(redis.call('GET', KEYS[1]) or '0')
+if tonumber(ARGV[1]) > current then
+    redis.call('SET', KEYS[1], ARGV[1])
+end
+return 0
+"#;
+
+/// 成交对缓存订单的一项修改
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) enum CacheOp {
+    /// 删除订单哈希和订单ID
+    Remove { oid_key: String, order_key: String, oid: String },
+    /// 增加订单哈希的数值字段
+    Incr { key: String, field: &'static str, delta: i64 },
+    /// 设置订单哈希的字段
+    Set { key: String, field: &'static str, value: String },
+}
+
+impl CacheOp {
+    fn apply(self, pipe: &mut redis::Pipeline) {
+        match self {
+            CacheOp::Remove { oid_key, order_key, oid } => {
+                pipe.del(order_key).ignore().zrem(oid_key, oid).ignore();
+            }
+            CacheOp::Incr { key, field, delta } => {
+                pipe.hincr(key, field, delta).ignore();
+            }
+            CacheOp::Set { key, field, value } => {
+                pipe.hset(key, field, value).ignore();
+            }
+        }
+    }
+}
+
+/// 按顺序应用成交对订单的修改，existing为提交前存在的订单哈希，订单哈希不存在的订单不修改，避免写入残缺的订单
+pub(crate) fn plan_updates(updates: &[OrderUpdate], existing: &HashSet<&str>) -> Vec<CacheOp> {
+    let mut existing: HashSet<&str> = existing.clone();
+    let mut ops = Vec::new();
+    for update in updates {
+        let sides = [
+            (&update.taker_order_key, &update.taker_oid, update.taker_state, update.del_taker_flag),
+            (&update.maker_order_key, &update.maker_oid, update.maker_state, update.del_maker_flag),
+        ];
+        for (key, oid, state, del) in sides {
+            if !existing.contains(key.as_str()) {
+                continue;
+            }
+            if del {
+                existing.remove(key.as_str());
+                ops.push(CacheOp::Remove { oid_key: update.oid_key.clone(), order_key: key.clone(), oid: oid.clone() });
+                continue;
+            }
+            if update.qty > 0 {
+                ops.push(CacheOp::Incr { key: key.clone(), field: "acc_fill_qty", delta: update.qty as i64 });
+            }
+            ops.push(CacheOp::Set { key: key.clone(), field: "state", value: state.to_string() });
+            ops.push(CacheOp::Set { key: key.clone(), field: "update_ts", value: update.ts.to_string() });
+        }
+        if !existing.contains(update.taker_order_key.as_str()) {
+            continue;
+        }
+        // 减量结果更新委托数量
+        if update.reduce_qty > 0 {
+            ops.push(CacheOp::Incr { key: update.taker_order_key.clone(), field: "qty", delta: -(update.reduce_qty as i64) });
+        }
+        // 只挂单改价结果更新委托价格
+        if let Some(price) = &update.price {
+            ops.push(CacheOp::Set { key: update.taker_order_key.clone(), field: "price", value: price.clone() });
+        }
     }
+    ops
 }
 
+/// 一笔成交对taker和maker订单的修改
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OrderUpdate {
     /// 撮合数量
@@ -257,39 +786,54 @@ pub struct OrderUpdate {
     del_maker_flag: bool,
     /// 成交时间
     ts: u128,
+    /// 减少的委托数量
+    reduce_qty: u64,
+    /// 只挂单改价后的委托价格
+    #[serde(skip_serializing_if = "Option::is_none")]
+    price: Option<String>,
 }
 
 impl OrderUpdate {
     pub fn new(trade: &MatchTrade) -> OrderUpdate {
         OrderUpdate {
-            qty: trade.qty.clone(),
+            qty: trade.qty,
             oid_key: CacheManager::cache_key_id(&trade.symbol),
             taker_oid: trade.taker_oid.to_string(),
             maker_oid: trade.maker_oid.to_string(),
             taker_order_key: CacheManager::cache_key_order(&trade.symbol, trade.taker_oid),
             maker_order_key: CacheManager::cache_key_order(&trade.symbol, trade.maker_oid),
-            taker_state: trade.taker_state.clone(),
-            maker_state: trade.maker_state.clone(),
+            taker_state: trade.taker_state,
+            maker_state: trade.maker_state,
             del_taker_flag: trade.taker_state.del_flag(),
             del_maker_flag: trade.maker_state.del_flag(),
-            ts: trade.ts.clone(),
+            ts: trade.ts,
+            reduce_qty: trade.reduce_qty,
+            price: trade.taker_px.as_ref().filter(|_| trade.is_reprice()).map(|px| px.to_string()),
         }
     }
 }
 
 #[cfg(test)]
 pub mod test {
+    use std::collections::HashSet;
+    use std::sync::Arc;
+
     use bigdecimal::BigDecimal;
     use bigdecimal::num_traits::zero;
     use serde::{Deserialize, Serialize};
     use validator::Validate;
 
+    use loom_core::market::{MarketBook, MatchTrades};
     use loom_core::order::{Order, OrderAction, OrderState, OrderTimeInForce, OrderType, TradeSide};
     use loom_core::order::OrderTimeInForce::{GTC, IOC};
     use loom_core::order::OrderType::MARKET;
+    use loom_core::symbol::Symbol;
+    use loom_core::testing::new_order;
     use loom_core::utils;
 
-    use crate::cache::CacheManager;
+    use crate::cache::{plan_updates, CacheManager, CacheOp, OrderUpdate};
+    use crate::fault::{FaultConfig, FaultInjector, InjectedFault};
+    use crate::ledger::Balance;
 
     #[derive(Eq, PartialEq, Debug, Serialize, Deserialize, Validate)]
     pub struct MatchOrderParam {
@@ -320,10 +864,10 @@ pub mod test {
             let now_ts = self.ts.unwrap_or_else(|| { utils::now_ts() });
             Order {
                 id: self.id,
-                symbol: self.symbol.clone(),
+                symbol: Symbol::from(&self.symbol),
                 side: self.side,
                 qty: self.qty,
-                price: self.price.as_ref().map(|x| x.clone()).unwrap_or(zero()),
+                price: self.price.clone().unwrap_or(zero()),
                 acc_fill_qty: 0,
                 ord_type: self.ord_type,
                 ts: now_ts,
@@ -337,27 +881,116 @@ pub mod test {
                     }
                 }),
                 action: self.action,
+                account: None,
+                seq: 0,
+                expire_ts: 0,
+                ts_ns: 0,
+                valid_until_ts: 0,
+                tag: None,
             }
         }
     }
 
-    fn new_order() -> Order {
-        let json = "{\"id\":1,\"symbol\":\"LOOM-USDT-SPOT\",\"side\":\"SELL\",\"qty\":3,\"price\":100,\"ord_type\":\"LIMIT\",\"action\":\"PLACE\"}";
-        serde_json::from_str::<MatchOrderParam>(json).unwrap().to_order()
-    }
-
     async fn get_cache() -> CacheManager {
         return CacheManager::new("redis://localhost:6379").await.unwrap();
     }
 
+    #[test]
+    fn plan_updates_test() {
+        let mut book = MarketBook::new("LOOM-USDT-SPOT");
+        book.try_match(new_order(1, TradeSide::SELL, 3, "100"));
+        let mut buy = new_order(1, TradeSide::SELL, 3, "100");
+        buy.id = 2;
+        buy.side = TradeSide::BUY;
+        buy.qty = 2;
+        let mut updates: Vec<OrderUpdate> = book.try_match(buy).iter().map(OrderUpdate::new).collect();
+        let mut reduce = new_order(1, TradeSide::SELL, 3, "100");
+        reduce.action = OrderAction::REDUCE;
+        reduce.qty = 1;
+        let mut trades = MatchTrades::new();
+        book.try_reduce_into(reduce, &mut trades);
+        updates.extend(trades.iter().map(OrderUpdate::new));
+        let maker = CacheManager::cache_key_order("LOOM-USDT-SPOT", 1);
+        let taker = CacheManager::cache_key_order("LOOM-USDT-SPOT", 2);
+        let ts = updates[0].ts.to_string();
+        // 全部成交的taker删除，maker累加成交数量，剩余数量全部减掉后删除
+        let existing = HashSet::from([maker.as_str(), taker.as_str()]);
+        assert_eq!(plan_updates(&updates, &existing), vec![
+            CacheOp::Remove { oid_key: String::from("Loom:ID:LOOM-USDT-SPOT"), order_key: taker.clone(), oid: String::from("2") },
+            CacheOp::Incr { key: maker.clone(), field: "acc_fill_qty", delta: 2 },
+            CacheOp::Set { key: maker.clone(), field: "state", value: OrderState::PARTIAL_FILLED.to_string() },
+            CacheOp::Set { key: maker.clone(), field: "update_ts", value: ts },
+            CacheOp::Remove { oid_key: String::from("Loom:ID:LOOM-USDT-SPOT"), order_key: maker.clone(), oid: String::from("1") },
+        ]);
+        // 订单哈希不存在时不修改，避免写入残缺的订单
+        assert!(plan_updates(&updates, &HashSet::new()).is_empty());
+    }
+
+    #[test]
+    fn scoped_key_test() {
+        assert_eq!(CacheManager::cache_key(&new_order(1, TradeSide::SELL, 3, "100")).1, "Loom:ORDER:LOOM-USDT-SPOT:1");
+        let mut order = new_order(1, TradeSide::SELL, 3, "100");
+        order.symbol = "acme.LOOM-USDT-SPOT".into();
+        assert_eq!(CacheManager::cache_key(&order), (String::from("Loom:acme:ID:LOOM-USDT-SPOT"), String::from("Loom:acme:ORDER:LOOM-USDT-SPOT:1")));
+        assert_eq!(CacheManager::cache_key_global_trades("LOOM-USDT-SPOT"), "Loom:TRADES");
+        assert_eq!(CacheManager::cache_key_global_trades(&order.symbol), "Loom:acme:TRADES");
+    }
+
+    #[tokio::test]
+    async fn fault_test() {
+        let faults = Arc::new(FaultInjector::new(FaultConfig::default()));
+        let cache = get_cache().await.with_faults(faults.clone());
+        // 模拟Redis中断，不会访问Redis
+        faults.set_down(true);
+        let err = cache.add_if_absent(new_order(1, TradeSide::SELL, 3, "100")).await.unwrap_err();
+        assert!(err.downcast_ref::<InjectedFault>().is_some());
+        assert!(cache.ping().await.unwrap_err().is::<InjectedFault>());
+        assert_eq!(faults.injected(), 2);
+    }
+
     #[tokio::test]
     #[ignore]
     async fn add_test() {
         let cache = get_cache().await;
         // 先删除
-        cache.del(&new_order()).await.unwrap();
-        assert!(cache.add_if_absent(new_order()).await.unwrap());
-        assert!(!cache.add_if_absent(new_order()).await.unwrap());
+        cache.del(&new_order(1, TradeSide::SELL, 3, "100")).await.unwrap();
+        assert!(cache.add_if_absent(new_order(1, TradeSide::SELL, 3, "100")).await.unwrap());
+        assert!(!cache.add_if_absent(new_order(1, TradeSide::SELL, 3, "100")).await.unwrap());
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn idempotency_test() {
+        let cache = get_cache().await;
+        let ttl = std::time::Duration::from_secs(5);
+        cache.release_idempotency("test").await.unwrap();
+        assert_eq!(cache.claim_idempotency("test", "PENDING", ttl).await.unwrap(), None);
+        assert_eq!(cache.claim_idempotency("test", "PENDING", ttl).await.unwrap().as_deref(), Some("PENDING"));
+        cache.store_idempotency("test", "DONE", ttl).await.unwrap();
+        assert_eq!(cache.claim_idempotency("test", "PENDING", ttl).await.unwrap().as_deref(), Some("DONE"));
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn journal_test() {
+        let cache = get_cache().await;
+        let tail = cache.journal_tail("LOOM-USDT-SPOT").await.unwrap();
+        let mut order = new_order(1, TradeSide::SELL, 3, "100");
+        order.seq = 7;
+        cache.append_journal(&order, 1000).await.unwrap();
+        let entries = cache.read_journal("LOOM-USDT-SPOT", &tail, 10, None).await.unwrap();
+        assert_eq!(entries.last().unwrap().1.seq, 7);
+        assert!(cache.read_journal("LOOM-USDT-SPOT", &entries.last().unwrap().0, 10, None).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn balance_test() {
+        let cache = get_cache().await;
+        let balance = Balance { available: BigDecimal::from(7), frozen: BigDecimal::from(3) };
+        cache.set_balances(&[("alice".to_string(), "USDT".to_string(), balance.clone())]).await.unwrap();
+        let balances = cache.get_balances().await.unwrap();
+        assert!(balances.contains(&("alice".to_string(), "USDT".to_string(), balance)));
     }
 
     #[tokio::test]
@@ -366,7 +999,7 @@ pub mod test {
         let ids = vec![1];
         let orders = get_cache().await.get_orders_by_ids("LOOM-USDT-SPOT", &ids).await.unwrap();
         assert_eq!(orders.len(), 1);
-        assert_eq!(orders.get(0).unwrap().id, 1);
+        assert_eq!(orders.first().unwrap().id, 1);
     }
 
     #[tokio::test]
@@ -383,14 +1016,14 @@ pub mod test {
     #[tokio::test]
     #[ignore]
     async fn del_test() {
-        let order = new_order();
+        let order = new_order(1, TradeSide::SELL, 3, "100");
         let cache = get_cache().await;
         // 添加
         cache.add_if_absent(order.clone()).await.unwrap();
         // 执行删除
         cache.del(&order).await.unwrap();
         // 再次查询
-        let orders = cache.get_orders_by_ids(&order.symbol, &vec![1]).await.unwrap();
+        let orders = cache.get_orders_by_ids(&order.symbol, &[1]).await.unwrap();
         assert_eq!(orders.len(), 0);
     }
 }
\ No newline at end of file
@@ -0,0 +1,217 @@
+use std::collections::VecDeque;
+
+use anyhow::anyhow;
+use bigdecimal::{BigDecimal, One, Zero};
+use serde::{Deserialize, Serialize};
+
+use loom_core::market::MatchTrade;
+
+/// 默认统计窗口，毫秒
+pub const DEFAULT_COLLAR_WINDOW_MS: u64 = 60_000;
+
+/// EMA的默认平滑系数
+const DEFAULT_EMA_ALPHA: &str = "0.2";
+
+/// 均价保留的小数位数
+const AVERAGE_SCALE: i64 = 12;
+
+/// 价格熔断配置，订单可能成交的价格偏离近期成交均价超过pct百分比时触发
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct CollarConfig {
+    /// 允许偏离均价的最大百分比
+    pub pct: BigDecimal,
+    /// 均价的计算方式，默认VWAP
+    pub basis: Option<CollarBasis>,
+    /// 统计窗口，毫秒，默认60000，窗口内没有成交时不检查
+    pub window_ms: Option<u64>,
+    /// EMA的平滑系数，取值(0, 1]，默认0.2
+    pub ema_alpha: Option<BigDecimal>,
+    /// 窗口内至少有多少笔成交才检查，默认1
+    pub min_trades: Option<usize>,
+    /// 触发后的处理方式，默认拒绝订单
+    pub action: Option<CollarAction>,
+}
+
+/// 均价的计算方式
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub enum CollarBasis {
+    /// 窗口内的成交量加权均价
+    #[default]
+    Vwap,
+    /// 成交价的指数移动平均
+    Ema,
+}
+
+/// 触发价格熔断后的处理方式
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub enum CollarAction {
+    /// 拒绝订单
+    #[default]
+    Reject,
+    /// 拒绝订单并以拒绝方式暂停交易对，需要人工恢复
+    Halt,
+}
+
+/// 价格熔断，由交易员按成交更新滚动统计，在撮合前检查订单
+#[derive(Debug, Clone)]
+pub struct PriceCollar {
+    pct: BigDecimal,
+    basis: CollarBasis,
+    window: u128,
+    alpha: BigDecimal,
+    min_trades: usize,
+    action: CollarAction,
+    /// 窗口内的成交时间、价格和数量
+    fills: VecDeque<(u128, BigDecimal, u64)>,
+    /// 窗口内的成交金额和数量
+    notional: BigDecimal,
+    qty: u64,
+    ema: Option<BigDecimal>,
+}
+
+impl PriceCollar {
+    pub fn new(config: &CollarConfig) -> anyhow::Result<PriceCollar> {
+        if config.pct <= BigDecimal::zero() {
+            return Err(anyhow!("invalid collar pct, pct={}", config.pct));
+        }
+        let alpha = config.ema_alpha.clone().unwrap_or_else(|| DEFAULT_EMA_ALPHA.parse().unwrap());
+        if alpha <= BigDecimal::zero() || alpha > BigDecimal::one() {
+            return Err(anyhow!("invalid collar ema alpha, alpha={}", alpha));
+        }
+        Ok(PriceCollar {
+            pct: config.pct.clone(),
+            basis: config.basis.unwrap_or_default(),
+            window: config.window_ms.unwrap_or(DEFAULT_COLLAR_WINDOW_MS) as u128,
+            alpha,
+            min_trades: config.min_trades.unwrap_or(1).max(1),
+            action: config.action.unwrap_or_default(),
+            fills: VecDeque::new(),
+            notional: BigDecimal::zero(),
+            qty: 0,
+            ema: None,
+        })
+    }
+
+    pub fn action(&self) -> CollarAction {
+        self.action
+    }
+
+    /// 统计成交，撤单和拒绝结果不计入
+    pub fn record(&mut self, trades: &[MatchTrade]) {
+        for trade in trades.iter().filter(|t| t.qty > 0 && t.maker_oid != 0) {
+            self.notional += &trade.px * BigDecimal::from(trade.qty);
+            self.qty += trade.qty;
+            self.fills.push_back((trade.ts, trade.px.clone(), trade.qty));
+            self.ema = Some(match self.ema.take() {
+                Some(ema) => (&self.alpha * &trade.px + (BigDecimal::one() - &self.alpha) * ema).with_scale(AVERAGE_SCALE),
+                None => trade.px.clone(),
+            });
+        }
+    }
+
+    fn evict(&mut self, now: u128) {
+        while let Some((ts, px, qty)) = self.fills.front() {
+            if ts + self.window > now {
+                break;
+            }
+            self.notional -= px * BigDecimal::from(*qty);
+            self.qty -= qty;
+            self.fills.pop_front();
+        }
+        if self.fills.is_empty() {
+            // 窗口内没有成交，EMA重新开始计算
+            self.ema = None;
+        }
+    }
+
+    /// now时刻的均价，窗口内成交不足时返回None
+    pub fn average(&mut self, now: u128) -> Option<BigDecimal> {
+        self.evict(now);
+        if self.fills.len() < self.min_trades || self.qty == 0 {
+            return None;
+        }
+        match self.basis {
+            CollarBasis::Vwap => Some((&self.notional / BigDecimal::from(self.qty)).with_scale(AVERAGE_SCALE)),
+            CollarBasis::Ema => self.ema.clone(),
+        }
+    }
+
+    /// 检查可能的成交价，偏离均价超过阈值时返回均价和偏离百分比
+    pub fn breach(&mut self, px: &BigDecimal, now: u128) -> Option<(BigDecimal, BigDecimal)> {
+        let average = self.average(now)?;
+        if average.is_zero() {
+            return None;
+        }
+        let deviation = (px - &average).abs() * BigDecimal::from(100) / &average;
+        (deviation > self.pct).then(|| (average, deviation.round(2)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use bigdecimal::BigDecimal;
+
+    use loom_core::market::{MarketBook, MatchTrade};
+    use loom_core::order::{Order, TradeSide};
+
+    use crate::collar::{CollarBasis, CollarConfig, PriceCollar};
+
+    fn config(basis: CollarBasis) -> CollarConfig {
+        CollarConfig {
+            pct: BigDecimal::from(5),
+            basis: Some(basis),
+            window_ms: Some(1000),
+            ema_alpha: Some(BigDecimal::from_str("0.5").unwrap()),
+            min_trades: None,
+            action: None,
+        }
+    }
+
+    fn fill(px: u64, qty: u64, ts: u128) -> Vec<MatchTrade> {
+        let mut book = MarketBook::new("LOOM-USDT-SPOT");
+        let order = |id: u64, side: TradeSide| Order {
+            id,
+            symbol: "LOOM-USDT-SPOT".into(),
+            side,
+            qty,
+            price: BigDecimal::from(px),
+            ..Default::default()
+        };
+        book.try_match(order(1, TradeSide::SELL));
+        let mut trades = book.try_match(order(2, TradeSide::BUY)).to_vec();
+        trades.iter_mut().for_each(|t| t.ts = ts);
+        trades
+    }
+
+    #[test]
+    fn vwap_test() {
+        let mut collar = PriceCollar::new(&config(CollarBasis::Vwap)).unwrap();
+        // 没有成交时不检查
+        assert_eq!(collar.breach(&BigDecimal::from(1000), 0), None);
+        collar.record(&fill(100, 3, 1000));
+        collar.record(&fill(104, 1, 1500));
+        assert_eq!(collar.average(1500), Some(BigDecimal::from(101)));
+        assert_eq!(collar.breach(&BigDecimal::from(106), 1500), None);
+        assert_eq!(collar.breach(&BigDecimal::from(95), 1500).unwrap().0, BigDecimal::from(101));
+        // 第一笔成交移出窗口
+        assert_eq!(collar.average(2000), Some(BigDecimal::from(104)));
+        assert!(collar.breach(&BigDecimal::from(98), 2000).is_some());
+        assert_eq!(collar.average(2500), None);
+
+        assert!(PriceCollar::new(&CollarConfig { pct: BigDecimal::from(0), ..config(CollarBasis::Vwap) }).is_err());
+        assert!(PriceCollar::new(&CollarConfig { ema_alpha: Some(BigDecimal::from(2)), ..config(CollarBasis::Ema) }).is_err());
+    }
+
+    #[test]
+    fn ema_test() {
+        let mut collar = PriceCollar::new(&CollarConfig { min_trades: Some(2), ..config(CollarBasis::Ema) }).unwrap();
+        collar.record(&fill(100, 1, 1000));
+        assert_eq!(collar.average(1000), None);
+        collar.record(&fill(110, 5, 1100));
+        assert_eq!(collar.average(1100), Some(BigDecimal::from(105)));
+        assert!(collar.breach(&BigDecimal::from(99), 1100).is_some());
+        assert!(collar.breach(&BigDecimal::from(100), 1100).is_none());
+    }
+}
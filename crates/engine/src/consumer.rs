@@ -1,19 +1,47 @@
+use std::borrow::Cow;
+#[cfg(feature = "fault-injection")]
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "clickhouse")]
+use anyhow::anyhow;
 use async_trait::async_trait;
-use log::info;
+#[cfg(feature = "clickhouse")]
+use log::debug;
+use log::{info, warn};
+use tokio::sync::broadcast;
+#[cfg(feature = "clickhouse")]
+use url::Url;
 
-use loom_core::market::MatchTrade;
+use loom_core::market::{MatchTrade, MatchTrades};
 
-use crate::cache::CacheManager;
+use crate::archive::TradeArchive;
+#[cfg(feature = "redis")]
+use crate::cache::{CacheManager, TradeStream};
+#[cfg(feature = "fault-injection")]
+use crate::fault::FaultInjector;
+#[cfg(feature = "clickhouse")]
+use crate::http_client;
+use crate::trader::Backpressure;
 
 #[derive(Debug, Clone)]
 pub enum TradeConsumer {
     Console(ConsoleConsumer),
+    #[cfg(feature = "redis")]
     RedisQueue(RedisQueueConsumer),
+    #[cfg(feature = "clickhouse")]
+    ClickHouse(ClickHouseConsumer),
+    Buffered(BufferedConsumer),
+    Archived(ArchivedConsumer),
+    Redacted(RedactedConsumer),
+    Broadcast(BroadcastConsumer),
+    #[cfg(feature = "fault-injection")]
+    Faulty(FaultyConsumer),
 }
 
 #[async_trait]
 pub trait Consumer {
-    async fn consume(&self, trades: Vec<MatchTrade>) -> anyhow::Result<()>;
+    async fn consume(&self, trades: &[MatchTrade]) -> anyhow::Result<()>;
 }
 
 #[derive(Debug, Clone)]
@@ -21,49 +49,669 @@ pub struct ConsoleConsumer {}
 
 #[async_trait]
 impl Consumer for ConsoleConsumer {
-    async fn consume(&self, trades: Vec<MatchTrade>) -> anyhow::Result<()> {
-        info!("{}", serde_json::to_string(&trades)?);
+    async fn consume(&self, trades: &[MatchTrade]) -> anyhow::Result<()> {
+        info!("{}", serde_json::to_string(trades)?);
         Ok(())
     }
 }
 
 impl TradeConsumer {
-    pub async fn consume(&self, trades: Vec<MatchTrade>) -> anyhow::Result<()> {
+    /// 消费成交，返回后trades被清空，调用方可复用该缓冲区
+    pub async fn consume(&mut self, trades: &mut MatchTrades) -> anyhow::Result<()> {
+        let result = match self {
+            TradeConsumer::Buffered(consumer) => consumer.consume(trades).await,
+            _ => self.consume_slice(trades).await,
+        };
+        trades.clear();
+        result
+    }
+
+    async fn consume_slice(&mut self, trades: &[MatchTrade]) -> anyhow::Result<()> {
         match self {
             TradeConsumer::Console(consumer) => {
                 consumer.consume(trades).await?;
             }
+            #[cfg(feature = "redis")]
             TradeConsumer::RedisQueue(consumer) => {
                 consumer.consume(trades).await?;
             }
+            #[cfg(feature = "clickhouse")]
+            TradeConsumer::ClickHouse(consumer) => {
+                consumer.consume(trades).await?;
+            }
+            TradeConsumer::Buffered(consumer) => {
+                consumer.extend(trades.iter().cloned()).await?;
+            }
+            TradeConsumer::Archived(consumer) => {
+                consumer.archive.append(trades)?;
+                Box::pin(consumer.inner.consume_slice(trades)).await?;
+            }
+            TradeConsumer::Redacted(consumer) => {
+                let trades = RedactedConsumer::redact(trades);
+                Box::pin(consumer.inner.consume_slice(&trades)).await?;
+            }
+            TradeConsumer::Broadcast(consumer) => {
+                if let Some(inner) = consumer.inner.as_mut() {
+                    Box::pin(inner.consume_slice(trades)).await?;
+                }
+                consumer.publish(trades);
+            }
+            #[cfg(feature = "fault-injection")]
+            TradeConsumer::Faulty(consumer) => {
+                consumer.faults.before("consume").await?;
+                // 模拟只推送了前一部分成交后下游失败
+                let written = consumer.faults.partial(trades.len());
+                Box::pin(consumer.inner.consume_slice(&trades[..written.unwrap_or(trades.len())])).await?;
+                if let Some(written) = written {
+                    return Err(consumer.faults.partial_fault("consume", written));
+                }
+            }
         }
         Ok(())
     }
+
+    /// 将缓冲区中的成交立即推送到下游，非缓冲消费器无需处理
+    pub async fn flush(&mut self) -> anyhow::Result<()> {
+        match self {
+            TradeConsumer::Buffered(consumer) => consumer.flush().await?,
+            TradeConsumer::Archived(consumer) => Box::pin(consumer.inner.flush()).await?,
+            TradeConsumer::Redacted(consumer) => Box::pin(consumer.inner.flush()).await?,
+            TradeConsumer::Broadcast(BroadcastConsumer { inner: Some(inner), .. }) => Box::pin(inner.flush()).await?,
+            #[cfg(feature = "fault-injection")]
+            TradeConsumer::Faulty(consumer) => Box::pin(consumer.inner.flush()).await?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// 探测下游是否可写入，用于启动预热
+    pub async fn probe(&self) -> anyhow::Result<()> {
+        match self {
+            TradeConsumer::Console(_) => Ok(()),
+            #[cfg(feature = "redis")]
+            TradeConsumer::RedisQueue(consumer) => consumer.cache_manager.ping().await,
+            #[cfg(feature = "clickhouse")]
+            TradeConsumer::ClickHouse(consumer) => consumer.probe().await,
+            TradeConsumer::Buffered(consumer) => Box::pin(consumer.inner.probe()).await,
+            TradeConsumer::Archived(consumer) => Box::pin(consumer.inner.probe()).await,
+            TradeConsumer::Redacted(consumer) => Box::pin(consumer.inner.probe()).await,
+            TradeConsumer::Broadcast(consumer) => match &consumer.inner {
+                Some(inner) => Box::pin(inner.probe()).await,
+                None => Ok(()),
+            },
+            #[cfg(feature = "fault-injection")]
+            TradeConsumer::Faulty(consumer) => {
+                consumer.faults.before("probe").await?;
+                Box::pin(consumer.inner.probe()).await
+            }
+        }
+    }
+
+    /// 定时刷新的间隔，None表示无需定时刷新
+    pub fn flush_interval(&self) -> Option<Duration> {
+        match self {
+            TradeConsumer::Buffered(consumer) => Some(consumer.max_delay),
+            TradeConsumer::Archived(consumer) => consumer.inner.flush_interval(),
+            TradeConsumer::Redacted(consumer) => consumer.inner.flush_interval(),
+            TradeConsumer::Broadcast(consumer) => consumer.inner.as_ref().and_then(|inner| inner.flush_interval()),
+            #[cfg(feature = "fault-injection")]
+            TradeConsumer::Faulty(consumer) => consumer.inner.flush_interval(),
+            _ => None,
+        }
+    }
 }
 
+#[cfg(feature = "redis")]
 #[derive(Clone, Debug)]
 pub struct RedisQueueConsumer {
     cache_manager: CacheManager,
+    /// 成交队列的分区方式
+    stream: TradeStream,
 }
 
+#[cfg(feature = "redis")]
 impl RedisQueueConsumer {
     pub async fn new(uri: &str) -> anyhow::Result<RedisQueueConsumer> {
         Ok(RedisQueueConsumer {
-            cache_manager: CacheManager::new(uri).await?
+            cache_manager: CacheManager::new(uri).await?,
+            stream: TradeStream::default(),
         })
     }
 
     pub async fn new_with_cache_manager(cache_manager: CacheManager) -> anyhow::Result<RedisQueueConsumer> {
         Ok(RedisQueueConsumer {
-            cache_manager
+            cache_manager,
+            stream: TradeStream::default(),
         })
     }
+
+    /// 设置成交队列的分区方式
+    pub fn with_stream(mut self, stream: TradeStream) -> RedisQueueConsumer {
+        self.stream = stream;
+        self
+    }
 }
 
+#[cfg(feature = "redis")]
 #[async_trait]
 impl Consumer for RedisQueueConsumer {
-    async fn consume(&self, trades: Vec<MatchTrade>) -> anyhow::Result<()> {
-        self.cache_manager.offer_trades(trades).await?;
+    async fn consume(&self, trades: &[MatchTrade]) -> anyhow::Result<()> {
+        self.cache_manager.offer_trades(trades, self.stream).await?;
+        Ok(())
+    }
+}
+
+/// ClickHouse消费器，通过HTTP接口将成交以JSONEachRow格式批量写入分析表
+#[cfg(feature = "clickhouse")]
+#[derive(Clone, Debug)]
+pub struct ClickHouseConsumer {
+    /// 复用连接的HTTP客户端
+    client: http_client::HttpClient,
+    /// 携带INSERT语句的写入路径
+    insert_path: String,
+    /// 执行探测查询的路径
+    query_path: String,
+    /// 用户名
+    username: Option<String>,
+    /// 密码
+    password: Option<String>,
+}
+
+#[cfg(feature = "clickhouse")]
+impl ClickHouseConsumer {
+    /// 构造ClickHouse消费器，url为HTTP接口地址，例如 http://localhost:8123，只支持http协议，
+    /// timeout为单次请求的超时时间，超时的写入按失败处理
+    pub fn new(url: &str, table: &str, username: Option<String>, password: Option<String>, timeout: Duration) -> anyhow::Result<ClickHouseConsumer> {
+        let query_url = Url::parse(url)?;
+        let mut insert_url = query_url.clone();
+        insert_url.query_pairs_mut()
+            .append_pair("query", &format!("INSERT INTO {} FORMAT JSONEachRow", table));
+        Ok(ClickHouseConsumer {
+            client: http_client::HttpClient::new(url, timeout)?,
+            insert_path: insert_url[url::Position::BeforePath..].to_string(),
+            query_path: query_url[url::Position::BeforePath..].to_string(),
+            username,
+            password,
+        })
+    }
+
+    fn encode(trades: &[MatchTrade]) -> anyhow::Result<Vec<u8>> {
+        let mut body = Vec::with_capacity(trades.len() * 256);
+        for trade in trades {
+            serde_json::to_writer(&mut body, trade)?;
+            body.push(b'\n');
+        }
+        Ok(body)
+    }
+
+    fn headers<'a>(&'a self, content_type: &'a str) -> Vec<(&'a str, &'a str)> {
+        let mut headers = vec![("Content-Type", content_type)];
+        if let Some(username) = &self.username {
+            headers.push(("X-ClickHouse-User", username));
+        }
+        if let Some(password) = &self.password {
+            headers.push(("X-ClickHouse-Key", password));
+        }
+        headers
+    }
+
+    /// 执行SELECT 1，校验地址与凭据
+    async fn probe(&self) -> anyhow::Result<()> {
+        let resp = self.client.post(&self.query_path, &self.headers("text/plain"), b"SELECT 1").await?;
+        if !resp.is_success() {
+            return Err(anyhow!("clickhouse probe failed, status={}, body={}", resp.status, resp.body_text()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "clickhouse")]
+#[async_trait]
+impl Consumer for ClickHouseConsumer {
+    async fn consume(&self, trades: &[MatchTrade]) -> anyhow::Result<()> {
+        if trades.is_empty() {
+            return Ok(());
+        }
+        let body = Self::encode(trades)?;
+        let resp = self.client.post(&self.insert_path, &self.headers("application/x-ndjson"), &body).await?;
+        if !resp.is_success() {
+            return Err(anyhow!("clickhouse insert failed, status={}, body={}", resp.status, resp.body_text()));
+        }
+        debug!("CLICKHOUSE INSERTED: trades_cnt={}", trades.len());
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// 归档消费器，先将成交写入归档再推送到下游消费器
+#[derive(Clone, Debug)]
+pub struct ArchivedConsumer {
+    /// 下游消费器
+    inner: Box<TradeConsumer>,
+    archive: TradeArchive,
+}
+
+impl ArchivedConsumer {
+    pub fn new(inner: TradeConsumer, archive: TradeArchive) -> ArchivedConsumer {
+        ArchivedConsumer {
+            inner: Box::new(inner),
+            archive,
+        }
+    }
+}
+
+/// 脱敏消费器，去掉成交中的taker和maker账户后推送到下游消费器
+#[derive(Clone, Debug)]
+pub struct RedactedConsumer {
+    /// 下游消费器
+    inner: Box<TradeConsumer>,
+}
+
+impl RedactedConsumer {
+    pub fn new(inner: TradeConsumer) -> RedactedConsumer {
+        RedactedConsumer {
+            inner: Box::new(inner),
+        }
+    }
+
+    /// 成交都不带账户时直接使用原成交，避免复制
+    fn redact(trades: &[MatchTrade]) -> Cow<'_, [MatchTrade]> {
+        if trades.iter().all(|t| t.taker_account.is_none() && t.maker_account.is_none()) {
+            return Cow::Borrowed(trades);
+        }
+        Cow::Owned(trades.iter().cloned().map(|mut trade| {
+            trade.taker_account = None;
+            trade.maker_account = None;
+            trade
+        }).collect())
+    }
+}
+
+/// 广播消费器，下游消费器写入成功后将成交广播给订阅者，订阅者处理过慢时丢弃最早的成交
+#[derive(Clone, Debug)]
+pub struct BroadcastConsumer {
+    sender: broadcast::Sender<MatchTrade>,
+    /// 下游消费器，为空时只广播
+    inner: Option<Box<TradeConsumer>>,
+}
+
+impl BroadcastConsumer {
+    pub fn new(sender: broadcast::Sender<MatchTrade>, inner: Option<TradeConsumer>) -> BroadcastConsumer {
+        BroadcastConsumer {
+            sender,
+            inner: inner.map(Box::new),
+        }
+    }
+
+    /// 没有订阅者时直接丢弃
+    fn publish(&self, trades: &[MatchTrade]) {
+        if self.sender.receiver_count() == 0 {
+            return;
+        }
+        for trade in trades {
+            let _ = self.sender.send(trade.clone());
+        }
+    }
+}
+
+/// 故障注入消费器，按注入器的配置延迟、失败或只推送部分成交，仅用于测试
+#[cfg(feature = "fault-injection")]
+#[derive(Clone, Debug)]
+pub struct FaultyConsumer {
+    /// 下游消费器
+    inner: Box<TradeConsumer>,
+    faults: Arc<FaultInjector>,
+}
+
+#[cfg(feature = "fault-injection")]
+impl FaultyConsumer {
+    pub fn new(inner: TradeConsumer, faults: Arc<FaultInjector>) -> FaultyConsumer {
+        FaultyConsumer {
+            inner: Box::new(inner),
+            faults,
+        }
+    }
+}
+
+/// 缓冲区默认最多保留的成交数量
+pub const DEFAULT_BUFFER_LIMIT: usize = 100_000;
+/// 推送失败后首次重试的等待时间
+const FLUSH_RETRY_MIN: Duration = Duration::from_millis(10);
+/// 推送失败后重试等待时间的上限
+const FLUSH_RETRY_MAX: Duration = Duration::from_secs(1);
+
+/// 缓冲消费器，跨多轮撮合累积成交，按数量或时间阈值批量推送到下游消费器
+///
+/// 下游失败时成交留在缓冲区，按指数退避重试；缓冲区达到上限后按背压策略等待下游恢复或丢弃新成交
+#[derive(Clone, Debug)]
+pub struct BufferedConsumer {
+    /// 下游消费器
+    inner: Box<TradeConsumer>,
+    /// 成交缓冲区
+    buffer: Vec<MatchTrade>,
+    /// 缓冲区达到该数量时立即推送
+    max_trades: usize,
+    /// 成交在缓冲区中停留的最长时间
+    max_delay: Duration,
+    /// 缓冲区中最早一笔成交的写入时间
+    first_buffered_at: Option<Instant>,
+    /// 缓冲区最多保留的成交数量
+    limit: usize,
+    /// 缓冲区已满时的处理策略，`Block`等待下游恢复，其他策略丢弃新成交
+    overflow: Backpressure,
+    /// 推送失败后下一次自动重试的时间
+    retry_at: Option<Instant>,
+    /// 下一次失败后的重试等待时间
+    retry_delay: Duration,
+    /// 缓冲区已满时丢弃的成交数量
+    dropped: u64,
+}
+
+impl BufferedConsumer {
+    pub fn new(inner: TradeConsumer, max_trades: usize, max_delay: Duration) -> BufferedConsumer {
+        BufferedConsumer {
+            inner: Box::new(inner),
+            buffer: Vec::with_capacity(max_trades),
+            max_trades: max_trades.max(1),
+            max_delay,
+            first_buffered_at: None,
+            limit: DEFAULT_BUFFER_LIMIT,
+            overflow: Backpressure::Block,
+            retry_at: None,
+            retry_delay: FLUSH_RETRY_MIN,
+            dropped: 0,
+        }
+    }
+
+    /// 设置缓冲区上限和达到上限时的处理策略，上限不小于批量推送的数量
+    pub fn with_limit(mut self, limit: usize, overflow: Backpressure) -> BufferedConsumer {
+        self.limit = limit.max(self.max_trades);
+        self.overflow = overflow;
+        self
+    }
+
+    /// 将成交移入缓冲区
+    pub async fn consume(&mut self, trades: &mut MatchTrades) -> anyhow::Result<()> {
+        self.extend(trades.drain(..)).await
+    }
+
+    async fn extend(&mut self, trades: impl Iterator<Item=MatchTrade>) -> anyhow::Result<()> {
+        let before = (self.buffer.len(), self.dropped);
+        for trade in trades {
+            if self.buffer.len() >= self.limit {
+                match self.overflow {
+                    Backpressure::Block => self.flush_blocking().await,
+                    Backpressure::Reject | Backpressure::Shed => {
+                        self.dropped += 1;
+                        continue;
+                    }
+                }
+            }
+            self.buffer.push(trade);
+        }
+        if self.dropped > before.1 {
+            warn!("TRADE BUFFER FULL: dropped={}, pending={}", self.dropped - before.1, self.buffer.len());
+        }
+        if self.buffer.len() == before.0 {
+            return Ok(());
+        }
+        if self.first_buffered_at.is_none() {
+            self.first_buffered_at = Some(Instant::now());
+        }
+        // 推送失败后等到退避结束再自动重试，期间成交继续留在缓冲区
+        let due = self.retry_at.is_none_or(|at| Instant::now() >= at);
+        if due && (self.buffer.len() >= self.max_trades || self.expired()) {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    /// 推送缓冲区中的全部成交
+    ///
+    /// 推送失败时去掉下游已写入的前缀，保留其余成交和最早写入时间，退避结束后的下一次写入或定时刷新时重试
+    pub async fn flush(&mut self) -> anyhow::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        if let Err(e) = Box::pin(self.inner.consume_slice(&self.buffer)).await {
+            let written = written_before(&e).min(self.buffer.len());
+            self.buffer.drain(..written);
+            self.retry_at = Some(Instant::now() + self.retry_delay);
+            self.retry_delay = (self.retry_delay * 2).min(FLUSH_RETRY_MAX);
+            return Err(e);
+        }
+        // 推送后清空缓冲区，保留已分配的容量
+        self.buffer.clear();
+        self.first_buffered_at = None;
+        self.retry_at = None;
+        self.retry_delay = FLUSH_RETRY_MIN;
+        Ok(())
+    }
+
+    /// 缓冲区已满时等待下游恢复，按退避间隔重试直到推送成功
+    async fn flush_blocking(&mut self) {
+        loop {
+            if let Some(at) = self.retry_at {
+                tokio::time::sleep_until(at.into()).await;
+            }
+            match self.flush().await {
+                Ok(()) => return,
+                Err(e) => warn!("TRADE BUFFER FULL, WAITING FOR CONSUMER: pending={}, err={}", self.buffer.len(), e),
+            }
+        }
+    }
+
+    /// 缓冲区中待推送的成交数量
+    pub fn pending(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// 缓冲区已满时累计丢弃的成交数量
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    fn expired(&self) -> bool {
+        self.first_buffered_at
+            .map(|at| at.elapsed() >= self.max_delay)
+            .unwrap_or(false)
+    }
+}
+
+/// 下游失败前已写入的成交数量，无法确定时按未写入处理
+fn written_before(e: &anyhow::Error) -> usize {
+    #[cfg(feature = "fault-injection")]
+    if let Some(fault) = e.downcast_ref::<crate::fault::InjectedFault>() {
+        return fault.written.unwrap_or(0);
+    }
+    let _ = e;
+    0
+}
+
+#[cfg(test)]
+mod test {
+    use std::borrow::Cow;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use bigdecimal::BigDecimal;
+
+    use smallvec::smallvec;
+
+    use loom_core::market::{MatchTrade, MatchTrades};
+    use loom_core::order::OrderState;
+
+    use crate::archive::TradeArchive;
+    #[cfg(feature = "clickhouse")]
+    use crate::consumer::ClickHouseConsumer;
+    use crate::consumer::{ArchivedConsumer, BufferedConsumer, ConsoleConsumer, FaultyConsumer, RedactedConsumer, TradeConsumer};
+    use crate::fault::{FaultConfig, FaultInjector, InjectedFault};
+    use crate::trader::Backpressure;
+
+    fn new_trade(oid: u64) -> MatchTrade {
+        MatchTrade {
+            id: oid,
+            symbol: "LOOM-USDT-SPOT".into(),
+            qty: 1,
+            px: BigDecimal::from(100),
+            taker_oid: oid,
+            maker_oid: 0,
+            taker_state: OrderState::FULL_FILLED,
+            maker_state: OrderState::FULL_FILLED,
+            ts: 0,
+            reduce_qty: 0,
+            reject: None,
+            taker_side: None,
+            taker_ord_type: None,
+            taker_px: None,
+            taker_remain: 0,
+            maker_remain: 0,
+            taker_account: None,
+            maker_account: None,
+            taker_tag: None,
+            maker_tag: None,
+        }
+    }
+
+    #[cfg(feature = "clickhouse")]
+    #[test]
+    fn clickhouse_encode_test() {
+        let consumer = ClickHouseConsumer::new("http://localhost:8123", "loom.trades", None, None, Duration::from_secs(1)).unwrap();
+        assert!(consumer.insert_path.starts_with("/?query=INSERT+INTO+loom.trades+FORMAT+JSONEachRow"));
+        assert!(ClickHouseConsumer::new("https://localhost:8443", "loom.trades", None, None, Duration::from_secs(1)).is_err());
+        let body = ClickHouseConsumer::encode(&[new_trade(1), new_trade(2)]).unwrap();
+        let body = String::from_utf8(body).unwrap();
+        assert_eq!(body.lines().count(), 2);
+    }
+
+    #[tokio::test]
+    async fn buffered_flush_by_size_test() {
+        let inner = TradeConsumer::Console(ConsoleConsumer {});
+        let mut consumer = BufferedConsumer::new(inner, 3, Duration::from_secs(60));
+        let mut trades: MatchTrades = smallvec![new_trade(1), new_trade(2)];
+        consumer.consume(&mut trades).await.unwrap();
+        assert!(trades.is_empty());
+        assert_eq!(consumer.pending(), 2);
+        trades.push(new_trade(3));
+        consumer.consume(&mut trades).await.unwrap();
+        assert_eq!(consumer.pending(), 0);
+    }
+
+    #[tokio::test]
+    async fn buffered_flush_by_delay_test() {
+        let inner = TradeConsumer::Console(ConsoleConsumer {});
+        let mut consumer = BufferedConsumer::new(inner, 100, Duration::from_millis(10));
+        consumer.consume(&mut smallvec![new_trade(1)]).await.unwrap();
+        assert_eq!(consumer.pending(), 1);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        consumer.consume(&mut smallvec![new_trade(2)]).await.unwrap();
+        assert_eq!(consumer.pending(), 0);
+    }
+
+    #[tokio::test]
+    async fn buffered_flush_retry_test() {
+        let faults = Arc::new(FaultInjector::new(FaultConfig::default()));
+        let inner = TradeConsumer::Faulty(FaultyConsumer::new(TradeConsumer::Console(ConsoleConsumer {}), faults.clone()));
+        let mut consumer = BufferedConsumer::new(inner, 2, Duration::from_secs(60));
+        // 下游中断时成交留在缓冲区
+        faults.set_down(true);
+        assert!(consumer.consume(&mut smallvec![new_trade(1), new_trade(2)]).await.is_err());
+        assert_eq!(consumer.pending(), 2);
+        // 退避期间的写入不重试推送
+        consumer.consume(&mut smallvec![new_trade(3)]).await.unwrap();
+        assert_eq!((consumer.pending(), faults.injected()), (3, 1));
+        assert!(consumer.flush().await.is_err());
+        assert_eq!(consumer.pending(), 3);
+        faults.set_down(false);
+        consumer.flush().await.unwrap();
+        assert_eq!(consumer.pending(), 0);
+    }
+
+    #[tokio::test]
+    async fn buffered_partial_write_test() {
+        let dir = std::env::temp_dir().join(format!("loom-buffered-partial-test-{}", std::process::id()));
+        let archive = TradeArchive::new(&dir);
+        let faults = Arc::new(FaultInjector::new(FaultConfig { partial_rate: Some(1.0), seed: Some(7), ..Default::default() }));
+        let inner = TradeConsumer::Archived(ArchivedConsumer::new(TradeConsumer::Console(ConsoleConsumer {}), archive.clone()));
+        let inner = TradeConsumer::Faulty(FaultyConsumer::new(inner, faults.clone()));
+        let mut consumer = BufferedConsumer::new(inner, 100, Duration::from_secs(60));
+        consumer.consume(&mut (1..=10).map(new_trade).collect()).await.unwrap();
+        let err = consumer.flush().await.unwrap_err();
+        let written = err.downcast_ref::<InjectedFault>().unwrap().written.unwrap();
+        // 已写入的前缀不再重复推送
+        assert_eq!(consumer.pending(), 10 - written);
+        faults.set_config(FaultConfig::default());
+        consumer.flush().await.unwrap();
+        let archived: Vec<u64> = archive.read("LOOM-USDT-SPOT", 0, 1).unwrap().iter().map(|t| t.id).collect();
+        assert_eq!(archived, (1..=10).collect::<Vec<_>>());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn buffered_overflow_test() {
+        let faults = Arc::new(FaultInjector::new(FaultConfig::default()));
+        let inner = TradeConsumer::Faulty(FaultyConsumer::new(TradeConsumer::Console(ConsoleConsumer {}), faults.clone()));
+        let mut consumer = BufferedConsumer::new(inner, 2, Duration::from_secs(60)).with_limit(3, Backpressure::Reject);
+        faults.set_down(true);
+        assert!(consumer.consume(&mut smallvec![new_trade(1), new_trade(2)]).await.is_err());
+        // 缓冲区已满时丢弃新成交
+        consumer.consume(&mut smallvec![new_trade(3), new_trade(4), new_trade(5)]).await.unwrap();
+        assert_eq!((consumer.pending(), consumer.dropped()), (3, 2));
+
+        // 等待策略在下游恢复后推送再接收新成交
+        let inner = TradeConsumer::Faulty(FaultyConsumer::new(TradeConsumer::Console(ConsoleConsumer {}), faults.clone()));
+        let mut consumer = BufferedConsumer::new(inner, 2, Duration::from_secs(60)).with_limit(2, Backpressure::Block);
+        assert!(consumer.consume(&mut smallvec![new_trade(1), new_trade(2)]).await.is_err());
+        let recover = faults.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            recover.set_down(false);
+        });
+        consumer.consume(&mut smallvec![new_trade(3)]).await.unwrap();
+        assert_eq!((consumer.pending(), consumer.dropped()), (1, 0));
+    }
+
+    #[tokio::test]
+    async fn redacted_consumer_test() {
+        let dir = std::env::temp_dir().join(format!("loom-redacted-consumer-test-{}", std::process::id()));
+        let archive = TradeArchive::new(&dir);
+        let redacted = TradeConsumer::Redacted(RedactedConsumer::new(TradeConsumer::Console(ConsoleConsumer {})));
+        let mut consumer = TradeConsumer::Archived(ArchivedConsumer::new(redacted, archive.clone()));
+        let mut trade = new_trade(1);
+        trade.taker_account = Some(String::from("alice"));
+        trade.maker_account = Some(String::from("bob"));
+        let redacted = RedactedConsumer::redact(std::slice::from_ref(&trade));
+        assert!(redacted.iter().all(|t| t.taker_account.is_none() && t.maker_account.is_none()));
+        assert!(matches!(RedactedConsumer::redact(&[new_trade(2)]), Cow::Borrowed(_)));
+        // 归档保留账户
+        consumer.consume(&mut smallvec![trade]).await.unwrap();
+        let archived = archive.read("LOOM-USDT-SPOT", 0, 1).unwrap();
+        assert_eq!(archived[0].maker_account.as_deref(), Some("bob"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn faulty_consumer_test() {
+        let dir = std::env::temp_dir().join(format!("loom-faulty-consumer-test-{}", std::process::id()));
+        let archive = TradeArchive::new(&dir);
+        let faults = Arc::new(FaultInjector::new(FaultConfig::default()));
+        let inner = TradeConsumer::Archived(ArchivedConsumer::new(TradeConsumer::Console(ConsoleConsumer {}), archive.clone()));
+        let mut consumer = TradeConsumer::Faulty(FaultyConsumer::new(inner, faults.clone()));
+
+        // 下游中断时不推送任何成交
+        faults.set_down(true);
+        let err = consumer.consume(&mut smallvec![new_trade(1)]).await.unwrap_err();
+        assert_eq!(err.downcast_ref::<InjectedFault>().unwrap().written, None);
+        faults.set_down(false);
+        consumer.consume(&mut smallvec![new_trade(2)]).await.unwrap();
+
+        // 部分写入时只有前written笔成交到达下游
+        faults.set_config(FaultConfig { partial_rate: Some(1.0), ..Default::default() });
+        let mut trades: MatchTrades = (3..8).map(new_trade).collect();
+        let err = consumer.consume(&mut trades).await.unwrap_err();
+        let written = err.downcast_ref::<InjectedFault>().unwrap().written.unwrap();
+        let archived: Vec<u64> = archive.read("LOOM-USDT-SPOT", 0, 1).unwrap().iter().map(|t| t.id).collect();
+        assert_eq!(archived, [2].into_iter().chain(3..3 + written as u64).collect::<Vec<_>>());
+        assert_eq!(faults.injected(), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
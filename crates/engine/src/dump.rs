@@ -0,0 +1,104 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use log::error;
+use serde::{Deserialize, Serialize};
+
+use loom_core::order::Order;
+use loom_core::snapshot::{BookDump, BookSnapshot};
+use loom_core::utils;
+
+use crate::engine::EngineHandle;
+
+/// 交易员转储
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraderDump {
+    pub symbol: String,
+    /// 完整订单簿，交易员无法响应时为空
+    pub book: Option<BookDump>,
+    /// 最新发布的档位快照，完整订单簿不可用时作为替代
+    pub snapshot: Option<BookSnapshot>,
+    /// 队列中尚未处理的撮合请求，只有交易员崩溃时才会取出
+    pub pending: Vec<Order>,
+    /// 队列中尚未处理的撮合请求数量
+    pub pending_count: usize,
+}
+
+/// 引擎转储
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EngineDump {
+    /// 转储时间
+    pub ts: u128,
+    /// 转储原因
+    pub reason: String,
+    pub traders: Vec<TraderDump>,
+}
+
+impl EngineDump {
+    pub fn new(reason: &str, traders: Vec<TraderDump>) -> EngineDump {
+        EngineDump {
+            ts: utils::now_ts(),
+            reason: String::from(reason),
+            traders,
+        }
+    }
+}
+
+/// 默认转储目录
+pub fn default_dump_dir() -> PathBuf {
+    std::env::temp_dir().join("loom")
+}
+
+/// 将转储写入dir目录，返回文件路径
+pub fn write_dump(dir: &Path, dump: &EngineDump) -> anyhow::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!("loom-dump-{}.json", dump.ts));
+    fs::write(&path, serde_json::to_vec_pretty(dump)?)?;
+    Ok(path)
+}
+
+/// 安装panic钩子，进程panic时将各交易对最新快照写入转储目录，随后执行原有钩子
+///
+/// 交易员撮合中panic时由交易员自行转储完整订单簿和队列，这里只能读取已发布的快照
+pub fn install_panic_hook(engine: EngineHandle, dir: PathBuf) {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let traders = engine.symbols()
+            .into_iter()
+            .map(|symbol| TraderDump {
+                snapshot: engine.snapshot(&symbol).map(|s| BookSnapshot::clone(&s)),
+                pending_count: engine.sender(&symbol).map(|s| s.pending()).unwrap_or(0),
+                symbol,
+                book: None,
+                pending: Vec::new(),
+            })
+            .collect();
+        let dump = EngineDump::new(&format!("panic: {}", info), traders);
+        match write_dump(&dir, &dump) {
+            Ok(path) => error!("PANIC DUMP WRITTEN: path={}", path.display()),
+            Err(e) => error!("PANIC DUMP FAILED: err={}", e),
+        }
+        previous(info);
+    }));
+}
+
+#[cfg(test)]
+mod test {
+    use crate::dump::{write_dump, EngineDump, TraderDump};
+
+    #[test]
+    fn write_dump_test() {
+        let dir = std::env::temp_dir().join(format!("loom-dump-test-{}", std::process::id()));
+        let dump = EngineDump::new("test", vec![TraderDump {
+            symbol: "LOOM-USDT-SPOT".into(),
+            book: None,
+            snapshot: None,
+            pending: Vec::new(),
+            pending_count: 0,
+        }]);
+        let path = write_dump(&dir, &dump).unwrap();
+        let read: EngineDump = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(read.traders[0].symbol, "LOOM-USDT-SPOT");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
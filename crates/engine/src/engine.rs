@@ -1,107 +1,1587 @@
 use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
 use anyhow::anyhow;
-use log::info;
-use tokio::sync::broadcast;
+use log::{info, warn};
+use tokio::sync::{broadcast, mpsc, oneshot};
 use tokio::task::JoinHandle;
+use tracing::{debug_span, Instrument};
 
+use loom_core::id::{IdStrategy, SnowflakeIds};
+use loom_core::market::{MarketBook, MatchTrade, UncrossPolicy};
 use loom_core::order::{Order, OrderAction};
+use loom_core::snapshot::{BookImage, BookSnapshot, QueuePosition, StateHash};
+use loom_core::symbol::{Symbol, SymbolId, SymbolPatch, SymbolSpec};
+use loom_core::utils;
 
+use crate::builder::EngineBuilder;
+#[cfg(feature = "redis")]
 use crate::cache::CacheManager;
+use crate::dump::{EngineDump, TraderDump};
+use crate::image::{self, EngineImage};
+use crate::ledger::Ledger;
+use crate::mmp::{MarketMakerProtection, MmpConfig};
+#[cfg(feature = "redis")]
+use crate::reconcile::Reconciler;
+use crate::reference::ReferencePrices;
+use crate::limits::{AccountLimiter, AccountLimits, SymbolThrottle, SymbolThrottled};
+use crate::logging;
+use crate::metrics::{LatencyHistogram, QueueWait};
+use crate::registry::SymbolRegistry;
+#[cfg(feature = "redis")]
+use crate::follower::Follower;
+use crate::replication::{Replication, ReplicationRole, StandbyMode};
+use crate::risk::{RiskChain, RiskCheck};
+use crate::replay::Replayer;
+use crate::sequencer::{IdCheck, IdWatermark, SequenceGuard, Sequencer};
+use crate::session::{SessionPhase, SessionState};
+use crate::shard::{ShardPool, ShardStats};
+use crate::snapshot::SnapshotCell;
 use crate::consumer::TradeConsumer;
-use crate::trader::Trader;
+use crate::trader::{self, HaltOrders, Liveness, MarketStatus, MatchReply, OrderSender, PauseMode, PauseState, TradeWaiters, Trader, TraderControl, TraderOptions, DEFAULT_DRAIN_TIMEOUT};
+use crate::warmup::{RecoveryPolicy, RecoveryProgress, RecoveryStatus, Warmup};
+
+/// 等待交易员响应转储请求的超时时间
+const DUMP_TIMEOUT: Duration = Duration::from_secs(1);
+/// 等待交易员撤销所有挂单并推送撤单结果的超时时间
+const CANCEL_ALL_TIMEOUT: Duration = Duration::from_secs(10);
+/// 关闭时交易员处理完队列后刷新消费器的额外等待时间
+const SHUTDOWN_FLUSH_GRACE: Duration = Duration::from_secs(1);
+/// 恢复时默认每批读取的订单数量
+pub const DEFAULT_RECOVERY_BATCH: usize = 10_000;
+/// 按日志恢复时每批读取的日志条数
+#[cfg(feature = "redis")]
+const JOURNAL_REPLAY_BATCH: usize = 1000;
 
 /// 交易员市场，其中注册了多个交易对交易员
 // #[derive(Debug)]
 pub struct MatchEngine {
-    traders: HashMap<String, Trader>,
-    handlers: Vec<JoinHandle<()>>,
+    traders: HashMap<SymbolId, Trader>,
+    /// 交易员协程句柄及其所在分片
+    handlers: Vec<(Option<usize>, JoinHandle<()>)>,
     ctx: broadcast::Sender<bool>,
-    is_shutdown: bool,
-    cache_manager: CacheManager,
+    handle: EngineHandle,
+    /// 工作分片，未配置时所有交易员运行在当前运行时中
+    shards: Option<ShardPool>,
+    /// 关闭时交易员处理队列中剩余请求的期限
+    drain_timeout: Duration,
+    /// 每处理N个请求检查一次市场不变式
+    verify_every: Option<u64>,
+    /// 恢复镜像后订单簿交叉时的处理策略
+    uncross: UncrossPolicy,
+    /// 分布式成交ID生成器，所有交易对共享，未配置时各交易对连续分配
+    snowflake: Option<Arc<SnowflakeIds>>,
+    /// 恢复时每批读取的订单数量
+    recovery_batch: usize,
+    /// 是否检查客户端订单ID的连续性
+    check_ids: bool,
+}
+
+/// 引擎句柄，持有各交易对的撮合请求发送器，可廉价克隆并在多个请求间并发使用
+#[derive(Clone, Debug)]
+pub struct EngineHandle {
+    /// 交易对路由表
+    routes: Arc<RwLock<RoutingTable>>,
+    /// 引擎是否已关闭
+    is_shutdown: Arc<AtomicBool>,
+    /// 订单持久化缓存，未配置时订单只保存在内存中，重启后无法恢复
+    #[cfg(feature = "redis")]
+    cache_manager: Option<CacheManager>,
+    /// 账户限制器，未配置时不限制账户
+    accounts: Option<Arc<AccountLimiter>>,
+    /// 下单前风控检查
+    risk: Arc<RiskChain>,
+    /// 账户余额账本，未启用时不检查余额
+    ledger: Option<Arc<Ledger>>,
+    /// 做市商保护，未启用时不统计挂单成交
+    mmp: Option<Arc<MarketMakerProtection>>,
+    /// 外部参考价，未启用时风控只使用最新成交价
+    reference: Option<Arc<ReferencePrices>>,
+    /// 缓存与订单簿对账，未启用时不对账
+    #[cfg(feature = "redis")]
+    reconciler: Option<Arc<Reconciler>>,
+    /// 主备复制状态
+    replication: Arc<Replication>,
+    /// 交易对规格注册表
+    registry: SymbolRegistry,
+    /// 启动预热状态
+    warmup: Arc<Warmup>,
+    /// 成交广播，通过[`EngineBuilder`]构造时启用
+    trades: Option<broadcast::Sender<MatchTrade>>,
+}
+
+/// 路由表，按进程内驻留表分配的交易对编号索引，订单携带编号时路由不需要查找交易对字符串
+#[derive(Debug, Default)]
+struct RoutingTable {
+    /// 已注册的交易对，按注册顺序
+    symbols: Vec<(SymbolId, String)>,
+    /// 按交易对编号索引的路由
+    routes: Vec<Option<Route>>,
+}
+
+/// 交易对路由
+#[derive(Debug, Clone)]
+struct Route {
+    /// 撮合请求发送器
+    sender: OrderSender,
+    /// 交易员发布的市场快照
+    snapshot: SnapshotCell,
+    /// 交易员存活状态
+    liveness: Liveness,
+    /// 撮合延迟
+    latency: Arc<LatencyHistogram>,
+    /// 撮合请求排队时间
+    queue_wait: Arc<QueueWait>,
+    /// 撮合请求处理失败次数
+    failures: Arc<AtomicU64>,
+    /// 交易员控制请求发送器
+    control: mpsc::UnboundedSender<TraderControl>,
+    /// 等待同步撮合结果的请求
+    waiters: TradeWaiters,
+    /// 序列号分配器
+    sequencer: Arc<Sequencer>,
+    /// 缓存恢复是否完成，完成前拒绝新的撮合请求
+    ready: Arc<AtomicBool>,
+    /// 缓存恢复进度
+    recovery: Arc<RecoveryProgress>,
+    /// 交易员暂停状态
+    pause: PauseState,
+    /// 交易时段阶段
+    session: SessionState,
+    /// 交易对下单速率限制
+    throttle: Arc<SymbolThrottle>,
+    /// 客户端订单ID高水位，未启用检查时为空
+    ids: Option<Arc<IdWatermark>>,
+}
+
+/// 撮合请求排队时间超过上限，暂不接受新订单
+#[derive(Debug, Clone)]
+pub struct QueueSaturated {
+    pub symbol: String,
+    pub wait: Duration,
+}
+
+impl std::fmt::Display for QueueSaturated {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "trader queue saturated, retry later, symbol={}, wait_us={}", self.symbol, self.wait.as_micros())
+    }
+}
+
+impl std::error::Error for QueueSaturated {}
+
+/// 交易对未注册，拒绝撮合请求
+#[derive(Debug, Clone)]
+pub struct UnknownSymbol {
+    pub symbol: String,
+}
+
+impl std::fmt::Display for UnknownSymbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "REJECTED: UNKNOWN_SYMBOL, symbol={}", self.symbol)
+    }
+}
+
+impl std::error::Error for UnknownSymbol {}
+
+/// 交易对正在恢复，暂不接受撮合请求
+#[derive(Debug, Clone)]
+pub struct SymbolNotReady {
+    pub symbol: String,
+}
+
+impl std::fmt::Display for SymbolNotReady {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "symbol recovering, retry later, symbol={}", self.symbol)
+    }
+}
+
+impl std::error::Error for SymbolNotReady {}
+
+/// 交易对已暂停并拒绝撮合请求
+#[derive(Debug, Clone)]
+pub struct SymbolPaused {
+    pub symbol: String,
+}
+
+impl std::fmt::Display for SymbolPaused {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "symbol paused, symbol={}", self.symbol)
+    }
+}
+
+impl std::error::Error for SymbolPaused {}
+
+/// 交易对处于交易时段之外并拒绝撮合请求
+#[derive(Debug, Clone)]
+pub struct SessionClosed {
+    pub symbol: String,
+}
+
+impl std::fmt::Display for SessionClosed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "trading session closed, symbol={}", self.symbol)
+    }
+}
+
+impl std::error::Error for SessionClosed {}
+
+/// 撤单时订单不在订单簿中，可能已成交、已撤销或从未挂单
+#[derive(Debug, Clone)]
+pub struct OrderNotFound {
+    pub symbol: String,
+    pub oid: u64,
+}
+
+impl std::fmt::Display for OrderNotFound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "order not found, symbol={}, oid={}", self.symbol, self.oid)
+    }
+}
+
+impl std::error::Error for OrderNotFound {}
+
+impl RoutingTable {
+    fn register(&mut self, id: SymbolId, symbol: &str, route: Route) {
+        if !self.symbols.iter().any(|(s, _)| *s == id) {
+            self.symbols.push((id, String::from(symbol)));
+        }
+        let idx = id.0 as usize;
+        if self.routes.len() <= idx {
+            self.routes.resize(idx + 1, None);
+        }
+        self.routes[idx] = Some(route);
+    }
+
+    fn route(&self, id: SymbolId) -> Option<&Route> {
+        self.routes.get(id.0 as usize).and_then(|s| s.as_ref())
+    }
+
+    fn iter(&self) -> impl Iterator<Item=(SymbolId, &str)> {
+        self.symbols.iter().map(|(id, s)| (*id, s.as_str()))
+    }
 }
 
 impl MatchEngine {
+    #[cfg(feature = "redis")]
     pub fn new(cache_manager: CacheManager) -> MatchEngine {
+        Self::in_memory().with_cache_manager(cache_manager)
+    }
+
+    /// 构造嵌入式引擎，参见[`EngineBuilder`]
+    pub fn builder() -> EngineBuilder {
+        EngineBuilder::default()
+    }
+
+    /// 不持久化订单的引擎
+    pub(crate) fn in_memory() -> MatchEngine {
         let sender = broadcast::Sender::new(1);
         MatchEngine {
             traders: HashMap::new(),
             handlers: Vec::new(),
             ctx: sender,
-            is_shutdown: false,
-            cache_manager,
+            handle: EngineHandle {
+                routes: Arc::new(RwLock::new(RoutingTable::default())),
+                is_shutdown: Arc::new(AtomicBool::new(false)),
+                #[cfg(feature = "redis")]
+                cache_manager: None,
+                accounts: None,
+                risk: Arc::new(RiskChain::new()),
+                ledger: None,
+                mmp: None,
+                reference: None,
+                #[cfg(feature = "redis")]
+                reconciler: None,
+                replication: Arc::new(Replication::default()),
+                registry: SymbolRegistry::new(),
+                warmup: Arc::new(Warmup::default()),
+                trades: None,
+            },
+            shards: None,
+            drain_timeout: DEFAULT_DRAIN_TIMEOUT,
+            verify_every: None,
+            uncross: UncrossPolicy::default(),
+            snowflake: None,
+            recovery_batch: DEFAULT_RECOVERY_BATCH,
+            check_ids: false,
         }
     }
 
+    /// 将交易员按交易对一致性哈希分配到shards个工作分片中运行
+    #[cfg(feature = "redis")]
+    pub fn with_shards(cache_manager: CacheManager, shards: usize) -> anyhow::Result<MatchEngine> {
+        Self::new(cache_manager).with_shard_pool(shards)
+    }
+
+    /// 订单写入缓存，创建交易员时从缓存恢复
+    #[cfg(feature = "redis")]
+    pub(crate) fn with_cache_manager(mut self, cache_manager: CacheManager) -> MatchEngine {
+        self.handle.cache_manager = Some(cache_manager);
+        self
+    }
+
+    pub(crate) fn with_shard_pool(mut self, shards: usize) -> anyhow::Result<MatchEngine> {
+        self.shards = Some(ShardPool::new(shards)?);
+        Ok(self)
+    }
+
+    pub(crate) fn with_trade_feed(mut self, sender: broadcast::Sender<MatchTrade>) -> MatchEngine {
+        self.handle.trades = Some(sender);
+        self
+    }
+
+    /// 启用账户挂单数量和下单速率限制，需在创建交易员之前调用
+    pub fn with_account_limits(mut self, limits: AccountLimits) -> MatchEngine {
+        self.handle.accounts = Some(Arc::new(AccountLimiter::new(limits)));
+        self
+    }
+
+    /// 替换下单前风控检查链
+    pub fn with_risk_checks(mut self, chain: RiskChain) -> MatchEngine {
+        self.handle.risk = Arc::new(chain);
+        self
+    }
+
+    /// 追加自定义风控检查，在已注册的检查之后执行
+    pub fn with_risk_check(mut self, check: Arc<dyn RiskCheck>) -> MatchEngine {
+        let chain = (*self.handle.risk).clone().with_check(check);
+        self.handle.risk = Arc::new(chain);
+        self
+    }
+
+    /// 启用账户余额账本，作为最后一项风控检查预留资金，需在配置风控检查之后、创建交易员之前调用
+    pub fn with_ledger(mut self) -> MatchEngine {
+        let ledger = Arc::new(Ledger::new(self.handle.registry.clone()));
+        let chain = (*self.handle.risk).clone().with_check(Arc::clone(&ledger) as Arc<dyn RiskCheck>);
+        self.handle.risk = Arc::new(chain);
+        self.handle.ledger = Some(ledger);
+        self
+    }
+
+    /// 启用做市商保护，需在创建交易员之前调用
+    pub fn with_mmp(mut self, config: MmpConfig) -> MatchEngine {
+        self.handle.mmp = Some(Arc::new(MarketMakerProtection::new(config)));
+        self
+    }
+
+    /// 启用缓存与订单簿对账，定时对账由`reconcile::launch`启动
+    #[cfg(feature = "redis")]
+    pub fn with_reconciler(mut self, reconciler: Reconciler) -> MatchEngine {
+        self.handle.reconciler = Some(Arc::new(reconciler));
+        self
+    }
+
+    /// 启用外部参考价，风控检查需通过`RiskChain::from_config_with_reference`使用同一份参考价
+    pub fn with_reference_prices(mut self, reference: Arc<ReferencePrices>) -> MatchEngine {
+        self.handle.reference = Some(reference);
+        self
+    }
+
+    /// 设置关闭时处理队列中剩余请求的期限，需在创建交易员之前调用
+    pub fn with_drain_timeout(mut self, drain_timeout: Duration) -> MatchEngine {
+        self.drain_timeout = drain_timeout;
+        self
+    }
+
+    /// 恢复时每批读取的订单数量，需在创建交易员之前调用
+    pub fn with_recovery_batch(mut self, batch: usize) -> MatchEngine {
+        self.recovery_batch = batch.max(1);
+        self
+    }
+
+    /// 调试模式，交易员每处理N个请求检查一次市场不变式，不满足时暂停交易对，需在创建交易员之前调用
+    pub fn with_verify_every(mut self, verify_every: u64) -> MatchEngine {
+        self.verify_every = Some(verify_every);
+        self
+    }
+
+    /// 检查各交易对新订单的客户端ID是否连续递增，发现缺失或回退时告警，需在创建交易员之前调用
+    ///
+    /// 适用于上游网关按交易对连续分配订单ID的部署，引擎仍自行分配序列号，检查结果不影响订单受理
+    pub fn with_id_check(mut self) -> MatchEngine {
+        self.check_ids = true;
+        self
+    }
+
+    /// 设置恢复镜像后订单簿交叉时的处理策略，需在创建交易员之前调用
+    pub fn with_uncross(mut self, uncross: UncrossPolicy) -> MatchEngine {
+        self.uncross = uncross;
+        self
+    }
+
+    /// 设置成交ID的分配方式，需在创建交易员之前调用，多个引擎使用分布式ID时节点号不能相同
+    pub fn with_id_strategy(mut self, strategy: IdStrategy) -> anyhow::Result<MatchEngine> {
+        self.snowflake = match strategy {
+            IdStrategy::Sequential => None,
+            IdStrategy::Snowflake { node } => Some(Arc::new(SnowflakeIds::new(node)?)),
+        };
+        Ok(self)
+    }
+
+    /// 启用主备复制，需在创建交易员之前调用，备机创建交易员后自动跟随主机日志
+    pub fn with_replication(mut self, role: ReplicationRole, journal_max_len: usize) -> MatchEngine {
+        self.handle.replication = Arc::new(Replication::new(role, journal_max_len));
+        self
+    }
+
+    /// 获取引擎句柄
+    pub fn handle(&self) -> EngineHandle {
+        self.handle.clone()
+    }
+
+    /// 订阅引擎关闭信号
+    pub fn subscribe(&self) -> broadcast::Receiver<bool> {
+        self.ctx.subscribe()
+    }
+
     /// 创建交易员并开始交易
     pub async fn new_trader(&mut self, symbol: &str, consumer: TradeConsumer) -> anyhow::Result<&Self> {
-        let exist = self.traders.contains_key(symbol);
+        self.new_trader_with_options(symbol, consumer, TraderOptions::default()).await
+    }
+
+    /// 按配置创建交易员，从缓存恢复后开始交易
+    pub async fn new_trader_with_options(&mut self, symbol: &str, consumer: TradeConsumer, options: TraderOptions) -> anyhow::Result<&Self> {
+        let spec = options.spec.clone().unwrap_or_else(|| SymbolSpec::new(symbol));
+        let recovery = options.recovery.clone();
+        self.register_symbol(spec, consumer, options).await?;
+        if self.handle.replication.role() == ReplicationRole::Standby {
+            self.follow(symbol).await?;
+        } else {
+            self.recover_symbol_with(symbol, &recovery).await?;
+        }
+        Ok(self)
+    }
+
+    /// 备机恢复交易对后跟随主机日志
+    #[cfg(feature = "redis")]
+    async fn follow(&self, symbol: &str) -> anyhow::Result<()> {
+        // 先记录日志位置再恢复，恢复期间主机写入的日志不会遗漏
+        let tail = self.handle.require_cache()?.journal_tail(symbol).await?;
+        self.recover_symbol(symbol).await?;
+        let follower = Follower::new(self.handle.clone(), symbol, tail).launch();
+        self.handle.replication.add_follower(follower);
+        Ok(())
+    }
+
+    #[cfg(not(feature = "redis"))]
+    async fn follow(&self, symbol: &str) -> anyhow::Result<()> {
+        Err(anyhow!("standby requires redis persistence, symbol={}", symbol))
+    }
+
+    /// 注册交易对并启动交易员，可在运行时调用，恢复完成前该交易对的撮合请求返回`SymbolNotReady`错误
+    pub async fn register_symbol(&mut self, spec: SymbolSpec, consumer: TradeConsumer, options: TraderOptions) -> anyhow::Result<SymbolId> {
+        let symbol = spec.symbol.as_str();
+        let symbol_id = SymbolId::intern(symbol);
+        let exist = self.traders.contains_key(&symbol_id);
         if exist {
             let msg = format!("engine already exist, symbol={}", symbol);
             return Err(anyhow!(msg));
         }
+        spec.validate()?;
+        // 成交ID从缓存记录的高水位继续分配
+        let trade_id = self.handle.last_trade_id(symbol).await?;
+        let ids = match self.check_ids {
+            true => Some(Arc::new(IdWatermark::new(self.handle.last_client_id(symbol).await?))),
+            false => None,
+        };
+        let options = options.with_spec(spec.clone())
+            .with_trade_id(trade_id)
+            .with_replication(Arc::clone(&self.handle.replication))
+            .with_drain_timeout(self.drain_timeout)
+            .with_uncross(self.uncross);
         // 构造交易员
-        let trader = Trader::new(symbol, consumer);
-        // 启动交易员
-        let handler = trader.launch(self.ctx.subscribe());
+        let options = match &self.handle.accounts {
+            Some(accounts) => options.with_accounts(Arc::clone(accounts)),
+            None => options,
+        };
+        let options = match &self.handle.ledger {
+            Some(ledger) => options.with_ledger(Arc::clone(ledger)),
+            None => options,
+        };
+        let options = match &self.handle.mmp {
+            Some(mmp) => options.with_mmp(Arc::clone(mmp)),
+            None => options,
+        };
+        let options = match &self.snowflake {
+            Some(snowflake) => options.with_snowflake(Arc::clone(snowflake)),
+            None => options,
+        };
+        let options = match self.verify_every {
+            Some(verify_every) => options.with_verify_every(verify_every),
+            None => options,
+        };
+        let trader = Trader::with_options(symbol, symbol_id, consumer, options);
+        // 启动交易员，配置分片时在交易对所属分片中运行
+        let (shard, handler) = match self.shards.as_mut() {
+            Some(pool) => {
+                let shard = pool.assign(symbol);
+                (Some(shard.id()), trader.launch_on(shard.handle(), shard.subscribe()))
+            }
+            None => (None, trader.launch(self.ctx.subscribe())),
+        };
         // 保存协程句柄
-        self.handlers.push(handler);
+        self.handlers.push((shard, handler));
+        // 注册路由，恢复完成前拒绝撮合请求
+        let route = Route {
+            sender: trader.get_input_sender(),
+            snapshot: trader.snapshot(),
+            liveness: trader.liveness(),
+            latency: trader.latency(),
+            queue_wait: trader.queue_wait(),
+            failures: trader.failures(),
+            control: trader.control(),
+            waiters: trader.waiters(),
+            sequencer: Arc::new(self.handle.sequencer(symbol)),
+            ready: Arc::new(AtomicBool::new(false)),
+            recovery: Arc::new(RecoveryProgress::default()),
+            pause: trader.pause_state(),
+            session: trader.session_state(),
+            throttle: Arc::new(SymbolThrottle::new(spec.max_orders_per_sec)),
+            ids,
+        };
+        self.handle.routes.write().unwrap().register(symbol_id, symbol, route);
+        self.handle.registry.insert(spec);
         // 保存交易员句柄
-        self.traders.insert(String::from(symbol), trader);
-        if let Some(trader) = self.traders.get(symbol) {
-            // 从缓存中恢复
-            let oid_buffer = &mut Vec::new();
-            self.cache_manager.get_ids(symbol, |id| {
-                oid_buffer.push(id);
-                Ok(())
-            }).await?;
-            let mut orders = self.cache_manager.get_orders_by_ids(symbol, &oid_buffer).await?;
-            let mut recover_cnt = 0;
-            for order in orders.drain(..) {
-                trader.feed(order).await?;
-                recover_cnt += 1;
-            };
-            info!("RECOVER: symbol={}, orders_cnt={}", symbol, recover_cnt);
+        self.traders.insert(symbol_id, trader);
+        Ok(symbol_id)
+    }
+
+    /// 从缓存中恢复交易对的挂单，完成后开始接受撮合请求，返回恢复的订单数量
+    ///
+    /// 订单按批次流水线读取，不会互相成交的挂单在撮合线程中一次性加入订单簿，其余订单逐个撮合
+    pub async fn recover_symbol(&self, symbol: &str) -> anyhow::Result<usize> {
+        let recover_cnt = self.refeed_symbol(symbol).await?;
+        self.mark_ready(symbol);
+        Ok(recover_cnt)
+    }
+
+    /// 按恢复方式恢复交易对，完成后开始接受撮合请求，返回恢复后订单簿中的挂单数量
+    pub async fn recover_symbol_with(&self, symbol: &str, policy: &RecoveryPolicy) -> anyhow::Result<usize> {
+        info!("RECOVER: symbol={}, policy={:?}", symbol, policy);
+        match policy {
+            RecoveryPolicy::Refeed => self.recover_symbol(symbol).await,
+            RecoveryPolicy::Empty => {
+                // 先加载缓存中的挂单再统一撤销，下游收到撤单结果，缓存和账户额度随之清理
+                self.refeed_symbol(symbol).await?;
+                let cancelled = self.handle.cancel_all(symbol).await?;
+                info!("RECOVER: symbol={}, started empty, cancelled={}", symbol, cancelled);
+                self.mark_ready(symbol);
+                Ok(0)
+            }
+            RecoveryPolicy::Image { path } => {
+                let book = Self::image_book(Path::new(path), symbol)?;
+                self.restore_symbol(book).await
+            }
+            RecoveryPolicy::Journal { image } => {
+                let spec = self.handle.registry.get(symbol).unwrap_or_else(|| SymbolSpec::new(symbol));
+                let mut replayer = Replayer::new().with_spec(spec.clone());
+                if let Some(path) = image {
+                    replayer.restore(Self::image_book(Path::new(path), symbol)?)?;
+                }
+                let applied = self.handle.replay_journal(symbol, &mut replayer).await?;
+                let book = replayer.books().find(|book| book.symbol == symbol)
+                    .map(MarketBook::image)
+                    .unwrap_or_else(|| MarketBook::with_spec(spec).image());
+                info!("RECOVER: symbol={}, journal_applied={}, seq={}", symbol, applied, book.seq);
+                self.restore_symbol(book).await
+            }
         }
-        Ok(self)
     }
 
-    /// 发送撮合请求
-    pub async fn feed(&mut self, order: Order) -> anyhow::Result<()> {
-        if self.is_shutdown {
+    /// 读取镜像中交易对的订单簿，path为目录时使用其中最新的镜像
+    fn image_book(path: &Path, symbol: &str) -> anyhow::Result<BookImage> {
+        let path = image::latest_image(path)?;
+        let book = image::read_image(&path)?.books.into_iter()
+            .find(|book| book.symbol == symbol)
+            .ok_or_else(|| anyhow!("symbol not found in image, symbol={}, path={}", symbol, path.display()))?;
+        info!("RECOVER: symbol={}, image={}, seq={}", symbol, path.display(), book.seq);
+        Ok(book)
+    }
+
+    /// 用重建的订单簿替换交易对的市场状态并按订单簿重写缓存，成交ID不低于缓存记录的高水位
+    async fn restore_symbol(&self, mut book: BookImage) -> anyhow::Result<usize> {
+        let symbol = book.symbol.clone();
+        book.trade_id = book.trade_id.max(self.handle.last_trade_id(&symbol).await?);
+        let orders: Vec<Order> = book.bids.iter().chain(book.asks.iter()).cloned().collect();
+        let restored = self.handle.restore(EngineImage::new(vec![book])).await?;
+        let removed = self.handle.replace_orders(&symbol, &orders).await?;
+        info!("RECOVER: symbol={}, orders_cnt={}, cache_removed={}", symbol, restored, removed);
+        Ok(restored)
+    }
+
+    fn mark_ready(&self, symbol: &str) {
+        if let Some(route) = self.handle.route(symbol) {
+            route.ready.store(true, Ordering::Release);
+        }
+    }
+
+    /// 缓存中的挂单重新经过撮合，返回读取的订单数量
+    async fn refeed_symbol(&self, symbol: &str) -> anyhow::Result<usize> {
+        let trader = self.handle.symbol_id(symbol)
+            .and_then(|id| self.traders.get(&id))
+            .ok_or_else(|| anyhow!("symbol not registered, symbol={}", symbol))?;
+        let progress = self.handle.route(symbol).map(|r| r.recovery)
+            .ok_or_else(|| anyhow!("symbol not registered, symbol={}", symbol))?;
+        let started = std::time::Instant::now();
+        let mut orders = self.handle.read_orders(symbol, self.recovery_batch.max(1), &progress).await?;
+        // 序列号与到达时间在同一把锁内分配，没有序列号的旧订单按到达引擎的先后排在前面
+        orders.sort_by_key(|o| (o.seq, o.arrival_ns(), o.id));
+        let recover_cnt = orders.len();
+        for order in &orders {
+            if let Some(accounts) = &self.handle.accounts {
+                accounts.track(order);
+            }
+            if let Some(ledger) = &self.handle.ledger {
+                ledger.track(order);
+            }
+        }
+        let (tx, rx) = oneshot::channel();
+        trader.control().send(TraderControl::Load(orders, tx))
+            .map_err(|_| anyhow!("trader stopped, symbol={}", symbol))?;
+        let rest = rx.await.map_err(|_| anyhow!("trader stopped, symbol={}", symbol))?;
+        let matched = rest.len();
+        for order in rest {
+            trader.feed(order).await?;
+        }
+        info!("RECOVER: symbol={}, orders_cnt={}, loaded={}, matched={}, elapsed_ms={}", symbol, recover_cnt, recover_cnt - matched, matched, started.elapsed().as_millis());
+        Ok(recover_cnt)
+    }
+
+    /// 发送撮合请求，返回引擎分配的序列号
+    pub async fn feed(&self, order: Order) -> anyhow::Result<u64> {
+        self.handle.feed(order).await
+    }
+
+    /// 生成所有交易对的市场镜像
+    pub async fn snapshot(&self) -> anyhow::Result<EngineImage> {
+        self.handle.image().await
+    }
+
+    /// 用镜像替换交易对的市场状态，返回恢复的挂单数量
+    pub async fn restore(&self, image: EngineImage) -> anyhow::Result<usize> {
+        self.handle.restore(image).await
+    }
+
+    /// 暂停交易对，交易员保留订单簿状态，按mode缓冲或拒绝新的撮合请求
+    pub fn pause(&self, symbol: &str, mode: PauseMode) -> anyhow::Result<()> {
+        self.handle.pause(symbol, mode)
+    }
+
+    /// 恢复交易对，处理暂停期间缓冲的撮合请求
+    pub fn resume(&self, symbol: &str) -> anyhow::Result<()> {
+        self.handle.resume(symbol)
+    }
+
+    /// 关闭市场，停止接收撮合请求，交易员在期限内处理完队列中剩余的请求、发布最终快照并刷新消费器后退出
+    ///
+    /// 超过期限仍未退出的交易员被强制终止，返回是否全部正常退出
+    pub async fn shutdown(&mut self) -> bool {
+        let mut graceful = true;
+        if !self.handle.is_shutdown.swap(true, Ordering::SeqCst) {
+            // 发送中断信号
+            let _ = self.ctx.send(true);
+            if let Some(pool) = &self.shards {
+                pool.iter().for_each(|shard| shard.terminate());
+            }
+            // 等待所有协程停止
+            let deadline = tokio::time::Instant::now() + self.drain_timeout + SHUTDOWN_FLUSH_GRACE;
+            for (_, mut handler) in self.handlers.drain(..) {
+                match tokio::time::timeout_at(deadline, &mut handler).await {
+                    Ok(result) => result.unwrap(),
+                    Err(_) => {
+                        handler.abort();
+                        graceful = false;
+                    }
+                }
+            }
+            if !graceful {
+                warn!("SHUTDOWN TIMEOUT, TRADERS ABORTED: timeout={:?}", self.drain_timeout + SHUTDOWN_FLUSH_GRACE);
+            }
+            // 交易员退出后写入最终的客户端ID高水位和余额
+            if let Err(e) = self.handle.flush_client_ids().await {
+                warn!("CLIENT ID FLUSH FAILED: err={}", e);
+            }
+            if let Some(ledger) = self.handle.ledger.as_ref().filter(|_| self.handle.replication.role() == ReplicationRole::Primary) {
+                if let Err(e) = self.handle.flush_ledger(ledger).await {
+                    warn!("LEDGER FLUSH FAILED: err={}", e);
+                }
+            }
+            // 强制终止时分片运行时中可能仍有未退出的交易员，不再等待分片停止
+            if let Some(pool) = self.shards.as_mut().filter(|_| graceful) {
+                for shard in pool.iter_mut() {
+                    shard.stop().await;
+                }
+            }
+        }
+        graceful
+    }
+
+    /// 关闭单个分片，分片内交易对之后的请求会失败
+    pub async fn shutdown_shard(&mut self, idx: usize) -> anyhow::Result<()> {
+        let shard = self.shards.as_mut()
+            .and_then(|pool| pool.get_mut(idx))
+            .ok_or_else(|| anyhow!("shard not found, shard={}", idx))?;
+        shard.terminate();
+        let (stopping, running): (Vec<_>, Vec<_>) = self.handlers.drain(..).partition(|(s, _)| *s == Some(idx));
+        self.handlers = running;
+        for (_, handler) in stopping {
+            handler.await?;
+        }
+        shard.stop().await;
+        info!("SHARD STOPPED: shard={}, symbols={:?}", idx, shard.symbols());
+        Ok(())
+    }
+
+    /// 各分片统计
+    pub fn shard_stats(&self) -> Vec<ShardStats> {
+        let pool = match &self.shards {
+            Some(pool) => pool,
+            None => return Vec::new(),
+        };
+        pool.iter()
+            .map(|shard| ShardStats {
+                shard: shard.id(),
+                symbols: shard.symbols().to_vec(),
+                pending: shard.symbols().iter()
+                    .filter_map(|symbol| self.handle.sender(symbol))
+                    .map(|sender| sender.pending())
+                    .sum(),
+                running: shard.is_running(),
+            })
+            .collect()
+    }
+}
+
+impl EngineHandle {
+    /// 发送撮合请求，返回引擎分配的序列号，交易对不存在时返回`UnknownSymbol`错误，队列已满且按背压策略拒绝时返回`QueueFull`错误
+    pub async fn feed(&self, order: Order) -> anyhow::Result<u64> {
+        let span = debug_span!("engine.feed", symbol = %order.symbol, oid = order.id);
+        let ctx = logging::current().unwrap_or_default().with_order(&order);
+        logging::scope(ctx, self.submit(order).instrument(span)).await
+    }
+
+    /// 发送撮合请求并等待撮合结果，返回序列号和撮合结果，超时未完成撮合时结果为None
+    pub async fn feed_sync(&self, order: Order, timeout: Duration) -> anyhow::Result<(u64, Option<MatchReply>)> {
+        let waiters = {
+            let routes = self.routes.read().unwrap();
+            order.symbol.id()
+                .and_then(|id| routes.route(id))
+                .map(|r| r.waiters.clone())
+        };
+        let waiters = match waiters {
+            Some(waiters) => waiters,
+            None => return Ok((self.feed(order).await?, None)),
+        };
+        let oid = order.id;
+        let rx = waiters.register(oid);
+        let seq = match self.feed(order).await {
+            Ok(seq) => seq,
+            Err(e) => {
+                waiters.cancel(oid);
+                return Err(e);
+            }
+        };
+        match tokio::time::timeout(timeout, rx).await {
+            Ok(reply) => Ok((seq, reply.ok())),
+            Err(_) => {
+                waiters.cancel(oid);
+                Ok((seq, None))
+            }
+        }
+    }
+
+    async fn submit(&self, order: Order) -> anyhow::Result<u64> {
+        if self.is_shutdown() {
             // 引擎关闭，无法提交
             return Err(anyhow!("engine stopping or stopped"));
         }
+        // 先校验交易对，未注册的请求不分配序列号也不写入缓存
+        let route = match self.route_of(&order.symbol) {
+            None => return Err(UnknownSymbol { symbol: order.symbol.to_string() }.into()),
+            Some(route) if !route.ready.load(Ordering::Acquire) => {
+                return Err(SymbolNotReady { symbol: order.symbol.to_string() }.into());
+            }
+            Some(route) if route.pause.rejects() => {
+                return Err(SymbolPaused { symbol: order.symbol.to_string() }.into());
+            }
+            Some(route) if route.session.rejects() => {
+                return Err(SessionClosed { symbol: order.symbol.to_string() }.into());
+            }
+            Some(route) => route,
+        };
+        if !self.replication.accepts_orders() {
+            return Err(StandbyMode { symbol: order.symbol.to_string() }.into());
+        }
+        // 风控检查可能访问外部服务，在分配序列号之前执行
+        let mut admitted = None;
+        if order.action == OrderAction::PLACE {
+            // 排队时间超过上限时拒绝新订单，撤单和减量不受影响
+            if route.queue_wait.saturated() {
+                return Err(QueueSaturated { symbol: order.symbol.to_string(), wait: route.queue_wait.last() }.into());
+            }
+            // 交易对下单速率超限时拒绝新订单，避免单个交易对挤占共享运行时
+            let sec = (utils::now_ts() / 1000) as u64;
+            if !route.throttle.admit(sec) {
+                return Err(SymbolThrottled { symbol: order.symbol.to_string() }.into());
+            }
+            // 被做市商保护或风控拒绝的订单不占用交易对的下单额度
+            let checked: anyhow::Result<()> = async {
+                if let Some(mmp) = &self.mmp {
+                    mmp.check(&order)?;
+                }
+                if !self.risk.is_empty() {
+                    self.risk.check(&order, &route.snapshot.load()).await?;
+                }
+                Ok(())
+            }.await;
+            if let Err(e) = checked {
+                route.throttle.refund(sec);
+                return Err(e);
+            }
+            admitted = Some(sec);
+        }
+        // 未进入撮合队列的新订单同样不占用交易对的下单额度
+        let result = self.enqueue(&route, order).await;
+        if let (Err(_), Some(sec)) = (&result, admitted) {
+            route.throttle.refund(sec);
+        }
+        result
+    }
+
+    /// 分配序列号、占用账户额度并写入缓存和日志后送入撮合队列，失败时撤回已完成的步骤
+    async fn enqueue(&self, route: &Route, mut order: Order) -> anyhow::Result<u64> {
+        // 持有分配器的锁直到请求进入队列，保证队列顺序与序列号顺序一致
+        let mut sequence = route.sequencer.lock().await;
+        order.seq = match sequence.next().await {
+            Ok(seq) => seq,
+            Err(e) => {
+                if order.action == OrderAction::PLACE {
+                    self.risk.release(&order);
+                }
+                return Err(e);
+            }
+        };
+        // 与序列号在同一把锁内分配，到达时间的先后与队列顺序一致
+        order.ts_ns = utils::monotonic_ns();
+        let seq = order.seq;
         match order.action {
             OrderAction::PLACE => {
-                // 加入缓存，防止关机内存丢失
-                let success = self.cache_manager.add_if_absent(order.clone()).await?;
+                // 检查并占用账户额度
+                if let Some(accounts) = &self.accounts {
+                    if let Err(e) = accounts.acquire(&order, (utils::now_ts() / 1000) as u64) {
+                        self.risk.release(&order);
+                        return Err(e.into());
+                    }
+                }
+                // 加入缓存，防止关机内存丢失，未持久化时由订单簿拒绝重复订单
+                let success = match self.persist(&order).await {
+                    Ok(success) => success,
+                    Err(e) => {
+                        self.release_account(&order);
+                        return Err(e);
+                    }
+                };
                 if !success {
                     // 已经存在订单
+                    self.release_account(&order);
                     return Err(anyhow!("order existed"));
                 }
             }
-            OrderAction::CANCEL => {}
+            OrderAction::CANCEL | OrderAction::REDUCE => {}
+        }
+        // 主机在持有分配器锁时先写入日志再进入撮合队列，保证日志顺序与序列号顺序一致，且撮合的请求都已写入日志
+        if self.replication.writes_journal() {
+            if let Err(e) = self.append_journal(&order).await {
+                // 日志写入失败时撤回请求，避免备机缺少主机撮合的请求
+                warn!("JOURNAL FAILED: symbol={}, oid={}, seq={}, err={}", &order.symbol, order.id, order.seq, e);
+                if order.action == OrderAction::PLACE {
+                    self.release_account(&order);
+                    self.unpersist(&order).await?;
+                }
+                return Err(e);
+            }
         }
+        let watched = route.ids.as_ref()
+            .filter(|_| order.action == OrderAction::PLACE)
+            .map(|ids| (Arc::clone(ids), order.symbol.clone(), order.id));
         // 提供撮合请求
-        if let Some(trader) = self.traders.get(&order.symbol) {
-            trader.feed(order).await?;
+        if let Err(e) = route.sender.send(order).await {
+            if let Some(order) = trader::unsent(&e) {
+                if order.action == OrderAction::PLACE {
+                    // 请求未进入撮合队列，撤回缓存和账户额度，已写入的日志追加撤单让备机同样移除该订单
+                    if self.replication.writes_journal() {
+                        self.revoke_journal(&mut sequence, order).await;
+                    }
+                    self.release_account(order);
+                    self.unpersist(order).await?;
+                }
+            }
+            return Err(e);
+        }
+        if let Some((ids, symbol, oid)) = watched {
+            Self::check_id(&ids, &symbol, oid, seq);
+        }
+        Ok(seq)
+    }
+
+    /// 为已写入日志但未进入撮合队列的新订单追加撤单日志，失败时只能由备机检查点发现
+    async fn revoke_journal(&self, sequence: &mut SequenceGuard<'_>, order: &Order) {
+        let mut cancel = order.clone();
+        cancel.action = OrderAction::CANCEL;
+        let result = match sequence.next().await {
+            Ok(seq) => {
+                cancel.seq = seq;
+                self.append_journal(&cancel).await
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            warn!("JOURNAL REVOKE FAILED: symbol={}, oid={}, seq={}, err={}", &order.symbol, order.id, order.seq, e);
+        }
+    }
+
+    /// 检查已受理订单的客户端ID，缺失或回退说明上游可能丢失或重放了消息，高水位由定时任务写入缓存
+    fn check_id(ids: &IdWatermark, symbol: &str, oid: u64, seq: u64) {
+        match ids.observe(oid) {
+            IdCheck::InOrder => {}
+            IdCheck::Gap(missing) => {
+                warn!("ORDER ID GAP: symbol={}, oid={}, seq={}, missing={}", symbol, oid, seq, missing);
+            }
+            IdCheck::Regression(high) => {
+                warn!("ORDER ID REGRESSION: symbol={}, oid={}, seq={}, high={}", symbol, oid, seq, high);
+            }
+        }
+    }
+
+    /// 将上次写入后提高的客户端ID高水位写入缓存，返回写入的交易对数量，写入失败的交易对下次重新写入
+    pub async fn flush_client_ids(&self) -> anyhow::Result<usize> {
+        let mut saved = 0;
+        let mut failed = None;
+        for (symbol, ids) in self.id_watermarks() {
+            let Some(high) = ids.unsaved() else {
+                continue;
+            };
+            match self.save_client_id(&symbol, high).await {
+                Ok(()) => {
+                    ids.mark_saved(high);
+                    saved += 1;
+                }
+                Err(e) => failed = Some(e),
+            }
+        }
+        match failed {
+            Some(e) => Err(e),
+            None => Ok(saved),
+        }
+    }
+
+    /// 将缓存中存在但订单簿中丢失的订单重新放入订单簿，不经过风控和缓存写入，与订单簿交叉时会撮合
+    #[cfg(feature = "redis")]
+    pub(crate) async fn reinstate(&self, order: Order) -> anyhow::Result<()> {
+        let route = self.route_of(&order.symbol)
+            .ok_or_else(|| anyhow!("symbol not registered, symbol={}", order.symbol))?;
+        if let Some(accounts) = &self.accounts {
+            accounts.track(&order);
+        }
+        if let Some(ledger) = &self.ledger {
+            ledger.freeze(&order);
+        }
+        if let Err(e) = route.sender.send(order).await {
+            if let Some(order) = trader::unsent(&e) {
+                self.release_account(order);
+            }
+            return Err(e);
         }
         Ok(())
     }
 
-    /// 关闭市场
-    pub async fn shutdown(&mut self) {
-        if !self.is_shutdown {
-            // 发送中断信号
-            self.ctx.send(true).unwrap();
-            // 等待所有协程停止
-            for handler in self.handlers.drain(..) {
-                handler.await.unwrap();
+    /// 备机重放主机日志，保留主机分配的序列号，不经过风控和复制角色检查
+    #[cfg(feature = "redis")]
+    pub(crate) async fn replicate(&self, order: Order) -> anyhow::Result<()> {
+        let route = self.route_of(&order.symbol)
+            .ok_or_else(|| anyhow!("symbol not registered, symbol={}", order.symbol))?;
+        // 提升后新分配的序列号需大于已重放的序列号
+        route.sequencer.lock().await.advance(order.seq);
+        if order.action == OrderAction::PLACE {
+            if let Some(accounts) = &self.accounts {
+                accounts.track(&order);
+            }
+            if let Some(ledger) = &self.ledger {
+                ledger.freeze(&order);
+            }
+        }
+        if let Err(e) = route.sender.send(order).await {
+            if let Some(order) = trader::unsent(&e) {
+                self.release_account(order);
+            }
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// 将备机提升为主机，重放完剩余日志后开始接受撮合请求
+    pub async fn promote(&self) -> anyhow::Result<()> {
+        if self.replication.role() != ReplicationRole::Standby {
+            return Err(anyhow!("engine is not standby, role={:?}", self.replication.role()));
+        }
+        self.replication.set_role(ReplicationRole::Promoting);
+        for follower in self.replication.take_followers() {
+            follower.await?;
+        }
+        self.replication.set_role(ReplicationRole::Primary);
+        info!("PROMOTED: symbols={:?}", self.symbols());
+        Ok(())
+    }
+
+    pub fn replication(&self) -> &Replication {
+        &self.replication
+    }
+
+    /// 计算交易对的市场状态哈希，由交易员在撮合线程中计算
+    pub async fn state_hash(&self, symbol: &str) -> anyhow::Result<StateHash> {
+        let route = self.route(symbol)
+            .ok_or_else(|| anyhow!("symbol not registered, symbol={}", symbol))?;
+        let (tx, rx) = oneshot::channel();
+        route.control.send(TraderControl::StateHash(tx))
+            .map_err(|_| anyhow!("trader stopped, symbol={}", symbol))?;
+        tokio::time::timeout(DUMP_TIMEOUT, rx).await
+            .map_err(|_| anyhow!("state hash timeout, symbol={}", symbol))?
+            .map_err(|_| anyhow!("trader stopped, symbol={}", symbol))
+    }
+
+    /// 查询挂单的排队位置，指定账户时订单不属于该账户按不存在处理，订单不在订单簿中时为空
+    pub async fn queue_position(&self, symbol: &str, oid: u64, account: Option<&str>) -> anyhow::Result<Option<QueuePosition>> {
+        let route = self.route(symbol)
+            .ok_or_else(|| anyhow!("symbol not registered, symbol={}", symbol))?;
+        let (tx, rx) = oneshot::channel();
+        route.control.send(TraderControl::QueuePosition(oid, account.map(String::from), tx))
+            .map_err(|_| anyhow!("trader stopped, symbol={}", symbol))?;
+        tokio::time::timeout(DUMP_TIMEOUT, rx).await
+            .map_err(|_| anyhow!("queue position timeout, symbol={}", symbol))?
+            .map_err(|_| anyhow!("trader stopped, symbol={}", symbol))
+    }
+
+    /// 检查交易对的市场不变式，由交易员在撮合线程中检查，返回违反的不变式，检查通过时为空
+    pub async fn verify(&self, symbol: &str) -> anyhow::Result<Option<String>> {
+        let route = self.route(symbol)
+            .ok_or_else(|| anyhow!("symbol not registered, symbol={}", symbol))?;
+        let (tx, rx) = oneshot::channel();
+        route.control.send(TraderControl::Verify(tx))
+            .map_err(|_| anyhow!("trader stopped, symbol={}", symbol))?;
+        tokio::time::timeout(DUMP_TIMEOUT, rx).await
+            .map_err(|_| anyhow!("verify timeout, symbol={}", symbol))?
+            .map_err(|_| anyhow!("trader stopped, symbol={}", symbol))
+            .map(|result| result.err().map(|e| e.to_string()))
+    }
+
+    /// 账户余额账本，未启用时为空
+    pub fn ledger(&self) -> Option<&Arc<Ledger>> {
+        self.ledger.as_ref()
+    }
+
+    /// 做市商保护，未启用时为空
+    pub fn mmp(&self) -> Option<&Arc<MarketMakerProtection>> {
+        self.mmp.as_ref()
+    }
+
+    pub fn reference_prices(&self) -> Option<&Arc<ReferencePrices>> {
+        self.reference.as_ref()
+    }
+
+    /// 缓存与订单簿对账，未启用时为空
+    #[cfg(feature = "redis")]
+    pub fn reconciler(&self) -> Option<&Arc<Reconciler>> {
+        self.reconciler.as_ref()
+    }
+
+    /// 订阅所有交易对的成交，未启用成交广播时返回错误，订阅者处理过慢时收到`Lagged`并丢失最早的成交
+    pub fn subscribe_trades(&self) -> anyhow::Result<broadcast::Receiver<MatchTrade>> {
+        self.trades.as_ref()
+            .map(|sender| sender.subscribe())
+            .ok_or_else(|| anyhow!("trade feed not enabled"))
+    }
+
+    /// 启动预热状态
+    pub fn warmup(&self) -> &Arc<Warmup> {
+        &self.warmup
+    }
+
+    /// 交易对规格注册表
+    pub fn registry(&self) -> &SymbolRegistry {
+        &self.registry
+    }
+
+    /// 运行时修改交易对规格，交易员应用后之后的请求按新规格检查，返回修改后的规格
+    pub async fn update_symbol(&self, symbol: &str, patch: &SymbolPatch) -> anyhow::Result<SymbolSpec> {
+        let _updates = self.registry.lock_updates().await;
+        let spec = self.registry.get(symbol)
+            .ok_or_else(|| anyhow!("symbol not registered, symbol={}", symbol))?
+            .apply(patch)?;
+        self.send_spec(spec).await
+    }
+
+    /// 运行时替换交易对的完整规格，用于重新加载配置，规格中未配置的项恢复为默认值
+    pub async fn replace_symbol(&self, spec: SymbolSpec) -> anyhow::Result<SymbolSpec> {
+        let _updates = self.registry.lock_updates().await;
+        spec.validate()?;
+        self.send_spec(spec).await
+    }
+
+    async fn send_spec(&self, spec: SymbolSpec) -> anyhow::Result<SymbolSpec> {
+        let symbol = spec.symbol.as_str();
+        let route = self.route(symbol)
+            .ok_or_else(|| anyhow!("symbol not registered, symbol={}", symbol))?;
+        let (tx, rx) = oneshot::channel();
+        route.control.send(TraderControl::UpdateSpec(spec.clone(), tx))
+            .map_err(|_| anyhow!("trader stopped, symbol={}", symbol))?;
+        tokio::time::timeout(DUMP_TIMEOUT, rx).await
+            .map_err(|_| anyhow!("update spec timeout, symbol={}", symbol))?
+            .map_err(|_| anyhow!("trader stopped, symbol={}", symbol))??;
+        route.throttle.set_max(spec.max_orders_per_sec);
+        self.registry.insert(spec.clone());
+        info!("SYMBOL UPDATED: {:?}", &spec);
+        Ok(spec)
+    }
+
+    /// 暂停交易对，缓冲模式下新请求进入队列直到队列已满，之后按背压策略处理
+    pub fn pause(&self, symbol: &str, mode: PauseMode) -> anyhow::Result<()> {
+        let route = self.route(symbol)
+            .ok_or_else(|| anyhow!("symbol not registered, symbol={}", symbol))?;
+        route.pause.pause(mode);
+        info!("SYMBOL PAUSED: symbol={}, mode={:?}", symbol, mode);
+        Ok(())
+    }
+
+    /// 撤销交易对的所有挂单，推送撤单结果并清理缓存，返回撤销的数量
+    pub async fn cancel_all(&self, symbol: &str) -> anyhow::Result<usize> {
+        let route = self.route(symbol)
+            .ok_or_else(|| anyhow!("symbol not registered, symbol={}", symbol))?;
+        let (tx, rx) = oneshot::channel();
+        route.control.send(TraderControl::CancelAll(tx))
+            .map_err(|_| anyhow!("trader stopped, symbol={}", symbol))?;
+        tokio::time::timeout(CANCEL_ALL_TIMEOUT, rx).await
+            .map_err(|_| anyhow!("cancel all timeout, symbol={}", symbol))?
+            .map_err(|_| anyhow!("trader stopped, symbol={}", symbol))?
+    }
+
+    /// 停牌交易对，按orders保留或撤销挂单，返回撤销的数量
+    pub async fn halt(&self, symbol: &str, mode: PauseMode, orders: HaltOrders) -> anyhow::Result<usize> {
+        self.pause(symbol, mode)?;
+        match orders {
+            HaltOrders::Freeze => Ok(0),
+            HaltOrders::Cancel => self.cancel_all(symbol).await,
+        }
+    }
+
+    /// 恢复交易对，交易员被唤醒后继续处理队列中的请求
+    pub fn resume(&self, symbol: &str) -> anyhow::Result<()> {
+        let route = self.route(symbol)
+            .ok_or_else(|| anyhow!("symbol not registered, symbol={}", symbol))?;
+        if route.pause.resume() {
+            route.control.send(TraderControl::Wake)
+                .map_err(|_| anyhow!("trader stopped, symbol={}", symbol))?;
+            info!("SYMBOL RESUMED: symbol={}, pending={}", symbol, route.sender.pending());
+        }
+        Ok(())
+    }
+
+    /// 交易对的暂停方式，未暂停时为空
+    pub fn paused(&self, symbol: &str) -> Option<PauseMode> {
+        self.route(symbol).and_then(|r| r.pause.mode())
+    }
+
+    /// 交易对的市场状态，暂停或缓存恢复未完成时为停牌，之后按交易时段阶段，交易对未注册时为空
+    pub fn status(&self, symbol: &str) -> Option<MarketStatus> {
+        let route = self.route(symbol)?;
+        if route.pause.is_paused() || !route.ready.load(Ordering::Acquire) {
+            return Some(MarketStatus::Halted);
+        }
+        match route.session.phase() {
+            SessionPhase::Closed => Some(MarketStatus::Closed),
+            SessionPhase::Auction => Some(MarketStatus::Auction),
+            SessionPhase::Open => Some(MarketStatus::Open),
+        }
+    }
+
+    /// 生成所有交易对的市场镜像，由各交易员在撮合线程中生成，任一交易员未响应时失败
+    pub async fn image(&self) -> anyhow::Result<EngineImage> {
+        let mut books = Vec::new();
+        for symbol in self.symbols() {
+            books.push(self.book_image(&symbol).await?);
+        }
+        Ok(EngineImage::new(books))
+    }
+
+    /// 生成交易对的市场镜像，由交易员在撮合线程中生成
+    pub async fn book_image(&self, symbol: &str) -> anyhow::Result<BookImage> {
+        let route = self.route(symbol)
+            .ok_or_else(|| anyhow!("symbol not registered, symbol={}", symbol))?;
+        let (tx, rx) = oneshot::channel();
+        route.control.send(TraderControl::Image(tx))
+            .map_err(|_| anyhow!("trader stopped, symbol={}", symbol))?;
+        tokio::time::timeout(DUMP_TIMEOUT, rx).await
+            .map_err(|_| anyhow!("image timeout, symbol={}", symbol))?
+            .map_err(|_| anyhow!("trader stopped, symbol={}", symbol))
+    }
+
+    /// 用镜像替换交易对的市场状态，镜像中的交易对必须已注册，返回恢复的挂单数量
+    ///
+    /// 恢复期间交易对拒绝新的撮合请求，缓存中的挂单不会随之修改
+    pub async fn restore(&self, image: EngineImage) -> anyhow::Result<usize> {
+        image.check_format()?;
+        let routes = image.books.iter()
+            .map(|book| self.route(&book.symbol).ok_or_else(|| anyhow!("symbol not registered, symbol={}", book.symbol)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let mut restored = 0;
+        for (book, route) in image.books.into_iter().zip(routes) {
+            let symbol = book.symbol.clone();
+            let seq = book.seq;
+            let orders: Vec<Order> = book.bids.iter().chain(book.asks.iter()).cloned().collect();
+            // 持有序列号锁，恢复期间不会分配新的序列号
+            let mut sequence = route.sequencer.lock().await;
+            let ready = route.ready.swap(false, Ordering::AcqRel);
+            let (tx, rx) = oneshot::channel();
+            let result = match route.control.send(TraderControl::Restore(book, tx)) {
+                Ok(_) => tokio::time::timeout(DUMP_TIMEOUT, rx).await
+                    .map_err(|_| anyhow!("restore timeout, symbol={}", symbol))
+                    .and_then(|r| r.map_err(|_| anyhow!("trader stopped, symbol={}", symbol)))
+                    .and_then(|r| r),
+                Err(_) => Err(anyhow!("trader stopped, symbol={}", symbol)),
+            };
+            if let Err(e) = result {
+                route.ready.store(ready, Ordering::Release);
+                return Err(e);
+            }
+            if let Some(accounts) = &self.accounts {
+                accounts.clear_symbol(&symbol);
+                orders.iter().for_each(|order| accounts.track(order));
             }
+            if let Some(ledger) = &self.ledger {
+                ledger.reset_symbol(&symbol, &orders);
+            }
+            sequence.advance(seq);
+            route.ready.store(true, Ordering::Release);
+            info!("RESTORED: symbol={}, seq={}, orders_cnt={}", &symbol, seq, orders.len());
+            restored += orders.len();
+        }
+        Ok(restored)
+    }
+
+    /// 撮合请求未被受理时释放账户额度并撤回风控检查
+    fn release_account(&self, order: &Order) {
+        if order.action != OrderAction::PLACE {
+            return;
+        }
+        if let Some(accounts) = &self.accounts {
+            accounts.release(&order.symbol, order.id);
         }
+        self.risk.release(order);
+    }
+
+    fn route(&self, symbol: &str) -> Option<Route> {
+        let id = SymbolId::lookup(symbol)?;
+        self.routes.read().unwrap().route(id).cloned()
+    }
+
+    /// 按订单携带的交易对编号查找路由
+    fn route_of(&self, symbol: &Symbol) -> Option<Route> {
+        let id = symbol.id()?;
+        self.routes.read().unwrap().route(id).cloned()
+    }
+
+    /// 交易对是否已完成恢复并接受撮合请求
+    pub fn is_ready(&self, symbol: &str) -> bool {
+        self.route(symbol).map(|r| r.ready.load(Ordering::Acquire)).unwrap_or(false)
+    }
+
+    /// 获取交易对的撮合请求发送器
+    pub fn sender(&self, symbol: &str) -> Option<OrderSender> {
+        let routes = self.routes.read().unwrap();
+        let id = SymbolId::lookup(symbol)?;
+        routes.route(id).map(|r| r.sender.clone())
+    }
+
+    /// 交易对最新发布的市场快照
+    pub fn snapshot(&self, symbol: &str) -> Option<Arc<BookSnapshot>> {
+        let routes = self.routes.read().unwrap();
+        let id = SymbolId::lookup(symbol)?;
+        routes.route(id).map(|r| r.snapshot.load())
+    }
+
+    /// 交易对的内部编号
+    pub fn symbol_id(&self, symbol: &str) -> Option<SymbolId> {
+        SymbolId::lookup(symbol).filter(|id| self.routes.read().unwrap().route(*id).is_some())
+    }
+
+    /// 已注册的交易对
+    pub fn symbols(&self) -> Vec<String> {
+        let routes = self.routes.read().unwrap();
+        routes.iter()
+            .filter(|(id, _)| routes.route(*id).is_some())
+            .map(|(_, s)| s.to_string())
+            .collect()
+    }
+
+    /// 各交易对的缓存恢复状态
+    pub fn recovery_status(&self) -> Vec<(String, RecoveryStatus)> {
+        let routes = self.routes.read().unwrap();
+        routes.iter()
+            .filter_map(|(id, s)| routes.route(id).map(|r| (s.to_string(), RecoveryStatus {
+                total: r.recovery.total(),
+                read: r.recovery.read(),
+                ready: r.ready.load(Ordering::Acquire),
+            })))
+            .collect()
+    }
+
+    /// 各交易对交易员是否存活
+    pub fn trader_liveness(&self) -> Vec<(String, bool)> {
+        let routes = self.routes.read().unwrap();
+        routes.iter()
+            .filter_map(|(id, s)| routes.route(id).map(|r| (s.to_string(), r.liveness.is_alive())))
+            .collect()
+    }
+
+    /// 各交易对撮合延迟直方图
+    pub fn latencies(&self) -> Vec<(String, Arc<LatencyHistogram>)> {
+        let routes = self.routes.read().unwrap();
+        routes.iter()
+            .filter_map(|(id, s)| routes.route(id).map(|r| (s.to_string(), Arc::clone(&r.latency))))
+            .collect()
+    }
+
+    /// 各交易对撮合请求排队时间
+    pub fn queue_waits(&self) -> Vec<(String, Arc<QueueWait>)> {
+        let routes = self.routes.read().unwrap();
+        routes.iter()
+            .filter_map(|(id, s)| routes.route(id).map(|r| (s.to_string(), Arc::clone(&r.queue_wait))))
+            .collect()
+    }
+
+    /// 各交易对累计撮合请求处理失败次数
+    pub fn consumer_failures(&self) -> Vec<(String, u64)> {
+        let routes = self.routes.read().unwrap();
+        routes.iter()
+            .filter_map(|(id, s)| routes.route(id).map(|r| (s.to_string(), r.failures.load(Ordering::Relaxed))))
+            .collect()
+    }
+
+    /// 各交易对累计因下单速率超限拒绝的订单数量
+    pub fn throttled(&self) -> Vec<(String, u64)> {
+        let routes = self.routes.read().unwrap();
+        routes.iter()
+            .filter_map(|(id, s)| routes.route(id).map(|r| (s.to_string(), r.throttle.throttled())))
+            .collect()
+    }
+
+    /// 启用客户端订单ID检查时各交易对的ID高水位
+    pub fn id_watermarks(&self) -> Vec<(String, Arc<IdWatermark>)> {
+        let routes = self.routes.read().unwrap();
+        routes.iter()
+            .filter_map(|(id, s)| routes.route(id).and_then(|r| r.ids.clone()).map(|ids| (s.to_string(), ids)))
+            .collect()
+    }
+
+    /// 转储所有交易对的完整订单簿，交易员未及时响应时以最新快照代替
+    pub async fn dump(&self, reason: &str) -> EngineDump {
+        let routes: Vec<(String, Route)> = {
+            let routes = self.routes.read().unwrap();
+            routes.iter()
+                .filter_map(|(id, s)| routes.route(id).map(|r| (s.to_string(), r.clone())))
+                .collect()
+        };
+        let mut traders = Vec::with_capacity(routes.len());
+        for (symbol, route) in routes {
+            let (tx, rx) = oneshot::channel();
+            let reply = match route.control.send(TraderControl::Dump(tx)) {
+                Ok(_) => tokio::time::timeout(DUMP_TIMEOUT, rx).await.ok().and_then(|r| r.ok()),
+                Err(_) => None,
+            };
+            traders.push(reply.unwrap_or_else(|| TraderDump {
+                symbol,
+                book: None,
+                snapshot: Some(BookSnapshot::clone(&route.snapshot.load())),
+                pending: Vec::new(),
+                pending_count: route.sender.pending(),
+            }));
+        }
+        EngineDump::new(reason, traders)
+    }
+
+    pub fn is_shutdown(&self) -> bool {
+        self.is_shutdown.load(Ordering::SeqCst)
+    }
+
+    /// 订单持久化缓存，未配置持久化时为空
+    #[cfg(feature = "redis")]
+    pub fn cache_manager(&self) -> Option<&CacheManager> {
+        self.cache_manager.as_ref()
+    }
+
+    /// 需要缓存的功能在未配置持久化时返回错误
+    #[cfg(feature = "redis")]
+    pub fn require_cache(&self) -> anyhow::Result<&CacheManager> {
+        self.cache_manager.as_ref().ok_or_else(|| anyhow!("persistence not configured"))
     }
 }
 
+/// 订单持久化，未配置缓存时订单只保存在内存中，由订单簿拒绝重复订单
+#[cfg(feature = "redis")]
+impl EngineHandle {
+    fn sequencer(&self, symbol: &str) -> Sequencer {
+        Sequencer::new(symbol).with_cache(self.cache_manager.clone())
+    }
+
+    /// 缓存记录的成交ID高水位
+    async fn last_trade_id(&self, symbol: &str) -> anyhow::Result<u64> {
+        match &self.cache_manager {
+            Some(cache) => cache.get_trade_id(symbol).await,
+            None => Ok(0),
+        }
+    }
+
+    /// 按订单簿重写交易对的缓存挂单，返回删除的订单数量
+    async fn replace_orders(&self, symbol: &str, orders: &[Order]) -> anyhow::Result<usize> {
+        let Some(cache) = &self.cache_manager else {
+            return Ok(0);
+        };
+        let removed = cache.clear_orders(symbol).await?;
+        for order in orders {
+            cache.put_order(order).await?;
+        }
+        Ok(removed)
+    }
 
+    /// 按顺序回放交易对的日志流，返回回放的请求数量
+    async fn replay_journal(&self, symbol: &str, replayer: &mut Replayer) -> anyhow::Result<usize> {
+        let cache = self.require_cache()?;
+        let mut after = String::from("0-0");
+        let mut applied = 0;
+        loop {
+            let entries = cache.read_journal(symbol, &after, JOURNAL_REPLAY_BATCH, None).await?;
+            let Some((last, _)) = entries.last() else {
+                return Ok(applied);
+            };
+            after = last.clone();
+            for (_, order) in entries {
+                if replayer.apply(order).is_some() {
+                    applied += 1;
+                }
+            }
+        }
+    }
 
+    /// 缓存记录的客户端订单ID高水位
+    async fn last_client_id(&self, symbol: &str) -> anyhow::Result<u64> {
+        match &self.cache_manager {
+            Some(cache) => cache.get_client_id(symbol).await,
+            None => Ok(0),
+        }
+    }
+
+    async fn save_client_id(&self, symbol: &str, id: u64) -> anyhow::Result<()> {
+        match &self.cache_manager {
+            Some(cache) => cache.raise_client_id(symbol, id).await,
+            None => Ok(()),
+        }
+    }
+
+    /// 写入订单，订单已存在时返回false
+    async fn persist(&self, order: &Order) -> anyhow::Result<bool> {
+        match &self.cache_manager {
+            Some(cache) => cache.add_if_absent(order.clone()).await,
+            None => Ok(true),
+        }
+    }
+
+    async fn unpersist(&self, order: &Order) -> anyhow::Result<()> {
+        match &self.cache_manager {
+            Some(cache) => cache.del(order).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn append_journal(&self, order: &Order) -> anyhow::Result<()> {
+        match &self.cache_manager {
+            Some(cache) => cache.append_journal(order, self.replication.journal_max_len()).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn flush_ledger(&self, ledger: &Ledger) -> anyhow::Result<()> {
+        match &self.cache_manager {
+            Some(cache) => ledger.flush(cache).await.map(|_| ()),
+            None => Ok(()),
+        }
+    }
+
+    /// 按批次读取缓存中的订单，读取过程记录到progress
+    async fn read_orders(&self, symbol: &str, batch: usize, progress: &RecoveryProgress) -> anyhow::Result<Vec<Order>> {
+        let Some(cache) = &self.cache_manager else {
+            progress.start(0);
+            return Ok(Vec::new());
+        };
+        let total = cache.count_ids(symbol).await?;
+        progress.start(total);
+        let mut orders = Vec::with_capacity(total);
+        cache.get_order_batch(symbol, batch, |order| {
+            orders.push(order);
+            let read = progress.advance();
+            if read.is_multiple_of(batch) {
+                info!("RECOVER: symbol={}, read={}/{}", symbol, read, total);
+            }
+            Ok(())
+        }).await?;
+        Ok(orders)
+    }
+}
+
+#[cfg(not(feature = "redis"))]
+impl EngineHandle {
+    fn sequencer(&self, symbol: &str) -> Sequencer {
+        Sequencer::new(symbol)
+    }
+
+    async fn last_trade_id(&self, _symbol: &str) -> anyhow::Result<u64> {
+        Ok(0)
+    }
+
+    async fn replace_orders(&self, _symbol: &str, _orders: &[Order]) -> anyhow::Result<usize> {
+        Ok(0)
+    }
+
+    async fn replay_journal(&self, symbol: &str, _replayer: &mut Replayer) -> anyhow::Result<usize> {
+        Err(anyhow!("journal recovery requires redis persistence, symbol={}", symbol))
+    }
+
+    async fn last_client_id(&self, _symbol: &str) -> anyhow::Result<u64> {
+        Ok(0)
+    }
+
+    async fn save_client_id(&self, _symbol: &str, _id: u64) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn persist(&self, _order: &Order) -> anyhow::Result<bool> {
+        Ok(true)
+    }
+
+    async fn unpersist(&self, _order: &Order) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn append_journal(&self, _order: &Order) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn flush_ledger(&self, _ledger: &Ledger) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn read_orders(&self, _symbol: &str, _batch: usize, progress: &RecoveryProgress) -> anyhow::Result<Vec<Order>> {
+        progress.start(0);
+        Ok(Vec::new())
+    }
+}
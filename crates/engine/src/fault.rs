@@ -0,0 +1,180 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, RwLock};
+use std::time::Duration;
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+/// 故障注入配置，仅用于测试
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FaultConfig {
+    /// 每次操作前增加的延迟，毫秒
+    pub latency_ms: Option<u64>,
+    /// 操作失败的概率，0到1
+    pub error_rate: Option<f64>,
+    /// 批量写入只写入部分数据后失败的概率，0到1
+    pub partial_rate: Option<f64>,
+    /// 随机数种子，相同的种子注入的故障序列相同
+    pub seed: Option<u64>,
+}
+
+/// 注入的故障，调用方可通过downcast区分注入的故障和真实故障
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct InjectedFault {
+    pub op: String,
+    /// 失败前已写入的数量，None表示操作未执行
+    pub written: Option<usize>,
+}
+
+impl Display for InjectedFault {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.written {
+            Some(written) => write!(f, "injected partial write, op={}, written={}", self.op, written),
+            None => write!(f, "injected fault, op={}", self.op),
+        }
+    }
+}
+
+impl Error for InjectedFault {}
+
+/// 故障注入器，包装CacheManager和成交消费器，模拟Redis中断、慢请求和部分写入
+#[derive(Debug)]
+pub struct FaultInjector {
+    config: RwLock<FaultConfig>,
+    /// 模拟完全中断，所有操作失败
+    down: AtomicBool,
+    rng: Mutex<u64>,
+    /// 必定失败的操作
+    failing: RwLock<HashSet<String>>,
+    /// 已注入的故障次数
+    injected: AtomicU64,
+}
+
+impl FaultInjector {
+    pub fn new(config: FaultConfig) -> FaultInjector {
+        let seed = config.seed.unwrap_or(0);
+        FaultInjector {
+            config: RwLock::new(config),
+            down: AtomicBool::new(false),
+            // 种子为0时xorshift只会输出0
+            rng: Mutex::new(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1),
+            failing: RwLock::new(HashSet::new()),
+            injected: AtomicU64::new(0),
+        }
+    }
+
+    pub fn set_config(&self, config: FaultConfig) {
+        *self.config.write().unwrap() = config;
+    }
+
+    /// 开始或结束模拟中断
+    pub fn set_down(&self, down: bool) {
+        self.down.store(down, Ordering::Relaxed);
+    }
+
+    /// 设置指定操作是否必定失败，用于模拟单个操作的故障
+    pub fn set_failing(&self, op: &str, failing: bool) {
+        let mut ops = self.failing.write().unwrap();
+        if failing {
+            ops.insert(op.to_string());
+        } else {
+            ops.remove(op);
+        }
+    }
+
+    pub fn injected(&self) -> u64 {
+        self.injected.load(Ordering::Relaxed)
+    }
+
+    /// [0, 1)内的随机数
+    fn next_f64(&self) -> f64 {
+        let mut x = self.rng.lock().unwrap();
+        *x ^= *x >> 12;
+        *x ^= *x << 25;
+        *x ^= *x >> 27;
+        (x.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn hit(&self, rate: Option<f64>) -> bool {
+        rate.is_some_and(|rate| self.next_f64() < rate)
+    }
+
+    fn fault(&self, op: &str, written: Option<usize>) -> anyhow::Error {
+        self.injected.fetch_add(1, Ordering::Relaxed);
+        let fault = InjectedFault { op: op.to_string(), written };
+        warn!("{}", &fault);
+        fault.into()
+    }
+
+    /// 操作执行前调用，按配置延迟，中断或命中失败概率时返回错误
+    pub async fn before(&self, op: &str) -> anyhow::Result<()> {
+        let (latency, error_rate) = {
+            let config = self.config.read().unwrap();
+            (config.latency_ms, config.error_rate)
+        };
+        if let Some(ms) = latency {
+            tokio::time::sleep(Duration::from_millis(ms)).await;
+        }
+        if self.down.load(Ordering::Relaxed) || self.failing.read().unwrap().contains(op) || self.hit(error_rate) {
+            return Err(self.fault(op, None));
+        }
+        Ok(())
+    }
+
+    /// 批量写入前调用，命中部分写入时返回只写入的数量，写入后调用partial_fault返回错误
+    pub fn partial(&self, len: usize) -> Option<usize> {
+        let rate = self.config.read().unwrap().partial_rate;
+        if len == 0 || !self.hit(rate) {
+            return None;
+        }
+        Some((self.next_f64() * len as f64) as usize)
+    }
+
+    pub fn partial_fault(&self, op: &str, written: usize) -> anyhow::Error {
+        self.fault(op, Some(written))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use crate::fault::{FaultConfig, FaultInjector, InjectedFault};
+
+    #[tokio::test]
+    async fn inject_test() {
+        let faults = FaultInjector::new(FaultConfig::default());
+        assert!(faults.before("get").await.is_ok());
+        assert_eq!(faults.partial(10), None);
+
+        faults.set_down(true);
+        let err = faults.before("get").await.unwrap_err();
+        assert_eq!(err.downcast_ref::<InjectedFault>(), Some(&InjectedFault { op: "get".to_string(), written: None }));
+        faults.set_down(false);
+        assert!(faults.before("get").await.is_ok());
+        faults.set_failing("get", true);
+        assert!(faults.before("get").await.is_err());
+        assert!(faults.before("set").await.is_ok());
+        faults.set_failing("get", false);
+
+        faults.set_config(FaultConfig { latency_ms: Some(20), error_rate: Some(1.0), partial_rate: Some(1.0), seed: None });
+        let start = Instant::now();
+        assert!(faults.before("get").await.is_err());
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        assert!(faults.partial(10).is_some_and(|n| n < 10));
+        assert_eq!(faults.injected(), 3);
+    }
+
+    #[test]
+    fn seed_test() {
+        let config = FaultConfig { partial_rate: Some(0.5), seed: Some(7), ..Default::default() };
+        let (a, b) = (FaultInjector::new(config.clone()), FaultInjector::new(config));
+        let a: Vec<_> = (0..50).map(|_| a.partial(100)).collect();
+        let b: Vec<_> = (0..50).map(|_| b.partial(100)).collect();
+        assert_eq!(a, b);
+        assert!(a.iter().any(Option::is_some) && a.iter().any(Option::is_none));
+    }
+}
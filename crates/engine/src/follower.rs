@@ -0,0 +1,149 @@
+use std::time::{Duration, Instant};
+
+use log::{debug, error, info, warn};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+use loom_core::snapshot::StateHash;
+
+use crate::engine::EngineHandle;
+use crate::replication::ReplicationRole;
+
+/// 备机每次读取的日志条目数量
+const FOLLOW_BATCH: usize = 512;
+/// 备机等待新日志的最长时间
+const FOLLOW_BLOCK: Duration = Duration::from_millis(1000);
+/// 备机等待交易员处理到检查点的最长时间
+const VERIFY_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// 主机定时发布各交易对的状态哈希检查点，备机重放到相同序列号时比对
+pub fn launch_checkpoints(engine: EngineHandle, interval: Duration, mut ctx: broadcast::Receiver<bool>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval.max(Duration::from_millis(100)));
+        loop {
+            tokio::select! {
+                _ = ctx.recv() => break,
+                _ = ticker.tick() => {
+                    // 备机提升后开始发布
+                    if engine.replication().role() != ReplicationRole::Primary {
+                        continue;
+                    }
+                    for symbol in engine.symbols() {
+                        let result = match engine.state_hash(&symbol).await {
+                            Ok(hash) => match engine.require_cache() {
+                                Ok(cache) => cache.set_checkpoint(&hash).await,
+                                Err(e) => Err(e),
+                            },
+                            Err(e) => Err(e),
+                        };
+                        if let Err(e) = result {
+                            warn!("CHECKPOINT FAILED: symbol={}, err={}", symbol, e);
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// 备机日志跟随器，按序列号顺序将主机日志重放到本机交易员
+pub(crate) struct Follower {
+    engine: EngineHandle,
+    symbol: String,
+    /// 最后读取的日志条目ID
+    last_id: String,
+    /// 最后重放的请求序列号
+    applied: u64,
+    /// 等待比对的主机检查点
+    checkpoint: Option<StateHash>,
+}
+
+impl Follower {
+    /// last_id为缓存恢复前日志流的末尾，之后写入的日志都会被重放
+    pub(crate) fn new(engine: EngineHandle, symbol: &str, last_id: String) -> Follower {
+        Follower {
+            engine,
+            symbol: String::from(symbol),
+            last_id,
+            applied: 0,
+            checkpoint: None,
+        }
+    }
+
+    pub(crate) fn launch(self) -> JoinHandle<()> {
+        tokio::spawn(self.run())
+    }
+
+    async fn run(mut self) {
+        info!("REPLICA FOLLOWING: symbol={}, from={}", &self.symbol, &self.last_id);
+        loop {
+            if self.engine.is_shutdown() {
+                break;
+            }
+            // 提升时不再等待新日志，重放完剩余日志后退出
+            let draining = self.engine.replication().role() != ReplicationRole::Standby;
+            let block = if draining { None } else { Some(FOLLOW_BLOCK) };
+            match self.poll(block).await {
+                Ok(0) if draining => break,
+                Ok(_) => {}
+                Err(e) => {
+                    warn!("REPLICA POLL FAILED: symbol={}, err={}", &self.symbol, e);
+                    if draining {
+                        break;
+                    }
+                    tokio::time::sleep(FOLLOW_BLOCK).await;
+                }
+            }
+        }
+        info!("REPLICA STOPPED: symbol={}, applied={}", &self.symbol, self.applied);
+    }
+
+    /// 读取并重放一批日志，返回读取的条目数量
+    async fn poll(&mut self, block: Option<Duration>) -> anyhow::Result<usize> {
+        let cache = self.engine.require_cache()?.clone();
+        if self.checkpoint.is_none() {
+            self.checkpoint = cache.get_checkpoint(&self.symbol).await?.filter(|c| c.seq > self.applied);
+        }
+        let entries = cache.read_journal(&self.symbol, &self.last_id, FOLLOW_BATCH, block).await?;
+        for (id, order) in entries.iter() {
+            // 重放越过检查点之前比对状态
+            if let Some(checkpoint) = self.checkpoint.take_if(|c| order.seq > c.seq) {
+                self.verify(checkpoint).await?;
+            }
+            if order.seq > self.applied {
+                self.engine.replicate(order.clone()).await?;
+                self.applied = order.seq;
+            }
+            self.last_id = id.clone();
+        }
+        if let Some(checkpoint) = self.checkpoint.take_if(|c| c.seq == self.applied) {
+            self.verify(checkpoint).await?;
+        }
+        Ok(entries.len())
+    }
+
+    /// 等待交易员处理完已重放的请求后与主机检查点比对
+    async fn verify(&self, checkpoint: StateHash) -> anyhow::Result<()> {
+        let deadline = Instant::now() + VERIFY_TIMEOUT;
+        let local = loop {
+            let local = self.engine.state_hash(&self.symbol).await?;
+            if local.seq >= self.applied {
+                break local;
+            }
+            if Instant::now() > deadline {
+                warn!("REPLICA VERIFY TIMEOUT: symbol={}, applied={}, local_seq={}", &self.symbol, self.applied, local.seq);
+                return Ok(());
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        };
+        if local.seq != checkpoint.seq {
+            debug!("REPLICA CHECKPOINT SKIPPED: symbol={}, checkpoint_seq={}, local_seq={}", &self.symbol, checkpoint.seq, local.seq);
+        } else if local.hash == checkpoint.hash {
+            debug!("REPLICA IN SYNC: symbol={}, seq={}, hash={}", &self.symbol, local.seq, &local.hash);
+        } else {
+            self.engine.replication().diverged();
+            error!("REPLICA DIVERGED: symbol={}, seq={}, primary={}, standby={}", &self.symbol, local.seq, &checkpoint.hash, &local.hash);
+        }
+        Ok(())
+    }
+}
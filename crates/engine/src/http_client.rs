@@ -0,0 +1,323 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::anyhow;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use url::Url;
+
+/// 连接池保留的最大空闲连接数
+const MAX_IDLE_CONNS: usize = 4;
+
+/// 简单的HTTP响应
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    /// 状态码
+    pub status: u16,
+    /// 响应体
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    pub fn body_text(&self) -> String {
+        String::from_utf8_lossy(&self.body).to_string()
+    }
+}
+
+/// 发送HTTP请求，仅支持http协议，每次请求使用独立连接
+pub async fn request(method: &str, url: &str, headers: &[(&str, &str)], body: &[u8]) -> anyhow::Result<HttpResponse> {
+    let url = Url::parse(url)?;
+    if url.scheme() != "http" {
+        return Err(anyhow!("unsupported url scheme: {}", url.scheme()));
+    }
+    let host = url.host_str().ok_or_else(|| anyhow!("url missing host: {}", url))?;
+    let port = url.port_or_known_default().unwrap_or(80);
+    let mut path = url.path().to_string();
+    if let Some(query) = url.query() {
+        path.push('?');
+        path.push_str(query);
+    }
+
+    let mut req = format!(
+        "{} {} HTTP/1.1\r\nHost: {}:{}\r\nConnection: close\r\nContent-Length: {}\r\n",
+        method, path, host, port, body.len()
+    );
+    for (name, value) in headers {
+        req.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    req.push_str("\r\n");
+
+    let mut stream = TcpStream::connect((host, port)).await?;
+    stream.write_all(req.as_bytes()).await?;
+    stream.write_all(body).await?;
+    stream.flush().await?;
+    let mut raw = Vec::new();
+    stream.read_to_end(&mut raw).await?;
+    parse_response(&raw)
+}
+
+pub async fn post(url: &str, headers: &[(&str, &str)], body: &[u8]) -> anyhow::Result<HttpResponse> {
+    request("POST", url, headers, body).await
+}
+
+/// 复用连接的HTTP客户端，仅支持http协议，请求固定主机，每次请求整体受超时限制
+#[derive(Clone, Debug)]
+pub struct HttpClient {
+    host: String,
+    port: u16,
+    timeout: Duration,
+    /// 空闲的keep-alive连接
+    idle: Arc<Mutex<Vec<TcpStream>>>,
+}
+
+impl HttpClient {
+    /// base_url只取协议、主机和端口，非http协议返回错误
+    pub fn new(base_url: &str, timeout: Duration) -> anyhow::Result<HttpClient> {
+        let url = Url::parse(base_url)?;
+        if url.scheme() != "http" {
+            return Err(anyhow!("unsupported url scheme: {}", url.scheme()));
+        }
+        let host = url.host_str().ok_or_else(|| anyhow!("url missing host: {}", url))?.to_string();
+        let port = url.port_or_known_default().unwrap_or(80);
+        Ok(HttpClient { host, port, timeout, idle: Arc::new(Mutex::new(Vec::new())) })
+    }
+
+    /// 发送请求，path包含查询串，超时后丢弃该连接
+    pub async fn request(&self, method: &str, path: &str, headers: &[(&str, &str)], body: &[u8]) -> anyhow::Result<HttpResponse> {
+        tokio::time::timeout(self.timeout, self.send(method, path, headers, body)).await
+            .map_err(|_| anyhow!("http request timed out after {:?}, host={}:{}", self.timeout, self.host, self.port))?
+    }
+
+    pub async fn post(&self, path: &str, headers: &[(&str, &str)], body: &[u8]) -> anyhow::Result<HttpResponse> {
+        self.request("POST", path, headers, body).await
+    }
+
+    async fn send(&self, method: &str, path: &str, headers: &[(&str, &str)], body: &[u8]) -> anyhow::Result<HttpResponse> {
+        let mut req = format!(
+            "{} {} HTTP/1.1\r\nHost: {}:{}\r\nContent-Length: {}\r\n",
+            method, path, self.host, self.port, body.len()
+        );
+        for (name, value) in headers {
+            req.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        req.push_str("\r\n");
+
+        let pooled = self.idle.lock().unwrap().pop();
+        if let Some(mut stream) = pooled {
+            // 空闲连接可能已被服务端关闭，未收到任何响应时换新连接重发
+            if let Some((resp, keep_alive)) = exchange(&mut stream, req.as_bytes(), body).await? {
+                self.release(stream, keep_alive);
+                return Ok(resp);
+            }
+        }
+        let mut stream = TcpStream::connect((self.host.as_str(), self.port)).await?;
+        let (resp, keep_alive) = exchange(&mut stream, req.as_bytes(), body).await?
+            .ok_or_else(|| anyhow!("connection closed before response, host={}:{}", self.host, self.port))?;
+        self.release(stream, keep_alive);
+        Ok(resp)
+    }
+
+    fn release(&self, stream: TcpStream, keep_alive: bool) {
+        let mut idle = self.idle.lock().unwrap();
+        if keep_alive && idle.len() < MAX_IDLE_CONNS {
+            idle.push(stream);
+        }
+    }
+}
+
+/// 在连接上发送一个请求并读取完整响应，连接在收到任何响应前关闭或出错时返回None
+async fn exchange(stream: &mut TcpStream, head: &[u8], body: &[u8]) -> anyhow::Result<Option<(HttpResponse, bool)>> {
+    let sent = async {
+        stream.write_all(head).await?;
+        stream.write_all(body).await?;
+        stream.flush().await
+    };
+    if sent.await.is_err() {
+        return Ok(None);
+    }
+    let mut raw = Vec::new();
+    let mut chunk = [0u8; 8192];
+    loop {
+        if let Some(complete) = complete_response(&raw)? {
+            return Ok(Some(complete));
+        }
+        let n = match stream.read(&mut chunk).await {
+            Ok(n) => n,
+            Err(_) if raw.is_empty() => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        if n == 0 {
+            if raw.is_empty() {
+                return Ok(None);
+            }
+            // 既没有Content-Length也没有分块编码，以连接关闭作为响应结束
+            return parse_response(&raw).map(|resp| Some((resp, false)));
+        }
+        raw.extend_from_slice(&chunk[..n]);
+    }
+}
+
+/// 按Content-Length或分块编码判断响应是否完整，完整时返回响应和连接能否复用
+fn complete_response(raw: &[u8]) -> anyhow::Result<Option<(HttpResponse, bool)>> {
+    let split = match raw.windows(4).position(|w| w == b"\r\n\r\n") {
+        Some(split) => split,
+        None => return Ok(None),
+    };
+    let head = String::from_utf8_lossy(&raw[..split]).to_ascii_lowercase();
+    let mut keep_alive = true;
+    let mut length = None;
+    let mut chunked = false;
+    for line in head.split("\r\n").skip(1) {
+        let (name, value) = match line.split_once(':') {
+            Some((name, value)) => (name.trim(), value.trim()),
+            None => continue,
+        };
+        match name {
+            "connection" => keep_alive = value != "close",
+            "content-length" => length = Some(value.parse::<usize>()?),
+            "transfer-encoding" => chunked = value.contains("chunked"),
+            _ => {}
+        }
+    }
+    let body = &raw[split + 4..];
+    let complete = if chunked {
+        chunked_complete(body)?
+    } else if let Some(length) = length {
+        body.len() >= length
+    } else {
+        false
+    };
+    if !complete {
+        return Ok(None);
+    }
+    Ok(Some((parse_response(raw)?, keep_alive)))
+}
+
+/// 分块编码体是否完整
+fn chunked_complete(data: &[u8]) -> anyhow::Result<bool> {
+    let mut pos = 0;
+    loop {
+        let line_end = match data[pos..].windows(2).position(|w| w == b"\r\n") {
+            Some(end) => pos + end,
+            None => return Ok(false),
+        };
+        let size_line = String::from_utf8_lossy(&data[pos..line_end]);
+        let size = usize::from_str_radix(size_line.split(';').next().unwrap_or("").trim(), 16)?;
+        pos = line_end + 2;
+        if size == 0 {
+            // 最后一块之后可能带trailer，以空行结束
+            let rest = &data[pos..];
+            return Ok(rest.starts_with(b"\r\n") || rest.windows(4).any(|w| w == b"\r\n\r\n"));
+        }
+        if data.len() < pos + size + 2 {
+            return Ok(false);
+        }
+        pos += size + 2;
+    }
+}
+
+fn parse_response(raw: &[u8]) -> anyhow::Result<HttpResponse> {
+    let split = raw.windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or_else(|| anyhow!("malformed http response"))?;
+    let head = String::from_utf8_lossy(&raw[..split]);
+    let mut lines = head.split("\r\n");
+    let status = lines.next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .ok_or_else(|| anyhow!("malformed http status line"))?
+        .parse::<u16>()?;
+    let chunked = lines.any(|line| {
+        let line = line.to_ascii_lowercase();
+        line.starts_with("transfer-encoding:") && line.contains("chunked")
+    });
+    let body = &raw[split + 4..];
+    let body = if chunked { decode_chunked(body)? } else { body.to_vec() };
+    Ok(HttpResponse { status, body })
+}
+
+fn decode_chunked(mut data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut body = Vec::new();
+    loop {
+        let line_end = data.windows(2)
+            .position(|w| w == b"\r\n")
+            .ok_or_else(|| anyhow!("malformed chunked body"))?;
+        let size_line = String::from_utf8_lossy(&data[..line_end]);
+        let size = usize::from_str_radix(size_line.split(';').next().unwrap_or("").trim(), 16)?;
+        data = &data[line_end + 2..];
+        if size == 0 {
+            break;
+        }
+        if data.len() < size {
+            return Err(anyhow!("truncated chunked body"));
+        }
+        body.extend_from_slice(&data[..size]);
+        data = &data[(size + 2).min(data.len())..];
+    }
+    Ok(body)
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use crate::http_client::{parse_response, HttpClient};
+
+    #[test]
+    fn parse_plain_response_test() {
+        let resp = parse_response(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok").unwrap();
+        assert_eq!(resp.status, 200);
+        assert_eq!(resp.body_text(), "ok");
+    }
+
+    #[test]
+    fn parse_chunked_response_test() {
+        let raw = b"HTTP/1.1 500 Internal Server Error\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n2\r\nde\r\n0\r\n\r\n";
+        let resp = parse_response(raw).unwrap();
+        assert_eq!(resp.status, 500);
+        assert_eq!(resp.body_text(), "abcde");
+    }
+
+    #[tokio::test]
+    async fn keep_alive_test() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            // 只接受一个连接，两次请求都在该连接上应答
+            let (mut stream, _) = listener.accept().await.unwrap();
+            for body in ["ok", "abcde"] {
+                let mut req = Vec::new();
+                let mut buf = [0u8; 1024];
+                while !req.ends_with(b"\r\n\r\nping") {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    assert!(n > 0);
+                    req.extend_from_slice(&buf[..n]);
+                }
+                let resp = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}", body.len(), body);
+                stream.write_all(resp.as_bytes()).await.unwrap();
+            }
+        });
+        let client = HttpClient::new(&format!("http://{}", addr), Duration::from_secs(5)).unwrap();
+        assert_eq!(client.post("/", &[], b"ping").await.unwrap().body_text(), "ok");
+        assert_eq!(client.post("/", &[], b"ping").await.unwrap().body_text(), "abcde");
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn timeout_test() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = HttpClient::new(&format!("http://{}", addr), Duration::from_millis(50)).unwrap();
+        // 服务端接受连接但不应答
+        let err = client.post("/", &[], b"ping").await.unwrap_err();
+        assert!(err.to_string().contains("timed out"));
+        drop(listener);
+        assert!(HttpClient::new("https://localhost", Duration::from_secs(1)).is_err());
+    }
+}
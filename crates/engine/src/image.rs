@@ -0,0 +1,113 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+use loom_core::snapshot::BookImage;
+use loom_core::utils;
+
+/// 镜像格式版本，格式不兼容时递增
+pub const IMAGE_FORMAT_VERSION: u32 = 1;
+
+/// 引擎镜像，包含所有交易对的市场状态，用于快速重启和蓝绿部署
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct EngineImage {
+    /// 镜像格式版本
+    pub format: u32,
+    /// 生成时间
+    pub ts: u128,
+    pub books: Vec<BookImage>,
+}
+
+impl EngineImage {
+    pub fn new(books: Vec<BookImage>) -> EngineImage {
+        EngineImage {
+            format: IMAGE_FORMAT_VERSION,
+            ts: utils::now_ts(),
+            books,
+        }
+    }
+
+    /// 各交易对的挂单数量
+    pub fn orders(&self) -> Vec<(String, usize)> {
+        self.books.iter()
+            .map(|b| (b.symbol.clone(), b.bids.len() + b.asks.len()))
+            .collect()
+    }
+
+    /// 检查镜像格式版本是否可以恢复
+    pub fn check_format(&self) -> anyhow::Result<()> {
+        if self.format != IMAGE_FORMAT_VERSION {
+            return Err(anyhow!("unsupported image format, format={}, expected={}", self.format, IMAGE_FORMAT_VERSION));
+        }
+        Ok(())
+    }
+}
+
+/// 将镜像写入dir目录，返回文件路径
+pub fn write_image(dir: &Path, image: &EngineImage) -> anyhow::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!("loom-image-{}.json", image.ts));
+    // 先写临时文件再重命名，避免读到写了一半的镜像
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_vec(image)?)?;
+    fs::rename(&tmp, &path)?;
+    Ok(path)
+}
+
+/// 转储目录中最新的镜像文件，path不是目录时原样返回
+pub fn latest_image(path: &Path) -> anyhow::Result<PathBuf> {
+    if !path.is_dir() {
+        return Ok(path.to_path_buf());
+    }
+    let mut latest: Option<(u128, PathBuf)> = None;
+    for entry in fs::read_dir(path)? {
+        let file = entry?.path();
+        let ts = file.file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix("loom-image-"))
+            .and_then(|name| name.strip_suffix(".json"))
+            .and_then(|ts| ts.parse::<u128>().ok());
+        if let Some(ts) = ts {
+            if latest.as_ref().is_none_or(|(latest, _)| ts > *latest) {
+                latest = Some((ts, file));
+            }
+        }
+    }
+    latest.map(|(_, file)| file)
+        .ok_or_else(|| anyhow!("no image found, dir={}", path.display()))
+}
+
+/// 读取镜像文件并检查格式版本
+pub fn read_image(path: &Path) -> anyhow::Result<EngineImage> {
+    let bytes = fs::read(path)
+        .map_err(|e| anyhow!("open image failed, path={}, err={}", path.display(), e))?;
+    let image: EngineImage = serde_json::from_slice(&bytes)?;
+    image.check_format()?;
+    Ok(image)
+}
+
+#[cfg(test)]
+mod test {
+    use loom_core::market::MarketBook;
+
+    use crate::image::{latest_image, read_image, write_image, EngineImage};
+
+    #[test]
+    fn image_file_test() {
+        let dir = std::env::temp_dir().join(format!("loom-image-test-{}", std::process::id()));
+        let image = EngineImage::new(vec![MarketBook::new("LOOM-USDT-SPOT").image()]);
+        let path = write_image(&dir, &image).unwrap();
+        assert_eq!(read_image(&path).unwrap(), image);
+        let mut future = image.clone();
+        future.format += 1;
+        future.ts += 1;
+        let path = write_image(&dir, &future).unwrap();
+        assert!(read_image(&path).is_err());
+        // 目录中按生成时间选择最新的镜像
+        assert_eq!(latest_image(&dir).unwrap(), path);
+        assert!(latest_image(&dir.join("missing")).is_ok_and(|p| p == dir.join("missing")));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
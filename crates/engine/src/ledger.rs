@@ -0,0 +1,574 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use bigdecimal::{BigDecimal, Zero};
+use log::warn;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "redis")]
+use tokio::sync::broadcast;
+#[cfg(feature = "redis")]
+use tokio::task::JoinHandle;
+
+use loom_core::market::MatchTrade;
+use loom_core::order::{Order, OrderType, TradeSide};
+use loom_core::snapshot::BookSnapshot;
+use loom_core::symbol::Symbol;
+
+use crate::audit::{AuditLog, BalanceAdjustment};
+#[cfg(feature = "redis")]
+use crate::cache::CacheManager;
+#[cfg(feature = "redis")]
+use crate::engine::EngineHandle;
+use crate::registry::SymbolRegistry;
+#[cfg(feature = "redis")]
+use crate::replication::ReplicationRole;
+use crate::risk::{RiskCheck, RiskRejected};
+use crate::tenant;
+
+/// 默认余额写入缓存的间隔
+pub const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 手续费入账的账户，返佣从该账户支出
+pub const FEE_ACCOUNT: &str = "fees";
+
+/// 账本配置，配置后启用账本
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct LedgerConfig {
+    /// 余额写入缓存的间隔，毫秒
+    pub flush_interval_ms: Option<u64>,
+}
+
+/// 账户单个资产的余额
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Balance {
+    /// 可用余额
+    pub available: BigDecimal,
+    /// 挂单冻结的余额
+    pub frozen: BigDecimal,
+}
+
+/// 挂单预留的资金，买单预留计价资产，卖单预留基础资产
+#[derive(Debug, Clone)]
+struct Reservation {
+    account: String,
+    side: TradeSide,
+    asset: String,
+    /// 每单位数量预留的金额，买单为预留价格，卖单为1
+    unit: BigDecimal,
+    /// 尚未释放的预留金额
+    amount: BigDecimal,
+}
+
+#[derive(Debug, Default)]
+struct LedgerState {
+    /// 账户和资产对应的余额
+    balances: HashMap<(String, String), Balance>,
+    /// 交易对和订单ID对应的预留
+    reservations: HashMap<(Symbol, u64), Reservation>,
+    /// 上次写入缓存后修改过的余额
+    dirty: HashSet<(String, String)>,
+    /// 余额调整的审计日志
+    audit: Option<AuditLog>,
+}
+
+impl LedgerState {
+    fn balance(&mut self, account: &str, asset: &str) -> &mut Balance {
+        let key = (account.to_string(), asset.to_string());
+        self.dirty.insert(key.clone());
+        self.balances.entry(key).or_default()
+    }
+
+    /// 释放预留的amount，amount不超过剩余预留
+    fn unfreeze(&mut self, symbol: &Symbol, oid: u64, amount: &BigDecimal) -> Option<Reservation> {
+        let reservation = self.reservations.get_mut(&(symbol.clone(), oid))?;
+        let amount = amount.min(&reservation.amount).clone();
+        reservation.amount -= &amount;
+        let reservation = reservation.clone();
+        let balance = self.balance(&reservation.account, &reservation.asset);
+        balance.frozen -= &amount;
+        balance.available += &amount;
+        Some(reservation)
+    }
+
+    /// 订单离开订单簿，剩余预留退回可用余额
+    fn release(&mut self, symbol: &Symbol, oid: u64) {
+        if let Some(reservation) = self.reservations.remove(&(symbol.clone(), oid)) {
+            let balance = self.balance(&reservation.account, &reservation.asset);
+            balance.frozen -= &reservation.amount;
+            balance.available += &reservation.amount;
+        }
+    }
+
+    /// 结算一方成交，预留按委托价格释放后扣除实际成交金额，收到的资产扣除手续费
+    fn fill(&mut self, assets: &(String, String), trade: &MatchTrade, oid: u64, fee_rate: &BigDecimal) {
+        let qty = BigDecimal::from(trade.qty);
+        let reservation = match self.reservations.get(&(trade.symbol.clone(), oid)) {
+            Some(reservation) => reservation.clone(),
+            None => return,
+        };
+        self.unfreeze(&trade.symbol, oid, &(&reservation.unit * &qty));
+        let (base, quote) = assets;
+        let (paid, pay, received, receive) = match reservation.side {
+            TradeSide::BUY => (quote, &trade.px * &qty, base, qty),
+            TradeSide::SELL => (base, qty.clone(), quote, &trade.px * &qty),
+        };
+        let fee = &receive * fee_rate;
+        self.balance(&reservation.account, paid).available -= &pay;
+        self.balance(&reservation.account, received).available += &receive - &fee;
+        if !fee.is_zero() {
+            self.balance(FEE_ACCOUNT, received).available += fee;
+        }
+    }
+}
+
+/// 账户余额账本，下单时预留资金，撤单时释放，成交时结算
+///
+/// 作为风控检查加入检查链，未设置账户的订单不记账；余额定期写入缓存，重启时从缓存加载，
+/// 最后一次写入之后的修改会丢失
+#[derive(Debug)]
+pub struct Ledger {
+    /// 交易对规格，用于查询手续费率
+    registry: SymbolRegistry,
+    state: Mutex<LedgerState>,
+}
+
+/// 交易对的基础资产和计价资产，如LOOM-USDT-SPOT为LOOM和USDT，租户的交易对不含租户前缀
+pub fn symbol_assets(symbol: &str) -> Option<(String, String)> {
+    let mut parts = tenant::split(symbol).1.split('-');
+    match (parts.next(), parts.next()) {
+        (Some(base), Some(quote)) if !base.is_empty() && !quote.is_empty() => Some((base.to_string(), quote.to_string())),
+        _ => None,
+    }
+}
+
+impl Ledger {
+    pub fn new(registry: SymbolRegistry) -> Ledger {
+        Ledger {
+            registry,
+            state: Mutex::new(LedgerState::default()),
+        }
+    }
+
+    /// 检查可用余额并预留订单所需资金，市价买单按快照中可成交的最差卖价预留
+    pub fn reserve(&self, order: &Order, snapshot: &BookSnapshot) -> Result<(), RiskRejected> {
+        let account = match &order.account {
+            Some(account) => account,
+            None => return Ok(()),
+        };
+        let reject = |reason: String| RiskRejected::new("ledger", order, reason);
+        let reservation = self.reservation(order, account, Some(snapshot)).map_err(|e| reject(e.to_string()))?;
+        let mut state = self.state.lock().unwrap();
+        if state.reservations.contains_key(&(order.symbol.clone(), order.id)) {
+            return Err(reject(String::from("order already reserved")));
+        }
+        let balance = state.balance(account, &reservation.asset);
+        if balance.available < reservation.amount {
+            return Err(reject(format!("insufficient {} balance, available={}, required={}", reservation.asset, balance.available, reservation.amount)));
+        }
+        balance.available -= &reservation.amount;
+        balance.frozen += &reservation.amount;
+        state.reservations.insert((order.symbol.clone(), order.id), reservation);
+        Ok(())
+    }
+
+    /// 不检查余额直接冻结资金，用于备机重放主机已受理的订单
+    pub fn freeze(&self, order: &Order) {
+        self.insert(order, true);
+    }
+
+    /// 记录已在订单簿中的订单，其资金已在加载的余额中冻结，用于缓存恢复
+    pub fn track(&self, order: &Order) {
+        self.insert(order, false);
+    }
+
+    fn insert(&self, order: &Order, freeze: bool) {
+        let account = match &order.account {
+            Some(account) => account,
+            None => return,
+        };
+        let reservation = match self.reservation(order, account, None) {
+            Ok(reservation) => reservation,
+            Err(e) => {
+                warn!("LEDGER TRACK FAILED: symbol={}, oid={}, err={}", &order.symbol, order.id, e);
+                return;
+            }
+        };
+        let mut state = self.state.lock().unwrap();
+        if freeze {
+            let balance = state.balance(account, &reservation.asset);
+            balance.available -= &reservation.amount;
+            balance.frozen += &reservation.amount;
+        }
+        state.reservations.insert((order.symbol.clone(), order.id), reservation);
+    }
+
+    fn reservation(&self, order: &Order, account: &str, snapshot: Option<&BookSnapshot>) -> anyhow::Result<Reservation> {
+        let (base, quote) = symbol_assets(&order.symbol)
+            .ok_or_else(|| anyhow!("unknown assets of symbol {}", order.symbol))?;
+        let remain = BigDecimal::from(order.remain());
+        let (asset, unit) = match (order.side, order.ord_type, snapshot) {
+            (TradeSide::SELL, _, _) => (base, BigDecimal::from(1)),
+            (TradeSide::BUY, OrderType::MARKET, Some(snapshot)) => (quote, Self::market_px(order.remain(), snapshot)?),
+            (TradeSide::BUY, _, _) => (quote, order.price.clone()),
+        };
+        Ok(Reservation {
+            account: account.to_string(),
+            side: order.side,
+            asset,
+            amount: &unit * &remain,
+            unit,
+        })
+    }
+
+    /// 市价买单可能成交的最差价格，卖方深度不足时为最高档位价格，无卖单时为最新成交价
+    fn market_px(qty: u64, snapshot: &BookSnapshot) -> anyhow::Result<BigDecimal> {
+        let mut acc = 0;
+        let mut px = None;
+        for level in snapshot.asks.iter() {
+            px = Some(&level.px);
+            acc += level.qty;
+            if acc >= qty {
+                break;
+            }
+        }
+        match px {
+            Some(px) => Ok(px.clone()),
+            None if !snapshot.px.is_zero() => Ok(snapshot.px.clone()),
+            None => Err(anyhow!("no reference price for market buy")),
+        }
+    }
+
+    /// 按撮合时订单簿中可成交的最差价格调整市价买单的预留，下单时的快照可能已过期，
+    /// 价格高于预留价格时从可用余额追加预留，可用余额不足时返回false
+    pub fn reprice(&self, order: &Order, px: &BigDecimal) -> bool {
+        let key = (order.symbol.clone(), order.id);
+        let mut state = self.state.lock().unwrap();
+        let (account, asset, extra) = match state.reservations.get(&key) {
+            Some(reservation) if px > &reservation.unit => {
+                let extra = (px - &reservation.unit) * BigDecimal::from(order.remain());
+                (reservation.account.clone(), reservation.asset.clone(), extra)
+            }
+            _ => return true,
+        };
+        let balance = state.balance(&account, &asset);
+        if balance.available < extra {
+            return false;
+        }
+        balance.available -= &extra;
+        balance.frozen += &extra;
+        let reservation = state.reservations.get_mut(&key).unwrap();
+        reservation.unit = px.clone();
+        reservation.amount += extra;
+        true
+    }
+
+    /// 订单未被受理或离开订单簿时释放剩余预留
+    pub fn release(&self, symbol: &str, oid: u64) {
+        self.state.lock().unwrap().release(&Symbol::from(symbol), oid);
+    }
+
+    /// 用镜像替换交易对后重建预留，原预留退回可用余额后按镜像中的挂单重新冻结
+    pub fn reset_symbol(&self, symbol: &str, orders: &[Order]) {
+        {
+            let symbol = Symbol::from(symbol);
+            let mut state = self.state.lock().unwrap();
+            let oids: Vec<u64> = state.reservations.keys().filter(|(s, _)| s == &symbol).map(|(_, oid)| *oid).collect();
+            oids.into_iter().for_each(|oid| state.release(&symbol, oid));
+        }
+        orders.iter().for_each(|order| self.freeze(order));
+    }
+
+    /// 根据撮合结果结算成交、释放减量和已完成订单的预留
+    pub fn settle(&self, trades: &[MatchTrade]) {
+        let mut state = self.state.lock().unwrap();
+        for trade in trades {
+            if trade.qty != 0 {
+                let (assets, fees) = match (symbol_assets(&trade.symbol), self.registry.get(&trade.symbol)) {
+                    (Some(assets), Some(spec)) => (assets, spec.fees),
+                    (Some(assets), None) => (assets, Default::default()),
+                    (None, _) => continue,
+                };
+                state.fill(&assets, trade, trade.taker_oid, &fees.taker);
+                state.fill(&assets, trade, trade.maker_oid, &fees.maker);
+                if trade.maker_state.del_flag() {
+                    state.release(&trade.symbol, trade.maker_oid);
+                }
+            }
+            if trade.is_reduce() {
+                if let Some(reservation) = state.reservations.get(&(trade.symbol.clone(), trade.taker_oid)) {
+                    let amount = &reservation.unit * BigDecimal::from(trade.reduce_qty);
+                    state.unfreeze(&trade.symbol, trade.taker_oid, &amount);
+                }
+            }
+            if trade.taker_state.del_flag() {
+                state.release(&trade.symbol, trade.taker_oid);
+            }
+        }
+    }
+
+    /// 余额调整写入审计日志，写入失败的调整不生效
+    pub fn set_audit(&self, audit: AuditLog) {
+        self.state.lock().unwrap().audit = Some(audit);
+    }
+
+    /// 调整账户可用余额，正数为充值，负数为提现，可用余额不足时失败
+    pub fn adjust(&self, account: &str, asset: &str, amount: &BigDecimal) -> anyhow::Result<Balance> {
+        let mut state = self.state.lock().unwrap();
+        let available = &state.balance(account, asset).available + amount;
+        if available < BigDecimal::zero() {
+            return Err(anyhow!("insufficient {} balance, account={}, available={}", asset, account, available - amount));
+        }
+        if let Some(audit) = state.audit.as_mut() {
+            let adjustment = BalanceAdjustment { account: account.to_string(), asset: asset.to_string(), amount: amount.clone(), available };
+            audit.append_adjustment(&adjustment)?;
+        }
+        let balance = state.balance(account, asset);
+        balance.available += amount;
+        Ok(balance.clone())
+    }
+
+    /// 账户各资产的余额，按资产排序
+    pub fn balances(&self, account: &str) -> BTreeMap<String, Balance> {
+        let state = self.state.lock().unwrap();
+        state.balances.iter()
+            .filter(|((a, _), _)| a == account)
+            .map(|((_, asset), balance)| (asset.clone(), balance.clone()))
+            .collect()
+    }
+
+    /// 从缓存加载所有账户余额，替换当前余额，返回加载的余额数量
+    #[cfg(feature = "redis")]
+    pub async fn load(&self, cache: &CacheManager) -> anyhow::Result<usize> {
+        let balances = cache.get_balances().await?;
+        let mut state = self.state.lock().unwrap();
+        state.balances = balances.into_iter().map(|(account, asset, balance)| ((account, asset), balance)).collect();
+        state.dirty.clear();
+        Ok(state.balances.len())
+    }
+
+    /// 将上次写入之后修改过的余额写入缓存，返回写入的余额数量
+    #[cfg(feature = "redis")]
+    pub async fn flush(&self, cache: &CacheManager) -> anyhow::Result<usize> {
+        let balances: Vec<(String, String, Balance)> = {
+            let mut state = self.state.lock().unwrap();
+            let dirty = std::mem::take(&mut state.dirty);
+            dirty.into_iter()
+                .filter_map(|key| state.balances.get(&key).cloned().map(|b| (key.0, key.1, b)))
+                .collect()
+        };
+        if let Err(e) = cache.set_balances(&balances).await {
+            // 写入失败的余额下次重新写入
+            let mut state = self.state.lock().unwrap();
+            state.dirty.extend(balances.into_iter().map(|(account, asset, _)| (account, asset)));
+            return Err(e);
+        }
+        Ok(balances.len())
+    }
+}
+
+/// 主机定时将修改过的余额写入缓存，引擎关闭时由引擎最后写入一次
+#[cfg(feature = "redis")]
+pub fn launch_flush(engine: EngineHandle, interval: Duration, mut ctx: broadcast::Receiver<bool>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval.max(Duration::from_millis(10)));
+        loop {
+            tokio::select! {
+                _ = ctx.recv() => break,
+                _ = ticker.tick() => {
+                    // 备机的余额由主机写入
+                    if engine.replication().role() != ReplicationRole::Primary {
+                        continue;
+                    }
+                    if let (Some(ledger), Some(cache)) = (engine.ledger(), engine.cache_manager()) {
+                        if let Err(e) = ledger.flush(cache).await {
+                            warn!("LEDGER FLUSH FAILED: err={}", e);
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+
+#[async_trait]
+impl RiskCheck for Ledger {
+    fn name(&self) -> &str {
+        "ledger"
+    }
+
+    async fn check(&self, order: &Order, snapshot: &BookSnapshot) -> anyhow::Result<()> {
+        self.reserve(order, snapshot)?;
+        Ok(())
+    }
+
+    fn release(&self, order: &Order) {
+        Ledger::release(self, &order.symbol, order.id);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use bigdecimal::BigDecimal;
+
+    use loom_core::market::{MarketBook, MatchTrades};
+    use loom_core::order::{Order, OrderAction, OrderTimeInForce, OrderType, TradeSide};
+    use loom_core::snapshot::BookSnapshot;
+    use loom_core::symbol::{FeeSchedule, SymbolSpec};
+    use loom_core::testing::account_order;
+
+    use crate::audit::{self, AuditEvent, AuditLog, LEDGER_AUDIT};
+    use crate::ledger::{Balance, Ledger, FEE_ACCOUNT};
+    use crate::registry::SymbolRegistry;
+
+    const SYMBOL: &str = "LOOM-USDT-SPOT";
+
+    fn dec(s: &str) -> BigDecimal {
+        BigDecimal::from_str(s).unwrap()
+    }
+
+    fn balance(ledger: &Ledger, account: &str, asset: &str) -> (BigDecimal, BigDecimal) {
+        let balance = ledger.balances(account).remove(asset).unwrap_or_default();
+        (balance.available, balance.frozen)
+    }
+
+    fn new_ledger() -> Ledger {
+        let registry = SymbolRegistry::new();
+        let fees = FeeSchedule { maker: dec("0.001"), taker: dec("0.002") };
+        registry.insert(SymbolSpec::new(SYMBOL).with_fees(fees));
+        let ledger = Ledger::new(registry);
+        ledger.adjust("alice", "USDT", &dec("1000")).unwrap();
+        ledger.adjust("bob", "LOOM", &dec("10")).unwrap();
+        ledger
+    }
+
+    fn submit(ledger: &Ledger, book: &mut MarketBook, order: Order) -> MatchTrades {
+        ledger.reserve(&order, &book.snapshot()).unwrap();
+        let mut trades = MatchTrades::new();
+        book.try_match_into(order, &mut trades);
+        ledger.settle(&trades);
+        trades
+    }
+
+    #[test]
+    fn reserve_test() {
+        let ledger = new_ledger();
+        let snapshot = BookSnapshot::empty(SYMBOL);
+        let reject = ledger.reserve(&account_order(1, "alice", TradeSide::BUY, 11, "100"), &snapshot).unwrap_err();
+        assert_eq!(reject.check, "ledger");
+        ledger.reserve(&account_order(1, "alice", TradeSide::BUY, 4, "100"), &snapshot).unwrap();
+        assert_eq!(balance(&ledger, "alice", "USDT"), (dec("600"), dec("400")));
+        assert!(ledger.reserve(&account_order(1, "alice", TradeSide::BUY, 1, "100"), &snapshot).is_err());
+        ledger.release(SYMBOL, 1);
+        assert_eq!(balance(&ledger, "alice", "USDT"), (dec("1000"), dec("0")));
+        // 尚无参考价格的市场无法预留市价买单
+        let mut market = account_order(2, "alice", TradeSide::BUY, 1, "0");
+        market.ord_type = OrderType::MARKET;
+        assert!(ledger.reserve(&market, &snapshot).is_err());
+        assert!(ledger.adjust("alice", "USDT", &dec("-1001")).is_err());
+        ledger.reserve(&account_order(3, "carol", TradeSide::SELL, 1, "100"), &snapshot).unwrap_err();
+        let mut anonymous = account_order(4, "", TradeSide::SELL, 1, "100");
+        anonymous.account = None;
+        ledger.reserve(&anonymous, &snapshot).unwrap();
+    }
+
+    #[test]
+    fn settle_test() {
+        let ledger = new_ledger();
+        let mut book = MarketBook::new(SYMBOL);
+        submit(&ledger, &mut book, account_order(1, "bob", TradeSide::SELL, 5, "100"));
+        assert_eq!(balance(&ledger, "bob", "LOOM"), (dec("5"), dec("5")));
+        // 买单以更低的挂单价成交，多预留的资金退回
+        let trades = submit(&ledger, &mut book, account_order(2, "alice", TradeSide::BUY, 3, "110"));
+        assert_eq!(trades.len(), 1);
+        assert_eq!(balance(&ledger, "alice", "USDT"), (dec("700"), dec("0")));
+        assert_eq!(balance(&ledger, "alice", "LOOM"), (dec("2.994"), dec("0")));
+        assert_eq!(balance(&ledger, "bob", "USDT"), (dec("299.7"), dec("0")));
+        assert_eq!(balance(&ledger, "bob", "LOOM"), (dec("5"), dec("2")));
+        assert_eq!(balance(&ledger, FEE_ACCOUNT, "LOOM"), (dec("0.006"), dec("0")));
+        assert_eq!(balance(&ledger, FEE_ACCOUNT, "USDT"), (dec("0.3"), dec("0")));
+        // 减量和撤单释放剩余预留
+        let mut trades = MatchTrades::new();
+        let mut reduce = account_order(1, "bob", TradeSide::SELL, 1, "100");
+        reduce.action = OrderAction::REDUCE;
+        book.try_reduce_into(reduce, &mut trades);
+        ledger.settle(&trades);
+        assert_eq!(balance(&ledger, "bob", "LOOM"), (dec("6"), dec("1")));
+        let mut cancel = account_order(1, "bob", TradeSide::SELL, 1, "100");
+        cancel.action = OrderAction::CANCEL;
+        trades.clear();
+        book.try_cancel_into(cancel, &mut trades);
+        ledger.settle(&trades);
+        assert_eq!(balance(&ledger, "bob", "LOOM"), (dec("7"), dec("0")));
+        assert_eq!(ledger.balances("carol").len(), 0);
+        assert_eq!(ledger.balances("bob").get("USDT"), Some(&Balance { available: dec("299.7"), frozen: dec("0") }));
+    }
+
+    #[test]
+    fn stale_snapshot_test() {
+        let ledger = new_ledger();
+        let mut book = MarketBook::new(SYMBOL);
+        submit(&ledger, &mut book, account_order(1, "bob", TradeSide::SELL, 2, "100"));
+        let stale = book.snapshot();
+        // 快照发布后卖单撤销，新卖单价格更高
+        let mut cancel = account_order(1, "bob", TradeSide::SELL, 2, "100");
+        cancel.action = OrderAction::CANCEL;
+        let mut trades = MatchTrades::new();
+        book.try_cancel_into(cancel, &mut trades);
+        ledger.settle(&trades);
+        submit(&ledger, &mut book, account_order(2, "bob", TradeSide::SELL, 2, "150"));
+        let mut market = account_order(3, "alice", TradeSide::BUY, 2, "0");
+        market.ord_type = OrderType::MARKET;
+        market.tif = OrderTimeInForce::IOC;
+        ledger.reserve(&market, &stale).unwrap();
+        assert_eq!(balance(&ledger, "alice", "USDT"), (dec("800"), dec("200")));
+        // 按实时订单簿追加预留后成交，余额不会为负
+        assert!(ledger.reprice(&market, &book.sweep_px(&market).unwrap()));
+        assert_eq!(balance(&ledger, "alice", "USDT"), (dec("700"), dec("300")));
+        let mut trades = MatchTrades::new();
+        book.try_match_into(market, &mut trades);
+        ledger.settle(&trades);
+        assert_eq!(balance(&ledger, "alice", "USDT"), (dec("700"), dec("0")));
+        // 可用余额不足以追加预留
+        submit(&ledger, &mut book, account_order(4, "bob", TradeSide::SELL, 2, "400"));
+        let mut market = account_order(5, "alice", TradeSide::BUY, 2, "0");
+        market.ord_type = OrderType::MARKET;
+        ledger.reserve(&market, &stale).unwrap();
+        assert!(!ledger.reprice(&market, &book.sweep_px(&market).unwrap()));
+        assert_eq!(balance(&ledger, "alice", "USDT"), (dec("500"), dec("200")));
+        ledger.release(SYMBOL, 5);
+        assert_eq!(balance(&ledger, "alice", "USDT"), (dec("700"), dec("0")));
+    }
+
+    #[test]
+    fn reset_symbol_test() {
+        let ledger = new_ledger();
+        let snapshot = BookSnapshot::empty(SYMBOL);
+        ledger.reserve(&account_order(1, "bob", TradeSide::SELL, 5, "100"), &snapshot).unwrap();
+        ledger.reset_symbol(SYMBOL, &[account_order(2, "bob", TradeSide::SELL, 3, "100")]);
+        assert_eq!(balance(&ledger, "bob", "LOOM"), (dec("7"), dec("3")));
+        ledger.release(SYMBOL, 1);
+        assert_eq!(balance(&ledger, "bob", "LOOM"), (dec("7"), dec("3")));
+        ledger.release(SYMBOL, 2);
+        assert_eq!(balance(&ledger, "bob", "LOOM"), (dec("10"), dec("0")));
+    }
+
+    #[test]
+    fn adjust_audit_test() {
+        let dir = std::env::temp_dir().join(format!("loom-ledger-audit-test-{}", std::process::id()));
+        let ledger = new_ledger();
+        ledger.set_audit(AuditLog::open(&dir, LEDGER_AUDIT).unwrap());
+        ledger.adjust("alice", "USDT", &dec("-300")).unwrap();
+        // 余额不足的调整不写审计日志
+        assert!(ledger.adjust("alice", "USDT", &dec("-800")).is_err());
+        let path = AuditLog::path(&dir, LEDGER_AUDIT);
+        assert_eq!(audit::verify(&path).unwrap().records, 1);
+        let records = audit::read(&path).unwrap();
+        assert!(matches!(&records[0].event, AuditEvent::Adjust(a) if a.account == "alice" && a.available == dec("700")));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
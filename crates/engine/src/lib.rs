@@ -1,4 +1,38 @@
 pub mod trader;
 pub mod engine;
 pub mod consumer;
-pub mod cache;
\ No newline at end of file
+#[cfg(feature = "redis")]
+pub mod cache;
+pub mod http_client;
+pub mod ring;
+pub mod snapshot;
+pub mod shard;
+pub mod metrics;
+pub mod logging;
+pub mod dump;
+pub mod image;
+pub mod alert;
+pub mod archive;
+pub mod audit;
+pub mod builder;
+pub mod collar;
+#[cfg(feature = "fault-injection")]
+pub mod fault;
+#[cfg(feature = "redis")]
+pub mod follower;
+pub mod ledger;
+pub mod limits;
+pub mod mmp;
+#[cfg(feature = "redis")]
+pub mod reconcile;
+pub mod reference;
+pub mod registry;
+pub mod replay;
+pub mod replication;
+pub mod report;
+pub mod risk;
+pub mod sequencer;
+pub mod session;
+pub mod settlement;
+pub mod tenant;
+pub mod warmup;
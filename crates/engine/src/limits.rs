@@ -0,0 +1,335 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use loom_core::market::MatchTrade;
+use loom_core::order::Order;
+use loom_core::symbol::Symbol;
+
+/// 账户限制，未配置的项不检查
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct AccountLimits {
+    /// 每个账户在每个交易对上的最大挂单数量
+    pub max_open_orders: Option<usize>,
+    /// 每个账户每秒最多新下单数量
+    pub max_orders_per_sec: Option<u32>,
+}
+
+/// 账户限制拒绝原因
+#[allow(non_camel_case_types)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub enum RejectReason {
+    /// 挂单数量超限
+    TOO_MANY_OPEN_ORDERS,
+    /// 下单速率超限
+    ORDER_RATE_EXCEEDED,
+    /// 订单ID与账户的挂单重复
+    DUPLICATE_ORDER_ID,
+}
+
+/// 账户限制拒绝错误
+#[derive(Debug, Clone)]
+pub struct AccountLimitExceeded {
+    pub account: String,
+    pub symbol: String,
+    pub reason: RejectReason,
+}
+
+impl Display for AccountLimitExceeded {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "REJECTED: {:?}, account={}, symbol={}", self.reason, self.account, self.symbol)
+    }
+}
+
+impl std::error::Error for AccountLimitExceeded {}
+
+/// 交易对下单速率超限错误
+#[derive(Debug, Clone)]
+pub struct SymbolThrottled {
+    pub symbol: String,
+}
+
+impl Display for SymbolThrottled {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "REJECTED: THROTTLED, symbol={}", self.symbol)
+    }
+}
+
+impl std::error::Error for SymbolThrottled {}
+
+#[derive(Debug, Default)]
+struct AccountState {
+    /// 每个交易对的挂单数量
+    open: HashMap<(String, Symbol), usize>,
+    /// 挂单所属账户
+    owners: HashMap<(Symbol, u64), String>,
+    /// 每个账户当前秒及其下单数量
+    rates: HashMap<String, (u64, u32)>,
+}
+
+/// 账户限制器，由引擎在下单时占用额度，交易员在订单离开订单簿时释放
+#[derive(Debug)]
+pub struct AccountLimiter {
+    limits: AccountLimits,
+    state: Mutex<AccountState>,
+}
+
+impl AccountLimiter {
+    pub fn new(limits: AccountLimits) -> AccountLimiter {
+        AccountLimiter {
+            limits,
+            state: Mutex::new(AccountState::default()),
+        }
+    }
+
+    pub fn limits(&self) -> AccountLimits {
+        self.limits
+    }
+
+    /// 检查并占用下单额度，sec为当前秒，未设置账户的订单不受限制
+    pub fn acquire(&self, order: &Order, sec: u64) -> Result<(), AccountLimitExceeded> {
+        let account = match &order.account {
+            Some(account) => account,
+            None => return Ok(()),
+        };
+        let reject = |reason| AccountLimitExceeded {
+            account: account.clone(),
+            symbol: order.symbol.to_string(),
+            reason,
+        };
+        let mut state = self.state.lock().unwrap();
+        // 重复ID的订单会被订单簿拒绝，占用额度后释放时会误释放原挂单的额度
+        let owner_key = (order.symbol.clone(), order.id);
+        if state.owners.contains_key(&owner_key) {
+            return Err(reject(RejectReason::DUPLICATE_ORDER_ID));
+        }
+        let open_key = (account.clone(), order.symbol.clone());
+        if let Some(max) = self.limits.max_open_orders {
+            if state.open.get(&open_key).copied().unwrap_or(0) >= max {
+                return Err(reject(RejectReason::TOO_MANY_OPEN_ORDERS));
+            }
+        }
+        if let Some(max) = self.limits.max_orders_per_sec {
+            let rate = state.rates.entry(account.clone()).or_insert((sec, 0));
+            if rate.0 != sec {
+                *rate = (sec, 0);
+            }
+            if rate.1 >= max {
+                return Err(reject(RejectReason::ORDER_RATE_EXCEEDED));
+            }
+            rate.1 += 1;
+        }
+        *state.open.entry(open_key).or_insert(0) += 1;
+        state.owners.insert(owner_key, account.clone());
+        Ok(())
+    }
+
+    /// 记录已在订单簿中的订单，用于缓存恢复，重复记录同一订单不重复计数
+    pub fn track(&self, order: &Order) {
+        if let Some(account) = &order.account {
+            let mut state = self.state.lock().unwrap();
+            if state.owners.insert((order.symbol.clone(), order.id), account.clone()).is_none() {
+                *state.open.entry((account.clone(), order.symbol.clone())).or_insert(0) += 1;
+            }
+        }
+    }
+
+    /// 清除交易对的全部挂单额度，用于镜像恢复前重置
+    pub fn clear_symbol(&self, symbol: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.owners.retain(|(s, _), _| s != symbol);
+        state.open.retain(|(_, s), _| s != symbol);
+    }
+
+    /// 订单离开订单簿或下单失败时释放挂单额度
+    pub fn release(&self, symbol: &str, oid: u64) {
+        self.release_symbol(&Symbol::from(symbol), oid);
+    }
+
+    fn release_symbol(&self, symbol: &Symbol, oid: u64) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(account) = state.owners.remove(&(symbol.clone(), oid)) {
+            let key = (account, symbol.clone());
+            if let Some(open) = state.open.get_mut(&key) {
+                *open = open.saturating_sub(1);
+                if *open == 0 {
+                    state.open.remove(&key);
+                }
+            }
+        }
+    }
+
+    /// 根据撮合结果释放已完成订单的额度
+    pub fn settle(&self, trades: &[MatchTrade]) {
+        for trade in trades {
+            if trade.taker_state.del_flag() {
+                self.release_symbol(&trade.symbol, trade.taker_oid);
+            }
+            if trade.maker_state.del_flag() {
+                self.release_symbol(&trade.symbol, trade.maker_oid);
+            }
+        }
+    }
+
+    /// 账户在交易对上的挂单数量
+    pub fn open_orders(&self, account: &str, symbol: &str) -> usize {
+        let state = self.state.lock().unwrap();
+        state.open.get(&(account.to_string(), Symbol::from(symbol))).copied().unwrap_or(0)
+    }
+}
+
+/// 交易对下单速率限制，速率由交易对规格配置，规格修改后随之更新
+#[derive(Debug, Default)]
+pub struct SymbolThrottle {
+    /// 每秒最多新订单数量，0表示不限制
+    max: AtomicU32,
+    /// 当前秒及其下单数量
+    window: Mutex<(u64, u32)>,
+    /// 累计拒绝次数
+    throttled: AtomicU64,
+}
+
+impl SymbolThrottle {
+    pub fn new(max: Option<u32>) -> SymbolThrottle {
+        let throttle = SymbolThrottle::default();
+        throttle.set_max(max);
+        throttle
+    }
+
+    pub fn set_max(&self, max: Option<u32>) {
+        self.max.store(max.unwrap_or(0), Ordering::Relaxed);
+    }
+
+    pub fn max(&self) -> Option<u32> {
+        Some(self.max.load(Ordering::Relaxed)).filter(|max| *max > 0)
+    }
+
+    /// 检查并占用当前秒的下单额度，sec为当前秒，超限时累计拒绝次数并返回false
+    pub fn admit(&self, sec: u64) -> bool {
+        let max = match self.max() {
+            Some(max) => max,
+            None => return true,
+        };
+        let mut window = self.window.lock().unwrap();
+        if window.0 != sec {
+            *window = (sec, 0);
+        }
+        if window.1 >= max {
+            self.throttled.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        window.1 += 1;
+        true
+    }
+
+    /// 退回admit占用的额度，sec为admit时的秒，已进入下一秒时不再退回
+    pub fn refund(&self, sec: u64) {
+        if self.max().is_none() {
+            return;
+        }
+        let mut window = self.window.lock().unwrap();
+        if window.0 == sec {
+            window.1 = window.1.saturating_sub(1);
+        }
+    }
+
+    /// 累计拒绝次数
+    pub fn throttled(&self) -> u64 {
+        self.throttled.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use loom_core::market::MarketBook;
+    use loom_core::order::TradeSide;
+    use loom_core::testing::account_order;
+
+    use crate::limits::{AccountLimiter, AccountLimits, RejectReason, SymbolThrottle};
+
+    #[test]
+    fn open_orders_test() {
+        let limiter = AccountLimiter::new(AccountLimits { max_open_orders: Some(2), max_orders_per_sec: None });
+        let mut market = MarketBook::new("LOOM-USDT-SPOT");
+        for id in 1..=2 {
+            let order = account_order(id, "alice", TradeSide::BUY, 1, "100");
+            limiter.acquire(&order, 0).unwrap();
+            limiter.settle(&market.try_match(order));
+        }
+        let err = limiter.acquire(&account_order(3, "alice", TradeSide::BUY, 1, "100"), 0).unwrap_err();
+        assert_eq!(err.reason, RejectReason::TOO_MANY_OPEN_ORDERS);
+        // 其他账户的卖单吃掉一笔买单，双方都释放额度
+        let mut sell = account_order(4, "alice", TradeSide::SELL, 1, "100");
+        sell.account = Some(String::from("bob"));
+        limiter.acquire(&sell, 0).unwrap();
+        limiter.settle(&market.try_match(sell));
+        assert_eq!(limiter.open_orders("alice", "LOOM-USDT-SPOT"), 1);
+        assert_eq!(limiter.open_orders("bob", "LOOM-USDT-SPOT"), 0);
+        assert!(limiter.acquire(&account_order(5, "alice", TradeSide::BUY, 1, "100"), 0).is_ok());
+    }
+
+    #[test]
+    fn duplicate_id_test() {
+        let limiter = AccountLimiter::new(AccountLimits { max_open_orders: None, max_orders_per_sec: Some(2) });
+        limiter.acquire(&account_order(1, "alice", TradeSide::BUY, 1, "100"), 0).unwrap();
+        // 重复ID被拒绝，不计入挂单和速率
+        let err = limiter.acquire(&account_order(1, "alice", TradeSide::SELL, 1, "100"), 0).unwrap_err();
+        assert_eq!(err.reason, RejectReason::DUPLICATE_ORDER_ID);
+        limiter.track(&account_order(1, "alice", TradeSide::BUY, 1, "100"));
+        assert_eq!(limiter.open_orders("alice", "LOOM-USDT-SPOT"), 1);
+        assert!(limiter.acquire(&account_order(2, "alice", TradeSide::BUY, 1, "100"), 0).is_ok());
+        limiter.release("LOOM-USDT-SPOT", 1);
+        assert_eq!(limiter.open_orders("alice", "LOOM-USDT-SPOT"), 1);
+        // 原挂单离开后可以复用ID
+        assert!(limiter.acquire(&account_order(1, "alice", TradeSide::BUY, 1, "100"), 1).is_ok());
+        assert_eq!(limiter.open_orders("alice", "LOOM-USDT-SPOT"), 2);
+    }
+
+    #[test]
+    fn clear_symbol_test() {
+        let limiter = AccountLimiter::new(AccountLimits::default());
+        limiter.track(&account_order(1, "alice", TradeSide::BUY, 1, "100"));
+        let mut other = account_order(2, "alice", TradeSide::BUY, 1, "100");
+        other.symbol = "LOOM-BTC-SPOT".into();
+        limiter.track(&other);
+        limiter.clear_symbol("LOOM-USDT-SPOT");
+        assert_eq!(limiter.open_orders("alice", "LOOM-USDT-SPOT"), 0);
+        assert_eq!(limiter.open_orders("alice", "LOOM-BTC-SPOT"), 1);
+    }
+
+    #[test]
+    fn order_rate_test() {
+        let limiter = AccountLimiter::new(AccountLimits { max_open_orders: None, max_orders_per_sec: Some(2) });
+        assert!(limiter.acquire(&account_order(1, "alice", TradeSide::BUY, 1, "100"), 10).is_ok());
+        assert!(limiter.acquire(&account_order(2, "alice", TradeSide::BUY, 1, "100"), 10).is_ok());
+        let err = limiter.acquire(&account_order(3, "alice", TradeSide::BUY, 1, "100"), 10).unwrap_err();
+        assert_eq!(err.reason, RejectReason::ORDER_RATE_EXCEEDED);
+        assert!(limiter.acquire(&account_order(3, "alice", TradeSide::BUY, 1, "100"), 11).is_ok());
+        let mut anonymous = account_order(4, "alice", TradeSide::BUY, 1, "100");
+        anonymous.account = None;
+        assert!(limiter.acquire(&anonymous, 11).is_ok());
+    }
+
+    #[test]
+    fn symbol_throttle_test() {
+        let throttle = SymbolThrottle::new(Some(2));
+        assert!(throttle.admit(10));
+        assert!(throttle.admit(10));
+        assert!(!throttle.admit(10));
+        assert_eq!(throttle.throttled(), 1);
+        assert!(throttle.admit(11));
+        // 退回当前秒的额度后可以再次下单，退回已过去的秒不影响当前秒
+        throttle.refund(11);
+        assert!(throttle.admit(11));
+        throttle.refund(10);
+        assert!(throttle.admit(11));
+        assert!(!throttle.admit(11));
+        // 取消限制后不再拒绝
+        throttle.set_max(None);
+        assert!((0..10).all(|_| throttle.admit(11)));
+        assert_eq!(throttle.throttled(), 2);
+    }
+}
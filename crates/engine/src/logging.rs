@@ -0,0 +1,55 @@
+use std::future::Future;
+
+use loom_core::order::Order;
+
+tokio::task_local! {
+    static LOG_CONTEXT: LogContext;
+}
+
+/// 日志关联上下文，在请求和撮合处理期间附加到每条日志上
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct LogContext {
+    /// 请求ID
+    pub request_id: Option<String>,
+    /// 交易对
+    pub symbol: Option<String>,
+    /// 订单ID
+    pub oid: Option<u64>,
+}
+
+impl LogContext {
+    pub fn with_request_id(mut self, request_id: &str) -> LogContext {
+        self.request_id = Some(String::from(request_id));
+        self
+    }
+
+    pub fn with_order(mut self, order: &Order) -> LogContext {
+        self.symbol = Some(order.symbol.to_string());
+        self.oid = Some(order.id);
+        self
+    }
+}
+
+/// 当前任务的日志上下文，不在上下文中时返回None
+pub fn current() -> Option<LogContext> {
+    LOG_CONTEXT.try_with(|ctx| ctx.clone()).ok()
+}
+
+/// 在日志上下文中执行fut
+pub async fn scope<F: Future>(ctx: LogContext, fut: F) -> F::Output {
+    LOG_CONTEXT.scope(ctx, fut).await
+}
+
+#[cfg(test)]
+mod test {
+    use crate::logging::{current, scope, LogContext};
+
+    #[tokio::test]
+    async fn scope_test() {
+        assert_eq!(current(), None);
+        let ctx = LogContext::default().with_request_id("req-1");
+        let inner = scope(ctx, async { current() }).await.unwrap();
+        assert_eq!(inner.request_id.as_deref(), Some("req-1"));
+        assert_eq!(current(), None);
+    }
+}
@@ -0,0 +1,213 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// 线性区间的桶数量，小于该值的纳秒数各占一个桶
+const LINEAR_BUCKETS: usize = 16;
+/// 每个2的幂区间内再细分的桶数量位数，误差约为1/8
+const SUB_BUCKET_BITS: u32 = 3;
+const SUB_BUCKETS: usize = 1 << SUB_BUCKET_BITS;
+const BUCKETS: usize = LINEAR_BUCKETS + (64 - 4) * SUB_BUCKETS;
+
+/// 延迟直方图，按对数分桶，写入和读取均无锁，可在撮合线程与查询方之间共享
+#[derive(Debug)]
+pub struct LatencyHistogram {
+    buckets: Box<[AtomicU64]>,
+    count: AtomicU64,
+    sum_ns: AtomicU64,
+    max_ns: AtomicU64,
+}
+
+/// 延迟统计摘要，单位为微秒
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LatencySummary {
+    pub count: u64,
+    pub mean_us: f64,
+    pub p50_us: f64,
+    pub p99_us: f64,
+    pub p999_us: f64,
+    pub max_us: f64,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LatencyHistogram {
+    pub fn new() -> LatencyHistogram {
+        LatencyHistogram {
+            buckets: (0..BUCKETS).map(|_| AtomicU64::new(0)).collect(),
+            count: AtomicU64::new(0),
+            sum_ns: AtomicU64::new(0),
+            max_ns: AtomicU64::new(0),
+        }
+    }
+
+    pub fn record(&self, latency: Duration) {
+        let ns = latency.as_nanos().min(u64::MAX as u128) as u64;
+        self.buckets[bucket_of(ns)].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_ns.fetch_add(ns, Ordering::Relaxed);
+        self.max_ns.fetch_max(ns, Ordering::Relaxed);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    /// 延迟分位数，返回所在桶的上界，q取值范围0~1
+    pub fn quantile(&self, q: f64) -> Duration {
+        let count = self.count();
+        if count == 0 {
+            return Duration::ZERO;
+        }
+        let rank = ((count as f64 * q).ceil() as u64).clamp(1, count);
+        let mut seen = 0;
+        for (idx, bucket) in self.buckets.iter().enumerate() {
+            seen += bucket.load(Ordering::Relaxed);
+            if seen >= rank {
+                let max = self.max_ns.load(Ordering::Relaxed);
+                return Duration::from_nanos(bucket_upper(idx).min(max));
+            }
+        }
+        Duration::from_nanos(self.max_ns.load(Ordering::Relaxed))
+    }
+
+    pub fn summary(&self) -> LatencySummary {
+        let count = self.count();
+        let micros = |d: Duration| d.as_nanos() as f64 / 1_000.0;
+        LatencySummary {
+            count,
+            mean_us: if count == 0 { 0.0 } else { self.sum_ns.load(Ordering::Relaxed) as f64 / count as f64 / 1_000.0 },
+            p50_us: micros(self.quantile(0.5)),
+            p99_us: micros(self.quantile(0.99)),
+            p999_us: micros(self.quantile(0.999)),
+            max_us: micros(Duration::from_nanos(self.max_ns.load(Ordering::Relaxed))),
+        }
+    }
+
+    pub fn sum(&self) -> Duration {
+        Duration::from_nanos(self.sum_ns.load(Ordering::Relaxed))
+    }
+}
+
+/// 撮合请求在交易员队列中的等待时间，超过上限时新订单在入队前被拒绝
+#[derive(Debug, Default)]
+pub struct QueueWait {
+    histogram: LatencyHistogram,
+    /// 最近出队请求的等待时间，纳秒
+    last_ns: AtomicU64,
+    /// 等待时间上限，未配置时不拒绝
+    bound: Option<Duration>,
+}
+
+impl QueueWait {
+    pub fn new(bound: Option<Duration>) -> QueueWait {
+        QueueWait { bound, ..QueueWait::default() }
+    }
+
+    pub fn record(&self, wait: Duration) {
+        self.histogram.record(wait);
+        self.last_ns.store(wait.as_nanos().min(u64::MAX as u128) as u64, Ordering::Relaxed);
+    }
+
+    pub fn histogram(&self) -> &LatencyHistogram {
+        &self.histogram
+    }
+
+    /// 最近出队请求的等待时间
+    pub fn last(&self) -> Duration {
+        Duration::from_nanos(self.last_ns.load(Ordering::Relaxed))
+    }
+
+    pub fn bound(&self) -> Option<Duration> {
+        self.bound
+    }
+
+    /// 最近出队请求的等待时间是否超过上限，队列排空后随新的出队请求恢复
+    pub fn saturated(&self) -> bool {
+        self.bound.is_some_and(|bound| self.last() > bound)
+    }
+}
+
+/// 进程常驻内存，字节，读取/proc/self/statm，其他平台返回None
+pub fn resident_memory_bytes() -> Option<u64> {
+    let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    Some(pages * page_size.max(0) as u64)
+}
+
+fn bucket_of(ns: u64) -> usize {
+    if ns < LINEAR_BUCKETS as u64 {
+        return ns as usize;
+    }
+    let msb = 63 - ns.leading_zeros();
+    let sub = ((ns >> (msb - SUB_BUCKET_BITS)) as usize) & (SUB_BUCKETS - 1);
+    LINEAR_BUCKETS + (msb as usize - 4) * SUB_BUCKETS + sub
+}
+
+fn bucket_upper(idx: usize) -> u64 {
+    if idx < LINEAR_BUCKETS {
+        return idx as u64;
+    }
+    let msb = ((idx - LINEAR_BUCKETS) / SUB_BUCKETS) as u32 + 4;
+    let sub = ((idx - LINEAR_BUCKETS) % SUB_BUCKETS) as u64;
+    let shift = msb - SUB_BUCKET_BITS;
+    let upper = ((SUB_BUCKETS as u128 + sub as u128 + 1) << shift) - 1;
+    upper.min(u64::MAX as u128) as u64
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use crate::metrics::{bucket_of, bucket_upper, resident_memory_bytes, LatencyHistogram, QueueWait};
+
+    #[test]
+    fn bucket_bound_test() {
+        for ns in [0, 15, 16, 17, 100, 1_000, 123_456, 10_000_000_000, u64::MAX] {
+            let idx = bucket_of(ns);
+            assert!(bucket_upper(idx) >= ns);
+            // 桶上界误差不超过1/8
+            assert!(bucket_upper(idx) - ns <= ns / 8 + 1);
+        }
+    }
+
+    #[test]
+    fn quantile_test() {
+        let histogram = LatencyHistogram::new();
+        for us in 1..=1000 {
+            histogram.record(Duration::from_micros(us));
+        }
+        let p50 = histogram.quantile(0.5).as_micros() as f64;
+        let p99 = histogram.quantile(0.99).as_micros() as f64;
+        assert!((500.0..=570.0).contains(&p50), "p50={}", p50);
+        assert!((990.0..=1000.0).contains(&p99), "p99={}", p99);
+        let summary = histogram.summary();
+        assert_eq!(summary.count, 1000);
+        assert_eq!(summary.max_us, 1000.0);
+    }
+
+    #[test]
+    fn queue_wait_test() {
+        let wait = QueueWait::new(Some(Duration::from_millis(10)));
+        wait.record(Duration::from_millis(20));
+        assert!(wait.saturated());
+        wait.record(Duration::from_millis(1));
+        assert!(!wait.saturated());
+        assert_eq!(wait.histogram().count(), 2);
+        let unbounded = QueueWait::new(None);
+        unbounded.record(Duration::from_secs(1));
+        assert!(!unbounded.saturated());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn resident_memory_test() {
+        assert!(resident_memory_bytes().is_some_and(|bytes| bytes > 0));
+    }
+}
@@ -0,0 +1,223 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{Display, Formatter};
+use std::sync::Mutex;
+
+use bigdecimal::{BigDecimal, Zero};
+use serde::{Deserialize, Serialize};
+
+use loom_core::market::MatchTrade;
+use loom_core::order::Order;
+use loom_core::symbol::Symbol;
+
+/// 默认统计窗口，毫秒
+pub const DEFAULT_MMP_WINDOW_MS: u64 = 1000;
+
+/// 做市商保护配置，账户的挂单在统计窗口内被成交的次数、数量或金额超过任一阈值时触发
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct MmpConfig {
+    /// 统计窗口，毫秒，默认1000
+    pub window_ms: Option<u64>,
+    /// 窗口内最多成交次数
+    pub max_fills: Option<u32>,
+    /// 窗口内最多成交数量
+    pub max_qty: Option<u64>,
+    /// 窗口内最多成交金额
+    pub max_notional: Option<BigDecimal>,
+}
+
+/// 触发原因
+#[allow(non_camel_case_types)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub enum MmpReason {
+    FILLS_EXCEEDED,
+    QTY_EXCEEDED,
+    NOTIONAL_EXCEEDED,
+}
+
+/// 已触发保护的账户
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct MmpTrip {
+    pub account: String,
+    pub symbol: String,
+    pub reason: MmpReason,
+    /// 触发时的成交时间
+    pub ts: u128,
+}
+
+/// 账户已触发做市商保护，重新启用前拒绝新订单
+#[derive(Debug, Clone)]
+pub struct MmpTripped {
+    pub account: String,
+    pub symbol: String,
+}
+
+impl Display for MmpTripped {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "REJECTED: MMP_TRIPPED, account={}, symbol={}", self.account, self.symbol)
+    }
+}
+
+impl std::error::Error for MmpTripped {}
+
+/// 账户在交易对上窗口内的挂单成交
+#[derive(Debug, Default)]
+struct Window {
+    /// 成交时间、数量和金额
+    fills: VecDeque<(u128, u64, BigDecimal)>,
+    qty: u64,
+    notional: BigDecimal,
+}
+
+#[derive(Debug, Default)]
+struct MmpState {
+    windows: HashMap<(String, Symbol), Window>,
+    tripped: HashMap<(String, Symbol), MmpTrip>,
+}
+
+/// 做市商保护，按账户和交易对统计挂单被成交的情况，触发后由交易员撤销该账户在交易对上的挂单，引擎拒绝其新订单
+#[derive(Debug)]
+pub struct MarketMakerProtection {
+    config: MmpConfig,
+    state: Mutex<MmpState>,
+}
+
+impl MarketMakerProtection {
+    pub fn new(config: MmpConfig) -> MarketMakerProtection {
+        MarketMakerProtection { config, state: Mutex::new(MmpState::default()) }
+    }
+
+    pub fn config(&self) -> &MmpConfig {
+        &self.config
+    }
+
+    /// 检查下单账户是否已触发保护，未设置账户的订单不受限制
+    pub fn check(&self, order: &Order) -> Result<(), MmpTripped> {
+        let Some(account) = &order.account else {
+            return Ok(());
+        };
+        let state = self.state.lock().unwrap();
+        if state.tripped.contains_key(&(account.clone(), order.symbol.clone())) {
+            return Err(MmpTripped { account: account.clone(), symbol: order.symbol.to_string() });
+        }
+        Ok(())
+    }
+
+    /// 统计撮合结果中挂单方的成交，返回本次新触发保护的账户
+    pub fn record(&self, trades: &[MatchTrade]) -> Vec<String> {
+        let window = self.config.window_ms.unwrap_or(DEFAULT_MMP_WINDOW_MS) as u128;
+        let mut state = self.state.lock().unwrap();
+        let mut tripped = Vec::new();
+        for trade in trades.iter().filter(|t| t.qty > 0 && t.reject.is_none() && t.maker_oid != 0) {
+            let Some(account) = &trade.maker_account else {
+                continue;
+            };
+            let key = (account.clone(), trade.symbol.clone());
+            if state.tripped.contains_key(&key) {
+                continue;
+            }
+            let fills = state.windows.entry(key.clone()).or_default();
+            let notional = &trade.px * BigDecimal::from(trade.qty);
+            fills.qty += trade.qty;
+            fills.notional += &notional;
+            fills.fills.push_back((trade.ts, trade.qty, notional));
+            while let Some((ts, qty, notional)) = fills.fills.front() {
+                if ts + window > trade.ts {
+                    break;
+                }
+                fills.qty -= qty;
+                fills.notional -= notional;
+                fills.fills.pop_front();
+            }
+            let reason = if self.config.max_fills.is_some_and(|max| fills.fills.len() > max as usize) {
+                Some(MmpReason::FILLS_EXCEEDED)
+            } else if self.config.max_qty.is_some_and(|max| fills.qty > max) {
+                Some(MmpReason::QTY_EXCEEDED)
+            } else if self.config.max_notional.as_ref().is_some_and(|max| &fills.notional > max) {
+                Some(MmpReason::NOTIONAL_EXCEEDED)
+            } else {
+                None
+            };
+            if let Some(reason) = reason {
+                state.windows.remove(&key);
+                state.tripped.insert(key, MmpTrip { account: account.clone(), symbol: trade.symbol.to_string(), reason, ts: trade.ts });
+                tripped.push(account.clone());
+            }
+        }
+        tripped
+    }
+
+    /// 重新启用账户在交易对上的下单，返回之前是否已触发
+    pub fn rearm(&self, account: &str, symbol: &str) -> bool {
+        let key = (account.to_string(), Symbol::from(symbol));
+        let mut state = self.state.lock().unwrap();
+        state.windows.remove(&key);
+        state.tripped.remove(&key).is_some()
+    }
+
+    /// 所有已触发保护的账户
+    pub fn tripped(&self) -> Vec<MmpTrip> {
+        let state = self.state.lock().unwrap();
+        let mut tripped: Vec<MmpTrip> = state.tripped.values().cloned().collect();
+        tripped.sort_by(|a, b| (&a.account, &a.symbol).cmp(&(&b.account, &b.symbol)));
+        tripped
+    }
+
+    /// 账户在交易对上窗口内的成交金额，用于监控
+    pub fn notional(&self, account: &str, symbol: &str) -> BigDecimal {
+        let state = self.state.lock().unwrap();
+        state.windows.get(&(account.to_string(), Symbol::from(symbol)))
+            .map(|w| w.notional.clone())
+            .unwrap_or_else(BigDecimal::zero)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bigdecimal::BigDecimal;
+
+    use loom_core::market::MarketBook;
+    use loom_core::order::TradeSide;
+    use loom_core::testing::account_order;
+
+    use crate::mmp::{MarketMakerProtection, MmpConfig, MmpReason};
+
+    #[test]
+    fn trip_test() {
+        let mmp = MarketMakerProtection::new(MmpConfig { max_fills: Some(2), max_notional: Some(BigDecimal::from(1000)), ..Default::default() });
+        let mut market = MarketBook::new("LOOM-USDT-SPOT");
+        market.try_match(account_order(1, "maker", TradeSide::SELL, 20, "100"));
+        // 只统计挂单方的成交
+        assert!(mmp.record(&market.try_match(account_order(2, "taker", TradeSide::BUY, 2, "100"))).is_empty());
+        assert!(mmp.record(&market.try_match(account_order(3, "taker", TradeSide::BUY, 1, "100"))).is_empty());
+        assert_eq!(mmp.notional("maker", "LOOM-USDT-SPOT"), BigDecimal::from(300));
+        assert_eq!(mmp.notional("taker", "LOOM-USDT-SPOT"), BigDecimal::from(0));
+        assert_eq!(mmp.record(&market.try_match(account_order(4, "taker", TradeSide::BUY, 1, "100"))), vec![String::from("maker")]);
+        assert_eq!(mmp.tripped()[0].reason, MmpReason::FILLS_EXCEEDED);
+        assert!(mmp.check(&account_order(6, "maker", TradeSide::SELL, 1, "100")).is_err());
+        assert!(mmp.check(&account_order(6, "taker", TradeSide::SELL, 1, "100")).is_ok());
+        // 触发后不再统计，重新启用后清空窗口
+        assert!(mmp.record(&market.try_match(account_order(7, "taker", TradeSide::BUY, 1, "100"))).is_empty());
+        assert!(mmp.rearm("maker", "LOOM-USDT-SPOT"));
+        assert!(!mmp.rearm("maker", "LOOM-USDT-SPOT"));
+        assert!(mmp.check(&account_order(6, "maker", TradeSide::SELL, 1, "100")).is_ok());
+        // 金额超限
+        assert_eq!(mmp.record(&market.try_match(account_order(8, "taker", TradeSide::BUY, 11, "100"))), vec![String::from("maker")]);
+        assert_eq!(mmp.tripped()[0].reason, MmpReason::NOTIONAL_EXCEEDED);
+    }
+
+    #[test]
+    fn window_test() {
+        let mmp = MarketMakerProtection::new(MmpConfig { window_ms: Some(100), max_qty: Some(2), ..Default::default() });
+        let mut market = MarketBook::new("LOOM-USDT-SPOT");
+        market.try_match(account_order(1, "maker", TradeSide::SELL, 10, "100"));
+        let mut trades = market.try_match(account_order(2, "taker", TradeSide::BUY, 2, "100")).to_vec();
+        trades[0].ts = 1000;
+        assert!(mmp.record(&trades).is_empty());
+        // 窗口外的成交已移出
+        trades[0].ts = 1100;
+        assert!(mmp.record(&trades).is_empty());
+        trades[0].ts = 1150;
+        assert_eq!(mmp.record(&trades).len(), 1);
+        assert_eq!(mmp.tripped()[0].reason, MmpReason::QTY_EXCEEDED);
+    }
+}
@@ -0,0 +1,388 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::bail;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+use loom_core::order::{Order, OrderAction};
+use loom_core::utils;
+
+use crate::alert::{Alert, AlertKind, AlertSink};
+use crate::engine::EngineHandle;
+
+/// 默认对账间隔
+pub const DEFAULT_RECONCILE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 默认复查等待时间
+pub const DEFAULT_RECONCILE_SETTLE: Duration = Duration::from_millis(500);
+
+/// 修复方向
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub enum RepairDirection {
+    /// 只报告不修复
+    #[default]
+    None,
+    /// 以订单簿为准修改缓存
+    BookToCache,
+    /// 以缓存为准修改订单簿，数量不一致的挂单无法修复
+    CacheToBook,
+}
+
+/// 缓存对账配置
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ReconcileConfig {
+    /// 定时对账间隔，秒，默认60
+    pub interval_secs: Option<u64>,
+    /// 发现不一致后复查前的等待时间，毫秒，两次都不一致才报告，默认500
+    pub settle_ms: Option<u64>,
+    /// 修复方向，默认只报告
+    pub repair: Option<RepairDirection>,
+}
+
+/// 不一致类型
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd, Serialize, Deserialize)]
+pub enum DriftKind {
+    /// 挂单在缓存中没有订单哈希
+    MissingHash,
+    /// 挂单有订单哈希但不在缓存的订单ID集合中，恢复时会丢失
+    MissingId,
+    /// 缓存的订单ID不是订单簿中的挂单
+    OrphanId,
+    /// 挂单的剩余数量与缓存不一致
+    QtyMismatch,
+}
+
+impl DriftKind {
+    pub const ALL: [DriftKind; 4] = [DriftKind::MissingHash, DriftKind::MissingId, DriftKind::OrphanId, DriftKind::QtyMismatch];
+}
+
+/// 一笔不一致的订单
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Drift {
+    pub kind: DriftKind,
+    pub oid: u64,
+    /// 订单簿中的剩余数量，订单不在订单簿中时为空
+    pub book_remain: Option<u64>,
+    /// 缓存中的剩余数量，订单哈希不存在时为空
+    pub cache_remain: Option<u64>,
+}
+
+/// 一个交易对的对账结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReconcileReport {
+    pub symbol: String,
+    /// 对账时订单簿已处理的最大请求序列号
+    pub seq: u64,
+    /// 订单簿挂单数量
+    pub book_orders: usize,
+    /// 缓存订单ID数量
+    pub cache_ids: usize,
+    /// 复查后仍不一致的订单
+    pub drifts: Vec<Drift>,
+    pub repair: RepairDirection,
+    /// 修复成功的数量
+    pub repaired: usize,
+    pub ts: u128,
+}
+
+impl ReconcileReport {
+    pub fn count(&self, kind: DriftKind) -> usize {
+        self.drifts.iter().filter(|d| d.kind == kind).count()
+    }
+}
+
+fn cache_remain(order: &Order) -> u64 {
+    order.qty.saturating_sub(order.acc_fill_qty)
+}
+
+/// 比较订单簿挂单与缓存，ids为缓存的订单ID集合，cached为读取到的订单哈希
+///
+/// seq之后的缓存订单还在撮合队列中，不作为不一致
+pub fn diff(book: &[Order], ids: &[u64], cached: &[Order], seq: u64) -> Vec<Drift> {
+    let ids: HashSet<u64> = ids.iter().copied().collect();
+    let cached: HashMap<u64, &Order> = cached.iter().map(|o| (o.id, o)).collect();
+    let resting: HashSet<u64> = book.iter().map(|o| o.id).collect();
+    let mut drifts = Vec::new();
+    for order in book {
+        let cache = cached.get(&order.id);
+        let kind = match cache {
+            None => DriftKind::MissingHash,
+            Some(_) if !ids.contains(&order.id) => DriftKind::MissingId,
+            Some(c) if cache_remain(c) != order.remain() => DriftKind::QtyMismatch,
+            Some(_) => continue,
+        };
+        drifts.push(Drift { kind, oid: order.id, book_remain: Some(order.remain()), cache_remain: cache.map(|c| cache_remain(c)) });
+    }
+    for id in ids.iter().filter(|id| !resting.contains(id)) {
+        let cache = cached.get(id);
+        if cache.is_some_and(|c| c.seq > seq) {
+            continue;
+        }
+        drifts.push(Drift { kind: DriftKind::OrphanId, oid: *id, book_remain: None, cache_remain: cache.map(|c| cache_remain(c)) });
+    }
+    drifts.sort_by_key(|d| (d.oid, d.kind));
+    drifts
+}
+
+/// 只保留两次检查都出现的不一致，撮合结果写入缓存前的短暂差异不会出现在两次检查中
+pub fn confirm(first: &[Drift], second: Vec<Drift>) -> Vec<Drift> {
+    let first: HashSet<(u64, DriftKind)> = first.iter().map(|d| (d.oid, d.kind)).collect();
+    second.into_iter().filter(|d| first.contains(&(d.oid, d.kind))).collect()
+}
+
+/// 一次检查读取的订单簿和缓存
+struct Scan {
+    seq: u64,
+    book: HashMap<u64, Order>,
+    ids: usize,
+    cached: HashMap<u64, Order>,
+    drifts: Vec<Drift>,
+}
+
+/// 缓存与订单簿对账，比较挂单与缓存的订单哈希和订单ID集合，复查后仍不一致时告警，按配置的方向修复
+pub struct Reconciler {
+    repair: RepairDirection,
+    settle: Duration,
+    sinks: Vec<AlertSink>,
+    /// 各交易对最近一次对账结果
+    reports: Mutex<BTreeMap<String, ReconcileReport>>,
+    /// 各交易对累计修复数量
+    repaired: Mutex<BTreeMap<String, u64>>,
+    /// 处于告警状态的交易对
+    active: Mutex<HashSet<String>>,
+}
+
+impl std::fmt::Debug for Reconciler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Reconciler")
+            .field("repair", &self.repair)
+            .field("settle", &self.settle)
+            .finish()
+    }
+}
+
+impl Reconciler {
+    pub fn new(config: &ReconcileConfig) -> Reconciler {
+        Reconciler {
+            repair: config.repair.unwrap_or_default(),
+            settle: config.settle_ms.map(Duration::from_millis).unwrap_or(DEFAULT_RECONCILE_SETTLE),
+            sinks: Vec::new(),
+            reports: Mutex::new(BTreeMap::new()),
+            repaired: Mutex::new(BTreeMap::new()),
+            active: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// 出现不一致时的告警输出
+    pub fn with_sinks(mut self, sinks: Vec<AlertSink>) -> Reconciler {
+        self.sinks = sinks;
+        self
+    }
+
+    /// 配置的修复方向
+    pub fn repair(&self) -> RepairDirection {
+        self.repair
+    }
+
+    /// 各交易对最近一次对账结果
+    pub fn reports(&self) -> Vec<ReconcileReport> {
+        self.reports.lock().unwrap().values().cloned().collect()
+    }
+
+    /// 各交易对累计修复数量
+    pub fn repaired(&self) -> Vec<(String, u64)> {
+        self.repaired.lock().unwrap().iter().map(|(s, n)| (s.clone(), *n)).collect()
+    }
+
+    /// 对账一个交易对，repair为空时使用配置的修复方向，备机不修复
+    pub async fn reconcile(&self, engine: &EngineHandle, symbol: &str, repair: Option<RepairDirection>) -> anyhow::Result<ReconcileReport> {
+        let repair = repair.unwrap_or(self.repair);
+        if repair != RepairDirection::None && !engine.replication().accepts_orders() {
+            bail!("repair not allowed on standby, symbol={}", symbol);
+        }
+        let mut scan = Self::scan(engine, symbol).await?;
+        if !scan.drifts.is_empty() {
+            tokio::time::sleep(self.settle).await;
+            let first = std::mem::take(&mut scan.drifts);
+            scan = Self::scan(engine, symbol).await?;
+            scan.drifts = confirm(&first, scan.drifts);
+        }
+        let repaired = match repair {
+            RepairDirection::None => 0,
+            _ => Self::fix(engine, symbol, repair, &scan).await,
+        };
+        let report = ReconcileReport {
+            symbol: symbol.into(),
+            seq: scan.seq,
+            book_orders: scan.book.len(),
+            cache_ids: scan.ids,
+            drifts: scan.drifts,
+            repair,
+            repaired,
+            ts: utils::now_ts(),
+        };
+        if !report.drifts.is_empty() {
+            warn!("CACHE DRIFT: symbol={}, drifts={}, repair={:?}, repaired={}", symbol, report.drifts.len(), repair, repaired);
+        }
+        *self.repaired.lock().unwrap().entry(symbol.to_string()).or_insert(0) += repaired as u64;
+        self.reports.lock().unwrap().insert(symbol.to_string(), report.clone());
+        self.notify(&report).await;
+        Ok(report)
+    }
+
+    async fn scan(engine: &EngineHandle, symbol: &str) -> anyhow::Result<Scan> {
+        let cache = engine.require_cache()?;
+        let image = engine.book_image(symbol).await?;
+        let mut ids = Vec::new();
+        cache.get_ids(symbol, |id| {
+            ids.push(id);
+            Ok(())
+        }).await?;
+        let book: Vec<Order> = image.bids.into_iter().chain(image.asks).collect();
+        // 挂单的订单哈希可能不在订单ID集合中，一并读取
+        let mut keys: Vec<u64> = ids.iter().copied().chain(book.iter().map(|o| o.id)).collect();
+        keys.sort_unstable();
+        keys.dedup();
+        let cached = cache.get_orders_by_ids(symbol, &keys).await?;
+        let drifts = diff(&book, &ids, &cached, image.seq);
+        Ok(Scan {
+            seq: image.seq,
+            book: book.into_iter().map(|o| (o.id, o)).collect(),
+            ids: ids.len(),
+            cached: cached.into_iter().map(|o| (o.id, o)).collect(),
+            drifts,
+        })
+    }
+
+    /// 按修复方向修复不一致，返回修复成功的数量
+    async fn fix(engine: &EngineHandle, symbol: &str, repair: RepairDirection, scan: &Scan) -> usize {
+        let Some(cache) = engine.cache_manager() else {
+            return 0;
+        };
+        let mut repaired = 0;
+        for drift in &scan.drifts {
+            let book = scan.book.get(&drift.oid);
+            let cached = scan.cached.get(&drift.oid);
+            let result = match (repair, drift.kind, book, cached) {
+                (RepairDirection::BookToCache, DriftKind::OrphanId, _, _) => cache.del_id(symbol, drift.oid).await.map(|_| true),
+                (RepairDirection::BookToCache, _, Some(order), _) => cache.put_order(order).await.map(|_| true),
+                (RepairDirection::CacheToBook, DriftKind::OrphanId, _, Some(order)) => engine.reinstate(order.clone()).await.map(|_| true),
+                // 订单哈希不存在，只能删除订单ID
+                (RepairDirection::CacheToBook, DriftKind::OrphanId, _, None) => cache.del_id(symbol, drift.oid).await.map(|_| true),
+                (RepairDirection::CacheToBook, DriftKind::MissingHash | DriftKind::MissingId, Some(order), _) => {
+                    let mut cancel = order.clone();
+                    cancel.action = OrderAction::CANCEL;
+                    engine.feed(cancel).await.map(|_| true)
+                }
+                // 修改挂单数量会产生减量结果并再次修改缓存
+                _ => Ok(false),
+            };
+            match result {
+                Ok(true) => repaired += 1,
+                Ok(false) => {}
+                Err(e) => warn!("CACHE REPAIR FAILED: symbol={}, oid={}, kind={:?}, err={}", symbol, drift.oid, drift.kind, e),
+            }
+        }
+        repaired
+    }
+
+    /// 出现不一致时告警一次，恢复一致后才会再次告警
+    async fn notify(&self, report: &ReconcileReport) {
+        let breached = !report.drifts.is_empty();
+        let changed = {
+            let mut active = self.active.lock().unwrap();
+            if breached {
+                active.insert(report.symbol.clone())
+            } else {
+                active.remove(&report.symbol)
+            }
+        };
+        if !changed {
+            return;
+        }
+        if !breached {
+            info!("ALERT RESOLVED: {:?}, symbol={}", AlertKind::CacheDrift, &report.symbol);
+            return;
+        }
+        let alert = Alert {
+            kind: AlertKind::CacheDrift,
+            symbol: Some(report.symbol.clone()),
+            value: report.drifts.len() as u64,
+            threshold: 0,
+            ts: report.ts,
+        };
+        for sink in &self.sinks {
+            if let Err(e) = sink.send(&alert).await {
+                warn!("SEND ALERT FAILED: alert={}, err={}", alert, e);
+            }
+        }
+    }
+}
+
+/// 定时对账所有已完成恢复的交易对，备机不对账
+pub fn launch(engine: EngineHandle, interval: Duration, mut ctx: broadcast::Receiver<bool>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval.max(Duration::from_secs(1)));
+        // 跳过立即触发的第一次，等待启动恢复完成
+        ticker.tick().await;
+        loop {
+            tokio::select! {
+                _ = ctx.recv() => break,
+                _ = ticker.tick() => {
+                    let Some(reconciler) = engine.reconciler() else {
+                        break;
+                    };
+                    if !engine.replication().accepts_orders() {
+                        continue;
+                    }
+                    for symbol in engine.symbols().into_iter().filter(|s| engine.is_ready(s)) {
+                        if let Err(e) = reconciler.reconcile(&engine, &symbol, None).await {
+                            warn!("RECONCILE FAILED: symbol={}, err={}", symbol, e);
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use loom_core::order::{Order, TradeSide};
+    use loom_core::testing::new_order;
+
+    use crate::reconcile::{confirm, diff, Drift, DriftKind};
+
+    #[test]
+    fn diff_test() {
+        let order = |id, acc_fill_qty, seq| Order { acc_fill_qty, seq, ..new_order(id, TradeSide::BUY, 10, "100") };
+        let book = vec![order(1, 0, 1), order(2, 4, 2), order(3, 0, 3), order(4, 0, 4)];
+        // 1一致，2数量不一致，3没有订单哈希，4不在订单ID集合中，5和6是孤立ID，7还在撮合队列中
+        let cached = vec![order(1, 0, 1), order(2, 0, 2), order(4, 0, 4), order(5, 0, 5), order(7, 0, 9)];
+        let ids = vec![1, 2, 3, 5, 6, 7];
+        let drifts = diff(&book, &ids, &cached, 8);
+        let kinds: Vec<(u64, DriftKind)> = drifts.iter().map(|d| (d.oid, d.kind)).collect();
+        assert_eq!(kinds, vec![
+            (2, DriftKind::QtyMismatch),
+            (3, DriftKind::MissingHash),
+            (4, DriftKind::MissingId),
+            (5, DriftKind::OrphanId),
+            (6, DriftKind::OrphanId),
+        ]);
+        assert_eq!(drifts[0], Drift { kind: DriftKind::QtyMismatch, oid: 2, book_remain: Some(6), cache_remain: Some(10) });
+        assert_eq!((drifts[4].book_remain, drifts[4].cache_remain), (None, None));
+        assert!(diff(&book[..1], &[1], &cached[..1], 1).is_empty());
+    }
+
+    #[test]
+    fn confirm_test() {
+        let drift = |oid: u64, kind: DriftKind| Drift { kind, oid, book_remain: None, cache_remain: None };
+        let first = vec![drift(1, DriftKind::OrphanId), drift(2, DriftKind::QtyMismatch)];
+        let second = vec![drift(2, DriftKind::QtyMismatch), drift(3, DriftKind::MissingHash), drift(1, DriftKind::MissingHash)];
+        assert_eq!(confirm(&first, second), vec![drift(2, DriftKind::QtyMismatch)]);
+    }
+}
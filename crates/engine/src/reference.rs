@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+#[cfg(feature = "redis")]
+use std::sync::Arc;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use anyhow::bail;
+use bigdecimal::{BigDecimal, Zero};
+#[cfg(feature = "redis")]
+use futures_util::StreamExt;
+#[cfg(feature = "redis")]
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "redis")]
+use tokio::sync::broadcast;
+#[cfg(feature = "redis")]
+use tokio::task::JoinHandle;
+
+use loom_core::snapshot::BookSnapshot;
+use loom_core::utils;
+
+/// 默认参考价有效期
+pub const DEFAULT_MAX_AGE: Duration = Duration::from_secs(60);
+
+/// 订阅断开后重连的间隔
+#[cfg(feature = "redis")]
+const RESUBSCRIBE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 参考价配置
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ReferenceConfig {
+    /// 最新成交价超过该时间没有更新时视为过期，改用参考价，毫秒，未配置时只在没有成交价时使用参考价
+    pub stale_after_ms: Option<u64>,
+    /// 参考价的有效期，毫秒，默认60秒
+    pub max_age_ms: Option<u64>,
+    /// 订阅参考价的Redis频道，消息为JSON格式的参考价
+    pub redis_channel: Option<String>,
+}
+
+/// 外部参考价，如指数价格或其他交易所的价格
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ReferencePrice {
+    pub symbol: String,
+    pub px: BigDecimal,
+    /// 报价时间，毫秒，未设置时为收到的时间
+    #[serde(default)]
+    pub ts: u128,
+}
+
+/// 各交易对最新的外部参考价，内部成交价过期或不存在时作为价格带等风控检查的锚定价
+#[derive(Debug)]
+pub struct ReferencePrices {
+    prices: RwLock<HashMap<String, ReferencePrice>>,
+    stale_after: Option<Duration>,
+    max_age: Duration,
+}
+
+impl ReferencePrices {
+    pub fn new(config: &ReferenceConfig) -> ReferencePrices {
+        ReferencePrices {
+            prices: RwLock::new(HashMap::new()),
+            stale_after: config.stale_after_ms.map(Duration::from_millis),
+            max_age: config.max_age_ms.map(Duration::from_millis).unwrap_or(DEFAULT_MAX_AGE),
+        }
+    }
+
+    /// 更新参考价，早于已有报价的更新忽略，返回是否更新
+    pub fn update(&self, mut price: ReferencePrice) -> anyhow::Result<bool> {
+        if price.px <= BigDecimal::zero() {
+            bail!("invalid reference price, symbol={}, px={}", &price.symbol, &price.px);
+        }
+        if price.ts == 0 {
+            price.ts = utils::now_ts();
+        }
+        let mut prices = self.prices.write().unwrap();
+        if prices.get(&price.symbol).is_some_and(|p| p.ts > price.ts) {
+            return Ok(false);
+        }
+        prices.insert(price.symbol.clone(), price);
+        Ok(true)
+    }
+
+    pub fn get(&self, symbol: &str) -> Option<ReferencePrice> {
+        self.prices.read().unwrap().get(symbol).cloned()
+    }
+
+    /// 风控检查使用的锚定价，最新成交价未过期时使用成交价，否则使用有效的参考价，
+    /// 都没有时使用过期的成交价，没有成交价时返回None
+    pub fn anchor(&self, snapshot: &BookSnapshot, now: u128) -> Option<BigDecimal> {
+        let last = Some(&snapshot.px).filter(|px| !px.is_zero());
+        let stale = self.stale_after.is_some_and(|after| now.saturating_sub(snapshot.px_ts) > after.as_millis());
+        if let (Some(px), false) = (last, stale) {
+            return Some(px.clone());
+        }
+        let reference = self.get(&snapshot.symbol)
+            .filter(|p| now.saturating_sub(p.ts) <= self.max_age.as_millis())
+            .map(|p| p.px);
+        reference.or(last.cloned())
+    }
+
+    /// 订阅Redis频道接收参考价，连接断开后自动重连
+    #[cfg(feature = "redis")]
+    pub fn launch_subscriber(self: Arc<Self>, redis_uri: &str, channel: &str, mut ctx: broadcast::Receiver<bool>) -> anyhow::Result<JoinHandle<()>> {
+        let client = redis::Client::open(redis_uri)?;
+        let channel = channel.to_string();
+        Ok(tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = ctx.recv() => break,
+                    result = self.subscribe(&client, &channel) => {
+                        if let Err(e) = result {
+                            warn!("REFERENCE SUBSCRIBE FAILED: channel={}, err={}", &channel, e);
+                        }
+                        tokio::time::sleep(RESUBSCRIBE_INTERVAL).await;
+                    }
+                }
+            }
+        }))
+    }
+
+    #[cfg(feature = "redis")]
+    async fn subscribe(&self, client: &redis::Client, channel: &str) -> anyhow::Result<()> {
+        let mut pubsub = client.get_async_pubsub().await?;
+        pubsub.subscribe(channel).await?;
+        info!("REFERENCE SUBSCRIBED: channel={}", channel);
+        let mut messages = pubsub.on_message();
+        while let Some(msg) = messages.next().await {
+            let payload: String = msg.get_payload()?;
+            let result = serde_json::from_str::<ReferencePrice>(&payload)
+                .map_err(anyhow::Error::from)
+                .and_then(|price| self.update(price));
+            if let Err(e) = result {
+                warn!("INVALID REFERENCE PRICE: channel={}, payload={}, err={}", channel, &payload, e);
+            }
+        }
+        bail!("subscription closed")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bigdecimal::BigDecimal;
+
+    use loom_core::snapshot::BookSnapshot;
+
+    use crate::reference::{ReferenceConfig, ReferencePrice, ReferencePrices};
+
+    fn reference(px: u32, ts: u128) -> ReferencePrice {
+        ReferencePrice { symbol: "LOOM-USDT-SPOT".into(), px: BigDecimal::from(px), ts }
+    }
+
+    #[test]
+    fn anchor_test() {
+        let prices = ReferencePrices::new(&ReferenceConfig { stale_after_ms: Some(1000), max_age_ms: Some(5000), redis_channel: None });
+        let mut snapshot = BookSnapshot::empty("LOOM-USDT-SPOT");
+        // 新市场没有成交价也没有参考价
+        assert_eq!(prices.anchor(&snapshot, 10_000), None);
+        assert!(prices.update(reference(100, 10_000)).unwrap());
+        assert_eq!(prices.anchor(&snapshot, 10_000), Some(BigDecimal::from(100)));
+        // 早于已有报价的更新忽略
+        assert!(!prices.update(reference(90, 9_000)).unwrap());
+        assert!(prices.update(reference(0, 11_000)).is_err());
+
+        snapshot.px = BigDecimal::from(120);
+        snapshot.px_ts = 10_000;
+        assert_eq!(prices.anchor(&snapshot, 10_500), Some(BigDecimal::from(120)));
+        // 成交价过期后使用参考价，参考价也过期时使用成交价
+        assert_eq!(prices.anchor(&snapshot, 12_000), Some(BigDecimal::from(100)));
+        assert_eq!(prices.anchor(&snapshot, 16_000), Some(BigDecimal::from(120)));
+    }
+}
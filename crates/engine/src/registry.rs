@@ -0,0 +1,63 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+
+use loom_core::symbol::SymbolSpec;
+
+/// 交易对规格注册表，由引擎句柄和管理接口共享，交易员持有规格副本，修改时由引擎同步
+#[derive(Debug, Clone, Default)]
+pub struct SymbolRegistry {
+    specs: Arc<RwLock<BTreeMap<String, SymbolSpec>>>,
+    /// 串行化规格修改，保证注册表与交易员的修改顺序一致
+    updates: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl SymbolRegistry {
+    pub fn new() -> SymbolRegistry {
+        SymbolRegistry::default()
+    }
+
+    pub fn get(&self, symbol: &str) -> Option<SymbolSpec> {
+        self.specs.read().unwrap().get(symbol).cloned()
+    }
+
+    /// 所有交易对规格，按交易对排序
+    pub fn list(&self) -> Vec<SymbolSpec> {
+        self.specs.read().unwrap().values().cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.specs.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub(crate) fn insert(&self, spec: SymbolSpec) {
+        self.specs.write().unwrap().insert(spec.symbol.clone(), spec);
+    }
+
+    pub(crate) async fn lock_updates(&self) -> tokio::sync::MutexGuard<'_, ()> {
+        self.updates.lock().await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use loom_core::symbol::SymbolSpec;
+
+    use crate::registry::SymbolRegistry;
+
+    #[test]
+    fn registry_test() {
+        let registry = SymbolRegistry::new();
+        registry.insert(SymbolSpec::new("LOOM-USDT-SPOT"));
+        registry.insert(SymbolSpec::new("BTC-USDT-SPOT").with_lot_size(10));
+        let shared = registry.clone();
+        shared.insert(SymbolSpec::new("BTC-USDT-SPOT").with_lot_size(100));
+        let symbols: Vec<String> = registry.list().into_iter().map(|s| s.symbol).collect();
+        assert_eq!(symbols, vec!["BTC-USDT-SPOT", "LOOM-USDT-SPOT"]);
+        assert_eq!(registry.get("BTC-USDT-SPOT").unwrap().lot_size, Some(100));
+        assert!(registry.get("ETH-USDT-SPOT").is_none());
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::BufRead;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+
+use loom_core::clock::ManualClock;
+use loom_core::market::{MarketBook, MatchTrades};
+use loom_core::order::{Order, OrderAction};
+use loom_core::snapshot::{BookImage, StateHash};
+use loom_core::symbol::SymbolSpec;
+
+/// 确定性回放，按日志顺序将请求交给撮合器，时钟取请求时间，相同日志的回放结果相同
+#[derive(Debug)]
+pub struct Replayer {
+    books: BTreeMap<String, MarketBook>,
+    /// 交易对规格，未注册的交易对使用默认规格
+    specs: HashMap<String, SymbolSpec>,
+    clock: Arc<ManualClock>,
+    /// 只回放序列号不大于until的请求
+    until: Option<u64>,
+    /// 已回放的请求数量
+    applied: usize,
+}
+
+impl Default for Replayer {
+    fn default() -> Self {
+        Replayer::new()
+    }
+}
+
+impl Replayer {
+    pub fn new() -> Replayer {
+        Replayer {
+            books: BTreeMap::new(),
+            specs: HashMap::new(),
+            clock: Arc::new(ManualClock::new(0)),
+            until: None,
+            applied: 0,
+        }
+    }
+
+    pub fn with_until(mut self, until: u64) -> Replayer {
+        self.until = Some(until);
+        self
+    }
+
+    /// 按交易对规格创建订单簿
+    pub fn with_spec(mut self, spec: SymbolSpec) -> Replayer {
+        self.specs.insert(spec.symbol.clone(), spec);
+        self
+    }
+
+    /// 用镜像恢复交易对的订单簿，之后只回放序列号大于镜像序列号的请求
+    pub fn restore(&mut self, image: BookImage) -> anyhow::Result<()> {
+        let symbol = image.symbol.clone();
+        Self::book(&mut self.books, &self.specs, &self.clock, &symbol).restore(image)
+    }
+
+    fn book<'a>(books: &'a mut BTreeMap<String, MarketBook>, specs: &HashMap<String, SymbolSpec>, clock: &Arc<ManualClock>, symbol: &str) -> &'a mut MarketBook {
+        books.entry(symbol.to_string()).or_insert_with(|| {
+            let spec = specs.get(symbol).cloned().unwrap_or_else(|| SymbolSpec::new(symbol));
+            MarketBook::with_spec(spec).with_clock(clock.clone())
+        })
+    }
+
+    /// 回放一个请求，超过until或重复的请求返回None
+    pub fn apply(&mut self, order: Order) -> Option<MatchTrades> {
+        if self.until.is_some_and(|until| order.seq > until) {
+            return None;
+        }
+        let book = Self::book(&mut self.books, &self.specs, &self.clock, &order.symbol);
+        if order.seq != 0 && order.seq <= book.seq() {
+            return None;
+        }
+        self.clock.set(order.ts as u64);
+        self.applied += 1;
+        // 请求时间之前到期的订单先撤销，与交易员的处理顺序一致
+        let mut trades = MatchTrades::new();
+        book.expire_into(&mut trades);
+        match order.action {
+            OrderAction::PLACE => book.try_match_into(order, &mut trades),
+            OrderAction::CANCEL => {
+                book.try_cancel_into(order, &mut trades);
+            }
+            OrderAction::REDUCE => {
+                book.try_reduce_into(order, &mut trades);
+            }
+        }
+        Some(trades)
+    }
+
+    pub fn applied(&self) -> usize {
+        self.applied
+    }
+
+    /// 回放涉及的交易对的订单簿，按交易对排序
+    pub fn books(&self) -> impl Iterator<Item=&MarketBook> + '_ {
+        self.books.values()
+    }
+
+    /// 各交易对的状态哈希，按交易对排序
+    pub fn state_hashes(&self) -> Vec<StateHash> {
+        self.books.values().map(|book| book.state_hash()).collect()
+    }
+
+    /// 读取日志文件，每行为一个JSON格式的请求，空行忽略
+    pub fn read_journal(path: &Path) -> anyhow::Result<Vec<Order>> {
+        let file = std::fs::File::open(path)
+            .map_err(|e| anyhow!("open journal failed, path={}, err={}", path.display(), e))?;
+        let mut orders = Vec::new();
+        for (i, line) in std::io::BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let order = serde_json::from_str(&line)
+                .map_err(|e| anyhow!("invalid journal line {}, err={}", i + 1, e))?;
+            orders.push(order);
+        }
+        Ok(orders)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use loom_core::order::{Order, TradeSide};
+    use loom_core::testing::new_order;
+
+    use crate::replay::Replayer;
+
+    fn journal() -> Vec<Order> {
+        vec![
+            Order { ts: 1001, seq: 1, ..new_order(1, TradeSide::SELL, 1, "100") },
+            Order { ts: 1002, seq: 2, ..new_order(2, TradeSide::SELL, 1, "100") },
+            Order { ts: 1003, seq: 3, ..new_order(3, TradeSide::BUY, 1, "100") },
+            Order { ts: 1003, seq: 3, ..new_order(3, TradeSide::BUY, 1, "100") },
+        ]
+    }
+
+    #[test]
+    fn replay_test() {
+        let mut replayer = Replayer::new();
+        let trades: Vec<_> = journal().into_iter().filter_map(|o| replayer.apply(o)).flatten().collect();
+        // 重复的请求只回放一次
+        assert_eq!(replayer.applied(), 3);
+        assert_eq!(trades.len(), 1);
+        assert_eq!((trades[0].maker_oid, trades[0].ts), (1, 1003));
+        let mut again = Replayer::new();
+        journal().into_iter().for_each(|o| { again.apply(o); });
+        assert_eq!(again.state_hashes(), replayer.state_hashes());
+        let mut until = Replayer::new().with_until(2);
+        journal().into_iter().for_each(|o| { until.apply(o); });
+        assert_eq!(until.state_hashes()[0].orders, 2);
+    }
+
+    #[test]
+    fn restore_test() {
+        let mut until = Replayer::new().with_until(2);
+        journal().into_iter().for_each(|o| { until.apply(o); });
+        // 从镜像继续回放，镜像已包含的请求跳过
+        let mut replayer = Replayer::new();
+        replayer.restore(until.books().next().unwrap().image()).unwrap();
+        let trades: Vec<_> = journal().into_iter().filter_map(|o| replayer.apply(o)).flatten().collect();
+        assert_eq!((replayer.applied(), trades.len()), (1, 1));
+        assert_eq!(replayer.books().next().unwrap().state_hash().orders, 1);
+    }
+}
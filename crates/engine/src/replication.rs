@@ -0,0 +1,150 @@
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+
+#[cfg(feature = "redis")]
+pub use crate::follower::launch_checkpoints;
+
+/// 日志流默认保留的条目数量
+pub const DEFAULT_JOURNAL_MAX_LEN: usize = 100_000;
+/// 默认状态哈希检查点发布间隔
+pub const DEFAULT_CHECKPOINT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// 复制角色
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[repr(u8)]
+pub enum ReplicationRole {
+    /// 单机运行，不写日志
+    #[default]
+    Standalone = 0,
+    /// 主机，接受撮合请求并写入日志
+    Primary = 1,
+    /// 备机，拒绝撮合请求，按日志重放到影子订单簿
+    Standby = 2,
+    /// 备机正在提升为主机，重放剩余日志
+    Promoting = 3,
+}
+
+impl ReplicationRole {
+    fn from_u8(value: u8) -> ReplicationRole {
+        match value {
+            1 => ReplicationRole::Primary,
+            2 => ReplicationRole::Standby,
+            3 => ReplicationRole::Promoting,
+            _ => ReplicationRole::Standalone,
+        }
+    }
+}
+
+/// 备机拒绝撮合请求的错误
+#[derive(Debug, Clone)]
+pub struct StandbyMode {
+    pub symbol: String,
+}
+
+impl Display for StandbyMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "engine is standby, symbol={}", self.symbol)
+    }
+}
+
+impl std::error::Error for StandbyMode {}
+
+/// 复制状态，由引擎句柄和交易员共享
+#[derive(Debug)]
+pub struct Replication {
+    role: AtomicU8,
+    /// 日志流保留的条目数量
+    journal_max_len: usize,
+    /// 备机日志跟随协程
+    followers: Mutex<Vec<JoinHandle<()>>>,
+    /// 检查点哈希不一致次数
+    divergences: AtomicU64,
+}
+
+impl Replication {
+    pub fn new(role: ReplicationRole, journal_max_len: usize) -> Replication {
+        Replication {
+            role: AtomicU8::new(role as u8),
+            journal_max_len,
+            followers: Mutex::new(Vec::new()),
+            divergences: AtomicU64::new(0),
+        }
+    }
+
+    pub fn role(&self) -> ReplicationRole {
+        ReplicationRole::from_u8(self.role.load(Ordering::Acquire))
+    }
+
+    pub(crate) fn set_role(&self, role: ReplicationRole) {
+        self.role.store(role as u8, Ordering::Release);
+    }
+
+    /// 是否接受外部撮合请求
+    pub fn accepts_orders(&self) -> bool {
+        matches!(self.role(), ReplicationRole::Standalone | ReplicationRole::Primary)
+    }
+
+    /// 是否需要将撮合请求写入日志
+    pub fn writes_journal(&self) -> bool {
+        self.role() == ReplicationRole::Primary
+    }
+
+    /// 备机重放时不推送成交，成交由主机推送
+    pub fn publishes_trades(&self) -> bool {
+        self.accepts_orders()
+    }
+
+    pub fn journal_max_len(&self) -> usize {
+        self.journal_max_len
+    }
+
+    pub fn divergences(&self) -> u64 {
+        self.divergences.load(Ordering::Relaxed)
+    }
+
+    /// 记录一次检查点哈希不一致
+    #[cfg(feature = "redis")]
+    pub(crate) fn diverged(&self) {
+        self.divergences.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[cfg(feature = "redis")]
+    pub(crate) fn add_follower(&self, handle: JoinHandle<()>) {
+        self.followers.lock().unwrap().push(handle);
+    }
+
+    pub(crate) fn take_followers(&self) -> Vec<JoinHandle<()>> {
+        std::mem::take(&mut *self.followers.lock().unwrap())
+    }
+}
+
+impl Default for Replication {
+    fn default() -> Self {
+        Replication::new(ReplicationRole::Standalone, DEFAULT_JOURNAL_MAX_LEN)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::replication::{Replication, ReplicationRole};
+
+    #[test]
+    fn role_test() {
+        let replication = Replication::default();
+        assert!(replication.accepts_orders());
+        assert!(!replication.writes_journal());
+        replication.set_role(ReplicationRole::Standby);
+        assert!(!replication.accepts_orders());
+        assert!(!replication.publishes_trades());
+        replication.set_role(ReplicationRole::Promoting);
+        assert!(!replication.accepts_orders());
+        replication.set_role(ReplicationRole::Primary);
+        assert!(replication.writes_journal());
+        assert!(replication.publishes_trades());
+    }
+}
@@ -0,0 +1,279 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use bigdecimal::BigDecimal;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use loom_core::market::MatchTrade;
+use loom_core::order::TradeSide;
+use loom_core::symbol::FeeSchedule;
+
+use crate::archive::TradeArchive;
+
+/// 毫秒换算为纳秒
+const NANOS_PER_MILLI: u128 = 1_000_000;
+
+/// 报表格式
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub enum ReportFormat {
+    /// 带表头的CSV
+    #[default]
+    Csv,
+    /// 每行一个JSON对象
+    Json,
+}
+
+impl ReportFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            ReportFormat::Csv => "csv",
+            ReportFormat::Json => "jsonl",
+        }
+    }
+}
+
+impl FromStr for ReportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(ReportFormat::Csv),
+            "json" => Ok(ReportFormat::Json),
+            _ => Err(anyhow!("unknown report format {}", s)),
+        }
+    }
+}
+
+/// 报表字段，按配置的顺序输出
+#[allow(non_camel_case_types)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub enum ReportField {
+    trade_id,
+    /// 成交时间，纳秒
+    ts_ns,
+    symbol,
+    price,
+    qty,
+    /// 成交金额
+    notional,
+    /// 主动成交方向
+    taker_side,
+    taker_oid,
+    maker_oid,
+    taker_account,
+    maker_account,
+    /// 手续费，买方按成交数量以基础资产收取，卖方按成交金额以计价资产收取
+    taker_fee,
+    maker_fee,
+    /// 手续费资产，BASE或QUOTE
+    taker_fee_asset,
+    maker_fee_asset,
+}
+
+/// 默认输出全部字段
+pub const DEFAULT_REPORT_FIELDS: [ReportField; 15] = [
+    ReportField::trade_id,
+    ReportField::ts_ns,
+    ReportField::symbol,
+    ReportField::price,
+    ReportField::qty,
+    ReportField::notional,
+    ReportField::taker_side,
+    ReportField::taker_oid,
+    ReportField::maker_oid,
+    ReportField::taker_account,
+    ReportField::maker_account,
+    ReportField::taker_fee,
+    ReportField::maker_fee,
+    ReportField::taker_fee_asset,
+    ReportField::maker_fee_asset,
+];
+
+/// 监管成交报表配置
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ReportConfig {
+    /// 报表目录，默认 ./report
+    pub dir: Option<String>,
+    /// 默认CSV
+    pub format: Option<ReportFormat>,
+    /// 输出的字段，默认全部字段
+    pub fields: Option<Vec<ReportField>>,
+}
+
+/// 成交一方的手续费和手续费资产
+fn fee(side: Option<TradeSide>, trade: &MatchTrade, rate: &BigDecimal) -> (Value, Value) {
+    match side {
+        Some(TradeSide::BUY) => (json!((BigDecimal::from(trade.qty) * rate).to_string()), json!("BASE")),
+        Some(TradeSide::SELL) => (json!((&trade.px * BigDecimal::from(trade.qty) * rate).to_string()), json!("QUOTE")),
+        None => (Value::Null, Value::Null),
+    }
+}
+
+/// 监管成交报表，从成交归档读取时间范围内的成交，按配置的字段和格式输出
+#[derive(Debug, Clone)]
+pub struct TradeReporter {
+    archive: TradeArchive,
+    /// 交易对的手续费率，未配置的交易对手续费为0
+    fees: HashMap<String, FeeSchedule>,
+    format: ReportFormat,
+    fields: Vec<ReportField>,
+}
+
+impl TradeReporter {
+    pub fn new(archive: TradeArchive, config: &ReportConfig) -> TradeReporter {
+        TradeReporter {
+            archive,
+            fees: HashMap::new(),
+            format: config.format.unwrap_or_default(),
+            fields: config.fields.clone().filter(|f| !f.is_empty()).unwrap_or_else(|| DEFAULT_REPORT_FIELDS.to_vec()),
+        }
+    }
+
+    pub fn with_fees(mut self, symbol: &str, fees: FeeSchedule) -> TradeReporter {
+        self.fees.insert(symbol.to_string(), fees);
+        self
+    }
+
+    pub fn with_format(mut self, format: ReportFormat) -> TradeReporter {
+        self.format = format;
+        self
+    }
+
+    fn value(&self, field: ReportField, trade: &MatchTrade) -> Value {
+        let fees = self.fees.get(trade.symbol.as_str()).cloned().unwrap_or_default();
+        match field {
+            ReportField::trade_id => json!(trade.id),
+            ReportField::ts_ns => json!((trade.ts * NANOS_PER_MILLI) as u64),
+            ReportField::symbol => json!(trade.symbol),
+            ReportField::price => json!(trade.px.to_string()),
+            ReportField::qty => json!(trade.qty),
+            ReportField::notional => json!((&trade.px * BigDecimal::from(trade.qty)).to_string()),
+            ReportField::taker_side => json!(trade.taker_side),
+            ReportField::taker_oid => json!(trade.taker_oid),
+            ReportField::maker_oid => json!(trade.maker_oid),
+            ReportField::taker_account => json!(trade.taker_account),
+            ReportField::maker_account => json!(trade.maker_account),
+            ReportField::taker_fee => fee(trade.taker_side, trade, &fees.taker).0,
+            ReportField::maker_fee => fee(trade.maker_side(), trade, &fees.maker).0,
+            ReportField::taker_fee_asset => fee(trade.taker_side, trade, &fees.taker).1,
+            ReportField::maker_fee_asset => fee(trade.maker_side(), trade, &fees.maker).1,
+        }
+    }
+
+    /// 按格式输出成交
+    pub fn render(&self, trades: &[MatchTrade]) -> anyhow::Result<String> {
+        let mut out = String::with_capacity(256 * (trades.len() + 1));
+        if self.format == ReportFormat::Csv {
+            let header: Vec<String> = self.fields.iter().map(|f| format!("{:?}", f)).collect();
+            out.push_str(&header.join(","));
+            out.push('\n');
+        }
+        for trade in trades {
+            match self.format {
+                ReportFormat::Csv => {
+                    let row: Vec<String> = self.fields.iter().map(|f| match self.value(*f, trade) {
+                        Value::Null => String::new(),
+                        Value::String(s) => csv_escape(&s),
+                        other => other.to_string(),
+                    }).collect();
+                    let _ = writeln!(out, "{}", row.join(","));
+                }
+                ReportFormat::Json => {
+                    let row: serde_json::Map<String, Value> = self.fields.iter()
+                        .map(|f| (format!("{:?}", f), self.value(*f, trade)))
+                        .collect();
+                    let _ = writeln!(out, "{}", serde_json::to_string(&row)?);
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    /// 导出[start, end)内的成交报表，未指定交易对时导出所有已归档交易对，返回文件路径和成交数量
+    pub fn export(&self, dir: &Path, symbol: Option<&str>, start: u128, end: u128) -> anyhow::Result<(PathBuf, usize)> {
+        let symbols = match symbol {
+            Some(symbol) => vec![symbol.to_string()],
+            None => self.archive.symbols()?,
+        };
+        let mut trades = Vec::new();
+        for symbol in &symbols {
+            trades.extend(self.archive.read(symbol, start, end)?);
+        }
+        trades.sort_by(|a, b| (a.ts, &a.symbol, a.id).cmp(&(b.ts, &b.symbol, b.id)));
+        let out = self.render(&trades)?;
+        fs::create_dir_all(dir)?;
+        let name = match symbol {
+            Some(symbol) => format!("report-{}-{}-{}.{}", symbol, start, end, self.format.extension()),
+            None => format!("report-{}-{}.{}", start, end, self.format.extension()),
+        };
+        let path = dir.join(name);
+        // 先写临时文件再重命名，避免读到写了一半的报表
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, out)?;
+        fs::rename(&tmp, &path)?;
+        Ok((path, trades.len()))
+    }
+}
+
+/// 包含逗号、引号或换行的值加引号
+fn csv_escape(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use bigdecimal::BigDecimal;
+
+    use loom_core::market::{MarketBook, MatchTrades};
+    use loom_core::order::TradeSide;
+    use loom_core::symbol::FeeSchedule;
+    use loom_core::testing::account_order;
+
+    use crate::archive::TradeArchive;
+    use crate::report::{ReportConfig, ReportField, ReportFormat, TradeReporter};
+
+    #[test]
+    fn report_test() {
+        let dir = std::env::temp_dir().join(format!("loom-report-test-{}", std::process::id()));
+        let archive = TradeArchive::new(&dir.join("archive"));
+        let mut book = MarketBook::new("LOOM-USDT-SPOT");
+        let mut trades = MatchTrades::new();
+        book.try_match_into(account_order(1, "bob", TradeSide::SELL, 2, "100"), &mut trades);
+        book.try_match_into(account_order(2, "alice,inc", TradeSide::BUY, 2, "100"), &mut trades);
+        archive.append(&trades).unwrap();
+        let ts = trades[0].ts;
+
+        let fees = FeeSchedule { maker: BigDecimal::from_str("0.001").unwrap(), taker: BigDecimal::from_str("0.002").unwrap() };
+        let config = ReportConfig {
+            dir: None,
+            format: None,
+            fields: Some(vec![ReportField::ts_ns, ReportField::taker_oid, ReportField::taker_account, ReportField::taker_fee, ReportField::maker_fee, ReportField::maker_fee_asset]),
+        };
+        let reporter = TradeReporter::new(archive.clone(), &config).with_fees("LOOM-USDT-SPOT", fees);
+        let (path, records) = reporter.export(&dir, None, ts, ts + 1).unwrap();
+        assert_eq!(records, 1);
+        let csv = std::fs::read_to_string(&path).unwrap();
+        let expected = format!("ts_ns,taker_oid,taker_account,taker_fee,maker_fee,maker_fee_asset\n{},2,\"alice,inc\",0.004,0.20000000000,QUOTE\n", ts * 1_000_000);
+        assert_eq!(csv, expected);
+
+        let reporter = reporter.with_format(ReportFormat::Json);
+        let (path, _) = reporter.export(&dir, Some("LOOM-USDT-SPOT"), ts, ts + 1).unwrap();
+        let row: serde_json::Value = serde_json::from_str(std::fs::read_to_string(&path).unwrap().trim()).unwrap();
+        assert_eq!(row["taker_account"], "alice,inc");
+        assert_eq!(row["taker_fee"], "0.004");
+        // 范围外没有成交
+        assert_eq!(reporter.export(&dir, None, ts + 1, ts + 2).unwrap().1, 0);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,219 @@
+use std::cell::UnsafeCell;
+use std::mem::MaybeUninit;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// 有界环形缓冲区，单生产者单消费者无锁读写
+struct RingBuffer<T> {
+    buf: Box<[UnsafeCell<MaybeUninit<T>>]>,
+    mask: usize,
+    /// 消费位置
+    head: AtomicUsize,
+    /// 写入位置
+    tail: AtomicUsize,
+    /// 消费者是否已关闭
+    closed: AtomicBool,
+}
+
+// 读写位置通过原子变量同步，同一槽位同一时刻只会被生产者或消费者之一访问
+unsafe impl<T: Send> Send for RingBuffer<T> {}
+unsafe impl<T: Send> Sync for RingBuffer<T> {}
+
+impl<T> RingBuffer<T> {
+    fn new(capacity: usize) -> RingBuffer<T> {
+        let capacity = capacity.max(2).next_power_of_two();
+        let buf = (0..capacity)
+            .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+            .collect::<Vec<_>>()
+            .into_boxed_slice();
+        RingBuffer {
+            buf,
+            mask: capacity - 1,
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            closed: AtomicBool::new(false),
+        }
+    }
+
+    /// 仅由唯一的生产端调用
+    fn push(&self, value: T) -> Result<(), T> {
+        let tail = self.tail.load(Ordering::Relaxed);
+        let head = self.head.load(Ordering::Acquire);
+        if tail.wrapping_sub(head) > self.mask {
+            return Err(value);
+        }
+        unsafe { (*self.buf[tail & self.mask].get()).write(value); }
+        self.tail.store(tail.wrapping_add(1), Ordering::Release);
+        Ok(())
+    }
+
+    /// 仅由唯一的消费端调用，或在缓冲区释放时调用
+    fn pop(&self) -> Option<T> {
+        let head = self.head.load(Ordering::Relaxed);
+        let tail = self.tail.load(Ordering::Acquire);
+        if head == tail {
+            return None;
+        }
+        let value = unsafe { (*self.buf[head & self.mask].get()).assume_init_read() };
+        self.head.store(head.wrapping_add(1), Ordering::Release);
+        Some(value)
+    }
+
+    /// 生产端和消费端之外的线程也可调用，先读消费位置再读写入位置，结果不会因读到较新的消费位置而回绕
+    fn len(&self) -> usize {
+        let head = self.head.load(Ordering::Acquire);
+        let tail = self.tail.load(Ordering::Acquire);
+        tail.saturating_sub(head).min(self.mask + 1)
+    }
+}
+
+impl<T> Drop for RingBuffer<T> {
+    fn drop(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
+/// 生产端，只能存在一个且不可克隆，写入需要独占引用，多个提交方共享时由调用方加锁
+pub struct RingProducer<T> {
+    ring: Arc<RingBuffer<T>>,
+}
+
+impl<T> std::fmt::Debug for RingProducer<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RingProducer").field("len", &self.ring.len()).finish()
+    }
+}
+
+impl<T> RingProducer<T> {
+    /// 写入数据，缓冲区已满时返回原数据
+    pub fn try_push(&mut self, value: T) -> Result<(), T> {
+        self.ring.push(value)
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.ring.closed.load(Ordering::Acquire)
+    }
+
+    pub fn len(&self) -> usize {
+        self.ring.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.ring.mask + 1
+    }
+}
+
+/// 消费端，只能存在一个且不可克隆，读取需要独占引用
+pub struct RingConsumer<T> {
+    ring: Arc<RingBuffer<T>>,
+}
+
+impl<T> std::fmt::Debug for RingConsumer<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RingConsumer").field("len", &self.ring.len()).finish()
+    }
+}
+
+impl<T> RingConsumer<T> {
+    pub fn pop(&mut self) -> Option<T> {
+        self.ring.pop()
+    }
+
+    pub fn len(&self) -> usize {
+        self.ring.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 关闭缓冲区，之后生产者的写入会失败
+    pub fn close(&self) {
+        self.ring.closed.store(true, Ordering::Release);
+    }
+}
+
+impl<T> Drop for RingConsumer<T> {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+/// 创建容量为capacity的环形缓冲区，容量向上取整为2的幂
+pub fn ring_buffer<T>(capacity: usize) -> (RingProducer<T>, RingConsumer<T>) {
+    let ring = Arc::new(RingBuffer::new(capacity));
+    (
+        RingProducer { ring: Arc::clone(&ring) },
+        RingConsumer { ring },
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    use crate::ring::{ring_buffer, RingBuffer};
+
+    #[test]
+    fn push_pop_test() {
+        let (mut producer, mut consumer) = ring_buffer::<u64>(3);
+        assert_eq!(producer.capacity(), 4);
+        for i in 0..4 {
+            producer.try_push(i).unwrap();
+        }
+        assert_eq!(producer.try_push(4), Err(4));
+        assert_eq!(consumer.pop(), Some(0));
+        producer.try_push(4).unwrap();
+        let rest: Vec<u64> = std::iter::from_fn(|| consumer.pop()).collect();
+        assert_eq!(rest, vec![1, 2, 3, 4]);
+        drop(consumer);
+        assert!(producer.is_closed());
+    }
+
+    #[test]
+    fn cross_thread_test() {
+        let (mut producer, mut consumer) = ring_buffer::<u64>(64);
+        let handle = thread::spawn(move || {
+            let mut expect = 0;
+            while expect < 10_000 {
+                if let Some(v) = consumer.pop() {
+                    assert_eq!(v, expect);
+                    expect += 1;
+                }
+            }
+        });
+        for i in 0..10_000 {
+            let mut v = i;
+            while let Err(back) = producer.try_push(v) {
+                v = back;
+                thread::yield_now();
+            }
+        }
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn concurrent_len_test() {
+        let ring = Arc::new(RingBuffer::<u64>::new(4));
+        let done = Arc::new(AtomicBool::new(false));
+        let (writer, stop) = (Arc::clone(&ring), Arc::clone(&done));
+        let handle = thread::spawn(move || {
+            for i in 0..100_000 {
+                writer.push(i).unwrap();
+                writer.pop().unwrap();
+            }
+            stop.store(true, Ordering::Release);
+        });
+        // 第三个线程读取长度，不会读到超过容量的值
+        while !done.load(Ordering::Acquire) {
+            assert!(ring.len() <= 4);
+        }
+        handle.join().unwrap();
+    }
+}
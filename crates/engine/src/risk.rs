@@ -0,0 +1,295 @@
+use std::fmt::{Debug, Display, Formatter};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bigdecimal::{BigDecimal, Zero};
+use serde::{Deserialize, Serialize};
+
+use loom_core::order::{Order, OrderType};
+use loom_core::snapshot::BookSnapshot;
+use loom_core::utils;
+
+use crate::reference::ReferencePrices;
+
+/// 下单前风控检查，按注册顺序依次执行，任一检查失败即拒绝订单
+#[async_trait]
+pub trait RiskCheck: Debug + Send + Sync {
+    /// 检查名称，用于拒绝原因
+    fn name(&self) -> &str;
+
+    /// 检查新订单，snapshot为交易对最近发布的市场快照，拒绝时返回`RiskRejected`错误
+    async fn check(&self, order: &Order, snapshot: &BookSnapshot) -> anyhow::Result<()>;
+
+    /// 检查通过后订单未被受理时调用，撤回检查中占用的资源
+    fn release(&self, _order: &Order) {}
+}
+
+/// 风控拒绝错误
+#[derive(Debug, Clone)]
+pub struct RiskRejected {
+    pub check: String,
+    pub symbol: String,
+    pub oid: u64,
+    pub reason: String,
+}
+
+impl RiskRejected {
+    pub fn new(check: &str, order: &Order, reason: String) -> RiskRejected {
+        RiskRejected {
+            check: String::from(check),
+            symbol: order.symbol.to_string(),
+            oid: order.id,
+            reason,
+        }
+    }
+}
+
+impl Display for RiskRejected {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "RISK REJECTED: check={}, symbol={}, oid={}, reason={}", self.check, self.symbol, self.oid, self.reason)
+    }
+}
+
+impl std::error::Error for RiskRejected {}
+
+/// 内置风控配置，未配置的项不检查
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct RiskConfig {
+    /// 单笔订单最大名义价值，价格乘以数量
+    pub max_notional: Option<BigDecimal>,
+    /// 限价偏离最新成交价的最大百分比
+    pub price_band_pct: Option<BigDecimal>,
+    /// 交易对最大挂单数量，按最近快照计算
+    pub max_open_orders: Option<usize>,
+}
+
+/// 风控检查链
+#[derive(Debug, Clone, Default)]
+pub struct RiskChain {
+    checks: Vec<Arc<dyn RiskCheck>>,
+}
+
+impl RiskChain {
+    pub fn new() -> RiskChain {
+        RiskChain::default()
+    }
+
+    /// 按配置构造内置检查
+    pub fn from_config(config: &RiskConfig) -> RiskChain {
+        Self::from_config_with_reference(config, None)
+    }
+
+    /// 按配置构造内置检查，价格相关的检查在最新成交价过期或不存在时使用外部参考价
+    pub fn from_config_with_reference(config: &RiskConfig, reference: Option<Arc<ReferencePrices>>) -> RiskChain {
+        let mut chain = RiskChain::new();
+        if let Some(max) = &config.max_notional {
+            chain = chain.with_check(Arc::new(MaxNotional { max: max.clone(), reference: reference.clone() }));
+        }
+        if let Some(pct) = &config.price_band_pct {
+            chain = chain.with_check(Arc::new(PriceBand { pct: pct.clone(), reference }));
+        }
+        if let Some(max) = config.max_open_orders {
+            chain = chain.with_check(Arc::new(MaxOpenOrders { max }));
+        }
+        chain
+    }
+
+    /// 追加检查，在已注册的检查之后执行
+    pub fn with_check(mut self, check: Arc<dyn RiskCheck>) -> RiskChain {
+        self.checks.push(check);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.checks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.checks.is_empty()
+    }
+
+    /// 依次执行所有检查，任一检查失败时撤回之前已通过的检查
+    pub async fn check(&self, order: &Order, snapshot: &BookSnapshot) -> anyhow::Result<()> {
+        for (i, check) in self.checks.iter().enumerate() {
+            if let Err(e) = check.check(order, snapshot).await {
+                self.checks[..i].iter().for_each(|c| c.release(order));
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// 订单通过检查但未被受理时撤回所有检查
+    pub fn release(&self, order: &Order) {
+        self.checks.iter().for_each(|c| c.release(order));
+    }
+}
+
+/// 价格检查的锚定价，配置参考价时按参考价规则选择，否则使用最新成交价，没有价格时返回None
+fn anchor(reference: &Option<Arc<ReferencePrices>>, snapshot: &BookSnapshot) -> Option<BigDecimal> {
+    match reference {
+        Some(reference) => reference.anchor(snapshot, utils::now_ts()),
+        None => Some(snapshot.px.clone()).filter(|px| !px.is_zero()),
+    }
+}
+
+/// 单笔订单名义价值上限，市价单按最新成交价估算
+#[derive(Debug, Clone)]
+pub struct MaxNotional {
+    pub max: BigDecimal,
+    pub reference: Option<Arc<ReferencePrices>>,
+}
+
+#[async_trait]
+impl RiskCheck for MaxNotional {
+    fn name(&self) -> &str {
+        "max_notional"
+    }
+
+    async fn check(&self, order: &Order, snapshot: &BookSnapshot) -> anyhow::Result<()> {
+        let px = match order.ord_type {
+            OrderType::MARKET => anchor(&self.reference, snapshot).unwrap_or_default(),
+            OrderType::LIMIT => order.price.clone(),
+        };
+        let notional = px * BigDecimal::from(order.qty);
+        if notional > self.max {
+            let reason = format!("notional {} exceeds {}", notional, self.max);
+            return Err(RiskRejected::new(self.name(), order, reason).into());
+        }
+        Ok(())
+    }
+}
+
+/// 限价带，限价单价格偏离最新成交价超过pct百分比时拒绝，尚无成交价时不检查
+#[derive(Debug, Clone)]
+pub struct PriceBand {
+    pub pct: BigDecimal,
+    pub reference: Option<Arc<ReferencePrices>>,
+}
+
+#[async_trait]
+impl RiskCheck for PriceBand {
+    fn name(&self) -> &str {
+        "price_band"
+    }
+
+    async fn check(&self, order: &Order, snapshot: &BookSnapshot) -> anyhow::Result<()> {
+        if order.ord_type != OrderType::LIMIT {
+            return Ok(());
+        }
+        let px = match anchor(&self.reference, snapshot) {
+            Some(px) => px,
+            None => return Ok(()),
+        };
+        let deviation = (&order.price - &px).abs() * BigDecimal::from(100) / &px;
+        if deviation > self.pct {
+            let reason = format!("price {} deviates {}% from anchor price {}", order.price, deviation.round(2), px);
+            return Err(RiskRejected::new(self.name(), order, reason).into());
+        }
+        Ok(())
+    }
+}
+
+/// 交易对挂单数量上限
+#[derive(Debug, Clone)]
+pub struct MaxOpenOrders {
+    pub max: usize,
+}
+
+#[async_trait]
+impl RiskCheck for MaxOpenOrders {
+    fn name(&self) -> &str {
+        "max_open_orders"
+    }
+
+    async fn check(&self, order: &Order, snapshot: &BookSnapshot) -> anyhow::Result<()> {
+        let open: usize = snapshot.bids.iter().chain(snapshot.asks.iter()).map(|l| l.orders).sum();
+        if open >= self.max {
+            let reason = format!("open orders {} reaches {}", open, self.max);
+            return Err(RiskRejected::new(self.name(), order, reason).into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use async_trait::async_trait;
+    use bigdecimal::BigDecimal;
+
+    use loom_core::order::{Order, TradeSide};
+    use loom_core::snapshot::{BookSnapshot, LevelSnapshot};
+    use loom_core::testing::account_order;
+
+    use loom_core::utils;
+
+    use crate::reference::{ReferenceConfig, ReferencePrice, ReferencePrices};
+    use crate::risk::{RiskChain, RiskCheck, RiskConfig, RiskRejected};
+
+    /// 模拟外部余额校验
+    #[derive(Debug)]
+    struct Balance;
+
+    #[async_trait]
+    impl RiskCheck for Balance {
+        fn name(&self) -> &str {
+            "balance"
+        }
+
+        async fn check(&self, order: &Order, _: &BookSnapshot) -> anyhow::Result<()> {
+            if order.account.as_deref() != Some("alice") {
+                return Err(RiskRejected::new(self.name(), order, String::from("insufficient balance")).into());
+            }
+            Ok(())
+        }
+    }
+
+    fn reject_check(result: anyhow::Result<()>) -> String {
+        result.unwrap_err().downcast_ref::<RiskRejected>().unwrap().check.clone()
+    }
+
+    #[tokio::test]
+    async fn risk_chain_test() {
+        let config = RiskConfig {
+            max_notional: Some(BigDecimal::from(1000)),
+            price_band_pct: Some(BigDecimal::from(10)),
+            max_open_orders: Some(2),
+        };
+        let chain = RiskChain::from_config(&config).with_check(Arc::new(Balance));
+        assert_eq!(chain.len(), 4);
+        let mut snapshot = BookSnapshot::empty("LOOM-USDT-SPOT");
+        // 尚无成交价时不检查价格带
+        chain.check(&account_order(1, "alice", TradeSide::BUY, 1, "500"), &snapshot).await.unwrap();
+        assert_eq!(reject_check(chain.check(&account_order(1, "alice", TradeSide::BUY, 11, "100"), &snapshot).await), "max_notional");
+        snapshot.px = BigDecimal::from(100);
+        chain.check(&account_order(1, "alice", TradeSide::BUY, 1, "110"), &snapshot).await.unwrap();
+        assert_eq!(reject_check(chain.check(&account_order(1, "alice", TradeSide::BUY, 1, "111"), &snapshot).await), "price_band");
+        snapshot.bids = vec![LevelSnapshot { px: BigDecimal::from(99), qty: 2, orders: 2 }];
+        assert_eq!(reject_check(chain.check(&account_order(1, "alice", TradeSide::BUY, 1, "100"), &snapshot).await), "max_open_orders");
+        snapshot.bids.clear();
+        let mut order = account_order(1, "alice", TradeSide::BUY, 1, "100");
+        order.account = Some("bob".to_string());
+        assert_eq!(reject_check(chain.check(&order, &snapshot).await), "balance");
+    }
+
+    #[tokio::test]
+    async fn reference_price_test() {
+        let config = RiskConfig { max_notional: None, price_band_pct: Some(BigDecimal::from(10)), max_open_orders: None };
+        let reference = Arc::new(ReferencePrices::new(&ReferenceConfig { stale_after_ms: Some(1000), ..Default::default() }));
+        let chain = RiskChain::from_config_with_reference(&config, Some(reference.clone()));
+        let mut snapshot = BookSnapshot::empty("LOOM-USDT-SPOT");
+        chain.check(&account_order(1, "alice", TradeSide::BUY, 1, "500"), &snapshot).await.unwrap();
+        // 新市场没有成交价时按参考价检查价格带
+        reference.update(ReferencePrice { symbol: "LOOM-USDT-SPOT".into(), px: BigDecimal::from(100), ts: 0 }).unwrap();
+        assert_eq!(reject_check(chain.check(&account_order(1, "alice", TradeSide::BUY, 1, "500"), &snapshot).await), "price_band");
+        chain.check(&account_order(1, "alice", TradeSide::BUY, 1, "105"), &snapshot).await.unwrap();
+        // 成交价过期时使用参考价
+        snapshot.px = BigDecimal::from(500);
+        snapshot.px_ts = 1;
+        assert_eq!(reject_check(chain.check(&account_order(1, "alice", TradeSide::BUY, 1, "500"), &snapshot).await), "price_band");
+        snapshot.px_ts = utils::now_ts();
+        chain.check(&account_order(1, "alice", TradeSide::BUY, 1, "500"), &snapshot).await.unwrap();
+    }
+}
@@ -0,0 +1,242 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+#[cfg(feature = "redis")]
+use log::warn;
+#[cfg(feature = "redis")]
+use tokio::sync::broadcast;
+use tokio::sync::{Mutex, MutexGuard};
+#[cfg(feature = "redis")]
+use tokio::task::JoinHandle;
+
+#[cfg(feature = "redis")]
+use crate::cache::CacheManager;
+#[cfg(feature = "redis")]
+use crate::engine::EngineHandle;
+
+/// 每次从缓存预留的序列号数量
+pub const DEFAULT_SEQUENCE_BLOCK: u64 = 1000;
+
+/// 默认客户端ID高水位写入缓存的间隔
+pub const DEFAULT_ID_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 交易对序列号分配器，按块从缓存预留序列号，重启后从缓存记录的高水位继续分配
+#[derive(Debug)]
+pub struct Sequencer {
+    #[cfg_attr(not(feature = "redis"), allow(dead_code))]
+    symbol: String,
+    block: u64,
+    /// 未配置缓存时只在内存中分配
+    #[cfg(feature = "redis")]
+    cache: Option<CacheManager>,
+    state: Mutex<SequenceRange>,
+}
+
+/// 已预留的区间(next, limit]
+#[derive(Debug, Default)]
+pub struct SequenceRange {
+    next: u64,
+    limit: u64,
+}
+
+/// 持有分配器的锁，锁释放前分配的序列号按分配顺序进入撮合队列
+pub struct SequenceGuard<'a> {
+    sequencer: &'a Sequencer,
+    range: MutexGuard<'a, SequenceRange>,
+}
+
+impl Sequencer {
+    /// 只在内存中分配，重启后从1开始
+    pub fn new(symbol: &str) -> Sequencer {
+        Sequencer {
+            symbol: String::from(symbol),
+            block: DEFAULT_SEQUENCE_BLOCK,
+            #[cfg(feature = "redis")]
+            cache: None,
+            state: Mutex::new(SequenceRange::default()),
+        }
+    }
+
+    /// 从缓存预留序列号
+    #[cfg(feature = "redis")]
+    pub fn with_cache(mut self, cache: Option<CacheManager>) -> Sequencer {
+        self.cache = cache;
+        self
+    }
+
+    pub fn with_block(mut self, block: u64) -> Sequencer {
+        self.block = block.max(1);
+        self
+    }
+
+    /// 获取分配器的锁
+    pub async fn lock(&self) -> SequenceGuard<'_> {
+        SequenceGuard { sequencer: self, range: self.state.lock().await }
+    }
+
+    /// 预留floor之后的区间，返回(start, limit)
+    #[cfg(feature = "redis")]
+    async fn reserve(&self, floor: u64) -> anyhow::Result<(u64, u64)> {
+        let Some(cache) = &self.cache else {
+            return Ok((floor, floor + self.block));
+        };
+        let limit = cache.reserve_sequence(&self.symbol, self.block).await?;
+        if limit > floor {
+            Ok((limit - self.block, limit))
+        } else {
+            // 缓存中的高水位落后于已跳过的序列号，补齐差值
+            let count = floor - limit + self.block;
+            Ok((floor, cache.reserve_sequence(&self.symbol, count).await?))
+        }
+    }
+
+    #[cfg(not(feature = "redis"))]
+    async fn reserve(&self, floor: u64) -> anyhow::Result<(u64, u64)> {
+        Ok((floor, floor + self.block))
+    }
+}
+
+impl SequenceGuard<'_> {
+    /// 跳过seq及之前的序列号，备机重放主机日志时调用
+    pub fn advance(&mut self, seq: u64) {
+        self.range.next = self.range.next.max(seq);
+    }
+
+    /// 分配下一个序列号，区间耗尽时从缓存预留新的区间
+    pub async fn next(&mut self) -> anyhow::Result<u64> {
+        if self.range.next >= self.range.limit {
+            let floor = self.range.next;
+            let (start, limit) = self.sequencer.reserve(floor).await?;
+            self.range.next = start.max(floor);
+            self.range.limit = limit;
+        }
+        self.range.next += 1;
+        Ok(self.range.next)
+    }
+}
+
+/// 客户端订单ID检查结果
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum IdCheck {
+    /// 紧接高水位，或尚未记录高水位
+    InOrder,
+    /// 跳过了若干ID，值为缺失的ID数量
+    Gap(u64),
+    /// 不大于高水位，值为当前高水位
+    Regression(u64),
+}
+
+/// 交易对已受理订单的客户端ID高水位，上游网关按交易对连续分配订单ID时用于发现丢失或重放的消息
+#[derive(Debug, Default)]
+pub struct IdWatermark {
+    high: AtomicU64,
+    /// 已写入缓存的高水位
+    saved: AtomicU64,
+    /// 累计缺失的ID数量
+    gaps: AtomicU64,
+    /// 累计回退次数
+    regressions: AtomicU64,
+}
+
+impl IdWatermark {
+    /// 从持久化的高水位继续检查
+    pub fn new(high: u64) -> IdWatermark {
+        IdWatermark { high: AtomicU64::new(high), saved: AtomicU64::new(high), ..IdWatermark::default() }
+    }
+
+    /// 记录已受理的订单ID，需在持有序列号分配器的锁时调用，保证与队列顺序一致
+    pub fn observe(&self, id: u64) -> IdCheck {
+        let high = self.high.load(Ordering::Relaxed);
+        if high != 0 && id <= high {
+            self.regressions.fetch_add(1, Ordering::Relaxed);
+            return IdCheck::Regression(high);
+        }
+        self.high.store(id, Ordering::Relaxed);
+        if high != 0 && id > high + 1 {
+            let missing = id - high - 1;
+            self.gaps.fetch_add(missing, Ordering::Relaxed);
+            return IdCheck::Gap(missing);
+        }
+        IdCheck::InOrder
+    }
+
+    pub fn high(&self) -> u64 {
+        self.high.load(Ordering::Relaxed)
+    }
+
+    /// 上次写入缓存后提高的高水位
+    pub fn unsaved(&self) -> Option<u64> {
+        let high = self.high();
+        (high > self.saved.load(Ordering::Relaxed)).then_some(high)
+    }
+
+    /// 记录已写入缓存的高水位
+    pub fn mark_saved(&self, high: u64) {
+        self.saved.fetch_max(high, Ordering::Relaxed);
+    }
+
+    pub fn gaps(&self) -> u64 {
+        self.gaps.load(Ordering::Relaxed)
+    }
+
+    pub fn regressions(&self) -> u64 {
+        self.regressions.load(Ordering::Relaxed)
+    }
+}
+
+/// 定时将提高的客户端ID高水位写入缓存，下单路径不访问Redis，引擎关闭时由引擎最后写入一次
+#[cfg(feature = "redis")]
+pub fn launch_id_flush(engine: EngineHandle, interval: Duration, mut ctx: broadcast::Receiver<bool>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval.max(Duration::from_millis(10)));
+        loop {
+            tokio::select! {
+                _ = ctx.recv() => break,
+                _ = ticker.tick() => {
+                    if let Err(e) = engine.flush_client_ids().await {
+                        warn!("CLIENT ID FLUSH FAILED: err={}", e);
+                    }
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sequencer::{IdCheck, IdWatermark, Sequencer};
+
+    #[tokio::test]
+    async fn sequence_test() {
+        let sequencer = Sequencer::new("LOOM-USDT-SPOT").with_block(2);
+        let mut seqs = Vec::new();
+        for _ in 0..5 {
+            seqs.push(sequencer.lock().await.next().await.unwrap());
+        }
+        assert_eq!(seqs, vec![1, 2, 3, 4, 5]);
+        let mut guard = sequencer.lock().await;
+        guard.advance(10);
+        assert_eq!(guard.next().await.unwrap(), 11);
+        guard.advance(3);
+        assert_eq!(guard.next().await.unwrap(), 12);
+    }
+
+    #[test]
+    fn id_watermark_test() {
+        let ids = IdWatermark::new(0);
+        assert_eq!(ids.observe(5), IdCheck::InOrder);
+        assert_eq!(ids.observe(6), IdCheck::InOrder);
+        assert_eq!(ids.observe(9), IdCheck::Gap(2));
+        assert_eq!(ids.observe(7), IdCheck::Regression(9));
+        assert_eq!(ids.observe(9), IdCheck::Regression(9));
+        assert_eq!((ids.high(), ids.gaps(), ids.regressions()), (9, 2, 2));
+        assert_eq!(ids.unsaved(), Some(9));
+        ids.mark_saved(9);
+        assert_eq!(ids.unsaved(), None);
+        // 从持久化的高水位继续
+        let restored = IdWatermark::new(9);
+        assert_eq!(restored.unsaved(), None);
+        assert_eq!(restored.observe(10), IdCheck::InOrder);
+        assert_eq!(restored.observe(10), IdCheck::Regression(10));
+    }
+}
@@ -0,0 +1,265 @@
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+const SECS_PER_DAY: i64 = 86_400;
+
+/// 交易时段配置，时间按utc_offset_minutes指定的时区计算
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SessionConfig {
+    /// 开盘时间，HH:MM
+    pub open: String,
+    /// 收盘时间，HH:MM，早于开盘时间时表示跨夜交易，与开盘时间相同时全天交易
+    pub close: String,
+    /// 时区相对UTC的偏移，分钟，默认0
+    pub utc_offset_minutes: Option<i32>,
+    /// 交易日，0为周日，默认周一到周五
+    pub weekdays: Option<Vec<u8>>,
+    /// 休市日期，YYYY-MM-DD
+    pub holidays: Option<Vec<String>>,
+    /// 休市期间新订单的处理方式，默认拒绝
+    pub closed: Option<ClosedPolicy>,
+    /// 开盘集合竞价时长，秒，配置后开盘时先进入集合竞价，结束时按单一价格撮合
+    pub auction_secs: Option<u64>,
+}
+
+/// 休市期间新订单的处理方式
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub enum ClosedPolicy {
+    /// 拒绝新订单
+    #[default]
+    Reject,
+    /// 新订单进入队列，开盘后按序处理
+    Queue,
+}
+
+/// 交易时段阶段
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub enum SessionPhase {
+    /// 休市
+    Closed,
+    /// 开盘集合竞价
+    Auction,
+    /// 连续撮合
+    Open,
+}
+
+/// 交易时段
+#[derive(Debug, Clone)]
+pub struct TradingSession {
+    /// 开盘时间，当天的秒数
+    open: i64,
+    close: i64,
+    offset: i64,
+    weekdays: [bool; 7],
+    /// 休市日期，自1970-01-01起的天数
+    holidays: HashSet<i64>,
+    closed: ClosedPolicy,
+    auction: i64,
+}
+
+fn parse_time(s: &str) -> anyhow::Result<i64> {
+    let (h, m) = s.split_once(':').ok_or_else(|| anyhow!("invalid session time, expect HH:MM, time={}", s))?;
+    let (h, m): (i64, i64) = (h.parse()?, m.parse()?);
+    if !(0..24).contains(&h) || !(0..60).contains(&m) {
+        return Err(anyhow!("invalid session time, time={}", s));
+    }
+    Ok(h * 3600 + m * 60)
+}
+
+/// 公历日期距1970-01-01的天数
+fn days_from_civil(y: i64, m: i64, d: i64) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * ((m + 9) % 12) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+fn parse_date(s: &str) -> anyhow::Result<i64> {
+    let parts: Vec<&str> = s.split('-').collect();
+    let [y, m, d] = parts.as_slice() else {
+        return Err(anyhow!("invalid holiday, expect YYYY-MM-DD, date={}", s));
+    };
+    let (y, m, d): (i64, i64, i64) = (y.parse()?, m.parse()?, d.parse()?);
+    if !(1..=12).contains(&m) || !(1..=31).contains(&d) {
+        return Err(anyhow!("invalid holiday, date={}", s));
+    }
+    Ok(days_from_civil(y, m, d))
+}
+
+impl TradingSession {
+    pub fn new(config: &SessionConfig) -> anyhow::Result<TradingSession> {
+        let mut weekdays = [false; 7];
+        for day in config.weekdays.clone().unwrap_or_else(|| vec![1, 2, 3, 4, 5]) {
+            *weekdays.get_mut(day as usize).ok_or_else(|| anyhow!("invalid weekday, weekday={}", day))? = true;
+        }
+        let holidays = config.holidays.iter().flatten()
+            .map(|d| parse_date(d))
+            .collect::<anyhow::Result<_>>()?;
+        Ok(TradingSession {
+            open: parse_time(&config.open)?,
+            close: parse_time(&config.close)?,
+            offset: config.utc_offset_minutes.unwrap_or(0) as i64 * 60,
+            weekdays,
+            holidays,
+            closed: config.closed.unwrap_or_default(),
+            auction: config.auction_secs.unwrap_or(0) as i64,
+        })
+    }
+
+    pub fn closed_policy(&self) -> ClosedPolicy {
+        self.closed
+    }
+
+    fn trading_day(&self, day: i64) -> bool {
+        // 1970-01-01为周四
+        self.weekdays[(day + 4).rem_euclid(7) as usize] && !self.holidays.contains(&day)
+    }
+
+    /// now时刻所处的阶段，now为毫秒时间戳，跨夜交易按开盘所在的日期判断交易日
+    pub fn phase(&self, now: u128) -> SessionPhase {
+        let local = (now / 1000) as i64 + self.offset;
+        let (day, secs) = (local.div_euclid(SECS_PER_DAY), local.rem_euclid(SECS_PER_DAY));
+        let (opened_day, since_open) = if self.open < self.close {
+            if secs < self.open || secs >= self.close {
+                return SessionPhase::Closed;
+            }
+            (day, secs - self.open)
+        } else if secs >= self.open {
+            (day, secs - self.open)
+        } else if secs < self.close {
+            (day - 1, secs + SECS_PER_DAY - self.open)
+        } else {
+            return SessionPhase::Closed;
+        };
+        if !self.trading_day(opened_day) {
+            SessionPhase::Closed
+        } else if since_open < self.auction {
+            SessionPhase::Auction
+        } else {
+            SessionPhase::Open
+        }
+    }
+}
+
+/// 交易员发布的交易时段阶段，未配置交易时段时始终为连续撮合
+#[derive(Debug, Clone, Default)]
+pub struct SessionState {
+    session: Option<Arc<TradingSession>>,
+    /// 0为尚未计算，之后为阶段编号
+    phase: Arc<AtomicU8>,
+}
+
+impl SessionState {
+    pub fn new(session: Option<TradingSession>) -> SessionState {
+        SessionState { session: session.map(Arc::new), phase: Arc::new(AtomicU8::new(0)) }
+    }
+
+    /// 当前阶段，交易员尚未计算时按连续撮合处理
+    pub fn phase(&self) -> SessionPhase {
+        match self.phase.load(Ordering::Acquire) {
+            1 => SessionPhase::Closed,
+            2 => SessionPhase::Auction,
+            _ => SessionPhase::Open,
+        }
+    }
+
+    /// 交易员是否处理撮合请求
+    pub fn accepts(&self) -> bool {
+        self.phase() != SessionPhase::Closed
+    }
+
+    /// 引擎是否拒绝新请求
+    pub fn rejects(&self) -> bool {
+        !self.accepts() && self.session.as_ref().is_some_and(|s| s.closed_policy() == ClosedPolicy::Reject)
+    }
+
+    /// 按now重新计算阶段，阶段变化时返回之前和之后的阶段，首次计算时之前的阶段为连续撮合
+    pub(crate) fn update(&self, now: u128) -> Option<(SessionPhase, SessionPhase)> {
+        let session = self.session.as_ref()?;
+        let phase = session.phase(now);
+        let value = match phase {
+            SessionPhase::Closed => 1,
+            SessionPhase::Auction => 2,
+            SessionPhase::Open => 3,
+        };
+        let previous = self.phase.swap(value, Ordering::AcqRel);
+        if previous == value {
+            return None;
+        }
+        let previous = match previous {
+            1 => SessionPhase::Closed,
+            2 => SessionPhase::Auction,
+            _ => SessionPhase::Open,
+        };
+        Some((previous, phase))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::session::{days_from_civil, ClosedPolicy, SessionConfig, SessionPhase, SessionState, TradingSession};
+
+    fn config(open: &str, close: &str) -> SessionConfig {
+        SessionConfig {
+            open: open.to_string(),
+            close: close.to_string(),
+            utc_offset_minutes: None,
+            weekdays: None,
+            holidays: None,
+            closed: None,
+            auction_secs: None,
+        }
+    }
+
+    /// 2024-01-01（周一）的时间
+    fn at(day: i64, h: i64, m: i64) -> u128 {
+        ((days_from_civil(2024, 1, 1) + day) * 86_400 + h * 3600 + m * 60) as u128 * 1000
+    }
+
+    #[test]
+    fn phase_test() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 3, 1), 11_017);
+        let session = TradingSession::new(&SessionConfig {
+            auction_secs: Some(600),
+            holidays: Some(vec![String::from("2024-01-02")]),
+            ..config("09:30", "16:00")
+        }).unwrap();
+        assert_eq!(session.phase(at(0, 9, 29)), SessionPhase::Closed);
+        assert_eq!(session.phase(at(0, 9, 30)), SessionPhase::Auction);
+        assert_eq!(session.phase(at(0, 9, 40)), SessionPhase::Open);
+        assert_eq!(session.phase(at(0, 16, 0)), SessionPhase::Closed);
+        // 假日和周末休市
+        assert_eq!(session.phase(at(1, 12, 0)), SessionPhase::Closed);
+        assert_eq!(session.phase(at(2, 12, 0)), SessionPhase::Open);
+        assert_eq!(session.phase(at(5, 12, 0)), SessionPhase::Closed);
+
+        // 跨夜交易，周五开盘的交易持续到周六早上
+        let overnight = TradingSession::new(&SessionConfig { utc_offset_minutes: Some(480), ..config("21:00", "02:30") }).unwrap();
+        assert_eq!(overnight.phase(at(4, 13, 0)), SessionPhase::Open);
+        assert_eq!(overnight.phase(at(4, 18, 0)), SessionPhase::Open);
+        assert_eq!(overnight.phase(at(4, 19, 0)), SessionPhase::Closed);
+        assert_eq!(overnight.phase(at(5, 13, 0)), SessionPhase::Closed);
+
+        assert!(TradingSession::new(&config("24:00", "16:00")).is_err());
+        assert!(TradingSession::new(&SessionConfig { weekdays: Some(vec![7]), ..config("09:30", "16:00") }).is_err());
+        assert!(TradingSession::new(&SessionConfig { holidays: Some(vec![String::from("2024/01/02")]), ..config("09:30", "16:00") }).is_err());
+    }
+
+    #[test]
+    fn state_test() {
+        let state = SessionState::new(Some(TradingSession::new(&SessionConfig { closed: Some(ClosedPolicy::Queue), ..config("09:30", "16:00") }).unwrap()));
+        assert!(state.accepts());
+        assert_eq!(state.update(at(0, 8, 0)), Some((SessionPhase::Open, SessionPhase::Closed)));
+        assert!(!state.accepts() && !state.rejects());
+        assert_eq!(state.update(at(0, 9, 0)), None);
+        assert_eq!(state.update(at(0, 10, 0)), Some((SessionPhase::Closed, SessionPhase::Open)));
+        assert_eq!(SessionState::default().update(at(0, 8, 0)), None);
+    }
+}
@@ -0,0 +1,247 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use bigdecimal::BigDecimal;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+use loom_core::market::MatchTrade;
+use loom_core::order::TradeSide;
+use loom_core::symbol::FeeSchedule;
+use loom_core::utils;
+
+use crate::archive::TradeArchive;
+use crate::registry::SymbolRegistry;
+
+/// 默认结算周期
+pub const DEFAULT_SETTLEMENT_PERIOD: Duration = Duration::from_secs(86_400);
+
+/// 结算文件表头
+const CSV_HEADER: &str = "period_start,period_end,account,symbol,trades,buy_qty,buy_notional,sell_qty,sell_notional,base_fee,quote_fee";
+
+/// 账户在一个结算周期内某交易对的成交汇总
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SettlementRecord {
+    /// 周期开始时间，包含
+    pub period_start: u128,
+    /// 周期结束时间，不包含
+    pub period_end: u128,
+    pub account: String,
+    pub symbol: String,
+    /// 成交笔数
+    pub trades: usize,
+    pub buy_qty: u64,
+    /// 买入成交金额
+    pub buy_notional: BigDecimal,
+    pub sell_qty: u64,
+    /// 卖出成交金额
+    pub sell_notional: BigDecimal,
+    /// 买入时从收到的基础资产中扣除的手续费
+    pub base_fee: BigDecimal,
+    /// 卖出时从收到的计价资产中扣除的手续费
+    pub quote_fee: BigDecimal,
+}
+
+impl SettlementRecord {
+    fn add(&mut self, side: TradeSide, trade: &MatchTrade, fee_rate: &BigDecimal) {
+        let notional = &trade.px * BigDecimal::from(trade.qty);
+        self.trades += 1;
+        match side {
+            TradeSide::BUY => {
+                self.buy_qty += trade.qty;
+                self.base_fee += BigDecimal::from(trade.qty) * fee_rate;
+                self.buy_notional += notional;
+            }
+            TradeSide::SELL => {
+                self.sell_qty += trade.qty;
+                self.quote_fee += &notional * fee_rate;
+                self.sell_notional += notional;
+            }
+        }
+    }
+
+    fn write_csv(&self, out: &mut String) {
+        let _ = writeln!(out, "{},{},{},{},{},{},{},{},{},{},{}",
+                         self.period_start, self.period_end, self.account, self.symbol, self.trades,
+                         self.buy_qty, self.buy_notional, self.sell_qty, self.sell_notional, self.base_fee, self.quote_fee);
+    }
+}
+
+/// 按账户、交易对和周期汇总成交，手续费与账本一致由收到资产的一方承担，未设置账户的一方不汇总
+pub fn aggregate(trades: &[MatchTrade], period: Duration, fees: &FeeSchedule) -> Vec<SettlementRecord> {
+    let period = period.as_millis().max(1);
+    let mut records: BTreeMap<(u128, String, String), SettlementRecord> = BTreeMap::new();
+    for trade in trades.iter().filter(|t| t.qty != 0) {
+        let (taker_side, maker_side) = match (trade.taker_side, trade.maker_side()) {
+            (Some(taker), Some(maker)) => (taker, maker),
+            _ => continue,
+        };
+        let start = trade.ts - trade.ts % period;
+        let legs = [
+            (&trade.taker_account, taker_side, &fees.taker),
+            (&trade.maker_account, maker_side, &fees.maker),
+        ];
+        for (account, side, rate) in legs {
+            let account = match account {
+                Some(account) => account,
+                None => continue,
+            };
+            records.entry((start, account.clone(), trade.symbol.to_string()))
+                .or_insert_with(|| SettlementRecord {
+                    period_start: start,
+                    period_end: start + period,
+                    account: account.clone(),
+                    symbol: trade.symbol.to_string(),
+                    ..Default::default()
+                })
+                .add(side, trade, rate);
+        }
+    }
+    records.into_values().collect()
+}
+
+/// 将结算记录写入dir目录下的CSV文件，返回文件路径
+pub fn write_csv(dir: &Path, start: u128, end: u128, records: &[SettlementRecord]) -> anyhow::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!("settlement-{}-{}.csv", start, end));
+    let mut out = String::with_capacity(128 * (records.len() + 1));
+    out.push_str(CSV_HEADER);
+    out.push('\n');
+    records.iter().for_each(|r| r.write_csv(&mut out));
+    // 先写临时文件再重命名，对账系统不会读到写了一半的文件
+    let tmp = path.with_extension("csv.tmp");
+    fs::write(&tmp, out)?;
+    fs::rename(&tmp, &path)?;
+    Ok(path)
+}
+
+/// 结算导出任务，从成交归档读取每个周期的成交，汇总后写入结算文件
+#[derive(Debug, Clone)]
+pub struct SettlementExporter {
+    archive: TradeArchive,
+    /// 交易对规格，用于查询手续费率
+    registry: SymbolRegistry,
+    /// 结算文件目录
+    dir: PathBuf,
+    period: Duration,
+}
+
+impl SettlementExporter {
+    pub fn new(archive: TradeArchive, registry: SymbolRegistry, dir: &Path, period: Duration) -> SettlementExporter {
+        SettlementExporter {
+            archive,
+            registry,
+            dir: dir.to_path_buf(),
+            period: period.max(Duration::from_secs(1)),
+        }
+    }
+
+    /// 导出[start, end)内所有已归档交易对的结算记录，重复导出同一周期时覆盖原文件
+    pub fn export(&self, start: u128, end: u128) -> anyhow::Result<(PathBuf, usize)> {
+        let mut records = Vec::new();
+        for symbol in self.archive.symbols()? {
+            let fees = self.registry.get(&symbol).map(|spec| spec.fees).unwrap_or_default();
+            let trades = self.archive.read(&symbol, start, end)?;
+            records.extend(aggregate(&trades, self.period, &fees));
+        }
+        records.sort_by(|a, b| (a.period_start, &a.account, &a.symbol).cmp(&(b.period_start, &b.account, &b.symbol)));
+        let path = write_csv(&self.dir, start, end, &records)?;
+        Ok((path, records.len()))
+    }
+
+    /// 最近一个已结束的周期
+    fn last_period(&self, now: u128) -> (u128, u128) {
+        let period = self.period.as_millis();
+        let end = now - now % period;
+        (end.saturating_sub(period), end)
+    }
+
+    /// 定时导出，每个周期结束后导出该周期，启动时导出最近一个已结束的周期
+    pub fn launch(self, mut ctx: broadcast::Receiver<bool>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(Duration::from_secs(1).min(self.period));
+            let mut exported = None;
+            loop {
+                tokio::select! {
+                    _ = ctx.recv() => break,
+                    _ = ticker.tick() => {
+                        let (start, end) = self.last_period(utils::now_ts());
+                        if exported == Some(end) {
+                            continue;
+                        }
+                        let exporter = self.clone();
+                        let result = tokio::task::spawn_blocking(move || exporter.export(start, end)).await;
+                        match result {
+                            Ok(Ok((path, records))) => {
+                                info!("SETTLEMENT EXPORTED: path={}, records={}", path.display(), records);
+                                exported = Some(end);
+                            }
+                            Ok(Err(e)) => warn!("SETTLEMENT EXPORT FAILED: start={}, end={}, err={}", start, end, e),
+                            Err(e) => warn!("SETTLEMENT EXPORT FAILED: start={}, end={}, err={}", start, end, e),
+                        }
+                    }
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    use bigdecimal::BigDecimal;
+
+    use loom_core::market::{MarketBook, MatchTrade, MatchTrades};
+    use loom_core::order::TradeSide;
+    use loom_core::symbol::FeeSchedule;
+    use loom_core::testing::{account_order, new_order};
+
+    use crate::settlement::{aggregate, write_csv};
+
+    fn trades() -> Vec<MatchTrade> {
+        let mut book = MarketBook::new("LOOM-USDT-SPOT");
+        let mut trades = MatchTrades::new();
+        book.try_match_into(account_order(1, "bob", TradeSide::SELL, 5, "100"), &mut trades);
+        book.try_match_into(account_order(2, "alice", TradeSide::BUY, 2, "100"), &mut trades);
+        book.try_match_into(new_order(3, TradeSide::BUY, 1, "100"), &mut trades);
+        book.try_match_into(account_order(4, "alice", TradeSide::BUY, 1, "100"), &mut trades);
+        let mut trades: Vec<_> = trades.into_iter().collect();
+        trades.iter_mut().enumerate().for_each(|(i, t)| t.ts = 1000 * i as u128);
+        trades
+    }
+
+    #[test]
+    fn aggregate_test() {
+        let fees = FeeSchedule { maker: BigDecimal::from_str("0.001").unwrap(), taker: BigDecimal::from_str("0.002").unwrap() };
+        let records = aggregate(&trades(), Duration::from_secs(2), &fees);
+        let summary: Vec<_> = records.iter()
+            .map(|r| (r.period_start, r.account.as_str(), r.trades, r.buy_qty, r.sell_qty))
+            .collect();
+        // 第三笔成交的taker未设置账户，只汇总maker
+        assert_eq!(summary, vec![(0, "alice", 1, 2, 0), (0, "bob", 2, 0, 3), (2000, "alice", 1, 1, 0), (2000, "bob", 1, 0, 1)]);
+        assert_eq!(records[0].buy_notional, BigDecimal::from(200));
+        assert_eq!(records[0].base_fee, BigDecimal::from_str("0.004").unwrap());
+        assert_eq!(records[1].quote_fee, BigDecimal::from_str("0.3").unwrap());
+        assert_eq!(records[2].period_end, 4000);
+    }
+
+    #[test]
+    fn write_csv_test() {
+        let dir = std::env::temp_dir().join(format!("loom-settlement-test-{}", std::process::id()));
+        let records = aggregate(&trades(), Duration::from_secs(2), &FeeSchedule::default());
+        let path = write_csv(&dir, 0, 4000, &records).unwrap();
+        let csv = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[0].starts_with("period_start,period_end,account"));
+        assert!(lines[1].starts_with("0,2000,alice,LOOM-USDT-SPOT,1,2,"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,227 @@
+use std::sync::mpsc as std_mpsc;
+use std::thread;
+
+use anyhow::anyhow;
+use log::info;
+use serde::{Deserialize, Serialize};
+use tokio::runtime::Handle;
+use tokio::sync::{broadcast, oneshot};
+
+/// 工作分片，独占一个线程并运行单线程tokio运行时，分配到该分片的交易员在其中调度
+#[derive(Debug)]
+pub struct Shard {
+    /// 分片编号
+    id: usize,
+    /// 运行时句柄
+    handle: Handle,
+    /// 分片内交易员的中断信号
+    ctx: broadcast::Sender<bool>,
+    /// 运行时停止信号
+    stop: Option<oneshot::Sender<()>>,
+    /// 运行时线程
+    thread: Option<thread::JoinHandle<()>>,
+    /// 分配到该分片的交易对
+    symbols: Vec<String>,
+}
+
+impl Shard {
+    fn spawn(id: usize) -> anyhow::Result<Shard> {
+        let (handle_tx, handle_rx) = std_mpsc::channel();
+        let (stop_tx, stop_rx) = oneshot::channel::<()>();
+        let thread = thread::Builder::new()
+            .name(format!("loom-shard-{}", id))
+            .spawn(move || {
+                let rt = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .expect("failed to build shard runtime");
+                handle_tx.send(rt.handle().clone()).unwrap();
+                // 驱动分片内的任务直到收到停止信号
+                let _ = rt.block_on(stop_rx);
+                info!("SHARD EXIT: {}", id);
+            })?;
+        let handle = handle_rx.recv().map_err(|_| anyhow!("shard runtime failed to start, shard={}", id))?;
+        let (ctx, _) = broadcast::channel(1);
+        Ok(Shard {
+            id,
+            handle,
+            ctx,
+            stop: Some(stop_tx),
+            thread: Some(thread),
+            symbols: Vec::new(),
+        })
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// 分片运行时句柄
+    pub fn handle(&self) -> &Handle {
+        &self.handle
+    }
+
+    /// 订阅分片中断信号
+    pub fn subscribe(&self) -> broadcast::Receiver<bool> {
+        self.ctx.subscribe()
+    }
+
+    pub fn symbols(&self) -> &[String] {
+        &self.symbols
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.stop.is_some()
+    }
+
+    /// 通知分片内的交易员退出
+    pub fn terminate(&self) {
+        let _ = self.ctx.send(true);
+    }
+
+    /// 停止分片运行时，需在分片内交易员退出后调用
+    pub async fn stop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+        if let Some(thread) = self.thread.take() {
+            let _ = tokio::task::spawn_blocking(move || thread.join()).await;
+        }
+    }
+}
+
+/// 分片统计
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShardStats {
+    /// 分片编号
+    pub shard: usize,
+    /// 分配到该分片的交易对
+    pub symbols: Vec<String>,
+    /// 分片内排队中的撮合请求数量
+    pub pending: usize,
+    /// 分片是否运行中
+    pub running: bool,
+}
+
+/// 分片池，按交易对一致性哈希将交易员分配到各分片
+#[derive(Debug)]
+pub struct ShardPool {
+    shards: Vec<Shard>,
+}
+
+impl ShardPool {
+    /// 创建count个分片
+    pub fn new(count: usize) -> anyhow::Result<ShardPool> {
+        let shards = (0..count.max(1))
+            .map(Shard::spawn)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(ShardPool { shards })
+    }
+
+    pub fn len(&self) -> usize {
+        self.shards.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shards.is_empty()
+    }
+
+    /// 交易对所属分片编号，分片数量变化时只有少量交易对需要迁移
+    pub fn shard_of(&self, symbol: &str) -> usize {
+        shard_of(symbol, self.shards.len())
+    }
+
+    /// 分配交易对到分片并返回该分片
+    pub fn assign(&mut self, symbol: &str) -> &Shard {
+        let idx = self.shard_of(symbol);
+        let shard = &mut self.shards[idx];
+        if !shard.symbols.iter().any(|s| s == symbol) {
+            shard.symbols.push(String::from(symbol));
+        }
+        shard
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&Shard> {
+        self.shards.get(idx)
+    }
+
+    pub fn get_mut(&mut self, idx: usize) -> Option<&mut Shard> {
+        self.shards.get_mut(idx)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=&Shard> {
+        self.shards.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item=&mut Shard> {
+        self.shards.iter_mut()
+    }
+}
+
+/// 交易对在count个分片中的编号
+pub fn shard_of(symbol: &str, count: usize) -> usize {
+    jump_hash(fnv1a(symbol.as_bytes()), count.max(1) as u32) as usize
+}
+
+/// FNV-1a哈希，结果与运行环境无关
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in bytes {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// Jump一致性哈希
+fn jump_hash(mut key: u64, buckets: u32) -> u32 {
+    let mut b: i64 = -1;
+    let mut j: i64 = 0;
+    while j < buckets as i64 {
+        b = j;
+        key = key.wrapping_mul(2862933555777941757).wrapping_add(1);
+        j = ((b + 1) as f64 * ((1u64 << 31) as f64 / ((key >> 33) + 1) as f64)) as i64;
+    }
+    b as u32
+}
+
+#[cfg(test)]
+mod test {
+    use crate::shard::{shard_of, ShardPool};
+
+    #[test]
+    fn consistent_shard_test() {
+        let symbols: Vec<String> = (0..1000).map(|i| format!("S{}-USDT-SPOT", i)).collect();
+        let mut counts = [0; 8];
+        let mut moved = 0;
+        for symbol in &symbols {
+            let before = shard_of(symbol, 8);
+            let after = shard_of(symbol, 9);
+            counts[before] += 1;
+            // 扩容时交易对只会迁移到新分片
+            if before != after {
+                assert_eq!(after, 8);
+                moved += 1;
+            }
+        }
+        assert!(counts.iter().all(|c| *c > 60));
+        assert!(moved < 200);
+        assert_eq!(shard_of("LOOM-USDT-SPOT", 1), 0);
+    }
+
+    #[tokio::test]
+    async fn shard_runtime_test() {
+        let mut pool = ShardPool::new(2).unwrap();
+        let shard = pool.assign("LOOM-USDT-SPOT");
+        let name = shard.handle()
+            .spawn(async { std::thread::current().name().map(String::from) })
+            .await
+            .unwrap();
+        assert!(name.unwrap().starts_with("loom-shard-"));
+        for shard in pool.iter_mut() {
+            shard.terminate();
+            shard.stop().await;
+            assert!(!shard.is_running());
+        }
+    }
+}
@@ -0,0 +1,29 @@
+use std::sync::{Arc, RwLock};
+
+use loom_core::snapshot::BookSnapshot;
+
+/// 交易员发布的最新市场快照，读取方只克隆引用，序列化在撮合线程之外进行
+#[derive(Debug, Clone)]
+pub struct SnapshotCell(Arc<RwLock<Arc<BookSnapshot>>>);
+
+impl SnapshotCell {
+    pub fn new(symbol: &str) -> SnapshotCell {
+        SnapshotCell(Arc::new(RwLock::new(Arc::new(BookSnapshot::empty(symbol)))))
+    }
+
+    /// 读取最新快照
+    pub fn load(&self) -> Arc<BookSnapshot> {
+        Arc::clone(&self.0.read().unwrap())
+    }
+
+    /// 替换为新快照，写锁只在交换引用时持有
+    pub fn store(&self, snapshot: BookSnapshot) {
+        let snapshot = Arc::new(snapshot);
+        *self.0.write().unwrap() = snapshot;
+    }
+
+    /// 最新快照的市场版本号
+    pub fn version(&self) -> u64 {
+        self.0.read().unwrap().version
+    }
+}
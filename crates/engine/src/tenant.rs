@@ -0,0 +1,29 @@
+/// 租户与名称之间的分隔符，租户的交易对和账户在引擎内表示为"租户.名称"
+pub const TENANT_SEPARATOR: char = '.';
+
+/// 将租户内的名称转换为引擎内的名称
+pub fn qualify(tenant: &str, name: &str) -> String {
+    format!("{}{}{}", tenant, TENANT_SEPARATOR, name)
+}
+
+/// 拆分引擎内的名称，返回租户和租户内的名称，不属于租户时租户为None
+pub fn split(name: &str) -> (Option<&str>, &str) {
+    match name.split_once(TENANT_SEPARATOR) {
+        Some((tenant, name)) if !tenant.is_empty() => (Some(tenant), name),
+        _ => (None, name),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::tenant::{qualify, split};
+
+    #[test]
+    fn tenant_test() {
+        let symbol = qualify("acme", "LOOM-USDT-SPOT");
+        assert_eq!(symbol, "acme.LOOM-USDT-SPOT");
+        assert_eq!(split(&symbol), (Some("acme"), "LOOM-USDT-SPOT"));
+        assert_eq!(split("LOOM-USDT-SPOT"), (None, "LOOM-USDT-SPOT"));
+        assert_eq!(split(".LOOM-USDT-SPOT"), (None, ".LOOM-USDT-SPOT"));
+    }
+}
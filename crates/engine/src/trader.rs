@@ -1,87 +1,1169 @@
+use std::fmt::{Display, Formatter};
+use std::panic::AssertUnwindSafe;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use log::{debug, info};
+use futures_util::FutureExt;
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
 use tokio::{
     select,
     sync::{
-        mpsc::{self},
+        mpsc::{self, error::{SendError, TrySendError}},
     },
     task::JoinHandle,
 };
+use tokio::runtime::Handle;
+use tracing::{debug_span, Instrument};
 use tokio::sync::{broadcast, Mutex, oneshot};
 
 use loom_core::{
-    market::{MarketBook, MatchTrade},
+    market::{CancelResult, MarketBook, MatchTrade, MatchTrades, RejectCode, UncrossPolicy},
     order::Order,
 };
-use loom_core::order::OrderAction;
+use loom_core::order::{OrderAction, OrderType, TradeSide};
+use loom_core::snapshot::{BookImage, BookSnapshot, QueuePosition, StateHash};
+use loom_core::clock::{Clock, SystemClock};
+use loom_core::id::SnowflakeIds;
+use loom_core::symbol::{SymbolId, SymbolSpec};
 
+use crate::audit::AuditLog;
+use crate::collar::{CollarAction, PriceCollar};
 use crate::consumer::TradeConsumer;
+use crate::dump::{self, EngineDump, TraderDump};
+use crate::ledger::Ledger;
+use crate::limits::AccountLimiter;
+use crate::mmp::MarketMakerProtection;
+use crate::replication::Replication;
+use crate::logging;
+use crate::metrics::{LatencyHistogram, QueueWait};
+use crate::ring::{ring_buffer, RingConsumer, RingProducer};
+use crate::session::{SessionPhase, SessionState, TradingSession};
+use crate::snapshot::SnapshotCell;
+use crate::warmup::RecoveryPolicy;
 
 pub type TraderMatchRequest = (Order, oneshot::Sender<Vec<MatchTrade>>);
 
+/// 默认撮合请求队列容量
+pub const DEFAULT_CHANNEL_CAPACITY: usize = 16;
+
+/// 独占线程模式默认环形缓冲区容量
+pub const DEFAULT_RING_CAPACITY: usize = 4096;
+
+/// 默认快照发布间隔
+pub const DEFAULT_SNAPSHOT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// 关闭时处理队列中剩余请求的默认期限
+pub const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 空闲时检查GTD订单到期的间隔
+const EXPIRY_INTERVAL: Duration = Duration::from_millis(10);
+
+/// 环形队列已满时让出的次数，超过后改为休眠等待
+const RING_FULL_SPINS: u32 = 64;
+
+/// 环形队列持续已满时每次休眠的时间
+const RING_FULL_SLEEP: Duration = Duration::from_millis(1);
+
+/// 交易员运行模式
+#[derive(Debug, Clone, Default)]
+pub enum TraderMode {
+    /// 运行在tokio运行时中，通过mpsc通道接收撮合请求
+    #[default]
+    Tokio,
+    /// 独占一个操作系统线程，通过有界环形缓冲区接收撮合请求，可绑定到指定CPU核心
+    Native {
+        /// 绑定的CPU核心
+        core: Option<usize>,
+    },
+}
+
+/// 交易员存活状态，撮合循环退出或崩溃后变为false
+#[derive(Debug, Clone, Default)]
+pub struct Liveness(Arc<AtomicBool>);
+
+impl Liveness {
+    pub fn is_alive(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+
+    /// 标记为存活，返回的守卫在撮合循环结束（包括panic）时清除标记
+    fn guard(&self) -> LivenessGuard {
+        self.0.store(true, Ordering::Release);
+        LivenessGuard(self.clone())
+    }
+}
+
+struct LivenessGuard(Liveness);
+
+impl Drop for LivenessGuard {
+    fn drop(&mut self) {
+        self.0.0.store(false, Ordering::Release);
+    }
+}
+
+/// 暂停方式
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub enum PauseMode {
+    /// 新请求进入队列，恢复后按序处理
+    #[default]
+    Buffer,
+    /// 拒绝新请求，队列中已有的请求恢复后处理
+    Reject,
+}
+
+/// 停牌时对挂单的处理方式
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub enum HaltOrders {
+    /// 保留挂单，恢复后继续撮合
+    #[default]
+    Freeze,
+    /// 撤销所有挂单，推送撤单结果并清理缓存
+    Cancel,
+}
+
+/// 交易对市场状态
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub enum MarketStatus {
+    /// 连续撮合
+    Open,
+    /// 暂停或恢复中，不处理新请求
+    Halted,
+    /// 开盘集合竞价，只挂单不撮合
+    Auction,
+    /// 交易时段之外
+    Closed,
+}
+
+/// 交易员暂停状态，暂停期间交易员不处理撮合请求，仍响应控制请求
+#[derive(Debug, Clone, Default)]
+pub struct PauseState(Arc<std::sync::atomic::AtomicU8>);
+
+impl PauseState {
+    pub fn mode(&self) -> Option<PauseMode> {
+        match self.0.load(Ordering::Acquire) {
+            1 => Some(PauseMode::Buffer),
+            2 => Some(PauseMode::Reject),
+            _ => None,
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.mode().is_some()
+    }
+
+    /// 是否拒绝新请求
+    pub fn rejects(&self) -> bool {
+        self.mode() == Some(PauseMode::Reject)
+    }
+
+    pub(crate) fn pause(&self, mode: PauseMode) {
+        let value = match mode {
+            PauseMode::Buffer => 1,
+            PauseMode::Reject => 2,
+        };
+        self.0.store(value, Ordering::Release);
+    }
+
+    /// 恢复处理，返回之前是否处于暂停状态
+    pub(crate) fn resume(&self) -> bool {
+        self.0.swap(0, Ordering::AcqRel) != 0
+    }
+}
+
+/// 同步等待的撮合结果
+#[derive(Debug, Clone, Default)]
+pub struct MatchReply {
+    /// 撮合产生的成交
+    pub trades: Vec<MatchTrade>,
+    /// 撤单结果，其他请求为空
+    pub cancel: Option<CancelResult>,
+}
+
+/// 等待同步撮合结果的请求，按订单号索引，交易员处理完对应请求后回传成交
+#[derive(Debug, Clone, Default)]
+pub struct TradeWaiters(Arc<TradeWaitersInner>);
+
+#[derive(Debug, Default)]
+struct TradeWaitersInner {
+    /// 等待数量，为0时交易员无需加锁
+    count: AtomicUsize,
+    waiters: std::sync::Mutex<HashMap<u64, oneshot::Sender<MatchReply>>>,
+}
+
+impl TradeWaiters {
+    /// 注册等待，同一订单号重复注册时之前的等待会收到通道关闭
+    pub fn register(&self, oid: u64) -> oneshot::Receiver<MatchReply> {
+        let (tx, rx) = oneshot::channel();
+        let mut waiters = self.0.waiters.lock().unwrap();
+        waiters.insert(oid, tx);
+        self.0.count.store(waiters.len(), Ordering::Release);
+        rx
+    }
+
+    /// 取消等待
+    pub fn cancel(&self, oid: u64) {
+        let mut waiters = self.0.waiters.lock().unwrap();
+        waiters.remove(&oid);
+        self.0.count.store(waiters.len(), Ordering::Release);
+    }
+
+    fn notify(&self, oid: u64, trades: &[MatchTrade], cancel: Option<CancelResult>) {
+        if self.0.count.load(Ordering::Acquire) == 0 {
+            return;
+        }
+        let waiter = {
+            let mut waiters = self.0.waiters.lock().unwrap();
+            let waiter = waiters.remove(&oid);
+            self.0.count.store(waiters.len(), Ordering::Release);
+            waiter
+        };
+        if let Some(waiter) = waiter {
+            let _ = waiter.send(MatchReply { trades: trades.to_vec(), cancel });
+        }
+    }
+}
+
+/// 撮合请求队列已满时的处理策略
+#[derive(Eq, PartialEq, Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum Backpressure {
+    /// 等待队列空出位置
+    #[default]
+    Block,
+    /// 直接拒绝请求
+    Reject,
+    /// 拒绝下单请求，撤单请求仍然等待，保证撤单最后被丢弃
+    Shed,
+}
+
+impl Backpressure {
+    /// 队列已满时是否丢弃该请求
+    fn should_shed(&self, order: &Order) -> bool {
+        match self {
+            Backpressure::Block => false,
+            Backpressure::Reject => true,
+            Backpressure::Shed => order.action == OrderAction::PLACE,
+        }
+    }
+}
+
+/// 交易员配置
+#[derive(Debug, Clone)]
+pub struct TraderOptions {
+    /// 运行模式
+    pub mode: TraderMode,
+    /// 撮合请求队列容量
+    pub capacity: usize,
+    /// 队列已满时的处理策略
+    pub backpressure: Backpressure,
+    /// 市场快照发布间隔，订单簿无变化时不发布
+    pub snapshot_interval: Duration,
+    /// 撮合崩溃时的转储目录
+    pub dump_dir: PathBuf,
+    /// 账户限制器，订单离开订单簿时释放账户额度
+    pub accounts: Option<Arc<AccountLimiter>>,
+    /// 账户余额账本，成交和订单离开订单簿时结算
+    pub ledger: Option<Arc<Ledger>>,
+    /// 做市商保护，账户挂单成交过快时撤销其挂单
+    pub mmp: Option<Arc<MarketMakerProtection>>,
+    /// 交易对规格，未配置时使用默认规格
+    pub spec: Option<SymbolSpec>,
+    /// 成交ID高水位，新成交从该值之后分配
+    pub trade_id: u64,
+    /// 分布式成交ID生成器，未配置时连续分配
+    pub snowflake: Option<Arc<SnowflakeIds>>,
+    /// 撮合时钟，回放和测试时使用可控时钟
+    pub clock: Arc<dyn Clock>,
+    /// 主备复制状态，备机不推送成交
+    pub replication: Option<Arc<Replication>>,
+    /// 关闭时处理队列中剩余请求的期限，超时后丢弃
+    pub drain_timeout: Duration,
+    /// 每处理N个请求检查一次市场不变式，用于调试，不满足时暂停交易对
+    pub verify_every: Option<u64>,
+    /// 恢复镜像后订单簿交叉时的处理策略
+    pub uncross: UncrossPolicy,
+    /// 交易时段，未配置时全天连续撮合
+    pub session: Option<TradingSession>,
+    /// 价格熔断，未配置时不检查成交价偏离
+    pub collar: Option<PriceCollar>,
+    /// 审计日志，记录撮合请求和撮合结果
+    pub audit: Option<Arc<std::sync::Mutex<AuditLog>>>,
+    /// 撮合请求排队时间上限，超过后拒绝新订单
+    pub max_queue_wait: Option<Duration>,
+    /// 启动时的恢复方式
+    pub recovery: RecoveryPolicy,
+}
+
+impl Default for TraderOptions {
+    fn default() -> Self {
+        TraderOptions {
+            mode: TraderMode::Tokio,
+            capacity: DEFAULT_CHANNEL_CAPACITY,
+            backpressure: Backpressure::Block,
+            snapshot_interval: DEFAULT_SNAPSHOT_INTERVAL,
+            dump_dir: dump::default_dump_dir(),
+            accounts: None,
+            ledger: None,
+            mmp: None,
+            spec: None,
+            trade_id: 0,
+            snowflake: None,
+            clock: Arc::new(SystemClock),
+            replication: None,
+            drain_timeout: DEFAULT_DRAIN_TIMEOUT,
+            verify_every: None,
+            uncross: UncrossPolicy::default(),
+            session: None,
+            max_queue_wait: None,
+            collar: None,
+            audit: None,
+            recovery: RecoveryPolicy::default(),
+        }
+    }
+}
+
+impl TraderOptions {
+    pub fn with_mode(mut self, mode: TraderMode) -> TraderOptions {
+        self.mode = mode;
+        self
+    }
+
+    pub fn with_capacity(mut self, capacity: usize) -> TraderOptions {
+        self.capacity = capacity;
+        self
+    }
+
+    pub fn with_backpressure(mut self, backpressure: Backpressure) -> TraderOptions {
+        self.backpressure = backpressure;
+        self
+    }
+
+    pub fn with_max_queue_wait(mut self, max_queue_wait: Duration) -> TraderOptions {
+        self.max_queue_wait = Some(max_queue_wait);
+        self
+    }
+
+    pub fn with_snapshot_interval(mut self, snapshot_interval: Duration) -> TraderOptions {
+        self.snapshot_interval = snapshot_interval;
+        self
+    }
+
+    pub fn with_dump_dir(mut self, dump_dir: PathBuf) -> TraderOptions {
+        self.dump_dir = dump_dir;
+        self
+    }
+
+    pub fn with_accounts(mut self, accounts: Arc<AccountLimiter>) -> TraderOptions {
+        self.accounts = Some(accounts);
+        self
+    }
+
+    pub fn with_ledger(mut self, ledger: Arc<Ledger>) -> TraderOptions {
+        self.ledger = Some(ledger);
+        self
+    }
+
+    pub fn with_mmp(mut self, mmp: Arc<MarketMakerProtection>) -> TraderOptions {
+        self.mmp = Some(mmp);
+        self
+    }
+
+    pub fn with_spec(mut self, spec: SymbolSpec) -> TraderOptions {
+        self.spec = Some(spec);
+        self
+    }
+
+    pub fn with_trade_id(mut self, trade_id: u64) -> TraderOptions {
+        self.trade_id = trade_id;
+        self
+    }
+
+    pub fn with_snowflake(mut self, snowflake: Arc<SnowflakeIds>) -> TraderOptions {
+        self.snowflake = Some(snowflake);
+        self
+    }
+
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> TraderOptions {
+        self.clock = clock;
+        self
+    }
+
+    pub fn with_replication(mut self, replication: Arc<Replication>) -> TraderOptions {
+        self.replication = Some(replication);
+        self
+    }
+
+    pub fn with_drain_timeout(mut self, drain_timeout: Duration) -> TraderOptions {
+        self.drain_timeout = drain_timeout;
+        self
+    }
+
+    pub fn with_verify_every(mut self, verify_every: u64) -> TraderOptions {
+        self.verify_every = Some(verify_every);
+        self
+    }
+
+    pub fn with_uncross(mut self, uncross: UncrossPolicy) -> TraderOptions {
+        self.uncross = uncross;
+        self
+    }
+
+    pub fn with_recovery(mut self, recovery: RecoveryPolicy) -> TraderOptions {
+        self.recovery = recovery;
+        self
+    }
+
+    pub fn with_session(mut self, session: TradingSession) -> TraderOptions {
+        self.session = Some(session);
+        self
+    }
+
+    pub fn with_collar(mut self, collar: PriceCollar) -> TraderOptions {
+        self.collar = Some(collar);
+        self
+    }
+
+    pub fn with_audit(mut self, audit: AuditLog) -> TraderOptions {
+        self.audit = Some(Arc::new(std::sync::Mutex::new(audit)));
+        self
+    }
+}
+
+/// 撮合请求队列已满且按策略丢弃时返回的错误，携带被拒绝的订单
+#[derive(Debug)]
+pub struct QueueFull {
+    pub order: Order,
+}
+
+impl Display for QueueFull {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "trader queue full, symbol={}, id={}", self.order.symbol, self.order.id)
+    }
+}
+
+impl std::error::Error for QueueFull {}
+
+/// 交易员已停止时返回的错误，携带未进入队列的订单
+#[derive(Debug)]
+pub struct TraderStopped {
+    pub order: Order,
+}
+
+impl Display for TraderStopped {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "trader stopped, symbol={}, id={}", self.order.symbol, self.order.id)
+    }
+}
+
+impl std::error::Error for TraderStopped {}
+
+/// 发送撮合请求失败时未进入队列的订单
+pub fn unsent(e: &anyhow::Error) -> Option<&Order> {
+    e.downcast_ref::<QueueFull>().map(|e| &e.order)
+        .or_else(|| e.downcast_ref::<TraderStopped>().map(|e| &e.order))
+}
+
+/// 队列中的撮合请求，记录入队时间用于统计排队时间
+#[derive(Debug)]
+struct Queued {
+    order: Order,
+    enqueued: Instant,
+}
+
+impl Queued {
+    /// 出队并记录排队时间
+    fn dequeue(self, queue_wait: &QueueWait) -> (Order, Duration) {
+        let wait = self.enqueued.elapsed();
+        queue_wait.record(wait);
+        (self.order, wait)
+    }
+}
+
+/// 撮合请求队列
+#[derive(Debug, Clone)]
+enum OrderQueue {
+    Channel(mpsc::Sender<Queued>),
+    /// 环形缓冲区只允许单个生产端，多个提交方通过互斥锁串行写入，提交方已由序列号分配器串行化，锁通常无竞争
+    Ring(Arc<std::sync::Mutex<RingProducer<Queued>>>),
+}
+
+/// 撮合请求发送器
+#[derive(Debug, Clone)]
+pub struct OrderSender {
+    queue: OrderQueue,
+    backpressure: Backpressure,
+}
+
+impl OrderSender {
+    /// 发送撮合请求，队列已满时按背压策略等待或返回`QueueFull`，交易员已停止时返回`TraderStopped`
+    pub async fn send(&self, order: Order) -> anyhow::Result<()> {
+        let queued = Queued { order, enqueued: Instant::now() };
+        match &self.queue {
+            OrderQueue::Channel(sender) => {
+                match sender.try_send(queued) {
+                    Ok(()) => {}
+                    Err(TrySendError::Full(queued)) => {
+                        if self.backpressure.should_shed(&queued.order) {
+                            return Err(QueueFull { order: queued.order }.into());
+                        }
+                        if let Err(SendError(queued)) = sender.send(queued).await {
+                            return Err(TraderStopped { order: queued.order }.into());
+                        }
+                    }
+                    Err(TrySendError::Closed(queued)) => {
+                        return Err(TraderStopped { order: queued.order }.into());
+                    }
+                }
+            }
+            OrderQueue::Ring(producer) => {
+                let mut queued = queued;
+                let mut attempts = 0u32;
+                loop {
+                    let pushed = {
+                        let mut producer = producer.lock().unwrap();
+                        if producer.is_closed() {
+                            return Err(TraderStopped { order: queued.order }.into());
+                        }
+                        producer.try_push(queued)
+                    };
+                    match pushed {
+                        Ok(()) => break,
+                        Err(back) => {
+                            if self.backpressure.should_shed(&back.order) {
+                                return Err(QueueFull { order: back.order }.into());
+                            }
+                            queued = back;
+                            // 调用方持有分配器的锁，队列持续已满时先短暂让出再休眠，避免空转占满CPU
+                            if attempts < RING_FULL_SPINS {
+                                tokio::task::yield_now().await;
+                            } else {
+                                tokio::time::sleep(RING_FULL_SLEEP).await;
+                            }
+                            attempts = attempts.saturating_add(1);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn backpressure(&self) -> Backpressure {
+        self.backpressure
+    }
+
+    /// 队列中等待处理的撮合请求数量
+    pub fn pending(&self) -> usize {
+        match &self.queue {
+            OrderQueue::Channel(sender) => sender.max_capacity() - sender.capacity(),
+            OrderQueue::Ring(producer) => producer.lock().unwrap().len(),
+        }
+    }
+}
+
+/// 撮合请求接收器
+#[derive(Debug)]
+enum OrderReceiver {
+    Channel(mpsc::Receiver<Queued>),
+    Ring(RingConsumer<Queued>),
+}
+
+/// 撮合结果的账户结算
+#[derive(Debug, Clone, Default)]
+struct Settlement {
+    /// 账户限制器，已完成的订单释放挂单额度
+    accounts: Option<Arc<AccountLimiter>>,
+    /// 账户余额账本
+    ledger: Option<Arc<Ledger>>,
+    /// 做市商保护
+    mmp: Option<Arc<MarketMakerProtection>>,
+    /// 价格熔断，按成交更新滚动统计
+    collar: Option<Arc<std::sync::Mutex<PriceCollar>>>,
+    /// 交易员暂停状态，价格熔断以停牌方式处理时暂停交易对
+    pause: PauseState,
+    /// 审计日志
+    audit: Option<Arc<std::sync::Mutex<AuditLog>>>,
+}
+
+impl Settlement {
+    fn settle(&self, trades: &[MatchTrade]) {
+        if let Some(accounts) = &self.accounts {
+            accounts.settle(trades);
+        }
+        if let Some(ledger) = &self.ledger {
+            ledger.settle(trades);
+        }
+        if let Some(collar) = &self.collar {
+            collar.lock().unwrap().record(trades);
+        }
+        if let Some(audit) = &self.audit {
+            if let Err(e) = audit.lock().unwrap().append_trades(trades) {
+                error!("AUDIT WRITE FAILED: trades={}, err={}", trades.len(), e);
+            }
+        }
+    }
+
+    /// 记录撮合请求，审计日志写入失败不影响撮合
+    fn audit(&self, order: &Order) {
+        if let Some(audit) = &self.audit {
+            if let Err(e) = audit.lock().unwrap().append_order(order) {
+                error!("AUDIT WRITE FAILED: symbol={}, oid={}, err={}", &order.symbol, order.id, e);
+            }
+        }
+    }
+
+    /// 订单可能的成交价偏离近期成交均价过多时拒绝订单，按配置暂停交易对，返回订单是否已被拒绝
+    fn collar(&self, book: &mut MarketBook, order: &Order, trades: &mut MatchTrades) -> bool {
+        let Some(collar) = &self.collar else {
+            return false;
+        };
+        let Some(px) = book.sweep_px(order) else {
+            return false;
+        };
+        let mut collar = collar.lock().unwrap();
+        let Some((average, deviation)) = collar.breach(&px, book.now_ts()) else {
+            return false;
+        };
+        book.reject_into(order, RejectCode::PRICE_COLLAR, trades);
+        match collar.action() {
+            CollarAction::Reject => {
+                warn!("PRICE COLLAR REJECTED: symbol={}, oid={}, px={}, average={}, deviation={}%", &book.symbol, order.id, px, average, deviation);
+            }
+            CollarAction::Halt => {
+                error!("PRICE COLLAR BREACHED, SYMBOL HALTED: symbol={}, oid={}, px={}, average={}, deviation={}%", &book.symbol, order.id, px, average, deviation);
+                self.pause.pause(PauseMode::Reject);
+            }
+        }
+        true
+    }
+
+    /// 市价买单按实时订单簿重新计算所需资金，预留不足且可用余额不够时拒绝订单，返回订单是否已被拒绝
+    fn fund(&self, book: &mut MarketBook, order: &Order, trades: &mut MatchTrades) -> bool {
+        let Some(ledger) = &self.ledger else {
+            return false;
+        };
+        if order.side != TradeSide::BUY || order.ord_type != OrderType::MARKET {
+            return false;
+        }
+        let Some(px) = book.sweep_px(order) else {
+            return false;
+        };
+        if ledger.reprice(order, &px) {
+            return false;
+        }
+        book.reject_into(order, RejectCode::INSUFFICIENT_FUNDS, trades);
+        true
+    }
+
+    /// 统计挂单成交，返回新触发做市商保护的账户
+    fn protect(&self, trades: &[MatchTrade]) -> Vec<String> {
+        match &self.mmp {
+            Some(mmp) => mmp.record(trades),
+            None => Vec::new(),
+        }
+    }
+}
+
+/// 交易员控制请求，在撮合循环中与撮合请求串行处理
+#[derive(Debug)]
+pub enum TraderControl {
+    /// 转储完整订单簿
+    Dump(oneshot::Sender<TraderDump>),
+    /// 计算市场状态哈希
+    StateHash(oneshot::Sender<StateHash>),
+    /// 生成市场镜像
+    Image(oneshot::Sender<BookImage>),
+    /// 用镜像替换市场状态
+    Restore(BookImage, oneshot::Sender<anyhow::Result<()>>),
+    /// 恢复时批量挂单，返回需要逐个撮合的剩余订单
+    Load(Vec<Order>, oneshot::Sender<Vec<Order>>),
+    /// 唤醒撮合循环，恢复暂停后发送
+    Wake,
+    /// 替换交易对规格
+    UpdateSpec(SymbolSpec, oneshot::Sender<anyhow::Result<()>>),
+    /// 检查市场不变式
+    Verify(oneshot::Sender<anyhow::Result<()>>),
+    /// 撤销所有挂单并推送撤单结果，返回撤销的数量
+    CancelAll(oneshot::Sender<anyhow::Result<usize>>),
+    /// 查询挂单的排队位置，指定账户时只返回该账户的挂单
+    QueuePosition(u64, Option<String>, oneshot::Sender<Option<QueuePosition>>),
+}
+
 /// 市场交易员
 #[derive(Debug)]
 pub struct Trader {
     /// 交易对
     symbol: String,
+    /// 交易对编号
+    symbol_id: SymbolId,
     /// 交易对市场
     book: Arc<Mutex<MarketBook>>,
     /// 撮合请求输入器
-    req_sender: mpsc::Sender<Order>,
-    /// 撮合结果输出器
-    req_receiver: Arc<Mutex<mpsc::Receiver<Order>>>,
+    req_sender: OrderSender,
+    /// 撮合请求接收器，启动时取出
+    req_receiver: std::sync::Mutex<Option<OrderReceiver>>,
     /// 消费器
     consumer: Arc<Mutex<TradeConsumer>>,
+    /// 运行模式
+    mode: TraderMode,
+    /// 最新市场快照
+    snapshot: SnapshotCell,
+    /// 快照发布间隔
+    snapshot_interval: Duration,
+    /// 存活状态
+    liveness: Liveness,
+    /// 从请求出队到成交推送完成的延迟
+    latency: Arc<LatencyHistogram>,
+    /// 请求在队列中的等待时间
+    queue_wait: Arc<QueueWait>,
+    /// 撮合请求处理失败次数，主要为成交推送失败
+    failures: Arc<AtomicU64>,
+    /// 控制请求发送器
+    control: mpsc::UnboundedSender<TraderControl>,
+    /// 控制请求接收器，启动时取出
+    control_receiver: std::sync::Mutex<Option<mpsc::UnboundedReceiver<TraderControl>>>,
+    /// 撮合崩溃时的转储目录
+    dump_dir: PathBuf,
+    /// 账户结算
+    settlement: Settlement,
+    /// 等待同步撮合结果的请求
+    waiters: TradeWaiters,
+    /// 主备复制状态
+    replication: Option<Arc<Replication>>,
+    /// 关闭时处理队列中剩余请求的期限
+    drain_timeout: Duration,
+    /// 暂停状态
+    pause: PauseState,
+    /// 每处理N个请求检查一次市场不变式
+    verify_every: Option<u64>,
+    /// 订单簿交叉时的处理策略
+    uncross: UncrossPolicy,
+    /// 交易时段阶段
+    session: SessionState,
 }
 
 impl Trader {
     /// 新建交易员
-    pub fn new(symbol: &str, consumer: TradeConsumer) -> Trader {
-        let (sender, receiver) = mpsc::channel(16);
+    pub fn new(symbol: &str, symbol_id: SymbolId, consumer: TradeConsumer) -> Trader {
+        Self::with_options(symbol, symbol_id, consumer, TraderOptions::default())
+    }
+
+    /// 按配置新建交易员
+    pub fn with_options(symbol: &str, symbol_id: SymbolId, consumer: TradeConsumer, options: TraderOptions) -> Trader {
+        let capacity = options.capacity.max(1);
+        let (queue, receiver) = match &options.mode {
+            TraderMode::Tokio => {
+                let (sender, receiver) = mpsc::channel(capacity);
+                (OrderQueue::Channel(sender), OrderReceiver::Channel(receiver))
+            }
+            TraderMode::Native { .. } => {
+                let (producer, consumer) = ring_buffer(capacity);
+                (OrderQueue::Ring(Arc::new(std::sync::Mutex::new(producer))), OrderReceiver::Ring(consumer))
+            }
+        };
+        let (control, control_receiver) = mpsc::unbounded_channel();
+        let spec = options.spec.unwrap_or_else(|| SymbolSpec::new(symbol));
+        let pause = PauseState::default();
+        let mut book = MarketBook::with_spec(spec).with_trade_id(options.trade_id).with_clock(options.clock);
+        if let Some(snowflake) = options.snowflake {
+            book = book.with_snowflake(snowflake);
+        }
+        let settlement = Settlement {
+            accounts: options.accounts,
+            ledger: options.ledger,
+            mmp: options.mmp,
+            collar: options.collar.map(|collar| Arc::new(std::sync::Mutex::new(collar))),
+            pause: pause.clone(),
+            audit: options.audit,
+        };
         Trader {
             symbol: String::from(symbol),
-            book: Arc::new(Mutex::new(MarketBook::new(symbol))),
-            req_sender: sender,
-            req_receiver: Arc::new(Mutex::new(receiver)),
+            symbol_id,
+            book: Arc::new(Mutex::new(book)),
+            req_sender: OrderSender { queue, backpressure: options.backpressure },
+            req_receiver: std::sync::Mutex::new(Some(receiver)),
             consumer: Arc::new(Mutex::new(consumer)),
+            mode: options.mode,
+            snapshot: SnapshotCell::new(symbol),
+            snapshot_interval: options.snapshot_interval,
+            liveness: Liveness::default(),
+            latency: Arc::new(LatencyHistogram::new()),
+            queue_wait: Arc::new(QueueWait::new(options.max_queue_wait)),
+            failures: Arc::new(AtomicU64::new(0)),
+            control,
+            control_receiver: std::sync::Mutex::new(Some(control_receiver)),
+            dump_dir: options.dump_dir,
+            settlement,
+            waiters: TradeWaiters::default(),
+            replication: options.replication,
+            drain_timeout: options.drain_timeout,
+            pause,
+            verify_every: options.verify_every,
+            uncross: options.uncross,
+            session: SessionState::new(options.session),
         }
     }
 
     /// 开始交易，返回协程句柄
-    pub fn launch(&self, mut ctx: broadcast::Receiver<bool>) -> JoinHandle<()> {
+    pub fn launch(&self, ctx: broadcast::Receiver<bool>) -> JoinHandle<()> {
+        self.launch_on(&Handle::current(), ctx)
+    }
+
+    /// 在指定运行时中开始交易，独占线程模式只在该运行时中等待线程退出
+    pub fn launch_on(&self, runtime: &Handle, ctx: broadcast::Receiver<bool>) -> JoinHandle<()> {
+        let receiver = self.req_receiver.lock().unwrap().take().expect("trader already launched");
+        let control = self.control_receiver.lock().unwrap().take().expect("trader already launched");
+        let handler = match (receiver, &self.mode) {
+            (OrderReceiver::Ring(receiver), TraderMode::Native { core, .. }) => {
+                self.launch_native(runtime, receiver, control, *core, ctx)
+            }
+            (OrderReceiver::Channel(receiver), _) => self.launch_tokio(runtime, receiver, control, ctx),
+            (OrderReceiver::Ring(_), _) => unreachable!("ring receiver only used in native mode"),
+        };
+        info!("NEW TRADER LAUNCHED: {}, mode={:?}", &self.symbol, &self.mode);
+        handler
+    }
+
+    fn launch_tokio(
+        &self,
+        runtime: &Handle,
+        mut receiver: mpsc::Receiver<Queued>,
+        mut control: mpsc::UnboundedReceiver<TraderControl>,
+        mut ctx: broadcast::Receiver<bool>,
+    ) -> JoinHandle<()> {
         let symbol = self.symbol.to_owned();
-        let receiver = Arc::clone(&self.req_receiver);
         let book = Arc::clone(&self.book);
         let consumer = Arc::clone(&self.consumer);
-        let handler = tokio::spawn(async move {
-            let mut receiver = receiver.lock().await;
+        let snapshot = self.snapshot.clone();
+        let snapshot_interval = self.snapshot_interval;
+        let liveness = self.liveness.guard();
+        let latency = Arc::clone(&self.latency);
+        let queue_wait = Arc::clone(&self.queue_wait);
+        let failures = Arc::clone(&self.failures);
+        let dump_dir = self.dump_dir.clone();
+        let settlement = self.settlement.clone();
+        let waiters = self.waiters.clone();
+        let replication = self.replication.clone();
+        let drain_timeout = self.drain_timeout;
+        let pause = self.pause.clone();
+        let verify_every = self.verify_every;
+        let uncross = self.uncross;
+        let session = self.session.clone();
+        runtime.spawn(async move {
+            let _liveness = liveness;
             let mut book = book.lock().await;
             let mut consumer = consumer.lock().await;
+            // 缓冲消费器需要定时刷新，保证成交推送的最大延迟
+            let flush_interval = consumer.flush_interval();
+            let mut flush_ticker = tokio::time::interval(flush_interval.unwrap_or(Duration::from_secs(1)));
+            let mut snapshot_ticker = tokio::time::interval(snapshot_interval.max(Duration::from_millis(1)));
+            let mut expiry_ticker = tokio::time::interval(EXPIRY_INTERVAL);
+            // 成交缓冲区在整个撮合循环中复用
+            let mut trades = MatchTrades::new();
+            let mut operations: u64 = 0;
             loop {
                 select! {
                     Ok(terminal) = ctx.recv() => {
                         info!("Rev terminal signal, symbol={}, terminal={}", &symbol, terminal);
                         if terminal {
                             info!("Terminal..., symbol={}", &symbol);
+                            // 停止接收新请求，在期限内处理完队列中剩余的请求
+                            receiver.close();
+                            let deadline = Instant::now() + drain_timeout;
+                            let mut drained = 0;
+                            let mut dropped = 0;
+                            while let Ok(queued) = receiver.try_recv() {
+                                if Instant::now() >= deadline {
+                                    dropped = 1 + receiver.len();
+                                    break;
+                                }
+                                let (order, wait) = queued.dequeue(&queue_wait);
+                                let handled = AssertUnwindSafe(handle_request(&mut book, order, wait, &mut consumer, &mut trades, &settlement, &waiters, replication.as_deref()))
+                                    .catch_unwind()
+                                    .await;
+                                if handled.is_err() {
+                                    let pending = std::iter::from_fn(|| receiver.try_recv().ok().map(|q| q.order)).collect();
+                                    fatal_dump(&book, &snapshot, pending, &dump_dir);
+                                    break;
+                                }
+                                drained += 1;
+                            }
+                            log_drained(&symbol, drained, dropped);
                             break
                         }
                     }
-                    Some(order) = receiver.recv() => {
-                        let _ = handle_request(&mut book, order, &mut consumer).await;
+                    // 暂停和休市期间请求留在队列中，恢复时由控制请求唤醒
+                    Some(queued) = receiver.recv(), if !pause.is_paused() && session.accepts() => {
+                        let (order, wait) = queued.dequeue(&queue_wait);
+                        let started = Instant::now();
+                        let handled = AssertUnwindSafe(handle_request(&mut book, order, wait, &mut consumer, &mut trades, &settlement, &waiters, replication.as_deref()))
+                            .catch_unwind()
+                            .await;
+                        latency.record(started.elapsed());
+                        if let Ok(Err(_)) = &handled {
+                            failures.fetch_add(1, Ordering::Relaxed);
+                        }
+                        if handled.is_err() {
+                            // 撮合崩溃，转储订单簿和队列中剩余的请求后退出
+                            let mut pending = Vec::new();
+                            while let Ok(queued) = receiver.try_recv() {
+                                pending.push(queued.order);
+                            }
+                            fatal_dump(&book, &snapshot, pending, &dump_dir);
+                            break;
+                        }
+                        operations += 1;
+                        verify_book(&book, operations, verify_every, &pause);
+                    }
+                    Some(request) = control.recv() => {
+                        match request {
+                            TraderControl::CancelAll(reply) => {
+                                let _ = reply.send(cancel_all(&mut book, &mut consumer, &mut trades, &settlement, replication.as_deref()).await);
+                            }
+                            request => handle_control(&mut book, &snapshot, request, receiver.len()),
+                        }
+                        if let Err(e) = uncross_orders(&mut book, uncross, &mut consumer, &mut trades, &settlement, replication.as_deref()).await {
+                            error!("UNCROSS FAILED: symbol={}, err={}", &symbol, e);
+                        }
+                    }
+                    _ = flush_ticker.tick(), if flush_interval.is_some() => {
+                        if let Err(e) = consumer.flush().await {
+                            error!("FLUSH TRADES FAILED: symbol={}, err={}", &symbol, e);
+                        }
+                    }
+                    _ = snapshot_ticker.tick() => {
+                        publish_snapshot(&book, &snapshot);
+                    }
+                    _ = expiry_ticker.tick(), if !pause.is_paused() => {
+                        if let Err(e) = advance_session(&mut book, &session, &mut consumer, &mut trades, &settlement, replication.as_deref()).await {
+                            error!("ADVANCE SESSION FAILED: symbol={}, err={}", &symbol, e);
+                        }
+                        if let Err(e) = expire_orders(&mut book, &mut consumer, &mut trades, &settlement, replication.as_deref()).await {
+                            error!("EXPIRE ORDERS FAILED: symbol={}, err={}", &symbol, e);
+                        }
                     }
                 }
             }
             receiver.close();
+            publish_snapshot(&book, &snapshot);
+            if let Err(e) = consumer.flush().await {
+                error!("FLUSH TRADES FAILED: symbol={}, err={}", &symbol, e);
+            }
             info!("TRADER EXIT: {}", &symbol);
-        });
-        info!("NEW TRADER LAUNCHED: {}", &self.symbol);
-        handler
+        })
     }
 
+    /// 在独立线程中运行撮合循环，线程内使用单线程运行时驱动消费器
+    fn launch_native(
+        &self,
+        runtime: &Handle,
+        mut receiver: RingConsumer<Queued>,
+        mut control: mpsc::UnboundedReceiver<TraderControl>,
+        core: Option<usize>,
+        mut ctx: broadcast::Receiver<bool>,
+    ) -> JoinHandle<()> {
+        let symbol = self.symbol.to_owned();
+        let book = Arc::clone(&self.book);
+        let consumer = Arc::clone(&self.consumer);
+        let snapshot = self.snapshot.clone();
+        let snapshot_interval = self.snapshot_interval;
+        let liveness = self.liveness.guard();
+        let latency = Arc::clone(&self.latency);
+        let queue_wait = Arc::clone(&self.queue_wait);
+        let failures = Arc::clone(&self.failures);
+        let dump_dir = self.dump_dir.clone();
+        let settlement = self.settlement.clone();
+        let waiters = self.waiters.clone();
+        let replication = self.replication.clone();
+        let drain_timeout = self.drain_timeout;
+        let pause = self.pause.clone();
+        let verify_every = self.verify_every;
+        let uncross = self.uncross;
+        let session = self.session.clone();
+        let thread = thread::Builder::new()
+            .name(format!("loom-trader-{}", &symbol))
+            .spawn(move || {
+                let _liveness = liveness;
+                if let Some(core) = core {
+                    if !pin_to_core(core) {
+                        warn!("PIN TRADER FAILED: symbol={}, core={}", &symbol, core);
+                    }
+                }
+                let rt = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .expect("failed to build trader runtime");
+                let mut book = book.blocking_lock();
+                let mut consumer = consumer.blocking_lock();
+                let flush_interval = consumer.flush_interval();
+                let mut last_flush = Instant::now();
+                let mut last_snapshot = Instant::now();
+                let mut last_expiry = Instant::now();
+                let mut idle: u32 = 0;
+                let mut handled: u32 = 0;
+                let mut operations: u64 = 0;
+                let mut trades = MatchTrades::new();
+                loop {
+                    // 暂停和休市期间请求留在队列中
+                    let next = if pause.is_paused() || !session.accepts() { None } else { receiver.pop() };
+                    if let Some(queued) = next {
+                        idle = 0;
+                        let (order, wait) = queued.dequeue(&queue_wait);
+                        let started = Instant::now();
+                        let outcome = std::panic::catch_unwind(AssertUnwindSafe(|| {
+                            rt.block_on(handle_request(&mut book, order, wait, &mut consumer, &mut trades, &settlement, &waiters, replication.as_deref()))
+                        }));
+                        latency.record(started.elapsed());
+                        if let Ok(Err(_)) = &outcome {
+                            failures.fetch_add(1, Ordering::Relaxed);
+                        }
+                        if outcome.is_err() {
+                            // 撮合崩溃，转储订单簿和队列中剩余的请求后退出
+                            let pending = std::iter::from_fn(|| receiver.pop().map(|q| q.order)).collect();
+                            fatal_dump(&book, &snapshot, pending, &dump_dir);
+                            break;
+                        }
+                        handled = handled.wrapping_add(1);
+                        operations += 1;
+                        verify_book(&book, operations, verify_every, &pause);
+                        // 持续有请求时也需要定期检查退出信号
+                        if !handled.is_multiple_of(1024) {
+                            continue;
+                        }
+                    }
+                    if let Ok(true) = ctx.try_recv() {
+                        info!("Terminal..., symbol={}", &symbol);
+                        // 停止接收新请求，在期限内处理完队列中剩余的请求
+                        receiver.close();
+                        let deadline = Instant::now() + drain_timeout;
+                        let mut drained = 0;
+                        let mut dropped = 0;
+                        while let Some(queued) = receiver.pop() {
+                            if Instant::now() >= deadline {
+                                dropped = 1 + receiver.len();
+                                break;
+                            }
+                            let (order, wait) = queued.dequeue(&queue_wait);
+                            let outcome = std::panic::catch_unwind(AssertUnwindSafe(|| {
+                                rt.block_on(handle_request(&mut book, order, wait, &mut consumer, &mut trades, &settlement, &waiters, replication.as_deref()))
+                            }));
+                            if outcome.is_err() {
+                                let pending = std::iter::from_fn(|| receiver.pop().map(|q| q.order)).collect();
+                                fatal_dump(&book, &snapshot, pending, &dump_dir);
+                                break;
+                            }
+                            drained += 1;
+                        }
+                        log_drained(&symbol, drained, dropped);
+                        break;
+                    }
+                    while let Ok(request) = control.try_recv() {
+                        match request {
+                            TraderControl::CancelAll(reply) => {
+                                let _ = reply.send(rt.block_on(cancel_all(&mut book, &mut consumer, &mut trades, &settlement, replication.as_deref())));
+                            }
+                            request => handle_control(&mut book, &snapshot, request, receiver.len()),
+                        }
+                        if let Err(e) = rt.block_on(uncross_orders(&mut book, uncross, &mut consumer, &mut trades, &settlement, replication.as_deref())) {
+                            error!("UNCROSS FAILED: symbol={}, err={}", &symbol, e);
+                        }
+                    }
+                    if let Some(interval) = flush_interval {
+                        if last_flush.elapsed() >= interval {
+                            if let Err(e) = rt.block_on(consumer.flush()) {
+                                error!("FLUSH TRADES FAILED: symbol={}, err={}", &symbol, e);
+                            }
+                            last_flush = Instant::now();
+                        }
+                    }
+                    if last_snapshot.elapsed() >= snapshot_interval {
+                        publish_snapshot(&book, &snapshot);
+                        last_snapshot = Instant::now();
+                    }
+                    if !pause.is_paused() && last_expiry.elapsed() >= EXPIRY_INTERVAL {
+                        if let Err(e) = rt.block_on(advance_session(&mut book, &session, &mut consumer, &mut trades, &settlement, replication.as_deref())) {
+                            error!("ADVANCE SESSION FAILED: symbol={}, err={}", &symbol, e);
+                        }
+                        if let Err(e) = rt.block_on(expire_orders(&mut book, &mut consumer, &mut trades, &settlement, replication.as_deref())) {
+                            error!("EXPIRE ORDERS FAILED: symbol={}, err={}", &symbol, e);
+                        }
+                        last_expiry = Instant::now();
+                    }
+                    backoff(&mut idle);
+                }
+                receiver.close();
+                publish_snapshot(&book, &snapshot);
+                if let Err(e) = rt.block_on(consumer.flush()) {
+                    error!("FLUSH TRADES FAILED: symbol={}, err={}", &symbol, e);
+                }
+                info!("TRADER EXIT: {}", &symbol);
+            })
+            .expect("failed to spawn trader thread");
+        runtime.spawn_blocking(move || {
+            let _ = thread.join();
+        })
+    }
+
+
+    pub fn symbol(&self) -> &str {
+        &self.symbol
+    }
+
+    pub fn symbol_id(&self) -> SymbolId {
+        self.symbol_id
+    }
+
+    /// 暂停状态
+    pub fn pause_state(&self) -> PauseState {
+        self.pause.clone()
+    }
+
+    /// 交易时段阶段
+    pub fn session_state(&self) -> SessionState {
+        self.session.clone()
+    }
+
+    /// 控制请求发送器
+    pub fn control(&self) -> mpsc::UnboundedSender<TraderControl> {
+        self.control.clone()
+    }
+
+    /// 撮合延迟直方图
+    pub fn latency(&self) -> Arc<LatencyHistogram> {
+        Arc::clone(&self.latency)
+    }
+
+    /// 撮合请求排队时间
+    pub fn queue_wait(&self) -> Arc<QueueWait> {
+        Arc::clone(&self.queue_wait)
+    }
+
+    /// 撮合请求处理失败次数
+    pub fn failures(&self) -> Arc<AtomicU64> {
+        Arc::clone(&self.failures)
+    }
+
+    /// 存活状态
+    pub fn liveness(&self) -> Liveness {
+        self.liveness.clone()
+    }
+
+    /// 等待同步撮合结果的请求
+    pub fn waiters(&self) -> TradeWaiters {
+        self.waiters.clone()
+    }
+
+    /// 最新发布的市场快照
+    pub fn snapshot(&self) -> SnapshotCell {
+        self.snapshot.clone()
+    }
 
     /// 获取新的发送器
-    pub fn get_input_sender(&self) -> mpsc::Sender<Order> {
+    pub fn get_input_sender(&self) -> OrderSender {
         self.req_sender.clone()
     }
 
@@ -92,20 +1174,774 @@ impl Trader {
     }
 }
 
-async fn handle_request(book: &mut MarketBook, order: Order, consumer: &mut TradeConsumer) -> anyhow::Result<()> {
-    debug!("NEW MATCH: {}", serde_json::to_string(&order)?);
-    let trades;
-    match order.action {
-        OrderAction::PLACE => {
-            // 撮合动作
-            trades = book.try_match(order);
+fn handle_control(book: &mut MarketBook, snapshot: &SnapshotCell, request: TraderControl, pending_count: usize) {
+    match request {
+        TraderControl::Dump(reply) => {
+            let _ = reply.send(TraderDump {
+                symbol: book.symbol.to_string(),
+                book: Some(book.dump()),
+                snapshot: Some(BookSnapshot::clone(&snapshot.load())),
+                pending: Vec::new(),
+                pending_count,
+            });
+        }
+        TraderControl::StateHash(reply) => {
+            let _ = reply.send(book.state_hash());
+        }
+        TraderControl::Image(reply) => {
+            let _ = reply.send(book.image());
+        }
+        TraderControl::Wake => {}
+        TraderControl::UpdateSpec(spec, reply) => {
+            let _ = reply.send(book.update_spec(spec));
+        }
+        TraderControl::Verify(reply) => {
+            let _ = reply.send(book.verify());
+        }
+        TraderControl::QueuePosition(oid, account, reply) => {
+            let owner = [book.bids(), book.asks()].into_iter().find_map(|b| b.get_by_id(oid)).map(|o| o.account.clone());
+            let position = book.queue_position(oid).filter(|_| account.is_none() || owner == Some(account));
+            let _ = reply.send(position);
+        }
+        TraderControl::CancelAll(_) => unreachable!("cancel all needs the consumer and is handled by the trader loop"),
+        TraderControl::Load(orders, reply) => {
+            let rest = book.load(orders);
+            snapshot.store(book.snapshot());
+            let _ = reply.send(rest);
+        }
+        TraderControl::Restore(image, reply) => {
+            let result = book.restore(image);
+            if result.is_ok() {
+                // 镜像版本可能与已发布的快照相同，直接发布
+                snapshot.store(book.snapshot());
+            }
+            let _ = reply.send(result);
+        }
+    }
+}
+
+/// 每处理verify_every个请求检查一次市场不变式，不满足时以拒绝方式暂停交易对，保留现场等待排查
+fn verify_book(book: &MarketBook, operations: u64, verify_every: Option<u64>, pause: &PauseState) {
+    if !verify_every.is_some_and(|n| n > 0 && operations.is_multiple_of(n)) {
+        return;
+    }
+    if let Err(e) = book.verify() {
+        error!("BOOK INVARIANT VIOLATED, SYMBOL HALTED: symbol={}, operations={}, err={}", &book.symbol, operations, e);
+        pause.pause(PauseMode::Reject);
+    }
+}
+
+/// 撮合崩溃时转储订单簿和队列中剩余的请求
+fn fatal_dump(book: &MarketBook, snapshot: &SnapshotCell, pending: Vec<Order>, dir: &std::path::Path) {
+    let trader = TraderDump {
+        symbol: book.symbol.to_string(),
+        book: Some(book.dump()),
+        snapshot: Some(BookSnapshot::clone(&snapshot.load())),
+        pending_count: pending.len(),
+        pending,
+    };
+    let dump = EngineDump::new(&format!("trader panicked, symbol={}", &book.symbol), vec![trader]);
+    match dump::write_dump(dir, &dump) {
+        Ok(path) => error!("TRADER PANICKED, BOOK DUMPED: symbol={}, path={}", &book.symbol, path.display()),
+        Err(e) => error!("TRADER PANICKED, DUMP FAILED: symbol={}, err={}", &book.symbol, e),
+    }
+}
+
+/// 记录关闭时队列的处理结果，超时丢弃的下单请求已写入缓存，重启后恢复
+fn log_drained(symbol: &str, drained: usize, dropped: usize) {
+    if dropped > 0 {
+        warn!("TRADER DRAIN TIMEOUT: symbol={}, drained={}, dropped={}", symbol, drained, dropped);
+    } else {
+        info!("TRADER DRAINED: symbol={}, drained={}", symbol, drained);
+    }
+}
+
+/// 订单簿有变化时发布新快照
+fn publish_snapshot(book: &MarketBook, snapshot: &SnapshotCell) {
+    if book.version() != snapshot.version() {
+        snapshot.store(book.snapshot());
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn handle_request(
+    book: &mut MarketBook,
+    order: Order,
+    queue_wait: Duration,
+    consumer: &mut TradeConsumer,
+    trades: &mut MatchTrades,
+    settlement: &Settlement,
+    waiters: &TradeWaiters,
+    replication: Option<&Replication>,
+) -> anyhow::Result<()> {
+    let span = debug_span!("trader.handle", symbol = %order.symbol, oid = order.id, action = ?order.action, queue_wait_us = queue_wait.as_micros() as u64);
+    let ctx = logging::LogContext::default().with_order(&order);
+    let oid = order.id;
+    let fut = async move {
+        // 先撤销到期的订单，到期的挂单不能再成交
+        expire_orders(book, consumer, trades, settlement, replication).await?;
+        debug!("NEW MATCH: {}", serde_json::to_string(&order)?);
+        settlement.audit(&order);
+        trades.clear();
+        let mut cancel = None;
+        {
+            let _span = debug_span!("market.match").entered();
+            match order.action {
+                OrderAction::PLACE => {
+                    // 撮合动作，触发价格熔断或资金不足的订单不撮合
+                    if !settlement.collar(book, &order, trades) && !settlement.fund(book, &order, trades) {
+                        book.try_match_into(order, trades)
+                    }
+                }
+                OrderAction::CANCEL => {
+                    // 撤单动作
+                    let result = book.try_cancel_into(order, trades);
+                    if result != CancelResult::CANCELED {
+                        debug!("CANCEL MISSED: oid={}, result={:?}", oid, result);
+                    }
+                    cancel = Some(result);
+                }
+                OrderAction::REDUCE => {
+                    // 减量动作
+                    if !book.try_reduce_into(order, trades) {
+                        debug!("REDUCE MISSED: oid={}", oid);
+                    }
+                }
+            };
         }
-        OrderAction::CANCEL => {
-            // 撤单动作
-            trades = book.try_cancel(order);
+        // 已完成的订单释放账户挂单额度，成交结算账户余额
+        settlement.settle(trades);
+        let tripped = settlement.protect(trades);
+        waiters.notify(oid, trades, cancel);
+        debug!("NEW TRADES: {}", serde_json::to_string(&trades[..])?);
+        if replication.is_some_and(|r| !r.publishes_trades()) {
+            // 备机重放的成交由主机推送
+            trades.clear();
+        } else {
+            let publish = debug_span!("consumer.publish", trades = trades.len());
+            consumer.consume(trades).instrument(publish).await?;
         }
+        cancel_quotes(book, &tripped, consumer, trades, settlement, replication).await
+    };
+    logging::scope(ctx, fut.instrument(span)).await
+}
+
+/// 撤销到期的GTD挂单，撤单结果与普通撤单一样结算并推送
+async fn expire_orders(
+    book: &mut MarketBook,
+    consumer: &mut TradeConsumer,
+    trades: &mut MatchTrades,
+    settlement: &Settlement,
+    replication: Option<&Replication>,
+) -> anyhow::Result<()> {
+    trades.clear();
+    if book.expire_into(trades) == 0 {
+        return Ok(());
+    }
+    debug!("EXPIRED ORDERS: {}", serde_json::to_string(&trades[..])?);
+    publish_results(consumer, trades, settlement, replication).await
+}
+
+/// 撤销所有挂单，订单簿中的挂单在推送失败时也已撤销
+async fn cancel_all(
+    book: &mut MarketBook,
+    consumer: &mut TradeConsumer,
+    trades: &mut MatchTrades,
+    settlement: &Settlement,
+    replication: Option<&Replication>,
+) -> anyhow::Result<usize> {
+    trades.clear();
+    let cancelled = book.cancel_all_into(trades);
+    warn!("ALL ORDERS CANCELLED: symbol={}, orders={}", &book.symbol, cancelled);
+    publish_results(consumer, trades, settlement, replication).await?;
+    Ok(cancelled)
+}
+
+/// 按时钟更新交易时段阶段，开盘时进入集合竞价，集合竞价结束时按单一价格撮合并推送成交
+async fn advance_session(
+    book: &mut MarketBook,
+    session: &SessionState,
+    consumer: &mut TradeConsumer,
+    trades: &mut MatchTrades,
+    settlement: &Settlement,
+    replication: Option<&Replication>,
+) -> anyhow::Result<()> {
+    let Some((previous, phase)) = session.update(book.now_ts()) else {
+        return Ok(());
+    };
+    info!("SESSION PHASE CHANGED: symbol={}, from={:?}, to={:?}", &book.symbol, previous, phase);
+    if phase == SessionPhase::Auction {
+        book.begin_auction();
+        return Ok(());
+    }
+    trades.clear();
+    let Some(result) = book.end_auction_into(trades) else {
+        return Ok(());
+    };
+    info!("AUCTION UNCROSSED: symbol={}, price={}, volume={}, trades={}", &book.symbol, &result.price, result.volume, trades.len());
+    let tripped = settlement.protect(trades);
+    publish_results(consumer, trades, settlement, replication).await?;
+    cancel_quotes(book, &tripped, consumer, trades, settlement, replication).await
+}
+
+/// 撤销触发做市商保护的账户在交易对上的挂单
+async fn cancel_quotes(
+    book: &mut MarketBook,
+    accounts: &[String],
+    consumer: &mut TradeConsumer,
+    trades: &mut MatchTrades,
+    settlement: &Settlement,
+    replication: Option<&Replication>,
+) -> anyhow::Result<()> {
+    for account in accounts {
+        trades.clear();
+        let cancelled = book.cancel_account_into(account, trades);
+        warn!("MMP TRIPPED, QUOTES CANCELLED: symbol={}, account={}, orders={}", &book.symbol, account, cancelled);
+        publish_results(consumer, trades, settlement, replication).await?;
+    }
+    Ok(())
+}
+
+/// 控制请求替换订单簿后消除交叉，交易对恢复接受请求前完成，集合竞价期间的交叉在结束时撮合
+async fn uncross_orders(
+    book: &mut MarketBook,
+    policy: UncrossPolicy,
+    consumer: &mut TradeConsumer,
+    trades: &mut MatchTrades,
+    settlement: &Settlement,
+    replication: Option<&Replication>,
+) -> anyhow::Result<()> {
+    if book.in_auction() || !book.is_crossed() {
+        return Ok(());
+    }
+    trades.clear();
+    let resolved = book.uncross_into(policy, trades);
+    warn!("BOOK UNCROSSED: symbol={}, orders={}, results={}", &book.symbol, resolved, trades.len());
+    publish_results(consumer, trades, settlement, replication).await
+}
+
+/// 结算并推送撮合请求之外产生的结果
+async fn publish_results(
+    consumer: &mut TradeConsumer,
+    trades: &mut MatchTrades,
+    settlement: &Settlement,
+    replication: Option<&Replication>,
+) -> anyhow::Result<()> {
+    settlement.settle(trades);
+    if replication.is_some_and(|r| !r.publishes_trades()) {
+        trades.clear();
+        return Ok(());
     }
-    debug!("NEW TRADES: {}", serde_json::to_string(&trades)?);
     consumer.consume(trades).await?;
+    trades.clear();
     Ok(())
 }
+
+/// 空闲时逐步退让：先自旋，再让出线程，最后短暂休眠
+fn backoff(idle: &mut u32) {
+    if *idle < 64 {
+        std::hint::spin_loop();
+    } else if *idle < 128 {
+        thread::yield_now();
+    } else {
+        thread::park_timeout(Duration::from_micros(50));
+    }
+    *idle = idle.saturating_add(1);
+}
+
+/// 将当前线程绑定到指定CPU核心
+#[cfg(target_os = "linux")]
+fn pin_to_core(core: usize) -> bool {
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(core, &mut set);
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) == 0
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn pin_to_core(_core: usize) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use bigdecimal::BigDecimal;
+    use tokio::sync::{broadcast, oneshot};
+
+    use loom_core::clock::ManualClock;
+    use loom_core::market::{CancelResult, RejectCode, UncrossPolicy};
+    use loom_core::order::{Order, OrderAction, OrderTimeInForce, OrderType, TradeSide};
+    use loom_core::symbol::SymbolId;
+    use loom_core::testing::new_order;
+
+    use crate::audit::{self, AuditLog};
+    use crate::collar::{CollarAction, CollarConfig, PriceCollar};
+    use crate::consumer::{ConsoleConsumer, TradeConsumer};
+    use crate::mmp::{MarketMakerProtection, MmpConfig};
+    use crate::session::{ClosedPolicy, SessionConfig, SessionPhase, TradingSession};
+    use crate::trader::{Backpressure, PauseMode, QueueFull, Trader, TraderControl, TraderMode, TraderOptions};
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn native_trader_test() {
+        let options = TraderOptions::default()
+            .with_mode(TraderMode::Native { core: None })
+            .with_capacity(8);
+        let trader = Trader::with_options("LOOM-USDT-SPOT", SymbolId(0), TradeConsumer::Console(ConsoleConsumer {}), options);
+        let (ctx, _) = broadcast::channel(1);
+        let handler = trader.launch(ctx.subscribe());
+        assert!(trader.liveness().is_alive());
+        for id in 1..=32 {
+            let side = if id % 2 == 0 { TradeSide::BUY } else { TradeSide::SELL };
+            trader.feed(new_order(id, side, 1, "100")).await.unwrap();
+        }
+        ctx.send(true).unwrap();
+        handler.await.unwrap();
+        assert!(!trader.liveness().is_alive());
+        assert_eq!(trader.latency().count(), 32);
+        assert!(trader.feed(new_order(33, TradeSide::BUY, 1, "100")).await.is_err());
+        // 退出前发布最终快照，买卖单全部成交
+        let snapshot = trader.snapshot().load();
+        assert!(snapshot.version >= 32);
+        assert!(snapshot.bids.is_empty() && snapshot.asks.is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn dump_test() {
+        for mode in [TraderMode::Tokio, TraderMode::Native { core: None }] {
+            let options = TraderOptions::default().with_mode(mode);
+            let trader = Trader::with_options("LOOM-USDT-SPOT", SymbolId(0), TradeConsumer::Console(ConsoleConsumer {}), options);
+            let (ctx, _) = broadcast::channel(1);
+            let handler = trader.launch(ctx.subscribe());
+            for id in 1..=3 {
+                trader.feed(new_order(id, TradeSide::BUY, 1, "100")).await.unwrap();
+            }
+            // 等待撮合请求处理完成
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            let (tx, rx) = oneshot::channel();
+            trader.control().send(TraderControl::Dump(tx)).unwrap();
+            let dump = rx.await.unwrap();
+            let ids: Vec<u64> = dump.book.unwrap().bids.iter().map(|o| o.id).collect();
+            assert_eq!(ids, vec![1, 2, 3]);
+            ctx.send(true).unwrap();
+            handler.await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn waiters_test() {
+        let trader = Trader::new("LOOM-USDT-SPOT", SymbolId(0), TradeConsumer::Console(ConsoleConsumer {}));
+        let (ctx, _) = broadcast::channel(1);
+        let handler = trader.launch(ctx.subscribe());
+        trader.feed(new_order(1, TradeSide::BUY, 1, "100")).await.unwrap();
+        let rx = trader.waiters().register(2);
+        trader.feed(new_order(2, TradeSide::SELL, 1, "100")).await.unwrap();
+        let trades = rx.await.unwrap().trades;
+        assert_eq!(trades.len(), 1);
+        assert_eq!((trades[0].taker_oid, trades[0].maker_oid), (2, 1));
+        // 撤销已成交的订单没有成交，由撤单结果说明原因
+        let rx = trader.waiters().register(1);
+        trader.feed(Order { action: OrderAction::CANCEL, ..new_order(1, TradeSide::BUY, 1, "100") }).await.unwrap();
+        let reply = rx.await.unwrap();
+        assert_eq!((reply.trades.len(), reply.cancel), (0, Some(CancelResult::ALREADY_FILLED)));
+        ctx.send(true).unwrap();
+        handler.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn state_hash_test() {
+        let trader = Trader::new("LOOM-USDT-SPOT", SymbolId(0), TradeConsumer::Console(ConsoleConsumer {}));
+        let (ctx, _) = broadcast::channel(1);
+        let handler = trader.launch(ctx.subscribe());
+        let mut order = new_order(1, TradeSide::BUY, 1, "100");
+        order.seq = 5;
+        let rx = trader.waiters().register(1);
+        trader.feed(order).await.unwrap();
+        rx.await.unwrap();
+        let (tx, rx) = oneshot::channel();
+        trader.control().send(TraderControl::StateHash(tx)).unwrap();
+        let hash = rx.await.unwrap();
+        assert_eq!((hash.seq, hash.orders), (5, 1));
+        ctx.send(true).unwrap();
+        handler.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn image_restore_test() {
+        let trader = Trader::new("LOOM-USDT-SPOT", SymbolId(0), TradeConsumer::Console(ConsoleConsumer {}));
+        let (ctx, _) = broadcast::channel(1);
+        let handler = trader.launch(ctx.subscribe());
+        let rx = trader.waiters().register(1);
+        trader.feed(new_order(1, TradeSide::BUY, 1, "100")).await.unwrap();
+        rx.await.unwrap();
+        let (tx, rx) = oneshot::channel();
+        trader.control().send(TraderControl::Image(tx)).unwrap();
+        let image = rx.await.unwrap();
+        assert_eq!(image.bids.len(), 1);
+        let rx = trader.waiters().register(2);
+        trader.feed(new_order(2, TradeSide::BUY, 1, "100")).await.unwrap();
+        rx.await.unwrap();
+        let (tx, rx) = oneshot::channel();
+        trader.control().send(TraderControl::Restore(image, tx)).unwrap();
+        rx.await.unwrap().unwrap();
+        let snapshot = trader.snapshot();
+        assert_eq!(snapshot.load().bids[0].orders, 1);
+        ctx.send(true).unwrap();
+        handler.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn verify_test() {
+        let options = TraderOptions::default().with_verify_every(2);
+        let trader = Trader::with_options("LOOM-USDT-SPOT", SymbolId(0), TradeConsumer::Console(ConsoleConsumer {}), options);
+        let (ctx, _) = broadcast::channel(1);
+        let handler = trader.launch(ctx.subscribe());
+        let rx = trader.waiters().register(1);
+        trader.feed(new_order(1, TradeSide::BUY, 1, "100")).await.unwrap();
+        rx.await.unwrap();
+        // 用没有剩余数量的挂单破坏订单簿
+        let (tx, rx) = oneshot::channel();
+        trader.control().send(TraderControl::Image(tx)).unwrap();
+        let mut image = rx.await.unwrap();
+        let mut ask = new_order(2, TradeSide::SELL, 1, "100");
+        ask.price = BigDecimal::from(102);
+        ask.acc_fill_qty = ask.qty;
+        image.asks.push(ask);
+        let (tx, rx) = oneshot::channel();
+        trader.control().send(TraderControl::Restore(image, tx)).unwrap();
+        rx.await.unwrap().unwrap();
+        let (tx, rx) = oneshot::channel();
+        trader.control().send(TraderControl::Verify(tx)).unwrap();
+        assert!(rx.await.unwrap().is_err());
+        assert!(!trader.pause_state().is_paused());
+        // 第二个请求后定期检查发现问题并暂停交易对
+        let rx = trader.waiters().register(3);
+        let mut order = new_order(3, TradeSide::SELL, 1, "100");
+        order.price = BigDecimal::from(101);
+        trader.feed(order).await.unwrap();
+        rx.await.unwrap();
+        let (tx, rx) = oneshot::channel();
+        trader.control().send(TraderControl::StateHash(tx)).unwrap();
+        rx.await.unwrap();
+        assert!(trader.pause_state().rejects());
+        ctx.send(true).unwrap();
+        handler.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn uncross_test() {
+        for policy in [UncrossPolicy::Match, UncrossPolicy::CancelNewer] {
+            let options = TraderOptions::default().with_uncross(policy);
+            let trader = Trader::with_options("LOOM-USDT-SPOT", SymbolId(0), TradeConsumer::Console(ConsoleConsumer {}), options);
+            let (ctx, _) = broadcast::channel(1);
+            let handler = trader.launch(ctx.subscribe());
+            let (tx, rx) = oneshot::channel();
+            trader.control().send(TraderControl::Image(tx)).unwrap();
+            let mut image = rx.await.unwrap();
+            image.bids.push(new_order(1, TradeSide::BUY, 1, "100"));
+            let mut ask = new_order(2, TradeSide::SELL, 1, "100");
+            ask.ts = 1;
+            image.asks.push(ask);
+            let (tx, rx) = oneshot::channel();
+            trader.control().send(TraderControl::Restore(image, tx)).unwrap();
+            rx.await.unwrap().unwrap();
+            let (tx, rx) = oneshot::channel();
+            trader.control().send(TraderControl::Verify(tx)).unwrap();
+            rx.await.unwrap().unwrap();
+            let (tx, rx) = oneshot::channel();
+            trader.control().send(TraderControl::StateHash(tx)).unwrap();
+            let expected = if policy == UncrossPolicy::Match { 0 } else { 1 };
+            assert_eq!(rx.await.unwrap().orders, expected);
+            ctx.send(true).unwrap();
+            handler.await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn drain_test() {
+        for mode in [TraderMode::Tokio, TraderMode::Native { core: None }] {
+            let options = TraderOptions::default().with_mode(mode).with_capacity(8);
+            let trader = Trader::with_options("LOOM-USDT-SPOT", SymbolId(0), TradeConsumer::Console(ConsoleConsumer {}), options);
+            let (ctx, _) = broadcast::channel(1);
+            let receiver = ctx.subscribe();
+            // 启动前排队的请求在收到退出信号后仍会处理完
+            for id in 1..=5 {
+                trader.feed(new_order(id, TradeSide::BUY, 1, "100")).await.unwrap();
+            }
+            ctx.send(true).unwrap();
+            trader.launch(receiver).await.unwrap();
+            assert_eq!(trader.snapshot().load().bids[0].orders, 5);
+            assert!(trader.feed(new_order(6, TradeSide::BUY, 1, "100")).await.is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn pause_test() {
+        for mode in [TraderMode::Tokio, TraderMode::Native { core: None }] {
+            let options = TraderOptions::default().with_mode(mode);
+            let trader = Trader::with_options("LOOM-USDT-SPOT", SymbolId(0), TradeConsumer::Console(ConsoleConsumer {}), options);
+            let (ctx, _) = broadcast::channel(1);
+            let handler = trader.launch(ctx.subscribe());
+            trader.pause_state().pause(PauseMode::Buffer);
+            let rx = trader.waiters().register(1);
+            trader.feed(new_order(1, TradeSide::BUY, 1, "100")).await.unwrap();
+            // 暂停期间请求留在队列中，控制请求仍然响应
+            let (tx, hash) = oneshot::channel();
+            trader.control().send(TraderControl::StateHash(tx)).unwrap();
+            assert_eq!(hash.await.unwrap().orders, 0);
+            assert_eq!(trader.get_input_sender().pending(), 1);
+            assert!(trader.pause_state().resume());
+            trader.control().send(TraderControl::Wake).unwrap();
+            rx.await.unwrap();
+            ctx.send(true).unwrap();
+            handler.await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn queue_wait_test() {
+        for mode in [TraderMode::Tokio, TraderMode::Native { core: None }] {
+            let options = TraderOptions::default().with_mode(mode).with_max_queue_wait(Duration::from_millis(20));
+            let trader = Trader::with_options("LOOM-USDT-SPOT", SymbolId(0), TradeConsumer::Console(ConsoleConsumer {}), options);
+            let (ctx, _) = broadcast::channel(1);
+            let handler = trader.launch(ctx.subscribe());
+            // 暂停期间排队的请求出队时等待时间超过上限
+            trader.pause_state().pause(PauseMode::Buffer);
+            let rx = trader.waiters().register(1);
+            trader.feed(new_order(1, TradeSide::BUY, 1, "100")).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            assert!(trader.pause_state().resume());
+            trader.control().send(TraderControl::Wake).unwrap();
+            rx.await.unwrap();
+            let queue_wait = trader.queue_wait();
+            assert!(queue_wait.saturated());
+            assert!(queue_wait.last() >= Duration::from_millis(50));
+            // 队列排空后新的请求恢复正常
+            let rx = trader.waiters().register(2);
+            trader.feed(new_order(2, TradeSide::BUY, 1, "100")).await.unwrap();
+            rx.await.unwrap();
+            assert!(!queue_wait.saturated());
+            assert_eq!(queue_wait.histogram().count(), 2);
+            ctx.send(true).unwrap();
+            handler.await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn cancel_all_test() {
+        for mode in [TraderMode::Tokio, TraderMode::Native { core: None }] {
+            let options = TraderOptions::default().with_mode(mode);
+            let trader = Trader::with_options("LOOM-USDT-SPOT", SymbolId(0), TradeConsumer::Console(ConsoleConsumer {}), options);
+            let (ctx, _) = broadcast::channel(1);
+            let handler = trader.launch(ctx.subscribe());
+            let rx = trader.waiters().register(2);
+            trader.feed(new_order(1, TradeSide::BUY, 1, "100")).await.unwrap();
+            trader.feed(new_order(2, TradeSide::BUY, 1, "100")).await.unwrap();
+            rx.await.unwrap();
+            // 暂停期间撤销所有挂单
+            trader.pause_state().pause(PauseMode::Reject);
+            let (tx, cancelled) = oneshot::channel();
+            trader.control().send(TraderControl::CancelAll(tx)).unwrap();
+            assert_eq!(cancelled.await.unwrap().unwrap(), 2);
+            let (tx, hash) = oneshot::channel();
+            trader.control().send(TraderControl::StateHash(tx)).unwrap();
+            assert_eq!(hash.await.unwrap().orders, 0);
+            ctx.send(true).unwrap();
+            handler.await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn expiry_test() {
+        for mode in [TraderMode::Tokio, TraderMode::Native { core: None }] {
+            let clock = Arc::new(ManualClock::new(1000));
+            let options = TraderOptions::default().with_mode(mode).with_clock(clock.clone());
+            let trader = Trader::with_options("LOOM-USDT-SPOT", SymbolId(0), TradeConsumer::Console(ConsoleConsumer {}), options);
+            let (ctx, _) = broadcast::channel(1);
+            let handler = trader.launch(ctx.subscribe());
+            let mut order = new_order(1, TradeSide::BUY, 1, "100");
+            order.tif = OrderTimeInForce::GTD;
+            order.expire_ts = 1010;
+            let rx = trader.waiters().register(1);
+            trader.feed(order).await.unwrap();
+            assert!(rx.await.unwrap().trades.is_empty());
+            clock.advance(10);
+            // 没有新请求时由定时检查撤销到期订单
+            let mut orders = 1;
+            for _ in 0..100 {
+                let (tx, hash) = oneshot::channel();
+                trader.control().send(TraderControl::StateHash(tx)).unwrap();
+                orders = hash.await.unwrap().orders;
+                if orders == 0 {
+                    break;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            }
+            assert_eq!(orders, 0);
+            ctx.send(true).unwrap();
+            handler.await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn session_test() {
+        for mode in [TraderMode::Tokio, TraderMode::Native { core: None }] {
+            // 2024-01-01（周一）09:29 UTC
+            let clock = Arc::new(ManualClock::new(1_704_101_340_000));
+            let session = TradingSession::new(&SessionConfig {
+                open: String::from("09:30"),
+                close: String::from("16:00"),
+                utc_offset_minutes: None,
+                weekdays: None,
+                holidays: None,
+                closed: Some(ClosedPolicy::Queue),
+                auction_secs: Some(60),
+            }).unwrap();
+            let options = TraderOptions::default().with_mode(mode).with_clock(clock.clone()).with_session(session);
+            let trader = Trader::with_options("LOOM-USDT-SPOT", SymbolId(0), TradeConsumer::Console(ConsoleConsumer {}), options);
+            let state = trader.session_state();
+            let (ctx, _) = broadcast::channel(1);
+            let handler = trader.launch(ctx.subscribe());
+            let wait = |phase: SessionPhase| {
+                let state = state.clone();
+                async move {
+                    for _ in 0..200 {
+                        if state.phase() == phase {
+                            return;
+                        }
+                        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+                    }
+                    panic!("session phase not reached, phase={:?}", phase);
+                }
+            };
+            wait(SessionPhase::Closed).await;
+            // 休市期间请求留在队列中，开盘后进入集合竞价，交叉的订单不撮合
+            trader.feed(new_order(1, TradeSide::BUY, 1, "100")).await.unwrap();
+            let rx = trader.waiters().register(2);
+            trader.feed(new_order(2, TradeSide::SELL, 1, "100")).await.unwrap();
+            assert_eq!(trader.get_input_sender().pending(), 2);
+            clock.advance(60_000);
+            wait(SessionPhase::Auction).await;
+            assert!(rx.await.unwrap().trades.is_empty());
+            let (tx, hash) = oneshot::channel();
+            trader.control().send(TraderControl::StateHash(tx)).unwrap();
+            assert_eq!(hash.await.unwrap().orders, 2);
+            // 集合竞价结束时按单一价格成交
+            clock.advance(60_000);
+            wait(SessionPhase::Open).await;
+            let (tx, hash) = oneshot::channel();
+            trader.control().send(TraderControl::StateHash(tx)).unwrap();
+            assert_eq!(hash.await.unwrap().orders, 0);
+            ctx.send(true).unwrap();
+            handler.await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn mmp_test() {
+        for mode in [TraderMode::Tokio, TraderMode::Native { core: None }] {
+            let mmp = Arc::new(MarketMakerProtection::new(MmpConfig { max_fills: Some(1), ..Default::default() }));
+            let options = TraderOptions::default().with_mode(mode).with_mmp(Arc::clone(&mmp));
+            let trader = Trader::with_options("LOOM-USDT-SPOT", SymbolId(0), TradeConsumer::Console(ConsoleConsumer {}), options);
+            let (ctx, _) = broadcast::channel(1);
+            let handler = trader.launch(ctx.subscribe());
+            for (id, price) in [(1, 100), (2, 101), (3, 102)] {
+                let order = Order { price: BigDecimal::from(price), account: Some(String::from("maker")), ..new_order(id, TradeSide::SELL, 1, "100") };
+                trader.feed(order).await.unwrap();
+            }
+            // 一次吃掉两笔挂单，触发保护后撤销剩余挂单
+            let rx = trader.waiters().register(4);
+            trader.feed(Order { qty: 2, price: BigDecimal::from(101), ..new_order(4, TradeSide::BUY, 1, "100") }).await.unwrap();
+            assert_eq!(rx.await.unwrap().trades.len(), 2);
+            let (tx, hash) = oneshot::channel();
+            trader.control().send(TraderControl::StateHash(tx)).unwrap();
+            assert_eq!(hash.await.unwrap().orders, 0);
+            assert_eq!(mmp.tripped().len(), 1);
+            ctx.send(true).unwrap();
+            handler.await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn collar_test() {
+        let collar = PriceCollar::new(&CollarConfig {
+            pct: BigDecimal::from(10),
+            basis: None,
+            window_ms: None,
+            ema_alpha: None,
+            min_trades: None,
+            action: Some(CollarAction::Halt),
+        }).unwrap();
+        let options = TraderOptions::default().with_collar(collar);
+        let trader = Trader::with_options("LOOM-USDT-SPOT", SymbolId(0), TradeConsumer::Console(ConsoleConsumer {}), options);
+        let (ctx, _) = broadcast::channel(1);
+        let handler = trader.launch(ctx.subscribe());
+        trader.feed(new_order(1, TradeSide::BUY, 1, "100")).await.unwrap();
+        trader.feed(Order { qty: 2, price: BigDecimal::from(80), ..new_order(2, TradeSide::BUY, 1, "100") }).await.unwrap();
+        let rx = trader.waiters().register(3);
+        trader.feed(new_order(3, TradeSide::SELL, 1, "100")).await.unwrap();
+        assert_eq!(rx.await.unwrap().trades.len(), 1);
+        // 卖单扫到80，偏离均价100超过10%，拒绝并暂停交易对
+        let rx = trader.waiters().register(4);
+        trader.feed(Order { qty: 3, price: BigDecimal::from(0), ord_type: OrderType::MARKET, tif: OrderTimeInForce::IOC, ..new_order(4, TradeSide::SELL, 1, "100") }).await.unwrap();
+        assert_eq!(rx.await.unwrap().trades[0].reject, Some(RejectCode::PRICE_COLLAR));
+        assert!(trader.pause_state().rejects());
+        let (tx, hash) = oneshot::channel();
+        trader.control().send(TraderControl::StateHash(tx)).unwrap();
+        assert_eq!(hash.await.unwrap().orders, 1);
+        ctx.send(true).unwrap();
+        handler.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn audit_test() {
+        let dir = std::env::temp_dir().join(format!("loom-trader-audit-test-{}", std::process::id()));
+        let options = TraderOptions::default().with_audit(AuditLog::open(&dir, "LOOM-USDT-SPOT").unwrap());
+        let trader = Trader::with_options("LOOM-USDT-SPOT", SymbolId(0), TradeConsumer::Console(ConsoleConsumer {}), options);
+        let (ctx, _) = broadcast::channel(1);
+        let handler = trader.launch(ctx.subscribe());
+        trader.feed(new_order(1, TradeSide::BUY, 1, "100")).await.unwrap();
+        let rx = trader.waiters().register(2);
+        trader.feed(new_order(2, TradeSide::SELL, 1, "100")).await.unwrap();
+        rx.await.unwrap();
+        ctx.send(true).unwrap();
+        handler.await.unwrap();
+        // 两个请求和一笔成交
+        let path = AuditLog::path(&dir, "LOOM-USDT-SPOT");
+        assert_eq!(audit::verify(&path).unwrap().records, 3);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn clock_test() {
+        let options = TraderOptions::default().with_clock(Arc::new(ManualClock::new(1000)));
+        let trader = Trader::with_options("LOOM-USDT-SPOT", SymbolId(0), TradeConsumer::Console(ConsoleConsumer {}), options);
+        let (ctx, _) = broadcast::channel(1);
+        let handler = trader.launch(ctx.subscribe());
+        trader.feed(new_order(1, TradeSide::BUY, 1, "100")).await.unwrap();
+        let rx = trader.waiters().register(2);
+        trader.feed(new_order(2, TradeSide::SELL, 1, "100")).await.unwrap();
+        assert_eq!(rx.await.unwrap().trades[0].ts, 1000);
+        ctx.send(true).unwrap();
+        handler.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn backpressure_test() {
+        for mode in [TraderMode::Tokio, TraderMode::Native { core: None }] {
+            let options = TraderOptions::default()
+                .with_mode(mode)
+                .with_capacity(2)
+                .with_backpressure(Backpressure::Shed);
+            // 未启动的交易员不会消费请求
+            let trader = Trader::with_options("LOOM-USDT-SPOT", SymbolId(0), TradeConsumer::Console(ConsoleConsumer {}), options);
+            trader.feed(new_order(1, TradeSide::BUY, 1, "100")).await.unwrap();
+            trader.feed(new_order(2, TradeSide::BUY, 1, "100")).await.unwrap();
+            let err = trader.feed(new_order(3, TradeSide::BUY, 1, "100")).await.unwrap_err();
+            assert_eq!(err.downcast_ref::<QueueFull>().unwrap().order.id, 3);
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+/// 交易对从缓存恢复的进度
+#[derive(Debug, Default)]
+pub struct RecoveryProgress {
+    /// 缓存中的订单数量
+    total: AtomicUsize,
+    /// 已读取的订单数量
+    read: AtomicUsize,
+}
+
+impl RecoveryProgress {
+    pub fn start(&self, total: usize) {
+        self.total.store(total, Ordering::Relaxed);
+        self.read.store(0, Ordering::Relaxed);
+    }
+
+    /// 读取了一个订单，返回已读取的数量
+    pub fn advance(&self) -> usize {
+        self.read.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    pub fn total(&self) -> usize {
+        self.total.load(Ordering::Relaxed)
+    }
+
+    pub fn read(&self) -> usize {
+        self.read.load(Ordering::Relaxed)
+    }
+}
+
+/// 交易对启动时的恢复方式，备机总是从缓存恢复后跟随主机日志
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub enum RecoveryPolicy {
+    /// 缓存中的挂单按到达顺序重新经过撮合
+    #[default]
+    Refeed,
+    /// 从镜像恢复订单簿，path为镜像文件或转储目录，目录时使用其中最新的镜像，缓存按订单簿重写
+    Image { path: String },
+    /// 回放主机写入的日志流重建订单簿，配置镜像时只回放镜像之后的请求，缓存按订单簿重写
+    ///
+    /// 日志流按长度裁剪，未配置镜像时需保证日志流从交易对开始交易起未被裁剪
+    Journal {
+        #[serde(default)]
+        image: Option<String>,
+    },
+    /// 以空订单簿启动，撤销缓存中的挂单并推送撤单结果
+    Empty,
+}
+
+/// 交易对恢复状态
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub struct RecoveryStatus {
+    /// 缓存中的订单数量
+    pub total: usize,
+    /// 已读取的订单数量
+    pub read: usize,
+    /// 是否已完成恢复并接受撮合请求
+    pub ready: bool,
+}
+
+/// 启动预热，开始后需等待预期的交易对全部恢复且消费器连通才对外提供服务，未开始时视为已完成
+#[derive(Debug, Default)]
+pub struct Warmup {
+    started: AtomicBool,
+    done: AtomicBool,
+    /// 预期的交易对
+    symbols: Mutex<Vec<String>>,
+    /// 消费器探测结果，未探测时为空
+    consumers: Mutex<Option<Result<(), String>>>,
+}
+
+impl Warmup {
+    /// 开始预热，symbols为启动时需恢复的交易对
+    pub fn begin(&self, symbols: Vec<String>) {
+        *self.symbols.lock().unwrap() = symbols;
+        self.done.store(false, Ordering::Release);
+        self.started.store(true, Ordering::Release);
+    }
+
+    /// 记录消费器探测结果
+    pub fn set_consumers(&self, result: Result<(), String>) {
+        *self.consumers.lock().unwrap() = Some(result);
+    }
+
+    /// 消费器探测结果，未探测时为空
+    pub fn consumers(&self) -> Option<Result<(), String>> {
+        self.consumers.lock().unwrap().clone()
+    }
+
+    pub fn symbols(&self) -> Vec<String> {
+        self.symbols.lock().unwrap().clone()
+    }
+
+    /// 完成预热
+    pub fn finish(&self) {
+        self.done.store(true, Ordering::Release);
+    }
+
+    pub fn is_warm(&self) -> bool {
+        !self.started.load(Ordering::Acquire) || self.done.load(Ordering::Acquire)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::warmup::{RecoveryProgress, Warmup};
+
+    #[test]
+    fn warmup_test() {
+        let warmup = Warmup::default();
+        assert!(warmup.is_warm());
+        warmup.begin(vec![String::from("LOOM-USDT-SPOT")]);
+        assert!(!warmup.is_warm());
+        assert_eq!(warmup.consumers(), None);
+        warmup.set_consumers(Err(String::from("connection refused")));
+        warmup.set_consumers(Ok(()));
+        assert_eq!(warmup.consumers(), Some(Ok(())));
+        warmup.finish();
+        assert!(warmup.is_warm());
+
+        let progress = RecoveryProgress::default();
+        progress.start(2);
+        assert_eq!((progress.advance(), progress.advance(), progress.total()), (1, 2, 2));
+    }
+}
@@ -0,0 +1,176 @@
+use std::fmt::Write as _;
+
+use bigdecimal::BigDecimal;
+
+use loom_core::market::{MarketBook, MatchTrades};
+use loom_core::order::{Order, OrderAction, OrderState, OrderTimeInForce, OrderType, TradeSide};
+
+use crate::reference::{Event, ReferenceBook, RestingKey};
+
+/// xorshift64*伪随机数，种子相同时生成的请求序列相同，便于复现失败的用例
+#[derive(Debug, Clone)]
+pub struct XorShift(u64);
+
+impl XorShift {
+    pub fn new(seed: u64) -> XorShift {
+        // 种子为0时xorshift只会输出0
+        XorShift(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// [lo, hi]内的随机数
+    pub fn range(&mut self, lo: u64, hi: u64) -> u64 {
+        lo + self.next_u64() % (hi - lo + 1)
+    }
+}
+
+/// 生成随机请求序列，价格集中在少数档位以制造同价排队、多档成交和部分成交
+pub fn generate(seed: u64, len: usize) -> Vec<Order> {
+    let mut rng = XorShift::new(seed);
+    let mut orders = Vec::with_capacity(len);
+    let mut next_id = 1;
+    for ts in 1..=len as u128 {
+        let roll = rng.range(0, 99);
+        let mut order = Order {
+            id: next_id,
+            symbol: "LOOM-USDT-SPOT".into(),
+            side: if rng.range(0, 1) == 0 { TradeSide::BUY } else { TradeSide::SELL },
+            qty: rng.range(1, 10),
+            price: BigDecimal::from(rng.range(95, 105)),
+            acc_fill_qty: 0,
+            ord_type: OrderType::LIMIT,
+            ts,
+            update_ts: ts,
+            state: OrderState::LIVE,
+            tif: OrderTimeInForce::GTC,
+            action: OrderAction::PLACE,
+            account: None,
+            seq: 0,
+            expire_ts: 0,
+            ts_ns: 0,
+            valid_until_ts: 0,
+            tag: None,
+        };
+        match roll {
+            // 撤单和减量指向之前的订单，可能已不在订单簿中
+            0..=24 if next_id > 1 => {
+                order.id = rng.range(1, next_id - 1);
+                order.action = if roll < 15 { OrderAction::CANCEL } else { OrderAction::REDUCE };
+                order.qty = rng.range(1, 6);
+            }
+            // 重复的订单ID
+            25..=27 if next_id > 1 => order.id = rng.range(1, next_id - 1),
+            28..=39 => order.tif = OrderTimeInForce::IOC,
+            40..=49 => order.tif = OrderTimeInForce::FOK,
+            50..=57 => {
+                order.ord_type = OrderType::MARKET;
+                order.price = BigDecimal::from(0);
+                order.tif = if roll < 54 { OrderTimeInForce::IOC } else { OrderTimeInForce::FOK };
+            }
+            _ => {}
+        }
+        if order.action == OrderAction::PLACE && order.id == next_id {
+            next_id += 1;
+        }
+        orders.push(order);
+    }
+    orders
+}
+
+fn book_side(book: &MarketBook, side: TradeSide) -> Vec<RestingKey> {
+    let orders = match side {
+        TradeSide::BUY => book.bids(),
+        TradeSide::SELL => book.asks(),
+    };
+    orders.iter().map(|o| (o.id, o.price.normalized(), o.remain())).collect()
+}
+
+/// 依次交给MarketBook和参考实现，返回第一个结果或订单簿不一致的请求位置和说明
+pub fn compare(orders: &[Order]) -> Result<(), (usize, String)> {
+    let mut book = MarketBook::new("LOOM-USDT-SPOT");
+    let mut reference = ReferenceBook::new();
+    for (i, order) in orders.iter().enumerate() {
+        let mut trades = MatchTrades::new();
+        match order.action {
+            OrderAction::PLACE => book.try_match_into(order.clone(), &mut trades),
+            OrderAction::CANCEL => {
+                book.try_cancel_into(order.clone(), &mut trades);
+            }
+            OrderAction::REDUCE => {
+                book.try_reduce_into(order.clone(), &mut trades);
+            }
+        }
+        let actual: Vec<Event> = trades.iter().map(Event::from_trade).collect();
+        let expected = reference.apply(order.clone());
+        if actual != expected {
+            return Err((i, format!("results differ\n  expected: {:?}\n  actual:   {:?}", expected, actual)));
+        }
+        for side in [TradeSide::BUY, TradeSide::SELL] {
+            let (expected, actual) = (reference.side(side), book_side(&book, side));
+            if actual != expected {
+                return Err((i, format!("{} book differs\n  expected: {:?}\n  actual:   {:?}", side, expected, actual)));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 执行一个种子的用例，失败时缩短为仍然失败的最短前缀并返回复现信息
+pub fn check_seed(seed: u64, len: usize) -> Result<(), String> {
+    let orders = generate(seed, len);
+    let (index, _) = match compare(&orders) {
+        Ok(_) => return Ok(()),
+        Err(e) => e,
+    };
+    // 不一致发生在index处，去掉之前不影响结果的请求
+    let mut minimal: Vec<Order> = orders[..=index].to_vec();
+    let mut i = 0;
+    while i + 1 < minimal.len() {
+        let mut candidate = minimal.clone();
+        candidate.remove(i);
+        if compare(&candidate).is_err() {
+            minimal = candidate;
+        } else {
+            i += 1;
+        }
+    }
+    let (_, reason) = compare(&minimal).unwrap_err();
+    let mut out = format!("seed {} failed at request {}, minimal sequence:\n", seed, index);
+    for order in minimal.iter() {
+        let _ = writeln!(out, "  {} {} {} {} {} qty={} px={}", order.action, order.id, order.side, order.ord_type, order.tif, order.qty, order.price);
+    }
+    out.push_str(&reason);
+    Err(out)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::differential::{check_seed, generate, XorShift};
+
+    /// 用例数量，可通过LOOM_DIFF_CASES增加
+    fn cases() -> u64 {
+        std::env::var("LOOM_DIFF_CASES").ok().and_then(|v| v.parse().ok()).unwrap_or(300)
+    }
+
+    #[test]
+    fn generate_test() {
+        assert_eq!(generate(7, 50), generate(7, 50));
+        let mut rng = XorShift::new(0);
+        assert!((0..100).map(|_| rng.range(3, 5)).all(|v| (3..=5).contains(&v)));
+    }
+
+    #[test]
+    fn differential_test() {
+        for seed in 0..cases() {
+            if let Err(e) = check_seed(seed, 200) {
+                panic!("{}", e);
+            }
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use std::path::{Path, PathBuf};
+
+use crate::scenario::Scenario;
+
+/// 设置该环境变量后用当前输出覆盖golden文件
+pub const BLESS_ENV: &str = "LOOM_BLESS";
+
+/// 场景文件对应的golden文件，如basic.json对应basic.golden
+pub fn golden_path(scenario: &Path) -> PathBuf {
+    scenario.with_extension("golden")
+}
+
+/// 执行场景并与golden文件比对，一致时返回None，否则返回差异
+pub fn check(scenario: &Path) -> anyhow::Result<Option<String>> {
+    let actual = Scenario::load(scenario)?.run();
+    let path = golden_path(scenario);
+    if std::env::var_os(BLESS_ENV).is_some() {
+        std::fs::write(&path, &actual)?;
+        return Ok(None);
+    }
+    let expected = match std::fs::read_to_string(&path) {
+        Ok(expected) => expected,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    if expected == actual {
+        return Ok(None);
+    }
+    Ok(Some(diff(&expected, &actual)))
+}
+
+/// 比对目录下所有.json场景，返回不一致的场景及差异
+pub fn check_dir(dir: &Path) -> anyhow::Result<Vec<String>> {
+    let mut scenarios: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    scenarios.sort();
+    let mut failures = Vec::new();
+    for scenario in scenarios {
+        if let Some(diff) = check(&scenario)? {
+            failures.push(format!("scenario {} differs from {} (rerun with {}=1 to accept):\n{}",
+                                  scenario.display(), golden_path(&scenario).display(), BLESS_ENV, diff));
+        }
+    }
+    Ok(failures)
+}
+
+/// 按行比较，-为golden中的行，+为实际输出的行
+pub fn diff(expected: &str, actual: &str) -> String {
+    let a: Vec<&str> = expected.lines().collect();
+    let b: Vec<&str> = actual.lines().collect();
+    // 最长公共子序列
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            out.push_str(&format!("  {}\n", a[i]));
+            i += 1;
+            j += 1;
+        } else if j < b.len() && (i == a.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            out.push_str(&format!("+ {}\n", b[j]));
+            j += 1;
+        } else {
+            out.push_str(&format!("- {}\n", a[i]));
+            i += 1;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use crate::golden::diff;
+
+    #[test]
+    fn diff_test() {
+        assert_eq!(diff("a\nb\nc\n", "a\nx\nc\n"), "  a\n+ x\n- b\n  c\n");
+        assert_eq!(diff("", "a\n"), "+ a\n");
+    }
+}
@@ -0,0 +1,4 @@
+pub mod differential;
+pub mod golden;
+pub mod reference;
+pub mod scenario;
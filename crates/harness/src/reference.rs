@@ -0,0 +1,207 @@
+use std::collections::HashSet;
+
+use bigdecimal::BigDecimal;
+
+use loom_core::market::{MatchTrade, RejectCode};
+use loom_core::order::{Order, OrderAction, OrderState, OrderTimeInForce, OrderType, TradeSide};
+
+/// 与撮合实现无关的撮合结果，用于比对参考实现和MarketBook
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Event {
+    pub id: u64,
+    pub qty: u64,
+    pub px: BigDecimal,
+    pub taker_oid: u64,
+    pub maker_oid: u64,
+    pub taker_state: OrderState,
+    pub maker_state: OrderState,
+    pub reduce_qty: u64,
+    pub reject: Option<RejectCode>,
+    pub taker_remain: u64,
+    pub maker_remain: u64,
+}
+
+impl Event {
+    pub fn from_trade(trade: &MatchTrade) -> Event {
+        Event {
+            id: trade.id,
+            qty: trade.qty,
+            px: trade.px.normalized(),
+            taker_oid: trade.taker_oid,
+            maker_oid: trade.maker_oid,
+            taker_state: trade.taker_state,
+            maker_state: trade.maker_state,
+            reduce_qty: trade.reduce_qty,
+            reject: trade.reject,
+            taker_remain: trade.taker_remain,
+            maker_remain: trade.maker_remain,
+        }
+    }
+
+    /// 只涉及taker订单的结果
+    fn taker_result(order: &Order, state: OrderState) -> Event {
+        Event {
+            id: 0,
+            qty: 0,
+            px: BigDecimal::from(0),
+            taker_oid: order.id,
+            maker_oid: 0,
+            taker_state: state,
+            maker_state: OrderState::INIT,
+            reduce_qty: 0,
+            reject: None,
+            taker_remain: 0,
+            maker_remain: 0,
+        }
+    }
+}
+
+/// 挂单，(订单ID, 价格, 剩余数量)，按撮合优先级比对订单簿
+pub type RestingKey = (u64, BigDecimal, u64);
+
+/// 暴力撮合参考实现，挂单按到达顺序保存在数组中，每次线性查找最优挂单，
+/// 只实现撮合规则本身，用于与MarketBook做差异测试，不支持GTD和交易对规格检查
+#[derive(Debug, Default)]
+pub struct ReferenceBook {
+    /// 按到达顺序排列的挂单
+    resting: Vec<Order>,
+    /// 下过的订单ID
+    seen: HashSet<u64>,
+    trade_id: u64,
+}
+
+impl ReferenceBook {
+    pub fn new() -> ReferenceBook {
+        ReferenceBook::default()
+    }
+
+    pub fn apply(&mut self, order: Order) -> Vec<Event> {
+        match order.action {
+            OrderAction::PLACE => self.place(order),
+            OrderAction::CANCEL => self.cancel(order.id).into_iter().collect(),
+            OrderAction::REDUCE => self.reduce(order.id, order.qty).into_iter().collect(),
+        }
+    }
+
+    /// 买方或卖方挂单，按撮合优先级排列
+    pub fn side(&self, side: TradeSide) -> Vec<RestingKey> {
+        let mut orders: Vec<(usize, &Order)> = self.resting.iter().enumerate().filter(|(_, o)| o.side == side).collect();
+        orders.sort_by(|(ia, a), (ib, b)| {
+            let by_price = match side {
+                TradeSide::BUY => b.price.cmp(&a.price),
+                TradeSide::SELL => a.price.cmp(&b.price),
+            };
+            by_price.then(ia.cmp(ib))
+        });
+        orders.into_iter().map(|(_, o)| (o.id, o.price.normalized(), o.remain())).collect()
+    }
+
+    /// 可与taker成交的最优挂单在数组中的位置
+    fn best_maker(&self, taker: &Order) -> Option<usize> {
+        let mut best: Option<usize> = None;
+        for (i, maker) in self.resting.iter().enumerate() {
+            if maker.side == taker.side {
+                continue;
+            }
+            let crosses = taker.ord_type == OrderType::MARKET || match taker.side {
+                TradeSide::BUY => taker.price >= maker.price,
+                TradeSide::SELL => taker.price <= maker.price,
+            };
+            if !crosses {
+                continue;
+            }
+            // 价格更优的挂单优先，同价格先到先得
+            let better = match best.map(|b| &self.resting[b]) {
+                None => true,
+                Some(current) => match taker.side {
+                    TradeSide::BUY => maker.price < current.price,
+                    TradeSide::SELL => maker.price > current.price,
+                },
+            };
+            if better {
+                best = Some(i);
+            }
+        }
+        best
+    }
+
+    fn place(&mut self, mut taker: Order) -> Vec<Event> {
+        let mut events = Vec::new();
+        if !self.seen.insert(taker.id) {
+            events.push(Event { reject: Some(RejectCode::DUPLICATE_ID), ..Event::taker_result(&taker, OrderState::CANCELED) });
+            return events;
+        }
+        if taker.tif == OrderTimeInForce::FOK {
+            let available: u64 = self.resting.iter()
+                .filter(|maker| maker.side != taker.side)
+                .filter(|maker| taker.ord_type == OrderType::MARKET || match taker.side {
+                    TradeSide::BUY => taker.price >= maker.price,
+                    TradeSide::SELL => taker.price <= maker.price,
+                })
+                .map(|maker| maker.remain())
+                .sum();
+            if available < taker.qty {
+                events.push(Event::taker_result(&taker, OrderState::CANCELED));
+                return events;
+            }
+        }
+        while taker.remain() > 0 {
+            let i = match self.best_maker(&taker) {
+                Some(i) => i,
+                None => break,
+            };
+            let maker = &mut self.resting[i];
+            let qty = taker.remain().min(maker.remain());
+            maker.acc_fill_qty += qty;
+            maker.state = if maker.remain() > 0 { OrderState::PARTIAL_FILLED } else { OrderState::FULL_FILLED };
+            taker.acc_fill_qty += qty;
+            taker.state = if taker.remain() > 0 { OrderState::PARTIAL_FILLED } else { OrderState::FULL_FILLED };
+            self.trade_id += 1;
+            events.push(Event {
+                id: self.trade_id,
+                qty,
+                px: maker.price.normalized(),
+                taker_oid: taker.id,
+                maker_oid: maker.id,
+                taker_state: taker.state,
+                maker_state: maker.state,
+                reduce_qty: 0,
+                reject: None,
+                taker_remain: taker.remain(),
+                maker_remain: maker.remain(),
+            });
+            if maker.remain() == 0 {
+                self.resting.remove(i);
+            }
+        }
+        if taker.remain() > 0 {
+            match taker.tif {
+                OrderTimeInForce::GTC | OrderTimeInForce::GTD | OrderTimeInForce::GTX if taker.ord_type == OrderType::LIMIT => self.resting.push(taker),
+                OrderTimeInForce::GTC | OrderTimeInForce::GTD | OrderTimeInForce::GTX => {}
+                _ if taker.acc_fill_qty == 0 => events.push(Event::taker_result(&taker, OrderState::CANCELED)),
+                _ => events.push(Event::taker_result(&taker, OrderState::PARTIAL_CANCELLED)),
+            }
+        }
+        events
+    }
+
+    fn cancel(&mut self, id: u64) -> Option<Event> {
+        let i = self.resting.iter().position(|o| o.id == id)?;
+        let order = self.resting.remove(i);
+        let state = if order.acc_fill_qty > 0 { OrderState::PARTIAL_CANCELLED } else { OrderState::CANCELED };
+        Some(Event::taker_result(&order, state))
+    }
+
+    fn reduce(&mut self, id: u64, qty: u64) -> Option<Event> {
+        let order = self.resting.iter_mut().find(|o| o.id == id)?;
+        if qty >= order.remain() {
+            return self.cancel(id);
+        }
+        order.qty -= qty;
+        Some(Event {
+            reduce_qty: qty,
+            taker_remain: order.remain(),
+            ..Event::taker_result(order, order.state)
+        })
+    }
+}
@@ -0,0 +1,215 @@
+use std::fmt::Write as _;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use bigdecimal::{BigDecimal, Zero};
+use serde::{Deserialize, Serialize};
+
+use loom_core::clock::ManualClock;
+use loom_core::market::{MarketBook, MatchTrade, MatchTrades};
+use loom_core::order::{Order, OrderAction, OrderState, OrderTimeInForce, OrderType, TradeSide};
+use loom_core::snapshot::LevelSnapshot;
+use loom_core::symbol::SymbolSpec;
+
+/// 场景的默认交易对
+const DEFAULT_SYMBOL: &str = "LOOM-USDT-SPOT";
+
+/// 场景中的一个请求，未设置的项使用常用默认值
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScenarioOrder {
+    pub id: u64,
+    #[serde(default = "default_side")]
+    pub side: TradeSide,
+    #[serde(default)]
+    pub qty: u64,
+    #[serde(default)]
+    pub price: Option<BigDecimal>,
+    #[serde(default = "default_ord_type")]
+    pub ord_type: OrderType,
+    /// 限价单默认GTC，市价单默认IOC
+    #[serde(default)]
+    pub tif: Option<OrderTimeInForce>,
+    #[serde(default = "default_action")]
+    pub action: OrderAction,
+    #[serde(default)]
+    pub account: Option<String>,
+    /// 请求时间，默认为上一个请求时间加1
+    #[serde(default)]
+    pub ts: Option<u128>,
+    #[serde(default)]
+    pub expire_ts: Option<u128>,
+}
+
+fn default_side() -> TradeSide {
+    TradeSide::BUY
+}
+
+fn default_ord_type() -> OrderType {
+    OrderType::LIMIT
+}
+
+fn default_action() -> OrderAction {
+    OrderAction::PLACE
+}
+
+/// 撮合场景，按顺序执行请求，输出每个请求的撮合结果和最终订单簿
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Scenario {
+    #[serde(default)]
+    pub description: String,
+    /// 交易对规格，默认使用默认规格
+    #[serde(default)]
+    pub spec: Option<SymbolSpec>,
+    pub orders: Vec<ScenarioOrder>,
+}
+
+impl ScenarioOrder {
+    fn to_order(&self, symbol: &str, ts: u128) -> Order {
+        let tif = self.tif.unwrap_or(match self.ord_type {
+            OrderType::MARKET => OrderTimeInForce::IOC,
+            OrderType::LIMIT => OrderTimeInForce::GTC,
+        });
+        Order {
+            id: self.id,
+            symbol: symbol.into(),
+            side: self.side,
+            qty: self.qty,
+            price: self.price.clone().unwrap_or_default(),
+            acc_fill_qty: 0,
+            ord_type: self.ord_type,
+            ts,
+            update_ts: ts,
+            state: OrderState::LIVE,
+            tif,
+            action: self.action,
+            account: self.account.clone(),
+            seq: 0,
+            expire_ts: self.expire_ts.unwrap_or(0),
+            ts_ns: 0,
+            valid_until_ts: 0,
+            tag: None,
+        }
+    }
+}
+
+impl Scenario {
+    /// 读取JSON格式的场景文件
+    pub fn load(path: &Path) -> anyhow::Result<Scenario> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("read scenario failed, path={}, err={}", path.display(), e))?;
+        serde_json::from_str(&contents).map_err(|e| anyhow!("invalid scenario, path={}, err={}", path.display(), e))
+    }
+
+    /// 执行场景，返回文本格式的结果，时间取请求时间，相同场景的结果相同
+    pub fn run(&self) -> String {
+        let spec = self.spec.clone().unwrap_or_else(|| SymbolSpec::new(DEFAULT_SYMBOL));
+        let decimals = spec.price_decimals;
+        let symbol = spec.symbol.clone();
+        let clock = Arc::new(ManualClock::new(0));
+        let mut book = MarketBook::with_spec(spec).with_clock(clock.clone());
+        let mut out = String::new();
+        let mut ts = 0;
+        for request in self.orders.iter() {
+            ts = request.ts.unwrap_or(ts + 1);
+            clock.set(ts as u64);
+            let order = request.to_order(&symbol, ts);
+            let _ = writeln!(out, "> {}", render_order(&order, decimals));
+            let mut trades = MatchTrades::new();
+            book.expire_into(&mut trades);
+            match order.action {
+                OrderAction::PLACE => book.try_match_into(order, &mut trades),
+                OrderAction::CANCEL => {
+                    book.try_cancel_into(order, &mut trades);
+                }
+                OrderAction::REDUCE => {
+                    book.try_reduce_into(order, &mut trades);
+                }
+            }
+            for trade in trades.iter() {
+                let _ = writeln!(out, "  {}", render_trade(trade, decimals));
+            }
+        }
+        let snapshot = book.snapshot();
+        let _ = writeln!(out, "book last_px={}", format_px(&snapshot.px, decimals));
+        // 卖方从高到低，与买方一起按价格从高到低排列
+        for level in snapshot.asks.iter().rev() {
+            let _ = writeln!(out, "  ask {}", render_level(level, decimals));
+        }
+        for level in snapshot.bids.iter() {
+            let _ = writeln!(out, "  bid {}", render_level(level, decimals));
+        }
+        out
+    }
+}
+
+/// 价格去掉末尾的0
+fn format_px(px: &BigDecimal, decimals: u32) -> String {
+    if px.is_zero() {
+        return String::from("0");
+    }
+    let s = px.with_scale(decimals as i64).to_string();
+    match s.contains('.') {
+        true => s.trim_end_matches('0').trim_end_matches('.').to_string(),
+        false => s,
+    }
+}
+
+fn render_order(order: &Order, decimals: u32) -> String {
+    match order.action {
+        OrderAction::PLACE if order.ord_type == OrderType::MARKET => format!("{} {} {} {} {} qty={}", order.action, order.id, order.side, order.ord_type, order.tif, order.qty),
+        OrderAction::PLACE => format!("{} {} {} {} {} qty={} px={}", order.action, order.id, order.side, order.ord_type, order.tif, order.qty,
+                                      format_px(&order.price, decimals)),
+        OrderAction::CANCEL => format!("{} {}", order.action, order.id),
+        OrderAction::REDUCE => format!("{} {} qty={}", order.action, order.id, order.qty),
+    }
+}
+
+fn render_trade(trade: &MatchTrade, decimals: u32) -> String {
+    if let Some(code) = &trade.reject {
+        format!("reject oid={} code={:?}", trade.taker_oid, code)
+    } else if trade.is_reduce() {
+        format!("reduce oid={} qty={} remain={} state={}", trade.taker_oid, trade.reduce_qty, trade.taker_remain, trade.taker_state)
+    } else if trade.qty == 0 {
+        let oid = if trade.maker_oid != 0 { trade.maker_oid } else { trade.taker_oid };
+        let state = if trade.maker_oid != 0 { trade.maker_state } else { trade.taker_state };
+        format!("result oid={} state={}", oid, state)
+    } else {
+        format!("fill id={} taker={} maker={} qty={} px={} taker_state={} maker_state={}",
+                trade.id, trade.taker_oid, trade.maker_oid, trade.qty, format_px(&trade.px, decimals), trade.taker_state, trade.maker_state)
+    }
+}
+
+fn render_level(level: &LevelSnapshot, decimals: u32) -> String {
+    format!("px={} qty={} orders={}", format_px(&level.px, decimals), level.qty, level.orders)
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use crate::golden;
+    use crate::scenario::Scenario;
+
+    #[test]
+    fn scenario_format_test() {
+        let scenario: Scenario = serde_json::from_str(r#"{
+            "orders": [
+                { "id": 1, "side": "SELL", "qty": 2, "price": "100.5" },
+                { "id": 2, "qty": 1, "price": "101" }
+            ]
+        }"#).unwrap();
+        let out = scenario.run();
+        assert!(out.starts_with("> PLACE 1 SELL LIMIT GTC qty=2 px=100.5\n"));
+        assert!(out.contains("  fill id=1 taker=2 maker=1 qty=1 px=100.5 taker_state=FULL_FILLED maker_state=PARTIAL_FILLED\n"));
+        assert!(out.ends_with("book last_px=100.5\n  ask px=100.5 qty=1 orders=1\n"));
+    }
+
+    /// 执行scenarios目录下的所有场景并与golden文件比对，设置LOOM_BLESS=1时重新生成golden文件
+    #[test]
+    fn golden_scenarios_test() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenarios");
+        let failures = golden::check_dir(&dir).unwrap();
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+}
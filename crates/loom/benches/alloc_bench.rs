@@ -0,0 +1,86 @@
+// 对比全局分配器在订单和成交频繁创建释放时的吞吐，每个分配器分别运行一次：
+//   cargo bench -p loom --bench alloc_bench
+//   cargo bench -p loom --bench alloc_bench --features jemalloc
+//   cargo bench -p loom --bench alloc_bench --features mimalloc
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use loom::allocator;
+use loom::handler_match::MatchOrderParam;
+use loom_core::market::MarketBook;
+
+const SYMBOL: &str = "LOOM-USDT-SPOT";
+
+/// 简单的伪随机数生成器，保证每个分配器处理的订单流一致
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+fn request(id: u64, rng: &mut XorShift) -> String {
+    let r = rng.next();
+    let side = if r.is_multiple_of(2) { "BUY" } else { "SELL" };
+    let tif = if (r >> 16).is_multiple_of(4) { "IOC" } else { "GTC" };
+    format!(
+        "{{\"id\":{},\"symbol\":\"{}\",\"side\":\"{}\",\"qty\":{},\"price\":{},\"ord_type\":\"LIMIT\",\"tif\":\"{}\",\"action\":\"PLACE\",\"ts\":1000,\"account\":\"acc-{}\"}}",
+        id, SYMBOL, side, 1 + (r >> 24) % 20, 9_950 + (r >> 8) % 100, tif, r % 64,
+    )
+}
+
+fn report(name: &str, iters: u64, elapsed: Duration) {
+    let per_op = elapsed.as_nanos() as f64 / iters as f64;
+    println!(
+        "{:<40} {:>10} iters {:>12.1} ns/op {:>12.0} ops/s",
+        format!("{}/{}", allocator::NAME, name), iters, per_op, 1e9 / per_op,
+    );
+}
+
+/// 只经过订单簿的混合订单流
+fn bench_book(depth: u64, iters: u64) {
+    let mut rng = XorShift(0xD1B54A32D192ED03);
+    let mut book = MarketBook::new(SYMBOL);
+    let orders: Vec<_> = (1..=depth + iters)
+        .map(|id| serde_json::from_str::<MatchOrderParam>(&request(id, &mut rng)).unwrap().to_order())
+        .collect();
+    let mut orders = orders.into_iter();
+    for order in orders.by_ref().take(depth as usize) {
+        book.try_match(order);
+    }
+    let start = Instant::now();
+    for order in orders {
+        black_box(book.try_match(order));
+    }
+    report(&format!("book/depth={}", depth), iters, start.elapsed());
+}
+
+/// 与服务端相同的请求路径：解析请求、撮合、序列化成交
+fn bench_request(depth: u64, iters: u64) {
+    let mut rng = XorShift(0xD1B54A32D192ED03);
+    let mut book = MarketBook::new(SYMBOL);
+    let requests: Vec<String> = (1..=depth + iters).map(|id| request(id, &mut rng)).collect();
+    for body in &requests[..depth as usize] {
+        book.try_match(serde_json::from_str::<MatchOrderParam>(body).unwrap().to_order());
+    }
+    let start = Instant::now();
+    for body in &requests[depth as usize..] {
+        let param = serde_json::from_str::<MatchOrderParam>(body).unwrap();
+        param.check().unwrap();
+        let trades = book.try_match(param.to_order());
+        black_box(serde_json::to_string(trades.as_slice()).unwrap());
+    }
+    report(&format!("request/depth={}", depth), iters, start.elapsed());
+}
+
+fn main() {
+    for depth in [1_000, 100_000] {
+        bench_book(depth, 200_000);
+        bench_request(depth, 200_000);
+    }
+}
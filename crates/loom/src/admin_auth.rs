@@ -0,0 +1,75 @@
+use std::sync::Arc;
+
+use axum::extract::{Request, State};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+
+use crate::config::AdminAuthConfig;
+
+/// 默认管理令牌请求头
+pub const DEFAULT_ADMIN_TOKEN_HEADER: &str = "x-admin-token";
+
+/// 管理接口鉴权，令牌与租户API Key相互独立
+#[derive(Debug, Clone)]
+pub struct AdminAuth {
+    tokens: Vec<String>,
+    header: String,
+}
+
+impl AdminAuth {
+    pub fn new(conf: &AdminAuthConfig) -> anyhow::Result<AdminAuth> {
+        if conf.tokens.is_empty() || conf.tokens.iter().any(|t| t.is_empty()) {
+            return Err(anyhow::anyhow!("admin tokens must not be empty"));
+        }
+        Ok(AdminAuth {
+            tokens: conf.tokens.clone(),
+            header: conf.header.as_deref().unwrap_or(DEFAULT_ADMIN_TOKEN_HEADER).to_ascii_lowercase(),
+        })
+    }
+
+    /// 令牌是否有效，逐字节比较全部令牌，耗时与匹配位置无关
+    pub fn verify(&self, token: Option<&str>) -> bool {
+        let Some(token) = token else {
+            return false;
+        };
+        self.tokens.iter().fold(false, |ok, t| ok | constant_eq(t.as_bytes(), token.as_bytes()))
+    }
+}
+
+fn constant_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// 管理接口鉴权中间件，令牌缺失或无效时返回401
+pub async fn admin_auth(State(auth): State<Arc<AdminAuth>>, req: Request, next: Next) -> Response {
+    let token = req.headers()
+        .get(auth.header.as_str())
+        .and_then(|v| v.to_str().ok());
+    if !auth.verify(token) {
+        return (StatusCode::UNAUTHORIZED, "invalid admin token").into_response();
+    }
+    next.run(req).await
+}
+
+/// 必须鉴权的管理接口在未配置管理令牌时返回403
+pub async fn required() -> Response {
+    (StatusCode::FORBIDDEN, "admin auth required, configure [server.admin_auth]").into_response()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::admin_auth::AdminAuth;
+    use crate::config::AdminAuthConfig;
+
+    #[test]
+    fn verify_test() {
+        let auth = AdminAuth::new(&AdminAuthConfig { tokens: vec![String::from("ops-1"), String::from("ops-2")], header: None }).unwrap();
+        assert!(auth.verify(Some("ops-2")));
+        assert!(!auth.verify(Some("ops-")));
+        assert!(!auth.verify(Some("ops-3")));
+        assert!(!auth.verify(None));
+        assert!(AdminAuth::new(&AdminAuthConfig { tokens: vec![], header: None }).is_err());
+        assert!(AdminAuth::new(&AdminAuthConfig { tokens: vec![String::new()], header: None }).is_err());
+    }
+}
@@ -0,0 +1,19 @@
+// 在库中声明全局分配器，loom可执行文件和基准测试使用同一个分配器
+
+/// jemalloc，同时启用两个特性时优先使用
+#[cfg(feature = "jemalloc")]
+#[global_allocator]
+static GLOBAL: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
+
+#[cfg(all(feature = "mimalloc", not(feature = "jemalloc")))]
+#[global_allocator]
+static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
+
+/// 当前使用的全局分配器，由`jemalloc`或`mimalloc`特性选择，都未启用时使用系统分配器
+pub const NAME: &str = if cfg!(feature = "jemalloc") {
+    "jemalloc"
+} else if cfg!(feature = "mimalloc") {
+    "mimalloc"
+} else {
+    "system"
+};
@@ -0,0 +1,378 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail};
+use bigdecimal::BigDecimal;
+
+use loom_core::market::{MarketBook, MatchTrades};
+use loom_core::order::{Order, OrderAction, OrderState, OrderTimeInForce, OrderType, TradeSide};
+use loom_core::symbol::SymbolSpec;
+use loom_engine::audit::{self, AuditLog};
+use loom_engine::archive::TradeArchive;
+use loom_engine::cache::CacheManager;
+use loom_engine::image::{self, EngineImage};
+use loom_engine::replay::Replayer;
+use loom_engine::report::{ReportFormat, TradeReporter};
+
+use crate::cli_replay::ReplayArgs;
+use crate::config::Config;
+
+pub const USAGE: &str = "usage: loom [--config <file>] [serve | snapshot --out <dir> | replay --journal <file> [--until seq] | verify-cache | rebuild-cache [--image <file>] [--journal <file>] | verify-audit [--symbol SYMBOL] | report --from <ms> --to <ms> [--symbol SYMBOL] [--format csv|json] [--out <dir>] | bench [--orders N] [--symbol SYMBOL]]";
+
+/// 子命令
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Command {
+    /// 启动撮合服务，未指定子命令时的默认行为
+    Serve,
+    /// 从缓存中的挂单生成市场镜像，无需启动服务
+    Snapshot { out: PathBuf },
+    /// 回放日志文件
+    Replay(ReplayArgs),
+    /// 检查缓存中的挂单能否恢复为合法的订单簿
+    VerifyCache,
+    /// 从镜像和日志重建订单簿，用重建后的挂单替换缓存中的订单
+    RebuildCache { image: Option<PathBuf>, journal: Option<PathBuf> },
+    /// 校验审计日志的哈希链
+    VerifyAudit { symbol: Option<String> },
+    /// 从成交归档导出[from, to)内的监管成交报表
+    Report { from: Option<u128>, to: Option<u128>, symbol: Option<String>, format: Option<ReportFormat>, out: Option<PathBuf> },
+    /// 在进程内对配置的交易对压测撮合性能
+    Bench { orders: u64, symbol: Option<String> },
+}
+
+/// 命令行参数
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Cli {
+    /// 配置文件，未指定时使用环境变量或默认路径
+    pub config: Option<String>,
+    pub command: Command,
+}
+
+impl Cli {
+    pub fn parse(args: impl Iterator<Item=String>) -> anyhow::Result<Cli> {
+        let mut args = args.peekable();
+        let mut config = None;
+        while args.peek().is_some_and(|a| a == "--config") {
+            args.next();
+            config = Some(args.next().ok_or_else(|| anyhow!("missing value for --config\n{}", USAGE))?);
+        }
+        let command = match args.next().as_deref() {
+            None | Some("serve") => Command::Serve,
+            Some("replay") => return Ok(Cli { config, command: Command::Replay(ReplayArgs::parse(args)?) }),
+            Some("snapshot") => Command::Snapshot { out: PathBuf::new() },
+            Some("verify-cache") => Command::VerifyCache,
+            Some("rebuild-cache") => Command::RebuildCache { image: None, journal: None },
+            Some("verify-audit") => Command::VerifyAudit { symbol: None },
+            Some("report") => Command::Report { from: None, to: None, symbol: None, format: None, out: None },
+            Some("bench") => Command::Bench { orders: 100_000, symbol: None },
+            Some(other) => bail!("unknown command {}\n{}", other, USAGE),
+        };
+        let command = Self::parse_flags(command, args)?;
+        Ok(Cli { config, command })
+    }
+
+    fn parse_flags(mut command: Command, mut args: impl Iterator<Item=String>) -> anyhow::Result<Command> {
+        while let Some(flag) = args.next() {
+            let value = args.next().ok_or_else(|| anyhow!("missing value for {}\n{}", flag, USAGE))?;
+            match (&mut command, flag.as_str()) {
+                (Command::Snapshot { out }, "--out") => *out = PathBuf::from(value),
+                (Command::Bench { orders, .. }, "--orders") => *orders = value.parse::<u64>()?.max(1),
+                (Command::Bench { symbol, .. }, "--symbol") => *symbol = Some(value),
+                (Command::VerifyAudit { symbol }, "--symbol") => *symbol = Some(value),
+                (Command::RebuildCache { image, .. }, "--image") => *image = Some(PathBuf::from(value)),
+                (Command::RebuildCache { journal, .. }, "--journal") => *journal = Some(PathBuf::from(value)),
+                (Command::Report { from, .. }, "--from") => *from = Some(value.parse()?),
+                (Command::Report { to, .. }, "--to") => *to = Some(value.parse()?),
+                (Command::Report { symbol, .. }, "--symbol") => *symbol = Some(value),
+                (Command::Report { format, .. }, "--format") => *format = Some(value.parse()?),
+                (Command::Report { out, .. }, "--out") => *out = Some(PathBuf::from(value)),
+                _ => bail!("unknown flag {}\n{}", flag, USAGE),
+            }
+        }
+        if matches!(&command, Command::Snapshot { out } if out.as_os_str().is_empty()) {
+            bail!("missing --out\n{}", USAGE);
+        }
+        if matches!(&command, Command::RebuildCache { image: None, journal: None }) {
+            bail!("missing --image or --journal\n{}", USAGE);
+        }
+        if let Command::Report { from, to, .. } = &command {
+            match (from, to) {
+                (Some(from), Some(to)) if from < to => {}
+                (Some(_), Some(_)) => bail!("--from must be before --to\n{}", USAGE),
+                _ => bail!("missing --from or --to\n{}", USAGE),
+            }
+        }
+        Ok(command)
+    }
+}
+
+/// 按服务启动时的恢复流程，从缓存中加载配置的交易对的挂单
+async fn load_books(config: &Config, cache: &CacheManager) -> anyhow::Result<Vec<MarketBook>> {
+    let mut books = Vec::new();
+    for (_, spec) in config.markets() {
+        let symbol = spec.symbol.clone();
+        let trade_id = cache.get_trade_id(&symbol).await?;
+        let mut book = MarketBook::with_spec(spec).with_trade_id(trade_id);
+        let mut ids = Vec::new();
+        cache.get_ids(&symbol, |id| {
+            ids.push(id);
+            Ok(())
+        }).await?;
+        let mut orders = cache.get_orders_by_ids(&symbol, &ids).await?;
+        orders.sort_by_key(|o| (o.arrival_ns(), o.id));
+        for order in orders {
+            book.try_match(order);
+        }
+        books.push(book);
+    }
+    Ok(books)
+}
+
+/// 生成市场镜像写入out目录，可用于快速重启或/admin/restore
+pub async fn snapshot(config: &Config, cache: &CacheManager, out: &Path) -> anyhow::Result<()> {
+    let books = load_books(config, cache).await?;
+    let image = EngineImage::new(books.iter().map(MarketBook::image).collect());
+    let path = image::write_image(out, &image)?;
+    for (symbol, orders) in image.orders() {
+        println!("{} orders={}", symbol, orders);
+    }
+    println!("image written to {}", path.display());
+    Ok(())
+}
+
+/// 检查缓存中的挂单：恢复后的订单簿满足不变式且没有交叉，输出状态哈希和主机保存的检查点用于比对
+pub async fn verify_cache(config: &Config, cache: &CacheManager) -> anyhow::Result<()> {
+    let mut failed = 0;
+    for book in load_books(config, cache).await? {
+        let hash = book.state_hash();
+        let checkpoint = cache.get_checkpoint(&book.symbol).await?;
+        let result = match book.verify() {
+            Ok(_) => String::from("ok"),
+            Err(e) => {
+                failed += 1;
+                format!("violated: {}", e)
+            }
+        };
+        println!("{} orders={} seq={} hash={} {}", &book.symbol, hash.orders, hash.seq, &hash.hash, result);
+        if let Some(checkpoint) = checkpoint {
+            println!("{} checkpoint seq={} orders={} hash={}", &book.symbol, checkpoint.seq, checkpoint.orders, &checkpoint.hash);
+        }
+    }
+    if failed > 0 {
+        bail!("{} symbols failed verification", failed);
+    }
+    Ok(())
+}
+
+/// 从镜像恢复订单簿后回放日志中镜像之后的请求，用重建的挂单替换缓存中的订单哈希和订单ID集合
+///
+/// 只处理镜像或日志中出现且已配置的交易对，其他交易对的缓存不受影响，成交ID高水位只会提高
+pub async fn rebuild_cache(config: &Config, cache: &CacheManager, image: Option<&Path>, journal: Option<&Path>) -> anyhow::Result<()> {
+    let markets = config.markets();
+    let configured: HashSet<&str> = markets.iter().map(|(_, spec)| spec.symbol.as_str()).collect();
+    let mut replayer = markets.iter().fold(Replayer::new(), |replayer, (_, spec)| replayer.with_spec(spec.clone()));
+    if let Some(path) = image {
+        for book in image::read_image(path)?.books {
+            replayer.restore(book)?;
+        }
+    }
+    if let Some(path) = journal {
+        for order in Replayer::read_journal(path)? {
+            replayer.apply(order);
+        }
+    }
+    for book in replayer.books() {
+        if !configured.contains(book.symbol.as_str()) {
+            println!("{} skipped, symbol not configured", &book.symbol);
+            continue;
+        }
+        book.verify().map_err(|e| anyhow!("rebuilt book violated, symbol={}, err={}", &book.symbol, e))?;
+        let removed = cache.clear_orders(&book.symbol).await?;
+        let mut orders = 0;
+        for order in book.bids().iter().chain(book.asks().iter()) {
+            cache.put_order(order).await?;
+            orders += 1;
+        }
+        let trade_id = cache.get_trade_id(&book.symbol).await?.max(book.trade_id());
+        cache.set_trade_id(&book.symbol, trade_id).await?;
+        println!("{} removed={} orders={} seq={} trade_id={}", &book.symbol, removed, orders, book.seq(), trade_id);
+    }
+    Ok(())
+}
+
+/// 校验配置的交易对和账本的审计日志，哈希链断裂时返回错误
+pub fn verify_audit(config: &Config, symbol: Option<&str>) -> anyhow::Result<()> {
+    let dir = config.audit.as_ref().map(|audit| PathBuf::from(&audit.dir)).ok_or_else(|| anyhow!("missing [audit] config section"))?;
+    let mut failed = 0;
+    let mut names: Vec<String> = config.markets().into_iter()
+        .map(|(_, spec)| spec.symbol)
+        .filter(|name| symbol.is_none_or(|s| s == name))
+        .collect();
+    // 账本的余额调整记录
+    if symbol.is_none_or(|s| s == audit::LEDGER_AUDIT) {
+        names.push(String::from(audit::LEDGER_AUDIT));
+    }
+    for name in names {
+        let path = AuditLog::path(&dir, &name);
+        if !path.exists() {
+            println!("{} no audit log", &name);
+            continue;
+        }
+        match audit::verify(&path) {
+            Ok(summary) => println!("{} records={} hash={} ok", &name, summary.records, &summary.hash),
+            Err(e) => {
+                failed += 1;
+                println!("{} broken: {}", &name, e);
+            }
+        }
+    }
+    if failed > 0 {
+        bail!("{} audit logs failed verification", failed);
+    }
+    Ok(())
+}
+
+/// 从成交归档导出监管成交报表，手续费率使用配置的交易对规格
+pub fn report(config: &Config, from: u128, to: u128, symbol: Option<&str>, format: Option<ReportFormat>, out: Option<&Path>) -> anyhow::Result<()> {
+    let archive = config.archive.as_ref().ok_or_else(|| anyhow!("report requires [archive] config section"))?;
+    let report = config.report.clone().unwrap_or_default();
+    let mut reporter = TradeReporter::new(TradeArchive::new(Path::new(&archive.dir)), &report);
+    for (_, spec) in config.markets() {
+        reporter = reporter.with_fees(&spec.symbol, spec.fees);
+    }
+    if let Some(format) = format {
+        reporter = reporter.with_format(format);
+    }
+    let dir = out.map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from(report.dir.as_deref().unwrap_or("./report")));
+    let (path, trades) = reporter.export(&dir, symbol, from, to)?;
+    println!("report written to {}, trades={}", path.display(), trades);
+    Ok(())
+}
+
+/// 压测使用的伪随机数生成器
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+/// 生成一笔合成订单，价格围绕100上下波动，约十分之一为撤单
+fn synthetic_order(symbol: &str, id: u64, rng: &mut XorShift) -> Order {
+    let r = rng.next();
+    let cancel = id > 1 && (r >> 8).is_multiple_of(10);
+    Order {
+        id: if cancel { 1 + (r >> 16) % (id - 1) } else { id },
+        symbol: symbol.into(),
+        side: if r.is_multiple_of(2) { TradeSide::BUY } else { TradeSide::SELL },
+        qty: 1 + (r >> 24) % 10,
+        price: BigDecimal::from(95 + (r >> 16) % 11),
+        acc_fill_qty: 0,
+        ord_type: OrderType::LIMIT,
+        ts: 0,
+        update_ts: 0,
+        state: OrderState::INIT,
+        tif: OrderTimeInForce::GTC,
+        action: if cancel { OrderAction::CANCEL } else { OrderAction::PLACE },
+        account: None,
+        seq: id,
+        expire_ts: 0,
+        ts_ns: 0,
+        valid_until_ts: 0,
+        tag: None,
+    }
+}
+
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    sorted[((sorted.len() - 1) as f64 * p).round() as usize]
+}
+
+/// 在进程内对交易对的订单簿压测，不经过HTTP和缓存，使用配置的交易对规格
+pub fn bench(config: &Config, orders: u64, symbol: Option<&str>) -> anyhow::Result<()> {
+    let specs: Vec<SymbolSpec> = config.markets().into_iter()
+        .map(|(_, spec)| spec)
+        .filter(|spec| symbol.is_none_or(|s| s == spec.symbol))
+        .collect();
+    if specs.is_empty() {
+        bail!("no symbol to bench");
+    }
+    for spec in specs {
+        let symbol = spec.symbol.clone();
+        let mut book = MarketBook::with_spec(spec);
+        let mut rng = XorShift(0x9E3779B97F4A7C15);
+        let mut trades = MatchTrades::new();
+        let mut latencies = Vec::with_capacity(orders as usize);
+        let mut fills = 0;
+        let start = Instant::now();
+        for id in 1..=orders {
+            let order = synthetic_order(&symbol, id, &mut rng);
+            let begin = Instant::now();
+            match order.action {
+                OrderAction::CANCEL => {
+                    book.try_cancel_into(order, &mut trades);
+                }
+                _ => book.try_match_into(order, &mut trades),
+            }
+            latencies.push(begin.elapsed());
+            fills += trades.iter().filter(|t| t.qty > 0).count();
+            trades.clear();
+        }
+        let elapsed = start.elapsed();
+        latencies.sort_unstable();
+        println!(
+            "{} orders={} fills={} resting={} elapsed={:?} throughput={:.0} req/s p50={:?} p99={:?} p999={:?} max={:?}",
+            symbol, orders, fills, book.state_hash().orders, elapsed, orders as f64 / elapsed.as_secs_f64(),
+            percentile(&latencies, 0.5), percentile(&latencies, 0.99), percentile(&latencies, 0.999),
+            latencies.last().copied().unwrap_or_default(),
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use loom_engine::report::ReportFormat;
+
+    use crate::cli::{Cli, Command};
+    use crate::cli_replay::ReplayArgs;
+
+    fn parse(args: &[&str]) -> anyhow::Result<Cli> {
+        Cli::parse(args.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn parse_test() {
+        assert_eq!(parse(&[]).unwrap(), Cli { config: None, command: Command::Serve });
+        let cli = parse(&["--config", "loom.toml", "snapshot", "--out", "images"]).unwrap();
+        assert_eq!(cli, Cli { config: Some("loom.toml".to_string()), command: Command::Snapshot { out: PathBuf::from("images") } });
+        assert_eq!(parse(&["bench", "--orders", "10"]).unwrap().command, Command::Bench { orders: 10, symbol: None });
+        assert_eq!(parse(&["verify-cache"]).unwrap().command, Command::VerifyCache);
+        let rebuild = parse(&["rebuild-cache", "--image", "loom-image.json", "--journal", "journal.jsonl"]).unwrap().command;
+        assert_eq!(rebuild, Command::RebuildCache { image: Some(PathBuf::from("loom-image.json")), journal: Some(PathBuf::from("journal.jsonl")) });
+        assert!(parse(&["rebuild-cache"]).is_err());
+        assert_eq!(parse(&["verify-audit", "--symbol", "LOOM-USDT-SPOT"]).unwrap().command, Command::VerifyAudit { symbol: Some("LOOM-USDT-SPOT".to_string()) });
+        let replay = parse(&["replay", "--journal", "journal.jsonl"]).unwrap().command;
+        assert_eq!(replay, Command::Replay(ReplayArgs { journal: PathBuf::from("journal.jsonl"), until: None }));
+
+        let report = parse(&["report", "--from", "0", "--to", "1000", "--format", "json"]).unwrap().command;
+        assert_eq!(report, Command::Report { from: Some(0), to: Some(1000), symbol: None, format: Some(ReportFormat::Json), out: None });
+
+        assert!(parse(&["report", "--from", "0"]).is_err());
+        assert!(parse(&["report", "--from", "10", "--to", "10"]).is_err());
+        assert!(parse(&["snapshot"]).is_err());
+        assert!(parse(&["verify-cache", "--out", "images"]).is_err());
+        assert!(parse(&["unknown"]).is_err());
+        assert!(parse(&["--config"]).is_err());
+    }
+}
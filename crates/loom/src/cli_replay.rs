@@ -0,0 +1,73 @@
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+
+use loom_engine::replay::Replayer;
+
+const USAGE: &str = "usage: loom replay --journal <file> [--until seq]";
+
+/// 回放参数
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ReplayArgs {
+    /// 日志文件，每行一个JSON格式的请求
+    pub journal: PathBuf,
+    /// 只回放序列号不大于until的请求
+    pub until: Option<u64>,
+}
+
+impl ReplayArgs {
+    pub fn parse(mut args: impl Iterator<Item=String>) -> anyhow::Result<ReplayArgs> {
+        let mut journal = None;
+        let mut until = None;
+        while let Some(flag) = args.next() {
+            let value = args.next().ok_or_else(|| anyhow!("missing value for {}\n{}", flag, USAGE))?;
+            match flag.as_str() {
+                "--journal" => journal = Some(PathBuf::from(value)),
+                "--until" => until = Some(value.parse()?),
+                _ => return Err(anyhow!("unknown flag {}\n{}", flag, USAGE)),
+            }
+        }
+        let journal = journal.ok_or_else(|| anyhow!("missing --journal\n{}", USAGE))?;
+        Ok(ReplayArgs { journal, until })
+    }
+}
+
+/// 回放日志，逐行输出成交，最后输出各交易对的状态哈希
+pub fn run(args: ReplayArgs) -> anyhow::Result<()> {
+    let orders = Replayer::read_journal(&args.journal)?;
+    let mut replayer = match args.until {
+        Some(until) => Replayer::new().with_until(until),
+        None => Replayer::new(),
+    };
+    for order in orders {
+        if let Some(trades) = replayer.apply(order) {
+            for trade in trades.iter() {
+                println!("TRADE {}", serde_json::to_string(trade)?);
+            }
+        }
+    }
+    for hash in replayer.state_hashes() {
+        println!("STATE {}", serde_json::to_string(&hash)?);
+    }
+    eprintln!("replayed {} requests from {}", replayer.applied(), args.journal.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use crate::cli_replay::ReplayArgs;
+
+    fn args(args: &[&str]) -> anyhow::Result<ReplayArgs> {
+        ReplayArgs::parse(args.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn parse_test() {
+        let parsed = args(&["--journal", "journal.jsonl", "--until", "42"]).unwrap();
+        assert_eq!(parsed, ReplayArgs { journal: PathBuf::from("journal.jsonl"), until: Some(42) });
+        assert!(args(&["--until", "42"]).is_err());
+        assert!(args(&["--journal"]).is_err());
+    }
+}
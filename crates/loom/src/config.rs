@@ -1,20 +1,216 @@
+use std::collections::HashMap;
 use std::env;
 use std::fs::File;
 use std::io::Read;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+use anyhow::{anyhow, bail};
 use log::{debug, info};
 use serde::{Deserialize, Serialize};
 
+use loom_core::id::IdStrategy;
+use loom_core::market::UncrossPolicy;
+use loom_core::symbol::SymbolSpec;
+use loom_engine::cache::TradeStream;
+use loom_engine::dump;
+use loom_engine::collar::CollarConfig;
+use loom_engine::consumer::ClickHouseConsumer;
+use loom_engine::ledger::LedgerConfig;
+use loom_engine::mmp::MmpConfig;
+use loom_engine::limits::AccountLimits;
+use loom_engine::reconcile::ReconcileConfig;
+use loom_engine::replication::ReplicationRole;
+use loom_engine::reference::ReferenceConfig;
+use loom_engine::report::ReportConfig;
+use loom_engine::risk::RiskConfig;
+use loom_engine::session::SessionConfig;
+use loom_engine::tenant;
+use loom_engine::warmup::RecoveryPolicy;
+use crate::admin_auth::AdminAuth;
+use crate::cors::Cors;
+use crate::logging::LogFilter;
+use crate::rate_limit::BucketConfig;
+use crate::tenant::TenantResolver;
+use crate::tls;
+use loom_engine::trader::Backpressure;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub server: Server,
     pub cache: Cache,
     pub consumer: ConsumerKind,
+    pub consumer_buffer: Option<ConsumerBuffer>,
+    /// Redis消费器的成交队列分区方式，默认每个交易对一个队列，需要跨交易对顺序时可写入全局队列
+    pub trade_stream: Option<TradeStream>,
+    /// 推送的成交是否携带taker和maker账户，供清算使用，默认不携带，归档始终保留账户
+    pub trade_accounts: Option<bool>,
+    pub clickhouse: Option<ClickHouseSink>,
     pub market: Market,
+    pub dump: Option<Dump>,
+    pub alert: Option<AlertConfig>,
+    pub rate_limit: Option<RateLimit>,
+    pub replication: Option<ReplicationConfig>,
+    /// 账户余额账本，配置后下单前检查并预留余额
+    pub ledger: Option<LedgerConfig>,
+    /// 缓存与订单簿对账，配置后定时比较挂单与缓存并按配置的方向修复
+    pub reconcile: Option<ReconcileConfig>,
+    /// 成交归档，配置后成交按交易对和日期写入本地文件
+    pub archive: Option<ArchiveConfig>,
+    /// 审计日志，配置后每个交易对的撮合请求和撮合结果写入带哈希链的只追加文件
+    pub audit: Option<AuditConfig>,
+    /// 结算导出，依赖成交归档
+    pub settlement: Option<SettlementConfig>,
+    /// 监管成交报表的格式和字段，由report子命令从成交归档导出
+    pub report: Option<ReportConfig>,
+    /// 外部参考价，最新成交价过期或不存在时用于价格带等风控检查
+    pub reference: Option<ReferenceConfig>,
+    /// 租户，每个租户有独立的交易对、账户、缓存键和成交消费者
+    pub tenants: Option<Vec<TenantConfig>>,
+    /// 日志过滤规则，未配置时使用RUST_LOG环境变量
+    pub log: Option<LogFilter>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Server {
+    pub host: Option<String>,
     pub port: Option<u16>,
+    /// host:port上提供的接口，默认为全部，管理接口单独监听时设为Api
+    pub routes: Option<ListenerRoutes>,
+    /// 额外的监听地址，未配置时只在host:port上提供全部接口
+    pub listeners: Option<Vec<Listener>>,
+    /// 管理接口鉴权，配置后/admin下的接口需要携带管理令牌
+    pub admin_auth: Option<AdminAuthConfig>,
+    /// TLS证书配置
+    pub tls: Option<Tls>,
+    /// 请求超时、请求体大小和并发限制
+    pub limits: Option<ServerLimits>,
+    /// 跨域配置，未配置时不允许跨域访问
+    pub cors: Option<CorsConfig>,
+    /// 是否监听TCP地址，默认为true，只使用Unix套接字时设为false
+    pub tcp: Option<bool>,
+    /// Unix套接字监听配置
+    pub unix_socket: Option<UnixSocket>,
+    /// 启动预热配置
+    pub warmup: Option<WarmupConfig>,
+}
+
+/// 启动预热配置，所有交易对恢复完成且消费器连通后才对外提供服务
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WarmupConfig {
+    /// 是否在恢复前绑定监听地址，默认为false，开启后预热完成前/readyz返回503
+    pub listen_early: Option<bool>,
+    /// 等待消费器连通的最长时间，毫秒，默认为30000，超时后启动失败
+    pub consumer_timeout_ms: Option<u64>,
+}
+
+/// Unix套接字监听配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnixSocket {
+    /// 套接字文件路径，启动时删除残留文件
+    pub path: String,
+    /// 套接字文件权限，例如 0o660，默认为0o660
+    pub mode: Option<u32>,
+    /// 该套接字上提供的接口，默认为全部
+    pub routes: Option<ListenerRoutes>,
+}
+
+/// 跨域配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CorsConfig {
+    /// 允许的来源，例如 https://trade.loom.io，*表示任意来源
+    pub origins: Vec<String>,
+    /// 允许的方法
+    pub methods: Option<Vec<String>>,
+    /// 允许的请求头
+    pub headers: Option<Vec<String>>,
+    /// 预检结果缓存时间，秒
+    pub max_age_secs: Option<u64>,
+}
+
+/// 服务端请求限制，未配置的项不限制
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ServerLimits {
+    /// 请求处理超时，毫秒
+    pub timeout_ms: Option<u64>,
+    /// 按路由覆盖的请求处理超时，毫秒
+    pub route_timeouts_ms: Option<HashMap<String, u64>>,
+    /// 请求体最大字节数
+    pub max_body_bytes: Option<usize>,
+    /// 所有监听地址合计的最大并发请求数，超出时直接返回503
+    pub max_concurrency: Option<usize>,
+}
+
+/// TLS证书配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tls {
+    /// PEM格式证书链路径
+    pub cert: String,
+    /// PEM格式私钥路径
+    pub key: String,
+}
+
+/// 管理接口鉴权配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdminAuthConfig {
+    /// 有效的管理令牌
+    pub tokens: Vec<String>,
+    /// 携带令牌的请求头，默认为x-admin-token
+    pub header: Option<String>,
+}
+
+/// 监听地址
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Listener {
+    pub host: Option<String>,
+    pub port: u16,
+    /// 该地址上提供的接口，默认为全部
+    pub routes: Option<ListenerRoutes>,
+}
+
+/// 监听地址上提供的接口
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default, Serialize, Deserialize)]
+pub enum ListenerRoutes {
+    /// 全部接口
+    #[default]
+    All,
+    /// 撮合与行情接口
+    Api,
+    /// 健康检查、指标与管理接口
+    Admin,
+}
+
+pub const DEFAULT_SERVER_HOST: &str = "0.0.0.0";
+pub const DEFAULT_SERVER_PORT: u16 = 7001;
+
+impl Server {
+    /// 所有监听地址及其提供的接口，主地址在前
+    pub fn bind_addrs(&self) -> Vec<(String, ListenerRoutes)> {
+        if self.tcp == Some(false) {
+            return Vec::new();
+        }
+        let host = self.host.as_deref().unwrap_or(DEFAULT_SERVER_HOST);
+        let mut addrs = vec![(format!("{}:{}", host, self.port.unwrap_or(DEFAULT_SERVER_PORT)), self.routes.unwrap_or_default())];
+        for listener in self.listeners.iter().flatten() {
+            let addr = format!("{}:{}", listener.host.as_deref().unwrap_or(host), listener.port);
+            addrs.push((addr, listener.routes.unwrap_or_default()));
+        }
+        addrs
+    }
+
+    /// 提供管理接口的非本机TCP监听地址，Unix套接字由文件权限控制访问，不在此列
+    pub fn admin_exposed(&self) -> Vec<String> {
+        self.bind_addrs().into_iter()
+            .filter(|(_, routes)| *routes != ListenerRoutes::Api)
+            .map(|(addr, _)| addr)
+            .filter(|addr| !is_loopback(addr.rsplit_once(':').map(|(host, _)| host).unwrap_or(addr)))
+            .collect()
+    }
+}
+
+fn is_loopback(host: &str) -> bool {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    host == "localhost" || host.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -41,16 +237,241 @@ pub struct RedisCache {
 pub enum ConsumerKind {
     Console,
     Redis,
+    ClickHouse,
+}
+
+/// 消费器缓冲配置，配置后成交按数量或时间阈值批量推送
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConsumerBuffer {
+    /// 缓冲成交数量阈值
+    pub max_trades: Option<usize>,
+    /// 缓冲最大延迟，毫秒
+    pub max_delay_ms: Option<u64>,
+    /// 下游失败时缓冲区最多保留的成交数量，默认100000
+    pub max_pending: Option<usize>,
+    /// 缓冲区已满时的处理策略，Block等待下游恢复，Reject或Shed丢弃新成交，默认Block
+    pub overflow: Option<Backpressure>,
+}
+
+/// ClickHouse成交写入配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClickHouseSink {
+    /// HTTP接口地址，例如 http://localhost:8123
+    pub url: String,
+    /// 写入的表，可带库名，例如 loom.trades
+    pub table: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// 单次写入超时，毫秒，默认5000
+    pub timeout_ms: Option<u64>,
+}
+
+impl ClickHouseSink {
+    /// 按配置构造ClickHouse消费器
+    pub fn consumer(&self) -> anyhow::Result<ClickHouseConsumer> {
+        let timeout = Duration::from_millis(self.timeout_ms.unwrap_or(5000));
+        ClickHouseConsumer::new(&self.url, &self.table, self.username.clone(), self.password.clone(), timeout)
+    }
+}
+
+/// 租户配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TenantConfig {
+    /// 租户ID，作为交易对、账户和缓存键的前缀
+    pub id: String,
+    /// 租户的API Key，携带后访问该租户的市场
+    pub api_keys: Vec<String>,
+    pub symbols: Vec<SymbolEntry>,
+    /// 成交消费者，默认与全局配置一致
+    pub consumer: Option<ConsumerKind>,
+    pub clickhouse: Option<ClickHouseSink>,
+}
+
+/// 成交归档配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveConfig {
+    /// 归档目录
+    pub dir: String,
+}
+
+/// 审计日志配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditConfig {
+    /// 审计日志目录
+    pub dir: String,
+}
+
+/// 结算导出配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettlementConfig {
+    /// 结算文件目录，默认 ./settlement
+    pub dir: Option<String>,
+    /// 结算周期，秒，默认一天
+    pub period_secs: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Market {
-    pub symbols: Option<Vec<String>>,
+    /// 交易对，可以只写交易对名称，也可以写完整规格
+    pub symbols: Option<Vec<SymbolEntry>>,
+    /// 配置后交易员以独占线程模式运行
+    pub native: Option<NativeTrader>,
+    /// 撮合请求队列容量，作用于所有交易对
+    pub capacity: Option<usize>,
+    /// 撮合请求队列已满时的处理策略，作用于所有交易对
+    pub backpressure: Option<Backpressure>,
+    /// 撮合请求排队时间上限，毫秒，超过后新订单以429拒绝，作用于所有交易对
+    pub max_queue_wait_ms: Option<u64>,
+    /// 按交易对覆盖的队列配置
+    pub traders: Option<HashMap<String, TraderQueue>>,
+    /// 市场快照发布间隔，毫秒
+    pub snapshot_interval_ms: Option<u64>,
+    /// 关闭时处理队列中剩余请求的期限，毫秒，超时后强制退出
+    pub drain_timeout_ms: Option<u64>,
+    /// 调试模式，每处理N个请求检查一次市场不变式，不满足时暂停交易对
+    pub verify_every: Option<u64>,
+    /// 恢复时每批读取的订单数量，默认10000
+    pub recovery_batch: Option<usize>,
+    /// 恢复镜像后订单簿交叉时的处理策略，默认撮合
+    pub uncross: Option<UncrossPolicy>,
+    /// 检查新订单的客户端ID是否按交易对连续递增，发现缺失或回退时告警，用于上游网关发现丢失的消息
+    pub check_order_ids: Option<bool>,
+    /// 工作分片数量，配置后交易员按交易对一致性哈希分配到各分片运行时中
+    pub shards: Option<usize>,
+    /// 账户挂单数量和下单速率限制
+    pub accounts: Option<AccountLimits>,
+    /// 下单前内置风控检查
+    pub risk: Option<RiskConfig>,
+    /// 交易时段，作用于所有交易对，未配置时全天连续撮合
+    pub session: Option<SessionConfig>,
+    /// 按交易对覆盖的交易时段
+    pub sessions: Option<HashMap<String, SessionConfig>>,
+    /// 做市商保护，账户挂单在统计窗口内成交过多时撤销其挂单并拒绝新订单
+    pub mmp: Option<MmpConfig>,
+    /// 成交ID的分配方式，多个引擎节点时使用Snowflake并为每个节点配置不同的节点号
+    pub id_strategy: Option<IdStrategy>,
+    /// 价格熔断，作用于所有交易对，作为价格带之外的第二道防线
+    pub collar: Option<CollarConfig>,
+    /// 按交易对覆盖的价格熔断
+    pub collars: Option<HashMap<String, CollarConfig>>,
+    /// 启动时的恢复方式，作用于所有交易对，默认缓存中的挂单重新撮合
+    pub recovery: Option<RecoveryPolicy>,
+    /// 按交易对覆盖的恢复方式
+    pub recoveries: Option<HashMap<String, RecoveryPolicy>>,
+}
+
+/// 交易对配置项
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SymbolEntry {
+    /// 只有交易对名称，使用默认规格
+    Name(String),
+    Spec(SymbolSpec),
+}
+
+impl SymbolEntry {
+    pub fn spec(&self) -> SymbolSpec {
+        match self {
+            SymbolEntry::Name(symbol) => SymbolSpec::new(symbol),
+            SymbolEntry::Spec(spec) => spec.clone(),
+        }
+    }
+}
+
+impl Market {
+    /// 所有交易对的规格
+    pub fn specs(&self) -> Vec<SymbolSpec> {
+        self.symbols.iter().flatten().map(|entry| entry.spec()).collect()
+    }
+
+    /// 交易对的交易时段，交易对配置优先于全局配置
+    pub fn session(&self, symbol: &str) -> Option<&SessionConfig> {
+        self.sessions.as_ref().and_then(|s| s.get(symbol)).or(self.session.as_ref())
+    }
+
+    /// 交易对的价格熔断，交易对配置优先于全局配置
+    pub fn collar(&self, symbol: &str) -> Option<&CollarConfig> {
+        self.collars.as_ref().and_then(|c| c.get(symbol)).or(self.collar.as_ref())
+    }
+
+    /// 交易对的恢复方式，交易对配置优先于全局配置
+    pub fn recovery(&self, symbol: &str) -> Option<&RecoveryPolicy> {
+        self.recoveries.as_ref().and_then(|r| r.get(symbol)).or(self.recovery.as_ref())
+    }
+}
+
+/// 交易对撮合请求队列配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraderQueue {
+    pub capacity: Option<usize>,
+    pub backpressure: Option<Backpressure>,
+    pub max_queue_wait_ms: Option<u64>,
+}
+
+/// 独占线程模式配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NativeTrader {
+    /// 环形缓冲区容量
+    pub capacity: Option<usize>,
+    /// 交易员线程绑定的CPU核心，按交易对顺序轮流分配
+    pub cores: Option<Vec<usize>>,
+}
+
+/// 订单簿转储配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Dump {
+    /// 转储文件目录，默认为系统临时目录下的loom目录
+    pub dir: Option<String>,
+}
+
+/// 主备复制配置，主备需共享同一缓存
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplicationConfig {
+    /// 复制角色，Primary或Standby
+    pub role: ReplicationRole,
+    /// 主机发布状态哈希检查点的间隔，毫秒
+    pub checkpoint_interval_ms: Option<u64>,
+    /// 日志流保留的条目数量
+    pub journal_max_len: Option<usize>,
+}
+
+/// 接口限流配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RateLimit {
+    /// 按客户端IP限流
+    pub per_ip: Option<BucketConfig>,
+    /// 按API Key限流
+    pub per_key: Option<BucketConfig>,
+    /// API Key请求头，默认为x-api-key
+    pub api_key_header: Option<String>,
+    /// 各路由请求消耗的令牌数，未配置的路由消耗1个
+    pub routes: Option<HashMap<String, u32>>,
+}
+
+/// 运维告警配置，未配置的阈值不检查
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertConfig {
+    /// 检查间隔，毫秒
+    pub interval_ms: Option<u64>,
+    /// 撮合请求队列积压数量
+    pub queue_depth: Option<usize>,
+    /// 每分钟成交推送失败次数
+    pub consumer_failures_per_min: Option<u64>,
+    /// Redis响应延迟，毫秒
+    pub redis_latency_ms: Option<u64>,
+    /// 交易员停止时告警
+    pub trader_halted: Option<bool>,
+    /// 告警推送地址，以JSON POST
+    pub webhook: Option<String>,
+    /// 告警发布的Redis频道
+    pub redis_channel: Option<String>,
 }
 
 
 pub const DEFAULT_CONFIG_ENV_VAR: &str = "LOOM_CONFIG_FILE";
 pub const DEFAULT_CONFIG_FILE_PATH: &str = "/etc/loom/config.toml";
+/// 覆盖配置项的环境变量前缀，层级之间用双下划线分隔，如LOOM__CACHE__REDIS__HOST
+pub const ENV_OVERRIDE_PREFIX: &str = "LOOM__";
 
 impl Config {
     pub fn from_file(file: Option<&str>) -> anyhow::Result<Config> {
@@ -70,9 +491,140 @@ impl Config {
         let mut file = File::open(path)?;
         let mut contents = String::new();
         file.read_to_string(&mut contents)?;
-        let config = toml::from_str::<Config>(&contents)?;
+        Config::from_toml(&contents, env::vars())
+    }
+
+    /// 解析TOML配置，再用LOOM__开头的环境变量覆盖配置项，容器部署时无需将密码等写入配置文件
+    pub fn from_toml(contents: &str, vars: impl IntoIterator<Item=(String, String)>) -> anyhow::Result<Config> {
+        let root = toml::Value::Table(toml::from_str(contents)?);
+        let mut overrides: Vec<(String, String)> = vars.into_iter()
+            .filter_map(|(key, value)| key.strip_prefix(ENV_OVERRIDE_PREFIX).map(|path| (path.to_string(), value)))
+            .collect();
+        // 按键排序，覆盖结果不依赖环境变量的顺序
+        overrides.sort();
+        for (path, _) in overrides.iter() {
+            if path.split("__").any(|s| s.is_empty()) {
+                bail!("invalid config override, key={}{}", ENV_OVERRIDE_PREFIX, path);
+            }
+            info!("config overridden by env: {}{}", ENV_OVERRIDE_PREFIX, path);
+        }
+        let config: Config = match apply_overrides(root.clone(), &overrides, false)?.try_into() {
+            Ok(config) => config,
+            // 配置文件中没有的键无法得知类型，纯数字的密码等被解析为数字时，作为字符串重试
+            Err(_) if !overrides.is_empty() => apply_overrides(root, &overrides, true)?.try_into()?,
+            Err(e) => return Err(e.into()),
+        };
+        config.validate()?;
         Ok(config)
     }
+
+    /// 检查配置是否可用，启动和重新加载时拒绝不合法的配置
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if let Some(tls) = &self.server.tls {
+            // 证书不可用时拒绝启动，不以明文方式暴露本应加密的接口
+            tls::load(tls).map_err(|e| anyhow!("invalid [server.tls] config: {}", e))?;
+        }
+        match &self.server.admin_auth {
+            Some(conf) => {
+                AdminAuth::new(conf).map_err(|e| anyhow!("invalid [server.admin_auth] config: {}", e))?;
+            }
+            None => {
+                // 管理接口可以停止撮合、清空订单簿和调整余额，只允许在本机地址上免鉴权提供
+                let exposed = self.server.admin_exposed();
+                if !exposed.is_empty() {
+                    bail!("admin routes are served on non-loopback addresses {:?} without [server.admin_auth], set routes = \"Api\" or configure admin tokens", exposed);
+                }
+            }
+        }
+        if let Some(conf) = &self.server.cors {
+            Cors::new(conf).map_err(|e| anyhow!("invalid [server.cors] config: {}", e))?;
+        }
+        TenantResolver::new(self.tenants.as_deref().unwrap_or_default())
+            .map_err(|e| anyhow!("invalid [[tenants]] config: {}", e))?;
+        let sinks = self.tenants.iter().flatten().filter_map(|t| t.clickhouse.as_ref());
+        for sink in self.clickhouse.iter().chain(sinks) {
+            sink.consumer().map_err(|e| anyhow!("invalid [clickhouse] config, url={}: {}", sink.url, e))?;
+        }
+        Ok(())
+    }
+
+    /// 默认市场和各租户的交易对规格，租户的交易对名称带租户前缀
+    pub fn markets(&self) -> Vec<(Option<&TenantConfig>, SymbolSpec)> {
+        let mut markets: Vec<_> = self.market.specs().into_iter().map(|spec| (None, spec)).collect();
+        for t in self.tenants.iter().flatten() {
+            for entry in t.symbols.iter() {
+                let mut spec = entry.spec();
+                spec.symbol = tenant::qualify(&t.id, &spec.symbol);
+                markets.push((Some(t), spec));
+            }
+        }
+        markets
+    }
+
+    /// 订单簿转储目录
+    pub fn dump_dir(&self) -> PathBuf {
+        self.dump.as_ref()
+            .and_then(|d| d.dir.as_ref())
+            .map(PathBuf::from)
+            .unwrap_or_else(dump::default_dump_dir)
+    }
+}
+
+fn apply_overrides(mut root: toml::Value, overrides: &[(String, String)], as_string: bool) -> anyhow::Result<toml::Value> {
+    for (path, value) in overrides.iter() {
+        override_value(&mut root, &path.split("__").collect::<Vec<_>>(), value, as_string)
+            .map_err(|e| anyhow!("invalid config override, key={}{}, err={}", ENV_OVERRIDE_PREFIX, path, e))?;
+    }
+    Ok(root)
+}
+
+/// 将环境变量的值写入配置中segments指定的位置，表中已有的键不区分大小写匹配，数组用下标访问
+fn override_value(target: &mut toml::Value, segments: &[&str], raw: &str, as_string: bool) -> anyhow::Result<()> {
+    let (first, rest) = match segments.split_first() {
+        Some(split) => split,
+        None => return Ok(()),
+    };
+    match target {
+        toml::Value::Table(table) => {
+            let key = table.keys()
+                .find(|k| k.eq_ignore_ascii_case(first))
+                .cloned()
+                .unwrap_or_else(|| first.to_ascii_lowercase());
+            if rest.is_empty() {
+                let value = env_value(raw, table.get(&key), as_string);
+                table.insert(key, value);
+                return Ok(());
+            }
+            let child = table.entry(key).or_insert_with(|| toml::Value::Table(toml::Table::new()));
+            override_value(child, rest, raw, as_string)
+        }
+        toml::Value::Array(items) => {
+            let child = first.parse::<usize>().ok()
+                .and_then(|i| items.get_mut(i))
+                .ok_or_else(|| anyhow!("array index out of range, index={}", first))?;
+            if rest.is_empty() {
+                *child = env_value(raw, Some(child), as_string);
+                return Ok(());
+            }
+            override_value(child, rest, raw, as_string)
+        }
+        _ => bail!("not a table, key={}", first),
+    }
+}
+
+/// 环境变量的值按TOML值解析，如数字、布尔值和数组，原配置为字符串或无法解析时作为字符串，
+/// as_string时配置文件中没有的键也作为字符串
+fn env_value(raw: &str, existing: Option<&toml::Value>, as_string: bool) -> toml::Value {
+    let keep_string = match existing {
+        Some(value) => value.is_str(),
+        None => as_string,
+    };
+    if keep_string {
+        return toml::Value::String(raw.to_string());
+    }
+    toml::from_str::<toml::Table>(&format!("v = {}", raw)).ok()
+        .and_then(|mut table| table.remove("v"))
+        .unwrap_or_else(|| toml::Value::String(raw.to_string()))
 }
 
 impl RedisCache {
@@ -85,7 +637,12 @@ impl RedisCache {
 
 #[cfg(test)]
 mod test {
-    use crate::config::Config;
+    use serde::Deserialize;
+
+    use loom_core::id::IdStrategy;
+    use loom_engine::warmup::RecoveryPolicy;
+
+    use crate::config::{Config, ConsumerKind, ListenerRoutes, Market, Server, TenantConfig};
 
     #[test]
     fn config_load_test() {
@@ -93,4 +650,150 @@ mod test {
         println!("{:?}", &config);
         assert!(config.server.port.is_some())
     }
+
+    #[test]
+    fn env_override_test() {
+        let contents = std::fs::read_to_string("config.toml").unwrap();
+        let vars = [
+            ("LOOM__CACHE__REDIS__HOST", "redis.internal"),
+            ("LOOM__CACHE__REDIS__PORT", "6380"),
+            ("LOOM__CACHE__REDIS__PASSWORD", "123456"),
+            ("LOOM__CONSUMER", "Console"),
+            ("LOOM__MARKET__TRADERS__loom-usdt-spot__CAPACITY", "8192"),
+            ("LOOM__MARKET__SYMBOLS__0__LOT_SIZE", "10"),
+            ("LOOM__SERVER__CORS__ORIGINS", r#"["https://loom.example"]"#),
+            ("OTHER__CACHE__REDIS__HOST", "ignored"),
+        ].map(|(k, v)| (k.to_string(), v.to_string()));
+        let config = Config::from_toml(&contents, vars).unwrap();
+        assert_eq!(config.cache.redis.host, "redis.internal");
+        assert_eq!(config.cache.redis.port, Some(6380));
+        assert_eq!(config.cache.redis.password.as_deref(), Some("123456"));
+        assert!(matches!(config.consumer, ConsumerKind::Console));
+        assert_eq!(config.market.traders.as_ref().unwrap()["LOOM-USDT-SPOT"].capacity, Some(8192));
+        assert_eq!(config.market.specs()[0].lot_size, Some(10));
+        assert_eq!(config.server.cors.unwrap().origins, vec!["https://loom.example".to_string()]);
+
+        let invalid = |k: &str, v: &str| Config::from_toml(&contents, [(k.to_string(), v.to_string())]).is_err();
+        assert!(invalid("LOOM__CACHE__REDIS__HOST__NAME", "x"));
+        assert!(invalid("LOOM__MARKET__SYMBOLS__5__LOT_SIZE", "10"));
+        assert!(invalid("LOOM__CACHE____HOST", "x"));
+        assert!(invalid("LOOM__CACHE__REDIS__PORT", "not a port"));
+    }
+
+    #[test]
+    fn symbol_entries_test() {
+        let market: Market = toml::from_str(r#"
+            symbols = [
+                "BTC-USDT-SPOT",
+                { symbol = "LOOM-USDT-SPOT", price_decimals = 2, tick_size = "0.05", lot_size = 10 },
+            ]
+        "#).unwrap();
+        let specs = market.specs();
+        assert_eq!(specs[0].price_decimals, 8);
+        assert_eq!((specs[1].price_decimals, specs[1].lot_size), (2, Some(10)));
+        assert_eq!(specs[1].tick_size.as_ref().map(|t| t.to_string()), Some(String::from("0.05")));
+        let market: Market = toml::from_str(r#"id_strategy = { Snowflake = { node = 7 } }"#).unwrap();
+        assert_eq!(market.id_strategy, Some(IdStrategy::Snowflake { node: 7 }));
+    }
+
+    #[test]
+    fn recovery_test() {
+        let market: Market = toml::from_str(r#"
+            recovery = { Image = { path = "./dump" } }
+            [recoveries]
+            "BTC-USDT-SPOT" = "Empty"
+            "ETH-USDT-SPOT" = { Journal = {} }
+        "#).unwrap();
+        assert_eq!(market.recovery("LOOM-USDT-SPOT"), Some(&RecoveryPolicy::Image { path: String::from("./dump") }));
+        assert_eq!(market.recovery("BTC-USDT-SPOT"), Some(&RecoveryPolicy::Empty));
+        assert_eq!(market.recovery("ETH-USDT-SPOT"), Some(&RecoveryPolicy::Journal { image: None }));
+    }
+
+    #[test]
+    fn tls_config_test() {
+        let contents = std::fs::read_to_string("config.toml").unwrap();
+        let tls = format!("{}\n[server.tls]\ncert = \"testdata/cert.pem\"\nkey = \"testdata/key.pem\"\n", contents);
+        assert!(Config::from_toml(&tls, []).unwrap().server.tls.is_some());
+        // 证书不可用时拒绝启动
+        let missing = format!("{}\n[server.tls]\ncert = \"testdata/missing.pem\"\nkey = \"testdata/key.pem\"\n", contents);
+        let err = Config::from_toml(&missing, []).unwrap_err();
+        assert!(err.to_string().contains("[server.tls]"));
+        let swapped = format!("{}\n[server.tls]\ncert = \"testdata/key.pem\"\nkey = \"testdata/cert.pem\"\n", contents);
+        assert!(Config::from_toml(&swapped, []).is_err());
+    }
+
+    #[test]
+    fn admin_exposure_test() {
+        let contents = std::fs::read_to_string("config.toml").unwrap();
+        let config = Config::from_toml(&contents, []).unwrap();
+        assert!(config.server.admin_exposed().is_empty());
+        // 公网地址提供管理接口时必须配置管理令牌
+        let all = [(String::from("LOOM__SERVER__ROUTES"), String::from("All"))];
+        let err = Config::from_toml(&contents, all.clone()).unwrap_err();
+        assert!(err.to_string().contains("0.0.0.0:7002"));
+        let local = [all[0].clone(), (String::from("LOOM__SERVER__HOST"), String::from("127.0.0.1"))];
+        assert!(Config::from_toml(&contents, local).is_ok());
+        let tokens = [all[0].clone(), (String::from("LOOM__SERVER__ADMIN_AUTH__TOKENS"), String::from(r#"["ops"]"#))];
+        assert!(Config::from_toml(&contents, tokens).is_ok());
+        let empty = [all[0].clone(), (String::from("LOOM__SERVER__ADMIN_AUTH__TOKENS"), String::from("[]"))];
+        assert!(Config::from_toml(&contents, empty).is_err());
+    }
+
+    #[test]
+    fn markets_test() {
+        let mut config = Config::from_file(Some("config.toml")).unwrap();
+        config.tenants = toml::from_str::<Tenants>(r#"
+            [[tenants]]
+            id = "acme"
+            api_keys = ["acme-key"]
+            symbols = ["BTC-USDT-SPOT"]
+            consumer = "Console"
+        "#).unwrap().tenants;
+        let markets = config.markets();
+        let (tenant, spec) = markets.last().unwrap();
+        assert_eq!(tenant.map(|t| t.id.as_str()), Some("acme"));
+        assert_eq!(spec.symbol, "acme.BTC-USDT-SPOT");
+        assert_eq!(markets.len(), config.market.specs().len() + 1);
+    }
+
+    #[derive(Deserialize)]
+    struct Tenants {
+        tenants: Option<Vec<TenantConfig>>,
+    }
+
+    #[test]
+    fn bind_addrs_test() {
+        let server: Server = toml::from_str(r#"
+            host = "127.0.0.1"
+            port = 7002
+            [[listeners]]
+            port = 7003
+            routes = "Admin"
+        "#).unwrap();
+        assert_eq!(server.bind_addrs(), vec![
+            (String::from("127.0.0.1:7002"), ListenerRoutes::All),
+            (String::from("127.0.0.1:7003"), ListenerRoutes::Admin),
+        ]);
+        let split: Server = toml::from_str(r#"
+            host = "0.0.0.0"
+            routes = "Api"
+            admin_auth = { tokens = ["ops"] }
+            [[listeners]]
+            host = "127.0.0.1"
+            port = 7101
+            routes = "Admin"
+        "#).unwrap();
+        assert_eq!(split.bind_addrs(), vec![
+            (String::from("0.0.0.0:7001"), ListenerRoutes::Api),
+            (String::from("127.0.0.1:7101"), ListenerRoutes::Admin),
+        ]);
+        assert!(split.admin_exposed().is_empty());
+        assert_eq!(split.admin_auth.unwrap().tokens, vec![String::from("ops")]);
+        let unix: Server = toml::from_str(r#"
+            tcp = false
+            unix_socket = { path = "/run/loom/loom.sock", mode = 0o600 }
+        "#).unwrap();
+        assert!(unix.bind_addrs().is_empty());
+        assert_eq!(unix.unix_socket.unwrap().mode, Some(0o600));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,92 @@
+use std::sync::Arc;
+
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderValue, Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+
+use crate::config::CorsConfig;
+
+/// 默认允许的方法
+const DEFAULT_METHODS: &str = "GET, POST, PUT, DELETE, OPTIONS";
+
+/// 默认允许的请求头
+const DEFAULT_HEADERS: &str = "content-type, x-api-key, x-request-id, idempotency-key";
+
+/// 默认预检结果缓存时间，秒
+const DEFAULT_MAX_AGE: u64 = 600;
+
+/// 跨域策略
+#[derive(Debug, Clone)]
+pub struct Cors {
+    /// 允许的来源，包含*时允许任意来源
+    origins: Vec<String>,
+    methods: HeaderValue,
+    headers: HeaderValue,
+    max_age: HeaderValue,
+}
+
+impl Cors {
+    pub fn new(conf: &CorsConfig) -> anyhow::Result<Cors> {
+        let methods = conf.methods.as_ref().map(|m| m.join(", ")).unwrap_or_else(|| DEFAULT_METHODS.to_string());
+        let headers = conf.headers.as_ref().map(|h| h.join(", ")).unwrap_or_else(|| DEFAULT_HEADERS.to_string());
+        Ok(Cors {
+            origins: conf.origins.clone(),
+            methods: HeaderValue::from_str(&methods)?,
+            headers: HeaderValue::from_str(&headers)?,
+            max_age: HeaderValue::from(conf.max_age_secs.unwrap_or(DEFAULT_MAX_AGE)),
+        })
+    }
+
+    /// 来源是否允许跨域访问
+    pub fn allow(&self, origin: &str) -> bool {
+        self.origins.iter().any(|o| o == "*" || o == origin)
+    }
+}
+
+/// 跨域中间件，处理预检请求并为允许的来源添加响应头
+pub async fn cors(State(cors): State<Arc<Cors>>, req: Request, next: Next) -> Response {
+    let origin = match req.headers().get(header::ORIGIN).cloned() {
+        Some(origin) if origin.to_str().map(|o| cors.allow(o)).unwrap_or(false) => origin,
+        // 非跨域请求或来源不允许时不添加跨域响应头，由浏览器拦截
+        _ => return next.run(req).await,
+    };
+    let preflight = req.method() == Method::OPTIONS
+        && req.headers().contains_key(header::ACCESS_CONTROL_REQUEST_METHOD);
+    let mut resp = if preflight {
+        let mut resp = StatusCode::NO_CONTENT.into_response();
+        let headers = resp.headers_mut();
+        headers.insert(header::ACCESS_CONTROL_ALLOW_METHODS, cors.methods.clone());
+        headers.insert(header::ACCESS_CONTROL_ALLOW_HEADERS, cors.headers.clone());
+        headers.insert(header::ACCESS_CONTROL_MAX_AGE, cors.max_age.clone());
+        resp
+    } else {
+        next.run(req).await
+    };
+    let headers = resp.headers_mut();
+    headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+    headers.append(header::VARY, HeaderValue::from_static("origin"));
+    resp
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::CorsConfig;
+    use crate::cors::Cors;
+
+    #[test]
+    fn allow_origin_test() {
+        let conf = CorsConfig {
+            origins: vec![String::from("https://trade.loom.io")],
+            methods: Some(vec![String::from("GET"), String::from("POST")]),
+            headers: None,
+            max_age_secs: None,
+        };
+        let cors = Cors::new(&conf).unwrap();
+        assert!(cors.allow("https://trade.loom.io"));
+        assert!(!cors.allow("https://evil.example"));
+        assert_eq!(cors.methods, "GET, POST");
+        let any = Cors::new(&CorsConfig { origins: vec![String::from("*")], ..conf }).unwrap();
+        assert!(any.allow("https://evil.example"));
+    }
+}
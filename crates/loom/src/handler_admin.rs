@@ -0,0 +1,296 @@
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use axum::extract::{Path, Query, State};
+use axum::Json;
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use loom_core::snapshot::StateHash;
+use loom_core::symbol::{SymbolPatch, SymbolSpec};
+use loom_engine::dump;
+use loom_engine::engine::EngineHandle;
+use loom_engine::image;
+use loom_engine::mmp::MmpTrip;
+use loom_engine::reconcile::{ReconcileReport, RepairDirection};
+use loom_engine::reference::ReferencePrice;
+use loom_engine::replication::ReplicationRole;
+use loom_engine::trader::{HaltOrders, PauseMode};
+
+use crate::http_server::AppError;
+use crate::logging::{self, LogFilter};
+
+/// 查询当前日志过滤规则
+pub async fn handler_get_loglevel() -> Result<Json<LogFilter>, AppError> {
+    let filter = logging::current_filter().ok_or_else(|| anyhow!("logger not initialized"))?;
+    Ok(Json(filter))
+}
+
+/// 运行时替换日志过滤规则，无需重启引擎
+pub async fn handler_put_loglevel(Json(param): Json<LogFilter>) -> Result<Json<LogFilter>, AppError> {
+    let filter = logging::reload(param)?;
+    info!("LOG FILTER RELOADED: {:?}", &filter);
+    Ok(Json(filter))
+}
+
+/// 转储结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DumpResult {
+    /// 转储文件路径
+    pub path: String,
+    /// 各交易对转储的挂单数量，交易员未响应时为空
+    pub orders: Vec<(String, Option<usize>)>,
+}
+
+/// 按需转储所有交易对的订单簿
+pub async fn handler_dump(State((engine, dir)): State<(EngineHandle, PathBuf)>) -> Result<Json<DumpResult>, AppError> {
+    let dump = engine.dump("admin request").await;
+    let path = dump::write_dump(&dir, &dump)?;
+    info!("BOOK DUMPED: path={}", path.display());
+    let orders = dump.traders.iter()
+        .map(|t| (t.symbol.clone(), t.book.as_ref().map(|b| b.bids.len() + b.asks.len())))
+        .collect();
+    Ok(Json(DumpResult { path: path.display().to_string(), orders }))
+}
+
+/// 镜像结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageResult {
+    /// 镜像文件路径
+    pub path: String,
+    /// 镜像格式版本
+    pub format: u32,
+    /// 各交易对的挂单数量
+    pub orders: Vec<(String, usize)>,
+}
+
+/// 生成所有交易对的市场镜像并写入转储目录
+pub async fn handler_snapshot(State((engine, dir)): State<(EngineHandle, PathBuf)>) -> Result<Json<ImageResult>, AppError> {
+    let image = engine.image().await?;
+    let path = image::write_image(&dir, &image)?;
+    info!("IMAGE WRITTEN: path={}", path.display());
+    Ok(Json(ImageResult { path: path.display().to_string(), format: image.format, orders: image.orders() }))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestoreParam {
+    /// 镜像文件路径
+    pub path: String,
+}
+
+/// 从镜像文件恢复市场状态
+pub async fn handler_restore(State((engine, _)): State<(EngineHandle, PathBuf)>, Json(param): Json<RestoreParam>) -> Result<Json<ImageResult>, AppError> {
+    let image = image::read_image(std::path::Path::new(&param.path))?;
+    let orders = image.orders();
+    let format = image.format;
+    engine.restore(image).await?;
+    Ok(Json(ImageResult { path: param.path, format, orders }))
+}
+
+/// 提升结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromoteResult {
+    pub role: ReplicationRole,
+    pub symbols: Vec<String>,
+}
+
+/// 将备机提升为主机，重放完主机日志后返回
+pub async fn handler_promote(State(engine): State<EngineHandle>) -> Result<Json<PromoteResult>, AppError> {
+    engine.promote().await?;
+    Ok(Json(PromoteResult { role: engine.replication().role(), symbols: engine.symbols() }))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateHashParam {
+    /// 交易对
+    pub symbol: String,
+}
+
+/// 计算交易对的市场状态哈希，用于比对主备或恢复后的节点
+pub async fn handler_statehash(State(engine): State<EngineHandle>, Query(param): Query<StateHashParam>) -> Result<Json<StateHash>, AppError> {
+    let hash = engine.state_hash(&param.symbol).await?;
+    Ok(Json(hash))
+}
+
+/// 市场不变式检查结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerifyResult {
+    pub symbol: String,
+    /// 违反的不变式，检查通过时为空
+    pub violation: Option<String>,
+}
+
+/// 检查交易对的市场不变式
+pub async fn handler_verify(State(engine): State<EngineHandle>, Query(param): Query<StateHashParam>) -> Result<Json<VerifyResult>, AppError> {
+    let violation = engine.verify(&param.symbol).await?;
+    Ok(Json(VerifyResult { symbol: param.symbol, violation }))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReconcileParam {
+    /// 交易对，为空时对账所有交易对
+    pub symbol: Option<String>,
+    /// 修复方向，为空时使用配置的修复方向
+    pub repair: Option<RepairDirection>,
+}
+
+/// 按需对账缓存与订单簿
+pub async fn handler_reconcile(State(engine): State<EngineHandle>, Query(param): Query<ReconcileParam>) -> Result<Json<Vec<ReconcileReport>>, AppError> {
+    let reconciler = engine.reconciler().ok_or_else(|| anyhow!("reconcile not enabled"))?;
+    let symbols = match param.symbol {
+        Some(symbol) => vec![symbol],
+        None => engine.symbols(),
+    };
+    let mut reports = Vec::with_capacity(symbols.len());
+    for symbol in symbols {
+        reports.push(reconciler.reconcile(&engine, &symbol, param.repair).await?);
+    }
+    Ok(Json(reports))
+}
+
+/// 查询各交易对最近一次对账结果
+pub async fn handler_get_reconcile(State(engine): State<EngineHandle>) -> Result<Json<Vec<ReconcileReport>>, AppError> {
+    let reconciler = engine.reconciler().ok_or_else(|| anyhow!("reconcile not enabled"))?;
+    Ok(Json(reconciler.reports()))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PauseParam {
+    /// 交易对
+    pub symbol: String,
+    /// 暂停方式，默认缓冲新请求
+    pub mode: Option<PauseMode>,
+    /// 挂单处理方式，默认保留
+    pub orders: Option<HaltOrders>,
+}
+
+/// 停牌所有交易对的参数
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HaltParam {
+    /// 暂停方式，默认缓冲新请求
+    pub mode: Option<PauseMode>,
+    /// 挂单处理方式，默认保留
+    pub orders: Option<HaltOrders>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PurgeParam {
+    /// 交易对
+    pub symbol: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResumeParam {
+    /// 交易对
+    pub symbol: String,
+}
+
+/// 暂停结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PauseResult {
+    pub symbol: String,
+    /// 暂停方式，已恢复时为空
+    pub paused: Option<PauseMode>,
+    /// 队列中等待处理的请求数量
+    pub pending: usize,
+    /// 本次请求撤销的挂单数量
+    #[serde(default)]
+    pub cancelled: usize,
+}
+
+impl PauseResult {
+    fn new(engine: &EngineHandle, symbol: String, cancelled: usize) -> PauseResult {
+        PauseResult {
+            paused: engine.paused(&symbol),
+            pending: engine.sender(&symbol).map(|s| s.pending()).unwrap_or(0),
+            symbol,
+            cancelled,
+        }
+    }
+}
+
+/// 暂停交易对，默认保留订单簿状态，也可撤销所有挂单
+pub async fn handler_pause(State(engine): State<EngineHandle>, Json(param): Json<PauseParam>) -> Result<Json<PauseResult>, AppError> {
+    let cancelled = engine.halt(&param.symbol, param.mode.unwrap_or_default(), param.orders.unwrap_or_default()).await?;
+    Ok(Json(PauseResult::new(&engine, param.symbol, cancelled)))
+}
+
+/// 恢复交易对，处理暂停期间缓冲的请求
+pub async fn handler_resume(State(engine): State<EngineHandle>, Json(param): Json<ResumeParam>) -> Result<Json<PauseResult>, AppError> {
+    engine.resume(&param.symbol)?;
+    Ok(Json(PauseResult::new(&engine, param.symbol, 0)))
+}
+
+/// 停牌所有交易对，任一交易对失败时返回错误，之前的交易对保持停牌
+pub async fn handler_halt(State(engine): State<EngineHandle>, Json(param): Json<HaltParam>) -> Result<Json<Vec<PauseResult>>, AppError> {
+    let mut results = Vec::new();
+    for symbol in engine.symbols() {
+        let cancelled = engine.halt(&symbol, param.mode.unwrap_or_default(), param.orders.unwrap_or_default()).await?;
+        results.push(PauseResult::new(&engine, symbol, cancelled));
+    }
+    Ok(Json(results))
+}
+
+/// 恢复所有交易对
+pub async fn handler_unhalt(State(engine): State<EngineHandle>) -> Result<Json<Vec<PauseResult>>, AppError> {
+    let mut results = Vec::new();
+    for symbol in engine.symbols() {
+        engine.resume(&symbol)?;
+        results.push(PauseResult::new(&engine, symbol, 0));
+    }
+    Ok(Json(results))
+}
+
+/// 撤销交易对的所有挂单，不改变暂停状态
+pub async fn handler_purge(State(engine): State<EngineHandle>, Json(param): Json<PurgeParam>) -> Result<Json<PauseResult>, AppError> {
+    let cancelled = engine.cancel_all(&param.symbol).await?;
+    Ok(Json(PauseResult::new(&engine, param.symbol, cancelled)))
+}
+
+/// 查询所有交易对规格
+pub async fn handler_get_symbols(State(engine): State<EngineHandle>) -> Result<Json<Vec<SymbolSpec>>, AppError> {
+    Ok(Json(engine.registry().list()))
+}
+
+/// 运行时修改交易对规格，价格精度不可修改
+pub async fn handler_patch_symbol(State(engine): State<EngineHandle>, Path(symbol): Path<String>, Json(patch): Json<SymbolPatch>) -> Result<Json<SymbolSpec>, AppError> {
+    let spec = engine.update_symbol(&symbol, &patch).await?;
+    Ok(Json(spec))
+}
+
+/// 推送外部参考价，早于已有报价的推送忽略
+pub async fn handler_put_reference(State(engine): State<EngineHandle>, Json(price): Json<ReferencePrice>) -> Result<Json<ReferencePrice>, AppError> {
+    let reference = engine.reference_prices().ok_or_else(|| anyhow!("reference prices not enabled"))?;
+    engine.registry().get(&price.symbol).ok_or_else(|| anyhow!("unknown symbol, symbol={}", &price.symbol))?;
+    let symbol = price.symbol.clone();
+    reference.update(price)?;
+    Ok(Json(reference.get(&symbol).unwrap()))
+}
+
+/// 查询已触发做市商保护的账户
+pub async fn handler_get_mmp(State(engine): State<EngineHandle>) -> Result<Json<Vec<MmpTrip>>, AppError> {
+    let mmp = engine.mmp().ok_or_else(|| anyhow!("mmp not enabled"))?;
+    Ok(Json(mmp.tripped()))
+}
+
+/// 重新启用账户下单参数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RearmParam {
+    pub account: String,
+    pub symbol: String,
+}
+
+/// 重新启用结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RearmResult {
+    /// 账户之前是否已触发保护
+    pub rearmed: bool,
+}
+
+/// 重新启用已触发做市商保护的账户在交易对上的下单
+pub async fn handler_rearm_mmp(State(engine): State<EngineHandle>, Json(param): Json<RearmParam>) -> Result<Json<RearmResult>, AppError> {
+    let mmp = engine.mmp().ok_or_else(|| anyhow!("mmp not enabled"))?;
+    let rearmed = mmp.rearm(&param.account, &param.symbol);
+    info!("MMP REARMED: account={}, symbol={}, tripped={}", &param.account, &param.symbol, rearmed);
+    Ok(Json(RearmResult { rearmed }))
+}
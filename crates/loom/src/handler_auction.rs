@@ -0,0 +1,45 @@
+use anyhow::anyhow;
+use axum::extract::{Query, State};
+use axum::{Extension, Json};
+use serde::{Deserialize, Serialize};
+
+use loom_core::auction::AuctionResult;
+use loom_engine::engine::EngineHandle;
+use loom_engine::trader::MarketStatus;
+
+use crate::http_server::AppError;
+use crate::tenant::Tenant;
+
+#[derive(Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct AuctionParam {
+    /// 交易对
+    pub symbol: String,
+}
+
+/// 集合竞价参考结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuctionPreview {
+    pub symbol: String,
+    pub status: MarketStatus,
+    /// 快照版本号
+    pub version: u64,
+    /// 最后处理请求的时间
+    pub ts: u128,
+    /// 按当前挂单计算的开盘价、成交量和剩余方向数量，不在集合竞价或订单簿没有交叉时为空
+    pub indicative: Option<AuctionResult>,
+}
+
+/// 查询集合竞价的参考开盘结果，读取交易员发布的快照，随新订单进入订单簿更新
+pub async fn handler_auction(State(engine): State<EngineHandle>, Extension(tenant): Extension<Tenant>, Query(param): Query<AuctionParam>) -> Result<Json<AuctionPreview>, AppError> {
+    let symbol = tenant.scope(&param.symbol)?;
+    let (snapshot, status) = engine.snapshot(&symbol)
+        .zip(engine.status(&symbol))
+        .ok_or_else(|| anyhow!("unknown symbol, symbol={}", &param.symbol))?;
+    Ok(Json(AuctionPreview {
+        symbol: param.symbol,
+        status,
+        version: snapshot.version,
+        ts: snapshot.ts,
+        indicative: snapshot.indicative.clone(),
+    }))
+}
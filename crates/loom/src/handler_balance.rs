@@ -0,0 +1,56 @@
+use std::collections::BTreeMap;
+
+use anyhow::anyhow;
+use axum::extract::{Query, State};
+use axum::{Extension, Json};
+use bigdecimal::BigDecimal;
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use loom_engine::engine::EngineHandle;
+use loom_engine::ledger::{Balance, Ledger};
+
+use crate::http_server::AppError;
+use crate::tenant::Tenant;
+
+#[derive(Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct BalanceParam {
+    /// 账户
+    pub account: String,
+}
+
+/// 调整账户可用余额
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BalanceAdjustParam {
+    pub account: String,
+    /// 资产，如USDT
+    pub asset: String,
+    /// 调整金额，正数为充值，负数为提现
+    pub amount: BigDecimal,
+}
+
+/// 账户余额
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BalanceResult {
+    pub account: String,
+    /// 各资产的可用和冻结余额
+    pub balances: BTreeMap<String, Balance>,
+}
+
+fn ledger(engine: &EngineHandle) -> anyhow::Result<&Ledger> {
+    engine.ledger().map(|l| l.as_ref()).ok_or_else(|| anyhow!("ledger not enabled"))
+}
+
+/// 查询账户各资产的余额
+pub async fn handler_balance(State(engine): State<EngineHandle>, Extension(tenant): Extension<Tenant>, Query(param): Query<BalanceParam>) -> Result<Json<BalanceResult>, AppError> {
+    let balances = ledger(&engine)?.balances(&tenant.scope(&param.account)?);
+    Ok(Json(BalanceResult { account: param.account, balances }))
+}
+
+/// 充值或提现，提现金额不能超过可用余额，租户的账户带租户前缀
+pub async fn handler_adjust_balance(State(engine): State<EngineHandle>, Json(param): Json<BalanceAdjustParam>) -> Result<Json<BalanceResult>, AppError> {
+    let ledger = ledger(&engine)?;
+    ledger.adjust(&param.account, &param.asset, &param.amount)?;
+    info!("BALANCE ADJUSTED: account={}, asset={}, amount={}", &param.account, &param.asset, &param.amount);
+    Ok(Json(BalanceResult { balances: ledger.balances(&param.account), account: param.account }))
+}
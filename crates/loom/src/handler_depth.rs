@@ -0,0 +1,30 @@
+use axum::extract::{Query, State};
+use axum::{Extension, Json};
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+use loom_core::snapshot::BookSnapshot;
+use loom_engine::engine::EngineHandle;
+
+use crate::http_server::AppError;
+use crate::tenant::Tenant;
+
+#[derive(Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct DepthParam {
+    /// 交易对
+    pub symbol: String,
+    /// 返回档位数量
+    pub limit: Option<usize>,
+}
+
+/// 查询交易对深度，读取交易员发布的快照，不占用撮合锁
+pub async fn handler_depth(State(engine): State<EngineHandle>, Extension(tenant): Extension<Tenant>, Query(param): Query<DepthParam>) -> Result<Json<BookSnapshot>, AppError> {
+    let snapshot = engine.snapshot(&tenant.scope(&param.symbol)?)
+        .ok_or_else(|| anyhow!("unknown symbol, symbol={}", &param.symbol))?;
+    let mut snapshot = BookSnapshot::clone(&snapshot);
+    snapshot.symbol = param.symbol;
+    if let Some(limit) = param.limit {
+        snapshot.truncate(limit);
+    }
+    Ok(Json(snapshot))
+}
@@ -0,0 +1,156 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+
+use loom_engine::engine::EngineHandle;
+use loom_engine::warmup::RecoveryStatus;
+
+/// 探测Redis的超时时间
+const REDIS_PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Eq, PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum HealthStatus {
+    UP,
+    DOWN,
+}
+
+/// 组件状态
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComponentHealth {
+    pub status: HealthStatus,
+    pub detail: Option<String>,
+}
+
+impl ComponentHealth {
+    fn up(detail: Option<String>) -> ComponentHealth {
+        ComponentHealth { status: HealthStatus::UP, detail }
+    }
+
+    fn down(detail: String) -> ComponentHealth {
+        ComponentHealth { status: HealthStatus::DOWN, detail: Some(detail) }
+    }
+}
+
+/// 健康检查结果，所有组件UP时整体为UP
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthReport {
+    pub status: HealthStatus,
+    pub components: BTreeMap<String, ComponentHealth>,
+}
+
+impl HealthReport {
+    fn new(components: BTreeMap<String, ComponentHealth>) -> HealthReport {
+        let status = if components.values().all(|c| c.status == HealthStatus::UP) {
+            HealthStatus::UP
+        } else {
+            HealthStatus::DOWN
+        };
+        HealthReport { status, components }
+    }
+
+    fn into_response(self) -> (StatusCode, Json<HealthReport>) {
+        let code = match self.status {
+            HealthStatus::UP => StatusCode::OK,
+            HealthStatus::DOWN => StatusCode::SERVICE_UNAVAILABLE,
+        };
+        (code, Json(self))
+    }
+}
+
+/// 存活探针，交易员全部存活即可，不依赖外部组件避免被反复重启
+pub async fn handler_healthz(State(engine): State<EngineHandle>) -> (StatusCode, Json<HealthReport>) {
+    let mut components = BTreeMap::new();
+    components.insert(String::from("traders"), check_traders(&engine));
+    HealthReport::new(components).into_response()
+}
+
+/// 就绪探针，检查Redis连通性、交易员存活以及引擎是否正在关闭
+pub async fn handler_readyz(State(engine): State<EngineHandle>) -> (StatusCode, Json<HealthReport>) {
+    let mut components = BTreeMap::new();
+    components.insert(String::from("traders"), check_traders(&engine));
+    components.insert(String::from("redis"), check_redis(&engine).await);
+    components.insert(String::from("recovery"), check_recovery(&engine));
+    components.insert(String::from("replication"), check_replication(&engine));
+    components.insert(String::from("warmup"), check_warmup(&engine));
+    let engine_health = if engine.is_shutdown() {
+        ComponentHealth::down(String::from("draining"))
+    } else {
+        ComponentHealth::up(None)
+    };
+    components.insert(String::from("engine"), engine_health);
+    HealthReport::new(components).into_response()
+}
+
+fn check_traders(engine: &EngineHandle) -> ComponentHealth {
+    let dead: Vec<String> = engine.trader_liveness()
+        .into_iter()
+        .filter(|(_, alive)| !alive)
+        .map(|(symbol, _)| symbol)
+        .collect();
+    if dead.is_empty() {
+        ComponentHealth::up(None)
+    } else {
+        ComponentHealth::down(format!("trader stopped: {}", dead.join(",")))
+    }
+}
+
+fn check_recovery(engine: &EngineHandle) -> ComponentHealth {
+    let recovering: Vec<String> = engine.recovery_status()
+        .into_iter()
+        .filter(|(_, status)| !status.ready)
+        .map(|(symbol, status)| format!("{}({}/{})", symbol, status.read, status.total))
+        .collect();
+    if recovering.is_empty() {
+        ComponentHealth::up(None)
+    } else {
+        ComponentHealth::down(format!("recovering: {}", recovering.join(",")))
+    }
+}
+
+/// 提前监听时，预热完成前列出消费器状态和尚未恢复的交易对
+fn check_warmup(engine: &EngineHandle) -> ComponentHealth {
+    let warmup = engine.warmup();
+    if warmup.is_warm() {
+        return ComponentHealth::up(None);
+    }
+    let consumers = match warmup.consumers() {
+        None => String::from("connecting"),
+        Some(Ok(())) => String::from("connected"),
+        Some(Err(e)) => format!("unreachable({})", e),
+    };
+    let status: HashMap<String, RecoveryStatus> = engine.recovery_status().into_iter().collect();
+    let pending: Vec<String> = warmup.symbols()
+        .into_iter()
+        .filter_map(|symbol| match status.get(&symbol) {
+            Some(s) if s.ready => None,
+            Some(s) => Some(format!("{}({}/{})", symbol, s.read, s.total)),
+            None => Some(format!("{}(waiting)", symbol)),
+        })
+        .collect();
+    ComponentHealth::down(format!("consumers: {}, pending: {}", consumers, pending.join(",")))
+}
+
+/// 备机不接受撮合请求，不应接收流量
+fn check_replication(engine: &EngineHandle) -> ComponentHealth {
+    let replication = engine.replication();
+    if replication.accepts_orders() {
+        ComponentHealth::up(None)
+    } else {
+        ComponentHealth::down(format!("role={:?}, divergences={}", replication.role(), replication.divergences()))
+    }
+}
+
+async fn check_redis(engine: &EngineHandle) -> ComponentHealth {
+    let Some(cache) = engine.cache_manager() else {
+        return ComponentHealth::up(Some(String::from("persistence disabled")));
+    };
+    match tokio::time::timeout(REDIS_PROBE_TIMEOUT, cache.ping()).await {
+        Ok(Ok(())) => ComponentHealth::up(None),
+        Ok(Err(e)) => ComponentHealth::down(e.to_string()),
+        Err(_) => ComponentHealth::down(String::from("ping timeout")),
+    }
+}
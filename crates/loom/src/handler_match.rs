@@ -1,22 +1,58 @@
-use std::sync::Arc;
+use std::time::Duration;
 
 use axum::extract::State;
+use axum::Extension;
+use std::future::Future;
+
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
 use axum::Json;
 use bigdecimal::{BigDecimal, Zero};
 use bigdecimal::num_traits::zero;
+use log::warn;
 use serde::{Deserialize, Serialize};
-use tokio::sync::Mutex;
+use tracing::{info_span, Instrument};
 use validator::{Validate, ValidationError};
 
 use loom_core::order::{Order, OrderAction, OrderState, OrderTimeInForce, OrderType, TradeSide};
-use loom_core::order::OrderTimeInForce::{GTC, IOC};
+use loom_core::order::OrderTimeInForce::{GTC, GTD, GTX, IOC};
 use loom_core::order::OrderType::MARKET;
+use loom_core::symbol::Symbol;
 use loom_core::utils;
-use loom_engine::engine::MatchEngine;
+use loom_engine::audit;
+use loom_engine::engine::EngineHandle;
 
 use crate::http_server::AppError;
+use crate::tenant::Tenant;
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// 幂等记录保留时间
+const IDEMPOTENCY_TTL: Duration = Duration::from_secs(60);
+
+/// 幂等键已占用但请求仍在处理中
+const IDEMPOTENCY_PENDING: &str = "PENDING";
 
-pub type TraderMarketWrap = Arc<Mutex<MatchEngine>>;
+/// 记录的处理结果
+#[derive(Debug, Serialize, Deserialize)]
+struct IdempotentResult {
+    status: u16,
+    body: String,
+    /// 响应体是否为JSON
+    #[serde(default)]
+    json: bool,
+    /// 请求参数的摘要，相同幂等键的请求参数不同时拒绝重放
+    #[serde(default)]
+    fingerprint: Option<String>,
+}
+
+impl IdempotentResult {
+    fn into_response(self) -> Result<Response, AppError> {
+        let status = StatusCode::from_u16(self.status)?;
+        let content_type = if self.json { "application/json" } else { "text/plain; charset=utf-8" };
+        Ok((status, [(header::CONTENT_TYPE, content_type)], self.body).into_response())
+    }
+}
 
 #[derive(Eq, PartialEq, Debug, Serialize, Deserialize, Validate)]
 pub struct MatchOrderParam {
@@ -39,18 +75,81 @@ pub struct MatchOrderParam {
     pub tif: Option<OrderTimeInForce>,
     /// 订单动作
     pub action: OrderAction,
+    #[serde(default, deserialize_with = "deserialize_ts")]
     pub ts: Option<u128>,
+    /// 下单账户
+    #[validate(length(min = 1, max = 64))]
+    pub account: Option<String>,
+    /// GTD订单的到期时间，毫秒
+    #[serde(default, deserialize_with = "deserialize_ts")]
+    pub expire_ts: Option<u128>,
+    /// 最晚出队时间，毫秒，撮合时已超过则以TOO_LATE拒绝
+    #[serde(default, deserialize_with = "deserialize_ts")]
+    pub valid_until_ts: Option<u128>,
+    /// 订单最长排队时间，毫秒，从订单时间戳开始计算，与valid_until_ts二选一
+    pub max_age: Option<u64>,
+    /// 订单标签，例如策略ID或交易台，原样带入成交结果
+    #[validate(length(min = 1, max = 64))]
+    pub tag: Option<String>,
+}
+
+/// 毫秒时间戳按u64读取，v2接口展开参数时serde不支持u128
+fn deserialize_ts<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<u128>, D::Error> {
+    Ok(Option::<u64>::deserialize(deserializer)?.map(u128::from))
 }
 
 impl MatchOrderParam {
+    /// 校验请求参数
+    pub fn check(&self) -> Result<(), AppError> {
+        self.validate()?;
+        // 检查价格不能小于0
+        if let Some(price) = &self.price {
+            let price = price.clone();
+            // info!("price is {}", &price);
+            if price < Zero::zero() {
+                return Err(ValidationError::new("price < 0").into());
+            }
+        };
+        // 检查市价单TimeInForce不能为GTC
+        if let Some(tif) = self.tif {
+            if self.ord_type == MARKET && tif == GTC {
+                return Err(ValidationError::new("market price type order's tif can not be GTC").into());
+            }
+        }
+        // 只有GTD订单需要到期时间
+        if (self.tif == Some(GTD)) != self.expire_ts.is_some() {
+            return Err(ValidationError::new("expire_ts is required for and only for GTD orders").into());
+        }
+        if self.ord_type == MARKET && self.tif == Some(GTD) {
+            return Err(ValidationError::new("market price type order's tif can not be GTD").into());
+        }
+        if self.ord_type == MARKET && self.tif == Some(GTX) {
+            return Err(ValidationError::new("market price type order's tif can not be GTX").into());
+        }
+        if self.valid_until_ts.is_some() && self.max_age.is_some() {
+            return Err(ValidationError::new("valid_until_ts and max_age can not be both set").into());
+        }
+        if self.action == OrderAction::PLACE && self.max_age == Some(0) {
+            return Err(ValidationError::new("max_age must be positive").into());
+        }
+        Ok(())
+    }
+
+    /// 交易对和账户转换为租户内的名称
+    pub fn scoped(mut self, tenant: &Tenant) -> anyhow::Result<MatchOrderParam> {
+        self.symbol = tenant.scope(&self.symbol)?;
+        self.account = self.account.as_deref().map(|account| tenant.scope(account)).transpose()?;
+        Ok(self)
+    }
+
     pub fn to_order(&self) -> Order {
         let now_ts = self.ts.unwrap_or_else(|| { utils::now_ts() });
         Order {
             id: self.id,
-            symbol: self.symbol.clone(),
+            symbol: Symbol::from(&self.symbol),
             side: self.side,
             qty: self.qty,
-            price: self.price.as_ref().map(|x| x.clone()).unwrap_or(zero()),
+            price: self.price.clone().unwrap_or(zero()),
             acc_fill_qty: 0,
             ord_type: self.ord_type,
             ts: now_ts,
@@ -64,28 +163,82 @@ impl MatchOrderParam {
                 }
             }),
             action: self.action,
+            account: self.account.clone(),
+            seq: 0,
+            expire_ts: self.expire_ts.unwrap_or(0),
+            ts_ns: 0,
+            valid_until_ts: self.valid_until_ts
+                .or_else(|| self.max_age.map(|age| now_ts + age as u128))
+                .unwrap_or(0),
+            tag: self.tag.clone(),
         }
     }
 }
 
-pub async fn handler_match(State(state): State<TraderMarketWrap>, Json(param): Json<MatchOrderParam>) -> Result<String, AppError> {
-    param.validate()?;
-    // 检查价格不能小于0
-    if let Some(price) = &param.price {
-        let price = price.clone();
-        // info!("price is {}", &price);
-        if price < Zero::zero() {
-            return Err(ValidationError::new("price < 0").into());
+/// 提交撮合请求，按Idempotency-Key或客户端订单号去重，重试的请求返回首次处理的结果
+pub async fn handler_match(State(engine): State<EngineHandle>, Extension(tenant): Extension<Tenant>, headers: HeaderMap, Json(param): Json<MatchOrderParam>) -> Result<Response, AppError> {
+    let param = param.scoped(&tenant)?;
+    let key = tenant.scope_key(&idempotency_key(&headers, &param));
+    let fingerprint = fingerprint(&param)?;
+    idempotent(&engine, &key, &fingerprint, false, async {
+        match submit(&engine, param).await {
+            Ok(body) => (StatusCode::OK, body),
+            Err(e) => (e.status(), e.to_string()),
+        }
+    }).await
+}
+
+/// 按幂等键执行请求，键已有记录时直接返回记录的结果，json表示响应体是否为JSON
+///
+/// 记录的请求摘要与fingerprint不一致时说明幂等键被不同的请求重用，返回422
+pub(crate) async fn idempotent<F>(engine: &EngineHandle, key: &str, fingerprint: &str, json: bool, fut: F) -> Result<Response, AppError>
+    where F: Future<Output=(StatusCode, String)>
+{
+    let cache = engine.require_cache()?;
+    if let Some(existing) = cache.claim_idempotency(key, IDEMPOTENCY_PENDING, IDEMPOTENCY_TTL).await? {
+        if existing == IDEMPOTENCY_PENDING {
+            return Ok((StatusCode::CONFLICT, "request in progress").into_response());
+        }
+        let result: IdempotentResult = serde_json::from_str(&existing)?;
+        if result.fingerprint.as_deref().is_some_and(|f| f != fingerprint) {
+            return Ok((StatusCode::UNPROCESSABLE_ENTITY, "idempotency key reused with a different request").into_response());
         }
-    };
-    // 检查市价单TimeInForce不能为GTC
-    if let Some(tif) = param.tif {
-        if param.ord_type == MARKET && tif == GTC {
-            return Err(ValidationError::new("market price type order's tif can not be GTC").into());
+        let mut resp = result.into_response()?;
+        resp.headers_mut().insert("idempotent-replayed", header::HeaderValue::from_static("true"));
+        return Ok(resp);
+    }
+    let (status, body) = fut.await;
+    let result = IdempotentResult { status: status.as_u16(), body, json, fingerprint: Some(fingerprint.to_string()) };
+    // 请求已经处理，记录失败时仍返回处理结果，不能让客户端把已受理的请求当作失败
+    if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+        // 可重试的失败不记录结果
+        if let Err(e) = cache.release_idempotency(key).await {
+            warn!("IDEMPOTENCY RELEASE FAILED: key={}, err={}", key, e);
         }
+    } else if let Err(e) = cache.store_idempotency(key, &serde_json::to_string(&result)?, IDEMPOTENCY_TTL).await {
+        warn!("IDEMPOTENCY STORE FAILED: key={}, err={}", key, e);
+    }
+    result.into_response()
+}
+
+/// 请求参数的摘要，按解析后的参数计算，与字段顺序和空白无关
+pub(crate) fn fingerprint<T: Serialize>(param: &T) -> Result<String, AppError> {
+    Ok(audit::hex(&audit::sha256(&serde_json::to_vec(param)?)))
+}
+
+/// 幂等键，未携带请求头时使用客户端订单号，按账户隔离
+pub(crate) fn idempotency_key(headers: &HeaderMap, param: &MatchOrderParam) -> String {
+    let account = param.account.as_deref().unwrap_or("");
+    match headers.get(IDEMPOTENCY_KEY_HEADER).and_then(|v| v.to_str().ok()) {
+        Some(key) => format!("{}:KEY:{}", account, key),
+        None => format!("{}:ORDER:{}:{}:{}", account, param.symbol, param.action, param.id),
     }
-    let mut market = state.lock().await;
+}
+
+async fn submit(engine: &EngineHandle, param: MatchOrderParam) -> Result<String, AppError> {
+    param.check()?;
     let order = param.to_order();
-    market.feed(order).await?;
+    let span = info_span!("http.match", symbol = %order.symbol, oid = order.id, action = ?order.action);
+    engine.feed(order).instrument(span).await?;
     Ok(String::from("ACCEPTED"))
 }
\ No newline at end of file
@@ -0,0 +1,226 @@
+use std::time::Duration;
+
+use axum::extract::State;
+use axum::Extension;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::Response;
+use axum::Json;
+use bigdecimal::BigDecimal;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{info_span, Instrument};
+
+use loom_core::market::{CancelResult, FillSummary, MatchTrade};
+use loom_core::order::{Order, OrderAction, OrderState, OrderTimeInForce, OrderType};
+use loom_core::utils;
+use loom_engine::engine::{EngineHandle, OrderNotFound};
+
+use crate::handler_match::{fingerprint, idempotency_key, idempotent, MatchOrderParam};
+use crate::http_server::AppError;
+use crate::tenant::Tenant;
+
+/// 同步模式等待撮合结果的最长时间
+const SYNC_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// v2下单参数
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OrderParamV2 {
+    #[serde(flatten)]
+    pub order: MatchOrderParam,
+    /// 同步模式，等待撮合完成后返回成交
+    pub sync: Option<bool>,
+}
+
+/// v2下单结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderResponse {
+    /// 客户端订单ID
+    pub id: u64,
+    /// 引擎分配的序列号
+    pub seq: u64,
+    pub symbol: String,
+    pub action: OrderAction,
+    /// 请求受理时间
+    pub accepted_ts: u128,
+    /// 撮合后的订单状态，仅同步模式且撮合完成时返回
+    pub state: Option<OrderState>,
+    /// 撮合产生的成交，仅同步模式且撮合完成时返回
+    pub trades: Option<Vec<MatchTrade>>,
+    /// 只挂单改价后的委托价格，仅同步模式且发生改价时返回
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub adjusted_px: Option<BigDecimal>,
+    /// 撤单结果，仅同步撤单且撮合完成时返回
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cancel: Option<CancelResult>,
+    /// 成交汇总，包含累计成交数量、均价和逐笔成交，仅同步下单且撮合完成时返回
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fill: Option<FillSummary>,
+}
+
+impl OrderResponse {
+    fn new(order: &Order, accepted_ts: u128, trades: Option<Vec<MatchTrade>>) -> OrderResponse {
+        let state = trades.as_ref().and_then(|trades| resolve_state(order, trades));
+        let fill = match (&trades, order.action) {
+            (Some(trades), OrderAction::PLACE) => Some(FillSummary::of(order.id, trades, state)),
+            _ => None,
+        };
+        OrderResponse {
+            id: order.id,
+            seq: order.seq,
+            symbol: order.symbol.to_string(),
+            action: order.action,
+            accepted_ts,
+            state,
+            adjusted_px: trades.iter().flatten().find(|t| t.taker_oid == order.id && t.is_reprice()).and_then(|t| t.taker_px.clone()),
+            trades,
+            cancel: None,
+            fill,
+        }
+    }
+
+    /// 同步撤单的结果，订单已完全成交时状态为FULL_FILLED
+    fn with_cancel(mut self, cancel: Option<CancelResult>) -> OrderResponse {
+        if cancel == Some(CancelResult::ALREADY_FILLED) {
+            self.state = Some(OrderState::FULL_FILLED);
+        }
+        self.cancel = cancel;
+        self
+    }
+}
+
+impl OrderResponse {
+    /// 去掉交易对和账户的租户前缀
+    fn unscoped(mut self, tenant: &Tenant) -> OrderResponse {
+        self.symbol = tenant.unscope(&self.symbol);
+        for trade in self.trades.iter_mut().flatten() {
+            trade.symbol = tenant.unscope(&trade.symbol).into();
+            trade.taker_account = trade.taker_account.as_deref().map(|a| tenant.unscope(a));
+            trade.maker_account = trade.maker_account.as_deref().map(|a| tenant.unscope(a));
+        }
+        self
+    }
+}
+
+/// 由撮合结果推断订单状态，撤单未找到订单时为空
+fn resolve_state(order: &Order, trades: &[MatchTrade]) -> Option<OrderState> {
+    if let Some(trade) = trades.iter().rev().find(|t| t.taker_oid == order.id) {
+        return Some(trade.taker_state);
+    }
+    match order.action {
+        // 未成交的限价单进入订单簿
+        OrderAction::PLACE if order.ord_type == OrderType::LIMIT && matches!(order.tif, OrderTimeInForce::GTC | OrderTimeInForce::GTD | OrderTimeInForce::GTX) => Some(OrderState::LIVE),
+        _ => None,
+    }
+}
+
+/// v2下单接口，返回JSON结果，幂等规则与v1一致
+pub async fn handler_order(State(engine): State<EngineHandle>, Extension(tenant): Extension<Tenant>, headers: HeaderMap, Json(mut param): Json<OrderParamV2>) -> Result<Response, AppError> {
+    param.order = param.order.scoped(&tenant)?;
+    let key = tenant.scope_key(&format!("V2:{}", idempotency_key(&headers, &param.order)));
+    let fingerprint = fingerprint(&param)?;
+    idempotent(&engine, &key, &fingerprint, true, async {
+        match submit(&engine, param).await {
+            Ok(resp) => (StatusCode::OK, json!(resp.unscoped(&tenant)).to_string()),
+            Err(e) => (e.status(), json!({ "error": e.to_string() }).to_string()),
+        }
+    }).await
+}
+
+async fn submit(engine: &EngineHandle, param: OrderParamV2) -> Result<OrderResponse, AppError> {
+    param.order.check()?;
+    let mut order = param.order.to_order();
+    let span = info_span!("http.order", symbol = %order.symbol, oid = order.id, action = ?order.action, sync = param.sync);
+    let accepted_ts = utils::now_ts();
+    let (seq, reply) = if param.sync.unwrap_or(false) {
+        engine.feed_sync(order.clone(), SYNC_TIMEOUT).instrument(span).await?
+    } else {
+        (engine.feed(order.clone()).instrument(span).await?, None)
+    };
+    order.seq = seq;
+    let cancel = reply.as_ref().and_then(|reply| reply.cancel);
+    let trades = reply.map(|reply| reply.trades);
+    // 同步减量没有结果时订单不在订单簿中，撤单由撤单结果说明原因
+    if order.action == OrderAction::REDUCE && trades.as_ref().is_some_and(|t| t.iter().all(|t| t.taker_oid != order.id)) {
+        return Err(OrderNotFound { symbol: order.symbol.to_string(), oid: order.id }.into());
+    }
+    Ok(OrderResponse::new(&order, accepted_ts, trades).with_cancel(cancel))
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use bigdecimal::BigDecimal;
+
+    use loom_core::market::MarketBook;
+    use loom_core::order::{Order, OrderState, OrderTimeInForce, TradeSide};
+    use loom_core::symbol::{CrossPolicy, SymbolSpec};
+    use loom_core::testing::new_order;
+
+    use crate::handler_order::{OrderParamV2, OrderResponse};
+
+    #[test]
+    fn resolve_state_test() {
+        let mut market = MarketBook::new("LOOM-USDT-SPOT");
+        let maker = new_order(1, TradeSide::BUY, 2, "100");
+        let resp = OrderResponse::new(&maker, 0, Some(market.try_match(maker.clone()).to_vec()));
+        assert_eq!(resp.state, Some(OrderState::LIVE));
+        let taker = new_order(2, TradeSide::SELL, 2, "100");
+        let resp = OrderResponse::new(&taker, 0, Some(market.try_match(taker.clone()).to_vec()));
+        assert_eq!(resp.state, Some(OrderState::FULL_FILLED));
+        assert_eq!(resp.trades.unwrap().len(), 1);
+        let ioc = Order { tif: OrderTimeInForce::IOC, ..new_order(3, TradeSide::SELL, 2, "100") };
+        let resp = OrderResponse::new(&ioc, 0, Some(market.try_match(ioc.clone()).to_vec()));
+        assert_eq!(resp.state, Some(OrderState::CANCELED));
+        // 异步模式不返回状态
+        assert_eq!(OrderResponse::new(&ioc, 0, None).state, None);
+        // 只挂单改价后返回新价格
+        let mut market = MarketBook::with_spec(SymbolSpec::new("LOOM-USDT-SPOT").with_tick_size(BigDecimal::from(1)).with_cross_policy(CrossPolicy::Reprice));
+        market.try_match(new_order(4, TradeSide::SELL, 2, "100"));
+        let gtx = Order { tif: OrderTimeInForce::GTX, ..new_order(5, TradeSide::BUY, 2, "100") };
+        let resp = OrderResponse::new(&gtx, 0, Some(market.try_match(gtx.clone()).to_vec()));
+        assert_eq!((resp.state, resp.adjusted_px), (Some(OrderState::LIVE), Some(BigDecimal::from(99))));
+    }
+
+    #[test]
+    fn fill_summary_test() {
+        let mut market = MarketBook::new("LOOM-USDT-SPOT");
+        market.try_match(new_order(1, TradeSide::SELL, 2, "100"));
+        let mut maker = new_order(2, TradeSide::SELL, 2, "100");
+        maker.price = BigDecimal::from(103);
+        market.try_match(maker);
+        // 吃掉两档后剩余数量撤销
+        let mut taker = Order { tif: OrderTimeInForce::IOC, ..new_order(3, TradeSide::BUY, 2, "100") };
+        taker.qty = 5;
+        taker.price = BigDecimal::from(103);
+        let resp = OrderResponse::new(&taker, 0, Some(market.try_match(taker.clone()).to_vec()));
+        let fill = resp.fill.unwrap();
+        assert_eq!((fill.filled_qty, fill.notional, fill.avg_px), (4, BigDecimal::from(406), Some(BigDecimal::from_str("101.5").unwrap())));
+        assert_eq!(fill.fills.iter().map(|f| (f.maker_oid, f.qty)).collect::<Vec<_>>(), vec![(1, 2), (2, 2)]);
+        assert_eq!(fill.state, Some(OrderState::PARTIAL_CANCELLED));
+        // 异步模式不返回汇总
+        assert!(OrderResponse::new(&taker, 0, None).fill.is_none());
+    }
+
+    #[test]
+    fn client_compat_test() {
+        use loom_client::api;
+
+        let request = api::OrderRequest::limit(1, "LOOM-USDT-SPOT", TradeSide::BUY, 2, BigDecimal::from(100))
+            .with_expire_ts(1000)
+            .with_sync(true);
+        let param: OrderParamV2 = serde_json::from_str(&serde_json::to_string(&request).unwrap()).unwrap();
+        param.order.check().unwrap();
+        assert_eq!((param.order.tif, param.order.expire_ts, param.sync), (Some(OrderTimeInForce::GTD), Some(1000), Some(true)));
+        let cancel: OrderParamV2 = serde_json::from_value(serde_json::to_value(api::OrderRequest::cancel(1, "LOOM-USDT-SPOT", TradeSide::BUY)).unwrap()).unwrap();
+        cancel.order.check().unwrap();
+
+        let mut market = MarketBook::new("LOOM-USDT-SPOT");
+        market.try_match(new_order(1, TradeSide::BUY, 2, "100"));
+        let taker = new_order(2, TradeSide::SELL, 2, "100");
+        let resp = OrderResponse::new(&taker, 0, Some(market.try_match(taker.clone()).to_vec()));
+        let client: api::OrderResponse = serde_json::from_value(serde_json::to_value(&resp).unwrap()).unwrap();
+        assert_eq!((client.state, client.fill), (resp.state, resp.fill));
+        assert_eq!(client.trades.unwrap(), resp.trades.unwrap());
+    }
+}
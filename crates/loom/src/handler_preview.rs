@@ -0,0 +1,38 @@
+use anyhow::anyhow;
+use axum::extract::{Query, State};
+use axum::{Extension, Json};
+use bigdecimal::BigDecimal;
+use serde::{Deserialize, Serialize};
+
+use loom_core::order::TradeSide;
+use loom_core::snapshot::MatchPreview;
+use loom_engine::engine::EngineHandle;
+
+use crate::http_server::AppError;
+use crate::tenant::Tenant;
+
+#[derive(Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct PreviewParam {
+    /// 交易对
+    pub symbol: String,
+    /// 交易方向
+    pub side: TradeSide,
+    /// 委托数量
+    pub qty: u64,
+    /// 限价，为空时按市价单模拟
+    pub px: Option<BigDecimal>,
+    /// 最多吃掉的对手方档位数量，默认不限制
+    pub depth: Option<usize>,
+}
+
+/// 按交易员发布的快照模拟撮合，估算成交均价和滑点，不占用撮合锁，不修改订单簿也不产生事件
+pub async fn handler_preview(State(engine): State<EngineHandle>, Extension(tenant): Extension<Tenant>, Query(param): Query<PreviewParam>) -> Result<Json<MatchPreview>, AppError> {
+    if param.qty == 0 {
+        return Err(anyhow!("qty must be positive").into());
+    }
+    let snapshot = engine.snapshot(&tenant.scope(&param.symbol)?)
+        .ok_or_else(|| anyhow!("unknown symbol, symbol={}", &param.symbol))?;
+    let mut preview = snapshot.preview(param.side, param.qty, param.px.as_ref(), param.depth.unwrap_or(usize::MAX));
+    preview.symbol = param.symbol;
+    Ok(Json(preview))
+}
@@ -0,0 +1,45 @@
+use anyhow::anyhow;
+use axum::extract::{Query, State};
+use axum::{Extension, Json};
+use bigdecimal::BigDecimal;
+use serde::{Deserialize, Serialize};
+
+use loom_engine::engine::EngineHandle;
+use loom_engine::trader::MarketStatus;
+
+use crate::http_server::AppError;
+use crate::tenant::Tenant;
+
+#[derive(Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct PriceParam {
+    /// 交易对
+    pub symbol: String,
+}
+
+/// 最新成交价和市场状态
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PriceResult {
+    pub symbol: String,
+    /// 最新成交价，没有成交时为0
+    pub px: BigDecimal,
+    /// 最后处理请求的时间
+    pub ts: u128,
+    /// 快照版本号
+    pub version: u64,
+    pub status: MarketStatus,
+}
+
+/// 查询交易对最新成交价，读取交易员发布的快照，不占用撮合锁
+pub async fn handler_price(State(engine): State<EngineHandle>, Extension(tenant): Extension<Tenant>, Query(param): Query<PriceParam>) -> Result<Json<PriceResult>, AppError> {
+    let symbol = tenant.scope(&param.symbol)?;
+    let (snapshot, status) = engine.snapshot(&symbol)
+        .zip(engine.status(&symbol))
+        .ok_or_else(|| anyhow!("unknown symbol, symbol={}", &param.symbol))?;
+    Ok(Json(PriceResult {
+        symbol: param.symbol,
+        px: snapshot.px.clone(),
+        ts: snapshot.ts,
+        version: snapshot.version,
+        status,
+    }))
+}
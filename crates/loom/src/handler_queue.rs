@@ -0,0 +1,50 @@
+use anyhow::anyhow;
+use axum::extract::{Query, State};
+use axum::{Extension, Json};
+use bigdecimal::BigDecimal;
+use serde::{Deserialize, Serialize};
+
+use loom_core::snapshot::QueuePosition;
+use loom_engine::engine::{EngineHandle, OrderNotFound};
+
+use crate::http_server::AppError;
+use crate::tenant::Tenant;
+
+/// 计算买卖失衡的默认档位数量
+const DEFAULT_IMBALANCE_LEVELS: usize = 5;
+
+#[derive(Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct QueueParam {
+    /// 交易对
+    pub symbol: String,
+    /// 客户端订单ID
+    pub oid: u64,
+    /// 下单账户，只能查询该账户的挂单
+    pub account: String,
+    /// 计算买卖失衡的档位数量，默认5
+    pub levels: Option<usize>,
+}
+
+/// 挂单排队位置和盘口失衡
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueResult {
+    pub account: String,
+    pub position: QueuePosition,
+    /// 前levels档的买卖数量失衡，(买量 - 卖量) / (买量 + 卖量)
+    pub imbalance: Option<BigDecimal>,
+}
+
+/// 查询账户挂单在价格档位中的排队位置，由交易员在撮合线程中计算，订单不属于该账户时按不存在处理
+pub async fn handler_queue(State(engine): State<EngineHandle>, Extension(tenant): Extension<Tenant>, Query(param): Query<QueueParam>) -> Result<Json<QueueResult>, AppError> {
+    let (symbol, account) = (tenant.scope(&param.symbol)?, tenant.scope(&param.account)?);
+    let snapshot = engine.snapshot(&symbol)
+        .ok_or_else(|| anyhow!("unknown symbol, symbol={}", &param.symbol))?;
+    let mut position = engine.queue_position(&symbol, param.oid, Some(&account)).await?
+        .ok_or_else(|| OrderNotFound { symbol: param.symbol.clone(), oid: param.oid })?;
+    position.symbol = param.symbol;
+    Ok(Json(QueueResult {
+        account: param.account,
+        position,
+        imbalance: snapshot.imbalance(param.levels.unwrap_or(DEFAULT_IMBALANCE_LEVELS)),
+    }))
+}
@@ -0,0 +1,149 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use axum::extract::{Query, State};
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+
+use loom_core::snapshot::BookStats;
+use loom_engine::engine::EngineHandle;
+use loom_engine::metrics::{self, LatencySummary};
+use loom_engine::reconcile::DriftKind;
+use loom_engine::tenant;
+
+/// 交易对统计
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SymbolStats {
+    /// 从请求出队到成交推送完成的延迟
+    pub latency: LatencySummary,
+    /// 请求在队列中的等待时间
+    pub queue_wait: LatencySummary,
+    /// 排队中的撮合请求数量
+    pub pending: usize,
+    /// 最新快照中的挂单汇总
+    pub book: BookStats,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatsParam {
+    /// 只返回该租户的交易对，交易对名称不带租户前缀
+    pub tenant: Option<String>,
+}
+
+/// 管理统计接口，按交易对返回撮合延迟分位数
+pub async fn handler_stats(State(engine): State<EngineHandle>, Query(param): Query<StatsParam>) -> Json<BTreeMap<String, SymbolStats>> {
+    let waits: BTreeMap<String, _> = engine.queue_waits().into_iter().collect();
+    let stats = engine.latencies()
+        .into_iter()
+        .filter_map(|(symbol, latency)| {
+            let name = match (&param.tenant, tenant::split(&symbol)) {
+                (None, _) => symbol.clone(),
+                (Some(id), (Some(owner), name)) if id == owner => name.to_string(),
+                _ => return None,
+            };
+            let pending = engine.sender(&symbol).map(|s| s.pending()).unwrap_or(0);
+            let queue_wait = waits.get(&symbol).map(|w| w.histogram().summary()).unwrap_or_default();
+            let book = engine.snapshot(&symbol).map(|s| s.stats.clone()).unwrap_or_default();
+            Some((name, SymbolStats { latency: latency.summary(), queue_wait, pending, book }))
+        })
+        .collect();
+    Json(stats)
+}
+
+/// Prometheus文本格式指标
+pub async fn handler_metrics(State(engine): State<EngineHandle>) -> impl IntoResponse {
+    let mut body = String::new();
+    let _ = writeln!(body, "# HELP loom_match_latency_seconds Time from order dequeue to trade publication.");
+    let _ = writeln!(body, "# TYPE loom_match_latency_seconds summary");
+    for (symbol, latency) in engine.latencies() {
+        for q in [0.5, 0.99, 0.999] {
+            let _ = writeln!(
+                body,
+                "loom_match_latency_seconds{{symbol=\"{}\",quantile=\"{}\"}} {}",
+                symbol, q, latency.quantile(q).as_secs_f64(),
+            );
+        }
+        let _ = writeln!(body, "loom_match_latency_seconds_sum{{symbol=\"{}\"}} {}", symbol, latency.sum().as_secs_f64());
+        let _ = writeln!(body, "loom_match_latency_seconds_count{{symbol=\"{}\"}} {}", symbol, latency.count());
+    }
+    let _ = writeln!(body, "# HELP loom_queue_wait_seconds Time an order waits in the trader queue before dequeue.");
+    let _ = writeln!(body, "# TYPE loom_queue_wait_seconds summary");
+    for (symbol, wait) in engine.queue_waits() {
+        let histogram = wait.histogram();
+        for q in [0.5, 0.99, 0.999] {
+            let _ = writeln!(
+                body,
+                "loom_queue_wait_seconds{{symbol=\"{}\",quantile=\"{}\"}} {}",
+                symbol, q, histogram.quantile(q).as_secs_f64(),
+            );
+        }
+        let _ = writeln!(body, "loom_queue_wait_seconds_sum{{symbol=\"{}\"}} {}", symbol, histogram.sum().as_secs_f64());
+        let _ = writeln!(body, "loom_queue_wait_seconds_count{{symbol=\"{}\"}} {}", symbol, histogram.count());
+    }
+    let _ = writeln!(body, "# HELP loom_throttled_total New orders rejected because the symbol order rate limit was exceeded.");
+    let _ = writeln!(body, "# TYPE loom_throttled_total counter");
+    for (symbol, throttled) in engine.throttled() {
+        let _ = writeln!(body, "loom_throttled_total{{symbol=\"{}\"}} {}", symbol, throttled);
+    }
+    let watermarks = engine.id_watermarks();
+    if !watermarks.is_empty() {
+        let _ = writeln!(body, "# HELP loom_order_id_high Highest client order id accepted per symbol.");
+        let _ = writeln!(body, "# TYPE loom_order_id_high gauge");
+        for (symbol, ids) in &watermarks {
+            let _ = writeln!(body, "loom_order_id_high{{symbol=\"{}\"}} {}", symbol, ids.high());
+        }
+        let _ = writeln!(body, "# HELP loom_order_id_gaps_total Client order ids skipped between accepted orders.");
+        let _ = writeln!(body, "# TYPE loom_order_id_gaps_total counter");
+        for (symbol, ids) in &watermarks {
+            let _ = writeln!(body, "loom_order_id_gaps_total{{symbol=\"{}\"}} {}", symbol, ids.gaps());
+        }
+        let _ = writeln!(body, "# HELP loom_order_id_regressions_total Accepted orders whose client id did not exceed the high-water mark.");
+        let _ = writeln!(body, "# TYPE loom_order_id_regressions_total counter");
+        for (symbol, ids) in &watermarks {
+            let _ = writeln!(body, "loom_order_id_regressions_total{{symbol=\"{}\"}} {}", symbol, ids.regressions());
+        }
+    }
+    let books: Vec<_> = engine.symbols().into_iter()
+        .filter_map(|symbol| engine.snapshot(&symbol).map(|s| (symbol, s.stats.clone())))
+        .collect();
+    let _ = writeln!(body, "# HELP loom_book_orders Resting orders per book side.");
+    let _ = writeln!(body, "# TYPE loom_book_orders gauge");
+    for (symbol, stats) in &books {
+        let _ = writeln!(body, "loom_book_orders{{symbol=\"{}\",side=\"bid\"}} {}", symbol, stats.bids.orders);
+        let _ = writeln!(body, "loom_book_orders{{symbol=\"{}\",side=\"ask\"}} {}", symbol, stats.asks.orders);
+    }
+    let _ = writeln!(body, "# HELP loom_book_qty Resting quantity per book side.");
+    let _ = writeln!(body, "# TYPE loom_book_qty gauge");
+    for (symbol, stats) in &books {
+        let _ = writeln!(body, "loom_book_qty{{symbol=\"{}\",side=\"bid\"}} {}", symbol, stats.bids.qty);
+        let _ = writeln!(body, "loom_book_qty{{symbol=\"{}\",side=\"ask\"}} {}", symbol, stats.asks.qty);
+    }
+    let _ = writeln!(body, "# HELP loom_book_notional Resting notional (price times remaining quantity) per book side.");
+    let _ = writeln!(body, "# TYPE loom_book_notional gauge");
+    for (symbol, stats) in &books {
+        let _ = writeln!(body, "loom_book_notional{{symbol=\"{}\",side=\"bid\"}} {}", symbol, stats.bids.notional);
+        let _ = writeln!(body, "loom_book_notional{{symbol=\"{}\",side=\"ask\"}} {}", symbol, stats.asks.notional);
+    }
+    if let Some(reconciler) = engine.reconciler() {
+        let _ = writeln!(body, "# HELP loom_cache_drift Orders that differ between the book and the cache at the last reconciliation.");
+        let _ = writeln!(body, "# TYPE loom_cache_drift gauge");
+        for report in reconciler.reports() {
+            for kind in DriftKind::ALL {
+                let _ = writeln!(body, "loom_cache_drift{{symbol=\"{}\",kind=\"{:?}\"}} {}", report.symbol, kind, report.count(kind));
+            }
+        }
+        let _ = writeln!(body, "# HELP loom_cache_repairs_total Drifted orders repaired by reconciliation.");
+        let _ = writeln!(body, "# TYPE loom_cache_repairs_total counter");
+        for (symbol, repaired) in reconciler.repaired() {
+            let _ = writeln!(body, "loom_cache_repairs_total{{symbol=\"{}\"}} {}", symbol, repaired);
+        }
+    }
+    if let Some(bytes) = metrics::resident_memory_bytes() {
+        let _ = writeln!(body, "# HELP process_resident_memory_bytes Resident memory size in bytes.");
+        let _ = writeln!(body, "# TYPE process_resident_memory_bytes gauge");
+        let _ = writeln!(body, "process_resident_memory_bytes {}", bytes);
+    }
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body)
+}
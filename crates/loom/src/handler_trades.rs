@@ -0,0 +1,52 @@
+use axum::extract::{Query, State};
+use axum::{Extension, Json};
+use serde::{Deserialize, Serialize};
+
+use loom_core::market::MatchTrade;
+use loom_engine::archive::{TradeArchive, TradeCursor};
+
+use crate::http_server::AppError;
+use crate::tenant::Tenant;
+
+/// 默认每页条数
+const DEFAULT_PAGE_LIMIT: usize = 100;
+
+#[derive(Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct MyTradesParam {
+    pub symbol: String,
+    pub account: String,
+    /// 开始时间，毫秒，包含
+    pub from: Option<u64>,
+    /// 结束时间，毫秒，不包含
+    pub to: Option<u64>,
+    /// 上一页返回的next_cursor
+    pub cursor: Option<String>,
+    /// 每页条数，默认100，最多1000
+    pub limit: Option<usize>,
+}
+
+/// 账户成交的一页
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MyTradesResult {
+    pub trades: Vec<MatchTrade>,
+    /// 下一页的位置，没有更多成交时为空
+    pub next_cursor: Option<String>,
+}
+
+/// 从成交归档分页查询账户的成交记录，按时间升序返回
+pub async fn handler_my_trades(State(archive): State<TradeArchive>, Extension(tenant): Extension<Tenant>, Query(param): Query<MyTradesParam>) -> Result<Json<MyTradesResult>, AppError> {
+    let symbol = tenant.scope(&param.symbol)?;
+    let account = tenant.scope(&param.account)?;
+    let cursor = param.cursor.as_deref().map(|c| c.parse::<TradeCursor>()).transpose()?;
+    let from = param.from.map(u128::from).unwrap_or(0);
+    let to = param.to.map(u128::from).unwrap_or(u128::MAX);
+    let limit = param.limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+    let page = tokio::task::spawn_blocking(move || archive.account_trades(&symbol, &account, from, to, cursor, limit)).await??;
+    let trades = page.trades.into_iter().map(|mut trade| {
+        trade.symbol = tenant.unscope(&trade.symbol).into();
+        trade.taker_account = trade.taker_account.as_deref().map(|a| tenant.unscope(a));
+        trade.maker_account = trade.maker_account.as_deref().map(|a| tenant.unscope(a));
+        trade
+    }).collect();
+    Ok(Json(MyTradesResult { trades, next_cursor: page.next.map(|c| c.to_string()) }))
+}
@@ -0,0 +1,212 @@
+use std::time::{Duration, Instant};
+
+use anyhow::anyhow;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Query, State};
+use axum::response::Response;
+use axum::Extension;
+use bigdecimal::BigDecimal;
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use loom_core::snapshot::{BookSnapshot, LevelSnapshot};
+use loom_engine::engine::EngineHandle;
+
+use crate::http_server::AppError;
+use crate::tenant::Tenant;
+
+/// 推送的默认档位数量
+const DEFAULT_FEED_LEVELS: usize = 20;
+
+/// 检查快照是否更新的间隔
+const FEED_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// 没有更新时发送心跳的间隔
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct DepthFeedParam {
+    /// 交易对
+    pub symbol: String,
+    /// 推送档位数量，默认20
+    pub limit: Option<usize>,
+}
+
+/// 深度推送消息，每条消息带连接内从1开始连续递增的seq，客户端发现不连续时应发送resync请求全量快照
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FeedMessage {
+    /// 全量快照，连接建立和客户端请求重新同步时发送
+    Snapshot { seq: u64, book: Box<BookSnapshot> },
+    /// 相对上一条消息变化的档位，数量为0表示档位已删除或已不在推送的档位范围内
+    Update { seq: u64, version: u64, px: BigDecimal, bids: Vec<LevelSnapshot>, asks: Vec<LevelSnapshot> },
+    /// 心跳，订单簿没有更新时按间隔发送，version为当前版本号
+    Heartbeat { seq: u64, version: u64 },
+}
+
+/// 客户端请求
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum FeedRequest {
+    /// 请求全量快照，之后的增量相对该快照
+    Resync,
+}
+
+/// 单个连接的推送状态，记录已发送的序号和客户端当前持有的档位
+#[derive(Debug)]
+pub struct FeedSession {
+    /// 对外的交易对名称
+    symbol: String,
+    limit: usize,
+    seq: u64,
+    /// 最后发送的快照或增量应用后的订单簿
+    last: Option<BookSnapshot>,
+}
+
+impl FeedSession {
+    pub fn new(symbol: &str, limit: usize) -> FeedSession {
+        FeedSession { symbol: String::from(symbol), limit, seq: 0, last: None }
+    }
+
+    fn next_seq(&mut self) -> u64 {
+        self.seq += 1;
+        self.seq
+    }
+
+    /// 全量快照
+    pub fn snapshot(&mut self, snapshot: &BookSnapshot) -> FeedMessage {
+        let mut book = snapshot.clone();
+        book.symbol = self.symbol.clone();
+        book.truncate(self.limit);
+        self.last = Some(book.clone());
+        FeedMessage::Snapshot { seq: self.next_seq(), book: Box::new(book) }
+    }
+
+    /// 订单簿版本变化时生成增量，尚未发送快照时生成快照，没有变化时为None
+    pub fn update(&mut self, snapshot: &BookSnapshot) -> Option<FeedMessage> {
+        let last = match &self.last {
+            Some(last) if last.version == snapshot.version => return None,
+            Some(last) => last,
+            None => return Some(self.snapshot(snapshot)),
+        };
+        let mut book = snapshot.clone();
+        book.symbol = self.symbol.clone();
+        book.truncate(self.limit);
+        let bids = diff_levels(&last.bids, &book.bids);
+        let asks = diff_levels(&last.asks, &book.asks);
+        let (version, px) = (book.version, book.px.clone());
+        self.last = Some(book);
+        Some(FeedMessage::Update { seq: self.next_seq(), version, px, bids, asks })
+    }
+
+    /// 心跳
+    pub fn heartbeat(&mut self) -> FeedMessage {
+        let version = self.last.as_ref().map(|b| b.version).unwrap_or(0);
+        FeedMessage::Heartbeat { seq: self.next_seq(), version }
+    }
+}
+
+/// 新档位中数量或订单数变化的档位，以及旧档位中已不存在的档位（数量为0）
+fn diff_levels(old: &[LevelSnapshot], new: &[LevelSnapshot]) -> Vec<LevelSnapshot> {
+    let mut changed: Vec<LevelSnapshot> = new.iter()
+        .filter(|level| !old.contains(level))
+        .cloned()
+        .collect();
+    changed.extend(old.iter()
+        .filter(|level| !new.iter().any(|l| l.px == level.px))
+        .map(|level| LevelSnapshot { px: level.px.clone(), qty: 0, orders: 0 }));
+    changed
+}
+
+/// 订阅交易对深度，先推送全量快照，之后推送变化的档位，空闲时推送心跳
+pub async fn handler_ws_depth(State(engine): State<EngineHandle>, Extension(tenant): Extension<Tenant>, Query(param): Query<DepthFeedParam>, ws: WebSocketUpgrade) -> Result<Response, AppError> {
+    let symbol = tenant.scope(&param.symbol)?;
+    if engine.snapshot(&symbol).is_none() {
+        return Err(anyhow!("unknown symbol, symbol={}", &param.symbol).into());
+    }
+    let session = FeedSession::new(&param.symbol, param.limit.unwrap_or(DEFAULT_FEED_LEVELS));
+    Ok(ws.on_upgrade(move |socket| feed_depth(socket, engine, symbol, session)))
+}
+
+/// 推送深度直到客户端断开或引擎关闭
+async fn feed_depth(mut socket: WebSocket, engine: EngineHandle, symbol: String, mut session: FeedSession) {
+    let mut poll = tokio::time::interval(FEED_POLL_INTERVAL);
+    let mut sent = Instant::now();
+    loop {
+        let message = tokio::select! {
+            _ = poll.tick() => {
+                let snapshot = match engine.snapshot(&symbol) {
+                    Some(snapshot) if !engine.is_shutdown() => snapshot,
+                    _ => break,
+                };
+                match session.update(&snapshot) {
+                    Some(message) => message,
+                    None if sent.elapsed() >= HEARTBEAT_INTERVAL => session.heartbeat(),
+                    None => continue,
+                }
+            }
+            received = socket.recv() => match received {
+                Some(Ok(Message::Text(text))) => match serde_json::from_str::<FeedRequest>(&text) {
+                    Ok(FeedRequest::Resync) => match engine.snapshot(&symbol) {
+                        Some(snapshot) => session.snapshot(&snapshot),
+                        None => break,
+                    },
+                    Err(e) => {
+                        debug!("INVALID FEED REQUEST: symbol={}, err={}", &symbol, e);
+                        continue;
+                    }
+                },
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => continue,
+            }
+        };
+        let text = match serde_json::to_string(&message) {
+            Ok(text) => text,
+            Err(e) => {
+                debug!("ENCODE FEED MESSAGE FAILED: symbol={}, err={}", &symbol, e);
+                break;
+            }
+        };
+        if socket.send(Message::Text(text)).await.is_err() {
+            return;
+        }
+        sent = Instant::now();
+    }
+    let _ = socket.send(Message::Close(None)).await;
+}
+
+#[cfg(test)]
+mod test {
+    use bigdecimal::BigDecimal;
+
+    use loom_core::snapshot::{BookSnapshot, LevelSnapshot};
+
+    use crate::handler_ws::{FeedMessage, FeedRequest, FeedSession};
+
+    fn level(px: u64, qty: u64) -> LevelSnapshot {
+        LevelSnapshot { px: BigDecimal::from(px), qty, orders: 1 }
+    }
+
+    #[test]
+    fn session_test() {
+        let mut session = FeedSession::new("LOOM-USDT-SPOT", 2);
+        let mut book = BookSnapshot { version: 1, bids: vec![level(100, 2), level(99, 3), level(98, 1)], ..BookSnapshot::empty("T1:LOOM-USDT-SPOT") };
+        // 首条消息为全量快照，使用对外的交易对名称并按档位数量截断
+        let FeedMessage::Snapshot { seq: 1, book: sent } = session.update(&book).unwrap() else { panic!() };
+        assert_eq!((sent.symbol.as_str(), sent.bids.len()), ("LOOM-USDT-SPOT", 2));
+        assert_eq!(session.update(&book), None);
+        assert_eq!(session.heartbeat(), FeedMessage::Heartbeat { seq: 2, version: 1 });
+
+        // 只推送变化的档位，退出推送范围的档位数量为0
+        book.version = 2;
+        book.bids = vec![level(101, 1), level(100, 2), level(99, 3)];
+        book.asks = vec![level(102, 4)];
+        let FeedMessage::Update { seq: 3, version: 2, bids, asks, .. } = session.update(&book).unwrap() else { panic!() };
+        assert_eq!(bids, vec![level(101, 1), LevelSnapshot { px: BigDecimal::from(99), qty: 0, orders: 0 }]);
+        assert_eq!(asks, vec![level(102, 4)]);
+
+        let request: FeedRequest = serde_json::from_str(r#"{"op":"resync"}"#).unwrap();
+        assert_eq!(request, FeedRequest::Resync);
+        assert!(matches!(session.snapshot(&book), FeedMessage::Snapshot { seq: 4, .. }));
+    }
+}
@@ -1,47 +1,322 @@
+use std::net::SocketAddr;
+use std::path::Path;
 use std::sync::Arc;
 
+use axum::extract::ConnectInfo;
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
-use axum::Router;
-use axum::routing::{get, post};
+use axum::{middleware, Router};
+use axum::routing::{get, patch, post};
+use hyper_util::rt::TokioIo;
+use log::{debug, info, warn};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, UnixListener};
+use tower::ServiceExt;
 use tokio::signal;
-use crate::config::Config;
+use tokio::sync::broadcast;
+use validator::{ValidationError, ValidationErrors};
 
-use crate::handler_match::{handler_match, TraderMarketWrap};
+use loom_engine::archive::TradeArchive;
+use loom_engine::engine::{EngineHandle, OrderNotFound, QueueSaturated, SessionClosed, SymbolNotReady, SymbolPaused, UnknownSymbol};
+use loom_engine::limits::{AccountLimitExceeded, RejectReason, SymbolThrottled};
+use loom_engine::mmp::MmpTripped;
+use loom_engine::replication::StandbyMode;
+use loom_engine::risk::RiskRejected;
+use loom_engine::trader::QueueFull;
 
-pub async fn start_http_server(config: &Config, market: TraderMarketWrap) {
-    let app = router(Arc::clone(&market));
-    serve(config, app, Arc::clone(&market)).await;
+use crate::config::{Config, ListenerRoutes};
+use crate::admin_auth::{self, AdminAuth};
+use crate::cors::{self, Cors};
+use crate::handler_admin::{handler_dump, handler_get_loglevel, handler_get_symbols, handler_get_mmp, handler_halt, handler_patch_symbol, handler_pause, handler_promote, handler_purge, handler_put_loglevel, handler_rearm_mmp, handler_put_reference, handler_get_reconcile, handler_reconcile, handler_restore, handler_resume, handler_snapshot, handler_statehash, handler_unhalt, handler_verify};
+use crate::handler_balance::{handler_adjust_balance, handler_balance};
+use crate::handler_depth::handler_depth;
+use crate::handler_price::handler_price;
+use crate::handler_auction::handler_auction;
+use crate::handler_queue::handler_queue;
+use crate::handler_preview::handler_preview;
+use crate::handler_health::{handler_healthz, handler_readyz};
+use crate::handler_match::handler_match;
+use crate::handler_order::handler_order;
+use crate::handler_ws::handler_ws_depth;
+use crate::handler_stats::{handler_metrics, handler_stats};
+use crate::handler_trades::handler_my_trades;
+use crate::logging;
+use crate::rate_limit::{self, RateLimiter};
+use crate::server_limits::Limits;
+use crate::tenant::{self, TenantResolver};
+use crate::tls::{self, TlsTerminator};
+
+/// 启动HttpServer，在所有配置的地址上监听，收到退出信号并处理完进行中的请求后返回，
+/// 所有监听地址共用limiter的令牌桶，配置tls时TCP地址只接受TLS连接
+pub async fn start_http_server(config: &Config, engine: EngineHandle, limiter: Option<Arc<RateLimiter>>, tls: Option<Arc<TlsTerminator>>) -> anyhow::Result<()> {
+    config.validate()?;
+    let (shutdown, _) = broadcast::channel(1);
+    let limits = Limits::new(config.server.limits.as_ref());
+    let mut servers = Vec::new();
+    for (addr, routes) in config.server.bind_addrs() {
+        let app = router(config, engine.clone(), routes, &limits, limiter.clone())?;
+        let listener = tokio::net::TcpListener::bind(&addr).await?;
+        info!("Listening on {}, routes={:?}, tls={}", listener.local_addr()?, routes, tls.is_some());
+        let mut rx = shutdown.subscribe();
+        if let Some(tls) = &tls {
+            servers.push(tokio::spawn(serve_tls(listener, app, Arc::clone(tls), rx)));
+            continue;
+        }
+        servers.push(tokio::spawn(async move {
+            axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+                .with_graceful_shutdown(async move { let _ = rx.recv().await; })
+                .await
+                .unwrap();
+        }));
+    }
+    if let Some(conf) = &config.server.unix_socket {
+        let routes = conf.routes.unwrap_or_default();
+        let app = router(config, engine.clone(), routes, &limits, limiter.clone())?;
+        let listener = bind_unix(&conf.path, conf.mode.unwrap_or(DEFAULT_UNIX_SOCKET_MODE))?;
+        info!("Listening on unix:{}, routes={:?}", &conf.path, routes);
+        let path = conf.path.clone();
+        let rx = shutdown.subscribe();
+        servers.push(tokio::spawn(async move {
+            serve_unix(listener, app, rx).await;
+            let _ = std::fs::remove_file(&path);
+        }));
+    }
+    shutdown_signal().await;
+    let _ = shutdown.send(());
+    for server in servers {
+        let _ = server.await;
+    }
+    Ok(())
+}
+
+/// Unix套接字默认权限，仅属主和同组用户可连接
+const DEFAULT_UNIX_SOCKET_MODE: u32 = 0o660;
+
+/// 绑定Unix套接字，删除残留的套接字文件并设置权限
+fn bind_unix(path: &str, mode: u32) -> anyhow::Result<UnixListener> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+    if let Ok(meta) = std::fs::symlink_metadata(path) {
+        if !meta.file_type().is_socket() {
+            return Err(anyhow::anyhow!("refuse to replace non-socket file, path={}", path));
+        }
+        std::fs::remove_file(path)?;
+    }
+    if let Some(dir) = std::path::Path::new(path).parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+    Ok(listener)
+}
+
+/// 在Unix套接字上提供服务，收到退出信号后停止接受连接并等待进行中的连接处理完成
+async fn serve_unix(listener: UnixListener, app: Router, mut shutdown: broadcast::Receiver<()>) {
+    let (closing, _) = broadcast::channel::<()>(1);
+    let mut connections = Vec::new();
+    loop {
+        let stream = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    warn!("ACCEPT UNIX CONNECTION FAILED: err={}", e);
+                    continue;
+                }
+            },
+            _ = shutdown.recv() => break,
+        };
+        connections.push(tokio::spawn(serve_connection(stream, app.clone(), None, closing.subscribe())));
+        connections.retain(|c| !c.is_finished());
+    }
+    let _ = closing.send(());
+    for conn in connections {
+        let _ = conn.await;
+    }
+}
+
+/// 在TCP地址上终止TLS并提供服务，握手在连接任务中完成，收到退出信号后停止接受连接并等待进行中的连接处理完成
+async fn serve_tls(listener: TcpListener, app: Router, tls: Arc<TlsTerminator>, mut shutdown: broadcast::Receiver<()>) {
+    let (closing, _) = broadcast::channel::<()>(1);
+    let mut connections = Vec::new();
+    loop {
+        let (stream, peer) = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    warn!("ACCEPT TLS CONNECTION FAILED: err={}", e);
+                    continue;
+                }
+            },
+            _ = shutdown.recv() => break,
+        };
+        // 使用接受连接时的证书，重新加载只影响之后的连接
+        let acceptor = tls.acceptor();
+        let (app, closing) = (app.clone(), closing.subscribe());
+        connections.push(tokio::spawn(async move {
+            let stream = match tokio::time::timeout(tls::HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                Ok(Ok(stream)) => stream,
+                Ok(Err(e)) => {
+                    debug!("TLS HANDSHAKE FAILED: peer={}, err={}", peer, e);
+                    return;
+                }
+                Err(_) => {
+                    debug!("TLS HANDSHAKE TIMEOUT: peer={}", peer);
+                    return;
+                }
+            };
+            serve_connection(stream, app, Some(peer), closing).await;
+        }));
+        connections.retain(|c| !c.is_finished());
+    }
+    let _ = closing.send(());
+    for conn in connections {
+        let _ = conn.await;
+    }
+}
+
+/// 在单个连接上提供HTTP/1.1服务，peer为TCP对端地址，收到closing后处理完当前请求再关闭
+async fn serve_connection<S>(stream: S, app: Router, peer: Option<SocketAddr>, mut closing: broadcast::Receiver<()>)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let service = hyper::service::service_fn(move |mut req: hyper::Request<hyper::body::Incoming>| {
+        // 与axum::serve一致提供对端地址，按IP限流依赖该信息
+        if let Some(peer) = peer {
+            req.extensions_mut().insert(ConnectInfo(peer));
+        }
+        app.clone().oneshot(req)
+    });
+    // 支持升级为WebSocket连接
+    let conn = hyper::server::conn::http1::Builder::new()
+        .serve_connection(TokioIo::new(stream), service)
+        .with_upgrades();
+    tokio::pin!(conn);
+    let result = tokio::select! {
+        result = conn.as_mut() => result,
+        _ = closing.recv() => {
+            conn.as_mut().graceful_shutdown();
+            conn.await
+        }
+    };
+    if let Err(e) = result {
+        debug!("CONNECTION CLOSED: peer={:?}, err={}", peer, e);
+    }
 }
 
 async fn handler_ping() -> &'static str {
     "pong"
 }
 
-fn router(market: TraderMarketWrap) -> Router {
-    // 注册路由
-    let ping_handler = Router::new()
-        .route("/ping", get(handler_ping));
+fn router(config: &Config, engine: EngineHandle, routes: ListenerRoutes, limits: &Limits, limiter: Option<Arc<RateLimiter>>) -> anyhow::Result<Router> {
+    let app = match routes {
+        ListenerRoutes::All => api_router(config, engine.clone())?.merge(admin_router(config, engine)?),
+        ListenerRoutes::Api => api_router(config, engine)?,
+        ListenerRoutes::Admin => admin_router(config, engine)?,
+    };
+    let mut app = limits.apply(Router::new()
+        .route("/ping", get(handler_ping))
+        .merge(app));
+    if let Some(limiter) = limiter {
+        app = app.layer(middleware::from_fn_with_state(limiter, rate_limit::rate_limit));
+    }
+    if let Some(conf) = &config.server.cors {
+        // 跨域预检请求不计入限流
+        let cors = Arc::new(Cors::new(conf)?);
+        app = app.layer(middleware::from_fn_with_state(cors, cors::cors));
+    }
+    Ok(app.layer(middleware::from_fn(logging::request_id)))
+}
 
-    let match_handler = Router::new()
-        .route("/api/v1/match", post(handler_match))
-        .with_state(Arc::clone(&market));
+/// 按配置构造限流器，未配置限流时返回None
+pub fn rate_limiter(config: &Config) -> Option<Arc<RateLimiter>> {
+    let conf = config.rate_limit.as_ref()?;
+    let mut limiter = RateLimiter::new(conf.per_ip, conf.per_key)
+        .with_weights(conf.routes.clone().unwrap_or_default());
+    if let Some(header) = &conf.api_key_header {
+        limiter = limiter.with_api_key_header(header);
+    }
+    Some(Arc::new(limiter))
+}
 
-    Router::new()
-        .merge(ping_handler)
-        .merge(match_handler)
+/// 撮合与行情接口
+fn api_router(config: &Config, engine: EngineHandle) -> anyhow::Result<Router> {
+    let mut resolver = TenantResolver::new(config.tenants.as_deref().unwrap_or_default())?;
+    if let Some(header) = config.rate_limit.as_ref().and_then(|conf| conf.api_key_header.as_ref()) {
+        resolver = resolver.with_header(header);
+    }
+    let mut api = Router::new()
+        .route("/api/v1/match", post(handler_match))
+        .route("/api/v1/depth", get(handler_depth))
+        .route("/api/v1/price", get(handler_price))
+        .route("/api/v1/auction", get(handler_auction))
+        .route("/api/v1/queue", get(handler_queue))
+        .route("/api/v1/preview", get(handler_preview))
+        .route("/api/v1/balance", get(handler_balance))
+        .route("/api/v2/order", post(handler_order))
+        .route("/api/v1/ws/depth", get(handler_ws_depth))
+        .with_state(engine);
+    // 成交历史由归档提供，未配置归档时不提供
+    if let Some(archive) = &config.archive {
+        api = api.merge(Router::new()
+            .route("/api/v1/myTrades", get(handler_my_trades))
+            .with_state(TradeArchive::new(Path::new(&archive.dir))));
+    }
+    Ok(api.layer(middleware::from_fn_with_state(Arc::new(resolver), tenant::tenant)))
 }
 
-async fn serve(config: &Config, app: Router, market: TraderMarketWrap) {
-    let addr = format!("0.0.0.0:{}", config.server.port.unwrap_or(7001));
-    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    println!("Listening on {}", listener.local_addr().unwrap());
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal(market))
-        .await.unwrap();
+/// 健康检查、指标与管理接口
+fn admin_router(config: &Config, engine: EngineHandle) -> anyhow::Result<Router> {
+    let admin_handler = Router::new()
+        .route("/admin/loglevel", get(handler_get_loglevel).put(handler_put_loglevel));
+
+    let dump_handler = Router::new()
+        .route("/admin/dump", post(handler_dump))
+        .route("/admin/snapshot", post(handler_snapshot))
+        .route("/admin/restore", post(handler_restore))
+        .with_state((engine.clone(), config.dump_dir()));
+
+    // 探针和指标不需要管理令牌
+    let health_handler = Router::new()
+        .route("/healthz", get(handler_healthz))
+        .route("/readyz", get(handler_readyz))
+        .route("/metrics", get(handler_metrics))
+        .with_state(engine.clone());
+
+    let control_handler = Router::new()
+        .route("/admin/stats", get(handler_stats))
+        .route("/admin/promote", post(handler_promote))
+        .route("/admin/pause", post(handler_pause))
+        .route("/admin/symbols", get(handler_get_symbols))
+        .route("/admin/symbols/:symbol", patch(handler_patch_symbol))
+        .route("/admin/resume", post(handler_resume))
+        .route("/admin/halt", post(handler_halt).delete(handler_unhalt))
+        .route("/admin/purge", post(handler_purge))
+        .route("/admin/statehash", get(handler_statehash))
+        .route("/admin/verify", get(handler_verify))
+        .route("/admin/reconcile", get(handler_get_reconcile).post(handler_reconcile))
+        .route("/admin/reference", post(handler_put_reference))
+        .route("/admin/mmp", get(handler_get_mmp))
+        .route("/admin/mmp/rearm", post(handler_rearm_mmp))
+        .with_state(engine.clone());
+
+    let mut control = Router::new()
+        .merge(control_handler)
+        .merge(admin_handler)
+        .merge(dump_handler);
+    match &config.server.admin_auth {
+        Some(conf) => {
+            control = control.route("/admin/balance", post(handler_adjust_balance).with_state(engine));
+            let auth = Arc::new(AdminAuth::new(conf)?);
+            control = control.layer(middleware::from_fn_with_state(auth, admin_auth::admin_auth));
+        }
+        // 调整余额绕过资金预留，不论监听地址如何配置都必须鉴权
+        None => control = control.route("/admin/balance", post(admin_auth::required)),
+    }
+    Ok(Router::new()
+        .merge(health_handler)
+        .merge(control))
 }
 
-async fn shutdown_signal(market: TraderMarketWrap) {
+async fn shutdown_signal() {
     let ctrl_c = async {
         signal::ctrl_c()
             .await
@@ -57,8 +332,6 @@ async fn shutdown_signal(market: TraderMarketWrap) {
         _ = ctrl_c => {},
         _ = terminate => {},
     }
-    // 关闭引擎
-    market.lock().await.shutdown().await;
 }
 
 
@@ -66,11 +339,68 @@ async fn shutdown_signal(market: TraderMarketWrap) {
 pub struct AppError(anyhow::Error);
 
 
+impl AppError {
+    pub fn status(&self) -> StatusCode {
+        // 撮合队列已满，提示客户端稍后重试
+        if self.0.downcast_ref::<QueueFull>().is_some() {
+            StatusCode::TOO_MANY_REQUESTS
+        } else if self.0.downcast_ref::<ValidationError>().is_some() || self.0.downcast_ref::<ValidationErrors>().is_some() {
+            // 请求参数校验失败
+            StatusCode::BAD_REQUEST
+        } else if self.0.downcast_ref::<QueueSaturated>().is_some() {
+            // 排队时间过长，丢弃新订单避免延迟持续增长
+            StatusCode::TOO_MANY_REQUESTS
+        } else if self.0.downcast_ref::<SymbolThrottled>().is_some() {
+            // 交易对下单速率超限
+            StatusCode::TOO_MANY_REQUESTS
+        } else if self.0.downcast_ref::<UnknownSymbol>().is_some() {
+            // 交易对未注册
+            StatusCode::NOT_FOUND
+        } else if self.0.downcast_ref::<SymbolNotReady>().is_some() {
+            // 交易对恢复中，稍后重试
+            StatusCode::SERVICE_UNAVAILABLE
+        } else if self.0.downcast_ref::<SymbolPaused>().is_some() {
+            // 交易对已暂停
+            StatusCode::SERVICE_UNAVAILABLE
+        } else if self.0.downcast_ref::<SessionClosed>().is_some() {
+            // 交易时段之外
+            StatusCode::SERVICE_UNAVAILABLE
+        } else if self.0.downcast_ref::<StandbyMode>().is_some() {
+            // 备机不接受撮合请求
+            StatusCode::SERVICE_UNAVAILABLE
+        } else if let Some(limit) = self.0.downcast_ref::<AccountLimitExceeded>() {
+            // 账户额度不足
+            match limit.reason {
+                RejectReason::ORDER_RATE_EXCEEDED => StatusCode::TOO_MANY_REQUESTS,
+                RejectReason::TOO_MANY_OPEN_ORDERS => StatusCode::UNPROCESSABLE_ENTITY,
+                RejectReason::DUPLICATE_ORDER_ID => StatusCode::CONFLICT,
+            }
+        } else if self.0.downcast_ref::<OrderNotFound>().is_some() {
+            // 撤单的订单不存在
+            StatusCode::NOT_FOUND
+        } else if self.0.downcast_ref::<MmpTripped>().is_some() {
+            // 做市商保护已触发，重新启用前拒绝下单
+            StatusCode::UNPROCESSABLE_ENTITY
+        } else if self.0.downcast_ref::<RiskRejected>().is_some() {
+            // 风控拒绝
+            StatusCode::UNPROCESSABLE_ENTITY
+        } else {
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+impl std::fmt::Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 // Tell axum how to convert `AppError` into a response.
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
         (
-            StatusCode::INTERNAL_SERVER_ERROR,
+            self.status(),
             format!("{}", self.0),
         ).into_response()
     }
@@ -82,4 +412,84 @@ impl<E> From<E> for AppError where E: Into<anyhow::Error> {
     fn from(err: E) -> Self {
         Self(err.into())
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod test {
+    use std::os::unix::fs::PermissionsExt;
+    use std::sync::Arc;
+
+    use axum::routing::get;
+    use axum::Router;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream, UnixStream};
+    use tokio::sync::broadcast;
+    use tokio_rustls::rustls::crypto::ring;
+    use tokio_rustls::rustls::pki_types::pem::PemObject;
+    use tokio_rustls::rustls::pki_types::{CertificateDer, ServerName};
+    use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+    use tokio_rustls::TlsConnector;
+
+    use crate::config::Tls;
+    use crate::http_server::{bind_unix, handler_ping, serve_tls, serve_unix};
+    use crate::tls::{self, TlsTerminator};
+
+    #[tokio::test]
+    async fn unix_socket_test() {
+        let path = std::env::temp_dir().join(format!("loom-test-{}.sock", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let listener = bind_unix(&path, 0o600).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        let (shutdown, rx) = broadcast::channel(1);
+        let server = tokio::spawn(serve_unix(listener, Router::new().route("/ping", get(handler_ping)), rx));
+
+        let mut stream = UnixStream::connect(&path).await.unwrap();
+        stream.write_all(b"GET /ping HTTP/1.1\r\nHost: loom\r\nConnection: close\r\n\r\n").await.unwrap();
+        let mut resp = String::new();
+        stream.read_to_string(&mut resp).await.unwrap();
+        assert!(resp.starts_with("HTTP/1.1 200"));
+        assert!(resp.ends_with("pong"));
+
+        shutdown.send(()).unwrap();
+        server.await.unwrap();
+        // 重新绑定时替换残留的套接字文件
+        drop(bind_unix(&path, 0o600).unwrap());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn tls_test() {
+        let conf = Tls { cert: String::from("testdata/cert.pem"), key: String::from("testdata/key.pem") };
+        let terminator = Arc::new(TlsTerminator::new(tls::load(&conf).unwrap()));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (shutdown, rx) = broadcast::channel(1);
+        let server = tokio::spawn(serve_tls(listener, Router::new().route("/ping", get(handler_ping)), Arc::clone(&terminator), rx));
+
+        let mut roots = RootCertStore::empty();
+        roots.add(CertificateDer::from_pem_file(&conf.cert).unwrap()).unwrap();
+        let client = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        let connector = TlsConnector::from(Arc::new(client));
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let mut stream = connector.connect(ServerName::try_from("localhost").unwrap(), stream).await.unwrap();
+        stream.write_all(b"GET /ping HTTP/1.1\r\nHost: loom\r\nConnection: close\r\n\r\n").await.unwrap();
+        let mut resp = Vec::new();
+        let _ = stream.read_to_end(&mut resp).await;
+        let resp = String::from_utf8(resp).unwrap();
+        assert!(resp.starts_with("HTTP/1.1 200"));
+        assert!(resp.ends_with("pong"));
+
+        // 明文请求无法完成握手
+        let mut plain = TcpStream::connect(addr).await.unwrap();
+        plain.write_all(b"GET /ping HTTP/1.1\r\nHost: loom\r\n\r\n").await.unwrap();
+        let mut resp = Vec::new();
+        let _ = plain.read_to_end(&mut resp).await;
+        assert!(!String::from_utf8_lossy(&resp).contains("pong"));
+
+        shutdown.send(()).unwrap();
+        server.await.unwrap();
+    }
+}
@@ -1,3 +1,26 @@
 pub mod http_server;
 pub mod handler_match;
+pub mod handler_order;
+pub mod handler_depth;
+pub mod handler_price;
+pub mod handler_auction;
+pub mod handler_queue;
+pub mod handler_preview;
+pub mod handler_balance;
+pub mod handler_trades;
+pub mod handler_health;
+pub mod handler_stats;
+pub mod handler_admin;
+pub mod handler_ws;
 pub mod config;
+pub mod logging;
+pub mod rate_limit;
+pub mod reload;
+pub mod server_limits;
+pub mod cors;
+pub mod admin_auth;
+pub mod allocator;
+pub mod cli;
+pub mod cli_replay;
+pub mod tenant;
+pub mod tls;
@@ -0,0 +1,241 @@
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{OnceLock, RwLock};
+
+use axum::extract::Request;
+use axum::http::HeaderValue;
+use axum::middleware::Next;
+use axum::response::Response;
+use env_logger::Logger;
+use log::{LevelFilter, Log, Metadata, Record};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use loom_core::utils;
+use loom_engine::logging::{self, LogContext};
+
+/// 日志格式环境变量，取值为json时输出JSON日志
+pub const LOG_FORMAT_ENV_VAR: &str = "LOOM_LOG_FORMAT";
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// 日志过滤器环境变量，语法与env_logger一致
+pub const LOG_FILTER_ENV_VAR: &str = "RUST_LOG";
+
+static LOGGER: OnceLock<&'static ReloadableLogger> = OnceLock::new();
+
+/// 日志过滤配置
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct LogFilter {
+    /// 全局过滤规则，例如 info,loom_engine::trader=debug
+    pub filter: String,
+    /// 只对该交易对放宽日志级别
+    pub symbol: Option<String>,
+    /// 交易对日志级别，例如 debug
+    pub symbol_level: Option<String>,
+}
+
+impl LogFilter {
+    /// 检查交易对日志级别，全局过滤规则中无法解析的部分由env_logger忽略
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if let Some(level) = &self.symbol_level {
+            level.parse::<LevelFilter>()
+                .map_err(|_| anyhow::anyhow!("invalid symbol log level, level={}", level))?;
+        }
+        Ok(())
+    }
+}
+
+/// 可在运行时替换过滤规则的日志器
+struct ReloadableLogger {
+    json: bool,
+    state: RwLock<LoggerState>,
+}
+
+struct LoggerState {
+    spec: LogFilter,
+    main: Logger,
+    /// 交易对日志器，主日志器过滤掉的记录属于该交易对时由它输出
+    symbol: Option<(String, Logger)>,
+}
+
+impl ReloadableLogger {
+    fn build(&self, spec: LogFilter) -> anyhow::Result<LoggerState> {
+        spec.validate()?;
+        let main = build_logger(&spec.filter, self.json);
+        let symbol = match &spec.symbol {
+            Some(symbol) => {
+                let level = spec.symbol_level.as_deref().unwrap_or("debug");
+                Some((symbol.clone(), build_logger(level, self.json)))
+            }
+            None => None,
+        };
+        Ok(LoggerState { spec, main, symbol })
+    }
+
+    fn max_level(state: &LoggerState) -> LevelFilter {
+        let symbol = state.symbol.as_ref().map(|(_, l)| l.filter()).unwrap_or(LevelFilter::Off);
+        state.main.filter().max(symbol)
+    }
+}
+
+impl Log for ReloadableLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        let state = self.state.read().unwrap();
+        state.main.enabled(metadata) || state.symbol.as_ref().map(|(_, l)| l.enabled(metadata)).unwrap_or(false)
+    }
+
+    fn log(&self, record: &Record) {
+        let state = self.state.read().unwrap();
+        if state.main.matches(record) {
+            state.main.log(record);
+            return;
+        }
+        if let Some((symbol, logger)) = &state.symbol {
+            let matched = logging::current()
+                .and_then(|ctx| ctx.symbol)
+                .map(|s| &s == symbol)
+                .unwrap_or(false);
+            if matched {
+                logger.log(record);
+            }
+        }
+    }
+
+    fn flush(&self) {
+        self.state.read().unwrap().main.flush();
+    }
+}
+
+fn build_logger(filter: &str, json: bool) -> Logger {
+    let mut builder = env_logger::Builder::new();
+    builder.parse_filters(filter);
+    if json {
+        builder.format(|buf, record| {
+            let line = json_line(
+                &buf.timestamp_millis().to_string(),
+                record.level().as_str(),
+                record.target(),
+                &record.args().to_string(),
+                logging::current(),
+            );
+            writeln!(buf, "{}", line)
+        });
+    }
+    builder.build()
+}
+
+/// 初始化日志，默认输出文本日志，过滤规则可通过`reload`在运行时替换
+pub fn init(default_filter: &str) {
+    let json = std::env::var(LOG_FORMAT_ENV_VAR)
+        .map(|v| v.eq_ignore_ascii_case("json"))
+        .unwrap_or(false);
+    let filter = std::env::var(LOG_FILTER_ENV_VAR).unwrap_or_else(|_| String::from(default_filter));
+    let spec = LogFilter { filter, symbol: None, symbol_level: None };
+    let main = build_logger(&spec.filter, json);
+    let logger: &'static ReloadableLogger = Box::leak(Box::new(ReloadableLogger {
+        json,
+        state: RwLock::new(LoggerState { spec, main, symbol: None }),
+    }));
+    if log::set_logger(logger).is_ok() {
+        log::set_max_level(ReloadableLogger::max_level(&logger.state.read().unwrap()));
+        let _ = LOGGER.set(logger);
+    }
+}
+
+/// 替换日志过滤规则
+pub fn reload(spec: LogFilter) -> anyhow::Result<LogFilter> {
+    let logger = LOGGER.get().ok_or_else(|| anyhow::anyhow!("logger not initialized"))?;
+    let state = logger.build(spec)?;
+    let max_level = ReloadableLogger::max_level(&state);
+    let spec = state.spec.clone();
+    *logger.state.write().unwrap() = state;
+    log::set_max_level(max_level);
+    Ok(spec)
+}
+
+/// 当前日志过滤规则
+pub fn current_filter() -> Option<LogFilter> {
+    LOGGER.get().map(|logger| logger.state.read().unwrap().spec.clone())
+}
+
+/// 构造一行JSON日志
+fn json_line(ts: &str, level: &str, target: &str, msg: &str, ctx: Option<LogContext>) -> String {
+    let mut line = Map::new();
+    line.insert(String::from("ts"), json!(ts));
+    line.insert(String::from("level"), json!(level));
+    line.insert(String::from("target"), json!(target));
+    line.insert(String::from("msg"), json!(msg));
+    if let Some(ctx) = ctx {
+        if let Some(request_id) = ctx.request_id {
+            line.insert(String::from("request_id"), json!(request_id));
+        }
+        if let Some(symbol) = ctx.symbol {
+            line.insert(String::from("symbol"), json!(symbol));
+        }
+        if let Some(oid) = ctx.oid {
+            line.insert(String::from("oid"), json!(oid));
+        }
+    }
+    Value::Object(line).to_string()
+}
+
+static REQUEST_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// 生成请求ID
+fn next_request_id() -> String {
+    format!("{:x}-{:x}", utils::now_ts(), REQUEST_SEQ.fetch_add(1, Ordering::Relaxed))
+}
+
+/// 请求ID中间件，使用请求头X-Request-Id或生成新ID，在请求处理期间附加到日志上并写回响应头
+pub async fn request_id(request: Request, next: Next) -> Response {
+    let request_id = request.headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.is_empty() && v.len() <= 128)
+        .map(String::from)
+        .unwrap_or_else(next_request_id);
+    let ctx = LogContext::default().with_request_id(&request_id);
+    let mut response = logging::scope(ctx, next.run(request)).await;
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::Value;
+
+    use loom_engine::logging::LogContext;
+
+    use crate::logging::{current_filter, init, json_line, reload, LogFilter};
+
+    #[test]
+    fn json_line_test() {
+        let ctx = LogContext { request_id: Some(String::from("req-1")), symbol: Some(String::from("LOOM-USDT-SPOT")), oid: Some(7) };
+        let line = json_line("2024-01-01T00:00:00.000Z", "INFO", "loom", "say \"hi\"", Some(ctx));
+        let value: Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(value["msg"], "say \"hi\"");
+        assert_eq!(value["request_id"], "req-1");
+        assert_eq!(value["oid"], 7);
+        let line = json_line("2024-01-01T00:00:00.000Z", "INFO", "loom", "plain", None);
+        assert!(!line.contains("request_id"));
+    }
+
+    #[test]
+    fn reload_test() {
+        init("info");
+        let spec = LogFilter {
+            filter: String::from("warn"),
+            symbol: Some(String::from("LOOM-USDT-SPOT")),
+            symbol_level: Some(String::from("trace")),
+        };
+        assert_eq!(reload(spec.clone()).unwrap(), spec);
+        assert_eq!(current_filter().unwrap(), spec);
+        // 交易对级别决定全局最大级别
+        assert_eq!(log::max_level(), log::LevelFilter::Trace);
+        let invalid = LogFilter { symbol_level: Some(String::from("loud")), ..spec };
+        assert!(reload(invalid).is_err());
+    }
+}
@@ -1,33 +1,213 @@
+use std::collections::HashMap;
+use std::path::Path;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use env_logger::Env;
-use tokio::sync::Mutex;
+use log::{info, warn};
 
+use loom::allocator;
+use loom::cli::{self, Cli, Command};
+use loom::cli_replay;
 use loom::config::CacheBackend::Redis;
-use loom::config::{Config, ConsumerKind};
-use loom::http_server::start_http_server;
-use loom_core::market;
+use loom::config::{ClickHouseSink, Config, ConsumerKind};
+use loom::http_server::{self, start_http_server};
+use loom::logging;
+use loom::reload::ConfigReloader;
+use loom::tls::TlsTerminator;
 use loom_engine::cache::CacheManager;
-use loom_engine::consumer::{ConsoleConsumer, RedisQueueConsumer, TradeConsumer};
+use loom_engine::alert::{AlertMonitor, AlertSink, AlertThresholds};
+use loom_engine::dump;
+use loom_engine::archive::TradeArchive;
+use loom_engine::audit::{self, AuditLog};
+use loom_engine::collar::PriceCollar;
+use loom_engine::consumer::{ArchivedConsumer, DEFAULT_BUFFER_LIMIT, BufferedConsumer, ConsoleConsumer, RedactedConsumer, RedisQueueConsumer, TradeConsumer};
 use loom_engine::engine::MatchEngine;
+use loom_engine::ledger;
+use loom_engine::reconcile::{self, Reconciler, DEFAULT_RECONCILE_INTERVAL};
+use loom_engine::replication::{self, DEFAULT_CHECKPOINT_INTERVAL, DEFAULT_JOURNAL_MAX_LEN};
+use loom_engine::reference::ReferencePrices;
+use loom_engine::risk::RiskChain;
+use loom_engine::sequencer::{self, DEFAULT_ID_FLUSH_INTERVAL};
+use loom_engine::session::TradingSession;
+use loom_engine::settlement::{SettlementExporter, DEFAULT_SETTLEMENT_PERIOD};
+use loom_engine::trader::{DEFAULT_CHANNEL_CAPACITY, DEFAULT_RING_CAPACITY, TraderMode, TraderOptions};
+use loom_engine::warmup::Warmup;
+
+/// 默认等待消费器连通的最长时间
+const DEFAULT_CONSUMER_TIMEOUT: Duration = Duration::from_secs(30);
+/// 消费器探测失败后的重试间隔
+const CONSUMER_PROBE_INTERVAL: Duration = Duration::from_secs(1);
 
 #[tokio::main]
 async fn main() {
+    // 子命令
+    let result = match Cli::parse(std::env::args().skip(1)) {
+        Ok(Cli { command: Command::Replay(args), .. }) => cli_replay::run(args),
+        Ok(Cli { command: Command::Serve, config }) => serve(config.as_deref()).await,
+        Ok(Cli { command, config }) => run_offline(config.as_deref(), command).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+/// 不启动服务的子命令，与服务使用相同的配置和缓存
+async fn run_offline(file: Option<&str>, command: Command) -> anyhow::Result<()> {
+    let config = Config::from_file(file)?;
+    match command {
+        Command::Bench { orders, symbol } => cli::bench(&config, orders, symbol.as_deref()),
+        Command::Snapshot { out } => cli::snapshot(&config, &init_cache_manager(&config).await, &out).await,
+        Command::VerifyCache => cli::verify_cache(&config, &init_cache_manager(&config).await).await,
+        Command::RebuildCache { image, journal } => cli::rebuild_cache(&config, &init_cache_manager(&config).await, image.as_deref(), journal.as_deref()).await,
+        Command::VerifyAudit { symbol } => cli::verify_audit(&config, symbol.as_deref()),
+        Command::Report { from, to, symbol, format, out } => cli::report(&config, from.unwrap_or(0), to.unwrap_or(0), symbol.as_deref(), format, out.as_deref()),
+        Command::Serve | Command::Replay(_) => unreachable!(),
+    }
+}
+
+async fn serve(file: Option<&str>) -> anyhow::Result<()> {
     // 初始化日志
-    env_logger::Builder::from_env(Env::default().default_filter_or("debug")).init();
+    logging::init("debug");
+    info!("ALLOCATOR: {}", allocator::NAME);
 
     // 初始化配置
-    let config = Config::from_file(None).unwrap();
+    let config = Config::from_file(file)?;
+    if let Some(log) = &config.log {
+        logging::reload(log.clone())?;
+    }
 
     // 初始化缓存管理器
     let cache_manager = init_cache_manager(&config).await;
 
     // 初始化引擎
-    let market = init_engine(&config, cache_manager).await;
+    let mut engine = init_engine(&config, cache_manager.clone()).await;
+
+    // 提前监听时预热完成前/readyz返回503，否则恢复完成后再监听
+    let limiter = http_server::rate_limiter(&config);
+    let tls = TlsTerminator::from_config(&config)?;
+    let warmup = config.server.warmup.clone().unwrap_or_default();
+    let server = if warmup.listen_early.unwrap_or(false) {
+        engine.handle().warmup().begin(config.markets().into_iter().map(|(_, spec)| spec.symbol).collect());
+        let (config, handle, limiter, tls) = (config.clone(), engine.handle(), limiter.clone(), tls.clone());
+        Some(tokio::spawn(async move { start_http_server(&config, handle, limiter, tls).await }))
+    } else {
+        None
+    };
+
+    // 连通消费器并恢复交易对
+    let timeout = warmup.consumer_timeout_ms.map(Duration::from_millis).unwrap_or(DEFAULT_CONSUMER_TIMEOUT);
+    init_traders(&config, &mut engine, &cache_manager, timeout).await;
+    engine.handle().warmup().finish();
+    info!("WARMUP: finished, symbols_cnt={}", engine.handle().symbols().len());
+
+    // 进程崩溃时转储订单簿
+    dump::install_panic_hook(engine.handle(), config.dump_dir());
+
+    // 启动告警监视
+    init_alert(&config, &engine, &cache_manager);
+
+    // 启动状态哈希检查点
+    if let Some(replication) = &config.replication {
+        let interval = replication.checkpoint_interval_ms
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_CHECKPOINT_INTERVAL);
+        replication::launch_checkpoints(engine.handle(), interval, engine.subscribe());
+    }
+
+    // 启动余额写入
+    if let Some(ledger) = &config.ledger {
+        let interval = ledger.flush_interval_ms
+            .map(Duration::from_millis)
+            .unwrap_or(ledger::DEFAULT_FLUSH_INTERVAL);
+        ledger::launch_flush(engine.handle(), interval, engine.subscribe());
+    }
+
+    // 启动客户端ID高水位定时写入
+    if config.market.check_order_ids.unwrap_or(false) {
+        sequencer::launch_id_flush(engine.handle(), DEFAULT_ID_FLUSH_INTERVAL, engine.subscribe());
+    }
+
+    // 启动缓存对账
+    if let Some(reconcile) = &config.reconcile {
+        let interval = reconcile.interval_secs
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_RECONCILE_INTERVAL);
+        reconcile::launch(engine.handle(), interval, engine.subscribe());
+    }
+
+    // 订阅参考价
+    if let (Some(reference), Some(channel)) = (engine.handle().reference_prices(), config.reference.as_ref().and_then(|r| r.redis_channel.as_ref())) {
+        Arc::clone(reference).launch_subscriber(&config.cache.redis.to_redis_uri(), channel, engine.subscribe()).unwrap();
+    }
+
+    // 启动结算导出
+    if let Some(settlement) = &config.settlement {
+        let archive = config.archive.as_ref().expect("[settlement] requires [archive] config section");
+        let period = settlement.period_secs
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_SETTLEMENT_PERIOD);
+        let dir = settlement.dir.clone().unwrap_or("./settlement".to_string());
+        SettlementExporter::new(
+            TradeArchive::new(Path::new(&archive.dir)),
+            engine.handle().registry().clone(),
+            Path::new(&dir),
+            period,
+        ).launch(engine.subscribe());
+    }
+
+    // 收到SIGHUP时重新加载配置
+    ConfigReloader::new(file, &config, engine.handle())
+        .with_rate_limiter(limiter.clone())
+        .with_tls(tls.clone())
+        .launch(engine.subscribe());
 
     // 启动HttpServer
-    let trader_market = Arc::new(Mutex::new(market));
-    start_http_server(&config, Arc::clone(&trader_market)).await
+    match server {
+        Some(server) => server.await??,
+        None => start_http_server(&config, engine.handle(), limiter, tls).await?,
+    }
+
+    // 关闭引擎，超时未退出的交易员线程无法终止，直接退出进程
+    if !engine.shutdown().await {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn init_alert(config: &Config, engine: &MatchEngine, cache_manager: &CacheManager) {
+    let alert = match &config.alert {
+        Some(alert) => alert,
+        None => return,
+    };
+    let thresholds = AlertThresholds {
+        queue_depth: alert.queue_depth,
+        consumer_failures_per_min: alert.consumer_failures_per_min,
+        redis_latency_ms: alert.redis_latency_ms,
+        trader_halted: alert.trader_halted.unwrap_or(true),
+    };
+    let sinks = alert_sinks(config, cache_manager);
+    let mut monitor = AlertMonitor::new(thresholds, sinks);
+    if let Some(interval) = alert.interval_ms {
+        monitor = monitor.with_interval(Duration::from_millis(interval));
+    }
+    monitor.launch(engine.handle(), engine.subscribe());
+}
+
+/// 告警输出，始终输出到日志
+fn alert_sinks(config: &Config, cache: &CacheManager) -> Vec<AlertSink> {
+    let mut sinks = vec![AlertSink::Log];
+    let Some(alert) = &config.alert else {
+        return sinks;
+    };
+    if let Some(url) = &alert.webhook {
+        sinks.push(AlertSink::Webhook(url.clone()));
+    }
+    if let Some(channel) = &alert.redis_channel {
+        sinks.push(AlertSink::Redis { cache: cache.clone(), channel: channel.clone() });
+    }
+    sinks
 }
 
 async fn init_cache_manager(config: &Config) -> CacheManager {
@@ -41,10 +221,145 @@ async fn init_cache_manager(config: &Config) -> CacheManager {
 }
 
 async fn init_engine(config: &Config, cache_manager: CacheManager) -> MatchEngine {
-    let mut market = MatchEngine::new(cache_manager.clone());
-    let kind = config.consumer.clone();
+    let mut market = match config.market.shards {
+        Some(shards) if shards > 0 => MatchEngine::with_shards(cache_manager.clone(), shards).unwrap(),
+        _ => MatchEngine::new(cache_manager.clone()),
+    };
+    if let Some(timeout) = config.market.drain_timeout_ms {
+        market = market.with_drain_timeout(Duration::from_millis(timeout));
+    }
+    if let Some(batch) = config.market.recovery_batch {
+        market = market.with_recovery_batch(batch);
+    }
+    if let Some(verify_every) = config.market.verify_every {
+        market = market.with_verify_every(verify_every);
+    }
+    if let Some(uncross) = config.market.uncross {
+        market = market.with_uncross(uncross);
+    }
+    if config.market.check_order_ids.unwrap_or(false) {
+        market = market.with_id_check();
+    }
+    if let Some(limits) = config.market.accounts {
+        market = market.with_account_limits(limits);
+    }
+    if let Some(mmp) = &config.market.mmp {
+        market = market.with_mmp(mmp.clone());
+    }
+    if let Some(strategy) = config.market.id_strategy {
+        market = market.with_id_strategy(strategy).unwrap();
+    }
+    let reference = config.reference.as_ref().map(|conf| Arc::new(ReferencePrices::new(conf)));
+    if let Some(reference) = &reference {
+        market = market.with_reference_prices(Arc::clone(reference));
+    }
+    if let Some(risk) = &config.market.risk {
+        market = market.with_risk_checks(RiskChain::from_config_with_reference(risk, reference));
+    }
+    if config.ledger.is_some() {
+        // 账本作为最后一项风控检查，在其他检查通过后预留资金
+        market = market.with_ledger();
+        let ledger = market.handle().ledger().cloned().unwrap();
+        ledger.load(&cache_manager).await.unwrap();
+        if let Some(audit) = &config.audit {
+            ledger.set_audit(AuditLog::open(Path::new(&audit.dir), audit::LEDGER_AUDIT).unwrap());
+        }
+    }
+    if let Some(reconcile) = &config.reconcile {
+        market = market.with_reconciler(Reconciler::new(reconcile).with_sinks(alert_sinks(config, &cache_manager)));
+    }
+    if let Some(replication) = &config.replication {
+        let max_len = replication.journal_max_len.unwrap_or(DEFAULT_JOURNAL_MAX_LEN);
+        market = market.with_replication(replication.role, max_len);
+    }
+    market
+}
+
+/// 连通消费器后注册并恢复所有交易对
+async fn init_traders(config: &Config, market: &mut MatchEngine, cache_manager: &CacheManager, timeout: Duration) {
+    // 租户未配置消费者时使用全局消费者
+    let consumer = init_consumer(config, &config.consumer, config.clickhouse.as_ref(), cache_manager).await;
+    let mut tenant_consumers = HashMap::new();
+    for t in config.tenants.iter().flatten() {
+        if let Some(kind) = &t.consumer {
+            let sink = t.clickhouse.as_ref().or(config.clickhouse.as_ref());
+            tenant_consumers.insert(t.id.clone(), init_consumer(config, kind, sink, cache_manager).await);
+        }
+    }
+    let warmup = Arc::clone(market.handle().warmup());
+    for consumer in std::iter::once(&consumer).chain(tenant_consumers.values()) {
+        probe_consumer(&warmup, consumer, timeout).await;
+    }
+    warmup.set_consumers(Ok(()));
 
-    let consumer = match kind {
+    for (i, (tenant, spec)) in config.markets().into_iter().enumerate() {
+        let symbol = spec.symbol.clone();
+        let (mode, default_capacity) = match &config.market.native {
+            None => (TraderMode::Tokio, DEFAULT_CHANNEL_CAPACITY),
+            Some(native) => (
+                TraderMode::Native {
+                    core: native.cores.as_ref()
+                        .filter(|cores| !cores.is_empty())
+                        .map(|cores| cores[i % cores.len()]),
+                },
+                native.capacity.unwrap_or(DEFAULT_RING_CAPACITY),
+            ),
+        };
+        // 交易对配置优先于全局配置
+        let queue = config.market.traders.as_ref().and_then(|traders| traders.get(&symbol));
+        let capacity = queue.and_then(|q| q.capacity)
+            .or(config.market.capacity)
+            .unwrap_or(default_capacity);
+        let backpressure = queue.and_then(|q| q.backpressure)
+            .or(config.market.backpressure)
+            .unwrap_or_default();
+        let mut options = TraderOptions::default()
+            .with_mode(mode)
+            .with_capacity(capacity)
+            .with_backpressure(backpressure)
+            .with_dump_dir(config.dump_dir())
+            .with_spec(spec);
+        if let Some(wait) = queue.and_then(|q| q.max_queue_wait_ms).or(config.market.max_queue_wait_ms) {
+            options = options.with_max_queue_wait(Duration::from_millis(wait));
+        }
+        if let Some(interval) = config.market.snapshot_interval_ms {
+            options = options.with_snapshot_interval(Duration::from_millis(interval));
+        }
+        if let Some(session) = config.market.session(&symbol) {
+            options = options.with_session(TradingSession::new(session).unwrap());
+        }
+        if let Some(collar) = config.market.collar(&symbol) {
+            options = options.with_collar(PriceCollar::new(collar).unwrap());
+        }
+        if let Some(recovery) = config.market.recovery(&symbol) {
+            options = options.with_recovery(recovery.clone());
+        }
+        if let Some(audit) = &config.audit {
+            options = options.with_audit(AuditLog::open(Path::new(&audit.dir), &symbol).unwrap());
+        }
+        let consumer = tenant.and_then(|t| tenant_consumers.get(&t.id)).unwrap_or(&consumer);
+        market.new_trader_with_options(symbol.as_str(), consumer.clone(), options).await.unwrap();
+    }
+}
+
+/// 等待消费器连通，超时后启动失败
+async fn probe_consumer(warmup: &Warmup, consumer: &TradeConsumer, timeout: Duration) {
+    let deadline = Instant::now() + timeout;
+    loop {
+        match consumer.probe().await {
+            Ok(()) => return,
+            Err(e) if Instant::now() < deadline => {
+                warn!("WARMUP: consumer unreachable, retrying, err={}", e);
+                warmup.set_consumers(Err(e.to_string()));
+                tokio::time::sleep(CONSUMER_PROBE_INTERVAL).await;
+            }
+            Err(e) => panic!("trade consumer unreachable after {}ms: {}", timeout.as_millis(), e),
+        }
+    }
+}
+
+async fn init_consumer(config: &Config, kind: &ConsumerKind, clickhouse: Option<&ClickHouseSink>, cache_manager: &CacheManager) -> TradeConsumer {
+    let mut consumer = match kind {
         ConsumerKind::Console => {
             TradeConsumer::Console(ConsoleConsumer {})
         }
@@ -53,21 +368,26 @@ async fn init_engine(config: &Config, cache_manager: CacheManager) -> MatchEngin
                 RedisQueueConsumer::new_with_cache_manager(cache_manager.clone())
                     .await
                     .unwrap()
+                    .with_stream(config.trade_stream.unwrap_or_default())
             )
         }
+        ConsumerKind::ClickHouse => {
+            let sink = clickhouse.expect("missing [clickhouse] config section");
+            TradeConsumer::ClickHouse(sink.consumer().unwrap())
+        }
     };
-
-    let symbols = config.market.symbols.clone().unwrap_or(Vec::new());
-    for symbol in symbols {
-        market.new_trader(symbol.as_str(), consumer.clone()).await.unwrap();
+    if !config.trade_accounts.unwrap_or(false) {
+        consumer = TradeConsumer::Redacted(RedactedConsumer::new(consumer));
     }
-
-    market
+    if let Some(archive) = &config.archive {
+        consumer = TradeConsumer::Archived(ArchivedConsumer::new(consumer, TradeArchive::new(Path::new(&archive.dir))));
+    }
+    if let Some(buffer) = &config.consumer_buffer {
+        consumer = TradeConsumer::Buffered(BufferedConsumer::new(
+            consumer,
+            buffer.max_trades.unwrap_or(500),
+            Duration::from_millis(buffer.max_delay_ms.unwrap_or(50)),
+        ).with_limit(buffer.max_pending.unwrap_or(DEFAULT_BUFFER_LIMIT), buffer.overflow.unwrap_or_default()));
+    }
+    consumer
 }
-
-
-
-
-
-
-
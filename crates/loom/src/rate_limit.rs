@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use serde::{Deserialize, Serialize};
+
+/// 默认API Key请求头
+pub const DEFAULT_API_KEY_HEADER: &str = "x-api-key";
+
+/// 空闲超过该时间的令牌桶会被清理
+const BUCKET_IDLE: Duration = Duration::from_secs(60);
+
+/// 每处理该数量的请求清理一次空闲令牌桶
+const SWEEP_EVERY: u64 = 4096;
+
+/// 令牌桶配置
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BucketConfig {
+    /// 每秒补充的令牌数
+    pub rate: f64,
+    /// 令牌桶容量，即允许的突发请求量
+    pub burst: f64,
+}
+
+/// 令牌桶
+#[derive(Debug, Copy, Clone)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    /// 扣除weight个令牌，不足时返回需要等待的时间
+    fn acquire(&mut self, config: &BucketConfig, weight: f64, now: Instant) -> Result<(), Duration> {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * config.rate).min(config.burst);
+        self.updated = now;
+        if self.tokens >= weight {
+            self.tokens -= weight;
+            return Ok(());
+        }
+        let wait = if config.rate > 0.0 { (weight - self.tokens) / config.rate } else { BUCKET_IDLE.as_secs_f64() };
+        Err(Duration::from_secs_f64(wait))
+    }
+}
+
+/// 限流器，按IP和API Key分别维护令牌桶，请求需同时通过两者
+#[derive(Debug)]
+pub struct RateLimiter {
+    rules: RwLock<Rules>,
+    api_key_header: String,
+    buckets: Mutex<Buckets>,
+}
+
+/// 限流规则，可在运行时替换
+#[derive(Debug, Default)]
+struct Rules {
+    per_ip: Option<BucketConfig>,
+    per_key: Option<BucketConfig>,
+    /// 各路由请求消耗的令牌数，未配置的路由消耗1个
+    weights: HashMap<String, u32>,
+}
+
+#[derive(Debug, Default)]
+struct Buckets {
+    map: HashMap<String, Bucket>,
+    requests: u64,
+}
+
+impl RateLimiter {
+    pub fn new(per_ip: Option<BucketConfig>, per_key: Option<BucketConfig>) -> RateLimiter {
+        RateLimiter {
+            rules: RwLock::new(Rules { per_ip, per_key, weights: HashMap::new() }),
+            api_key_header: String::from(DEFAULT_API_KEY_HEADER),
+            buckets: Mutex::new(Buckets::default()),
+        }
+    }
+
+    pub fn with_weights(self, weights: HashMap<String, u32>) -> RateLimiter {
+        self.rules.write().unwrap().weights = weights;
+        self
+    }
+
+    pub fn with_api_key_header(mut self, header: &str) -> RateLimiter {
+        self.api_key_header = header.to_ascii_lowercase();
+        self
+    }
+
+    pub fn weight(&self, path: &str) -> u32 {
+        self.rules.read().unwrap().weights.get(path).copied().unwrap_or(1)
+    }
+
+    /// 替换限流规则，已有令牌桶保留剩余令牌，超过新容量的部分在下次请求时丢弃
+    pub fn reload(&self, per_ip: Option<BucketConfig>, per_key: Option<BucketConfig>, weights: HashMap<String, u32>) {
+        *self.rules.write().unwrap() = Rules { per_ip, per_key, weights };
+    }
+
+    /// 检查请求是否放行，被限流时返回建议的重试等待时间
+    pub fn check(&self, ip: Option<&str>, api_key: Option<&str>, path: &str, now: Instant) -> Result<(), Duration> {
+        let rules = self.rules.read().unwrap();
+        let weight = rules.weights.get(path).copied().unwrap_or(1) as f64;
+        let mut buckets = self.buckets.lock().unwrap();
+        buckets.requests += 1;
+        if buckets.requests.is_multiple_of(SWEEP_EVERY) {
+            buckets.map.retain(|_, b| now.saturating_duration_since(b.updated) < BUCKET_IDLE);
+        }
+        let mut checks = Vec::with_capacity(2);
+        if let (Some(config), Some(ip)) = (&rules.per_ip, ip) {
+            checks.push((config, format!("ip:{}", ip)));
+        }
+        if let (Some(config), Some(key)) = (&rules.per_key, api_key) {
+            checks.push((config, format!("key:{}", key)));
+        }
+        // 先确认所有令牌桶都足够再扣除，避免一个桶拒绝时另一个桶白白扣减
+        let mut wait = Duration::ZERO;
+        for (config, key) in &checks {
+            let mut bucket = buckets.map.get(key).copied()
+                .unwrap_or(Bucket { tokens: config.burst, updated: now });
+            if let Err(w) = bucket.acquire(config, weight, now) {
+                wait = wait.max(w);
+            }
+        }
+        if wait > Duration::ZERO {
+            return Err(wait);
+        }
+        for (config, key) in checks {
+            let bucket = buckets.map.entry(key).or_insert(Bucket { tokens: config.burst, updated: now });
+            let _ = bucket.acquire(config, weight, now);
+        }
+        Ok(())
+    }
+
+    /// 退回请求扣除的API Key令牌，参数校验失败的请求不占用API Key的额度，IP令牌不退回
+    pub fn refund(&self, api_key: &str, path: &str) {
+        let rules = self.rules.read().unwrap();
+        let Some(config) = &rules.per_key else {
+            return;
+        };
+        let weight = rules.weights.get(path).copied().unwrap_or(1) as f64;
+        let mut buckets = self.buckets.lock().unwrap();
+        if let Some(bucket) = buckets.map.get_mut(&format!("key:{}", api_key)) {
+            bucket.tokens = (bucket.tokens + weight).min(config.burst);
+        }
+    }
+}
+
+/// 限流中间件，被限流的请求返回429并携带Retry-After
+pub async fn rate_limit(State(limiter): State<Arc<RateLimiter>>, req: Request, next: Next) -> Response {
+    let ip = req.extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip().to_string());
+    let api_key = req.headers()
+        .get(limiter.api_key_header.as_str())
+        .and_then(|v| v.to_str().ok())
+        .map(String::from);
+    let path = req.uri().path().to_string();
+    match limiter.check(ip.as_deref(), api_key.as_deref(), &path, Instant::now()) {
+        Ok(_) => {
+            let resp = next.run(req).await;
+            if let (Some(api_key), StatusCode::BAD_REQUEST) = (&api_key, resp.status()) {
+                limiter.refund(api_key, &path);
+            }
+            resp
+        }
+        Err(wait) => {
+            let retry_after = wait.as_secs_f64().ceil().max(1.0) as u64;
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, retry_after.to_string())],
+                "rate limit exceeded",
+            ).into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::time::{Duration, Instant};
+
+    use crate::rate_limit::{BucketConfig, RateLimiter};
+
+    #[test]
+    fn token_bucket_test() {
+        let limiter = RateLimiter::new(
+            Some(BucketConfig { rate: 10.0, burst: 2.0 }),
+            Some(BucketConfig { rate: 1.0, burst: 5.0 }),
+        ).with_weights(HashMap::from([(String::from("/api/v1/match"), 2)]));
+        let now = Instant::now();
+        // IP令牌桶容量为2，一次下单即耗尽
+        assert!(limiter.check(Some("10.0.0.1"), None, "/api/v1/match", now).is_ok());
+        let wait = limiter.check(Some("10.0.0.1"), None, "/api/v1/depth", now).unwrap_err();
+        assert_eq!(wait, Duration::from_millis(100));
+        // 其他IP不受影响，补充令牌后恢复
+        assert!(limiter.check(Some("10.0.0.2"), None, "/api/v1/depth", now).is_ok());
+        assert!(limiter.check(Some("10.0.0.1"), None, "/api/v1/depth", now + Duration::from_millis(100)).is_ok());
+        // 同一API Key在不同IP上共享额度
+        let later = now + Duration::from_secs(1);
+        assert!(limiter.check(Some("10.0.0.3"), Some("k"), "/api/v1/match", later).is_ok());
+        assert!(limiter.check(Some("10.0.0.4"), Some("k"), "/api/v1/match", later).is_ok());
+        assert!(limiter.check(Some("10.0.0.5"), Some("k"), "/api/v1/match", later).is_err());
+        // 被拒绝的请求不扣除IP令牌
+        assert!(limiter.check(Some("10.0.0.5"), None, "/api/v1/match", later).is_ok());
+        // 退回的API Key令牌可以再次使用，不超过容量
+        limiter.refund("k", "/api/v1/match");
+        assert!(limiter.check(Some("10.0.0.6"), Some("k"), "/api/v1/match", later).is_ok());
+        (0..5).for_each(|_| limiter.refund("k", "/api/v1/match"));
+        let later = later + Duration::from_secs(1);
+        assert!(limiter.check(Some("10.0.0.7"), Some("k"), "/api/v1/match", later).is_ok());
+        assert!(limiter.check(Some("10.0.0.8"), Some("k"), "/api/v1/match", later).is_ok());
+        assert!(limiter.check(Some("10.0.0.9"), Some("k"), "/api/v1/match", later).is_err());
+    }
+
+    #[test]
+    fn reload_test() {
+        let limiter = RateLimiter::new(Some(BucketConfig { rate: 1.0, burst: 4.0 }), None);
+        let now = Instant::now();
+        assert!(limiter.check(Some("10.0.0.1"), None, "/api/v1/match", now).is_ok());
+        // 新容量小于剩余令牌时按新容量计算
+        limiter.reload(Some(BucketConfig { rate: 1.0, burst: 1.0 }), None, HashMap::from([(String::from("/api/v1/depth"), 3)]));
+        assert_eq!(limiter.weight("/api/v1/depth"), 3);
+        assert!(limiter.check(Some("10.0.0.1"), None, "/api/v1/match", now).is_ok());
+        assert!(limiter.check(Some("10.0.0.1"), None, "/api/v1/match", now).is_err());
+        limiter.reload(None, None, HashMap::new());
+        assert!(limiter.check(Some("10.0.0.1"), None, "/api/v1/match", now).is_ok());
+    }
+}
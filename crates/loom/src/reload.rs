@@ -0,0 +1,325 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+use anyhow::bail;
+use log::{error, info, warn};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::{broadcast, Mutex};
+use tokio_rustls::rustls::ServerConfig;
+
+use loom_core::symbol::SymbolSpec;
+use loom_engine::engine::EngineHandle;
+
+use crate::config::{Config, RateLimit};
+use crate::logging::{self, LogFilter};
+use crate::rate_limit::{BucketConfig, RateLimiter};
+use crate::tls::{self, TlsTerminator};
+
+/// 重新加载配置时需要应用的修改，生成时已检查全部修改，应用时不再因配置不合法失败
+#[derive(Debug, Clone, Default)]
+pub struct ReloadPlan {
+    /// 规格有变化的交易对，包括费率和价格带
+    pub specs: Vec<SymbolSpec>,
+    /// 新的限流规则
+    pub rate_limit: Option<RateLimit>,
+    /// 新的日志过滤规则
+    pub log: Option<LogFilter>,
+    /// 重新读取的TLS证书，证书文件可能已被替换，启用TLS时每次重新加载都重新读取
+    pub tls: Option<Arc<ServerConfig>>,
+    /// 修改后需要重启才能生效的配置段
+    pub restart_required: Vec<String>,
+}
+
+impl ReloadPlan {
+    pub fn is_empty(&self) -> bool {
+        self.specs.is_empty() && self.rate_limit.is_none() && self.log.is_none() && self.tls.is_none()
+    }
+}
+
+/// 对比当前配置和新配置，生成可热加载部分的修改，新配置不合法或增删交易对时拒绝整个重新加载
+pub fn plan(current: &Config, next: &Config, rate_limited: bool) -> anyhow::Result<ReloadPlan> {
+    let mut plan = ReloadPlan::default();
+    let old: BTreeMap<String, SymbolSpec> = current.markets().into_iter().map(|(_, s)| (s.symbol.clone(), s)).collect();
+    let new: BTreeMap<String, SymbolSpec> = next.markets().into_iter().map(|(_, s)| (s.symbol.clone(), s)).collect();
+    if old.keys().ne(new.keys()) {
+        let (old, new): (BTreeSet<_>, BTreeSet<_>) = (old.keys().collect(), new.keys().collect());
+        bail!("adding or removing symbols requires restart, added={:?}, removed={:?}", &new - &old, &old - &new);
+    }
+    for (symbol, spec) in new {
+        let current = &old[&symbol];
+        if &spec == current {
+            continue;
+        }
+        if spec.price_decimals != current.price_decimals {
+            bail!("changing price decimals requires restart, symbol={}", symbol);
+        }
+        spec.validate()?;
+        plan.specs.push(spec);
+    }
+
+    if !same(&current.rate_limit, &next.rate_limit) {
+        let conf = next.rate_limit.as_ref();
+        if !rate_limited && conf.is_some() {
+            bail!("enabling [rate_limit] requires restart");
+        }
+        let header = |conf: Option<&RateLimit>| conf.and_then(|c| c.api_key_header.clone());
+        if header(conf) != header(current.rate_limit.as_ref()) {
+            bail!("changing rate_limit.api_key_header requires restart");
+        }
+        for bucket in conf.iter().flat_map(|c| [c.per_ip, c.per_key]).flatten() {
+            validate_bucket(&bucket)?;
+        }
+        // 删除限流配置时不再限流
+        plan.rate_limit = Some(conf.cloned().unwrap_or(RateLimit { per_ip: None, per_key: None, api_key_header: None, routes: None }));
+    }
+
+    if current.log != next.log {
+        if let Some(log) = &next.log {
+            log.validate()?;
+            plan.log = Some(log.clone());
+        }
+    }
+
+    match (&current.server.tls, &next.server.tls) {
+        (Some(_), Some(conf)) => plan.tls = Some(tls::load(conf)?),
+        (None, None) => {}
+        _ => bail!("enabling or disabling [server.tls] requires restart"),
+    }
+
+    plan.restart_required = static_changes(current, next);
+    Ok(plan)
+}
+
+fn same<T: serde::Serialize>(a: &T, b: &T) -> bool {
+    serde_json::to_value(a).ok() == serde_json::to_value(b).ok()
+}
+
+fn validate_bucket(bucket: &BucketConfig) -> anyhow::Result<()> {
+    if !(bucket.rate >= 0.0 && bucket.burst > 0.0 && bucket.rate.is_finite() && bucket.burst.is_finite()) {
+        bail!("invalid rate limit bucket, rate={}, burst={}", bucket.rate, bucket.burst);
+    }
+    Ok(())
+}
+
+/// 去掉可热加载的部分后仍有变化的顶层配置段
+fn static_changes(current: &Config, next: &Config) -> Vec<String> {
+    let strip = |config: &Config| {
+        let mut config = config.clone();
+        config.market.symbols = None;
+        for t in config.tenants.iter_mut().flatten() {
+            t.symbols.clear();
+        }
+        config.rate_limit = None;
+        config.log = None;
+        config.server.tls = None;
+        serde_json::to_value(config).unwrap_or_default()
+    };
+    let (old, new) = (strip(current), strip(next));
+    let (Some(old), Some(new)) = (old.as_object(), new.as_object()) else {
+        return Vec::new();
+    };
+    old.keys().chain(new.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter(|key| old.get(*key) != new.get(*key))
+        .cloned()
+        .collect()
+}
+
+/// 配置重新加载器，收到SIGHUP时重新读取配置文件，应用交易对规格、限流、日志级别和TLS证书的修改
+pub struct ConfigReloader {
+    file: Option<String>,
+    current: Mutex<Config>,
+    engine: EngineHandle,
+    limiter: Option<Arc<RateLimiter>>,
+    tls: Option<Arc<TlsTerminator>>,
+}
+
+impl ConfigReloader {
+    pub fn new(file: Option<&str>, config: &Config, engine: EngineHandle) -> ConfigReloader {
+        ConfigReloader {
+            file: file.map(String::from),
+            current: Mutex::new(config.clone()),
+            engine,
+            limiter: None,
+            tls: None,
+        }
+    }
+
+    /// 服务使用的限流器，未配置限流时为None
+    pub fn with_rate_limiter(mut self, limiter: Option<Arc<RateLimiter>>) -> ConfigReloader {
+        self.limiter = limiter;
+        self
+    }
+
+    /// 服务使用的TLS终止，未配置TLS时为None
+    pub fn with_tls(mut self, tls: Option<Arc<TlsTerminator>>) -> ConfigReloader {
+        self.tls = tls;
+        self
+    }
+
+    /// 重新读取配置文件并应用修改，配置不合法时不应用任何修改
+    pub async fn reload(&self) -> anyhow::Result<ReloadPlan> {
+        let next = Config::from_file(self.file.as_deref())?;
+        self.apply(next).await
+    }
+
+    /// 应用新配置，交易对规格部分应用失败时恢复已应用的交易对，整个重新加载不生效
+    pub async fn apply(&self, next: Config) -> anyhow::Result<ReloadPlan> {
+        let mut current = self.current.lock().await;
+        let plan = plan(&current, &next, self.limiter.is_some())?;
+        for (i, spec) in plan.specs.iter().enumerate() {
+            if let Err(e) = self.engine.replace_symbol(spec.clone()).await {
+                self.rollback(&current, &plan.specs[..i]).await;
+                bail!("apply symbol spec failed, symbol={}, err={}", &spec.symbol, e);
+            }
+        }
+        if let (Some(limiter), Some(conf)) = (&self.limiter, &plan.rate_limit) {
+            limiter.reload(conf.per_ip, conf.per_key, conf.routes.clone().unwrap_or_default());
+            info!("RATE LIMIT RELOADED: per_ip={:?}, per_key={:?}", conf.per_ip, conf.per_key);
+        }
+        if let Some(log) = &plan.log {
+            logging::reload(log.clone())?;
+            info!("LOG FILTER RELOADED: {:?}", log);
+        }
+        if let (Some(terminator), Some(conf), Some(paths)) = (&self.tls, &plan.tls, &next.server.tls) {
+            terminator.reload(Arc::clone(conf));
+            info!("TLS CERT RELOADED: cert={}, key={}", paths.cert, paths.key);
+        }
+        if !plan.restart_required.is_empty() {
+            warn!("CONFIG CHANGES IGNORED UNTIL RESTART: sections={:?}", &plan.restart_required);
+        }
+        *current = next;
+        Ok(plan)
+    }
+
+    /// 将已应用的交易对恢复为当前配置中的规格
+    async fn rollback(&self, current: &Config, applied: &[SymbolSpec]) {
+        let specs: BTreeMap<String, SymbolSpec> = current.markets().into_iter().map(|(_, s)| (s.symbol.clone(), s)).collect();
+        for spec in applied.iter().rev() {
+            match self.engine.replace_symbol(specs[&spec.symbol].clone()).await {
+                Ok(_) => warn!("SYMBOL SPEC ROLLED BACK: symbol={}", &spec.symbol),
+                Err(e) => error!("ROLLBACK SYMBOL SPEC FAILED: symbol={}, err={}", &spec.symbol, e),
+            }
+        }
+    }
+
+    /// 启动SIGHUP监听，引擎关闭时退出
+    pub fn launch(self, mut ctx: broadcast::Receiver<bool>) {
+        let mut hangup = signal(SignalKind::hangup()).expect("failed to install SIGHUP handler");
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = hangup.recv() => {}
+                    _ = ctx.recv() => break,
+                }
+                match self.reload().await {
+                    Ok(plan) => info!("CONFIG RELOADED: symbols={}, rate_limit={}, log={}, tls={}",
+                        plan.specs.len(), plan.rate_limit.is_some(), plan.log.is_some(), plan.tls.is_some()),
+                    Err(e) => error!("CONFIG RELOAD REJECTED: err={}", e),
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use bigdecimal::BigDecimal;
+
+    use loom_core::symbol::{FeeSchedule, SymbolSpec};
+    use loom_engine::engine::MatchEngine;
+    use loom_engine::trader::TraderOptions;
+
+    use crate::config::{Config, SymbolEntry, Tls};
+    use crate::logging::LogFilter;
+    use crate::rate_limit::BucketConfig;
+    use crate::reload::{plan, ConfigReloader};
+
+    fn with_spec(config: &Config, spec: SymbolSpec) -> Config {
+        let mut config = config.clone();
+        config.market.symbols = Some(vec![SymbolEntry::Spec(spec)]);
+        config
+    }
+
+    #[test]
+    fn plan_test() {
+        let current = Config::from_toml(&std::fs::read_to_string("config.toml").unwrap(), []).unwrap();
+        assert!(plan(&current, &current, true).unwrap().is_empty());
+
+        let spec = current.market.specs()[0].clone();
+        let mut changed = spec.clone();
+        changed.price_band_pct = Some(BigDecimal::from(5));
+        changed.fees.taker = BigDecimal::from_str("0.001").unwrap();
+        let mut next = with_spec(&current, changed.clone());
+        next.log = Some(LogFilter { filter: String::from("warn"), symbol: None, symbol_level: None });
+        next.rate_limit.as_mut().unwrap().per_ip = Some(BucketConfig { rate: 50.0, burst: 100.0 });
+        next.market.capacity = Some(2048);
+        let result = plan(&current, &next, true).unwrap();
+        assert_eq!(result.specs, vec![changed]);
+        assert_eq!(result.rate_limit.unwrap().per_ip, Some(BucketConfig { rate: 50.0, burst: 100.0 }));
+        assert_eq!(result.log.unwrap().filter, "warn");
+        assert_eq!(result.restart_required, vec![String::from("market")]);
+
+        // 不合法的修改拒绝整个重新加载
+        let mut invalid = next.clone();
+        invalid.rate_limit.as_mut().unwrap().per_key = Some(BucketConfig { rate: 1.0, burst: 0.0 });
+        assert!(plan(&current, &invalid, true).is_err());
+        let mut bad_lot = spec.clone();
+        bad_lot.lot_size = Some(0);
+        assert!(plan(&current, &with_spec(&current, bad_lot), true).is_err());
+        let mut decimals = spec.clone();
+        decimals.price_decimals = 2;
+        assert!(plan(&current, &with_spec(&current, decimals), true).is_err());
+        assert!(plan(&current, &with_spec(&current, SymbolSpec::new("BTC-USDT-SPOT")), true).is_err());
+        let log = LogFilter { filter: String::from("info"), symbol: Some(spec.symbol.clone()), symbol_level: Some(String::from("loud")) };
+        assert!(plan(&current, &Config { log: Some(log), ..current.clone() }, true).is_err());
+        // 启动时未启用限流的服务不能通过重新加载启用
+        assert!(plan(&Config { rate_limit: None, ..current.clone() }, &current, false).is_err());
+    }
+
+    #[test]
+    fn tls_plan_test() {
+        let current = Config::from_toml(&std::fs::read_to_string("config.toml").unwrap(), []).unwrap();
+        let mut tls = current.clone();
+        tls.server.tls = Some(Tls { cert: String::from("testdata/cert.pem"), key: String::from("testdata/key.pem") });
+        // 启用TLS时每次重新加载都重新读取证书，不需要重启
+        let result = plan(&tls, &tls, true).unwrap();
+        assert!(result.tls.is_some());
+        assert!(result.restart_required.is_empty());
+        // 启用或关闭TLS需要重启，证书不可用时拒绝整个重新加载
+        assert!(plan(&current, &tls, true).is_err());
+        assert!(plan(&tls, &current, true).is_err());
+        let mut missing = tls.clone();
+        missing.server.tls.as_mut().unwrap().cert = String::from("testdata/missing.pem");
+        assert!(plan(&tls, &missing, true).is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn apply_rollback_test() {
+        let config = Config::from_toml(&std::fs::read_to_string("config.toml").unwrap(), []).unwrap();
+        let applied = SymbolSpec::new("AAA-USDT-SPOT");
+        let missing = config.market.specs()[0].clone();
+        let mut current = config.clone();
+        current.market.symbols = Some(vec![SymbolEntry::Spec(applied.clone()), SymbolEntry::Spec(missing.clone())]);
+        // 引擎中没有第二个交易对，应用其规格时失败
+        let mut engine = MatchEngine::builder()
+            .spec(applied.clone(), TraderOptions::default())
+            .build()
+            .await
+            .unwrap();
+        let reloader = ConfigReloader::new(None, &current, engine.handle());
+        let fee = BigDecimal::from_str("0.003").unwrap();
+        let mut next = current.clone();
+        next.market.symbols = Some(vec![&applied, &missing].into_iter()
+            .map(|spec| SymbolEntry::Spec(SymbolSpec { fees: FeeSchedule { taker: fee.clone(), ..spec.fees.clone() }, ..spec.clone() }))
+            .collect());
+        assert!(reloader.apply(next.clone()).await.is_err());
+        assert_eq!(engine.handle().registry().get("AAA-USDT-SPOT").unwrap(), applied);
+        // 失败的重新加载不改变当前配置，修复后重试仍能识别出修改
+        assert_eq!(plan(&*reloader.current.lock().await, &next, false).unwrap().specs.len(), 2);
+        assert!(engine.shutdown().await);
+    }
+}
@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::error_handling::HandleErrorLayer;
+use axum::extract::{Request, State};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::{middleware, BoxError, Router};
+use log::warn;
+use tokio::sync::Semaphore;
+use tower::limit::GlobalConcurrencyLimitLayer;
+use tower::ServiceBuilder;
+
+use crate::config::ServerLimits;
+
+/// 请求超时配置
+#[derive(Debug, Clone, Default)]
+pub struct RequestTimeouts {
+    /// 未单独配置的路由使用的超时时间
+    default: Option<Duration>,
+    /// 各路由超时时间
+    routes: HashMap<String, Duration>,
+}
+
+impl RequestTimeouts {
+    pub fn new(default: Option<Duration>, routes: HashMap<String, Duration>) -> RequestTimeouts {
+        RequestTimeouts { default, routes }
+    }
+
+    pub fn timeout(&self, path: &str) -> Option<Duration> {
+        self.routes.get(path).copied().or(self.default)
+    }
+}
+
+/// 超时中间件，处理超时的请求返回408
+pub async fn request_timeout(State(timeouts): State<Arc<RequestTimeouts>>, req: Request, next: Next) -> Response {
+    let timeout = match timeouts.timeout(req.uri().path()) {
+        Some(timeout) => timeout,
+        None => return next.run(req).await,
+    };
+    let path = req.uri().path().to_string();
+    match tokio::time::timeout(timeout, next.run(req)).await {
+        Ok(resp) => resp,
+        Err(_) => {
+            warn!("REQUEST TIMEOUT: path={}, timeout={:?}", path, timeout);
+            (StatusCode::REQUEST_TIMEOUT, "request timeout").into_response()
+        }
+    }
+}
+
+/// 服务端限制，并发许可在所有监听地址间共享
+#[derive(Debug, Clone)]
+pub struct Limits {
+    timeouts: Arc<RequestTimeouts>,
+    max_body_bytes: Option<usize>,
+    concurrency: Option<Arc<Semaphore>>,
+}
+
+impl Limits {
+    pub fn new(conf: Option<&ServerLimits>) -> Limits {
+        let conf = conf.cloned().unwrap_or_default();
+        let routes = conf.route_timeouts_ms.unwrap_or_default()
+            .into_iter()
+            .map(|(path, ms)| (path, Duration::from_millis(ms)))
+            .collect();
+        Limits {
+            timeouts: Arc::new(RequestTimeouts::new(conf.timeout_ms.map(Duration::from_millis), routes)),
+            max_body_bytes: conf.max_body_bytes,
+            concurrency: conf.max_concurrency.map(|n| Arc::new(Semaphore::new(n))),
+        }
+    }
+
+    /// 为路由添加超时、请求体大小和并发限制，并发已满时直接拒绝新请求
+    pub fn apply(&self, mut app: Router) -> Router {
+        app = app.layer(middleware::from_fn_with_state(Arc::clone(&self.timeouts), request_timeout));
+        if let Some(max) = self.max_body_bytes {
+            app = app.layer(axum::extract::DefaultBodyLimit::max(max));
+        }
+        if let Some(semaphore) = &self.concurrency {
+            app = app.layer(
+                ServiceBuilder::new()
+                    .layer(HandleErrorLayer::new(overloaded))
+                    .load_shed()
+                    .layer(GlobalConcurrencyLimitLayer::with_semaphore(Arc::clone(semaphore)))
+            );
+        }
+        app
+    }
+}
+
+async fn overloaded(err: BoxError) -> (StatusCode, String) {
+    if err.is::<tower::load_shed::error::Overloaded>() {
+        (StatusCode::SERVICE_UNAVAILABLE, String::from("server overloaded"))
+    } else {
+        (StatusCode::INTERNAL_SERVER_ERROR, format!("{}", err))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use crate::server_limits::RequestTimeouts;
+
+    #[test]
+    fn route_timeout_test() {
+        let timeouts = RequestTimeouts::new(
+            Some(Duration::from_secs(5)),
+            HashMap::from([(String::from("/api/v1/match"), Duration::from_millis(200))]),
+        );
+        assert_eq!(timeouts.timeout("/api/v1/match"), Some(Duration::from_millis(200)));
+        assert_eq!(timeouts.timeout("/api/v1/depth"), Some(Duration::from_secs(5)));
+        assert_eq!(RequestTimeouts::default().timeout("/ping"), None);
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail};
+use axum::extract::{Request, State};
+use axum::middleware::Next;
+use axum::response::Response;
+
+use loom_engine::tenant::{self, TENANT_SEPARATOR};
+
+use crate::config::TenantConfig;
+use crate::rate_limit::DEFAULT_API_KEY_HEADER;
+
+/// 请求所属的租户，未携带租户API Key的请求访问默认市场
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Tenant(Option<String>);
+
+impl Tenant {
+    pub fn id(&self) -> Option<&str> {
+        self.0.as_deref()
+    }
+
+    /// 将请求中的交易对或账户转换为引擎内的名称，名称中不能出现租户分隔符，避免访问其他租户的市场
+    pub fn scope(&self, name: &str) -> anyhow::Result<String> {
+        if name.contains(TENANT_SEPARATOR) {
+            bail!("invalid name, name={}", name);
+        }
+        Ok(match &self.0 {
+            Some(id) => tenant::qualify(id, name),
+            None => name.to_string(),
+        })
+    }
+
+    /// 幂等键等内部键加上租户前缀，不校验分隔符
+    pub fn scope_key(&self, key: &str) -> String {
+        match &self.0 {
+            Some(id) => tenant::qualify(id, key),
+            None => key.to_string(),
+        }
+    }
+
+    /// 去掉引擎内名称的租户前缀，用于返回给客户端
+    pub fn unscope(&self, name: &str) -> String {
+        match (&self.0, tenant::split(name)) {
+            (Some(id), (Some(owner), name)) if id == owner => name.to_string(),
+            _ => name.to_string(),
+        }
+    }
+}
+
+/// 按API Key识别租户
+#[derive(Debug, Clone, Default)]
+pub struct TenantResolver {
+    /// API Key请求头，小写
+    header: String,
+    /// API Key到租户ID
+    keys: HashMap<String, String>,
+}
+
+impl TenantResolver {
+    pub fn new(tenants: &[TenantConfig]) -> anyhow::Result<TenantResolver> {
+        let mut keys = HashMap::new();
+        for t in tenants {
+            if t.id.is_empty() || t.id.contains(TENANT_SEPARATOR) {
+                bail!("invalid tenant id, id={}", &t.id);
+            }
+            for key in t.api_keys.iter() {
+                if let Some(other) = keys.insert(key.clone(), t.id.clone()) {
+                    return Err(anyhow!("api key shared by tenants, tenants={},{}", other, &t.id));
+                }
+            }
+        }
+        Ok(TenantResolver { header: String::from(DEFAULT_API_KEY_HEADER), keys })
+    }
+
+    pub fn with_header(mut self, header: &str) -> TenantResolver {
+        self.header = header.to_ascii_lowercase();
+        self
+    }
+
+    pub fn resolve(&self, api_key: Option<&str>) -> Tenant {
+        Tenant(api_key.and_then(|key| self.keys.get(key)).cloned())
+    }
+}
+
+/// 识别请求所属的租户并放入请求扩展
+pub async fn tenant(State(resolver): State<Arc<TenantResolver>>, mut req: Request, next: Next) -> Response {
+    let api_key = req.headers()
+        .get(resolver.header.as_str())
+        .and_then(|v| v.to_str().ok());
+    let tenant = resolver.resolve(api_key);
+    req.extensions_mut().insert(tenant);
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::TenantConfig;
+    use crate::tenant::{Tenant, TenantResolver};
+
+    fn tenant_config(id: &str, key: &str) -> TenantConfig {
+        TenantConfig {
+            id: id.to_string(),
+            api_keys: vec![key.to_string()],
+            symbols: vec![],
+            consumer: None,
+            clickhouse: None,
+        }
+    }
+
+    #[test]
+    fn resolve_test() {
+        let resolver = TenantResolver::new(&[tenant_config("acme", "k1"), tenant_config("globex", "k2")]).unwrap();
+        let acme = resolver.resolve(Some("k1"));
+        assert_eq!(acme.id(), Some("acme"));
+        assert_eq!(resolver.resolve(Some("unknown")), Tenant::default());
+        assert_eq!(resolver.resolve(None), Tenant::default());
+
+        assert_eq!(acme.scope("LOOM-USDT-SPOT").unwrap(), "acme.LOOM-USDT-SPOT");
+        assert_eq!(acme.unscope("acme.LOOM-USDT-SPOT"), "LOOM-USDT-SPOT");
+        assert_eq!(Tenant::default().scope("LOOM-USDT-SPOT").unwrap(), "LOOM-USDT-SPOT");
+        // 不能通过带前缀的名称访问其他租户
+        assert!(Tenant::default().scope("acme.LOOM-USDT-SPOT").is_err());
+        assert!(acme.scope("globex.LOOM-USDT-SPOT").is_err());
+
+        assert!(TenantResolver::new(&[tenant_config("a.b", "k1")]).is_err());
+        assert!(TenantResolver::new(&[tenant_config("acme", "k1"), tenant_config("globex", "k1")]).is_err());
+    }
+}
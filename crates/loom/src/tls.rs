@@ -0,0 +1,64 @@
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use anyhow::anyhow;
+use tokio_rustls::rustls::crypto::ring;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::TlsAcceptor;
+
+use crate::config::{Config, Tls};
+
+/// TLS握手超时，超时未完成握手的连接直接关闭
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 读取PEM格式的证书链和私钥，生成服务端TLS配置
+pub fn load(conf: &Tls) -> anyhow::Result<Arc<ServerConfig>> {
+    let certs = CertificateDer::pem_file_iter(&conf.cert)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| anyhow!("read tls cert failed, path={}, err={}", conf.cert, e))?;
+    if certs.is_empty() {
+        return Err(anyhow!("no certificate found in tls cert, path={}", conf.cert));
+    }
+    let key = PrivateKeyDer::from_pem_file(&conf.key)
+        .map_err(|e| anyhow!("read tls key failed, path={}, err={}", conf.key, e))?;
+    let mut config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| anyhow!("invalid tls cert or key, cert={}, key={}, err={}", conf.cert, conf.key, e))?;
+    // 只提供HTTP/1.1
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(Arc::new(config))
+}
+
+/// TLS终止，所有TCP监听地址共用，重新加载配置时替换证书，已建立的连接不受影响
+#[derive(Debug)]
+pub struct TlsTerminator {
+    config: RwLock<Arc<ServerConfig>>,
+}
+
+impl TlsTerminator {
+    pub fn new(config: Arc<ServerConfig>) -> TlsTerminator {
+        TlsTerminator { config: RwLock::new(config) }
+    }
+
+    /// 按配置创建，未配置[server.tls]时为None
+    pub fn from_config(config: &Config) -> anyhow::Result<Option<Arc<TlsTerminator>>> {
+        match &config.server.tls {
+            Some(conf) => Ok(Some(Arc::new(TlsTerminator::new(load(conf)?)))),
+            None => Ok(None),
+        }
+    }
+
+    /// 替换证书，之后的新连接使用新证书
+    pub fn reload(&self, config: Arc<ServerConfig>) {
+        *self.config.write().unwrap() = config;
+    }
+
+    /// 当前证书的握手器
+    pub fn acceptor(&self) -> TlsAcceptor {
+        TlsAcceptor::from(Arc::clone(&self.config.read().unwrap()))
+    }
+}